@@ -36,7 +36,18 @@
 //! 5. **Effect Scope**: Effects are "borrowed" by blocks, not owned
 
 use crate::error_msg::{RsplError, ErrorCode, SourceLocation};
-use std::collections::{HashMap, HashSet, BTreeSet};
+use crate::effect_detector::{find_effect_construct, line_has_effect, EffectCategory};
+use crate::lowering::depth_tracking_lowering::{
+    count_braces_outside_strings, find_outside_strings, split_top_level, split_top_level_types,
+};
+use crate::eir::{self, EffectAlgebra, EffectItem};
+use crate::target_cfg::{eval_cfg_attribute, eval_condition, split_effect_condition, Target};
+use crate::input_limits::{check_input_limits, InputLimits};
+use crate::contracts::ContractMode;
+use crate::constants::ConstRegistry;
+use crate::function::{parse_function_line, FunctionParseResult};
+use crate::hir::{BindingId, BindingInfo};
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet};
 
 //=============================================================================
 // ANSI COLOR CODES
@@ -463,6 +474,10 @@ pub enum Effect {
     Alloc,
     /// May panic: `panic`
     Panic,
+    /// Spawns or joins threads: `concurrent`
+    Concurrent,
+    /// Opens a file or socket handle: `resource(file)`, `resource(socket)`
+    Resource(String),
     /// Call effectful function (internal tracking): `calls(fn_name)`
     Calls(String),
 }
@@ -475,6 +490,8 @@ impl Effect {
             Effect::Io => "io".to_string(),
             Effect::Alloc => "alloc".to_string(),
             Effect::Panic => "panic".to_string(),
+            Effect::Concurrent => "concurrent".to_string(),
+            Effect::Resource(kind) => format!("resource({})", kind),
             Effect::Calls(f) => format!("calls({})", f),
         }
     }
@@ -492,6 +509,9 @@ impl Effect {
         if s == "panic" {
             return Some(Effect::Panic);
         }
+        if s == "concurrent" {
+            return Some(Effect::Concurrent);
+        }
         
         if s.starts_with("read(") && s.ends_with(')') {
             let inner = &s[5..s.len()-1];
@@ -524,18 +544,60 @@ impl Effect {
             return Some(Effect::Calls(inner.trim().to_string()));
         }
         
+        if s.starts_with("resource(") && s.ends_with(')') {
+            let inner = s[9..s.len()-1].trim();
+            if !inner.is_empty() {
+                return Some(Effect::Resource(inner.to_string()));
+            }
+        }
+        
         None
     }
     
     /// Check if this is a propagatable effect (should bubble up to callers)
     pub fn is_propagatable(&self) -> bool {
-        matches!(self, Effect::Io | Effect::Alloc | Effect::Panic)
+        matches!(self, Effect::Io | Effect::Alloc | Effect::Panic | Effect::Concurrent)
     }
     
     /// Check if this is a parameter-bound effect
     pub fn is_parameter_bound(&self) -> bool {
         matches!(self, Effect::Read(_) | Effect::Write(_))
     }
+    
+    /// Is this a write to a single field (`write(acc.balance)`)?
+    pub fn is_field_write(&self) -> bool {
+        matches!(self, Effect::Write(p) if p.contains('.'))
+    }
+    
+    /// Parameter a read/write targets: `acc` for `write(acc.balance)`
+    pub fn target_param(&self) -> Option<&str> {
+        match self {
+            Effect::Read(p) | Effect::Write(p) => p.split('.').next(),
+            _ => None,
+        }
+    }
+    
+    /// Field path below the parameter: `["balance"]` for `write(acc.balance)`,
+    /// empty for a write to the whole parameter
+    pub fn field_path(&self) -> Vec<&str> {
+        match self {
+            Effect::Read(p) | Effect::Write(p) => p.split('.').skip(1).collect(),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// Does declaring this effect allow `detected`? A write to a parameter
+    /// covers writes to its fields: `write(acc)` covers `write(acc.balance)`,
+    /// but `write(acc.balance)` covers neither `write(acc)` nor
+    /// `write(acc.owner)`.
+    pub fn covers(&self, detected: &Effect) -> bool {
+        match (self, detected) {
+            (Effect::Write(declared), Effect::Write(written)) => written
+                .strip_prefix(declared.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.')),
+            _ => self == detected,
+        }
+    }
 }
 
 //=============================================================================
@@ -575,6 +637,11 @@ impl EffectSignature {
         self.effects.contains(effect)
     }
     
+    /// Is `effect` allowed by a declared effect (see [`Effect::covers`])?
+    pub fn covers(&self, effect: &Effect) -> bool {
+        self.effects.iter().any(|declared| declared.covers(effect))
+    }
+    
     pub fn has_write(&self, param: &str) -> bool {
         self.effects.contains(&Effect::Write(param.to_string()))
     }
@@ -595,24 +662,83 @@ impl EffectSignature {
         self.effects.contains(&Effect::Panic)
     }
     
-    /// Get all propagatable effects
-    pub fn propagatable_effects(&self) -> Vec<Effect> {
-        self.effects.iter()
-            .filter(|e| e.is_propagatable())
-            .cloned()
-            .collect()
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
     }
     
     /// Format effects for display
     pub fn display(&self) -> String {
-        if self.effects.is_empty() {
-            return "pure".to_string();
-        }
-        self.effects.iter()
-            .map(|e| e.display())
-            .collect::<Vec<_>>()
-            .join(", ")
+        self.display_with(|e| e.display())
+    }
+    
+    /// Name-based view of an IR effect set; reads and writes take the
+    /// binding's name, calls keep only the callee
+    pub fn from_effect_set(set: &eir::EffectSet, bindings: &HashMap<BindingId, BindingInfo>) -> Self {
+        let name = |id: &BindingId| bindings.get(id)
+            .map(|b| b.name.clone())
+            .unwrap_or_else(|| format!("?{}", id.0));
+        Self::from_effects(set.iter().map(|e| match e {
+            eir::Effect::Read(id) => Effect::Read(name(id)),
+            eir::Effect::Write(id) => Effect::Write(name(id)),
+            eir::Effect::Io => Effect::Io,
+            eir::Effect::Alloc => Effect::Alloc,
+            eir::Effect::Panic => Effect::Panic,
+            eir::Effect::Calls { func_name, .. } => Effect::Calls(func_name.clone()),
+        }).collect())
+    }
+    
+    /// IR view of this signature. A field write becomes a write to its
+    /// parameter; reads and writes of names not in `params` are dropped.
+    pub fn to_effect_set(&self, params: &HashMap<String, BindingId>) -> eir::EffectSet {
+        self.effects.iter().filter_map(|e| match e {
+            Effect::Read(_) | Effect::Write(_) => {
+                let id = *params.get(e.target_param()?)?;
+                Some(if matches!(e, Effect::Read(_)) { eir::Effect::Read(id) } else { eir::Effect::Write(id) })
+            }
+            Effect::Io => Some(eir::Effect::Io),
+            Effect::Alloc => Some(eir::Effect::Alloc),
+            Effect::Panic => Some(eir::Effect::Panic),
+            Effect::Concurrent | Effect::Resource(_) => None,
+            Effect::Calls(f) => Some(eir::Effect::Calls { func_name: f.clone(), effects: BTreeSet::new() }),
+        }).collect()
+    }
+}
+
+impl EffectItem for Effect {
+    fn is_propagatable(&self) -> bool {
+        Effect::is_propagatable(self)
+    }
+}
+
+impl EffectAlgebra for EffectSignature {
+    type Item = Effect;
+
+    fn effects(&self) -> &BTreeSet<Effect> {
+        &self.effects
     }
+
+    fn from_effects(effects: BTreeSet<Effect>) -> Self {
+        EffectSignature::with_effects(effects)
+    }
+}
+
+/// Effects a `test` block may perform without declaring them: asserts
+/// panic, and printing and building expected values are io and alloc.
+/// `--test-effects` replaces the grant.
+pub const DEFAULT_TEST_EFFECTS: [Effect; 3] = [Effect::Io, Effect::Panic, Effect::Alloc];
+
+/// Parse a `--test-effects` grant: `io,panic`, or `none` for no grant
+pub fn parse_test_effects(list: &str) -> Result<EffectSignature, String> {
+    if list.trim() == "none" {
+        return Ok(EffectSignature::new());
+    }
+    list.split(',')
+        .map(|item| match Effect::parse(item) {
+            Some(effect @ (Effect::Io | Effect::Alloc | Effect::Panic | Effect::Concurrent)) => Ok(effect),
+            _ => Err(format!("`{}` cannot be granted to tests; expected io, alloc, panic, concurrent or none", item.trim())),
+        })
+        .collect::<Result<BTreeSet<Effect>, String>>()
+        .map(EffectSignature::with_effects)
 }
 
 //=============================================================================
@@ -632,6 +758,27 @@ pub struct FunctionInfo {
     pub calls: Vec<String>,  // Functions this function calls
     pub is_public: bool,
     pub body_lines: Vec<(usize, String)>,  // (line_num, content)
+    /// Anonymous closures in the body (filled by `analyze_functions`)
+    pub closures: Vec<ClosureInfo>,
+    /// Where each detected effect was first performed
+    pub effect_origins: BTreeMap<Effect, EffectOrigin>,
+}
+
+/// The statement an effect was detected on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectOrigin {
+    pub line: usize,
+    /// What performs the effect, as quoted in diagnostics: `println!`,
+    /// `.unwrap()`, `acc.balance = ..`
+    pub construct: String,
+}
+
+impl EffectOrigin {
+    /// The part of the source line to highlight: `println` of a bare
+    /// `println(..)`, `acc.balance` of `acc.balance = ..`
+    pub fn highlight(&self) -> &str {
+        self.construct.trim_end_matches(" = ..").trim_end_matches('!')
+    }
 }
 
 impl FunctionInfo {
@@ -647,20 +794,42 @@ impl FunctionInfo {
             calls: Vec::new(),
             is_public: false,
             body_lines: Vec::new(),
+            closures: Vec::new(),
+            effect_origins: BTreeMap::new(),
         }
     }
     
-    /// Check if function is main (special case for I/O allowance)
+    /// Check if function is main (special case for I/O allowance),
+    /// including the user's `main` renamed for the test harness
     pub fn is_main(&self) -> bool {
-        self.name == "main"
+        self.name == "main" || self.name == crate::entry::USER_MAIN_RENAMED
+    }
+    
+    /// Check if function was generated from a `test` block. Tests are
+    /// roots of their own: they may perform the test grant (see
+    /// [`DEFAULT_TEST_EFFECTS`]) without declaring it, and nothing calls them.
+    pub fn is_test(&self) -> bool {
+        crate::entry::is_test_fn_name(&self.name)
     }
     
     /// Get all effects that are detected but not declared
-    pub fn undeclared_effects(&self) -> Vec<Effect> {
-        self.detected_effects.effects.iter()
-            .filter(|e| !self.declared_effects.has_effect(e))
-            .cloned()
-            .collect()
+    pub fn undeclared_effects(&self) -> EffectSignature {
+        EffectSignature::from_effects(self.detected_effects.effects.iter()
+            .filter(|e| !self.declared_effects.covers(e))
+            .map(|e| self.reported_effect(e))
+            .collect())
+    }
+    
+    /// How an undeclared effect is reported: a field write is only reported
+    /// by field when the function declares field writes on that parameter,
+    /// otherwise as a write to the whole parameter (`write(acc)`).
+    pub fn reported_effect(&self, effect: &Effect) -> Effect {
+        let declares_fields = self.declared_effects.effects.iter()
+            .any(|d| d.is_field_write() && d.target_param() == effect.target_param());
+        match effect.target_param() {
+            Some(param) if effect.is_field_write() && !declares_fields => Effect::Write(param.to_string()),
+            _ => effect.clone(),
+        }
     }
     
     /// Check if a parameter exists
@@ -669,6 +838,191 @@ impl FunctionInfo {
     }
 }
 
+//=============================================================================
+// CLOSURE EFFECTS - Per-closure view for --analyze
+//=============================================================================
+
+/// An anonymous closure inside a function
+#[derive(Debug, Clone)]
+pub struct ClosureInfo {
+    /// Parameter list as written, without the pipes
+    pub params: String,
+    pub line_number: usize,
+    /// `|x| effects(io) { ... }` - `None` when the closure is not annotated
+    pub declared_effects: Option<EffectSignature>,
+    /// Effects of the closure body, including declared effects of the
+    /// functions it calls
+    pub detected_effects: EffectSignature,
+}
+
+/// Matching close for the opener at `start` in `chars`, skipping strings
+fn closure_delimited_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (i, &c) in chars.iter().enumerate().skip(start) {
+        if c == '"' && (i == 0 || chars[i - 1] != '\\') {
+            in_string = !in_string;
+        }
+        if in_string {
+            continue;
+        }
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// End of an expression-bodied closure: the first `,` `;` or unmatched closer
+/// at depth 0, or the end of the line
+fn closure_expr_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0;
+    let mut in_string = false;
+    for (i, &c) in chars.iter().enumerate().skip(start) {
+        if c == '"' && chars[i - 1] != '\\' {
+            in_string = !in_string;
+        }
+        if in_string {
+            continue;
+        }
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => return i,
+            ')' | ']' | '}' => depth -= 1,
+            ',' | ';' | '\n' if depth == 0 => return i,
+            _ => {}
+        }
+    }
+    chars.len()
+}
+
+/// Find closures in one function body. `body` holds the function's lines,
+/// the first of which is source line `first_line`.
+fn find_closures(body: &str, first_line: usize, info: &FunctionInfo, functions: &HashMap<String, FunctionInfo>) -> Vec<ClosureInfo> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut closures = Vec::new();
+    let mut in_string = false;
+    let mut i = 0;
+    
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' && (i == 0 || chars[i - 1] != '\\') {
+            in_string = !in_string;
+        }
+        // Skip line comments
+        if !in_string && c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if in_string || c != '|' {
+            i += 1;
+            continue;
+        }
+        
+        // `|` opens a closure after `(`, `,`, `=`, `move` or at line start;
+        // anywhere else it is `|` / `||` as an operator
+        let before: String = chars[..i].iter().collect();
+        let before = before.trim_end();
+        let opens_closure = before.is_empty()
+            || before.ends_with(['(', ',', '=', '\n', '{'])
+            || before.ends_with(" move") || before.ends_with("(move") || before == "move";
+        let Some(close) = chars.iter().skip(i + 1).position(|&c| c == '|').map(|p| p + i + 1) else {
+            break;
+        };
+        if !opens_closure {
+            i += 1;
+            continue;
+        }
+        let params: String = chars[i + 1..close].iter().collect();
+        let line_number = first_line + chars[..i].iter().filter(|&&c| c == '\n').count();
+        
+        // Optional `effects(...)` and `-> Type` before the body
+        let mut pos = close + 1;
+        while chars.get(pos).is_some_and(|c| *c == ' ') {
+            pos += 1;
+        }
+        let mut declared_effects = None;
+        let rest: String = chars[pos..chars.len().min(pos + 8)].iter().collect();
+        if rest == "effects(" {
+            let end = closure_delimited_end(&chars, pos + 7).unwrap_or(chars.len() - 1);
+            let list: String = chars[pos + 8..end].iter().collect();
+            declared_effects = Some(EffectSignature::with_effects(
+                list.split(',').filter_map(Effect::parse).collect(),
+            ));
+            pos = end + 1;
+        }
+        while chars.get(pos).is_some_and(|c| *c == ' ') {
+            pos += 1;
+        }
+        if chars.get(pos) == Some(&'-') && chars.get(pos + 1) == Some(&'>') {
+            while chars.get(pos).is_some_and(|c| *c != '{') {
+                pos += 1;
+            }
+        }
+        let body_end = if chars.get(pos) == Some(&'{') {
+            closure_delimited_end(&chars, pos).map(|e| e + 1).unwrap_or(chars.len())
+        } else {
+            closure_expr_end(&chars, pos)
+        };
+        let closure_body: String = chars[pos.min(body_end)..body_end].iter().collect();
+        
+        let mut analyzer = EffectAnalyzer::new();
+        analyzer.enter_function("<closure>", &info.parameters, &EffectSignature::new());
+        for (offset, line) in closure_body.lines().enumerate() {
+            analyzer.analyze_line(line.trim(), line_number + offset);
+        }
+        let (effects, calls) = analyzer.exit_function();
+        let mut detected_effects = EffectSignature::with_effects(effects);
+        for (name, _) in calls {
+            if let Some(callee) = functions.get(&name).filter(|f| f.name != info.name) {
+                detected_effects = detected_effects.union(&callee.declared_effects.propagatable());
+            }
+        }
+        
+        closures.push(ClosureInfo { params, line_number, declared_effects, detected_effects });
+        // Continue inside the body so nested closures are found too
+        i = close + 1;
+    }
+    closures
+}
+
+/// Attach the closures of every function in `source` to its `FunctionInfo`
+fn collect_closures(source: &str, functions: &mut HashMap<String, FunctionInfo>) {
+    let masked = crate::closures::lower_fn_lambdas(&crate::verbatim::mask_verbatim_blocks(source).0);
+    let lines: Vec<&str> = masked.lines().collect();
+    let snapshot = functions.clone();
+    
+    for info in functions.values_mut() {
+        let start = info.line_number.saturating_sub(1);
+        let mut depth: i64 = 0;
+        let mut opened = false;
+        let mut end = start;
+        for (idx, line) in lines.iter().enumerate().skip(start) {
+            let (opens, closes) = crate::lowering::depth_tracking_lowering::count_braces_outside_strings(line);
+            depth += opens as i64 - closes as i64;
+            opened |= opens > 0;
+            end = idx;
+            if opened && depth <= 0 {
+                break;
+            }
+        }
+        // The signature line itself may hold `|`-typed parameters - skip it
+        if end > start {
+            let body = lines[start + 1..=end].join("\n");
+            info.closures = find_closures(&body, start + 2, info, &snapshot);
+        }
+    }
+}
+
 //=============================================================================
 // LOGIC VIOLATION CATEGORIES
 //=============================================================================
@@ -687,6 +1041,8 @@ pub enum LogicViolation {
     UnclearIntent,
     /// Logic-06: Same-scope reassignment without `mut`
     SameScopeReassignment,
+    /// Logic-07: Rust keyword used as a binding name
+    KeywordIdentifier,
     /// Effect-01: Undeclared effect performed
     UndeclaredEffect,
     /// Effect-02: Effect leak (effect in nested scope without propagation)
@@ -710,6 +1066,7 @@ impl LogicViolation {
             Self::ImplicitMutation => "Logic-04",
             Self::UnclearIntent => "Logic-05",
             Self::SameScopeReassignment => "Logic-06",
+            Self::KeywordIdentifier => "Logic-07",
             Self::UndeclaredEffect => "Effect-01",
             Self::EffectLeak => "Effect-02",
             Self::PureCallingEffectful => "Effect-03",
@@ -727,6 +1084,7 @@ impl LogicViolation {
             Self::ImplicitMutation => "implicit mutation without declaration",
             Self::UnclearIntent => "unclear code intent",
             Self::SameScopeReassignment => "same-scope reassignment without mut",
+            Self::KeywordIdentifier => "keyword used as identifier",
             Self::UndeclaredEffect => "undeclared effect performed",
             Self::EffectLeak => "effect leaked to nested scope",
             Self::PureCallingEffectful => "pure function calling effectful function",
@@ -957,6 +1315,8 @@ pub struct EffectAnalyzer {
     declared_effects: EffectSignature,
     /// Function calls detected
     function_calls: Vec<(String, usize)>,  // (name, line)
+    /// First statement performing each detected effect
+    origins: BTreeMap<Effect, EffectOrigin>,
     /// Effect ownership tracker
     ownership_tracker: EffectOwnershipTracker,
     // NEW: IR-based effect context
@@ -964,6 +1324,217 @@ pub struct EffectAnalyzer {
     ir_detected_effects: Option<crate::eir::EffectSet>,
 }
 
+/// Find functions passed BY NAME to std higher-order methods (the
+/// `[higher_order]` table of [`crate::effect_db`]).
+///
+/// `xs.iter().map(parse_item).for_each(log_item)` → ["parse_item", "log_item"]
+///
+/// Closure arguments need no special handling here: their bodies are on the
+/// same line (or following lines) and are analyzed like any other code.
+fn detect_hof_function_args(line: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let parens = crate::lowering::depth_tracking_lowering::delimiter_positions(line, &['(', ')']);
+    
+    for (idx, &(open_pos, c)) in parens.iter().enumerate() {
+        if c != '(' {
+            continue;
+        }
+        let before = &line[..open_pos];
+        let method = before
+            .rsplit(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+            .next()
+            .unwrap_or("");
+        let is_method_call = before[..before.len() - method.len()].ends_with('.');
+        if !is_method_call || !crate::effect_db::with_effect_db(|db| db.calls_argument(method)) {
+            continue;
+        }
+        
+        // Matching `)` for this call
+        let mut depth = 0;
+        let mut close_pos = None;
+        for &(pos, ch) in &parens[idx..] {
+            if ch == '(' {
+                depth += 1;
+            } else {
+                depth -= 1;
+                if depth == 0 {
+                    close_pos = Some(pos);
+                    break;
+                }
+            }
+        }
+        let Some(close_pos) = close_pos else { continue };
+        
+        for arg in split_top_level(&line[open_pos + 1..close_pos], ',') {
+            let arg = arg.trim();
+            let name = arg.rsplit("::").next().unwrap_or(arg);
+            let is_fn_name = !name.is_empty()
+                && name.chars().next().is_some_and(|c| c.is_lowercase() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if is_fn_name && !found.iter().any(|f| f == name) {
+                found.push(name.to_string());
+            }
+        }
+    }
+    found
+}
+
+/// Method receiver parameter: `&self` → `("self", "&Self")`
+fn receiver_param(param: &str) -> Option<(String, String)> {
+    let ty = match param.split_whitespace().collect::<Vec<_>>().join(" ").as_str() {
+        "self" | "mut self" => "Self",
+        "&self" | "& self" => "&Self",
+        "&mut self" | "& mut self" => "&mut Self",
+        _ => return None,
+    };
+    Some(("self".to_string(), ty.to_string()))
+}
+
+/// Self type of an `impl` header: `impl[T] Stack[T] {` → `Stack`,
+/// `impl Display for Point {` → `Point`
+fn impl_self_type(trimmed: &str) -> Option<String> {
+    let rest = trimmed.strip_prefix("impl")?;
+    // Skip impl-level generics `impl[T]` / `impl<T>`
+    let rest = match rest.chars().next()? {
+        open @ ('[' | '<') => {
+            let close = if open == '[' { ']' } else { '>' };
+            &rest[rest.find(close)? + 1..]
+        }
+        c if c.is_whitespace() => rest,
+        _ => return None,
+    };
+    let rest = rest.split('{').next()?.trim();
+    let target = match rest.find(" for ") {
+        Some(pos) => &rest[pos + 5..],
+        None => rest,
+    };
+    let name: String = target.trim().chars().take_while(|&c| crate::ident::is_ident_continue(c)).collect();
+    if name.is_empty() { None } else { Some(name) }
+}
+
+/// Method calls on a named receiver: `acc.inner.push(x)` → `("acc", "push")`.
+/// Returns (receiver root, method name) pairs.
+fn method_call_receivers(line: &str) -> Vec<(String, String)> {
+    let mut calls = Vec::new();
+    for (pos, _) in crate::lowering::depth_tracking_lowering::delimiter_positions(line, &['(']) {
+        let before = &line[..pos];
+        let method_start = before
+            .char_indices()
+            .rfind(|&(_, c)| !crate::ident::is_ident_continue(c))
+            .map_or(0, |(p, c)| p + c.len_utf8());
+        let method = &before[method_start..];
+        if method.is_empty() || !before[..method_start].ends_with('.') {
+            continue;
+        }
+        // Walk back over `root.field.field.`
+        let chain = &before[..method_start - 1];
+        let chain_start = chain
+            .char_indices()
+            .rfind(|&(_, c)| !(crate::ident::is_ident_continue(c) || c == '.'))
+            .map_or(0, |(p, c)| p + c.len_utf8());
+        let root = chain[chain_start..].split('.').next().unwrap_or("");
+        if crate::ident::is_valid_identifier(root) && !root.starts_with(|c: char| c.is_ascii_digit()) {
+            calls.push((root.to_string(), method.to_string()));
+        }
+    }
+    calls
+}
+
+/// Net `(` minus `)` on a line, ignoring string and char literals
+fn paren_balance(line: &str) -> i32 {
+    crate::lowering::depth_tracking_lowering::delimiter_positions(line, &['(', ')'])
+        .iter()
+        .map(|&(_, c)| if c == '(' { 1 } else { -1 })
+        .sum()
+}
+
+/// `(effect, condition)` for each conditional effect of a signature:
+/// `effects(io when not wasm, alloc)` → `[("io", "not wasm")]`
+fn effect_conditions(signature: &str) -> Vec<(&str, &str)> {
+    let Some(start) = signature.find("effects(") else { return Vec::new() };
+    let clause = &signature[start + "effects(".len()..];
+    let mut depth = 1;
+    let end = clause.char_indices()
+        .find(|&(_, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })
+        .map_or(clause.len(), |(i, _)| i);
+    clause[..end].split(',')
+        .filter_map(|effect| match split_effect_condition(effect) {
+            (effect, Some(condition)) => Some((effect, condition)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Find Rust keywords used as binding names on a line.
+///
+/// - `type = 1`, `mut match = x`, `mut fn Vec[u8] = ...`
+/// - `for type in xs {`
+/// - parameters of a function signature: `fn f(type String)`
+fn keyword_binding_names(trimmed: &str, is_fn_signature: bool) -> Vec<String> {
+    binding_names_matching(trimmed, is_fn_signature, crate::ident::is_rust_keyword)
+}
+
+/// Find binding names on a line for which `is_match` holds, in the forms
+/// listed for [`keyword_binding_names`]. An annotated binding without `mut`
+/// counts only for non-keywords (`type Alias = u8` is a type alias).
+fn binding_names_matching(trimmed: &str, is_fn_signature: bool, is_match: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut found = Vec::new();
+    
+    if is_fn_signature {
+        if let (Some(open), Some(close)) = (trimmed.find('('), trimmed.rfind(')')) {
+            if open < close {
+                for param in split_top_level_types(&trimmed[open + 1..close], ',') {
+                    let param = param.trim();
+                    let param = param.strip_prefix("mut ").unwrap_or(param);
+                    if let Some(name) = param.split(|c: char| c.is_whitespace() || c == ':').next() {
+                        if is_match(name) && name != "self" && !param.contains("self") {
+                            found.push(name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        return found;
+    }
+    
+    if let Some(rest) = trimmed.strip_prefix("for ") {
+        if let Some(name) = rest.split_whitespace().next() {
+            if is_match(name) && rest[name.len()..].trim_start().starts_with("in ") {
+                found.push(name.to_string());
+            }
+        }
+        return found;
+    }
+    
+    // Assignment forms: `kw = v`, `mut kw = v`, `mut kw Type = v`
+    let (is_mut, rest) = match trimmed.strip_prefix("mut ") {
+        Some(r) => (true, r.trim_start()),
+        None => (false, trimmed),
+    };
+    let Some(name) = rest.split(|c: char| c.is_whitespace() || c == '=' || c == ':').next() else {
+        return found;
+    };
+    if !is_match(name) {
+        return found;
+    }
+    let after = rest[name.len()..].trim_start();
+    let is_plain_assign = after.starts_with('=') && !after.starts_with("==") && !after.starts_with("=>");
+    // With a type annotation only `mut` makes it unambiguous (`type Alias = u8` is an alias)
+    let is_annotated = (is_mut || !crate::ident::is_rust_keyword(name))
+        && !after.is_empty() && !after.starts_with('=') && !after.starts_with("::") && after.contains(" = ");
+    if is_plain_assign || is_annotated {
+        found.push(name.to_string());
+    }
+    found
+}
+
 impl EffectAnalyzer {
     pub fn new() -> Self {
         EffectAnalyzer {
@@ -972,6 +1543,7 @@ impl EffectAnalyzer {
             detected_effects: BTreeSet::new(),
             declared_effects: EffectSignature::new(),
             function_calls: Vec::new(),
+            origins: BTreeMap::new(),
             ownership_tracker: EffectOwnershipTracker::new(),
             ir_context: None,
             ir_detected_effects: None,
@@ -984,9 +1556,15 @@ impl EffectAnalyzer {
         self.detected_effects.clear();
         self.declared_effects = declared.clone();
         self.function_calls.clear();
+        self.origins.clear();
         self.ownership_tracker.enter_function(name, declared);
     }
     
+    /// Where each effect detected since `enter_function` was first performed
+    pub fn take_origins(&mut self) -> BTreeMap<Effect, EffectOrigin> {
+        std::mem::take(&mut self.origins)
+    }
+    
     pub fn exit_function(&mut self) -> (BTreeSet<Effect>, Vec<(String, usize)>) {
         self.ownership_tracker.exit_function();
         self.current_function = None;
@@ -1008,43 +1586,69 @@ impl EffectAnalyzer {
         self.ownership_tracker.exit_block();
     }
     
+    /// Record an effect that was declared rather than detected (verbatim blocks)
+    pub fn record_effect(&mut self, effect: Effect, line_num: usize) {
+        self.detect(effect, line_num, "rust block".to_string());
+    }
+    
+    fn detect(&mut self, effect: Effect, line_num: usize, construct: String) {
+        self.origins.entry(effect.clone()).or_insert(EffectOrigin { line: line_num, construct });
+        self.detected_effects.insert(effect.clone());
+        self.ownership_tracker.record_effect(effect, line_num);
+    }
+    
     pub fn analyze_line(&mut self, line: &str, line_num: usize) {
+        let construct = |category: EffectCategory| {
+            find_effect_construct(line, category).unwrap_or_else(|| category.name().to_string())
+        };
+        
         // Detect I/O effects
         if self.detect_io_effect(line) {
-            self.detected_effects.insert(Effect::Io);
-            self.ownership_tracker.record_effect(Effect::Io, line_num);
+            self.detect(Effect::Io, line_num, construct(EffectCategory::Io));
         }
         
         // Detect allocation effects
         if self.detect_alloc_effect(line) {
-            self.detected_effects.insert(Effect::Alloc);
-            self.ownership_tracker.record_effect(Effect::Alloc, line_num);
+            self.detect(Effect::Alloc, line_num, construct(EffectCategory::Alloc));
         }
         
         // Detect panic effects
         if self.detect_panic_effect(line) {
-            self.detected_effects.insert(Effect::Panic);
-            self.ownership_tracker.record_effect(Effect::Panic, line_num);
+            self.detect(Effect::Panic, line_num, construct(EffectCategory::Panic));
+        }
+        
+        // Detect thread spawns and joins
+        if line_has_effect(line, EffectCategory::Concurrent) {
+            self.detect(Effect::Concurrent, line_num, construct(EffectCategory::Concurrent));
+        }
+        
+        // Detect opened file and socket handles
+        if let Some((kind, call)) = crate::resources::opened_resource(line) {
+            self.detect(Effect::Resource(kind.to_string()), line_num, call.to_string());
         }
         
         // Detect parameter mutations (write effects)
         if let Some(param) = self.detect_param_mutation(line) {
-            let effect = Effect::Write(param.clone());
-            self.detected_effects.insert(effect.clone());
-            self.ownership_tracker.record_effect(effect, line_num);
+            let construct = format!("{} = ..", param);
+            self.detect(Effect::Write(param), line_num, construct);
         }
         
         // Detect parameter reads
         if let Some(param) = self.detect_param_read(line) {
-            let effect = Effect::Read(param);
-            self.detected_effects.insert(effect.clone());
-            self.ownership_tracker.record_effect(effect, line_num);
+            let construct = param.clone();
+            self.detect(Effect::Read(param), line_num, construct);
         }
         
         // Detect function calls
         for call in self.detect_function_calls(line) {
             self.function_calls.push((call, line_num));
         }
+        
+        // Functions passed by name to std higher-order methods are called too:
+        // `xs.iter().for_each(log_item)` performs log_item's effects
+        for call in detect_hof_function_args(line) {
+            self.function_calls.push((call, line_num));
+        }
     }
     
     fn detect_io_effect(&self, line: &str) -> bool {
@@ -1053,112 +1657,19 @@ impl EffectAnalyzer {
             return effects.has_io();
         }
         
-        // Fallback to pattern matching
-        // IMPROVED: Added comprehensive I/O patterns for various categories
-        let io_patterns = [
-            // === CONSOLE I/O ===
-            "println!", "print!", "eprintln!", "eprint!",
-            "stdin()", "stdout()", "stderr()",
-            
-            // === FILE I/O ===
-            "std::io", "File::", "OpenOptions::",
-            // ═══════════════════════════════════════════════════════════════════════
-            // BUGFIX: Removed generic ".read(" and ".write(" patterns
-            // ═══════════════════════════════════════════════════════════════════════
-            // These patterns were causing FALSE POSITIVES with synchronization primitives!
-            // 
-            // RwLock.read(), RwLock.write(), Mutex.lock(), RefCell.borrow() are NOT I/O!
-            // They are memory synchronization primitives that operate in-process.
-            //
-            // TRUE I/O operations:
-            //   - File::open().read() - reads from filesystem
-            //   - TcpStream::connect().write() - writes to network
-            //   - stdin().read() - reads from console
-            //
-            // NOT I/O (synchronization):
-            //   - RwLock::new().read() - acquires read lock in memory
-            //   - Mutex::new().lock() - acquires mutex in memory
-            //   - RefCell::new().borrow() - borrows reference in memory
-            //
-            // We now use more specific patterns to avoid false positives.
-            // ═══════════════════════════════════════════════════════════════════════
-            ".read_exact(", ".read_to_string(", ".read_to_end(",
-            ".write_all(", ".flush(",
-            "Read::read", "Write::write",
-            "BufRead::", "io::Read", "io::Write",
-            "fs::read", "fs::write", "fs::create", "fs::open",
-            "fs::remove", "fs::rename", "fs::copy",
-            "fs::create_dir", "fs::remove_dir", "fs::read_dir",
-            "BufReader::", "BufWriter::",
-            
-            // === NETWORKING I/O ===
-            "TcpStream::", "TcpListener::", "UdpSocket::",
-            "std::net::", "ToSocketAddrs",
-            ".connect(", ".bind(", ".listen(", ".accept(",
-            ".send(", ".recv(", ".send_to(", ".recv_from(",
-            
-            // === ENVIRONMENT I/O ===
-            "std::env::var", "std::env::args", "std::env::current_dir",
-            "std::env::set_var", "std::env::remove_var",
-            "env::var", "env::args", "env::current_dir",
-            
-            // === PROCESS I/O ===
-            "std::process::", "Command::", "Child::",
-            ".spawn(", ".output(", ".status(",
-            
-            // === PATH OPERATIONS (may do filesystem checks) ===
-            ".canonicalize(", ".metadata(", ".symlink_metadata(",
-            ".exists()", ".is_file()", ".is_dir()",
-        ];
-        
-        io_patterns.iter().any(|p| line.contains(p))
+        // Fallback to the shared detector registry
+        line_has_effect(line, EffectCategory::Io)
     }
     
     fn detect_alloc_effect(&self, line: &str) -> bool {
-        // CRITICAL FIX: Removed `.clone()` and `.collect()` from alloc patterns
-        //
-        // Reason for removing `.clone()`:
-        //   `.clone()` on Copy types (i32, u64, bool, char, etc.) does NOT
-        //   allocate memory - it just copies bits on the stack. Only `.clone()`
-        //   on heap-allocated types (String, Vec, Box, etc.) performs allocation.
-        //   Since we can't determine the type at this stage (no type inference),
-        //   including `.clone()` causes many false positives.
-        //
-        // Reason for removing `.collect()`:
-        //   `.collect()` can produce various outputs, some that don't allocate
-        //   (e.g., collecting into `()`, summing with `Sum`, etc.).
-        //
-        // For strict effect tracking, users can explicitly declare `effects(alloc)`
-        // when they know they're cloning heap types or collecting into containers.
-        let alloc_patterns = [
-            // Explicit constructors - definite heap allocation
-            "Vec::new", "Vec::with_capacity",
-            "String::new", "String::from", "String::with_capacity",
-            "Box::new", "Rc::new", "Arc::new",
-            "HashMap::new", "HashMap::with_capacity",
-            "HashSet::new", "HashSet::with_capacity",
-            "BTreeMap::new", "BTreeSet::new",
-            "VecDeque::new", "LinkedList::new", "BinaryHeap::new",
-            // Macros that allocate
-            "vec!", "format!",
-            // Methods that definitely allocate new heap memory
-            ".to_string()", ".to_owned()", ".to_vec()",
-            ".into_boxed_slice()", ".into_boxed_str()",
-        ];
-        
-        alloc_patterns.iter().any(|p| line.contains(p))
+        line_has_effect(line, EffectCategory::Alloc)
     }
     
     fn detect_panic_effect(&self, line: &str) -> bool {
-        let panic_patterns = [
-            "panic!", ".unwrap()", ".expect(",
-            "assert!", "assert_eq!", "assert_ne!",
-            "unreachable!", "unimplemented!", "todo!",
-        ];
-        
-        panic_patterns.iter().any(|p| line.contains(p))
+        line_has_effect(line, EffectCategory::Panic)
     }
     
+    /// The parameter (or parameter field path, `acc.balance`) a line writes
     fn detect_param_mutation(&self, line: &str) -> Option<String> {
         let trimmed = line.trim();
         
@@ -1193,10 +1704,21 @@ impl EffectAnalyzer {
                     let mut found_field = false;
                     let mut in_field_name = true;
                     let mut chars_iter = after_dot.chars().peekable();
+                    // The written path: `acc.balance` for `acc.balance = x`
+                    let mut path = param.clone();
                     
                     while let Some(c) = chars_iter.next() {
+                        // Compound assignment: `param.field += value`
+                        if found_field && "+-*/%^&|".contains(c) && chars_iter.peek() == Some(&'=') {
+                            return Some(path);
+                        }
                         if in_field_name {
-                            if c.is_alphanumeric() || c == '_' {
+                            // Nested field: `param.inner.field = value`
+                            if c.is_alphanumeric() || c == '_' || (c == '.' && found_field) {
+                                if !found_field {
+                                    path.push('.');
+                                }
+                                path.push(c);
                                 found_field = true;
                                 continue;
                             }
@@ -1207,7 +1729,7 @@ impl EffectAnalyzer {
                             if c == '=' && found_field {
                                 // Check it's not ==
                                 if chars_iter.peek() != Some(&'=') {
-                                    return Some(param.clone());
+                                    return Some(path);
                                 }
                             }
                             break;
@@ -1215,7 +1737,7 @@ impl EffectAnalyzer {
                             // After field name, look for =
                             if c == '=' {
                                 if chars_iter.peek() != Some(&'=') {
-                                    return Some(param.clone());
+                                    return Some(path);
                                 }
                             }
                             if !c.is_whitespace() && c != '=' {
@@ -1463,6 +1985,33 @@ impl EffectDependencyGraph {
             .insert(effect);
     }
     
+    /// Shortest call path from `func` to a function that performs `effect`
+    /// itself (`func` first), for explaining where a propagated effect
+    /// comes from
+    pub fn call_chain(&self, func: &str, effect: &Effect, function_table: &HashMap<String, FunctionInfo>) -> Option<Vec<String>> {
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([func]);
+        while let Some(current) = queue.pop_front() {
+            if function_table.get(current).is_some_and(|info| info.effect_origins.contains_key(effect)) {
+                let mut chain = vec![current.to_string()];
+                let mut node = current;
+                while let Some(prev) = previous.get(node) {
+                    chain.push(prev.to_string());
+                    node = prev;
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            for callee in self.call_graph.get(current).into_iter().flatten() {
+                if callee != func && !previous.contains_key(callee.as_str()) {
+                    previous.insert(callee, current);
+                    queue.push_back(callee);
+                }
+            }
+        }
+        None
+    }
+    
     /// Compute transitive effect requirements
     pub fn compute_transitive_effects(&self, func: &str, function_table: &HashMap<String, FunctionInfo>) -> BTreeSet<Effect> {
         let mut visited = HashSet::new();
@@ -1527,6 +2076,8 @@ pub struct AntiFailLogicChecker {
     // Variable tracking
     function_vars: HashMap<String, usize>,
     reassigned_vars: HashSet<String>,
+    /// Bindings of the enclosing `for` loops, with the depth of each body
+    loop_bindings: Vec<(String, usize)>,
     in_function: bool,
     function_depth: usize,
     strict_mode: bool,
@@ -1554,6 +2105,44 @@ pub struct AntiFailLogicChecker {
     
     // Strict effect mode (require all effects to be declared)
     strict_effect_mode: bool,
+    
+    // Effects `test` blocks may perform without declaring them
+    test_effects: EffectSignature,
+    
+    // Nesting, line length and file size checked before anything else
+    limits: InputLimits,
+    
+    // Whether `requires` / `ensures` conditions are kept (and may panic)
+    contracts: ContractMode,
+    
+    // `const` / `static` names, which are never struct literal types
+    constants: ConstRegistry,
+    
+    // Target `when` conditions and `#[cfg(..)]` statements are evaluated for
+    target: Target,
+    // A false `#[cfg(..)]` was seen: the next statement is compiled out
+    cfg_pending: bool,
+    // Brace depth a compiled-out block started at
+    cfg_out_depth: Option<usize>,
+    
+    // Methods by name (resolves `recv.method()` calls) and the impl type of
+    // each method, keyed by its signature line
+    methods: HashMap<String, Vec<MethodEntry>>,
+    method_owners: HashMap<usize, String>,
+    
+    // Every analyzed function by signature line; `function_table` keeps
+    // only the last of several same-named methods
+    functions_by_line: BTreeMap<usize, FunctionInfo>,
+    
+    // Signature lines of `memo` functions (see `crate::memo`)
+    memo_lines: BTreeSet<usize>,
+}
+
+/// A method declared inside an `impl` block
+#[derive(Debug, Clone)]
+struct MethodEntry {
+    owner: String,
+    declared_effects: EffectSignature,
 }
 
 impl AntiFailLogicChecker {
@@ -1569,6 +2158,7 @@ impl AntiFailLogicChecker {
             source_lines: Vec::new(),
             function_vars: HashMap::new(),
             reassigned_vars: HashSet::new(),
+            loop_bindings: Vec::new(),
             in_function: false,
             function_depth: 0,
             strict_mode: true,
@@ -1580,6 +2170,17 @@ impl AntiFailLogicChecker {
             effect_graph: EffectDependencyGraph::new(),
             effect_checking_enabled: true,
             strict_effect_mode: true,
+            test_effects: EffectSignature::with_effects(DEFAULT_TEST_EFFECTS.into_iter().collect()),
+            methods: HashMap::new(),
+            method_owners: HashMap::new(),
+            functions_by_line: BTreeMap::new(),
+            memo_lines: BTreeSet::new(),
+            limits: InputLimits::default(),
+            contracts: ContractMode::default(),
+            constants: ConstRegistry::new(),
+            target: Target::host(),
+            cfg_pending: false,
+            cfg_out_depth: None,
         }
     }
     
@@ -1593,13 +2194,76 @@ impl AntiFailLogicChecker {
         self.strict_effect_mode = strict;
     }
     
+    /// Effects `test` blocks may perform without declaring them
+    /// ([`DEFAULT_TEST_EFFECTS`] unless set)
+    pub fn set_test_effects(&mut self, grant: EffectSignature) {
+        self.test_effects = grant;
+    }
+    
+    /// Limits the source is checked against first (see
+    /// [`crate::input_limits`]); the defaults unless set
+    pub fn set_input_limits(&mut self, limits: InputLimits) {
+        self.limits = limits;
+    }
+    
+    /// How contract clauses are lowered (see [`crate::contracts`]): kept
+    /// conditions are analyzed like statements of the body, so they may
+    /// panic and call functions
+    pub fn set_contract_mode(&mut self, mode: ContractMode) {
+        self.contracts = mode;
+    }
+    
+    /// Target for `effects(.. when ..)` conditions and `#[cfg(..)]`
+    /// statements (see [`crate::target_cfg`]); the host by default
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
+    
     /// Main entry point - runs all checks
     pub fn check(&mut self, source: &str) -> Result<(), Vec<RsplError>> {
+        // Past a limit, only the limit is reported
+        if let Err(mut errors) = check_input_limits(source, &self.limits) {
+            for error in &mut errors {
+                error.location.file = self.file_name.clone();
+            }
+            return Err(errors);
+        }
         self.source_lines = source.lines().map(String::from).collect();
         
+        // Verbatim `rust { ... }` blocks are not analyzed - only their
+        // declared effects are seen (via the marker comment). Functions
+        // written in plain Rust are treated the same way.
+        let (masked, mut blocks) = crate::verbatim::mask_verbatim_blocks(source);
+        let (masked, _) = crate::dialect::mask_rust_functions(&masked, &mut blocks);
+        let masked = crate::method_sugar::lower_method_sugar(&masked);
+        let masked = crate::map_literals::lower_map_literals(&masked);
+        let masked = crate::closures::lower_fn_lambdas(&masked);
+        let masked = crate::concurrency::lower_concurrency(&masked);
+        let (masked, modifiers) = crate::function::strip_fn_modifiers(&masked);
+        self.memo_lines = crate::memo::memo_lines(&modifiers);
+        let source = masked.as_str();
+        self.constants = ConstRegistry::from_source(source);
+        
         // PASS 1: Collect function signatures with effects
         self.collect_function_signatures(source);
         
+        // PASS 1b: Report every malformed signature up front
+        self.check_function_signatures(source);
+        
+        // PASS 1c: Report match arms shadowed by an earlier arm, and bool
+        // matches missing `true` or `false`
+        self.check_match_arm_reachability(source);
+        self.check_bool_match_coverage(source);
+        
+        // PASS 1d: Report variants built with the wrong number of values
+        self.check_variant_arity(source);
+        
+        // PASS 1e: Report arithmetic on integers and floats together
+        self.check_numeric_mixing(source);
+        
+        // PASS 1f: Report `outer` assignments with no enclosing variable
+        self.check_outer_targets(source);
+        
         // PASS 2: Analyze function bodies
         for (line_num, line) in source.lines().enumerate() {
             self.analyze_line(line, line_num + 1);
@@ -1617,7 +2281,8 @@ impl AntiFailLogicChecker {
         if self.effect_checking_enabled {
             self.validate_effect_contracts();
             self.validate_effect_propagation();
-            self.validate_effect_scope();
+            self.validate_effect_scope(source);
+            self.check_spawn_conflicts(source);
         }
         
         if self.errors.is_empty() {
@@ -1632,9 +2297,33 @@ impl AntiFailLogicChecker {
     //=========================================================================
     
     fn collect_function_signatures(&mut self, source: &str) {
+        // (impl self type, brace depth of the impl body)
+        let mut current_impl: Option<(String, usize)> = None;
+        let mut depth = 0usize;
+        
         for (line_num, line) in source.lines().enumerate() {
-            if self.is_function_start(line.trim()) {
+            let trimmed = line.trim();
+            let (opens, closes) = count_braces_outside_strings(trimmed);
+            if current_impl.is_none() && trimmed.contains('{') {
+                let header = trimmed.strip_prefix("pub ").unwrap_or(trimmed);
+                current_impl = impl_self_type(header).map(|owner| (owner, depth + 1));
+            }
+            depth = (depth + opens).saturating_sub(closes);
+            if matches!(&current_impl, Some((_, body)) if depth < *body) {
+                current_impl = None;
+            }
+            
+            if self.is_function_start(trimmed) {
                 if let Some(func_info) = self.parse_function_with_effects(line, line_num + 1) {
+                    if let Some((owner, _)) = &current_impl {
+                        if func_info.has_parameter("self") {
+                            self.method_owners.insert(line_num + 1, owner.clone());
+                            self.methods.entry(func_info.name.clone()).or_default().push(MethodEntry {
+                                owner: owner.clone(),
+                                declared_effects: func_info.declared_effects.clone(),
+                            });
+                        }
+                    }
                     self.effect_graph.add_function(&func_info.name);
                     self.function_table.insert(func_info.name.clone(), func_info);
                 }
@@ -1642,6 +2331,129 @@ impl AntiFailLogicChecker {
         }
     }
     
+    /// Report all malformed function signatures (RSPL020) in one run.
+    /// Multi-line signatures are joined until their parentheses balance.
+    fn check_function_signatures(&mut self, source: &str) {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut i = 0;
+        while i < lines.len() {
+            let trimmed = lines[i].trim();
+            if !(trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ")) {
+                i += 1;
+                continue;
+            }
+            
+            // (line index, offset of the piece inside the joined signature)
+            let mut pieces = vec![(i, 0usize)];
+            let mut joined = trimmed.to_string();
+            let mut depth = paren_balance(trimmed);
+            while depth > 0 && i + 1 < lines.len() {
+                i += 1;
+                let next = lines[i].trim();
+                joined.push(' ');
+                pieces.push((i, joined.len()));
+                joined.push_str(next);
+                depth += paren_balance(next);
+            }
+            i += 1;
+            
+            for (effect, condition) in effect_conditions(&joined) {
+                if let Err(e) = eval_condition(condition, &self.target) {
+                    let line = pieces.iter().rev()
+                        .find(|(_, offset)| joined[*offset..].contains(condition))
+                        .map_or(pieces[0].0, |(line, _)| *line);
+                    let error = crate::error_msg::effect_errors::invalid_effect_condition(effect, condition, &e)
+                        .at(self.make_location(line + 1, condition));
+                    self.errors.push(error);
+                }
+            }
+            
+            if let crate::function::FunctionParseResult::Error(e) = crate::function::parse_function_line(&joined) {
+                let mut location = crate::function::signature_error_location(&lines, &pieces, &joined, &e);
+                location.file = self.file_name.clone();
+                let error = crate::error_msg::structure_errors::malformed_signature(&e)
+                    .at(location)
+                    .note("checking continues after a malformed signature, so every one is reported in a single run");
+                self.errors.push(error);
+            }
+        }
+    }
+    
+    /// Report unreachable match arms (RSPL063): arms after a catch-all, and
+    /// literal or range patterns already covered by an earlier arm.
+    fn check_match_arm_reachability(&mut self, source: &str) {
+        for arm in crate::match_arms::find_unreachable_arms(source) {
+            let error = crate::error_msg::control_flow_errors::unreachable_match_arm(
+                &arm.pattern,
+                &arm.earlier_pattern,
+                arm.earlier_line,
+            )
+            .at(self.make_location(arm.line, &arm.pattern))
+            .label(self.make_location(arm.earlier_line, &arm.earlier_pattern), "first matched here");
+            self.errors.push(error);
+        }
+    }
+    
+    /// Report bool matches with no arm for `true` or `false` (RSPL064)
+    fn check_bool_match_coverage(&mut self, source: &str) {
+        for gap in crate::match_arms::find_missing_bool_arms(source) {
+            let error = crate::error_msg::control_flow_errors::bool_match_missing_arm(
+                &gap.header,
+                gap.missing,
+                gap.is_value,
+            )
+            .at(self.make_location(gap.line, &gap.header));
+            let fix = if gap.is_value { None } else { crate::fixes::add_catch_all_arm(&self.source_lines, gap.line) };
+            self.errors.push(error.with_fix(fix));
+        }
+    }
+    
+    /// Report enum variant constructions that do not match the variant's
+    /// declaration (RSPL027), pointing at both
+    fn check_variant_arity(&mut self, source: &str) {
+        for mismatch in crate::variant_arity::find_arity_mismatches(source) {
+            let variant = mismatch.path.rsplit("::").next().unwrap_or_default();
+            let error = crate::error_msg::structure_errors::variant_arity_mismatch(
+                &mismatch.path,
+                &mismatch.decl.kind,
+                mismatch.decl.arity,
+                mismatch.given,
+                mismatch.decl.line,
+            )
+            .at(self.make_location(mismatch.line, &mismatch.call))
+            .label(self.make_location(mismatch.decl.line, variant), "declared here");
+            self.errors.push(error);
+        }
+    }
+    
+    /// Operation on numeric types that do not mix (RSPL047), pointing at
+    /// the operand to convert
+    fn check_numeric_mixing(&mut self, source: &str) {
+        for mix in crate::numeric_mixing::find_numeric_mixes(source) {
+            let error = crate::error_msg::expression_errors::mixed_numeric_types(
+                &mix.expr,
+                &mix.left.display(),
+                &mix.right.display(),
+                &mix.fixed,
+            )
+            .at(self.make_location(mix.line, &mix.operand));
+            self.errors.push(error);
+        }
+    }
+    
+    /// `outer x = ..` where no enclosing scope declares `x` (RSPL082),
+    /// resolved the way the lowering resolves it
+    fn check_outer_targets(&mut self, source: &str) {
+        let mut analyzer = crate::scope::ScopeAnalyzer::new();
+        analyzer.analyze(source);
+        let functions = crate::diagnostic_groups::enclosing_functions(source);
+        for (line_idx, var_name) in &analyzer.outer_errors {
+            let error = analyzer.outer_error(*line_idx, var_name, &functions)
+                .at(self.make_location(line_idx + 1, &format!("outer {}", var_name)));
+            self.errors.push(error);
+        }
+    }
+    
     fn parse_function_with_effects(&self, line: &str, line_num: usize) -> Option<FunctionInfo> {
         let trimmed = line.trim();
         
@@ -1663,15 +2475,21 @@ impl AntiFailLogicChecker {
         
         // Extract parameters
         let params_start = trimmed.find('(')? + 1;
-        let params_end = trimmed.find(')')?;
+        let params_end = params_start + trimmed[params_start..].find(')')?;
         let params_str = &trimmed[params_start..params_end];
         
-        for param in params_str.split(',') {
+        for param in split_top_level_types(params_str, ',') {
             let param = param.trim();
             if param.is_empty() {
                 continue;
             }
             
+            // `self` is a parameter like any other: `effects(write self)`
+            if let Some(receiver) = receiver_param(param) {
+                func_info.parameters.push(receiver);
+                continue;
+            }
+            
             let parts: Vec<&str> = param.splitn(2, ' ').collect();
             if parts.len() == 2 {
                 let name = parts[0].trim().to_string();
@@ -1690,7 +2508,7 @@ impl AntiFailLogicChecker {
             // Find matching close paren
             let mut depth = 1;
             let mut end_pos = 0;
-            for (i, c) in after_effects.chars().enumerate() {
+            for (i, c) in after_effects.char_indices() {
                 match c {
                     '(' => depth += 1,
                     ')' => {
@@ -1706,11 +2524,20 @@ impl AntiFailLogicChecker {
             
             let effects_str = &after_effects[..end_pos];
             for effect_str in effects_str.split(',') {
-                if let Some(effect) = Effect::parse(effect_str.trim()) {
-                    func_info.declared_effects.add(effect);
+                // `io when not wasm`: declared only where the condition holds
+                let (effect_str, condition) = split_effect_condition(effect_str);
+                if condition.is_some_and(|c| eval_condition(c, &self.target) != Ok(true)) {
+                    continue;
+                }
+                if let Some(effect) = Effect::parse(effect_str) {
+                    func_info.declared_effects.add(effect);
                 }
             }
         }
+        // The cache of a `memo` function allocates
+        if self.memo_lines.contains(&line_num) {
+            func_info.declared_effects.add(Effect::Alloc);
+        }
         
         Some(func_info)
     }
@@ -1722,15 +2549,38 @@ impl AntiFailLogicChecker {
     fn analyze_line(&mut self, line: &str, line_num: usize) {
         let trimmed = line.trim();
         
+        // Verbatim block: record its declared effects for the enclosing function
+        if let Some(effects) = crate::verbatim::marker_effects(trimmed) {
+            if self.in_function && self.effect_checking_enabled {
+                for effect in effects.iter().filter_map(|e| Effect::parse(e)) {
+                    self.effect_analyzer.record_effect(effect, line_num);
+                }
+            }
+            return;
+        }
+        
         if trimmed.is_empty() || trimmed.starts_with("//") {
             return;
         }
         
+        // `#[cfg(..)]` false for the target: the next statement is compiled out
+        if self.in_function && eval_cfg_attribute(trimmed, &self.target) == Some(false) {
+            self.cfg_pending = true;
+            return;
+        }
+        if self.cfg_out_depth.is_some_and(|depth| self.brace_depth <= depth) {
+            self.cfg_out_depth = None;
+        }
+        let compiled_out = std::mem::take(&mut self.cfg_pending) || self.cfg_out_depth.is_some();
+        
         // Update expression context from brackets (IMPORTANT for enum constructor fix)
         self.expression_context.update_from_line(trimmed, line_num);
         
         let opens = self.count_open_braces(trimmed);
         let closes = self.count_close_braces(trimmed);
+        if compiled_out && self.cfg_out_depth.is_none() && opens > closes {
+            self.cfg_out_depth = Some(self.brace_depth);
+        }
         
         // ═══════════════════════════════════════════════════════════════════════
         // FIX: Detect struct/enum literals (single-line and multi-line)
@@ -1786,6 +2636,9 @@ impl AntiFailLogicChecker {
             self.check_control_flow_start(trimmed, line_num);
         }
         
+        // Logic-07: Keywords as binding names
+        self.check_keyword_bindings(trimmed, line_num);
+        
         // Logic-03: Check for illegal statements
         self.check_illegal_statement(trimmed, line_num);
         
@@ -1803,8 +2656,10 @@ impl AntiFailLogicChecker {
         // Effect analysis (if in function)
         if self.in_function && self.effect_checking_enabled {
             // Skip effect analysis for struct literal field initializations
-            if self.in_struct_literal_depth == 0 && !is_struct_literal_single {
+            // and statements compiled out for the target
+            if self.in_struct_literal_depth == 0 && !is_struct_literal_single && !compiled_out {
                 self.effect_analyzer.analyze_line(trimmed, line_num);
+                self.detect_receiver_writes(trimmed, line_num);
             }
         }
         
@@ -1873,12 +2728,71 @@ impl AntiFailLogicChecker {
             self.handle_close_brace();
         }
         
+        // A `for` binding belongs to the loop: assigning it in the body
+        // mutates it (see `crate::for_loops`)
+        if net_opens > net_closes {
+            if let Some((pattern, _)) = crate::for_loops::parse_for_header(trimmed) {
+                for name in crate::for_loops::loop_bindings(pattern) {
+                    self.loop_bindings.push((name, self.brace_depth));
+                }
+            }
+        }
+        
         // Check if function ended
         if self.in_function && self.brace_depth < self.function_depth {
             self.exit_function();
         }
     }
     
+    /// Calling a `write(self)` method on a parameter writes that parameter:
+    /// `acc.deposit(5)` inside `fn f(acc &mut Account)` is `write(acc)`, and
+    /// a `write(self.balance)` method makes it `write(acc.balance)`.
+    fn detect_receiver_writes(&mut self, line: &str, line_num: usize) {
+        let Some(func_info) = &self.current_function_info else {
+            return;
+        };
+        let mut writes = Vec::new();
+        for (receiver, method) in method_call_receivers(line) {
+            let Some(receiver_type) = func_info.parameters.iter()
+                .find(|(name, _)| *name == receiver)
+                .map(|(_, ty)| ty.as_str()) else {
+                continue;
+            };
+            for path in self.method_self_writes(&method, receiver_type, func_info.line_number) {
+                writes.push(Effect::Write(format!("{}{}", receiver, path)));
+            }
+        }
+        for effect in writes {
+            self.effect_analyzer.record_effect(effect, line_num);
+        }
+    }
+    
+    /// Resolve `recv.method()` by the receiver's type and return the parts of
+    /// `self` the method declares it writes: `""` for `write(self)`,
+    /// `".balance"` for `write(self.balance)`. `Self` resolves to the impl
+    /// type of the calling method.
+    fn method_self_writes(&self, method: &str, receiver_type: &str, caller_line: usize) -> Vec<String> {
+        let Some(candidates) = self.methods.get(method) else {
+            return Vec::new();
+        };
+        let base = receiver_type.trim_start_matches('&').trim_start_matches("mut ").trim();
+        let base: String = base.chars().take_while(|&c| crate::ident::is_ident_continue(c)).collect();
+        let owner = if base == "Self" {
+            self.method_owners.get(&caller_line).cloned()
+        } else {
+            Some(base)
+        };
+        let Some(method) = candidates.iter().find(|m| Some(&m.owner) == owner.as_ref()) else {
+            return Vec::new();
+        };
+        method.declared_effects.effects.iter()
+            .filter_map(|e| match e {
+                Effect::Write(p) if e.target_param() == Some("self") => Some(p["self".len()..].to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+    
     fn detect_closure(&self, line: &str) -> bool {
         let trimmed = line.trim();
         // Patterns: |args| { ... } or move |args| { ... }
@@ -1922,12 +2836,25 @@ impl AntiFailLogicChecker {
             let params: Vec<(String, String)> = func_info.parameters.clone();
             self.effect_analyzer.enter_function(&func_info.name, &params, &func_info.declared_effects);
             self.current_function_info = Some(func_info);
+            if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(line.trim()) {
+                if self.contracts.is_kept() {
+                    for condition in sig.contract.conditions() {
+                        self.effect_analyzer.analyze_line(&format!("assert!({});", condition), line_num);
+                    }
+                }
+                // `fn f(..) T = expr` has no body to wait for
+                if let (Some(expr), 0) = (&sig.single_line_expr, opens) {
+                    self.effect_analyzer.analyze_line(expr, line_num);
+                    self.exit_function();
+                }
+            }
         }
     }
     
     fn exit_function(&mut self) {
         // Collect detected effects
         if let Some(mut func_info) = self.current_function_info.take() {
+            func_info.effect_origins = self.effect_analyzer.take_origins();
             let (detected_effects, calls) = self.effect_analyzer.exit_function();
             
             for effect in detected_effects {
@@ -1936,6 +2863,7 @@ impl AntiFailLogicChecker {
             func_info.calls = calls.into_iter().map(|(name, _line)| name).collect();
             
             // Update function table
+            self.functions_by_line.insert(func_info.line_number, func_info.clone());
             self.function_table.insert(func_info.name.clone(), func_info);
         }
         
@@ -1978,6 +2906,11 @@ impl AntiFailLogicChecker {
         let functions: Vec<_> = self.function_table.values().cloned().collect();
         
         for func_info in functions {
+            // `memo` functions may perform nothing but their cache's `alloc`
+            if self.memo_lines.contains(&func_info.line_number) {
+                self.check_memo_purity(&func_info);
+                continue;
+            }
             // Check 1: All detected effects must be declared
             self.check_undeclared_effects(&func_info);
         }
@@ -1987,23 +2920,91 @@ impl AntiFailLogicChecker {
         let functions: Vec<_> = self.function_table.values().cloned().collect();
         
         for func_info in functions {
+            if self.memo_lines.contains(&func_info.line_number) {
+                continue;
+            }
             // Check 2: Cross-function effect propagation
             self.check_effect_propagation(&func_info);
         }
     }
     
-    fn validate_effect_scope(&mut self) {
-        // TODO: Implement closure effect leak detection
-        // This requires more sophisticated analysis of closure bodies
+    /// Effect-02: a closure annotated `effects(..)` performs no other
+    /// effect. The effects of closures without the annotation are the
+    /// enclosing function's (checked with its body).
+    fn validate_effect_scope(&mut self, source: &str) {
+        let mut functions = self.function_table.clone();
+        collect_closures(source, &mut functions);
+        let mut functions: Vec<&FunctionInfo> = functions.values().collect();
+        functions.sort_by_key(|info| info.line_number);
+        for info in functions {
+            for closure in &info.closures {
+                let Some(declared) = &closure.declared_effects else {
+                    continue;
+                };
+                for effect in closure.detected_effects.propagatable().effects.iter().filter(|e| !declared.covers(e)) {
+                    let error = crate::error_msg::effect_errors::effect_leak_closure(&info.name, &effect.display(), &declared.display())
+                        .at(self.make_location(closure.line_number, "effects"));
+                    self.errors.push(error);
+                }
+            }
+        }
+    }
+    
+    /// Effect-06: a spawned block writing state of the spawning function
+    fn check_spawn_conflicts(&mut self, source: &str) {
+        for conflict in crate::concurrency::find_spawn_conflicts(source) {
+            let error = crate::error_msg::effect_errors::concurrent_write_conflict(&conflict.binding, conflict.spawn_line)
+                .at(self.make_location(conflict.line, &conflict.binding))
+                .label(self.make_location(conflict.spawn_line, "spawn"), "thread spawned here");
+            self.errors.push(error);
+        }
+    }
+    
+    /// RSPL317 for the first effect of a `memo` function other than `alloc`:
+    /// declared, performed, or reached through a call
+    fn check_memo_purity(&mut self, func_info: &FunctionInfo) {
+        let impure = |effect: &&Effect| !matches!(effect, Effect::Alloc | Effect::Read(_));
+        let name = &func_info.name;
+        let violation = if let Some(effect) = func_info.declared_effects.effects.iter().find(impure) {
+            Some((format!("declares `{}`", effect.display()), func_info.line_number, "effects".to_string()))
+        } else if let Some(effect) = func_info.detected_effects.effects.iter().find(impure) {
+            let origin = func_info.effect_origins.get(effect);
+            Some((
+                format!("performs `{}`", func_info.reported_effect(effect).display()),
+                origin.map_or(func_info.line_number, |o| o.line),
+                origin.map_or(name.clone(), |o| o.highlight().to_string()),
+            ))
+        } else {
+            func_info.calls.iter().find_map(|called| {
+                let callee = self.function_table.get(called)?;
+                let effect = callee.declared_effects.effects.iter().find(impure)?;
+                let call = format!("{}(", called);
+                let line = self.source_lines.iter().enumerate().skip(func_info.line_number - 1)
+                    .take_while(|(i, l)| *i < func_info.line_number || !l.starts_with('}'))
+                    .find(|(_, l)| l.contains(&call))
+                    .map_or(func_info.line_number, |(i, _)| i + 1);
+                Some((format!("calls `{}`, which has effect `{}`", called, effect.display()), line, called.clone()))
+            })
+        };
+        if let Some((reason, line, highlight)) = violation {
+            let error = crate::error_msg::effect_errors::memo_not_pure(name, &reason)
+                .at(self.make_location(line, &highlight));
+            self.errors.push(error);
+        }
     }
     
     fn check_undeclared_effects(&mut self, func_info: &FunctionInfo) {
-        // Skip main function for I/O, alloc, panic (main is allowed these by default)
+        // Skip main function for I/O, alloc, panic, concurrent and resources (main is allowed these by default)
         let is_main = func_info.is_main();
         
+        let mut reported = BTreeSet::new();
         for detected in &func_info.detected_effects.effects {
-            // Main is allowed implicit I/O, panic, and alloc
-            if is_main && matches!(detected, Effect::Io | Effect::Panic | Effect::Alloc) {
+            // Main is allowed implicit I/O, panic, alloc, threads and handles
+            if is_main && matches!(detected, Effect::Io | Effect::Panic | Effect::Alloc | Effect::Concurrent | Effect::Resource(_)) {
+                continue;
+            }
+            // Tests only what they are granted
+            if func_info.is_test() && self.test_effects.covers(detected) {
                 continue;
             }
             
@@ -2012,15 +3013,18 @@ impl AntiFailLogicChecker {
                 continue;
             }
             
-            if !func_info.declared_effects.has_effect(detected) {
+            if !func_info.declared_effects.covers(detected) {
                 // For write effects, check if parameter exists
-                if let Effect::Write(ref param) = detected {
+                if let Some(param) = detected.target_param() {
                     if !func_info.has_parameter(param) {
                         continue; // Not a parameter write
                     }
                 }
                 
-                self.emit_undeclared_effect_error(func_info, detected);
+                let effect = func_info.reported_effect(detected);
+                if reported.insert(effect.clone()) {
+                    self.emit_undeclared_effect_error(func_info, &effect, func_info.effect_origins.get(detected));
+                }
             }
         }
     }
@@ -2035,26 +3039,48 @@ impl AntiFailLogicChecker {
                 }
                 
                 // Check if caller declares all propagatable effects of callee
-                for effect in called_func.declared_effects.propagatable_effects() {
-                    if !func_info.declared_effects.has_effect(&effect) {
-                        // Main is exempt from propagation requirements
-                        if !func_info.is_main() {
-                            self.emit_missing_propagation_error(func_info, called_name, &effect);
+                let missing = called_func.declared_effects.propagatable()
+                    .difference(&func_info.declared_effects);
+                // Main is exempt from propagation requirements; a test
+                // propagates into its grant
+                if !func_info.is_main() {
+                    for effect in &missing.effects {
+                        if func_info.is_test() && self.test_effects.covers(effect) {
+                            continue;
                         }
+                        self.emit_missing_propagation_error(func_info, called_name, effect);
                     }
                 }
                 
                 // Check 3: Pure function calling effectful function
                 if func_info.declared_effects.is_pure && 
                    !called_func.declared_effects.is_pure &&
-                   !func_info.is_main() {
+                   !func_info.is_main() && !func_info.is_test() {
                     self.emit_pure_calling_effectful_error(func_info, called_name);
                 }
             }
         }
     }
     
-    fn emit_undeclared_effect_error(&mut self, func_info: &FunctionInfo, effect: &Effect) {
+    fn emit_undeclared_effect_error(&mut self, func_info: &FunctionInfo, effect: &Effect, origin: Option<&EffectOrigin>) {
+        // `write(acc.balance)` is declared, but the whole `acc` (or another
+        // field) is written
+        let field_writes: Vec<String> = func_info.declared_effects.effects.iter()
+            .filter(|e| e.is_field_write() && e.target_param() == effect.target_param())
+            .map(|e| e.display())
+            .collect();
+        let field_note = if matches!(effect, Effect::Write(_)) && !field_writes.is_empty() {
+            let written = if effect.is_field_write() {
+                format!("field `{}`", effect.field_path().join("."))
+            } else {
+                format!("whole `{}`", effect.target_param().unwrap_or_default())
+            };
+            let covered = if field_writes.len() == 1 { "that field" } else { "those fields" };
+            format!("\n\n`{}` only covers {}, but the {} is written.", field_writes.join("`, `"), covered, written)
+        } else {
+            String::new()
+        };
+        
         let error = RsplError::new(
             ErrorCode::RSPL300,
             format!(
@@ -2071,12 +3097,14 @@ impl AntiFailLogicChecker {
              RustS+ enforces effect honesty - no hidden side effects allowed.\n\n\
              Effect Contract:\n\
              - Declared: {}\n\
-             - Detected: {}",
+             - Detected: {}{}{}",
             LogicViolation::UndeclaredEffect.code(),
             func_info.name,
             effect.display(),
             func_info.declared_effects.display(),
-            effect.display()
+            effect.display(),
+            origin.map(|o| format!(" (`{}` at line {})", o.construct, o.line)).unwrap_or_default(),
+            field_note
         ))
         .help(format!(
             "add `effects({})` to the function signature:\n\n    fn {}(...) effects({}) {{ ... }}",
@@ -2088,11 +3116,34 @@ impl AntiFailLogicChecker {
                 format!("{}, {}", func_info.declared_effects.display(), effect.display())
             }
         ));
+        let error = match origin {
+            Some(o) => error.label(self.make_location(o.line, o.highlight()), format!("`{}` performed here", effect.display())),
+            None => error,
+        };
+        let error = if func_info.is_test() {
+            error.help(self.test_grant_help(effect))
+        } else {
+            error.with_fix(crate::fixes::declare_effect(&self.source_lines, func_info.line_number, &func_info.name, &effect.display()))
+        };
         
         self.errors.push(error);
     }
     
     fn emit_missing_propagation_error(&mut self, func_info: &FunctionInfo, called: &str, effect: &Effect) {
+        // `f -> g -> h` down to the function performing the effect
+        let chain = self.effect_graph.call_chain(called, effect, &self.function_table);
+        let origin = chain.as_ref()
+            .and_then(|chain| self.function_table.get(chain.last()?))
+            .and_then(|info| info.effect_origins.get(effect))
+            .cloned();
+        let chain_note = match (&chain, &origin) {
+            (Some(chain), Some(o)) => format!(
+                "\n\ncall chain: `{} -> {} -> {}` (line {})",
+                func_info.name, chain.join(" -> "), o.construct, o.line
+            ),
+            _ => String::new(),
+        };
+
         let error = RsplError::new(
             ErrorCode::RSPL301,
             format!(
@@ -2107,11 +3158,12 @@ impl AntiFailLogicChecker {
             "{} VIOLATION: Missing Effect Propagation\n\n\
              function `{}` calls `{}` which performs `{}`.\n\
              effects must propagate upward - the caller must declare callee's effects.\n\n\
-             This ensures no hidden effects can leak through the call chain.",
+             This ensures no hidden effects can leak through the call chain.{}",
             LogicViolation::MissingEffectPropagation.code(),
             func_info.name,
             called,
-            effect.display()
+            effect.display(),
+            chain_note
         ))
         .help(format!(
             "add `{}` to the effects of `{}`:\n\n    fn {}(...) effects({}) {{ ... }}",
@@ -2124,10 +3176,30 @@ impl AntiFailLogicChecker {
                 format!("{}, {}", func_info.declared_effects.display(), effect.display())
             }
         ));
+        let error = match origin {
+            Some(o) => error.label(self.make_location(o.line, o.highlight()), format!("`{}` performed here", effect.display())),
+            None => error,
+        };
+        let error = if func_info.is_test() {
+            error.help(self.test_grant_help(effect))
+        } else {
+            error.with_fix(crate::fixes::declare_effect(&self.source_lines, func_info.line_number, &func_info.name, &effect.display()))
+        };
         
         self.errors.push(error);
     }
     
+    /// Help for an effect a `test` block performs outside its grant
+    fn test_grant_help(&self, effect: &Effect) -> String {
+        let mut grant = self.test_effects.clone();
+        grant.add(effect.clone());
+        let grant: Vec<String> = grant.effects.iter().map(|e| e.display()).collect();
+        format!(
+            "test blocks do not declare effects; widen the grant of all tests instead:\n\n    rustsp test {} --test-effects {}",
+            self.file_name, grant.join(",")
+        )
+    }
+    
     fn emit_pure_calling_effectful_error(&mut self, func_info: &FunctionInfo, called: &str) {
         let error = RsplError::new(
             ErrorCode::RSPL302,
@@ -2436,6 +3508,9 @@ impl AntiFailLogicChecker {
         }
         
         let is_mut_decl = trimmed.starts_with("mut ");
+        if !is_mut_decl && self.loop_bindings.iter().any(|(name, _)| *name == var_name) {
+            return;
+        }
         
         // ═══════════════════════════════════════════════════════════════════════
         // FIX: Proper scope-aware checking for reassignment vs shadowing
@@ -2492,6 +3567,46 @@ impl AntiFailLogicChecker {
         }
     }
     
+    //=========================================================================
+    // Logic-07: KEYWORD BINDINGS
+    //=========================================================================
+    
+    /// Reject Rust keywords used as binding names: `type = 1`, `mut match = x`,
+    /// `for fn in xs`, `fn f(type String)`. Raw identifiers (`r#type`) are fine.
+    fn check_keyword_bindings(&mut self, trimmed: &str, line_num: usize) {
+        for name in keyword_binding_names(trimmed, self.is_function_start(trimmed)) {
+            self.emit_keyword_identifier_error(&name, line_num, trimmed);
+        }
+    }
+    
+    fn emit_keyword_identifier_error(&mut self, keyword: &str, line_num: usize, source: &str) {
+        let rename = crate::ident::keyword_rename_suggestion(keyword);
+        let help = if crate::ident::can_be_raw_identifier(keyword) {
+            format!(
+                "rename the binding:\n\n    {} = ...\n\n\
+                 or keep the name as a raw identifier:\n\n    r#{} = ...",
+                rename, keyword
+            )
+        } else {
+            format!("rename the binding:\n\n    {} = ...", rename)
+        };
+        
+        let error = RsplError::new(
+            ErrorCode::RSPL086,
+            format!("`{}` is a Rust keyword and cannot be used as a name", keyword)
+        )
+        .at(self.make_location(line_num, source))
+        .note(format!(
+            "{} VIOLATION: Keyword Used As Identifier\n\n\
+             `{}` is reserved in Rust, so the generated code would not compile.",
+            LogicViolation::KeywordIdentifier.code(),
+            keyword
+        ))
+        .help(help);
+        
+        self.errors.push(error);
+    }
+    
     fn check_shadowing(&mut self, var_name: &str, line_num: usize, trimmed: &str) {
         if !self.in_function || self.scopes.len() <= 2 {
             return;
@@ -2526,7 +3641,8 @@ impl AntiFailLogicChecker {
             var_name,
             var_name
         ))
-        .help(format!("use `outer {} = ...` to modify outer variable", var_name));
+        .help(format!("use `outer {} = ...` to modify outer variable", var_name))
+        .with_fix(crate::fixes::assign_outer(&self.source_lines, line_num, var_name));
         
         self.errors.push(error);
     }
@@ -2569,7 +3685,8 @@ impl AntiFailLogicChecker {
         .help(format!(
             "change original declaration to:\n\n    mut {} = ...",
             var_name
-        ));
+        ))
+        .with_fix(crate::fixes::declare_mut(&self.source_lines, original_line, var_name));
         
         self.errors.push(error);
     }
@@ -2654,7 +3771,7 @@ impl AntiFailLogicChecker {
         }
         
         // Lines with `->` before `{` are function return types (Rust syntax)
-        if let Some(brace_pos) = trimmed.find('{') {
+        if let Some(brace_pos) = find_outside_strings(trimmed, '{') {
             let before_brace = &trimmed[..brace_pos];
             if before_brace.contains("->") {
                 return false;
@@ -2662,15 +3779,15 @@ impl AntiFailLogicChecker {
         }
         
         // Must contain `{` but NOT `}` (multiline start)
-        let has_open = trimmed.contains('{');
-        let has_close = trimmed.contains('}');
+        // CRITICAL FIX: Braces inside string literals (`"{}"`) don't count
+        let (open_count, close_count) = count_braces_outside_strings(trimmed);
         
-        if !has_open || has_close {
+        if open_count == 0 || close_count > 0 {
             return false;
         }
         
         // Find position of first `{`
-        let brace_pos = match trimmed.find('{') {
+        let brace_pos = match find_outside_strings(trimmed, '{') {
             Some(p) => p,
             None => return false,
         };
@@ -2697,6 +3814,11 @@ impl AntiFailLogicChecker {
         if let Some(last_word) = before_brace.split_whitespace().last() {
             let first_char = last_word.chars().next().unwrap_or('_');
             
+            // `ERR_NOT_FOUND {` is a match arm on a constant
+            if self.constants.contains(last_word) {
+                return false;
+            }
+            
             // If last word before `{` starts with uppercase, likely struct/enum
             if first_char.is_uppercase() {
                 return true;
@@ -2768,7 +3890,7 @@ impl AntiFailLogicChecker {
             return false;
         }
         
-        if let Some(brace_pos) = trimmed.find('{') {
+        if let Some(brace_pos) = find_outside_strings(trimmed, '{') {
             let before_brace = &trimmed[..brace_pos];
             if before_brace.contains("->") {
                 return false;
@@ -2786,16 +3908,17 @@ impl AntiFailLogicChecker {
         // For multiline starts, is_struct_literal_start() handles detection,
         // and in_struct_literal_depth tracks the nested field lines.
         // ═══════════════════════════════════════════════════════════════════════
-        let has_open = trimmed.contains('{');
-        let has_close = trimmed.contains('}');
+        // CRITICAL FIX: Braces inside string literals (`println!("{}", x)`) are
+        // format placeholders, not literal braces - count only real ones
+        let (open_count, close_count) = count_braces_outside_strings(trimmed);
         
         // Must have BOTH open AND close braces to be a single-line literal
-        if !has_open || !has_close {
+        if open_count == 0 || close_count == 0 {
             return false;
         }
         
         // Find position of first `{`
-        let brace_pos = match trimmed.find('{') {
+        let brace_pos = match find_outside_strings(trimmed, '{') {
             Some(p) => p,
             None => return false,
         };
@@ -2808,8 +3931,6 @@ impl AntiFailLogicChecker {
         let before_brace = &trimmed[..brace_pos].trim();
         
         // Check brace balance - must be balanced for single-line literal
-        let open_count = trimmed.chars().filter(|c| *c == '{').count();
-        let close_count = trimmed.chars().filter(|c| *c == '}').count();
         
         if open_count != close_count {
             // Unbalanced - this is NOT a complete single-line literal
@@ -3009,6 +4130,8 @@ impl AntiFailLogicChecker {
         if self.brace_depth > 0 {
             self.brace_depth -= 1;
         }
+        let depth = self.brace_depth;
+        self.loop_bindings.retain(|&(_, body_depth)| body_depth <= depth);
         
         if self.scopes.len() > 1 {
             self.scopes.pop();
@@ -3074,15 +4197,24 @@ pub fn check_logic_no_effects(source: &str, file_name: &str) -> Result<(), Vec<R
 }
 
 /// Run logic check with custom settings
+#[allow(clippy::too_many_arguments)]
 pub fn check_logic_custom(
     source: &str, 
     file_name: &str, 
     effect_checking: bool,
     strict_effects: bool,
+    test_effects: &EffectSignature,
+    target: &Target,
+    limits: &InputLimits,
+    contracts: ContractMode,
 ) -> Result<(), Vec<RsplError>> {
     let mut checker = AntiFailLogicChecker::new(file_name);
     checker.set_effect_checking(effect_checking);
     checker.set_strict_effect_mode(strict_effects);
+    checker.set_test_effects(test_effects.clone());
+    checker.set_target(target.clone());
+    checker.set_input_limits(*limits);
+    checker.set_contract_mode(contracts);
     checker.check(source)
 }
 
@@ -3090,9 +4222,108 @@ pub fn check_logic_custom(
 pub fn analyze_functions(source: &str, file_name: &str) -> HashMap<String, FunctionInfo> {
     let mut checker = AntiFailLogicChecker::new(file_name);
     let _ = checker.check(source);
+    collect_closures(source, &mut checker.function_table);
     checker.function_table
 }
 
+/// Function info for every function of a source file, keyed by the
+/// 1-based line of its signature (methods of different types may share a name)
+pub fn analyze_functions_by_line(source: &str, file_name: &str) -> BTreeMap<usize, FunctionInfo> {
+    let mut checker = AntiFailLogicChecker::new(file_name);
+    let _ = checker.check(source);
+    checker.functions_by_line
+}
+
+/// Function info and diagnostics for a source file from a single check run
+pub fn analyze_with_diagnostics(source: &str, file_name: &str) -> (HashMap<String, FunctionInfo>, Vec<RsplError>) {
+    let mut checker = AntiFailLogicChecker::new(file_name);
+    let errors = checker.check(source).err().unwrap_or_default();
+    (checker.function_table, errors)
+}
+
+/// Stage 1 warnings: reported, but never stop compilation. Currently
+/// `loop`s without an exit (RSPL003, see [`crate::loop_exits`]),
+/// bindings named like a macro that are then called (RSPL087, see
+/// [`crate::macro_shadowing`]) and file or socket handles left open
+/// (RSPL318, see [`crate::resources`]).
+pub fn logic_warnings(source: &str, file_name: &str) -> Vec<RsplError> {
+    let mut checker = AntiFailLogicChecker::new(file_name);
+    checker.source_lines = source.lines().map(String::from).collect();
+    let (masked, mut blocks) = crate::verbatim::mask_verbatim_blocks(source);
+    let (masked, _) = crate::dialect::mask_rust_functions(&masked, &mut blocks);
+    let mut warnings: Vec<RsplError> = crate::loop_exits::find_loops_without_exit(&masked)
+        .into_iter()
+        .map(|found| {
+            crate::error_msg::control_flow_errors::loop_without_exit(found.function.as_deref())
+                .at(checker.make_location(found.line, "loop"))
+        })
+        .collect();
+    for (name, line_num) in called_macro_shadows(&masked, &checker) {
+        warnings.push(
+            crate::error_msg::scope_errors::binding_shadows_macro(&name)
+                .at(checker.make_location(line_num, &name))
+        );
+    }
+    for handle in crate::resources::find_unclosed_handles(&masked) {
+        warnings.push(
+            crate::error_msg::effect_errors::unclosed_resource(&handle.binding, handle.kind, handle.leaked_at)
+                .at(checker.make_location(handle.line, &handle.binding))
+        );
+    }
+    warnings.sort_by_key(|w| w.location.line);
+    warnings
+}
+
+/// Bindings named like a macro (`format = compute()`) that are called later
+/// in the same function, with their 1-based declaration line
+fn called_macro_shadows(source: &str, checker: &AntiFailLogicChecker) -> Vec<(String, usize)> {
+    let mut found = Vec::new();
+    // Declared in the current function and not yet reported: name → line
+    let mut declared: Vec<(String, usize)> = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let clean = crate::helpers::strip_inline_comment(line);
+        let trimmed = clean.trim();
+        let is_fn = checker.is_function_start(trimmed);
+        if is_fn {
+            declared.clear();
+        }
+        declared.retain(|(name, decl_line)| {
+            let called = is_plain_call(trimmed, name);
+            if called {
+                found.push((name.clone(), *decl_line));
+            }
+            !called
+        });
+        for name in binding_names_matching(trimmed, is_fn, crate::macro_shadowing::is_macro_name) {
+            if !declared.iter().any(|(n, _)| *n == name) {
+                declared.push((name, idx + 1));
+            }
+        }
+    }
+    found
+}
+
+/// Does `line` call `name(...)` as a plain function (not `x.name(`, `name!(`)?
+fn is_plain_call(line: &str, name: &str) -> bool {
+    let pattern = format!("{}(", name);
+    line.match_indices(&pattern).any(|(i, _)| {
+        line[..i].chars().next_back()
+            .is_none_or(|c| !crate::ident::is_ident_continue(c) && c != '.' && c != ':')
+    })
+}
+
+/// Format logic warnings for display
+pub fn format_logic_warnings(warnings: &[RsplError]) -> String {
+    let mut output = String::new();
+    for warning in warnings {
+        let formatted = format_error(warning)
+            .replacen(&format!("{}error[", ansi::BOLD_RED), &format!("{}warning[", ansi::BOLD_YELLOW), 1);
+        output.push_str(&formatted);
+        output.push('\n');
+    }
+    output
+}
+
 /// Format logic errors for display
 pub fn format_logic_errors(errors: &[RsplError]) -> String {
     let mut output = String::new();
@@ -3154,6 +4385,25 @@ fn format_error(error: &RsplError) -> String {
         ));
     }
     
+    // Secondary labels: where a match arm, variant or effect comes from
+    for (location, message) in &error.labels {
+        if location.source_line.is_empty() {
+            continue;
+        }
+        let padding = " ".repeat(location.line.to_string().len());
+        // A label in another module file names it, as rustc does
+        if location.file != error.location.file && !location.file.is_empty() {
+            output.push_str(&format!("  {}::: {}:{}:{}{}\n", BLUE, location.file, location.line, location.column, RESET));
+        }
+        output.push_str(&format!("{}{}  |{}\n", BLUE, padding, RESET));
+        output.push_str(&format!("{}{} |{}   {}\n", BLUE, location.line, RESET, location.source_line));
+        output.push_str(&format!(
+            "{}{}  |{}   {}{}{} {}{}\n",
+            BLUE, padding, RESET,
+            " ".repeat(location.highlight_start), BOLD_CYAN, "-".repeat(location.highlight_len.max(1)), message, RESET
+        ));
+    }
+    
     // Note
     if let Some(ref note) = error.explanation {
         output.push_str(&format!("\n{}note{}:\n", BOLD_CYAN, RESET));
@@ -3212,6 +4462,23 @@ fn main() {
         assert!(result.is_ok());
     }
     
+    #[test]
+    fn test_for_binding_assignment() {
+        let source = r#"
+fn main() {
+    x = 1
+    for x in xs {
+        x = x + 1
+    }
+    y = x
+    y = 2
+}
+"#;
+        let errors = check_logic_no_effects(source, "test.rss").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].code, errors[0].location.line), (ErrorCode::RSPL071, 8));
+    }
+    
     #[test]
     fn test_logic02_shadowing() {
         let source = r#"
@@ -3402,6 +4669,113 @@ fn main() {
             result.unwrap_err());
     }
     
+    #[test]
+    fn test_unreachable_match_arm_reported() {
+        let source = r#"
+fn label(code i32) String {
+    match code {
+        0..=9 { "digit" }
+        5 { "five" }
+        _ { "other" }
+    }
+}
+"#;
+        let errors = check_logic_no_effects(source, "test.rss").unwrap_err();
+        let unreachable: Vec<_> = errors.iter().filter(|e| e.code == ErrorCode::RSPL063).collect();
+        assert_eq!(unreachable.len(), 1, "Expected one RSPL063, got: {:?}", errors);
+        assert_eq!(unreachable[0].location.line, 5);
+        assert_eq!(unreachable[0].labels[0].0.line, 4);
+    }
+    
+    #[test]
+    fn test_variant_arity_mismatch_reported() {
+        let source = r#"
+enum Event {
+    Query(u32)
+    Ping
+}
+
+fn build() Event {
+    Event::Query(3, 4)
+}
+"#;
+        let errors = check_logic_no_effects(source, "test.rss").unwrap_err();
+        let arity: Vec<_> = errors.iter().filter(|e| e.code == ErrorCode::RSPL027).collect();
+        assert_eq!(arity.len(), 1, "Expected one RSPL027, got: {:?}", errors);
+        assert_eq!(arity[0].location.line, 8);
+        assert_eq!(arity[0].labels[0].0.line, 3);
+        assert!(arity[0].title.contains("takes 1 value but 2 were given"));
+        
+        let fixed = source.replace("Query(3, 4)", "Query(3)");
+        assert!(check_logic_no_effects(&fixed, "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_numeric_mixing_reported() {
+        let source = r#"
+fn average(count i32) f64 {
+    total = count + 1.5
+    total
+}
+"#;
+        let errors = check_logic_no_effects(source, "test.rss").unwrap_err();
+        let mixed: Vec<_> = errors.iter().filter(|e| e.code == ErrorCode::RSPL047).collect();
+        assert_eq!(mixed.len(), 1, "Expected one RSPL047, got: {:?}", errors);
+        assert_eq!((mixed[0].location.line, mixed[0].location.column), (3, 13));
+        assert!(mixed[0].suggestion.as_deref().unwrap_or_default().contains("`count as f64 + 1.5`"));
+        
+        let fixed = source.replace("count + 1.5", "count as f64 + 1.5");
+        assert!(check_logic_no_effects(&fixed, "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_outer_without_enclosing_variable() {
+        let source = r#"
+fn tally() i32 {
+    mut total = 0
+    if ready() {
+        outer total = 1
+        outer missing = 2
+    }
+    total
+}
+"#;
+        let errors = check_logic_no_effects(source, "test.rss").unwrap_err();
+        let outer: Vec<_> = errors.iter().filter(|e| e.code == ErrorCode::RSPL082).collect();
+        assert_eq!(outer.len(), 1, "Expected one RSPL082, got: {:?}", errors);
+        assert_eq!((outer[0].location.line, outer[0].location.column), (6, 9));
+        assert!(outer[0].title.contains("`missing`"));
+    }
+    
+    #[test]
+    fn test_loop_without_exit_is_a_warning() {
+        let source = r#"fn spin(n i32) effects(io) {
+    loop {
+        println("{}", n)
+    }
+}
+"#;
+        assert!(check_logic(source, "test.rss").is_ok());
+        let warnings = logic_warnings(source, "test.rss");
+        assert_eq!(warnings.len(), 1, "Expected one RSPL003, got: {:?}", warnings);
+        assert_eq!(warnings[0].code, ErrorCode::RSPL003);
+        assert_eq!(warnings[0].location.line, 2);
+        assert!(format_logic_warnings(&warnings).contains("warning[RSPL003]"));
+
+        let allowed = source.replace("    loop {", "    loop {   // allow(infinite-loop)");
+        assert!(logic_warnings(&allowed, "test.rss").is_empty());
+    }
+    
+    #[test]
+    fn test_called_macro_shadow_is_a_warning() {
+        let source = "fn main() effects(io) {\n    format = make()\n    file = open()\n    s = format(5)\n    println(\"{}\", s)\n}\n\nfn other() String {\n    format(\"{}\", 1)\n}\n";
+        let warnings = logic_warnings(source, "test.rss");
+        assert_eq!(warnings.len(), 1, "Expected one RSPL087, got: {:?}", warnings);
+        assert_eq!(warnings[0].code, ErrorCode::RSPL087);
+        assert_eq!(warnings[0].location.line, 2);
+        assert_eq!(warnings[0].location.column, 5);
+    }
+    
     //=========================================================================
     // Effect System Tests
     //=========================================================================
@@ -3413,6 +4787,41 @@ fn main() {
         assert_eq!(Effect::parse("panic"), Some(Effect::Panic));
         assert_eq!(Effect::parse("read(x)"), Some(Effect::Read("x".to_string())));
         assert_eq!(Effect::parse("write(acc)"), Some(Effect::Write("acc".to_string())));
+        assert_eq!(Effect::parse("write acc.balance"), Some(Effect::Write("acc.balance".to_string())));
+    }
+    
+    #[test]
+    fn test_field_write_coverage() {
+        let whole = Effect::Write("acc".to_string());
+        let balance = Effect::Write("acc.balance".to_string());
+        let owner_name = Effect::Write("acc.owner.name".to_string());
+        assert!(whole.covers(&balance) && whole.covers(&owner_name));
+        assert!(balance.covers(&balance));
+        assert!(!balance.covers(&whole));
+        assert!(!balance.covers(&owner_name));
+        assert!(!balance.covers(&Effect::Write("acc.balances".to_string())));
+        assert!(Effect::Write("acc.owner".to_string()).covers(&owner_name));
+        assert_eq!(balance.target_param(), Some("acc"));
+        assert_eq!(owner_name.field_path(), vec!["owner", "name"]);
+        assert!(balance.is_field_write() && !whole.is_field_write());
+    }
+    
+    #[test]
+    fn test_field_write_effect_checked() {
+        let ok = "struct Account {\n    balance i64\n    owner String\n}\n\nfn pay(acc &mut Account, amount i64) effects(write acc.balance) {\n    acc.balance += amount\n}";
+        assert!(check_logic(ok, "test.rss").is_ok(), "{:?}", check_logic(ok, "test.rss"));
+        
+        let other_field = ok.replace("acc.balance += amount", "acc.owner = String::new()");
+        let errors = check_logic(&other_field, "test.rss").unwrap_err();
+        let error = errors.iter().find(|e| e.code == ErrorCode::RSPL300).expect("RSPL300");
+        assert!(error.title.contains("write(acc.owner)"), "{}", error.title);
+        assert!(error.explanation.as_ref().unwrap().contains("field `owner` is written"));
+        
+        let whole = "struct Account {\n    balance i64\n}\n\nfn reset(acc &mut Account, fresh Account) effects(write acc.balance) {\n    acc = fresh\n}";
+        let errors = check_logic(whole, "test.rss").unwrap_err();
+        let error = errors.iter().find(|e| e.code == ErrorCode::RSPL300).expect("RSPL300");
+        assert!(error.title.contains("write(acc)"), "{}", error.title);
+        assert!(error.explanation.as_ref().unwrap().contains("whole `acc` is written"));
     }
     
     #[test]
@@ -3430,6 +4839,176 @@ fn main() {
         assert!(result.is_ok());
     }
     
+    #[test]
+    fn test_closure_effect_attributed_to_enclosing_fn() {
+        let source = r#"
+fn show(xs Vec[i32]) {
+    xs.iter().for_each(|x| println("{}", x))
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        assert!(errors.iter().any(|e| e.title.contains("`show` performs effect `io`")));
+    }
+    
+    #[test]
+    fn test_fn_passed_to_hof_propagates_effects() {
+        let source = r#"
+fn log_item(x i32) effects(io) {
+    println!("{}", x)
+}
+
+fn show(xs Vec[i32]) {
+    xs.into_iter().for_each(log_item)
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        assert!(errors.iter().any(|e| e.title.contains("`show` calls `log_item`")));
+        
+        assert_eq!(
+            detect_hof_function_args("xs.iter().map(parse).filter(|x| keep(x)).for_each(log::write_line)"),
+            vec!["parse".to_string(), "write_line".to_string()]
+        );
+        assert!(detect_hof_function_args("println!(\".map(f)\")").is_empty());
+        assert_eq!(detect_hof_function_args("xs.into_iter().reduce(pick)"), vec!["pick".to_string()]);
+        assert!(detect_hof_function_args("xs.push(log_item)").is_empty());
+    }
+    
+    #[test]
+    fn test_verbatim_block_effects() {
+        // Body is not analyzed: `x = 1` inside would otherwise be a logic error
+        let declared = r#"
+fn stamp() u128 effects(io) {
+    rust effects(io) {
+        let x = 1;
+        std::time::SystemTime::now().elapsed().unwrap().as_millis() + x
+    }
+}
+"#;
+        assert!(check_logic(declared, "test.rss").is_ok());
+        
+        // Declared block effects still need to be on the enclosing function
+        let undeclared = r#"
+fn stamp() u128 {
+    rust effects(io) {
+        0
+    }
+}
+"#;
+        assert!(check_logic(undeclared, "test.rss").is_err());
+    }
+    
+    #[test]
+    fn test_custom_effect_detector() {
+        use crate::effect_detector::{register_detector, PatternDetector};
+        let source = "fn start() {\n    audit_trail_write(\"boot\")\n}";
+        assert!(check_logic(source, "test.rss").is_ok());
+        
+        register_detector(PatternDetector::new("audit-trail", EffectCategory::Io, &["audit_trail_write("]));
+        assert!(check_logic(source, "test.rss").is_err());
+        assert!(check_logic(&source.replace("fn start()", "fn start() effects(io)"), "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_effect_diagnostics_name_their_cause() {
+        let source = "fn log_it(msg String) effects(io) {\n    x = 1\n    println(\"{}\", msg)\n}\n\nfn step(msg String) effects(io) {\n    log_it(msg)\n}\n\nfn run() effects(panic) {\n    step(\"go\")\n}\n\nfn count(v Vec[i32]) usize {\n    n = v.len()\n    print(\"{}\", n)\n    n\n}\n";
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        
+        let undeclared = errors.iter().find(|e| e.code == ErrorCode::RSPL300).unwrap();
+        assert!(undeclared.explanation.as_deref().unwrap().contains("- Detected: io (`print!` at line 16)"), "{:?}", undeclared.explanation);
+        assert_eq!(undeclared.labels[0].0.line, 16);
+        assert_eq!(undeclared.labels[0].0.highlight_start, 4);
+        
+        let propagated = errors.iter().find(|e| e.code == ErrorCode::RSPL301).unwrap();
+        assert!(propagated.explanation.as_deref().unwrap().contains("call chain: `run -> step -> log_it -> println!` (line 3)"), "{:?}", propagated.explanation);
+        assert_eq!(propagated.labels[0].0.line, 3);
+        
+        let verbatim = "fn now_ms() u128 {\n    rust effects(io) {\n        0\n    }\n}\n";
+        let errors = check_logic(verbatim, "test.rss").unwrap_err();
+        let undeclared = errors.iter().find(|e| e.code == ErrorCode::RSPL300).unwrap();
+        assert!(undeclared.explanation.as_deref().unwrap().contains("- Detected: io (`rust block` at line 2)"), "{:?}", undeclared.explanation);
+    }
+    
+    #[test]
+    fn test_constants_are_not_struct_literals() {
+        // The reassignment without `mut` is found behind a constant arm or
+        // condition, as it is behind `404 {`
+        let arm = "const ERR_NOT_FOUND i32 = 404\n\nfn f(code i32) i32 {\n    match code {\n        ERR_NOT_FOUND {\n            y = 1\n            y = 2\n            y\n        }\n        _ {\n            0\n        }\n    }\n}\n";
+        let condition = "const LIMIT i32 = 10\n\nfn g(n i32) i32 {\n    if n > LIMIT {\n        y = 1\n        y = 2\n    }\n    n\n}\n";
+        for source in [arm, condition] {
+            let errors = check_logic(source, "test.rss").unwrap_err();
+            assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL071), "{:?}", errors);
+        }
+    }
+    
+    #[test]
+    fn test_target_conditional_effects() {
+        let wasm = Target::from_triple("wasm32-unknown-unknown");
+        let linux = Target::from_triple("x86_64-unknown-linux-gnu");
+        let check = |source: &str, target: &Target| check_logic_custom(source, "test.rss", true, true, &EffectSignature::with_effects(DEFAULT_TEST_EFFECTS.into_iter().collect()), target, &InputLimits::default(), ContractMode::default());
+        
+        // println on native only: honest on both targets
+        let guarded = "fn greet() effects(io when not wasm) {\n    #[cfg(not(target_arch = \"wasm32\"))]\n    println(\"hi\")\n}";
+        assert!(check(guarded, &linux).is_ok());
+        assert!(check(guarded, &wasm).is_ok());
+        
+        // A compiled-out block
+        let block = "fn greet() effects(io when native) {\n    #[cfg(unix)]\n    if true {\n        println(\"hi\")\n    }\n}";
+        assert!(check(block, &wasm).is_ok());
+        
+        // Unguarded println still needs io on wasm
+        let unguarded = "fn greet() effects(io when not wasm) {\n    println(\"hi\")\n}";
+        assert!(check(unguarded, &linux).is_ok());
+        let errors = check(unguarded, &wasm).unwrap_err();
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL300), "{:?}", errors);
+        
+        let malformed = "fn greet() effects(io when not) {\n}";
+        let errors = check(malformed, &linux).unwrap_err();
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL305 && e.location.line == 1), "{:?}", errors);
+    }
+    
+    #[test]
+    fn test_keyword_binding_rejected() {
+        for source in ["fn main() {\n    match = 1\n}", "fn main() {\n    mut type i32 = 1\n}",
+                       "fn f(fn i32) {\n}", "fn main() {\n    for loop in 0..3 {\n    }\n}"] {
+            let errors = check_logic(source, "test.rss").unwrap_err();
+            assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL086), "Keyword not rejected in {:?}", source);
+        }
+        
+        // Raw identifiers and type aliases are fine
+        let ok = "type Alias = u8\nfn main() {\n    r#type = 5\n    größe = r#type\n}";
+        assert!(check_logic(ok, "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_method_write_self_effect() {
+        let source = "struct Counter {\n    n i32\n}\n\nimpl Counter {\n    fn bump(&mut self) effects(write self) {\n        self.n += 1\n    }\n\n    fn reset(&mut self) {\n        self.n = 0\n    }\n}\n\nfn run(c &mut Counter) {\n    c.bump()\n}\n\nfn run_declared(c &mut Counter) effects(write c) {\n    c.bump()\n}";
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        let undeclared: Vec<&str> = errors.iter()
+            .filter(|e| e.code == ErrorCode::RSPL300)
+            .map(|e| e.title.as_str())
+            .collect();
+        assert_eq!(undeclared.len(), 2, "{:?}", undeclared);
+        assert!(undeclared.iter().any(|m| m.contains("`reset`") && m.contains("write(self)")));
+        assert!(undeclared.iter().any(|m| m.contains("`run`") && m.contains("write(c)")));
+        
+        assert_eq!(method_call_receivers("self.items.push(x.len())"), vec![
+            ("self".to_string(), "push".to_string()),
+            ("x".to_string(), "len".to_string()),
+        ]);
+        assert_eq!(impl_self_type("impl[T] Display for Stack[T] {").as_deref(), Some("Stack"));
+    }
+    
+    #[test]
+    fn test_malformed_signatures_reported_together() {
+        let source = "fn bad(x) i32 {\n    x\n}\n\nfn ok(a i32) i32 {\n    a\n}\n\nfn bad2(\n    a i32,\n    b\n) {\n}";
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        let spans: Vec<(usize, usize)> = errors.iter()
+            .filter(|e| e.code == ErrorCode::RSPL020)
+            .map(|e| (e.location.line, e.location.column))
+            .collect();
+        assert_eq!(spans, vec![(1, 8), (11, 5)]);
+    }
+    
     #[test]
     fn test_effect_undeclared_io_error() {
         let source = r#"
@@ -3470,6 +5049,52 @@ fn main() {
         assert!(result.is_ok(), "main should be allowed implicit I/O");
     }
     
+    #[test]
+    fn test_test_blocks_get_test_grant() {
+        let source = r#"
+fn add(a i32, b i32) i32 {
+    a + b
+}
+
+fn start() effects(concurrent, panic) {
+    h = spawn {
+        work()
+    }
+    join h
+}
+
+test "adds" {
+    println!("checking")
+    assert_eq!(add(1, 2), 3)
+}
+
+test "starts" {
+    start()
+}
+
+fn main() {
+    println!("{}", add(2, 2))
+}
+"#;
+        let (source, _) = crate::entry::rewrite_test_blocks(source, true);
+        let source = crate::entry::rename_user_main(&source);
+        let check = |grant: &str| check_logic_custom(&source, "test.rss", true, false, &parse_test_effects(grant).unwrap(), &Target::host(), &InputLimits::default(), ContractMode::default());
+        
+        // io, panic and alloc are granted; `concurrent` has to be
+        let errors = check_logic_custom(&source, "test.rss", true, false,
+            &EffectSignature::with_effects(DEFAULT_TEST_EFFECTS.into_iter().collect()), &Target::host(), &InputLimits::default(), ContractMode::default()).unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].code, ErrorCode::RSPL301);
+        assert!(errors[0].suggestion.as_deref().unwrap().contains("--test-effects io,alloc,panic,concurrent"), "{:?}", errors[0].suggestion);
+        assert!(check("io,panic,alloc,concurrent").is_ok());
+        
+        let errors = check("none").unwrap_err();
+        assert!(errors.iter().all(|e| e.title.contains("__rustsp_test_")), "{:?}", errors);
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL300 && e.title.contains("`io`")));
+        
+        assert!(parse_test_effects("io, read(x)").is_err());
+    }
+    
     #[test]
     fn test_effect_propagation() {
         let source = r#"
@@ -3503,6 +5128,109 @@ fn outer() {
         assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL301 || e.code == ErrorCode::RSPL302));
     }
     
+    #[test]
+    fn test_memo_requires_pure_function() {
+        let source = r#"
+fn log(x u64) effects(io) {
+    println!("{}", x)
+}
+
+memo fn fib(n u64) u64 {
+    if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+}
+
+memo fn noisy(n u64) u64 {
+    println!("{}", n)
+    n
+}
+
+memo fn declared(n u64) effects(io) u64 {
+    n
+}
+
+memo fn calling(n u64) u64 {
+    log(n)
+    n
+}
+
+fn main() {
+    println!("{}", fib(10))
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        let mut memo: Vec<(usize, &str)> = errors.iter()
+            .filter(|e| e.code == ErrorCode::RSPL317)
+            .map(|e| (e.location.line, e.title.as_str()))
+            .collect();
+        memo.sort();
+        assert_eq!(memo, vec![
+            (11, "memo function `noisy` is not pure: it performs `io`"),
+            (15, "memo function `declared` is not pure: it declares `io`"),
+            (20, "memo function `calling` is not pure: it calls `log`, which has effect `io`"),
+        ], "{:?}", errors);
+        // Neither `fib` nor the callers of `noisy` get another effect error
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        
+        // The cache is an `alloc` callers propagate
+        let caller = "memo fn sq(n u64) u64 {\n    n * n\n}\n\nfn area(n u64) u64 {\n    sq(n)\n}\n";
+        let errors = check_logic(caller, "test.rss").unwrap_err();
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL301 && e.title.contains("`alloc`")), "{:?}", errors);
+        assert!(check_logic(&caller.replace("fn area(n u64) u64", "fn area(n u64) effects(alloc) u64"), "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_spawn_effects() {
+        let source = r#"
+fn worker(n i32) effects(panic) i32 {
+    h = spawn { n * 2 }
+    join h
+}
+
+fn main() {
+    mut count = 0
+    h = spawn {
+        count += 1
+    }
+    join h
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL300 && e.title.contains("`concurrent`")));
+        assert!(errors.iter().any(|e| e.code == ErrorCode::RSPL309 && e.location.line == 10));
+        
+        let declared = "fn worker(n i32) effects(concurrent, panic) i32 {\n    h = spawn { n * 2 }\n    join h\n}";
+        assert!(check_logic(declared, "test.rss").is_ok());
+    }
+    
+    #[test]
+    fn test_resource_effects() {
+        assert_eq!(Effect::parse("resource(socket)"), Some(Effect::Resource("socket".to_string())));
+        assert!(!Effect::Resource("file".to_string()).is_propagatable());
+        let source = r#"
+fn size(path String) effects(io, panic) u64 {
+    file = File::open(path).unwrap()
+    file.metadata().unwrap().len()
+}
+
+fn main() {
+    f = File::open("a.txt").unwrap()
+    println("{}", size("a.txt".to_string()))
+    drop(f)
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].code == ErrorCode::RSPL300 && errors[0].title.contains("`resource(file)`"));
+        let declared = source.replace("effects(io, panic)", "effects(io, panic, resource(file))");
+        assert!(check_logic(&declared, "test.rss").is_ok());
+        
+        let warnings = logic_warnings(&declared, "test.rss");
+        assert_eq!(warnings.len(), 1, "Expected one RSPL318, got: {:?}", warnings);
+        assert_eq!(warnings[0].code, ErrorCode::RSPL318);
+        assert_eq!(warnings[0].location.line, 3);
+        assert!(warnings[0].title.contains("before line 5"));
+    }
+    
     #[test]
     fn test_pure_function() {
         let source = r#"
@@ -3526,4 +5254,104 @@ fn add(a i32, b i32) i32 {
         assert!(sig.display().contains("io"));
         assert!(sig.display().contains("write(acc)"));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_effect_signature_matches_effect_set() {
+        let mut sig = EffectSignature::new();
+        sig.add(Effect::Panic);
+        sig.add(Effect::Io);
+        sig.add(Effect::Write("acc".to_string()));
+        sig.add(Effect::Read("acc".to_string()));
+        
+        let params: HashMap<String, BindingId> = [("acc".to_string(), BindingId::new(0))].into_iter().collect();
+        let bindings: HashMap<BindingId, BindingInfo> = [(BindingId::new(0), BindingInfo {
+            id: BindingId::new(0),
+            name: "acc".to_string(),
+            ty: None,
+            mutable: false,
+            scope_depth: 0,
+            decl_span: Default::default(),
+            is_outer: false,
+            is_param: true,
+        })].into_iter().collect();
+        
+        // Both analyzers print the same set the same way
+        let set = sig.to_effect_set(&params);
+        assert_eq!(set.display(&bindings), sig.display());
+        assert_eq!(EffectSignature::from_effect_set(&set, &bindings).effects, sig.effects);
+        
+        // ... and agree on the set operations
+        let declared = EffectSignature::with_effects([Effect::Io].into_iter().collect());
+        let undeclared = sig.propagatable().difference(&declared);
+        let ir_undeclared = set.propagatable().difference(&declared.to_effect_set(&params));
+        assert_eq!(undeclared.display(), "panic");
+        assert_eq!(ir_undeclared.display(&bindings), undeclared.display());
+        assert!(declared.is_subset_of(&sig));
+        assert!(EffectSignature::new().is_subset_of(&declared));
+    }
+    
+    #[test]
+    fn test_field_write_maps_to_parameter_binding() {
+        let sig = EffectSignature::with_effects([Effect::Write("acc.balance".to_string())].into_iter().collect());
+        let params: HashMap<String, BindingId> = [("acc".to_string(), BindingId::new(3))].into_iter().collect();
+        assert!(sig.to_effect_set(&params).has_write(BindingId::new(3)));
+        assert!(sig.to_effect_set(&HashMap::new()).is_empty());
+    }
+    
+    #[test]
+    fn test_closures_listed_with_effects() {
+        let source = r#"
+fn log_item(x i32) effects(io) {
+    println("{}", x)
+}
+
+fn show(xs Vec[i32]) effects(io) {
+    xs.iter().for_each(|x| effects(io) {
+        println("{}", x)
+    })
+    ys = xs.iter().map(|x| x * 2).collect::<Vec<i32>>()
+    ys.iter().for_each(|y| log_item(*y))
+    ok = ys.len() > 2 || ys.is_empty()
+}
+"#;
+        let functions = analyze_functions(source, "test.rss");
+        let closures = &functions["show"].closures;
+        assert_eq!(closures.len(), 3, "closures: {:?}", closures);
+        assert_eq!(closures[0].line_number, 7);
+        assert!(closures[0].declared_effects.as_ref().unwrap().has_io());
+        assert!(closures[0].detected_effects.has_io());
+        assert_eq!(closures[1].params, "x");
+        assert!(closures[1].declared_effects.is_none());
+        assert!(closures[1].detected_effects.propagatable().is_empty());
+        // Effects of called functions count for the closure
+        assert!(closures[2].detected_effects.has_io());
+    }
+    
+    #[test]
+    fn test_annotated_closure_effect_leak() {
+        let source = r#"
+fn log_item(x i32) effects(io) {
+    println("{}", x)
+}
+
+fn show(xs Vec[i32]) effects(io) {
+    quiet = fn(x i32) effects() {
+        log_item(x)
+    }
+    loud = |x i32| effects(io) {
+        log_item(x)
+    }
+    xs.iter().for_each(|x| log_item(*x))
+}
+"#;
+        let errors = check_logic(source, "test.rss").unwrap_err();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].code, errors[0].location.line), (ErrorCode::RSPL303, 7));
+        assert_eq!(errors[0].title, "effect `io` leaks to closure in function `show`");
+        
+        // `fn(..)` lambdas are listed like pipe closures
+        let closures = &analyze_functions(source, "test.rss")["show"].closures;
+        assert_eq!(closures[0].params, "x i32");
+        assert!(closures[0].declared_effects.as_ref().unwrap().effects.is_empty());
+    }
+}
@@ -35,12 +35,9 @@ impl Span {
     }
     
     pub fn merge(&self, other: &Span) -> Span {
-        Span {
-            start_line: self.start_line.min(other.start_line),
-            start_col: if self.start_line <= other.start_line { self.start_col } else { other.start_col },
-            end_line: self.end_line.max(other.end_line),
-            end_col: if self.end_line >= other.end_line { self.end_col } else { other.end_col },
-        }
+        let (start_line, start_col) = (self.start_line, self.start_col).min((other.start_line, other.start_col));
+        let (end_line, end_col) = (self.end_line, self.end_col).max((other.end_line, other.end_col));
+        Span { start_line, start_col, end_line, end_col }
     }
 }
 
@@ -255,6 +252,12 @@ pub enum Expr {
     /// Array literal: `[1, 2, 3]`
     Array(Vec<Spanned<Expr>>),
     
+    /// Array repeat: `[0; 64]`
+    Repeat {
+        value: Box<Spanned<Expr>>,
+        count: Box<Spanned<Expr>>,
+    },
+    
     /// If expression: `if cond { then } else { else }`
     If {
         condition: Box<Spanned<Expr>>,
@@ -268,6 +271,28 @@ pub enum Expr {
         arms: Vec<MatchArm>,
     },
     
+    /// Pattern test in an `if`/`while` condition: `let Some(x) = value`
+    Let {
+        pattern: Box<Spanned<Pattern>>,
+        value: Box<Spanned<Expr>>,
+    },
+    
+    /// While loop: `while cond { body }`
+    While {
+        condition: Box<Spanned<Expr>>,
+        body: Box<Spanned<Block>>,
+    },
+    
+    /// For loop: `for pattern in iter { body }`
+    For {
+        pattern: Box<Spanned<Pattern>>,
+        iter: Box<Spanned<Expr>>,
+        body: Box<Spanned<Block>>,
+    },
+    
+    /// Infinite loop: `loop { body }`
+    Loop(Box<Spanned<Block>>),
+    
     /// Block expression: `{ stmts; expr }`
     Block(Box<Spanned<Block>>),
     
@@ -318,6 +343,9 @@ pub enum Expr {
         expr: Box<Spanned<Expr>>,
         target_type: Type,
     },
+    
+    /// Error propagation: `parse(s)?`
+    Try(Box<Spanned<Expr>>),
 }
 
 /// Match arm
@@ -436,6 +464,8 @@ pub enum EffectDecl {
     Read(Ident),
     /// Write to parameter: `write(param)`
     Write(Ident),
+    /// Write to a field of a parameter: `write(acc.balance)`
+    WriteField { param: Ident, fields: Vec<Ident> },
     /// I/O effect: `io`
     Io,
     /// Allocation effect: `alloc`
@@ -449,6 +479,10 @@ impl EffectDecl {
         match self {
             EffectDecl::Read(p) => format!("read({})", p.name),
             EffectDecl::Write(p) => format!("write({})", p.name),
+            EffectDecl::WriteField { param, fields } => {
+                let path: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+                format!("write({}.{})", param.name, path.join("."))
+            }
             EffectDecl::Io => "io".to_string(),
             EffectDecl::Alloc => "alloc".to_string(),
             EffectDecl::Panic => "panic".to_string(),
@@ -654,5 +688,7 @@ mod tests {
     fn test_effect_decl() {
         let eff = EffectDecl::Write(Ident::new("acc"));
         assert_eq!(eff.to_string(), "write(acc)");
+        let field = EffectDecl::WriteField { param: Ident::new("acc"), fields: vec![Ident::new("balance")] };
+        assert_eq!(field.to_string(), "write(acc.balance)");
     }
 }
\ No newline at end of file
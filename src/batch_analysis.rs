@@ -0,0 +1,220 @@
+//! Batch Analysis over Directories
+//!
+//! `rustsp analyze-dir src/` runs the Stage 1 effect/logic analysis on every
+//! `.rss` file below a directory and aggregates the results:
+//!
+//! ```text
+//! Files analyzed:      12
+//! Functions:           148 (97 pure, 65.5%)
+//! Violations:          3
+//!   RSPL300 [effect]   2
+//!   RSPL071 [logic]    1
+//! ```
+//!
+//! Files are analyzed in parallel (one worker per available core); the report
+//! itself is deterministic - files are sorted by path. `--json` / `--csv`
+//! export the same data for CI dashboards.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::anti_fail_logic::analyze_with_diagnostics;
+use crate::eir::EffectAlgebra;
+use crate::error_msg::RsplError;
+use crate::rust_project::escape_json;
+
+/// Analysis result for one file
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub path: PathBuf,
+    /// Number of functions found
+    pub functions: usize,
+    /// Functions with no effect beyond reading their parameters
+    pub pure_functions: usize,
+    /// Stage 1 diagnostics
+    pub errors: Vec<RsplError>,
+    /// Set when the file could not be read
+    pub read_error: Option<String>,
+}
+
+/// Aggregate of all analyzed files
+#[derive(Debug, Clone, Default)]
+pub struct BatchReport {
+    pub files: Vec<FileReport>,
+}
+
+impl BatchReport {
+    pub fn function_count(&self) -> usize {
+        self.files.iter().map(|f| f.functions).sum()
+    }
+
+    pub fn pure_count(&self) -> usize {
+        self.files.iter().map(|f| f.pure_functions).sum()
+    }
+
+    /// Share of pure functions (0.0 when there are none)
+    pub fn purity_ratio(&self) -> f64 {
+        match self.function_count() {
+            0 => 0.0,
+            total => self.pure_count() as f64 / total as f64,
+        }
+    }
+
+    pub fn violation_count(&self) -> usize {
+        self.files.iter().map(|f| f.errors.len()).sum()
+    }
+
+    /// Violation counts per error code, in code order
+    pub fn violations_by_code(&self) -> BTreeMap<&'static str, (String, usize)> {
+        let mut by_code: BTreeMap<&'static str, (String, usize)> = BTreeMap::new();
+        for error in self.files.iter().flat_map(|f| &f.errors) {
+            let entry = by_code
+                .entry(error.code.code_str())
+                .or_insert_with(|| (error.category().to_string(), 0));
+            entry.1 += 1;
+        }
+        by_code
+    }
+
+    pub fn to_json(&self) -> String {
+        let files: Vec<String> = self.files.iter().map(|f| {
+            let errors: Vec<String> = f.errors.iter().map(|e| format!(
+                "{{\"code\": \"{}\", \"line\": {}, \"column\": {}, \"message\": \"{}\"}}",
+                e.code.code_str(), e.location.line, e.location.column, escape_json(&e.title)
+            )).collect();
+            let read_error = match &f.read_error {
+                Some(err) => format!("\"{}\"", escape_json(err)),
+                None => "null".to_string(),
+            };
+            format!(
+                "    {{\"path\": \"{}\", \"functions\": {}, \"pure_functions\": {}, \"read_error\": {}, \"violations\": [{}]}}",
+                escape_json(&f.path.to_string_lossy()), f.functions, f.pure_functions, read_error, errors.join(", ")
+            )
+        }).collect();
+        let by_code: Vec<String> = self.violations_by_code().iter()
+            .map(|(code, (_, count))| format!("\"{}\": {}", code, count))
+            .collect();
+        format!(
+            "{{\n  \"files_analyzed\": {},\n  \"functions\": {},\n  \"pure_functions\": {},\n  \"purity_ratio\": {:.4},\n  \"violations\": {},\n  \"violations_by_code\": {{{}}},\n  \"files\": [\n{}\n  ]\n}}\n",
+            self.files.len(), self.function_count(), self.pure_count(), self.purity_ratio(),
+            self.violation_count(), by_code.join(", "), files.join(",\n")
+        )
+    }
+
+    /// One row per violation: `path,line,column,code,category,message`
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("path,line,column,code,category,message\n");
+        for f in &self.files {
+            for e in &f.errors {
+                out.push_str(&format!(
+                    "{},{},{},{},{},{}\n",
+                    csv_field(&f.path.to_string_lossy()), e.location.line, e.location.column,
+                    e.code.code_str(), e.category(), csv_field(&e.title)
+                ));
+            }
+        }
+        out
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Recursively find all .rss files below `dir` (skips target/ and hidden dirs)
+pub fn find_rss_files(dir: &Path) -> Vec<PathBuf> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                if name != "target" && !name.starts_with('.') && name != "node_modules" {
+                    walk(&path, files);
+                }
+            } else if path.extension().is_some_and(|e| e == "rss") {
+                files.push(path);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, &mut files);
+    files.sort();
+    files
+}
+
+/// Analyze one source file
+pub fn analyze_source(path: &Path, source: &str) -> FileReport {
+    let (functions, mut errors) = analyze_with_diagnostics(source, &path.to_string_lossy());
+    // Effect checks walk a HashMap - order by position for a stable report
+    errors.sort_by_key(|e| (e.location.line, e.location.column));
+    let pure_functions = functions.values()
+        .filter(|f| f.declared_effects.union(&f.detected_effects).propagatable().is_empty())
+        .count();
+    FileReport {
+        path: path.to_path_buf(),
+        functions: functions.len(),
+        pure_functions,
+        errors,
+        read_error: None,
+    }
+}
+
+fn analyze_path(path: &Path) -> FileReport {
+    match fs::read_to_string(path) {
+        Ok(source) => analyze_source(path, &source),
+        Err(e) => FileReport {
+            path: path.to_path_buf(),
+            functions: 0,
+            pure_functions: 0,
+            errors: Vec::new(),
+            read_error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Analyze every .rss file below `dir`, spreading files over worker threads
+pub fn analyze_dir(dir: &Path) -> BatchReport {
+    let paths = find_rss_files(dir);
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(paths.len()).max(1);
+    let chunk_size = paths.len().div_ceil(workers).max(1);
+
+    let mut files: Vec<FileReport> = thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|p| analyze_path(p)).collect::<Vec<_>>()))
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap_or_default()).collect()
+    });
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    BatchReport { files }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_report_aggregates() {
+        let good = analyze_source(Path::new("good.rss"), "fn add(a i32, b i32) i32 {\n    a + b\n}\n");
+        let bad = analyze_source(Path::new("bad.rss"), "fn log(msg String) {\n    println(\"{}\", msg)\n}\n");
+        assert!(good.errors.is_empty());
+        assert!(!bad.errors.is_empty());
+
+        let report = BatchReport { files: vec![good, bad] };
+        assert_eq!(report.function_count(), 2);
+        assert_eq!(report.pure_count(), 1);
+        assert!((report.purity_ratio() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(report.violations_by_code().get("RSPL300").map(|v| v.1), Some(report.violation_count()));
+        assert!(report.to_json().contains("\"files_analyzed\": 2"));
+        let row = report.to_csv().lines().nth(1).unwrap().to_string();
+        assert!(row.starts_with("bad.rss,") && row.contains(",RSPL300,effect,"), "Bad CSV row: {}", row);
+    }
+}
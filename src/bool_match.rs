@@ -0,0 +1,186 @@
+//! Bool Matches
+//!
+//! A `match` on a `bool` uses the same arm syntax as any other match, on
+//! one line too:
+//!
+//! ```text
+//! label = match ok { true { "on" } false { "off" } }
+//! // → let label = match ok { true => "on", false => "off" };
+//! ```
+//!
+//! An arm of a bool match has `true`, `false`, `true | false`, `_` or a
+//! binding as its pattern, with an optional `if` guard. Such a match never
+//! gets `.as_str()` on its scrutinee, and Stage 1 checks that both truth
+//! values are covered by an arm without a guard (`RSPL064`, see
+//! [`crate::match_arms::find_missing_bool_arms`]).
+//!
+//! A one-line match whose patterns are all values (literals, constants
+//! declared in the output and `_`, see
+//! [`crate::constants::ConstRegistry::is_value_pattern`]) is lowered the
+//! same way: `match code { ERR_NOT_FOUND { 1 } _ { 0 } }`.
+
+use crate::constants::ConstRegistry;
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+use crate::match_arms::is_bool_match;
+
+/// A whole `match` written on one line with `pattern { body }` arms
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineMatch<'a> {
+    /// Byte offset of `match`
+    pub start: usize,
+    /// Byte offset just past the closing `}`
+    pub end: usize,
+    pub scrutinee: &'a str,
+    /// (pattern, body) of each arm; the body without its braces
+    pub arms: Vec<(&'a str, &'a str)>,
+}
+
+/// The first `match` of `line` if it opens and closes on that line and its
+/// arms are `pattern { body }`; `None` for `=>` arms
+pub fn find_inline_match(line: &str) -> Option<InlineMatch<'_>> {
+    let code = strip_inline_comment(line);
+    let start = match_keyword(&code)?;
+    let code = &line[..code.len()];
+
+    let mut depth = 0usize;
+    let mut body_open = None;
+    let mut arm_open = None;
+    let mut pattern_start = 0;
+    let mut arms = Vec::new();
+    for (offset, delimiter) in delimiter_positions(&code[start..], &['(', '[', '{', ')', ']', '}']) {
+        let pos = start + offset;
+        match delimiter {
+            '{' if depth == 0 && body_open.is_none() => {
+                body_open = Some(pos);
+                pattern_start = pos + 1;
+            }
+            '{' if depth == 1 && arm_open.is_none() => {
+                arm_open = Some(pos);
+            }
+            '}' if depth == 2 && arm_open.is_some() => {
+                let open = arm_open.take()?;
+                let pattern = code[pattern_start..open].trim().trim_start_matches(',').trim();
+                if pattern.is_empty() || pattern.contains("=>") {
+                    return None;
+                }
+                arms.push((pattern, code[open + 1..pos].trim()));
+                pattern_start = pos + 1;
+            }
+            '}' if depth == 1 => {
+                let body_open = body_open?;
+                if code[pattern_start..pos].trim().trim_matches(',').trim().is_empty() && !arms.is_empty() {
+                    return Some(InlineMatch {
+                        start,
+                        end: pos + 1,
+                        scrutinee: code[start + "match ".len()..body_open].trim(),
+                        arms,
+                    });
+                }
+                return None;
+            }
+            _ => {}
+        }
+        if matches!(delimiter, '(' | '[' | '{') {
+            depth += 1;
+        } else {
+            depth = depth.checked_sub(1)?;
+        }
+    }
+    None
+}
+
+/// Byte offset of the first `match ` keyword outside string literals
+fn match_keyword(code: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in code.char_indices() {
+        if c == '"' && prev != '\\' {
+            in_string = !in_string;
+        }
+        let at_word_start = !(prev.is_alphanumeric() || prev == '_');
+        if !in_string && at_word_start && code[i..].starts_with("match ") {
+            return Some(i);
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Lower every one-line bool match, and every one-line match on values,
+/// to `pattern => value` arms
+pub fn lower_inline_matches(lines: Vec<String>) -> Vec<String> {
+    let constants = ConstRegistry::from_source(&lines.join("\n"));
+    lines.into_iter().map(|line| lower_inline_match(&line, &constants).unwrap_or(line)).collect()
+}
+
+fn lower_inline_match(line: &str, constants: &ConstRegistry) -> Option<String> {
+    let found = find_inline_match(line)?;
+    let patterns: Vec<&str> = found.arms.iter().map(|(pattern, _)| *pattern).collect();
+    if !is_bool_match(&patterns) && !patterns.iter().all(|pattern| constants.is_value_pattern(pattern)) {
+        return None;
+    }
+    let arms: Vec<String> = found.arms.iter()
+        .map(|(pattern, body)| match *body {
+            "" => format!("{} => {{}}", pattern),
+            body if body.contains(';') => format!("{} => {{ {} }}", pattern, body),
+            body => format!("{} => {}", pattern, body),
+        })
+        .collect();
+    Some(format!(
+        "{}match {} {{ {} }}{}",
+        &line[..found.start], found.scrutinee, arms.join(", "), &line[found.end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_inline_match() {
+        let line = "let a = match ok && f(x) { true { \"}\" } false { g({ 1 }) } };";
+        let found = find_inline_match(line).unwrap();
+        assert_eq!(found.scrutinee, "ok && f(x)");
+        assert_eq!(found.arms, vec![("true", "\"}\""), ("false", "g({ 1 })")]);
+        assert_eq!(&line[found.end..], ";");
+
+        assert!(find_inline_match("let a = match ok { true => 1, false => 0 };").is_none());
+        assert!(find_inline_match("let a = match ok {").is_none());
+        assert!(find_inline_match("let s = \"match x { a { b } }\";").is_none());
+    }
+
+    #[test]
+    fn test_lower_inline_bool_matches() {
+        let lines = lower_inline_matches(vec![
+            "let a = match ok { true { \"on\" } false { \"off\" } };".to_string(),
+            "match ok { true if n > 0 { println!(\"a\"); } _ {} }".to_string(),
+            "let x = match o { Some(v) { v } None { 0 } };".to_string(),
+        ]);
+        assert_eq!(lines[0], "let a = match ok { true => \"on\", false => \"off\" };");
+        assert_eq!(lines[1], "match ok { true if n > 0 => { println!(\"a\"); }, _ => {} }");
+        assert_eq!(lines[2], "let x = match o { Some(v) { v } None { 0 } };");
+    }
+
+    #[test]
+    fn test_lower_inline_value_matches() {
+        let lines = lower_inline_matches(vec![
+            "const ERR_NOT_FOUND: i32 = 404;".to_string(),
+            "let r = match code { ERR_NOT_FOUND | 410 { 1 } 500..=599 { 2 } _ { 0 } };".to_string(),
+            "let c = match ch { 'a' { 1 } _ { 0 } };".to_string(),
+            "let p = match v { Point { x } { x } _ { 0 } };".to_string(),
+        ]);
+        assert_eq!(lines[1], "let r = match code { ERR_NOT_FOUND | 410 => 1, 500..=599 => 2, _ => 0 };");
+        assert_eq!(lines[2], "let c = match ch { 'a' => 1, _ => 0 };");
+        assert_eq!(lines[3], "let p = match v { Point { x } { x } _ { 0 } };");
+    }
+
+    #[test]
+    fn test_assigned_bool_match_keeps_arm_values() {
+        let rust = crate::parse_rusts("fn main() {\n    ok = true\n    d = match ok {\n        true {\n            log(\"a\")\n            \"a\"\n        }\n        false {\n            \"b\"\n        }\n    }\n    n = match ok { true { 1 } false { 0 } }\n}\n");
+        let lines: Vec<&str> = rust.lines().map(str::trim).collect();
+        let start = lines.iter().position(|l| *l == "let d = match ok {").unwrap();
+        assert_eq!(&lines[start..start + 9], ["let d = match ok {", "true => {", "log(\"a\");", "\"a\"", "},", "false => {", "\"b\"", "},", "};"], "{}", rust);
+        assert!(lines.contains(&"let n = match ok { true => 1, false => 0 };"), "{}", rust);
+    }
+}
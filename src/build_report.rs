@@ -0,0 +1,237 @@
+//! Build Report
+//!
+//! `--build-report` prints, after Stage 3, the size of the binary, how long
+//! rustc took and the generated functions with the most lines, so .rss
+//! constructs that lower to a lot of Rust stand out:
+//!
+//! ```text
+//! Build report
+//!   binary:    412 KiB (./wallet)
+//!   rustc:     1.84 s
+//!   generated: 236 lines of Rust from 120 lines of RustS+
+//!   largest generated functions:
+//!      88 lines  fn apply_all (wallet.rss:41)
+//!      31 lines  fn main (wallet.rss:97)
+//! ```
+//!
+//! `--stats <file>` writes the same data as JSON. Function sizes are
+//! counted on the generated Rust, from the signature to its closing brace;
+//! the .rss line is that of the first RustS+ function with the same name.
+//! When the binary came from the rustc cache, no rustc time is reported.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::function::{parse_function_line, FunctionParseResult};
+use crate::ident::is_valid_identifier;
+use crate::lowered_ir::json_str;
+
+/// Value of the `"format"` field of `--stats`
+pub const STATS_FORMAT: &str = "rustsp-stats";
+/// Version of the `--stats` document
+pub const STATS_VERSION: u64 = 1;
+/// How many of the largest functions are reported
+pub const DEFAULT_TOP_FUNCTIONS: usize = 5;
+
+/// A function of the generated Rust
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFn {
+    pub name: String,
+    /// 1-based line of the signature in the generated Rust
+    pub rust_line: usize,
+    /// Lines from the signature to the closing brace
+    pub lines: usize,
+    /// 1-based line of the .rss function it was lowered from
+    pub source_line: Option<usize>,
+}
+
+/// What `--build-report` and `--stats` show about one build
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildReport {
+    pub binary: String,
+    /// Size of the binary in bytes, if it could be read
+    pub binary_size: Option<u64>,
+    /// Wall time of rustc; `None` when the binary came from the cache
+    pub rustc_time: Option<Duration>,
+    pub rust_lines: usize,
+    pub source_lines: usize,
+    /// Largest generated functions, largest first
+    pub largest: Vec<GeneratedFn>,
+}
+
+impl BuildReport {
+    pub fn new(binary: &str, rust: &str, source: &str, rustc_time: Option<Duration>) -> Self {
+        BuildReport {
+            binary: binary.to_string(),
+            binary_size: std::fs::metadata(binary).ok().map(|m| m.len()),
+            rustc_time,
+            rust_lines: rust.lines().count(),
+            source_lines: source.lines().count(),
+            largest: largest_functions(rust, source, DEFAULT_TOP_FUNCTIONS),
+        }
+    }
+
+    /// Human-readable report; `file` names the .rss in function locations
+    pub fn format(&self, file: &str) -> String {
+        let mut out = String::from("Build report\n");
+        match self.binary_size {
+            Some(size) => out.push_str(&format!("  binary:    {} ({})\n", format_size(size), self.binary)),
+            None => out.push_str(&format!("  binary:    size unknown ({})\n", self.binary)),
+        }
+        match self.rustc_time {
+            Some(time) => out.push_str(&format!("  rustc:     {:.2} s\n", time.as_secs_f64())),
+            None => out.push_str("  rustc:     skipped (cached)\n"),
+        }
+        out.push_str(&format!("  generated: {} lines of Rust from {} lines of RustS+\n", self.rust_lines, self.source_lines));
+        if !self.largest.is_empty() {
+            out.push_str("  largest generated functions:\n");
+            for f in &self.largest {
+                let origin = match f.source_line {
+                    Some(line) => format!(" ({}:{})", file, line),
+                    None => String::new(),
+                };
+                out.push_str(&format!("    {:>4} lines  fn {}{}\n", f.lines, f.name, origin));
+            }
+        }
+        out
+    }
+
+    /// The `--stats` document
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let largest: Vec<String> = self.largest.iter()
+            .map(|f| format!(
+                "    {{\"name\": {}, \"rust_line\": {}, \"lines\": {}, \"source_line\": {}}}",
+                json_str(&f.name), f.rust_line, f.lines, optional(f.source_line.map(|l| l.to_string()))
+            ))
+            .collect();
+        format!(
+            "{{\n  \"format\": \"{}\",\n  \"version\": {},\n  \"binary\": {},\n  \"binary_size\": {},\n  \"rustc_ms\": {},\n  \"rust_lines\": {},\n  \"source_lines\": {},\n  \"largest_functions\": [\n{}\n  ]\n}}\n",
+            STATS_FORMAT, STATS_VERSION, json_str(&self.binary),
+            optional(self.binary_size.map(|s| s.to_string())),
+            optional(self.rustc_time.map(|t| t.as_millis().to_string())),
+            self.rust_lines, self.source_lines, largest.join(",\n")
+        )
+    }
+}
+
+/// The `top` generated functions with the most lines, largest first
+pub fn largest_functions(rust: &str, source: &str, top: usize) -> Vec<GeneratedFn> {
+    let mut source_lines: HashMap<String, usize> = HashMap::new();
+    for (idx, line) in source.lines().enumerate() {
+        if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(line) {
+            source_lines.entry(sig.name).or_insert(idx + 1);
+        }
+    }
+
+    let lines: Vec<&str> = rust.lines().collect();
+    let mut functions: Vec<GeneratedFn> = lines.iter().enumerate()
+        .filter_map(|(idx, line)| {
+            let name = fn_name(line)?;
+            let lines = function_length(&lines[idx..])?;
+            Some(GeneratedFn { source_line: source_lines.get(&name).copied(), name, rust_line: idx + 1, lines })
+        })
+        .collect();
+    functions.sort_by(|a, b| b.lines.cmp(&a.lines).then(a.rust_line.cmp(&b.rust_line)));
+    functions.truncate(top);
+    functions
+}
+
+/// Name of the function defined on a line of Rust
+fn fn_name(line: &str) -> Option<String> {
+    let mut code = line.trim();
+    while code.starts_with("#[") {
+        code = code[code.find("] ")? + 1..].trim_start();
+    }
+    for qualifier in ["pub(crate) ", "pub ", "const ", "async ", "unsafe "] {
+        code = code.strip_prefix(qualifier).unwrap_or(code);
+    }
+    let rest = code.strip_prefix("fn ")?;
+    let name = &rest[..rest.find(['<', '('])?];
+    is_valid_identifier(name).then(|| name.to_string())
+}
+
+/// Lines from a signature to the brace that closes its body; `None` for a
+/// declaration without a body
+fn function_length(lines: &[&str]) -> Option<usize> {
+    let mut depth = 0i32;
+    let mut opened = false;
+    for (i, line) in lines.iter().enumerate() {
+        let mut in_string = false;
+        let mut escaped = false;
+        for c in line.chars() {
+            if in_string {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => in_string = true,
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                }
+                '}' => depth -= 1,
+                ';' if !opened => return None,
+                _ => {}
+            }
+            if opened && depth == 0 {
+                return Some(i + 1);
+            }
+        }
+    }
+    None
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        b if b >= 1024 * 1024 => format!("{:.1} MiB", b as f64 / (1024.0 * 1024.0)),
+        b if b >= 1024 => format!("{} KiB", b / 1024),
+        b => format!("{} B", b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "fn small() i32 {\n    1\n}\n\nfn big(xs Vec[i32]) i32 {\n    mut t = 0\n    for x in xs {\n        t += x\n    }\n    t\n}\n";
+
+    #[test]
+    fn test_largest_functions() {
+        let rust = "trait T {\nfn decl(&self);\n}\nfn small() -> i32 {\n1\n}\n\nfn big(xs: Vec<i32>) -> i32 {\nlet mut t = 0;\nfor x in xs {\nt += x;\n}\nlet s = \"}\";\nt\n}\n";
+        let largest = largest_functions(rust, SRC, 5);
+        assert_eq!(largest.len(), 2);
+        assert_eq!(largest[0], GeneratedFn { name: "big".to_string(), rust_line: 8, lines: 8, source_line: Some(5) });
+        assert_eq!(largest[1].name, "small");
+        assert_eq!(largest_functions(rust, SRC, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_report_and_json() {
+        let rust = crate::parse_rusts(SRC);
+        let report = BuildReport {
+            binary: "./big".to_string(),
+            binary_size: Some(2048),
+            rustc_time: Some(Duration::from_millis(1500)),
+            rust_lines: rust.lines().count(),
+            source_lines: 11,
+            largest: largest_functions(&rust, SRC, 5),
+        };
+        let text = report.format("big.rss");
+        assert!(text.contains("binary:    2 KiB (./big)"), "{}", text);
+        assert!(text.contains("rustc:     1.50 s"));
+        assert!(text.contains("fn big (big.rss:5)"), "{}", text);
+
+        let doc = crate::lowered_ir::parse_json(&report.to_json()).unwrap();
+        assert_eq!(doc.get("format").and_then(|f| f.as_str()), Some(STATS_FORMAT));
+        assert_eq!(doc.get("rustc_ms").and_then(|m| m.as_u64()), Some(1500));
+        let cached = BuildReport { rustc_time: None, ..report };
+        assert!(cached.to_json().contains("\"rustc_ms\": null"));
+        assert!(cached.format("big.rss").contains("skipped (cached)"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Call Graph Export
+//!
+//! `--emit-callgraph dot|json` writes the calls between the functions of a
+//! file, each function annotated with its effects, so effect flow through a
+//! program can be looked at rather than read off `--analyze`:
+//!
+//! ```text
+//! rustsp main.rss --emit-callgraph dot | dot -Tsvg > calls.svg
+//! ```
+//!
+//! Effects are those `rustsp effects-diff` compares (declared and detected,
+//! without `read(..)` and internal calls). A function whose detected
+//! effects are not declared is marked, as Stage 1 would report it. Calls to
+//! functions outside the file are left out, and so are calls of a function
+//! to itself: the analysis cannot tell recursion from the signature line.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::anti_fail_logic::{Effect, FunctionInfo, DEFAULT_TEST_EFFECTS};
+use crate::effects_diff::FunctionEffects;
+use crate::lowered_ir::json_str;
+
+/// Value of the `"format"` field of the JSON export
+pub const CALLGRAPH_FORMAT: &str = "rustsp-callgraph";
+/// Version of the JSON export
+pub const CALLGRAPH_VERSION: u64 = 1;
+
+/// Output format of `--emit-callgraph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallGraphFormat {
+    /// Graphviz `digraph`
+    Dot,
+    /// Versioned JSON document
+    Json,
+}
+
+impl CallGraphFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dot" => Some(CallGraphFormat::Dot),
+            "json" => Some(CallGraphFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// One function of the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallNode {
+    pub name: String,
+    /// 1-based line of the signature
+    pub line: usize,
+    pub is_public: bool,
+    pub is_pure: bool,
+    pub effects: BTreeSet<String>,
+    /// Detected effects the signature does not declare
+    pub undeclared: BTreeSet<String>,
+    /// Functions of the same file this one calls, by name
+    pub calls: BTreeSet<String>,
+}
+
+/// Functions of one file and the calls between them
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallGraph {
+    /// Ordered by line
+    pub nodes: Vec<CallNode>,
+}
+
+impl CallGraph {
+    /// Build the graph from the result of
+    /// [`analyze_functions`](crate::anti_fail_logic::analyze_functions)
+    pub fn build(functions: &HashMap<String, FunctionInfo>) -> Self {
+        let mut nodes: Vec<CallNode> = functions.values()
+            .map(|info| {
+                let effects = FunctionEffects::from_info(info);
+                CallNode {
+                    name: info.name.clone(),
+                    line: info.line_number,
+                    is_public: info.is_public,
+                    is_pure: effects.is_pure,
+                    effects: effects.effects,
+                    undeclared: undeclared_effects(info),
+                    calls: info.calls.iter()
+                        .filter(|callee| **callee != info.name && functions.contains_key(*callee))
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.name.cmp(&b.name)));
+        CallGraph { nodes }
+    }
+
+    /// Graphviz rendering: pure functions green, effectful ones orange and
+    /// those with undeclared effects red
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph rustsp {\n    rankdir=LR;\n    node [shape=box, style=rounded];\n");
+        for node in &self.nodes {
+            let mut label = format!("{}\\nline {}", dot_escape(&node.name), node.line);
+            if node.is_pure {
+                label.push_str("\\npure");
+            } else {
+                label.push_str(&format!("\\neffects({})", join(&node.effects)));
+            }
+            if !node.undeclared.is_empty() {
+                label.push_str(&format!("\\nundeclared: {}", join(&node.undeclared)));
+            }
+            let color = if !node.undeclared.is_empty() {
+                "red"
+            } else if node.is_pure {
+                "darkgreen"
+            } else {
+                "darkorange"
+            };
+            out.push_str(&format!("    \"{}\" [label=\"{}\", color={}];\n", dot_escape(&node.name), label, color));
+        }
+        for node in &self.nodes {
+            for callee in &node.calls {
+                out.push_str(&format!("    \"{}\" -> \"{}\";\n", dot_escape(&node.name), dot_escape(callee)));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Versioned JSON rendering
+    pub fn to_json(&self) -> String {
+        let strings = |set: &BTreeSet<String>| set.iter().map(|s| json_str(s)).collect::<Vec<_>>().join(", ");
+        let functions: Vec<String> = self.nodes.iter()
+            .map(|node| format!(
+                "    {{\"name\": {}, \"line\": {}, \"public\": {}, \"pure\": {}, \"effects\": [{}], \"undeclared\": [{}], \"calls\": [{}]}}",
+                json_str(&node.name), node.line, node.is_public, node.is_pure,
+                strings(&node.effects), strings(&node.undeclared), strings(&node.calls)
+            ))
+            .collect();
+        format!(
+            "{{\n  \"format\": \"{}\",\n  \"version\": {},\n  \"functions\": [\n{}\n  ]\n}}\n",
+            CALLGRAPH_FORMAT, CALLGRAPH_VERSION, functions.join(",\n")
+        )
+    }
+
+    pub fn render(&self, format: CallGraphFormat) -> String {
+        match format {
+            CallGraphFormat::Dot => self.to_dot(),
+            CallGraphFormat::Json => self.to_json(),
+        }
+    }
+}
+
+/// Undeclared effects as the Stage 1 effect check counts them: `main` may
+/// perform io, alloc, panic, concurrent and resource implicitly, `test` blocks the
+/// default test grant, reads are implicit and only writes to parameters
+/// are effects
+pub(crate) fn undeclared_effects(info: &FunctionInfo) -> BTreeSet<String> {
+    info.detected_effects.effects.iter()
+        .filter(|e| !(info.is_main() && matches!(e, Effect::Io | Effect::Panic | Effect::Alloc | Effect::Concurrent | Effect::Resource(_))))
+        .filter(|e| !(info.is_test() && DEFAULT_TEST_EFFECTS.contains(e)))
+        .filter(|e| !matches!(e, Effect::Read(_) | Effect::Calls(_)))
+        .filter(|e| !info.declared_effects.covers(e))
+        .filter(|e| e.target_param().is_none_or(|param| info.has_parameter(param)))
+        .map(|e| info.reported_effect(e).display())
+        .collect()
+}
+
+fn join(set: &BTreeSet<String>) -> String {
+    set.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anti_fail_logic::analyze_functions;
+
+    const SRC: &str = "fn add(a i32, b i32) i32 {\n    a + b\n}\n\nfn show(x i32) effects(io) {\n    println(\"{}\", x)\n}\n\nfn sneaky(x i32) {\n    show(x)\n    println(\"{}\", add(x, 1))\n}\n";
+
+    #[test]
+    fn test_nodes_and_edges() {
+        let graph = CallGraph::build(&analyze_functions(SRC, "test.rss"));
+        let names: Vec<&str> = graph.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, vec!["add", "show", "sneaky"]);
+        assert!(graph.nodes[0].is_pure && graph.nodes[0].calls.is_empty());
+        assert!(graph.nodes[1].undeclared.is_empty());
+        let sneaky = &graph.nodes[2];
+        assert_eq!(sneaky.calls.iter().map(String::as_str).collect::<Vec<_>>(), vec!["add", "show"]);
+        assert!(sneaky.undeclared.contains("io"));
+    }
+
+    #[test]
+    fn test_dot_output() {
+        let dot = CallGraph::build(&analyze_functions(SRC, "test.rss")).to_dot();
+        assert!(dot.starts_with("digraph rustsp {"));
+        assert!(dot.contains("\"add\" [label=\"add\\nline 1\\npure\", color=darkgreen];"));
+        assert!(dot.contains("\"show\" [label=\"show\\nline 5\\neffects(io)\", color=darkorange];"));
+        assert!(dot.contains("\"sneaky\" -> \"show\";"));
+        assert!(dot.contains("color=red"));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let json = CallGraph::build(&analyze_functions(SRC, "test.rss")).to_json();
+        let doc = crate::lowered_ir::parse_json(&json).unwrap();
+        assert_eq!(doc.get("format").and_then(|f| f.as_str()), Some(CALLGRAPH_FORMAT));
+        let functions = doc.get("functions").and_then(|f| f.as_array()).unwrap();
+        assert_eq!(functions.len(), 3);
+        assert!(json.contains("\"name\": \"sneaky\", \"line\": 9, \"public\": false, \"pure\": false, \"effects\": [\"io\"], \"undeclared\": [\"io\"], \"calls\": [\"add\", \"show\"]"));
+    }
+}
@@ -0,0 +1,302 @@
+//! Cargo Workspace Output for Multi-File Programs
+//!
+//! `rustsp main.rss --emit-cargo-workspace gen` writes every module file of
+//! the program (see [`crate::modules`]) as a crate of its own, so Cargo
+//! rebuilds only the crates whose module changed instead of one big `.rs`:
+//!
+//! ```text
+//! main.rss              gen/Cargo.toml           [workspace] members = [..]
+//! mod util              gen/main/src/main.rs     use main_util as util;
+//! util.rss              gen/main_util/src/lib.rs pub use main_util_shapes as shapes;
+//! pub mod shapes        gen/main_util_shapes/src/lib.rs
+//! util/shapes.rss
+//! ```
+//!
+//! Each `mod name { .. }` spliced from a file becomes a path dependency on
+//! its crate and a `use crate_name as name;` with the visibility of the
+//! `mod`, so `util::shapes::area` still resolves in the parent. Crate
+//! boundaries now lie between the modules, so `pub(crate)` (and
+//! `pub(super)` at the top of a module) widens to `pub`; `crate::` and a
+//! `super::` that leaves the module file cannot be expressed and are errors.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+use crate::modules::{parse_mod_decl, ModuleMap};
+
+/// Version requirement of the `thiserror` dependency
+const THISERROR_VERSION: &str = "2";
+
+/// One crate of the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceCrate {
+    /// Package and crate name: `main_util_shapes`
+    pub name: String,
+    /// `util::shapes`; empty for the input file
+    pub module: String,
+    /// Generated Rust of the module, file modules replaced by `use`
+    pub source: String,
+    /// Crates of the module files this module declares
+    pub deps: Vec<String>,
+    /// A top-level `fn main` makes the input file's crate a binary
+    pub is_bin: bool,
+}
+
+impl WorkspaceCrate {
+    /// Crate root, relative to the crate directory
+    pub fn root_file(&self) -> &'static str {
+        if self.is_bin { "src/main.rs" } else { "src/lib.rs" }
+    }
+
+    /// `Cargo.toml` of the crate; dependencies are sibling directories,
+    /// and `thiserror` for the error enums of `!T` functions (see
+    /// [`crate::fallible`])
+    pub fn manifest(&self, edition: &str) -> String {
+        let mut out = format!(
+            "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"{}\"\n\n[dependencies]\n",
+            self.name, edition
+        );
+        for dep in &self.deps {
+            out.push_str(&format!("{} = {{ path = \"../{}\" }}\n", dep, dep));
+        }
+        if self.source.contains("thiserror::Error") {
+            out.push_str(&format!("thiserror = \"{}\"\n", THISERROR_VERSION));
+        }
+        out
+    }
+}
+
+/// Crate name of `module` in the workspace of input file `root`
+fn crate_name(root: &str, module: &str) -> String {
+    let mut name: String = root
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "rss_");
+    }
+    for part in module.split("::").filter(|part| !part.is_empty()) {
+        name.push('_');
+        name.push_str(part);
+    }
+    name
+}
+
+/// `depth` after the braces of `line`
+fn track_depth(depth: usize, line: &str) -> usize {
+    delimiter_positions(&strip_inline_comment(line), &['{', '}'])
+        .iter()
+        .fold(depth, |depth, &(_, c)| if c == '{' { depth + 1 } else { depth.saturating_sub(1) })
+}
+
+/// Split `rust_code`, lowered from the program of `map`, into one crate per
+/// module file. `root` names the input file's crate (usually its stem).
+pub fn split_crates(rust_code: &str, map: &ModuleMap, root: &str) -> Result<Vec<WorkspaceCrate>, String> {
+    let mut crates = Vec::new();
+    extract(rust_code, "", root, map, &mut crates)?;
+    for (i, krate) in crates.iter().enumerate() {
+        if let Some(other) = crates[..i].iter().find(|other| other.name == krate.name) {
+            return Err(format!(
+                "modules `{}` and `{}` would both be crate `{}`; rename one of them",
+                other.module, krate.module, krate.name
+            ));
+        }
+    }
+    Ok(crates)
+}
+
+/// Add the crate of `module`, whose generated Rust is `code`, and the
+/// crates of its module files to `crates`
+fn extract(code: &str, module: &str, root: &str, map: &ModuleMap, crates: &mut Vec<WorkspaceCrate>) -> Result<(), String> {
+    let index = crates.len();
+    crates.push(WorkspaceCrate {
+        name: crate_name(root, module),
+        module: module.to_string(),
+        source: String::new(),
+        deps: Vec::new(),
+        is_bin: false,
+    });
+    let mut source = String::new();
+    let mut depth = 0usize;
+    let mut lines = code.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let file_module = (depth == 0)
+            .then(|| trimmed.strip_suffix('{').and_then(|head| parse_mod_decl(head.trim_end())))
+            .flatten()
+            .map(|decl| {
+                let path = if module.is_empty() { decl.name.to_string() } else { format!("{}::{}", module, decl.name) };
+                (decl, path)
+            })
+            .filter(|(_, path)| map.files.iter().any(|file| &file.module == path));
+        let Some((decl, path)) = file_module else {
+            if depth == 0 && module.is_empty() && trimmed.starts_with("fn main(") {
+                crates[index].is_bin = true;
+            }
+            depth = track_depth(depth, line);
+            source.push_str(line);
+            source.push('\n');
+            continue;
+        };
+        let mut body = String::new();
+        let mut inner = 1;
+        for line in lines.by_ref() {
+            inner = track_depth(inner, line);
+            if inner == 0 {
+                break;
+            }
+            body.push_str(line);
+            body.push('\n');
+        }
+        let name = crate_name(root, &path);
+        let visibility = decl.head.strip_suffix(decl.name).unwrap_or("").trim_end().strip_suffix("mod").unwrap_or("");
+        let indent = &line[..line.len() - line.trim_start().len()];
+        source.push_str(&format!("{}{}use {} as {};\n", indent, visibility, name, decl.name));
+        crates[index].deps.push(name);
+        extract(&body, &path, root, map, crates)?;
+    }
+    if !module.is_empty() {
+        let file = map.files.iter().find(|file| file.module == module).map(|file| file.path.display().to_string());
+        source = detach(&source, module, file.as_deref().unwrap_or(module))?;
+    }
+    crates[index].source = source;
+    Ok(())
+}
+
+/// Paths of a module crate no longer reach the rest of the program:
+/// reject `crate::` and `super::` out of the file, widen `pub(crate)`
+fn detach(source: &str, module: &str, file: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut depth = 0usize;
+    // Depth outside each inline `mod name {` the line is in
+    let mut inline_mods: Vec<usize> = Vec::new();
+    for line in source.lines() {
+        let code = strip_inline_comment(line);
+        let trimmed = code.trim();
+        for (keyword, count) in path_roots(&code) {
+            if keyword == "crate" || count > inline_mods.len() {
+                return Err(format!(
+                    "{}: module `{}` is a crate of its own in the Cargo workspace and cannot use `{}::`: `{}`",
+                    file, module, keyword, trimmed
+                ));
+            }
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let item = line.trim_start();
+        let widened = item.strip_prefix("pub(crate) ")
+            .or_else(|| item.strip_prefix("pub(super) ").filter(|_| inline_mods.is_empty()));
+        match widened {
+            Some(rest) => out.push_str(&format!("{}pub {}", indent, rest)),
+            None => out.push_str(line),
+        }
+        out.push('\n');
+        if trimmed.strip_suffix('{').and_then(|head| parse_mod_decl(head.trim_end())).is_some() {
+            inline_mods.push(depth);
+        }
+        depth = track_depth(depth, line);
+        while inline_mods.last().is_some_and(|&outside| depth <= outside) {
+            inline_mods.pop();
+        }
+    }
+    Ok(out)
+}
+
+/// `crate::` and runs of `super::` outside strings: (`"crate"`, 1) or
+/// (`"super"`, number of `super::` in a row)
+fn path_roots(code: &str) -> Vec<(&'static str, usize)> {
+    let outside: Vec<usize> = delimiter_positions(code, &['c', 's']).into_iter().map(|(pos, _)| pos).collect();
+    let starts_path = |pos: usize| {
+        outside.contains(&pos)
+            && !code[..pos].ends_with("::")
+            && !code[..pos].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+    };
+    let crates = code.match_indices("crate::").filter(|&(pos, _)| starts_path(pos)).map(|_| ("crate", 1));
+    let supers = code.match_indices("super::").filter(|&(pos, _)| starts_path(pos)).map(|(pos, _)| {
+        ("super", (0..).take_while(|n| code[pos + n * "super::".len()..].starts_with("super::")).count())
+    });
+    crates.chain(supers).collect()
+}
+
+/// Root `Cargo.toml` listing every crate
+pub fn render_workspace_manifest(crates: &[WorkspaceCrate]) -> String {
+    let mut out = String::from("[workspace]\nmembers = [\n");
+    for krate in crates {
+        out.push_str(&format!("    \"{}\",\n", krate.name));
+    }
+    out.push_str("]\nresolver = \"2\"\n");
+    out
+}
+
+/// Write the workspace of `crates` into `dir`: the root `Cargo.toml` and
+/// `<crate>/Cargo.toml` with the crate root of each. Returns the path of
+/// the workspace manifest.
+pub fn write_cargo_workspace(dir: &Path, crates: &[WorkspaceCrate], edition: &str) -> io::Result<PathBuf> {
+    for krate in crates {
+        let crate_dir = dir.join(&krate.name);
+        let root_file = crate_dir.join(krate.root_file());
+        fs::create_dir_all(root_file.parent().unwrap_or(&crate_dir))?;
+        fs::write(crate_dir.join("Cargo.toml"), krate.manifest(edition))?;
+        fs::write(root_file, &krate.source)?;
+    }
+    let manifest = dir.join("Cargo.toml");
+    fs::write(&manifest, render_workspace_manifest(crates))?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::Program;
+
+    /// Module map of `main.rss` with `util.rss` and `util/shapes.rss`
+    fn program(util: &str) -> ModuleMap {
+        let util = util.to_string();
+        let read = move |path: &Path| match path.to_str() {
+            Some("util.rss") => Ok(util.clone()),
+            Some("util/shapes.rss") => Ok("pub fn area(w i32, h i32) i32 {\n    w * h\n}\n".to_string()),
+            _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+        };
+        Program::load_with(Path::new("main.rss"), "mod util\n\nfn main() {\n}\n", &read).unwrap().map
+    }
+
+    const LOWERED: &str = "mod util {\npub mod shapes {\npub fn area(w: i32, h: i32) -> i32 {\nw * h\n}\n}\n\npub(crate) fn log(x: i32) {\nprintln!(\"{}\", x);\n}\n}\n\nfn main() {\nutil::log(util::shapes::area(2, 3));\n}\n";
+
+    #[test]
+    fn test_split_crates_per_module_file() {
+        let crates = split_crates(LOWERED, &program("pub mod shapes\n"), "Main").unwrap();
+        let names: Vec<&str> = crates.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["main", "main_util", "main_util_shapes"]);
+
+        let root = &crates[0];
+        assert!(root.is_bin);
+        assert_eq!(root.root_file(), "src/main.rs");
+        assert_eq!(root.source, "use main_util as util;\n\nfn main() {\nutil::log(util::shapes::area(2, 3));\n}\n");
+        assert_eq!(root.deps, ["main_util"]);
+
+        let util = &crates[1];
+        assert!(!util.is_bin);
+        assert_eq!(util.source, "pub use main_util_shapes as shapes;\n\npub fn log(x: i32) {\nprintln!(\"{}\", x);\n}\n");
+        assert!(util.manifest("2021").ends_with("[dependencies]\nmain_util_shapes = { path = \"../main_util_shapes\" }\n"));
+        assert_eq!(crates[2].source, "pub fn area(w: i32, h: i32) -> i32 {\nw * h\n}\n");
+
+        assert_eq!(render_workspace_manifest(&crates), "[workspace]\nmembers = [\n    \"main\",\n    \"main_util\",\n    \"main_util_shapes\",\n]\nresolver = \"2\"\n");
+    }
+
+    #[test]
+    fn test_paths_out_of_a_module_crate() {
+        let map = program("pub mod shapes\n");
+        let uses_crate = LOWERED.replace("println!(\"{}\", x);", "crate::main();");
+        let error = split_crates(&uses_crate, &map, "main").unwrap_err();
+        assert_eq!(error, "util.rss: module `util` is a crate of its own in the Cargo workspace and cannot use `crate::`: `crate::main();`");
+
+        let leaves_file = LOWERED.replace("w * h", "super::log(w) * h");
+        assert!(split_crates(&leaves_file, &map, "main").unwrap_err().contains("module `util::shapes`"));
+
+        // Inside an inline `mod`, `super::` stays in the file; strings are not paths
+        let inline = LOWERED.replace("w * h\n}\n", "w * h\n}\nmod tests {\nfn t() {\nsuper::area(1, \"crate::\".len() as i32);\n}\n}\n");
+        assert!(split_crates(&inline, &map, "main").is_ok());
+    }
+}
@@ -0,0 +1,449 @@
+//! Control-Flow Graphs for HIR Function Bodies
+//!
+//! [`ControlFlowGraph::build`] turns a HIR block into a graph of nodes:
+//! one per statement, one head per loop, join nodes after branches and
+//! loops, plus a single function exit.
+//!
+//! ```text
+//! entry → stmt → loop head ⇄ body …
+//!                    │ (while/for: condition fails)   ↑ continue
+//!                    ↓                                │
+//!                  after ← break            return → exit
+//! ```
+//!
+//! Edges:
+//! - `if` / `match` branch from the condition or scrutinee and meet again
+//! - `while` and `for` heads also lead to their `after` node; a `loop` head
+//!   does not, so a `loop` is only left through `break`
+//! - `break` goes to the innermost loop's `after`, `continue` to its head
+//! - `return` and calls that never return (`exit`, `panic`, ...) go to the
+//!   exit node
+//! - code after a jump starts a node without predecessors
+//!
+//! Closure bodies are separate functions: a `return` inside one does not
+//! leave the enclosing function, so they are not descended into.
+
+use std::collections::HashSet;
+
+use crate::ast::Span;
+use crate::hir::{HirBlock, HirCallTarget, HirExpr, HirFnDef, HirStmt, Spanned};
+
+/// Index of a node in [`ControlFlowGraph::nodes`]
+pub type NodeId = usize;
+
+/// Calls that never return
+const DIVERGING_CALLS: &[&str] = &["exit", "panic", "unreachable", "todo", "unimplemented"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Entry,
+    Exit,
+    Stmt(Span),
+    LoopHead(Span),
+    /// Branches meeting again, the code after a loop, or code after a jump
+    Join,
+}
+
+#[derive(Debug, Clone)]
+pub struct CfgNode {
+    pub kind: NodeKind,
+    pub succs: Vec<NodeId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+    Loop,
+    While,
+    For,
+}
+
+/// A loop and the nodes control reaches when it starts and when it ends
+#[derive(Debug, Clone)]
+pub struct LoopInfo {
+    pub kind: LoopKind,
+    pub span: Span,
+    pub head: NodeId,
+    pub after: NodeId,
+}
+
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub nodes: Vec<CfgNode>,
+    pub entry: NodeId,
+    pub exit: NodeId,
+    pub loops: Vec<LoopInfo>,
+}
+
+impl ControlFlowGraph {
+    pub fn from_fn(func: &HirFnDef) -> Self {
+        Self::build(&func.body)
+    }
+
+    /// Graph of a function body
+    pub fn build(body: &Spanned<HirBlock>) -> Self {
+        let mut builder = Builder {
+            cfg: ControlFlowGraph { nodes: Vec::new(), entry: 0, exit: 1, loops: Vec::new() },
+            loop_stack: Vec::new(),
+        };
+        builder.node(NodeKind::Entry);
+        builder.node(NodeKind::Exit);
+        let end = builder.block(body, 0);
+        builder.edge(end, 1);
+        builder.cfg
+    }
+
+    /// Nodes reachable from `from`, including itself
+    pub fn reachable_from(&self, from: NodeId) -> HashSet<NodeId> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(&self.nodes[id].succs);
+            }
+        }
+        seen
+    }
+
+    /// Can control leave this loop, through its `after` node or by leaving
+    /// the function?
+    pub fn loop_exits(&self, info: &LoopInfo) -> bool {
+        let reachable = self.reachable_from(info.head);
+        reachable.contains(&info.after) || reachable.contains(&self.exit)
+    }
+
+    /// Every `loop` that control can never leave (RSPL003)
+    pub fn loops_without_exit(&self) -> Vec<&LoopInfo> {
+        self.loops.iter()
+            .filter(|l| l.kind == LoopKind::Loop && !self.loop_exits(l))
+            .collect()
+    }
+}
+
+struct Builder {
+    cfg: ControlFlowGraph,
+    /// (head, after) of the loops around the current node
+    loop_stack: Vec<(NodeId, NodeId)>,
+}
+
+impl Builder {
+    fn node(&mut self, kind: NodeKind) -> NodeId {
+        self.cfg.nodes.push(CfgNode { kind, succs: Vec::new() });
+        self.cfg.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: NodeId, to: NodeId) {
+        if !self.cfg.nodes[from].succs.contains(&to) {
+            self.cfg.nodes[from].succs.push(to);
+        }
+    }
+
+    /// Jump from `from` to `to`; the code that follows is unreachable
+    fn jump(&mut self, from: NodeId, to: NodeId) -> NodeId {
+        self.edge(from, to);
+        self.node(NodeKind::Join)
+    }
+
+    fn block(&mut self, block: &Spanned<HirBlock>, mut cur: NodeId) -> NodeId {
+        for stmt in &block.node.stmts {
+            cur = self.stmt(stmt, cur);
+        }
+        match &block.node.expr {
+            Some(expr) => self.expr(expr, cur),
+            None => cur,
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Spanned<HirStmt>, cur: NodeId) -> NodeId {
+        match &stmt.node {
+            HirStmt::While { condition, body } => {
+                let head = self.loop_head(stmt.span, cur);
+                let after = self.node(NodeKind::Join);
+                let cond = self.expr(condition, head);
+                self.edge(cond, after);
+                self.loop_body(LoopKind::While, stmt.span, head, after, body, cond)
+            }
+            HirStmt::For { iter, body, .. } => {
+                let iter_end = self.expr(iter, cur);
+                let head = self.loop_head(stmt.span, iter_end);
+                let after = self.node(NodeKind::Join);
+                self.edge(head, after);
+                self.loop_body(LoopKind::For, stmt.span, head, after, body, head)
+            }
+            HirStmt::Loop { body } => {
+                let head = self.loop_head(stmt.span, cur);
+                let after = self.node(NodeKind::Join);
+                self.loop_body(LoopKind::Loop, stmt.span, head, after, body, head)
+            }
+            HirStmt::Let { init, .. } => {
+                let node = self.stmt_node(stmt.span, cur);
+                match init {
+                    Some(init) => self.expr(init, node),
+                    None => node,
+                }
+            }
+            HirStmt::Expr(expr) => {
+                let node = self.stmt_node(stmt.span, cur);
+                self.expr(expr, node)
+            }
+            HirStmt::Assign { target, value } => {
+                let node = self.stmt_node(stmt.span, cur);
+                let value_end = self.expr(value, node);
+                self.expr(target, value_end)
+            }
+        }
+    }
+
+    fn stmt_node(&mut self, span: Span, cur: NodeId) -> NodeId {
+        let node = self.node(NodeKind::Stmt(span));
+        self.edge(cur, node);
+        node
+    }
+
+    fn loop_head(&mut self, span: Span, cur: NodeId) -> NodeId {
+        let head = self.node(NodeKind::LoopHead(span));
+        self.edge(cur, head);
+        head
+    }
+
+    /// Body from `start` back to `head`; returns the node after the loop
+    fn loop_body(
+        &mut self,
+        kind: LoopKind,
+        span: Span,
+        head: NodeId,
+        after: NodeId,
+        body: &Spanned<HirBlock>,
+        start: NodeId,
+    ) -> NodeId {
+        self.loop_stack.push((head, after));
+        let end = self.block(body, start);
+        self.loop_stack.pop();
+        self.edge(end, head);
+        self.cfg.loops.push(LoopInfo { kind, span, head, after });
+        after
+    }
+
+    fn exprs(&mut self, exprs: &[Spanned<HirExpr>], mut cur: NodeId) -> NodeId {
+        for expr in exprs {
+            cur = self.expr(expr, cur);
+        }
+        cur
+    }
+
+    /// Add the control flow inside `expr`, starting at `cur`; returns the
+    /// node reached once it is evaluated
+    fn expr(&mut self, expr: &Spanned<HirExpr>, cur: NodeId) -> NodeId {
+        match &expr.node {
+            HirExpr::Literal(_) | HirExpr::Var(_) | HirExpr::FnRef(_) | HirExpr::Closure { .. } => cur,
+            HirExpr::Field { base, .. } | HirExpr::Deref(base) => self.expr(base, cur),
+            HirExpr::Unary { operand, .. } => self.expr(operand, cur),
+            HirExpr::Ref { expr: inner, .. } => self.expr(inner, cur),
+            HirExpr::Index { base, index } => {
+                let base_end = self.expr(base, cur);
+                self.expr(index, base_end)
+            }
+            HirExpr::Binary { left, right, .. } => {
+                let left_end = self.expr(left, cur);
+                self.expr(right, left_end)
+            }
+            HirExpr::Range { start, end, .. } => {
+                let start_end = match start {
+                    Some(start) => self.expr(start, cur),
+                    None => cur,
+                };
+                match end {
+                    Some(end) => self.expr(end, start_end),
+                    None => start_end,
+                }
+            }
+            HirExpr::Array(items) | HirExpr::Tuple(items) => self.exprs(items, cur),
+            HirExpr::Struct { fields, .. } => {
+                fields.iter().fold(cur, |cur, (_, value)| self.expr(value, cur))
+            }
+            HirExpr::Call { target, args } => {
+                let cur = match target {
+                    HirCallTarget::Method { receiver, .. } => self.expr(receiver, cur),
+                    HirCallTarget::Function(_) => cur,
+                };
+                let cur = self.exprs(args, cur);
+                match target {
+                    HirCallTarget::Function(path) if is_diverging_call(path) => self.jump(cur, self.cfg.exit),
+                    _ => cur,
+                }
+            }
+            HirExpr::If { condition, then_branch, else_branch } => {
+                let cond = self.expr(condition, cur);
+                let join = self.node(NodeKind::Join);
+                let then_end = self.block(then_branch, cond);
+                self.edge(then_end, join);
+                let else_end = match else_branch {
+                    Some(else_branch) => self.block(else_branch, cond),
+                    None => cond,
+                };
+                self.edge(else_end, join);
+                join
+            }
+            HirExpr::Match { scrutinee, arms } => {
+                let scrutinee_end = self.expr(scrutinee, cur);
+                let join = self.node(NodeKind::Join);
+                for arm in arms {
+                    let arm_start = match &arm.guard {
+                        Some(guard) => self.expr(guard, scrutinee_end),
+                        None => scrutinee_end,
+                    };
+                    let arm_end = self.expr(&arm.body, arm_start);
+                    self.edge(arm_end, join);
+                }
+                if arms.is_empty() {
+                    self.edge(scrutinee_end, join);
+                }
+                join
+            }
+            HirExpr::Block(block) => self.block(block, cur),
+            HirExpr::Return(value) => {
+                let cur = match value {
+                    Some(value) => self.expr(value, cur),
+                    None => cur,
+                };
+                self.jump(cur, self.cfg.exit)
+            }
+            HirExpr::Break(value) => {
+                let cur = match value {
+                    Some(value) => self.expr(value, cur),
+                    None => cur,
+                };
+                match self.loop_stack.last() {
+                    Some(&(_, after)) => self.jump(cur, after),
+                    // RSPL066, reported elsewhere
+                    None => self.node(NodeKind::Join),
+                }
+            }
+            HirExpr::Continue => match self.loop_stack.last() {
+                Some(&(head, _)) => self.jump(cur, head),
+                None => self.node(NodeKind::Join),
+            },
+        }
+    }
+}
+
+/// `exit(..)`, `process::exit(..)`, `panic(..)`, ...
+fn is_diverging_call(path: &crate::hir::Path) -> bool {
+    path.segments.last().is_some_and(|name| DIVERGING_CALLS.contains(&name.name.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{BinOp, Ident, Literal};
+    use crate::hir::{BindingId, Path};
+
+    fn span(line: usize) -> Span {
+        Span { start_line: line, start_col: 1, end_line: line, end_col: 1 }
+    }
+
+    fn spanned<T>(node: T, line: usize) -> Spanned<T> {
+        Spanned::new(node, span(line))
+    }
+
+    fn block(stmts: Vec<Spanned<HirStmt>>) -> Spanned<HirBlock> {
+        spanned(HirBlock { stmts, expr: None }, 0)
+    }
+
+    fn expr_stmt(expr: HirExpr, line: usize) -> Spanned<HirStmt> {
+        spanned(HirStmt::Expr(spanned(expr, line)), line)
+    }
+
+    fn call(name: &str, line: usize) -> Spanned<HirStmt> {
+        expr_stmt(HirExpr::Call { target: HirCallTarget::Function(Path::simple(name)), args: Vec::new() }, line)
+    }
+
+    fn loop_stmt(body: Vec<Spanned<HirStmt>>, line: usize) -> Spanned<HirStmt> {
+        spanned(HirStmt::Loop { body: block(body) }, line)
+    }
+
+    /// `if x < 10 { <then> }`
+    fn if_stmt(then: Vec<Spanned<HirStmt>>, line: usize) -> Spanned<HirStmt> {
+        let condition = HirExpr::Binary {
+            op: BinOp::Lt,
+            left: Box::new(spanned(HirExpr::Var(BindingId::new(0)), line)),
+            right: Box::new(spanned(HirExpr::Literal(Literal::Int(10)), line)),
+        };
+        expr_stmt(HirExpr::If {
+            condition: Box::new(spanned(condition, line)),
+            then_branch: Box::new(block(then)),
+            else_branch: None,
+        }, line)
+    }
+
+    fn unexited_lines(body: Vec<Spanned<HirStmt>>) -> Vec<usize> {
+        let cfg = ControlFlowGraph::build(&block(body));
+        cfg.loops_without_exit().iter().map(|l| l.span.start_line).collect()
+    }
+
+    #[test]
+    fn test_loop_without_break() {
+        assert_eq!(unexited_lines(vec![loop_stmt(vec![call("tick", 2)], 1)]), vec![1]);
+    }
+
+    #[test]
+    fn test_conditional_break_exits() {
+        let body = vec![loop_stmt(vec![
+            call("tick", 2),
+            if_stmt(vec![expr_stmt(HirExpr::Break(None), 4)], 3),
+        ], 1)];
+        assert!(unexited_lines(body).is_empty());
+    }
+
+    #[test]
+    fn test_break_in_inner_loop_only_leaves_it() {
+        let inner = spanned(HirStmt::While {
+            condition: spanned(HirExpr::Literal(Literal::Bool(true)), 2),
+            body: block(vec![expr_stmt(HirExpr::Break(None), 3)]),
+        }, 2);
+        assert_eq!(unexited_lines(vec![loop_stmt(vec![inner], 1)]), vec![1]);
+    }
+
+    #[test]
+    fn test_return_and_diverging_calls_exit() {
+        let returns = vec![loop_stmt(vec![if_stmt(vec![expr_stmt(HirExpr::Return(None), 3)], 2)], 1)];
+        assert!(unexited_lines(returns).is_empty());
+
+        let exit_path = Path { segments: vec![Ident::new("process"), Ident::new("exit")] };
+        let exits = vec![loop_stmt(vec![expr_stmt(HirExpr::Call {
+            target: HirCallTarget::Function(exit_path),
+            args: vec![spanned(HirExpr::Literal(Literal::Int(1)), 2)],
+        }, 2)], 1)];
+        assert!(unexited_lines(exits).is_empty());
+    }
+
+    #[test]
+    fn test_return_inside_closure_does_not_exit() {
+        let closure = HirExpr::Closure {
+            params: Vec::new(),
+            body: Box::new(spanned(HirExpr::Return(None), 2)),
+            captures: Vec::new(),
+        };
+        assert_eq!(unexited_lines(vec![loop_stmt(vec![expr_stmt(closure, 2)], 1)]), vec![1]);
+    }
+
+    #[test]
+    fn test_graph_shape() {
+        let cfg = ControlFlowGraph::build(&block(vec![
+            call("a", 1),
+            loop_stmt(vec![if_stmt(vec![expr_stmt(HirExpr::Break(None), 4)], 3)], 2),
+            call("b", 5),
+        ]));
+        let info = &cfg.loops[0];
+        assert!(cfg.loop_exits(info));
+        // The code after the loop is reached only through the `break`
+        assert!(cfg.reachable_from(info.after).contains(&cfg.exit));
+        assert!(cfg.reachable_from(cfg.entry).contains(&info.after));
+        assert!(!cfg.nodes[info.head].succs.contains(&info.after));
+        // A `while` loop can always end
+        let cfg = ControlFlowGraph::build(&block(vec![spanned(HirStmt::While {
+            condition: spanned(HirExpr::Literal(Literal::Bool(true)), 1),
+            body: block(vec![call("tick", 2)]),
+        }, 1)]));
+        assert!(cfg.loops_without_exit().is_empty());
+    }
+}
@@ -4,8 +4,14 @@
 //! - Array access clone transformation
 //! - Type detection from array elements
 //! - Clone-related utility functions
+//! - Copy element detection (no `.clone()` needed)
 
-use crate::helpers::is_valid_identifier;
+use std::collections::{HashMap, HashSet};
+
+use crate::helpers::{is_valid_identifier, transform_generic_brackets};
+use crate::lowering::depth_tracking_lowering::split_top_level_types;
+use crate::variable::parse_rusts_assignment_ext;
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// Transform array index access to add .clone() for non-Copy types
 /// 
@@ -260,10 +266,191 @@ pub fn is_cloneable_array_access(expr: &str) -> bool {
     true
 }
 
+/// Builtin types that are always `Copy`
+const BUILTIN_COPY_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize",
+    "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64", "bool", "char", "()",
+];
+
+/// Is `ty` a Copy type? `copy_types` holds user types deriving `Copy`.
+/// Shared references, tuples, fixed arrays and `Option` of Copy types are
+/// Copy too.
+pub fn is_copy_type(ty: &str, copy_types: &HashSet<String>) -> bool {
+    let ty = transform_generic_brackets(ty.trim());
+    let ty = ty.trim();
+    
+    if BUILTIN_COPY_TYPES.contains(&ty) || copy_types.contains(ty) {
+        return true;
+    }
+    if ty.starts_with('&') {
+        return !ty.starts_with("&mut ");
+    }
+    if let Some(inner) = ty.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        return split_top_level_types(inner, ',').iter().all(|t| is_copy_type(t, copy_types));
+    }
+    if let Some(inner) = ty.strip_prefix("Option<").and_then(|t| t.strip_suffix('>')) {
+        return is_copy_type(inner, copy_types);
+    }
+    if ty.starts_with('[') && ty.contains(';') {
+        return element_type(ty).is_some_and(|elem| is_copy_type(&elem, copy_types));
+    }
+    false
+}
+
+/// Element type of an indexable type: `[T; N]`, `[T]`, `&[T]`, `Vec<T>`,
+/// `Vec[T]`, `&Vec<T>`
+pub fn element_type(ty: &str) -> Option<String> {
+    let ty = transform_generic_brackets(ty.trim());
+    let ty = ty.trim().trim_start_matches('&').trim_start_matches("mut ").trim();
+    
+    if let Some(inner) = ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let elem = split_top_level_types(inner, ';').into_iter().next()?;
+        return Some(elem.trim().to_string());
+    }
+    if let Some(inner) = ty.strip_prefix("Vec<").and_then(|t| t.strip_suffix('>')) {
+        return Some(inner.trim().to_string());
+    }
+    None
+}
+
+/// Is a literal element (`1`, `2.5`, `'a'`, `P { .. }`) of a Copy type?
+fn is_copy_literal(element: &str, copy_types: &HashSet<String>) -> bool {
+    let trimmed = element.trim();
+    // String literals stay `&str`, which is Copy
+    if trimmed.starts_with('"') || trimmed.starts_with('\'') || trimmed == "true" || trimmed == "false" {
+        return true;
+    }
+    // Numbers, including suffixed ones (`0u8`, `1_000`, `-2.5f32`)
+    let digits = trimmed.strip_prefix('-').unwrap_or(trimmed);
+    if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return digits.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    }
+    detect_type_from_element(trimmed).is_some_and(|ty| copy_types.contains(&ty))
+}
+
+/// Does an array literal value (`[1, 2]`, `[0; 4]`, `vec![1.0]`) hold Copy
+/// elements? Only the first element is inspected - the rest share its type.
+pub fn is_copy_array_literal(value: &str, copy_types: &HashSet<String>) -> bool {
+    let trimmed = value.trim().trim_end_matches(';');
+    let trimmed = trimmed.strip_prefix("vec!").unwrap_or(trimmed);
+    let Some(inner) = trimmed.strip_prefix('[').and_then(|t| t.strip_suffix(']')) else {
+        return false;
+    };
+    let first = split_top_level_types(inner, ',').into_iter().next().unwrap_or_default();
+    let first = first.split(';').next().unwrap_or_default();
+    is_copy_literal(first, copy_types)
+}
+
+/// Arrays of a function whose elements are Copy, so indexing them needs no
+/// `.clone()`.
+///
+/// Sources: parameter types, declared local types and array literals. A
+/// variable assigned anything else in the same function is left out, so a
+/// shadowing `nums = names` keeps the clone.
+pub fn scan_copy_element_arrays(
+    fn_lines: &[&str],
+    params: &HashMap<String, String>,
+    copy_types: &HashSet<String>,
+) -> HashSet<String> {
+    let is_copy_elem = |ty: &str| element_type(ty).is_some_and(|elem| is_copy_type(&elem, copy_types));
+    let mut copy_arrays: HashSet<String> = params.iter()
+        .filter(|(_, ty)| is_copy_elem(ty))
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut other: HashSet<String> = HashSet::new();
+    
+    for (i, line) in fn_lines.iter().enumerate() {
+        let Some((var, var_type, value, _, _)) = parse_rusts_assignment_ext(line) else {
+            continue;
+        };
+        let is_copy = match var_type {
+            Some(ty) => is_copy_elem(&ty),
+            // Multi-line literal: `nums = [` followed by the elements
+            None if value == "[" || value == "vec![" => fn_lines.get(i + 1)
+                .is_some_and(|next| is_copy_literal(next.trim().trim_end_matches(','), copy_types)),
+            None => is_copy_array_literal(&value, copy_types),
+        };
+        if is_copy {
+            copy_arrays.insert(var);
+        } else {
+            other.insert(var);
+        }
+    }
+    
+    copy_arrays.retain(|name| !other.contains(name));
+    copy_arrays
+}
+
+/// Named types that must derive `Clone` for a value of type `ty` to be
+/// cloned: `User` → `User`, `Option[User]` → `User`, `Vec<(Id, User)>` →
+/// `Id`, `User`. A shared reference is Copy and needs nothing.
+pub fn clone_requirements(ty: &str) -> Vec<String> {
+    let ty = ty.trim();
+    if ty.starts_with('&') && !ty.starts_with("&mut ") {
+        return Vec::new();
+    }
+    ty.split(|c: char| !crate::ident::is_ident_continue(c))
+        .filter(|name| name.starts_with(char::is_uppercase))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Add `Clone` to the derives of the type definition about to be pushed.
+///
+/// A `#[derive(...)]` directly above the definition gets `Clone` added to
+/// its list (unless already there); otherwise `#[derive(Clone)]` is pushed.
+/// Only types that receive a generated `.clone()` need it - injecting it
+/// everywhere breaks types holding non-Clone fields (e.g. `AtomicU64`).
+pub fn inject_clone_derive(output_lines: &mut Vec<String>, leading_ws: &str, needs_clone: bool) {
+    if !needs_clone || !enabled(LoweringRule::L12) {
+        return;
+    }
+    
+    // Attributes directly above the definition
+    for line in output_lines.iter_mut().rev() {
+        let trimmed = line.trim();
+        if !trimmed.starts_with("#[") {
+            break;
+        }
+        if let Some(rest) = trimmed.strip_prefix("#[derive(") {
+            let has_clone = rest.split([',', ')'])
+                .any(|d| d.trim() == "Clone");
+            if !has_clone {
+                *line = line.replacen("#[derive(", "#[derive(Clone, ", 1);
+            }
+            return;
+        }
+    }
+    output_lines.push(format!("{}#[derive(Clone)]", leading_ws));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    #[test]
+    fn test_clone_requirements() {
+        assert_eq!(clone_requirements("User"), vec!["User"]);
+        assert_eq!(clone_requirements("Option[User]"), vec!["Option", "User"]);
+        assert_eq!(clone_requirements("Vec<(Id, Option<User>)>"), vec!["Vec", "Id", "Option", "User"]);
+        assert!(clone_requirements("&User").is_empty());
+    }
+    
+    #[test]
+    fn test_inject_clone_derive() {
+        let mut lines = vec!["#[derive(Debug)]".to_string()];
+        inject_clone_derive(&mut lines, "", true);
+        assert_eq!(lines, vec!["#[derive(Clone, Debug)]"]);
+        inject_clone_derive(&mut lines, "", true);
+        assert_eq!(lines, vec!["#[derive(Clone, Debug)]"]);
+        
+        let mut lines = vec!["fn f() {}".to_string()];
+        inject_clone_derive(&mut lines, "", false);
+        inject_clone_derive(&mut lines, "    ", true);
+        assert_eq!(lines, vec!["fn f() {}", "    #[derive(Clone)]"]);
+    }
+    
     #[test]
     fn test_transform_array_access_clone() {
         assert_eq!(transform_array_access_clone("events[i]"), "events[i].clone()");
@@ -333,4 +520,51 @@ mod tests {
         assert!(!is_cloneable_array_access("arr[i].clone()"));
         assert!(!is_cloneable_array_access("arr[i].len()"));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_is_copy_type() {
+        let copy_types: HashSet<String> = ["Point".to_string()].into_iter().collect();
+        assert!(is_copy_type("i32", &copy_types));
+        assert!(is_copy_type("&str", &copy_types));
+        assert!(is_copy_type("(u8, Point)", &copy_types));
+        assert!(is_copy_type("[f64; 3]", &copy_types));
+        assert!(is_copy_type("Option[char]", &copy_types));
+        assert!(!is_copy_type("String", &copy_types));
+        assert!(!is_copy_type("&mut i32", &copy_types));
+        assert!(!is_copy_type("(u8, String)", &copy_types));
+    }
+    
+    #[test]
+    fn test_element_type() {
+        assert_eq!(element_type("[i32; 3]"), Some("i32".to_string()));
+        assert_eq!(element_type("&[u8]"), Some("u8".to_string()));
+        assert_eq!(element_type("Vec[String]"), Some("String".to_string()));
+        assert_eq!(element_type("[[u8; 2]; 4]"), Some("[u8; 2]".to_string()));
+        assert_eq!(element_type("HashMap<u8, u8>"), None);
+    }
+    
+    #[test]
+    fn test_scan_copy_element_arrays() {
+        let copy_types: HashSet<String> = ["Point".to_string()].into_iter().collect();
+        let params: HashMap<String, String> = [
+            ("ids".to_string(), "&[u64]".to_string()),
+            ("names".to_string(), "&[String]".to_string()),
+        ].into_iter().collect();
+        let lines = [
+            "nums = [1, 2, 3]",
+            "zeros = [0u8; 16]",
+            "pts = vec![Point { x = 1 }]",
+            "fixed [char; 2] = ['a', 'b']",
+            "words = vec![String::from(\"a\")]",
+            "grid = [",
+            "    1.5,",
+            "]",
+            "shadowed = [1]",
+            "shadowed = words",
+        ];
+        let found = scan_copy_element_arrays(&lines, &params, &copy_types);
+        let mut found: Vec<&str> = found.iter().map(|s| s.as_str()).collect();
+        found.sort();
+        assert_eq!(found, vec!["fixed", "grid", "ids", "nums", "pts", "zeros"]);
+    }
+}
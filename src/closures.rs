@@ -0,0 +1,144 @@
+//! Closures
+//!
+//! Besides Rust's pipes (`|x i32| x * 2`), a closure can be written as a
+//! function without a name:
+//!
+//! ```text
+//! double = fn(x i32) i32 { x * 2 }     →  let double = |x: i32| -> i32 { x * 2 };
+//! log = fn(s &str) effects(io) {       →  let log = |s: &str| {
+//!     println!("{}", s)                       println!("{}", s);
+//! }                                       };
+//! ```
+//!
+//! The rewrite to pipes runs on the whole source before Stage 1, like
+//! [`crate::method_sugar`], so both forms get the same closure analysis.
+//! The effects of a closure count for the function it is written in; a
+//! closure annotated `effects(..)` must also declare every effect its body
+//! performs (Effect-02, RSPL303). Line numbers do not change.
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{delimiter_positions, find_matching_close};
+
+/// Rewrite every `fn(params) Ret effects(..) {` lambda of `source` to pipes
+pub fn lower_fn_lambdas(source: &str) -> String {
+    if !source.contains("fn(") {
+        return source.to_string();
+    }
+    let mut out: Vec<String> = source
+        .lines()
+        .map(|line| lower_fn_lambdas_line(line).unwrap_or_else(|| line.to_string()))
+        .collect();
+    if source.ends_with('\n') {
+        out.push(String::new());
+    }
+    out.join("\n")
+}
+
+/// `f = fn(x i32) i32 {` → `f = |x i32| -> i32 {`; None when the line has
+/// no lambda
+pub fn lower_fn_lambdas_line(line: &str) -> Option<String> {
+    let code = strip_inline_comment(line);
+    let comment = &line[code.len()..];
+    let mut out = String::new();
+    let mut rest = 0;
+    for (pos, _) in delimiter_positions(&code, &['f']) {
+        if pos < rest || !code[pos..].starts_with("fn(") || !opens_lambda(&code[..pos]) {
+            continue;
+        }
+        let Some((replacement, end)) = lambda_head(&code, pos) else {
+            continue;
+        };
+        out.push_str(&code[rest..pos]);
+        out.push_str(&replacement);
+        rest = end;
+    }
+    if rest == 0 {
+        return None;
+    }
+    out.push_str(&code[rest..]);
+    out.push_str(comment);
+    Some(out)
+}
+
+/// Whether `fn(` after `before` starts an expression rather than a fn
+/// pointer type (`f fn(i32) i32`, `-> fn(i32)`) or a function name
+fn opens_lambda(before: &str) -> bool {
+    if before.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return false;
+    }
+    let before = before.trim_end();
+    before.is_empty()
+        || (before.ends_with(['=', '(', ',', '{', '[', '>']) && !before.ends_with("->"))
+        || ["move", "return"].iter().any(|word| {
+            before.strip_suffix(word).is_some_and(|rest| !rest.ends_with(|c: char| c.is_alphanumeric() || c == '_'))
+        })
+}
+
+/// Pipes for the lambda whose `fn(` is at `start` of `code`, and the end of
+/// its head (the position of the body's `{`)
+fn lambda_head(code: &str, start: usize) -> Option<(String, usize)> {
+    let open = start + "fn".len();
+    let close = find_matching_close(code, open)?;
+    let params = &code[open + 1..close];
+    let body = close + 1 + code[close + 1..].find('{')?;
+    let mut between = code[close + 1..body].trim().to_string();
+
+    let mut effects = String::new();
+    if let Some(at) = between.find("effects(") {
+        let end = find_matching_close(&between, at + "effects".len())?;
+        effects = format!(" {}", &between[at..=end]);
+        between.replace_range(at..=end, "");
+    }
+    let ret = between.trim().trim_start_matches("->").trim();
+    // A fn pointer type in a list or a signature, not a lambda head
+    if ret.contains([',', ')', ';', '=', '"']) {
+        return None;
+    }
+    let ret = if ret.is_empty() { String::new() } else { format!(" -> {}", ret) };
+    Some((format!("|{}|{}{} ", params, effects, ret), body))
+}
+
+/// Whether the value of an assignment is a closure whose block body
+/// continues on the next lines: `|x| {`, `move |x i32| -> i32 {`
+pub fn opens_closure_block(value: &str, opens: usize, closes: usize) -> bool {
+    let value = value.trim();
+    let value = value.strip_prefix("move ").unwrap_or(value).trim_start();
+    value.starts_with('|') && value.ends_with('{') && opens > closes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fn_lambda_to_pipes() {
+        assert_eq!(lower_fn_lambdas_line("    double = fn(x i32) i32 { x * 2 }").unwrap(), "    double = |x i32| -> i32 { x * 2 }");
+        assert_eq!(lower_fn_lambdas_line("log = fn(s &str) effects(io) {").unwrap(), "log = |s &str| effects(io) {");
+        assert_eq!(lower_fn_lambdas_line("f = fn(a, b) -> Vec[i32] effects(io) {  // pair").unwrap(), "f = |a, b| effects(io) -> Vec[i32] {  // pair");
+        assert_eq!(lower_fn_lambdas_line("xs.iter().map(fn(x &i32) i32 { x + 1 }).sum()").unwrap(), "xs.iter().map(|x &i32| -> i32 { x + 1 }).sum()");
+        assert_eq!(lower_fn_lambdas_line("t = spawn(move fn() { work() })").unwrap(), "t = spawn(move || { work() })");
+    }
+
+    #[test]
+    fn test_fn_pointers_and_functions_unchanged() {
+        for line in [
+            "fn apply(f fn(i32) i32, x i32) i32 {",
+            "fn make() fn(i32) -> i32 {",
+            "type Op = fn(i32) -> i32",
+            "ops: [fn(i32) -> i32; 2] = [double, triple]",
+            "println!(\"fn(x) {}\", x)",
+            "callback = myfn(x) { y }",
+        ] {
+            assert_eq!(lower_fn_lambdas_line(line), None, "{}", line);
+        }
+        assert_eq!(lower_fn_lambdas("fn main() {\n}\n"), "fn main() {\n}\n");
+    }
+
+    #[test]
+    fn test_opens_closure_block() {
+        assert!(opens_closure_block("|s: &str| {", 1, 0));
+        assert!(opens_closure_block("move |x: i32| -> i32 {", 1, 0));
+        assert!(!opens_closure_block("|x| { x + 1 }", 1, 1));
+        assert!(!opens_closure_block("Point {", 1, 0));
+    }
+}
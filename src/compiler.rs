@@ -0,0 +1,305 @@
+//! Compiler Builder for Embedders
+//!
+//! One place to configure lowering instead of assembling
+//! [`LoweringOptions`] by hand:
+//!
+//! ```text
+//! let compiler = rustsp::Compiler::builder()
+//!     .preserve_lines(true)
+//!     .hook(MyDslHook)
+//!     .build();
+//! let rust = compiler.lower(&source)?;
+//! ```
+//!
+//! [`Compiler::emit`] sends the result to an [`OutputTarget`] instead:
+//! a file, or rustc in a temporary directory.
+
+use std::io::{BufRead, Seek, Write};
+
+use crate::contracts::ContractMode;
+use crate::edition::Edition;
+use crate::input_limits::InputLimits;
+use crate::error_msg::RsplError;
+use crate::lowering_hook::LoweringHook;
+use crate::lowering_rules::LoweringRule;
+use crate::output_target::{OutputError, OutputTarget};
+use crate::streaming::{lower_rusts_streaming, StreamingError};
+use crate::source_map::LineTable;
+use crate::transpile_main::{lower_rusts, lower_rusts_mapped, parse_rusts_with_options, LoweringOptions};
+
+/// A configured RustS+ to Rust lowering
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    options: LoweringOptions,
+}
+
+impl Compiler {
+    pub fn builder() -> CompilerBuilder {
+        CompilerBuilder::default()
+    }
+
+    pub fn options(&self) -> &LoweringOptions {
+        &self.options
+    }
+
+    /// Lower to Rust, or return the diagnostics (see [`lower_rusts`])
+    pub fn lower(&self, source: &str) -> Result<String, Vec<RsplError>> {
+        lower_rusts(source, &self.options)
+    }
+
+    /// Lower to Rust with the source line of every generated line
+    pub fn lower_mapped(&self, source: &str) -> Result<(String, LineTable), Vec<RsplError>> {
+        lower_rusts_mapped(source, &self.options)
+    }
+
+    /// Lower to Rust, emitting lowering errors as `compile_error!`
+    pub fn transpile(&self, source: &str) -> String {
+        parse_rusts_with_options(source, &self.options)
+    }
+
+    /// Lower to Rust and hand the result to `target`
+    pub fn emit<T: OutputTarget>(&self, source: &str, target: &mut T) -> Result<T::Output, OutputError> {
+        let rust = self.lower(source).map_err(OutputError::Lowering)?;
+        target.emit(&rust)
+    }
+
+    /// Lower a large input piece by piece (see [`crate::streaming`])
+    pub fn lower_streaming<R: BufRead + Seek, W: Write>(
+        &self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), StreamingError> {
+        lower_rusts_streaming(input, output, &self.options)
+    }
+}
+
+/// Builder for [`Compiler`]
+#[derive(Debug, Clone, Default)]
+pub struct CompilerBuilder {
+    options: LoweringOptions,
+}
+
+impl CompilerBuilder {
+    /// See [`LoweringOptions::preserve_lines`]
+    pub fn preserve_lines(mut self, preserve: bool) -> Self {
+        self.options.preserve_lines = preserve;
+        self
+    }
+
+    /// See [`LoweringOptions::derive_clone_all`]
+    pub fn derive_clone_all(mut self, derive: bool) -> Self {
+        self.options.derive_clone_all = derive;
+        self
+    }
+
+    /// See [`LoweringOptions::opt_level`]
+    pub fn opt_level(mut self, level: u8) -> Self {
+        self.options.opt_level = level;
+        self
+    }
+
+    /// See [`LoweringOptions::effect_badges`]
+    pub fn effect_badges(mut self, badges: bool) -> Self {
+        self.options.effect_badges = badges;
+        self
+    }
+
+    /// See [`LoweringOptions::error_enums`]
+    pub fn error_enums(mut self, enums: bool) -> Self {
+        self.options.error_enums = enums;
+        self
+    }
+
+    /// See [`LoweringOptions::edition`]
+    pub fn edition(mut self, edition: Edition) -> Self {
+        self.options.edition = Some(edition);
+        self
+    }
+    
+    /// See [`LoweringOptions::limits`]
+    pub fn limits(mut self, limits: InputLimits) -> Self {
+        self.options.limits = limits;
+        self
+    }
+
+    /// See [`LoweringOptions::contracts`]
+    pub fn contracts(mut self, mode: ContractMode) -> Self {
+        self.options.contracts = mode;
+        self
+    }
+
+    /// See [`LoweringOptions::rules`]
+    pub fn disable_rule(mut self, rule: LoweringRule) -> Self {
+        self.options.rules.disable(rule);
+        self
+    }
+
+    /// See [`LoweringOptions::license`]
+    pub fn license(mut self, spdx: impl Into<String>) -> Self {
+        self.options.license = Some(spdx.into());
+        self
+    }
+
+    /// See [`LoweringOptions::attribution`]
+    pub fn attribution(mut self, text: impl Into<String>) -> Self {
+        self.options.attribution = Some(text.into());
+        self
+    }
+
+    /// Add a project-specific lowering rule. Hooks run in the order they are
+    /// added; one with the same name as an earlier hook replaces it.
+    pub fn hook<H: LoweringHook + 'static>(mut self, hook: H) -> Self {
+        self.options.hooks.register(hook);
+        self
+    }
+
+    pub fn build(self) -> Compiler {
+        Compiler { options: self.options }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lowering_hook::{LineCtx, Rewrite};
+
+    /// `audit <expr>` → `audit_log::record(<fn>, <expr>);`
+    struct Audit;
+
+    impl LoweringHook for Audit {
+        fn name(&self) -> &str {
+            "audit"
+        }
+        fn on_line(&self, ctx: &LineCtx) -> Option<Rewrite> {
+            let event = ctx.code.strip_prefix("audit ")?;
+            let record = format!("audit_log::record({:?}, {});", ctx.function.unwrap_or(""), event);
+            if ctx.in_match_arm {
+                Some(Rewrite::lines(["audit_log::enter_arm();", record.as_str()]))
+            } else {
+                Some(Rewrite::line(record))
+            }
+        }
+    }
+
+    const SOURCE: &str = r#"fn charge(amount i32) i32 {
+    total = amount + 1
+    audit total
+    match amount {
+        0 {
+            audit "zero"
+            0
+        }
+        _ {
+            total
+        }
+    }
+}"#;
+
+    #[test]
+    fn test_hook_rewrites_lines() {
+        let output = Compiler::builder().hook(Audit).build().lower(SOURCE).unwrap();
+        assert!(output.contains("audit_log::record(\"charge\", total);"), "Hook not applied: {}", output);
+        assert!(output.contains("audit_log::enter_arm();\naudit_log::record(\"charge\", \"zero\");"), "Arm context lost: {}", output);
+        // Lines the hook declines are lowered as usual
+        assert!(output.contains("let total = amount + 1;"), "Assignment not lowered: {}", output);
+        assert!(!output.contains("audit total"), "Source line leaked: {}", output);
+    }
+
+    #[test]
+    fn test_hook_output_stays_on_its_source_line() {
+        let output = Compiler::builder().hook(Audit).preserve_lines(true).build().lower(SOURCE).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), SOURCE.lines().count(), "Line count changed: {}", output);
+        assert!(lines[2].contains("audit_log::record(\"charge\", total);"), "Wrong line: {}", output);
+        assert!(lines[5].contains("audit_log::enter_arm(); audit_log::record(\"charge\", \"zero\");"), "Wrong line: {}", output);
+    }
+
+    #[test]
+    fn test_without_hooks_output_is_unchanged() {
+        let plain = crate::parse_rusts(SOURCE);
+        assert_eq!(Compiler::builder().build().transpile(SOURCE), plain);
+    }
+
+    #[test]
+    fn test_license_header() {
+        let compiler = Compiler::builder().license("MIT OR Apache-2.0").attribution("Generated from charge.rss\n\nby rustsp").build();
+        let output = compiler.lower(SOURCE).unwrap();
+        let plain = crate::parse_rusts(SOURCE);
+        assert_eq!(
+            output,
+            format!("// SPDX-License-Identifier: MIT OR Apache-2.0\n// Generated from charge.rss\n//\n// by rustsp\n{}", plain)
+        );
+    }
+
+    #[test]
+    fn test_license_header_keeps_line_numbers() {
+        let compiler = Compiler::builder().license("MIT").attribution("ACME */ Corp").preserve_lines(true).build();
+        let output = compiler.lower(SOURCE).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), SOURCE.lines().count(), "Line count changed: {}", output);
+        assert!(lines[0].starts_with("/* SPDX-License-Identifier: MIT; ACME * / Corp */ fn charge("), "Bad header: {}", output);
+    }
+
+    #[test]
+    fn test_effect_badges() {
+        let source = "fn charge(amount i32) i32 {\n    amount + 1\n}\n\nfn main() effects(io) {\n    println(\"{}\", charge(1))\n}";
+        let output = Compiler::builder().effect_badges(true).build().lower(source).unwrap();
+        assert!(output.starts_with("/// rustsp: pure\nfn charge("), "Missing badge: {}", output);
+        assert!(output.contains("/// rustsp: effects(io)\nfn main()"), "Missing badge: {}", output);
+
+        let aligned = Compiler::builder().effect_badges(true).preserve_lines(true).build().lower(source).unwrap();
+        assert_eq!(aligned.lines().count(), source.lines().count(), "Line count changed: {}", aligned);
+        assert!(aligned.contains("#[doc = \"rustsp: effects(io)\"] fn main()"), "Missing badge: {}", aligned);
+    }
+
+    #[test]
+    fn test_error_enums() {
+        let source = "fn half(s String) !i32 {\n    n = s.parse[i32]()?\n    Ok(n / 2)\n}\n\nfn main() effects(io) {\n    println(\"{:?}\", half(String::from(\"8\")))\n}";
+        let boxed = Compiler::builder().build().lower(source).unwrap();
+        assert!(boxed.contains("fn half(s: String) -> Result<i32, Box<dyn std::error::Error>> {"), "{}", boxed);
+
+        let output = Compiler::builder().error_enums(true).build().lower(source).unwrap();
+        assert!(output.contains("fn half(s: String) -> Result<i32, HalfError> {"), "{}", output);
+        assert!(output.contains("s.parse::<i32>().map_err(|e| HalfError::Parse(e.into()))?;"), "{}", output);
+        assert!(output.contains("}\n#[derive(Debug, thiserror::Error)]\nenum HalfError {\n"), "{}", output);
+
+        let aligned = Compiler::builder().error_enums(true).preserve_lines(true).build().lower(source).unwrap();
+        assert_eq!(aligned.lines().count(), source.lines().count(), "Line count changed: {}", aligned);
+    }
+
+    #[test]
+    fn test_disable_rule() {
+        let source = "fn show(xs [i32]) effects(io) {\n    println(\"{}\", xs[0])\n}\n\nfn main() effects(io) {\n    mut n = 1\n    n += 1\n    show([n])\n}";
+        let full = Compiler::builder().build().lower(source).unwrap();
+        assert!(full.contains("let mut n = 1;") && full.contains("println!("), "{}", full);
+
+        let output = Compiler::builder()
+            .disable_rule(LoweringRule::L04)
+            .disable_rule(LoweringRule::L05)
+            .disable_rule(LoweringRule::L08)
+            .build()
+            .lower(source)
+            .unwrap();
+        assert!(output.contains("fn show(xs: &[i32]) effects(io) {"), "Effects stripped: {}", output);
+        assert!(output.contains("println(\"{}\", xs[0]);"), "Macro lowered: {}", output);
+        assert!(output.contains("\nmut n = 1;"), "Mutability lowered: {}", output);
+        // Rules that stay on still apply
+        assert!(output.contains("show(&[n]);"), "{}", output);
+    }
+
+    #[test]
+    fn test_edition_lowering() {
+        let source = "trait Shape {\n    fn area(&self) f64\n}\n\nfn check(s &Shape, n i32) {\n    if n < 0 {\n        panic(\"bad {n}\")\n    }\n}";
+        let output = Compiler::builder().edition(Edition::E2018).build().lower(source).unwrap();
+        assert!(output.starts_with("// Rust edition: 2018\n"), "Missing header: {}", output);
+        assert!(output.contains("panic!(\"bad {n}\", n = n);"), "Capture not made explicit: {}", output);
+        assert!(output.contains("s: &Shape"), "dyn added before 2021: {}", output);
+
+        let output = Compiler::builder().edition(Edition::E2021).build().lower(source).unwrap();
+        assert!(output.contains("panic!(\"bad {n}\");"), "Capture rewritten on 2021: {}", output);
+        assert!(output.contains("s: &dyn Shape"), "Missing dyn: {}", output);
+
+        let errors = Compiler::builder().edition(Edition::E2015).build().lower("async fn load() {\n}").unwrap_err();
+        assert_eq!(errors[0].code, crate::error_msg::ErrorCode::RSPL028);
+    }
+}
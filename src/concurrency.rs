@@ -0,0 +1,303 @@
+//! Concurrency Sugar: `spawn`, `channel`, `join`
+//!
+//! ```text
+//! handle = spawn { work() }     →  let handle = std::thread::spawn(move || { work() });
+//! (tx, rx) = channel()          →  let (tx, rx) = std::sync::mpsc::channel();
+//! (tx, rx) = channel[i32]()     →  let (tx, rx) = std::sync::mpsc::channel::<i32>();
+//! join handle                   →  handle.join().unwrap();
+//! r = join handle               →  let r = handle.join().unwrap();
+//! ```
+//!
+//! A `spawn` block may span several lines; its closing `}` becomes `})`.
+//! The rewrite runs on the whole source before Stage 1 and before lowering
+//! and keeps line numbers, so a spawned block's effects count as effects of
+//! the enclosing function, and spawning or joining a thread is the
+//! `concurrent` effect.
+//!
+//! The spawned closure moves what it captures. A write inside it to a
+//! `mut` binding or parameter of the enclosing function changes the
+//! thread's own copy (or does not compile), so Stage 1 reports it as an
+//! Effect-06 conflict (RSPL309); see [`find_spawn_conflicts`].
+
+use std::collections::HashSet;
+
+use crate::helpers::{strip_inline_comment, transform_generic_brackets};
+use crate::ident::is_valid_identifier;
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, delimiter_positions, split_top_level_types};
+
+/// Methods that change the collection they are called on
+const MUTATING_METHODS: &[&str] = &[
+    "push", "push_str", "push_back", "push_front", "insert", "remove", "extend",
+    "append", "clear", "pop", "truncate", "sort", "retain", "drain",
+];
+
+/// Rewrite every `spawn`, `channel` and `join` of `source`
+pub fn lower_concurrency(source: &str) -> String {
+    let defines_channel = source.lines().any(|l| fn_name(l).as_deref() == Some("channel"));
+    let mut depth = 0usize;
+    // Depths the bodies of open multi-line spawn blocks start at
+    let mut open_spawns: Vec<usize> = Vec::new();
+    let mut lines = Vec::new();
+    for line in source.lines() {
+        let code = strip_inline_comment(line);
+        let (opens, closes) = count_braces_outside_strings(&code);
+        let before = depth;
+        depth = (depth + opens).saturating_sub(closes);
+
+        let lowered = match lower_concurrency_line(line, defines_channel) {
+            Some((lowered, opens_block)) => {
+                if opens_block {
+                    open_spawns.push(before + 1);
+                }
+                lowered
+            }
+            None if open_spawns.last().is_some_and(|&body| depth < body && code.trim_start().starts_with('}')) => {
+                open_spawns.pop();
+                let close = line.find('}').unwrap_or(0);
+                format!("{}}}){}", &line[..close], &line[close + 1..])
+            }
+            None => line.to_string(),
+        };
+        lines.push(lowered);
+    }
+    lines.join("\n")
+}
+
+/// One line of [`lower_concurrency`], and whether it opens a `spawn` block
+/// that continues below; None when the line has no such form
+fn lower_concurrency_line(line: &str, defines_channel: bool) -> Option<(String, bool)> {
+    let code = strip_inline_comment(line);
+    let comment = &line[code.len()..];
+    let body = code.trim_start();
+    let indent = &code[..code.len() - body.len()];
+    let (lhs, rhs) = match split_binding(body) {
+        Some((lhs, rhs)) => (format!("{} = ", lhs), rhs),
+        None => (String::new(), body.trim_end()),
+    };
+
+    // `spawn { .. }` / `spawn {`
+    if let Some(block) = rhs.strip_prefix("spawn").filter(|b| b.trim_start().starts_with('{')) {
+        let block = block.trim_start();
+        return match block_close(block) {
+            Some(close) => Some((format!("{}{}std::thread::spawn(move || {}){}{}", indent, lhs, &block[..=close], &block[close + 1..], comment), false)),
+            None if block.trim_end() == "{" => Some((format!("{}{}std::thread::spawn(move || {{{}", indent, lhs, comment), true)),
+            None => None,
+        };
+    }
+
+    // `join handle`
+    if let Some(handle) = rhs.strip_prefix("join ").map(str::trim) {
+        if handle.split('.').all(is_valid_identifier) {
+            return Some((format!("{}{}{}.join().unwrap(){}", indent, lhs, handle, comment), false));
+        }
+    }
+
+    // `(tx, rx) = channel()` / `channel[T]()`
+    if !lhs.is_empty() && !defines_channel {
+        let args = rhs.strip_prefix("channel")?.strip_suffix("()")?;
+        let turbofish = match args {
+            "" => String::new(),
+            _ => format!("::<{}>", transform_generic_brackets(args.strip_prefix('[')?.strip_suffix(']')?)),
+        };
+        return Some((format!("{}{}std::sync::mpsc::channel{}(){}", indent, lhs, turbofish, comment), false));
+    }
+    None
+}
+
+/// `h = rest` → `("h", "rest")`; also `mut h`, `(tx, rx)` and `h: T`
+fn split_binding(body: &str) -> Option<(&str, &str)> {
+    let (eq, _) = delimiter_positions(body, &['=']).into_iter().find(|&(pos, _)| {
+        let prev = body[..pos].chars().next_back();
+        let next = body[pos + 1..].chars().next();
+        !matches!(prev, Some('=' | '!' | '<' | '>' | '+' | '-' | '*' | '/' | '%' | '&' | '|' | '^'))
+            && !matches!(next, Some('=' | '>'))
+    })?;
+    Some((body[..eq].trim(), body[eq + 1..].trim()))
+}
+
+/// Position of the `}` closing the block `block` starts with
+fn block_close(block: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (pos, c) in delimiter_positions(block, &['{', '}']) {
+        depth += if c == '{' { 1 } else { -1 };
+        if depth == 0 {
+            return Some(pos);
+        }
+    }
+    None
+}
+
+/// `fn name(` / `pub fn name(` → `name`
+fn fn_name(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix("pub ").unwrap_or(trimmed).strip_prefix("fn ")?;
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// A `std::thread::spawn(move || { .. })` block (1-based lines)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnBlock {
+    pub line: usize,
+    pub end_line: usize,
+}
+
+/// Every spawned block of `source` (after [`lower_concurrency`])
+pub fn spawn_blocks(source: &str) -> Vec<SpawnBlock> {
+    let lines: Vec<String> = source.lines().map(strip_inline_comment).collect();
+    let mut blocks = Vec::new();
+    for (idx, code) in lines.iter().enumerate() {
+        let Some(at) = code.find("thread::spawn(") else {
+            continue;
+        };
+        if !code[at..].contains('{') {
+            continue;
+        }
+        let mut depth = 0usize;
+        let mut end = idx;
+        for (j, line) in lines.iter().enumerate().skip(idx) {
+            let text = if j == idx { &line[at..] } else { line.as_str() };
+            let (opens, closes) = count_braces_outside_strings(text);
+            depth = (depth + opens).saturating_sub(closes);
+            end = j;
+            if depth == 0 {
+                break;
+            }
+        }
+        blocks.push(SpawnBlock { line: idx + 1, end_line: end + 1 });
+    }
+    blocks
+}
+
+/// A spawned block writing state of the function that spawns it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnConflict {
+    /// 1-based line of the write
+    pub line: usize,
+    /// The binding or parameter written
+    pub binding: String,
+    /// 1-based line of the `spawn`
+    pub spawn_line: usize,
+}
+
+/// Writes inside spawned blocks to `mut` bindings and parameters declared
+/// outside them (one per block and binding)
+pub fn find_spawn_conflicts(source: &str) -> Vec<SpawnConflict> {
+    let lines: Vec<String> = source.lines().map(strip_inline_comment).collect();
+    let mut conflicts = Vec::new();
+    for block in spawn_blocks(source) {
+        let spawn_idx = block.line - 1;
+        let Some(fn_idx) = (0..=spawn_idx).rev().find(|&i| fn_name(&lines[i]).is_some()) else {
+            continue;
+        };
+        let mut outer = parameter_names(&lines[fn_idx]);
+        for code in &lines[fn_idx + 1..spawn_idx] {
+            if let Some((lhs, _)) = split_binding(code.trim()) {
+                if let Some(name) = lhs.strip_prefix("mut ").map(|n| n.split(':').next().unwrap_or(n).trim()) {
+                    outer.insert(name.to_string());
+                }
+            }
+        }
+
+        // A single-line block starts inside the spawn line
+        let body: Vec<(usize, &str)> = if block.line == block.end_line {
+            let first = &lines[spawn_idx];
+            let open = first.find("thread::spawn(").and_then(|at| first[at..].find('{').map(|b| at + b));
+            open.and_then(|open| block_close(&first[open..]).map(|close| &first[open + 1..open + close]))
+                .map(|inner| vec![(block.line, inner.trim())])
+                .unwrap_or_default()
+        } else {
+            (block.line..block.end_line - 1).map(|i| (i + 1, lines[i].trim())).collect()
+        };
+
+        let mut local: HashSet<String> = HashSet::new();
+        let mut reported: HashSet<String> = HashSet::new();
+        for (line, code) in body {
+            let Some((name, declares)) = written_binding(code) else {
+                continue;
+            };
+            if declares || !outer.contains(&name) {
+                local.insert(name);
+            } else if !local.contains(&name) && reported.insert(name.clone()) {
+                conflicts.push(SpawnConflict { line, binding: name, spawn_line: block.line });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Names of the parameters in a function header
+fn parameter_names(header: &str) -> HashSet<String> {
+    let (Some(open), Some(close)) = (header.find('('), header.find(')')) else {
+        return HashSet::new();
+    };
+    if close < open {
+        return HashSet::new();
+    }
+    split_top_level_types(&header[open + 1..close], ',')
+        .into_iter()
+        .filter_map(|p| {
+            let p = p.trim().strip_prefix("mut ").unwrap_or(p.trim());
+            let name = p.split([' ', ':']).next()?;
+            (is_valid_identifier(name) && name != "self").then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// The binding a statement writes, and whether it declares it
+/// (`mut x = ..`): `c += 1`, `outer c = 2`, `xs.push(v)`, `acc.total = 0`
+fn written_binding(code: &str) -> Option<(String, bool)> {
+    let code = code.strip_prefix("outer ").unwrap_or(code);
+    let root = |target: &str| -> Option<String> {
+        let name: String = target.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+        is_valid_identifier(&name).then_some(name)
+    };
+    let assignment = delimiter_positions(code, &['=']).into_iter().find(|&(pos, _)| {
+        let before = &code[..pos];
+        !matches!(code[pos + 1..].chars().next(), Some('=' | '>'))
+            && !before.ends_with(['=', '!'])
+            && (!before.ends_with(['<', '>']) || before.ends_with("<<") || before.ends_with(">>"))
+    });
+    if let Some((eq, _)) = assignment {
+        let lhs = code[..eq].trim();
+        let compound = lhs.strip_suffix(['+', '-', '*', '/', '%', '|', '&', '^', '<', '>']).map(|t| t.trim_end_matches(['<', '>']).trim_end());
+        return match (compound, lhs.strip_prefix("mut ")) {
+            (Some(target), _) => root(target).map(|n| (n, false)),
+            (None, Some(declared)) => root(declared.trim()).map(|n| (n, true)),
+            (None, None) if lhs.starts_with('(') => None,
+            (None, None) => root(lhs).map(|n| (n, false)),
+        };
+    }
+    let name = root(code)?;
+    let after = code[name.len()..].strip_prefix('.')?;
+    let method: String = after.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    (MUTATING_METHODS.contains(&method.as_str()) && after[method.len()..].starts_with('(')).then_some((name, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sugar_lowered() {
+        let src = "fn main() {\n    (tx, rx) = channel[Vec[u8]]()\n    h = spawn {\n        tx.send(vec![1]).unwrap()\n    }\n    g = spawn { 7 }  // seven\n    join h\n    r = join g\n}";
+        assert_eq!(
+            lower_concurrency(src),
+            "fn main() {\n    (tx, rx) = std::sync::mpsc::channel::<Vec<u8>>()\n    h = std::thread::spawn(move || {\n        tx.send(vec![1]).unwrap()\n    })\n    g = std::thread::spawn(move || { 7 })  // seven\n    h.join().unwrap()\n    r = g.join().unwrap()\n}"
+        );
+    }
+
+    #[test]
+    fn test_other_lines_untouched() {
+        let src = "fn channel() i32 { 1 }\nfn main() {\n    c = channel()\n    s = \"spawn { x }\"\n    joined = names.join(\", \")\n    spawned = 1\n}";
+        assert_eq!(lower_concurrency(src), src);
+    }
+
+    #[test]
+    fn test_spawn_conflicts() {
+        let src = "fn run(acc Account) {\n    mut count = 0\n    mut log = Vec::new()\n    h = std::thread::spawn(move || {\n        count += 1\n        count += 2\n        mut seen = 0\n        seen += 1\n        acc.total = 5\n        local = 1\n    })\n    g = std::thread::spawn(move || { log.push(1) })\n}";
+        let conflicts = find_spawn_conflicts(src);
+        let found: Vec<(usize, &str, usize)> = conflicts.iter().map(|c| (c.line, c.binding.as_str(), c.spawn_line)).collect();
+        assert_eq!(found, vec![(5, "count", 4), (9, "acc", 4), (12, "log", 12)]);
+    }
+}
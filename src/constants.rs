@@ -0,0 +1,197 @@
+//! Constants
+//!
+//! `const NAME Type = value` (and `static`) names a value the rest of the
+//! program uses in places only a literal could otherwise go. The registry
+//! of declared constants lets the passes that guess from capitalization
+//! tell a constant from a type name:
+//!
+//! ```text
+//! const SIZE usize = 4
+//! const ERR_NOT_FOUND i32 = 404
+//! const ORIGIN Point = Point { x = 0, y = 0 }
+//!
+//! fn checksum(buf [u8; SIZE]) u8 { ... }   // by value, not `&[u8; SIZE]`
+//! buf [u8; SIZE] = [0; SIZE]
+//! r = match code { ERR_NOT_FOUND { 1 } _ { 0 } }
+//! ```
+//!
+//! | Position                       | Without the registry                  |
+//! |--------------------------------|---------------------------------------|
+//! | `ERR_NOT_FOUND {` match arm    | Stage 1 takes it for a struct literal |
+//! | one-line match on constants    | arms left as `pattern { body }`       |
+//! | `ADMIN {` arm, `&str` constant | scrutinee without `.as_str()`         |
+//! | `const ORIGIN Point = Point {` | lowered to a `let`                    |
+
+use std::collections::HashMap;
+
+/// Constants and statics declared anywhere in a source: name → type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConstRegistry {
+    types: HashMap<String, String>,
+}
+
+impl ConstRegistry {
+    pub fn new() -> Self {
+        ConstRegistry::default()
+    }
+
+    /// Every `const` / `static` declaration in `source`
+    pub fn from_source(source: &str) -> Self {
+        let mut registry = ConstRegistry::new();
+        for line in source.lines() {
+            if let Some(decl) = parse_const_decl(line.trim()) {
+                registry.register(decl.name, decl.ty);
+            }
+        }
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, ty: &str) {
+        self.types.insert(name.to_string(), ty.to_string());
+    }
+
+    /// Add the constants of another piece of the same program
+    pub fn merge(&mut self, other: ConstRegistry) {
+        self.types.extend(other.types);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    /// Declared type of constant `name`, as written
+    pub fn type_of(&self, name: &str) -> Option<&str> {
+        self.types.get(name).map(String::as_str)
+    }
+
+    /// Whether `name` is a string slice constant (`&str`, `&'static str`)
+    pub fn is_str(&self, name: &str) -> bool {
+        self.type_of(name).is_some_and(|ty| ty.starts_with('&') && ty.ends_with("str"))
+    }
+
+    /// Whether a match arm pattern only compares against values: literals,
+    /// ranges of them, constants and `_`, joined by `|` and with an
+    /// optional `if` guard
+    pub fn is_value_pattern(&self, pattern: &str) -> bool {
+        let pattern = pattern.split(" if ").next().unwrap_or(pattern).trim();
+        !pattern.is_empty() && pattern.split('|').all(|alternative| {
+            let alternative = alternative.trim();
+            let mut bounds = alternative.splitn(2, "..=");
+            let low = bounds.next().unwrap_or_default().trim();
+            match bounds.next() {
+                Some(high) => self.is_value(low) && self.is_value(high.trim()),
+                None => alternative == "_" || self.is_value(alternative),
+            }
+        })
+    }
+
+    fn is_value(&self, word: &str) -> bool {
+        let digits = word.strip_prefix('-').unwrap_or(word);
+        self.contains(word)
+            || digits.starts_with(|c: char| c.is_ascii_digit())
+            || (word.len() >= 2 && word.starts_with('"') && word.ends_with('"'))
+            || (word.len() >= 3 && word.starts_with('\'') && word.ends_with('\''))
+    }
+}
+
+/// One `const` / `static` declaration line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstDecl<'a> {
+    /// `const`, `pub static mut`, ...
+    pub keyword: &'a str,
+    pub name: &'a str,
+    /// The type as written, RustS+ brackets included
+    pub ty: &'a str,
+    /// Everything after `=`
+    pub value: &'a str,
+}
+
+impl ConstDecl<'_> {
+    /// `pub const NAME: Type`, the part of the Rust declaration before `=`
+    pub fn rust_head(&self) -> String {
+        format!("{} {}: {}", self.keyword, self.name, crate::helpers::transform_generic_brackets(self.ty))
+    }
+}
+
+/// `[pub] const NAME Type = value` or `[pub] static [mut] NAME Type = value`,
+/// with or without a colon after the name
+pub fn parse_const_decl(trimmed: &str) -> Option<ConstDecl<'_>> {
+    let visibility = ["pub(crate) ", "pub(super) ", "pub "]
+        .iter()
+        .find_map(|prefix| trimmed.strip_prefix(prefix).map(|_| prefix.len()))
+        .unwrap_or(0);
+    let after_vis = &trimmed[visibility..];
+    let keyword_len = ["static mut ", "static ", "const "]
+        .iter()
+        .find(|keyword| after_vis.starts_with(*keyword))?
+        .len();
+    let rest = &after_vis[keyword_len..];
+    let eq = rest.find('=')?;
+    let (name, ty) = rest[..eq].trim().split_once(|c: char| c == ':' || c.is_whitespace())?;
+    let ty = ty.trim_start_matches(':').trim();
+    let is_identifier = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !is_identifier || ty.is_empty() {
+        return None;
+    }
+    Some(ConstDecl {
+        keyword: trimmed[..visibility + keyword_len].trim_end(),
+        name,
+        ty,
+        value: rest[eq + 1..].trim(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_const_decl() {
+        let decl = parse_const_decl("pub static mut TABLE Vec[i32] = vec![]").unwrap();
+        assert_eq!((decl.keyword, decl.name, decl.ty, decl.value), ("pub static mut", "TABLE", "Vec[i32]", "vec![]"));
+        assert_eq!(decl.rust_head(), "pub static mut TABLE: Vec<i32>");
+
+        let decl = parse_const_decl("const NAME: &'static str = \"a = b\"").unwrap();
+        assert_eq!((decl.name, decl.ty, decl.value), ("NAME", "&'static str", "\"a = b\""));
+
+        assert!(parse_const_decl("constant = 1").is_none());
+        assert!(parse_const_decl("const X = 1").is_none());
+        assert!(parse_const_decl("x = const_fn()").is_none());
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = ConstRegistry::from_source("const SIZE usize = 4\nconst ADMIN &str = \"admin\"\n\nfn main() {\n    static LIMIT i64 = -1\n}\n");
+        assert_eq!(registry.type_of("SIZE"), Some("usize"));
+        assert!(registry.contains("LIMIT"));
+        assert!(registry.is_str("ADMIN"));
+        assert!(!registry.is_str("SIZE"));
+        assert!(!registry.contains("Point"));
+
+        assert!(registry.is_value_pattern("SIZE | 0 | -1"));
+        assert!(registry.is_value_pattern("1..=SIZE if n > 0"));
+        assert!(registry.is_value_pattern("ADMIN | \"root\" | 'c' | _"));
+        assert!(!registry.is_value_pattern("Some(x)"));
+        assert!(!registry.is_value_pattern("Point"));
+        assert!(!registry.is_value_pattern("other"));
+    }
+
+    #[test]
+    fn test_constants_lowered() {
+        let source = "const SIZE usize = 4\nconst ERR_NOT_FOUND i32 = 404\nconst ADMIN &str = \"admin\"\n\nstruct Point {\n    x i32,\n    y i32,\n}\n\nconst ORIGIN Point = Point { x = 0, y = 0 }\nconst CORNER Point = Point {\n    x = 1,\n    y = 1,\n}\n\nfn checksum(buf [u8; SIZE]) u8 {\n    buf[0]\n}\n\nfn role(name String) i32 {\n    match name {\n        ADMIN {\n            1\n        }\n        _ {\n            0\n        }\n    }\n}\n\nfn main() {\n    buf [u8; SIZE] = [0; SIZE]\n    r = match 404 { ERR_NOT_FOUND { checksum(buf) } _ { 0 } }\n}\n";
+        let rust = crate::parse_rusts(source);
+        let lines: Vec<&str> = rust.lines().map(str::trim).collect();
+        for expected in [
+            "const ORIGIN: Point = Point { x: 0, y: 0 };",
+            "const CORNER: Point = Point {",
+            "fn checksum(buf: [u8; SIZE]) -> u8 {",
+            "match name.as_str() {",
+            "ADMIN => {",
+            "let buf: [u8; SIZE] = [0; SIZE];",
+            "let r = match 404 { ERR_NOT_FOUND => checksum(buf), _ => 0 };",
+        ] {
+            assert!(lines.contains(&expected), "missing {:?} in\n{}", expected, rust);
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Function Contracts
+//!
+//! `requires(..)` and `ensures(..)` clauses go after the parameters, next
+//! to `effects(..)`. Each condition becomes an assertion: `requires` at
+//! entry, `ensures` on the tail value, bound to `result`:
+//!
+//! ```text
+//! fn withdraw(balance i64, amount i64) effects(panic) requires(amount > 0) ensures(result >= 0) i64 {
+//!     balance - amount
+//! }
+//!
+//! fn withdraw(balance: i64, amount: i64) -> i64 {
+//!     debug_assert!(amount > 0, "contract of `withdraw` violated: requires(amount > 0)");
+//!     let result = {
+//!         balance - amount
+//!     };
+//!     debug_assert!(result >= 0, "contract of `withdraw` violated: ensures(result >= 0)");
+//!     result
+//! }
+//! ```
+//!
+//! | Mode                       | Lowered to       | Stage 1                 |
+//! |----------------------------|------------------|-------------------------|
+//! | default                    | `debug_assert!`  | conditions are `panic`  |
+//! | `--release`                | nothing          | not checked             |
+//! | `--check-contracts`        | `assert!`        | conditions are `panic`  |
+//!
+//! An early `return` leaves the function without checking `ensures`.
+
+use crate::lowering::depth_tracking_lowering::find_matching_close;
+
+/// How contract clauses are lowered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContractMode {
+    /// `debug_assert!`: checked in builds with debug assertions
+    #[default]
+    Debug,
+    /// Stripped (`--release`)
+    Off,
+    /// `assert!`: checked in every build (`--check-contracts`)
+    Always,
+}
+
+impl ContractMode {
+    /// Whether the assertions are in the output (and may panic)
+    pub fn is_kept(self) -> bool {
+        self != ContractMode::Off
+    }
+
+    fn macro_name(self) -> &'static str {
+        match self {
+            ContractMode::Always => "assert!",
+            _ => "debug_assert!",
+        }
+    }
+}
+
+/// The `requires` and `ensures` conditions of one function
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Contract {
+    pub requires: Vec<String>,
+    pub ensures: Vec<String>,
+}
+
+impl Contract {
+    pub fn is_empty(&self) -> bool {
+        self.requires.is_empty() && self.ensures.is_empty()
+    }
+
+    /// Every condition, `requires` first
+    pub fn conditions(&self) -> impl Iterator<Item = &String> {
+        self.requires.iter().chain(&self.ensures)
+    }
+}
+
+/// Remove every `requires(..)` / `ensures(..)` clause from the part of a
+/// signature after its parameters, returning the conditions and the rest
+pub fn split_contract_clauses(after_params: &str) -> (Contract, String) {
+    let mut contract = Contract::default();
+    let mut rest = after_params.to_string();
+    while let Some((start, keyword)) = find_clause(&rest) {
+        let open = start + keyword.len();
+        let Some(close) = find_matching_close(&rest, open) else {
+            break;
+        };
+        let condition = rest[open + 1..close].trim().to_string();
+        match keyword {
+            "requires" => contract.requires.push(condition),
+            _ => contract.ensures.push(condition),
+        }
+        let end = close + 1 + (rest[close + 1..].len() - rest[close + 1..].trim_start().len());
+        rest.replace_range(start..end, "");
+    }
+    (contract, rest)
+}
+
+/// First `requires(` or `ensures(` that starts a word, outside strings and
+/// before the body
+fn find_clause(s: &str) -> Option<(usize, &'static str)> {
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in s.char_indices() {
+        if c == '"' && prev != '\\' {
+            in_string = !in_string;
+        }
+        if !in_string {
+            if c == '{' || (c == '=' && !s[i..].starts_with("==") && prev != '=') {
+                return None;
+            }
+            if prev.is_whitespace() || i == 0 {
+                for keyword in ["requires", "ensures"] {
+                    if s[i..].starts_with(keyword) && s[i + keyword.len()..].starts_with('(') {
+                        return Some((i, keyword));
+                    }
+                }
+            }
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Lines at the top of the body: the `requires` assertions, then
+/// `let result = {` when there is an `ensures`
+pub fn entry_lines(function: &str, contract: &Contract, mode: ContractMode, indent: &str) -> Vec<String> {
+    if !mode.is_kept() {
+        return Vec::new();
+    }
+    let mut lines: Vec<String> = contract.requires.iter()
+        .map(|condition| assertion(function, "requires", condition, mode, indent))
+        .collect();
+    if !contract.ensures.is_empty() {
+        lines.push(format!("{}let result = {{", indent));
+    }
+    lines
+}
+
+/// Lines before the closing brace of the body: the `ensures` assertions
+/// on `result`, which is then the function's value
+pub fn exit_lines(function: &str, contract: &Contract, mode: ContractMode, indent: &str) -> Vec<String> {
+    if !mode.is_kept() || contract.ensures.is_empty() {
+        return Vec::new();
+    }
+    let mut lines = vec![format!("{}}};", indent)];
+    lines.extend(contract.ensures.iter().map(|condition| assertion(function, "ensures", condition, mode, indent)));
+    lines.push(format!("{}result", indent));
+    lines
+}
+
+/// A single-line function (`fn f(x i32) i32 = x + 1`) lowered with its
+/// contract: `fn f(x: i32) -> i32 { <entry> x + 1 <exit> }`
+pub fn wrap_single_line(signature: &str, function: &str, contract: &Contract, mode: ContractMode) -> String {
+    let (Some(open), Some(body)) = (signature.find(" { "), signature.strip_suffix(" }")) else {
+        return signature.to_string();
+    };
+    let mut parts = entry_lines(function, contract, mode, "");
+    parts.push(body[open + 3..].to_string());
+    parts.extend(exit_lines(function, contract, mode, ""));
+    format!("{} {{ {} }}", &signature[..open], parts.join(" "))
+}
+
+fn assertion(function: &str, clause: &str, condition: &str, mode: ContractMode, indent: &str) -> String {
+    let function = function.split('<').next().unwrap_or(function);
+    let message = format!("contract of `{}` violated: {}({})", function, clause, condition)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('{', "{{")
+        .replace('}', "}}");
+    format!("{}{}({}, \"{}\");", indent, mode.macro_name(), condition, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_contract_clauses() {
+        let (contract, rest) = split_contract_clauses("effects(panic) requires(amount > 0) requires(f(a, \"(\")) ensures(result >= 0) i64 {");
+        assert_eq!(contract.requires, ["amount > 0", "f(a, \"(\")"]);
+        assert_eq!(contract.ensures, ["result >= 0"]);
+        assert_eq!(rest, "effects(panic) i64 {");
+
+        let (contract, rest) = split_contract_clauses("requires(c != ')') bool {");
+        assert_eq!(contract.requires, ["c != ')'"]);
+        assert_eq!(rest, "bool {");
+
+        let (contract, rest) = split_contract_clauses("i32 = requires(x)");
+        assert!(contract.is_empty());
+        assert_eq!(rest, "i32 = requires(x)");
+    }
+
+    #[test]
+    fn test_entry_and_exit_lines() {
+        let contract = Contract { requires: vec!["n > 0".to_string()], ensures: vec!["result != \"{}\"".to_string()] };
+        assert_eq!(entry_lines("f", &contract, ContractMode::Debug, "    "), [
+            "    debug_assert!(n > 0, \"contract of `f` violated: requires(n > 0)\");",
+            "    let result = {",
+        ]);
+        assert_eq!(exit_lines("f", &contract, ContractMode::Always, ""), [
+            "};",
+            "assert!(result != \"{}\", \"contract of `f` violated: ensures(result != \\\"{{}}\\\")\");",
+            "result",
+        ]);
+        assert!(entry_lines("f", &contract, ContractMode::Off, "").is_empty());
+        assert!(exit_lines("f", &contract, ContractMode::Off, "").is_empty());
+    }
+
+    #[test]
+    fn test_wrap_single_line() {
+        let contract = Contract { requires: vec!["x > 0".to_string()], ensures: vec![] };
+        assert_eq!(
+            wrap_single_line("fn f(x: i32) -> i32 { x + 1 }", "f", &contract, ContractMode::Debug),
+            "fn f(x: i32) -> i32 { debug_assert!(x > 0, \"contract of `f` violated: requires(x > 0)\"); x + 1 }"
+        );
+    }
+
+    const WITHDRAW: &str = "fn withdraw(balance i64, amount i64) effects(panic) requires(amount > 0) ensures(result >= 0) i64 {\n    balance - amount\n}\n\nfn half(n i32) effects(panic) requires(n % 2 == 0) i32 = n / 2\n";
+
+    #[test]
+    fn test_contracts_lowered_by_mode() {
+        let lower = |mode| {
+            let options = crate::LoweringOptions { contracts: mode, ..Default::default() };
+            let rust = crate::lower_rusts(WITHDRAW, &options).unwrap();
+            rust.lines().map(|l| l.trim().to_string()).collect::<Vec<_>>()
+        };
+        let lines = lower(ContractMode::Debug);
+        assert_eq!(&lines[..7], [
+            "fn withdraw(balance: i64, amount: i64) -> i64 {",
+            "debug_assert!(amount > 0, \"contract of `withdraw` violated: requires(amount > 0)\");",
+            "let result = {",
+            "balance - amount",
+            "};",
+            "debug_assert!(result >= 0, \"contract of `withdraw` violated: ensures(result >= 0)\");",
+            "result",
+        ], "{:?}", lines);
+        assert!(lines.contains(&"fn half(n: i32) -> i32 { debug_assert!(n % 2 == 0, \"contract of `half` violated: requires(n % 2 == 0)\"); n / 2 }".to_string()), "{:?}", lines);
+
+        assert!(lower(ContractMode::Always)[1].starts_with("assert!(amount > 0"));
+        let lines = lower(ContractMode::Off);
+        assert_eq!(&lines[..3], ["fn withdraw(balance: i64, amount: i64) -> i64 {", "balance - amount", "}"]);
+        assert!(lines.contains(&"fn half(n: i32) -> i32 { n / 2 }".to_string()), "{:?}", lines);
+    }
+
+    #[test]
+    fn test_kept_contracts_may_panic() {
+        let source = WITHDRAW.replace("effects(panic) ", "");
+        let check = |mode| {
+            let mut checker = crate::anti_fail_logic::AntiFailLogicChecker::new("test.rss");
+            checker.set_contract_mode(mode);
+            checker.check(&source)
+        };
+        let errors = check(ContractMode::Debug).unwrap_err();
+        assert!(errors.iter().all(|e| e.code == crate::error_msg::ErrorCode::RSPL300), "{:?}", errors);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(check(ContractMode::Always).is_err());
+        assert!(check(ContractMode::Off).is_ok());
+    }
+}
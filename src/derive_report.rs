@@ -0,0 +1,173 @@
+//! Derive Explanations for RustS+
+//!
+//! The lowering adds `#[derive(Clone)]` to a struct or enum only when the
+//! generated code clones one of its values: an element copied out of an
+//! array, an array a `for` loop iterates a clone of, a struct passed to a
+//! by-value parameter, or a type contained in another type that needs
+//! `Clone`. This module turns the reasons recorded
+//! by the first pass into the chain of source lines behind each derive:
+//!
+//! ```text
+//! Node gets #[derive(Clone)]
+//!   main.rss:3   Init(Node)           `Node` is contained in `Event`
+//!   main.rss:12  e = events[i]        copies an element out of `events`
+//!   main.rss:10  events = [ ...       `events` holds `Event` elements
+//! ```
+
+use crate::first_pass::{run_first_pass, CloneReason};
+use crate::variable::VariableTracker;
+
+/// One source line in the chain behind a derive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeriveStep {
+    /// 1-based source line
+    pub line: usize,
+    pub code: String,
+    pub note: String,
+}
+
+/// A type that gets `#[derive(Clone)]`, and why
+#[derive(Debug, Clone)]
+pub struct DeriveExplanation {
+    pub type_name: String,
+    /// From the type itself back to the clone that requires it
+    pub steps: Vec<DeriveStep>,
+}
+
+/// Explain every `#[derive(Clone)]` the lowering injects, by type name
+pub fn explain_derives(source: &str) -> Vec<DeriveExplanation> {
+    let lines: Vec<&str> = source.lines().collect();
+    let first_pass = run_first_pass(&lines, &mut VariableTracker::new());
+
+    let mut types: Vec<&String> = first_pass.types_need_clone.iter()
+        .filter(|name| first_pass.struct_registry.is_struct(name) || first_pass.enum_registry.is_enum(name))
+        .collect();
+    types.sort();
+
+    types.into_iter().map(|type_name| {
+        let mut steps = Vec::new();
+        let mut current = type_name.clone();
+        let mut seen = vec![current.clone()];
+        while let Some(reason) = first_pass.clone_reasons.get(&current) {
+            let code = |line: usize| lines.get(line - 1).map(|l| l.trim().to_string()).unwrap_or_default();
+            match reason {
+                CloneReason::Contained { line, container } => {
+                    steps.push(DeriveStep {
+                        line: *line,
+                        code: code(*line),
+                        note: format!("`{}` is contained in `{}`", current, container),
+                    });
+                    if seen.contains(container) {
+                        break;
+                    }
+                    seen.push(container.clone());
+                    current = container.clone();
+                }
+                CloneReason::ArrayAccess { line, array, element, element_line } => {
+                    steps.push(DeriveStep {
+                        line: *line,
+                        code: code(*line),
+                        note: format!("copies an element out of `{}`", array),
+                    });
+                    steps.push(DeriveStep {
+                        line: *element_line,
+                        code: code(*element_line),
+                        note: format!("`{}` holds `{}` elements", array, element),
+                    });
+                    break;
+                }
+                CloneReason::LoopClone { line, array, element, element_line } => {
+                    steps.push(DeriveStep {
+                        line: *line,
+                        code: code(*line),
+                        note: format!("iterates a clone of `{}`", array),
+                    });
+                    steps.push(DeriveStep {
+                        line: *element_line,
+                        code: code(*element_line),
+                        note: format!("`{}` holds `{}` elements", array, element),
+                    });
+                    break;
+                }
+                CloneReason::ByValueParam { line, function, param, param_type } => {
+                    steps.push(DeriveStep {
+                        line: *line,
+                        code: code(*line),
+                        note: format!("`{}` takes `{} {}` by value, so arguments are cloned", function, param, param_type),
+                    });
+                    break;
+                }
+            }
+        }
+        DeriveExplanation { type_name: type_name.clone(), steps }
+    }).collect()
+}
+
+/// Render the explanations for the terminal
+pub fn format_derive_report(explanations: &[DeriveExplanation], file_name: &str) -> String {
+    use crate::anti_fail_logic::ansi;
+
+    let mut out = String::new();
+    out.push_str(&format!("{}Derived Clone in {}{}\n\n", ansi::BOLD_CYAN, file_name, ansi::RESET));
+
+    if explanations.is_empty() {
+        out.push_str(&format!("  {}✓{} no `#[derive(Clone)]` is injected\n", ansi::BOLD_GREEN, ansi::RESET));
+        return out;
+    }
+
+    for e in explanations {
+        out.push_str(&format!("  {}{}{} gets #[derive(Clone)]\n", ansi::BOLD_YELLOW, e.type_name, ansi::RESET));
+        for step in &e.steps {
+            out.push_str(&format!("    {}{}:{}{}  {}\n", ansi::CYAN, file_name, step.line, ansi::RESET, step.code));
+            out.push_str(&format!("      {}\n", step.note));
+        }
+    }
+    out.push_str(&format!("\n  {} derive(s) injected\n", explanations.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines_of(e: &DeriveExplanation) -> Vec<usize> {
+        e.steps.iter().map(|s| s.line).collect()
+    }
+
+    #[test]
+    fn test_array_access_chain() {
+        let src = "struct Node {\n    id u32\n}\n\nenum Event {\n    Init(Node)\n    Stop\n}\n\nfn main() {\n    events = [\n        Event::Stop,\n    ]\n    e = events[0]\n}";
+        let found = explain_derives(src);
+        let names: Vec<&str> = found.iter().map(|e| e.type_name.as_str()).collect();
+        assert_eq!(names, vec!["Event", "Node"]);
+        assert_eq!(lines_of(&found[0]), vec![14, 12]);
+        assert_eq!(lines_of(&found[1]), vec![6, 14, 12]);
+        assert_eq!(found[1].steps[0].code, "Init(Node)");
+        assert_eq!(found[1].steps[0].note, "`Node` is contained in `Event`");
+    }
+
+    #[test]
+    fn test_by_value_param() {
+        let src = "struct User {\n    name String\n}\n\nfn show(u User) {\n    println!(\"{}\", u.name)\n}";
+        let found = explain_derives(src);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].steps[0].line, 5);
+        assert!(found[0].steps[0].note.contains("`show` takes `u User` by value"));
+    }
+
+    #[test]
+    fn test_loop_clone() {
+        let src = "struct User {\n    name String\n}\n\nfn main() {\n    users Vec[User] = load()\n    for u in users {\n        keep.push(u)\n    }\n    println!(\"{}\", users.len())\n}";
+        let found = explain_derives(src);
+        assert_eq!(found.len(), 1);
+        assert_eq!(lines_of(&found[0]), vec![7, 6]);
+        assert_eq!(found[0].steps[0].note, "iterates a clone of `users`");
+    }
+
+    #[test]
+    fn test_no_derives() {
+        let src = "struct P {\n    x i32\n}\n\nfn main() {\n    p = P { x = 1 }\n}";
+        assert!(explain_derives(src).is_empty());
+        assert!(format_derive_report(&[], "a.rss").contains("no `#[derive(Clone)]`"));
+    }
+}
@@ -9,8 +9,13 @@
 //! CRITICAL FIX: All detection functions must ignore braces inside string literals!
 //! Example: `anyhow::bail("header {} mismatch")` should NOT trigger literal detection
 //! because the `{` is inside a string.
+//!
+//! Whether a line leaves its literal open for the following lines is decided
+//! by the statement parser ([`parse_line`]); bracket counting is only the
+//! fallback for lines it does not parse.
 
-use crate::helpers::{is_rust_block_start, is_valid_identifier};
+use crate::helpers::{char_to_byte_index, is_rust_block_start, is_valid_identifier};
+use crate::parser::{parse_line, LineShape, Open};
 use crate::struct_def::StructRegistry;
 
 //===========================================================================
@@ -24,7 +29,7 @@ fn find_brace_outside_string(s: &str) -> Option<usize> {
     let mut in_string = false;
     let mut escape_next = false;
     
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         if escape_next {
             escape_next = false;
             continue;
@@ -178,7 +183,7 @@ fn find_assignment_eq_position(s: &str) -> Option<usize> {
             }
             
             // Found a standalone assignment `=`
-            return Some(i);
+            return Some(char_to_byte_index(s, i));
         }
         
         prev_char = c;
@@ -187,6 +192,34 @@ fn find_assignment_eq_position(s: &str) -> Option<usize> {
     None
 }
 
+/// Does the line leave a struct or enum literal open (`f(a, User {`)?
+fn leaves_literal_open(trimmed: &str) -> bool {
+    match parse_line(trimmed) {
+        LineShape::Open { opens, .. } => opens.iter().any(|open| matches!(open, Open::Struct(_))),
+        LineShape::Complete(_) => false,
+        LineShape::Unparsed => trimmed.matches('{').count() > trimmed.matches('}').count(),
+    }
+}
+
+/// Does the line leave an array open (`names = ["]",`)? `rhs` is the
+/// part after the assignment, for the counting fallback.
+fn leaves_array_open(trimmed: &str, rhs: &str) -> bool {
+    match parse_line(trimmed) {
+        LineShape::Open { opens, .. } => opens.contains(&Open::Array),
+        LineShape::Complete(_) => false,
+        LineShape::Unparsed => {
+            let close_brackets = rhs.matches(']').count();
+            rhs.matches('[').count() != close_brackets || close_brackets == 0
+        }
+    }
+}
+
+/// The entries after `HashMap::from([` or `std::collections::HashMap::from([`
+pub fn map_from_array(rhs: &str) -> Option<&str> {
+    let rest = rhs.strip_prefix("std::collections::").unwrap_or(rhs);
+    rest.strip_prefix("HashMap::from([")
+}
+
 /// Check if line starts with control flow keyword that should never be detected as literal
 fn is_control_flow_start(s: &str) -> bool {
     let trimmed = s.trim();
@@ -248,9 +281,10 @@ pub fn detect_struct_literal_start(line: &str, registry: &StructRegistry) -> Opt
         None => return None,
     };
     
-    // EXCLUDE enum paths (:: before {)
+    // EXCLUDE enum paths (:: before {) and literals passed to a call
+    // (`x = Some(User {`, see `detect_struct_literal_in_call`)
     let before_brace = &rhs[..brace_pos];
-    if before_brace.contains("::") {
+    if before_brace.contains("::") || before_brace.contains('(') {
         return None;
     }
     
@@ -430,8 +464,8 @@ pub fn detect_struct_literal_in_call(line: &str, registry: &StructRegistry) -> O
         _ => return None,
     };
     
-    // Extract text between `(` and `{`
-    let between = trimmed[paren_pos + 1..brace_pos].trim();
+    // Extract text between `(` and `{` (the last argument: `f(a, User {`)
+    let between = trimmed[paren_pos + 1..brace_pos].rsplit(',').next().unwrap_or("").trim();
     
     // Must be a valid struct name (PascalCase identifier or registered struct)
     if between.is_empty() {
@@ -455,10 +489,7 @@ pub fn detect_struct_literal_in_call(line: &str, registry: &StructRegistry) -> O
     }
     
     // Check that brace is unclosed (multi-line struct literal)
-    let opens = trimmed.matches('{').count();
-    let closes = trimmed.matches('}').count();
-    
-    if opens > closes {
+    if leaves_literal_open(trimmed) {
         return Some(struct_name);
     }
     
@@ -491,7 +522,7 @@ pub fn detect_enum_literal_in_call(line: &str) -> Option<String> {
         _ => return None,
     };
     
-    let between = &trimmed[paren_pos + 1..brace_pos];
+    let between = trimmed[paren_pos + 1..brace_pos].rsplit(',').next().unwrap_or("");
     
     // Must have ::
     if !between.contains("::") {
@@ -507,10 +538,7 @@ pub fn detect_enum_literal_in_call(line: &str) -> Option<String> {
             let first_char = variant.chars().next().unwrap();
             if first_char.is_uppercase() {
                 // Check that brace is unclosed
-                let opens = trimmed.matches('{').count();
-                let closes = trimmed.matches('}').count();
-                
-                if opens > closes {
+                if leaves_literal_open(trimmed) {
                     return Some(enum_path.to_string());
                 }
             }
@@ -630,6 +658,9 @@ pub fn detect_array_literal_start(line: &str) -> Option<(String, Option<String>,
     } else if rhs.starts_with("Vec::from([") {
         // Vec::from: `Vec::from([...])`
         (true, &rhs[11..])
+    } else if let Some(after) = map_from_array(rhs) {
+        // Map literal: `HashMap::from([(k, v), ...])`
+        (true, after)
     } else {
         (false, rhs)
     };
@@ -638,12 +669,9 @@ pub fn detect_array_literal_start(line: &str) -> Option<(String, Option<String>,
         return None;
     }
     
-    // If the line ends with ] or ];, it's a single-line array - let normal handling take it
-    // Count brackets in the ENTIRE rhs to check if complete
-    let open_brackets = rhs.matches('[').count();
-    let close_brackets = rhs.matches(']').count();
-    if open_brackets == close_brackets && close_brackets > 0 {
-        return None; // Complete on one line
+    // A single-line array is left to normal handling
+    if !leaves_array_open(trimmed, rhs) {
+        return None;
     }
     
     // Extract var name and optional type
@@ -835,6 +863,18 @@ mod tests {
         // Single-line array should return None
         let result = detect_array_literal_start("arr = [1, 2, 3]");
         assert!(result.is_none());
+        
+        // Brackets in strings are not counted
+        assert!(detect_array_literal_start("open = [\"[\", \"(\"]").is_none());
+        assert!(detect_array_literal_start("close = [\"]\",").is_some());
+    }
+    
+    #[test]
+    fn test_literal_in_call_ignores_braces_in_strings() {
+        let registry = StructRegistry::new();
+        assert_eq!(detect_struct_literal_in_call("items.push(Item { label = \"}\",", &registry), Some("Item".to_string()));
+        assert_eq!(detect_struct_literal_in_call("items.push(Item { label = \"{\" })", &registry), None);
+        assert_eq!(detect_enum_literal_in_call("send(Msg::Text { body = \"}\","), Some("Msg::Text".to_string()));
     }
     
     //=========================================================================
@@ -0,0 +1,198 @@
+//! Diagnostic Grouping
+//!
+//! One mistake repeated on many lines should not bury the rest of the
+//! report. Diagnostics are grouped per function, code and title, so two
+//! different mistakes under one code (two undeclared effects, two unknown
+//! variants) stay apart; each group is printed as its first diagnostic in
+//! full, with the group's size in the title and the next few locations
+//! listed in its note:
+//!
+//! ```text
+//! error[RSPL081][scope]: variable `total` not found (and 49 more like it in `report`)
+//!   --> main.rss:12:5
+//!   ...
+//! note:
+//!   ...
+//!   also at:
+//!     13:5  variable `total` not found
+//!     14:5  variable `total` not found
+//!   47 more not shown (--verbose-diagnostics lists every diagnostic)
+//! ```
+//!
+//! `--diagnostic-locations N` sets how many locations a group shows (the
+//! full one included); `--verbose-diagnostics` turns grouping off. Counts
+//! such as the exit summary's `violations=` always cover every diagnostic.
+
+use crate::error_msg::{ErrorCode, RsplError};
+use crate::function::strip_fn_modifiers;
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::verbatim::mask_verbatim_blocks;
+
+/// Locations a group shows when `--diagnostic-locations` is not given
+pub const DEFAULT_DIAGNOSTIC_LOCATIONS: usize = 3;
+
+/// `fn name(` / `pub async fn name[T](` → `name`
+fn fn_header_name(code: &str) -> Option<String> {
+    let mut rest = code.trim_start();
+    for prefix in ["pub(crate) ", "pub ", "const ", "async ", "unsafe "] {
+        rest = rest.strip_prefix(prefix).unwrap_or(rest);
+    }
+    let rest = rest.strip_prefix("fn ")?.trim_start();
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// The function each (0-based) line of `source` belongs to
+pub fn enclosing_functions(source: &str) -> Vec<Option<String>> {
+    let (masked, _) = mask_verbatim_blocks(source);
+    let (stripped, _) = strip_fn_modifiers(&masked);
+
+    // (name, depth at the header, body opened)
+    let mut open: Vec<(String, usize, bool)> = Vec::new();
+    let mut depth = 0usize;
+    let mut functions = Vec::new();
+    for line in stripped.lines() {
+        let code = strip_inline_comment(line);
+        if let Some(name) = fn_header_name(&code) {
+            open.push((name, depth, false));
+        }
+        functions.push(open.last().map(|(name, _, _)| name.clone()));
+
+        let (opens, closes) = count_braces_outside_strings(&code);
+        depth = (depth + opens).saturating_sub(closes);
+        while let Some((_, header_depth, opened)) = open.last_mut() {
+            if depth > *header_depth {
+                *opened = true;
+                break;
+            }
+            // A signature continued on the next line has not opened yet
+            if !*opened && code.trim_end().ends_with(['(', ',']) {
+                break;
+            }
+            open.pop();
+        }
+    }
+    functions
+}
+
+/// Group `errors` per function, code and title, in order of first appearance.
+/// Each group becomes its first diagnostic, naming the group's size and up
+/// to `locations` locations in all.
+pub fn group_diagnostics(errors: &[RsplError], source: &str, locations: usize) -> Vec<RsplError> {
+    let functions = enclosing_functions(source);
+    let function_of = |error: &RsplError| -> Option<String> {
+        functions.get(error.location.line.wrapping_sub(1)).cloned().flatten()
+    };
+
+    let mut groups: Vec<(Option<String>, ErrorCode, Vec<&RsplError>)> = Vec::new();
+    for error in errors {
+        let function = function_of(error);
+        let same = |f: &Option<String>, code: &ErrorCode, members: &[&RsplError]| {
+            *f == function && *code == error.code && members[0].title == error.title
+        };
+        match groups.iter_mut().find(|(f, code, members)| same(f, code, members)) {
+            Some((_, _, members)) => members.push(error),
+            None => groups.push((function, error.code, vec![error])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(function, _, members)| {
+            let mut first = members[0].clone();
+            if members.len() == 1 {
+                return first;
+            }
+            let rest = &members[1..];
+            let place = function.map(|f| format!(" in `{}`", f)).unwrap_or_default();
+            first.title = format!("{} (and {} more like it{})", first.title, rest.len(), place);
+
+            let shown = locations.saturating_sub(1).min(rest.len());
+            let mut note: Vec<String> = Vec::new();
+            if shown > 0 {
+                note.push("also at:".to_string());
+                for error in &rest[..shown] {
+                    note.push(format!("  {}:{}  {}", error.location.line, error.location.column, error.title));
+                }
+            }
+            if rest.len() > shown {
+                note.push(format!("{} more not shown (--verbose-diagnostics lists every diagnostic)", rest.len() - shown));
+            }
+            let note = note.join("\n");
+            first.explanation = Some(match first.explanation.take() {
+                Some(explanation) => format!("{}\n\n{}", explanation, note),
+                None => note,
+            });
+            first
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_msg::SourceLocation;
+
+    const SRC: &str = "fn report(\n    n i32,\n) {\n    a = x\n    b = x\n    c = x\n}\n\nimpl T {\n    inline fn get() i32 = y\n    fn other() {\n        d = x\n    }\n}";
+
+    fn error(code: ErrorCode, line: usize) -> RsplError {
+        RsplError::new(code, format!("bad {}", code.code_str())).at(SourceLocation::new("m.rss", line, 5))
+    }
+
+    #[test]
+    fn test_enclosing_functions() {
+        let functions = enclosing_functions(SRC);
+        let names: Vec<Option<&str>> = functions.iter().map(|f| f.as_deref()).collect();
+        assert!(names[..7].iter().all(|f| *f == Some("report")));
+        assert_eq!(names[7..9], [None, None]);
+        assert_eq!(names[9], Some("get"));
+        assert_eq!(names[11], Some("other"));
+        assert_eq!(names[13], None);
+    }
+
+    #[test]
+    fn test_groups_per_function_and_code() {
+        let errors = vec![
+            error(ErrorCode::RSPL081, 4),
+            error(ErrorCode::RSPL081, 5),
+            error(ErrorCode::RSPL300, 1),
+            error(ErrorCode::RSPL081, 6),
+            error(ErrorCode::RSPL081, 12),
+        ];
+        let grouped = group_diagnostics(&errors, SRC, 2);
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(grouped[0].title, "bad RSPL081 (and 2 more like it in `report`)");
+        assert_eq!(
+            grouped[0].explanation.as_deref(),
+            Some("also at:\n  5:5  bad RSPL081\n1 more not shown (--verbose-diagnostics lists every diagnostic)")
+        );
+        assert_eq!(grouped[1].title, "bad RSPL300");
+        assert_eq!((grouped[2].title.as_str(), grouped[2].location.line), ("bad RSPL081", 12));
+    }
+
+    #[test]
+    fn test_every_location_shown() {
+        let errors = vec![error(ErrorCode::RSPL081, 4), error(ErrorCode::RSPL081, 5)];
+        let grouped = group_diagnostics(&errors, SRC, DEFAULT_DIAGNOSTIC_LOCATIONS);
+        assert_eq!(
+            grouped[0].explanation.as_deref(),
+            Some("also at:\n  5:5  bad RSPL081")
+        );
+    }
+
+    #[test]
+    fn test_different_titles_stay_apart() {
+        let undeclared = |line: usize, effect: &str| {
+            RsplError::new(ErrorCode::RSPL300, format!("effect `{}` not declared", effect))
+                .help(format!("add `{}` to the effects clause", effect))
+                .at(SourceLocation::new("m.rss", line, 5))
+        };
+        let errors = vec![undeclared(4, "write(acc.owner)"), undeclared(5, "alloc"), undeclared(6, "write(acc.owner)")];
+        let grouped = group_diagnostics(&errors, SRC, DEFAULT_DIAGNOSTIC_LOCATIONS);
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].title, "effect `write(acc.owner)` not declared (and 1 more like it in `report`)");
+        assert_eq!(grouped[1].title, "effect `alloc` not declared");
+        assert_eq!(grouped[1].suggestion.as_deref(), Some("add `alloc` to the effects clause"));
+    }
+}
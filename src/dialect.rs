@@ -0,0 +1,210 @@
+//! Per-Function Dialect Detection
+//!
+//! A `.rss` file may mix RustS+ functions with functions that are already
+//! plain Rust:
+//!
+//! ```text
+//! fn describe(kind: &str) -> String {
+//!     let label = match kind {
+//!         "a" => "alpha",
+//!         _ => "other",
+//!     };
+//!     label.to_string()
+//! }
+//! ```
+//!
+//! Lowering such a function again double-transforms it (extra `String::from`,
+//! `.as_str()` in `=>` matches, a `;` after the closing brace). Each function
+//! is classified by its signature style and body markers; Rust-dialect
+//! functions are masked exactly like `rust { ... }` blocks and emitted
+//! untouched. Their signatures are still returned so RustS+ call sites get
+//! argument coercion.
+
+use crate::function::{parse_function_line, parse_rust_signature, FunctionParseResult, FunctionSignature};
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::verbatim::{marker_line, VerbatimBlock};
+
+/// Dialect a function is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    RustSPlus,
+    Rust,
+}
+
+/// RustS+ macro calls written without `!`
+const RUSTSP_MACRO_CALLS: &[&str] = &["println(", "print(", "eprintln(", "format(", "panic(", "assert("];
+
+/// Signature written in Rust style: `name: Type` parameters or a `->` return
+fn is_rust_signature(signature: &str) -> bool {
+    match parse_function_line(signature) {
+        FunctionParseResult::RustPassthrough => true,
+        FunctionParseResult::RustSPlusSignature(sig) => {
+            sig.parameters.is_empty() && signature.contains("->") && !signature.contains("effects(")
+        }
+        _ => false,
+    }
+}
+
+/// Body line that only RustS+ accepts
+fn is_rustsp_marker(trimmed: &str) -> bool {
+    if trimmed.starts_with("mut ") || trimmed.starts_with("outer ") {
+        return true;
+    }
+    if RUSTSP_MACRO_CALLS.iter().any(|m| {
+        trimmed.match_indices(m).any(|(pos, _)| {
+            pos == 0 || !trimmed[..pos].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+        })
+    }) {
+        return true;
+    }
+    // Bare binding / assignment without `let` and without `;`: `x = 1`, `x i32 = 1`
+    let is_statement_keyword = ["let ", "if ", "while ", "for ", "match ", "return "]
+        .iter()
+        .any(|k| trimmed.starts_with(k));
+    !is_statement_keyword
+        && trimmed.contains(" = ")
+        && !trimmed.ends_with([';', '{', ',', '('])
+}
+
+/// Body line that only plain Rust uses
+fn is_rust_marker(trimmed: &str) -> bool {
+    trimmed.starts_with("let ") || trimmed.ends_with(';') || trimmed.contains("=>")
+}
+
+/// Classify a function from its (joined) signature and body lines
+pub fn function_dialect(signature: &str, body: &[&str]) -> Dialect {
+    if !is_rust_signature(signature) {
+        return Dialect::RustSPlus;
+    }
+    let lines = body.iter().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with("//"));
+    let mut has_rust_marker = false;
+    for trimmed in lines {
+        if is_rustsp_marker(trimmed) {
+            return Dialect::RustSPlus;
+        }
+        has_rust_marker |= is_rust_marker(trimmed);
+    }
+    if has_rust_marker { Dialect::Rust } else { Dialect::RustSPlus }
+}
+
+fn is_fn_start(trimmed: &str) -> bool {
+    trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ")
+}
+
+/// Mask every Rust-dialect function in `source` as a verbatim block
+/// appended to `blocks`. Line count is preserved.
+///
+/// Returns the masked source and the signatures of the masked functions.
+pub fn mask_rust_functions(source: &str, blocks: &mut Vec<VerbatimBlock>) -> (String, Vec<FunctionSignature>) {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut signatures = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if !is_fn_start(trimmed) {
+            out.push(lines[i].to_string());
+            i += 1;
+            continue;
+        }
+
+        // Signature (possibly multi-line) up to the body's opening brace
+        let mut signature = trimmed.to_string();
+        let mut sig_end = i;
+        while !signature.contains('{') && !signature.ends_with(';') && sig_end + 1 < lines.len() {
+            sig_end += 1;
+            signature.push(' ');
+            signature.push_str(lines[sig_end].trim());
+        }
+        let signature = match signature.find('{') {
+            Some(pos) if !signature.ends_with(';') => signature[..=pos].to_string(),
+            _ => {
+                out.push(lines[i].to_string());
+                i += 1;
+                continue;
+            }
+        };
+
+        // Body: until the brace opened by the signature closes
+        let mut depth: i64 = 0;
+        let mut end = None;
+        for (j, line) in lines.iter().enumerate().skip(i) {
+            let (opens, closes) = count_braces_outside_strings(line.trim());
+            depth += opens as i64 - closes as i64;
+            if j >= sig_end && depth <= 0 {
+                end = Some(j);
+                break;
+            }
+        }
+        let Some(end) = end else {
+            // Unterminated - leave it for the brace pre-check
+            out.push(lines[i].to_string());
+            i += 1;
+            continue;
+        };
+
+        let body = if end > sig_end { &lines[sig_end + 1..end] } else { &[][..] };
+        if function_dialect(&signature, body) == Dialect::RustSPlus {
+            out.push(lines[i].to_string());
+            i += 1;
+            continue;
+        }
+
+        if let Some(sig) = parse_rust_signature(&signature) {
+            signatures.push(sig);
+        }
+        let leading_ws: String = lines[i].chars().take_while(|c| c.is_whitespace()).collect();
+        out.push(marker_line(&leading_ws, blocks.len(), &[]));
+        for _ in i + 1..=end {
+            out.push(String::new());
+        }
+        blocks.push(VerbatimBlock {
+            line: i + 1,
+            effects: Vec::new(),
+            body: lines[i..=end].join("\n"),
+            span: end - i,
+            starts_on_header: true,
+        });
+        i = end + 1;
+    }
+
+    let mut masked = out.join("\n");
+    if source.ends_with('\n') {
+        masked.push('\n');
+    }
+    (masked, signatures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_dialect() {
+        let rust_body = ["    let x = kind.len();", "    x"];
+        assert_eq!(function_dialect("fn f(kind: &str) -> usize {", &rust_body), Dialect::Rust);
+        // Rust signature, RustS+ body
+        assert_eq!(function_dialect("fn f(kind: &str) -> usize {", &["    x = kind.len()", "    x"]), Dialect::RustSPlus);
+        assert_eq!(function_dialect("fn f(kind: &str) {", &["    println(\"{}\", kind);"]), Dialect::RustSPlus);
+        // RustS+ signature
+        assert_eq!(function_dialect("fn f(kind String) usize {", &rust_body), Dialect::RustSPlus);
+        // Nothing Rust-specific in the body - lowering it is harmless
+        assert_eq!(function_dialect("fn add(a: i32, b: i32) -> i32 {", &["    a + b"]), Dialect::RustSPlus);
+    }
+
+    #[test]
+    fn test_mask_rust_functions() {
+        let src = "fn describe(kind: &str) -> String {\n    let label = match kind {\n        \"a\" => \"alpha\",\n        _ => \"other\",\n    };\n    label.to_string()\n}\n\nfn main() {\n    k = describe(\"a\")\n}";
+        let mut blocks = Vec::new();
+        let (masked, signatures) = mask_rust_functions(src, &mut blocks);
+        assert_eq!(masked.lines().count(), src.lines().count());
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0].body.starts_with("fn describe(kind: &str) -> String {"));
+        assert!(blocks[0].body.ends_with('}'));
+        assert!(masked.contains("k = describe(\"a\")"));
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].name, "describe");
+        assert_eq!(signatures[0].parameters[0].param_type, "&str");
+    }
+}
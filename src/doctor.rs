@@ -0,0 +1,202 @@
+//! Toolchain Diagnostics (`rustsp doctor`)
+//!
+//! RustS+ hands its output to rustc in Stage 3, so a missing or outdated
+//! toolchain used to surface only after Stages 0-2 had run. `rustsp doctor`
+//! checks the environment up front and prints a fix for every problem:
+//!
+//! ```text
+//! ✓ rustc        rustc 1.80.0 (051478957 2024-07-21)
+//! ✓ cargo        cargo 1.80.0 (376290515 2024-07-16)
+//! ✗ target       wasm32-unknown-unknown is not installed
+//!     fix: rustup target add wasm32-unknown-unknown
+//! ```
+//!
+//! The compiler driver runs [`toolchain_preflight`] before Stage 0 when the
+//! run will end in Stage 3.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Oldest rustc the generated code is tested against (edition 2021)
+pub const MIN_RUSTC_VERSION: (u32, u32, u32) = (1, 56, 0);
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    /// Works, but some features are unavailable
+    Warning,
+    /// Compilation will fail
+    Error,
+}
+
+/// One environment check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to run or change to fix the problem
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: String) -> Self {
+        Check { name, status: CheckStatus::Ok, detail, fix: None }
+    }
+
+    fn failed(name: &'static str, status: CheckStatus, detail: String, fix: &str) -> Self {
+        Check { name, status, detail, fix: Some(fix.to_string()) }
+    }
+}
+
+/// `rustc 1.80.0 (051478957 2024-07-21)` → `(1, 80, 0)`
+pub fn parse_rustc_version(version: &str) -> Option<(u32, u32, u32)> {
+    let number = version.split_whitespace().nth(1)?;
+    // Drop pre-release suffixes: `1.82.0-nightly`
+    let number = number.split('-').next()?;
+    let mut parts = number.split('.').map(|p| p.parse::<u32>().ok());
+    Some((parts.next()??, parts.next()??, parts.next().flatten().unwrap_or(0)))
+}
+
+/// First line of `<tool> --version`, or `None` when the tool cannot run
+fn tool_version(tool: &str) -> Option<String> {
+    let output = Command::new(tool).arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let version = String::from_utf8_lossy(&output.stdout).lines().next()?.trim().to_string();
+    (!version.is_empty()).then_some(version)
+}
+
+/// Classify a `rustc --version` result
+pub fn check_rustc_version(version: Option<&str>) -> Check {
+    let Some(version) = version else {
+        return Check::failed(
+            "rustc",
+            CheckStatus::Error,
+            "rustc was not found in PATH".to_string(),
+            "install Rust from https://rustup.rs and make sure `rustc` is in PATH",
+        );
+    };
+    match parse_rustc_version(version) {
+        Some(found) if found < MIN_RUSTC_VERSION => {
+            let (major, minor, patch) = MIN_RUSTC_VERSION;
+            Check::failed(
+                "rustc",
+                CheckStatus::Error,
+                format!("{} is older than the supported minimum {}.{}.{}", version, major, minor, patch),
+                "rustup update stable",
+            )
+        }
+        Some(_) => Check::ok("rustc", version.to_string()),
+        None => Check::failed(
+            "rustc",
+            CheckStatus::Warning,
+            format!("could not read a version from `{}`", version),
+            "check that `rustc --version` prints `rustc X.Y.Z`",
+        ),
+    }
+}
+
+/// rustc present and recent enough
+pub fn check_rustc() -> Check {
+    check_rustc_version(tool_version("rustc").as_deref())
+}
+
+/// cargo is only needed for cargo-rustsp projects
+pub fn check_cargo() -> Check {
+    match tool_version("cargo") {
+        Some(version) => Check::ok("cargo", version),
+        None => Check::failed(
+            "cargo",
+            CheckStatus::Warning,
+            "cargo was not found; single files still compile, `cargo rustsp` projects do not".to_string(),
+            "install Rust with rustup (it ships cargo) or add cargo to PATH",
+        ),
+    }
+}
+
+/// Standard library for `target` (the host when `None`) is installed
+pub fn check_target(target: Option<&str>) -> Check {
+    let sysroot = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    let Some(sysroot) = sysroot else {
+        return Check::failed(
+            "target",
+            CheckStatus::Error,
+            "could not query the rustc sysroot".to_string(),
+            "fix the rustc installation first",
+        );
+    };
+    let rustlib = Path::new(&sysroot).join("lib").join("rustlib");
+    match target {
+        Some(triple) if rustlib.join(triple).join("lib").is_dir() => {
+            Check::ok("target", format!("{} installed", triple))
+        }
+        Some(triple) => Check::failed(
+            "target",
+            CheckStatus::Error,
+            format!("{} is not installed", triple),
+            &format!("rustup target add {}", triple),
+        ),
+        None => {
+            let has_std = std::fs::read_dir(&rustlib)
+                .map(|entries| entries.filter_map(|e| e.ok()).any(|e| e.path().join("lib").is_dir()))
+                .unwrap_or(false);
+            if has_std {
+                Check::ok("target", "host standard library installed".to_string())
+            } else {
+                Check::failed(
+                    "target",
+                    CheckStatus::Error,
+                    format!("no standard library found under {}", rustlib.display()),
+                    "rustup component add rust-std",
+                )
+            }
+        }
+    }
+}
+
+/// All checks run by `rustsp doctor`
+pub fn run_checks(target: Option<&str>) -> Vec<Check> {
+    let rustc = check_rustc();
+    let rustc_ok = rustc.status != CheckStatus::Error;
+    let mut checks = vec![rustc, check_cargo()];
+    if rustc_ok {
+        checks.push(check_target(target));
+    }
+    checks
+}
+
+/// Fast toolchain check before a run that ends in Stage 3.
+/// Returns the failing check so the driver can report it before lowering.
+pub fn toolchain_preflight() -> Result<(), Check> {
+    let rustc = check_rustc();
+    if rustc.status == CheckStatus::Error {
+        return Err(rustc);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rustc_version_checks() {
+        assert_eq!(parse_rustc_version("rustc 1.80.0 (051478957 2024-07-21)"), Some((1, 80, 0)));
+        assert_eq!(parse_rustc_version("rustc 1.82.0-nightly (abc 2024-08-01)"), Some((1, 82, 0)));
+        assert_eq!(parse_rustc_version("garbage"), None);
+
+        assert_eq!(check_rustc_version(Some("rustc 1.80.0 (x 2024-07-21)")).status, CheckStatus::Ok);
+        let old = check_rustc_version(Some("rustc 1.40.0 (x 2019-12-16)"));
+        assert_eq!(old.status, CheckStatus::Error);
+        assert_eq!(old.fix.as_deref(), Some("rustup update stable"));
+        assert_eq!(check_rustc_version(None).status, CheckStatus::Error);
+    }
+}
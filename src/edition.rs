@@ -0,0 +1,295 @@
+//! Rust Editions
+//!
+//! `--edition 2015|2018|2021|2024` picks the edition of the generated Rust.
+//! It is passed to rustc, written to the output header and to
+//! `rust-project.json`, and changes a few lowering details:
+//!
+//! | Construct                  | 2015       | 2018       | 2021+        |
+//! |----------------------------|------------|------------|--------------|
+//! | `panic("{x}")`             | `x = x` added to the call | `x = x` added | as written |
+//! | `Box[Display]`, `&Error`   | as written | as written | `dyn` added  |
+//! | `async fn`, `.await`       | RSPL028    | allowed    | allowed      |
+//!
+//! Before 2021 a lone format string in `panic!` is not formatted, so names
+//! captured by a format string get explicit named arguments in every
+//! formatting macro. From 2021 on a bare trait object is an error; `dyn` is
+//! added in front of traits declared in the source and the common std
+//! traits, unless the source declares a type of the same name.
+
+use std::collections::HashSet;
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{delimiter_positions, find_matching_close, split_top_level_types};
+
+/// A Rust edition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Edition {
+    E2015,
+    E2018,
+    /// What the lowering targets when no edition is given
+    #[default]
+    E2021,
+    E2024,
+}
+
+impl Edition {
+    /// `"2015"`, `"2018"`, `"2021"` or `"2024"`
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim() {
+            "2015" => Some(Edition::E2015),
+            "2018" => Some(Edition::E2018),
+            "2021" => Some(Edition::E2021),
+            "2024" => Some(Edition::E2024),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Edition::E2015 => "2015",
+            Edition::E2018 => "2018",
+            Edition::E2021 => "2021",
+            Edition::E2024 => "2024",
+        }
+    }
+
+    /// Does a format string capture names (`"{x}"`) in every macro?
+    pub fn formats_captured_args(&self) -> bool {
+        *self >= Edition::E2021
+    }
+
+    /// Are bare trait objects (`Box<Display>`) an error?
+    pub fn requires_dyn(&self) -> bool {
+        *self >= Edition::E2021
+    }
+
+    pub fn has_async(&self) -> bool {
+        *self >= Edition::E2018
+    }
+}
+
+/// Std traits commonly used as trait objects
+const STD_OBJECT_TRAITS: &[&str] = &[
+    "Any", "Debug", "Display", "Error", "Fn", "FnMut", "FnOnce", "Future", "Iterator",
+];
+
+/// Formatting macros and the position of their format string argument
+const FORMAT_MACROS: &[(&str, usize)] = &[
+    ("println", 0), ("print", 0), ("eprintln", 0), ("eprint", 0), ("format", 0),
+    ("panic", 0), ("unreachable", 0), ("todo", 0), ("unimplemented", 0),
+    ("write", 1), ("writeln", 1), ("assert", 1),
+    ("assert_eq", 2), ("assert_ne", 2),
+];
+
+/// Traits that get `dyn` when used as a bare trait object in `source`
+pub fn trait_object_names(source: &str) -> HashSet<String> {
+    let mut traits: HashSet<String> = STD_OBJECT_TRAITS.iter().map(|t| t.to_string()).collect();
+    let mut types = HashSet::new();
+    for line in source.lines() {
+        let trimmed = line.trim().strip_prefix("pub ").unwrap_or(line.trim());
+        for keyword in ["trait ", "struct ", "enum ", "type "] {
+            let Some(rest) = trimmed.strip_prefix(keyword) else {
+                continue;
+            };
+            let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            if name.is_empty() {
+                continue;
+            }
+            if keyword == "trait " {
+                traits.insert(name);
+            } else {
+                types.insert(name);
+            }
+        }
+    }
+    traits.retain(|t| !types.contains(t));
+    traits
+}
+
+/// `Box<Display>` → `Box<dyn Display>`, `&mut Write` → `&mut dyn Write`
+/// for every name in `traits`
+pub fn add_dyn(line: &str, traits: &HashSet<String>) -> String {
+    let mut result = line.to_string();
+    for prefix in ["Box<", "Rc<", "Arc<", "&mut ", "&"] {
+        let mut search_from = 0;
+        while let Some(found) = result[search_from..].find(prefix) {
+            let at = search_from + found + prefix.len();
+            search_from = at;
+            let name: String = result[at..].chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            let next = result[at + name.len()..].chars().next();
+            let is_trait_object = traits.contains(&name)
+                && !matches!(next, Some(':') | Some('{'))
+                && !result[..at].ends_with("dyn ")
+                && !in_string(&result, at);
+            if is_trait_object {
+                result.insert_str(at, "dyn ");
+                search_from = at + 4 + name.len();
+            }
+        }
+    }
+    result
+}
+
+/// Give every name a format string captures (`"{x} {y:?}"`) an explicit
+/// `x = x` argument: `panic!("{x}")` → `panic!("{x}", x = x)`
+pub fn explicit_format_args(line: &str) -> String {
+    let mut result = line.to_string();
+    // Right to left, so earlier positions stay valid
+    for (name, fmt_index) in FORMAT_MACROS {
+        let pattern = format!("{}!(", name);
+        let starts: Vec<usize> = result.match_indices(&pattern).map(|(p, _)| p).collect();
+        for start in starts.into_iter().rev() {
+            let before = result[..start].chars().next_back();
+            if matches!(before, Some(c) if c.is_alphanumeric() || c == '_') || in_string(&result, start) {
+                continue;
+            }
+            let open = start + pattern.len() - 1;
+            let Some(close) = find_matching_close(&result, open) else {
+                continue;
+            };
+            let args = split_top_level_types(&result[open + 1..close], ',');
+            let Some(fmt) = args.get(*fmt_index).map(|a| a.trim()) else {
+                continue;
+            };
+            if !(fmt.starts_with('"') && fmt.ends_with('"') && fmt.len() >= 2) {
+                continue;
+            }
+            let named: HashSet<&str> = args.iter()
+                .filter_map(|a| a.split_once('='))
+                .filter(|(n, value)| !value.starts_with('=') && crate::ident::is_valid_identifier(n.trim()))
+                .map(|(n, _)| n.trim())
+                .collect();
+            let missing: Vec<String> = captured_names(&fmt[1..fmt.len() - 1])
+                .into_iter()
+                .filter(|n| !named.contains(n.as_str()))
+                .collect();
+            if missing.is_empty() {
+                continue;
+            }
+            let extra: String = missing.iter().map(|n| format!(", {} = {}", n, n)).collect();
+            let insert_at = result[..close].trim_end().trim_end_matches(',').len();
+            result.insert_str(insert_at, &extra);
+        }
+    }
+    result
+}
+
+/// Names a format string captures: `{x}`, `{x:?}`, `{:>width$}` (in order,
+/// without duplicates)
+fn captured_names(fmt: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        let is_ident = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if is_ident && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    };
+    let mut rest = fmt;
+    while let Some(open) = rest.find('{') {
+        if rest[open + 1..].starts_with('{') {
+            rest = &rest[open + 2..];
+            continue;
+        }
+        let Some(close) = rest[open..].find('}') else {
+            break;
+        };
+        let inner = &rest[open + 1..open + close];
+        let (arg, spec) = inner.split_once(':').unwrap_or((inner, ""));
+        push(arg.trim());
+        for part in spec.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')) {
+            if let Some(name) = part.strip_suffix('$') {
+                push(name);
+            }
+        }
+        rest = &rest[open + close + 1..];
+    }
+    names
+}
+
+/// The `async` construct used on `line` and where it starts, if any
+fn async_use(line: &str) -> Option<(usize, &'static str)> {
+    let code = strip_inline_comment(line);
+    for (pos, c) in delimiter_positions(&code, &['a', '.']) {
+        let rest = &code[pos..];
+        let word_start = pos == 0 || !code[..pos].ends_with(|c: char| c.is_alphanumeric() || c == '_');
+        if c == 'a' && word_start && rest.starts_with("async") {
+            let after = rest["async".len()..].trim_start();
+            if after.starts_with("fn ") {
+                return Some((pos, "async fn"));
+            }
+            if after.starts_with('{') || after.starts_with("move") {
+                return Some((pos, "async block"));
+            }
+        }
+        if c == '.' && rest.starts_with(".await") && !rest[6..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+            return Some((pos, ".await"));
+        }
+    }
+    None
+}
+
+/// `(1-based line, column, construct)` for every use of `async` the
+/// edition lacks
+pub fn unavailable_async(source: &str, edition: Edition) -> Vec<(usize, usize, &'static str)> {
+    if edition.has_async() {
+        return Vec::new();
+    }
+    source.lines()
+        .enumerate()
+        .filter_map(|(i, line)| async_use(line).map(|(column, what)| (i + 1, column, what)))
+        .collect()
+}
+
+/// Is the character at `pos` inside a string or char literal?
+fn in_string(line: &str, pos: usize) -> bool {
+    match line[pos..].chars().next() {
+        Some(c) => !delimiter_positions(line, &[c]).iter().any(|&(p, _)| p == pos),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_order() {
+        assert_eq!(Edition::parse("2018"), Some(Edition::E2018));
+        assert_eq!(Edition::parse("2020"), None);
+        assert_eq!(Edition::default().as_str(), "2021");
+        assert!(Edition::E2015 < Edition::E2024);
+        assert!(!Edition::E2018.requires_dyn());
+        assert!(Edition::E2024.formats_captured_args());
+    }
+
+    #[test]
+    fn test_explicit_format_args() {
+        assert_eq!(explicit_format_args("panic!(\"bad {x}\");"), "panic!(\"bad {x}\", x = x);");
+        assert_eq!(
+            explicit_format_args("println!(\"{} {name:?} {:>w$}\", a, b);"),
+            "println!(\"{} {name:?} {:>w$}\", a, b, name = name, w = w);"
+        );
+        assert_eq!(explicit_format_args("writeln!(f, \"{x}\", x = 1)?;"), "writeln!(f, \"{x}\", x = 1)?;");
+        assert_eq!(explicit_format_args("println!(\"{{x}} {0}\", a);"), "println!(\"{{x}} {0}\", a);");
+        assert_eq!(explicit_format_args("s = \"println!(\\\"{x}\\\")\";"), "s = \"println!(\\\"{x}\\\")\";");
+    }
+
+    #[test]
+    fn test_add_dyn() {
+        let traits = trait_object_names("trait Shape {\n}\nenum Error {\n}");
+        assert!(traits.contains("Shape") && !traits.contains("Error"));
+        assert_eq!(add_dyn("fn f(s: &Shape) -> Box<Display> {", &traits), "fn f(s: &dyn Shape) -> Box<dyn Display> {");
+        assert_eq!(add_dyn("let v: Vec<Box<dyn Shape>> = x;", &traits), "let v: Vec<Box<dyn Shape>> = x;");
+        assert_eq!(add_dyn("let e = &Error::Io;", &traits), "let e = &Error::Io;");
+        assert_eq!(add_dyn("let f: Box<Fn(i32) -> i32> = g;", &traits), "let f: Box<dyn Fn(i32) -> i32> = g;");
+        assert_eq!(add_dyn("println!(\"&Shape\");", &traits), "println!(\"&Shape\");");
+    }
+
+    #[test]
+    fn test_unavailable_async() {
+        let src = "async fn load() {\n    x = fetch().await\n    s = \"async fn\"\n}";
+        assert_eq!(unavailable_async(src, Edition::E2015), vec![(1, 0, "async fn"), (2, 15, ".await")]);
+        assert!(unavailable_async(src, Edition::E2018).is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+//! Effect Badges in Generated Rust
+//!
+//! With `LoweringOptions::effect_badges` every function lowered from
+//! RustS+ carries its effect contract as a doc comment, so readers of the
+//! generated Rust and `rustdoc` see it:
+//!
+//! ```text
+//! /// rustsp: pure
+//! fn add(a: i32, b: i32) -> i32 {
+//!
+//! /// rustsp: effects(alloc, io)
+//! fn report(items: Vec<Item>) {
+//! ```
+//!
+//! The effects are those `rustsp effects-diff` compares: declared and
+//! detected effects, without `read(..)` and internal calls. Output kept
+//! line-for-line with the source (`preserve_lines`) gets the same text as a
+//! `#[doc = ".."]` attribute on the signature line.
+
+use std::collections::HashMap;
+
+use crate::anti_fail_logic::analyze_functions_by_line;
+use crate::effects_diff::FunctionEffects;
+
+/// Badge text for every function of `source`, by 0-based signature line
+pub fn effect_badges(source: &str) -> HashMap<usize, String> {
+    analyze_functions_by_line(source, "")
+        .into_iter()
+        .map(|(line, info)| {
+            let effects = FunctionEffects::from_info(&info).effects;
+            let badge = if effects.is_empty() {
+                "rustsp: pure".to_string()
+            } else {
+                format!("rustsp: effects({})", effects.into_iter().collect::<Vec<_>>().join(", "))
+            };
+            (line - 1, badge)
+        })
+        .collect()
+}
+
+/// Put `badge` on a lowered signature: a doc comment line above it, or an
+/// attribute in front of it when output lines must match source lines
+pub fn attach_badge(signature: &str, leading_ws: &str, badge: &str, same_line: bool) -> Vec<String> {
+    if same_line {
+        let code = signature.strip_prefix(leading_ws).unwrap_or(signature);
+        vec![format!("{}#[doc = {:?}] {}", leading_ws, badge, code)]
+    } else {
+        vec![format!("{}/// {}", leading_ws, badge), signature.to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_badges_by_line() {
+        let src = "fn add(a i32, b i32) i32 {\n    a + b\n}\n\nfn show(x i32) effects(io) {\n    println(\"{}\", x)\n}\n\nfn build() Vec[i32] effects(alloc) {\n    v = vec![1]\n    v\n}";
+        let badges = effect_badges(src);
+        assert_eq!(badges[&0], "rustsp: pure");
+        assert_eq!(badges[&4], "rustsp: effects(io)");
+        assert_eq!(badges[&8], "rustsp: effects(alloc)");
+    }
+
+    #[test]
+    fn test_attach_badge() {
+        assert_eq!(attach_badge("    fn f() {", "    ", "rustsp: pure", false), vec!["    /// rustsp: pure", "    fn f() {"]);
+        assert_eq!(attach_badge("    fn f() {", "    ", "rustsp: pure", true), vec!["    #[doc = \"rustsp: pure\"] fn f() {"]);
+    }
+}
@@ -0,0 +1,390 @@
+//! Stdlib Effect Database
+//!
+//! What the standard library's functions, methods and macros do is data,
+//! not code: `src/stdlib_effects.toml` is embedded in the compiler and
+//! parsed once. The type-driven inference ([`crate::type_env::TypeEnv`])
+//! and the line-pattern detectors ([`crate::effect_detector`]) are both
+//! built from it.
+//!
+//! `--effect-db <file>` reads a file in the same format over the embedded
+//! table:
+//!
+//! ```text
+//! version = 1
+//!
+//! [functions]
+//! io = ["log_info", "log_warn"]      # added
+//! pure = ["flush"]                   # removed
+//!
+//! [methods]
+//! panic = ["get_or_die"]
+//!
+//! [higher_order]                     # calls the function passed to it
+//! calls = ["for_each_entry"]
+//!
+//! [patterns.app-logging]             # a new detector
+//! effect = "io"
+//! match = ["log_info(", "log_warn("]
+//!
+//! [patterns.fs-io]                   # replaces the built-in table
+//! effect = "io"
+//! match = ["File::", "fs::"]
+//! ```
+//!
+//! A file whose `version` is newer than [`EFFECT_DB_VERSION`] is rejected.
+//! Only the part of TOML the tables need is read: tables, strings,
+//! integers, arrays of strings and `#` comments.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{OnceLock, RwLock};
+
+use crate::effect_detector::{register_detector, EffectCategory, PatternDetector};
+
+/// Value of the optional `format` key
+pub const EFFECT_DB_FORMAT: &str = "rustsp-effect-db";
+/// Newest database version this compiler reads
+pub const EFFECT_DB_VERSION: u64 = 1;
+
+const STDLIB_EFFECTS: &str = include_str!("stdlib_effects.toml");
+
+/// One `[patterns.NAME]` table: a line detector
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternTable {
+    pub name: String,
+    pub effect: EffectCategory,
+    /// Substrings that perform the effect
+    pub patterns: Vec<String>,
+    /// Macros also matched when called without `!`
+    pub bare_macros: Vec<String>,
+}
+
+/// Effects of standard library items
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EffectDb {
+    /// Functions, associated functions (`Vec::new`) and macros
+    pub functions: BTreeMap<String, BTreeSet<EffectCategory>>,
+    /// Methods, by name
+    pub methods: BTreeMap<String, BTreeSet<EffectCategory>>,
+    /// Methods that call the function passed to them (`map`, `for_each`):
+    /// the effects of a function value argument are the caller's
+    pub higher_order: BTreeSet<String>,
+    /// Line detectors, in order
+    pub patterns: Vec<PatternTable>,
+}
+
+impl EffectDb {
+    /// The embedded database (parsed on first use)
+    pub fn builtin() -> &'static EffectDb {
+        static BUILTIN: OnceLock<EffectDb> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            EffectDb::default()
+                .read_over(STDLIB_EFFECTS)
+                .expect("embedded stdlib_effects.toml is valid")
+        })
+    }
+
+    /// The embedded database with the file at `path` read over it
+    pub fn load(path: &str) -> Result<EffectDb, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::builtin().read_over(&text)
+    }
+
+    /// This database with the TOML document `text` read over it: entries
+    /// are added, `pure` entries removed and pattern tables replaced by name
+    pub fn read_over(&self, text: &str) -> Result<EffectDb, String> {
+        let mut db = self.clone();
+        let mut version = None;
+        // Pattern tables in order of appearance, `effect` not yet checked
+        let mut tables: Vec<(Option<EffectCategory>, PatternTable)> = Vec::new();
+
+        for (table, key, value, line) in parse_toml(text)? {
+            let at = |msg: String| format!("line {}: {}", line, msg);
+            match (table.as_str(), key.as_str()) {
+                ("", "format") => {
+                    let format = value.as_str().ok_or_else(|| at("`format` must be a string".to_string()))?;
+                    if format != EFFECT_DB_FORMAT {
+                        return Err(at(format!("unknown format \"{}\" (expected \"{}\")", format, EFFECT_DB_FORMAT)));
+                    }
+                }
+                ("", "version") => {
+                    let TomlValue::Int(v) = value else {
+                        return Err(at("`version` must be a number".to_string()));
+                    };
+                    if v > EFFECT_DB_VERSION {
+                        return Err(at(format!("version {} is newer than this compiler supports ({})", v, EFFECT_DB_VERSION)));
+                    }
+                    version = Some(v);
+                }
+                ("functions" | "methods", effect) => {
+                    let names = value.as_array().ok_or_else(|| at(format!("`{}` must be an array of names", effect)))?;
+                    let entries = if table == "functions" { &mut db.functions } else { &mut db.methods };
+                    if effect == "pure" {
+                        for name in names {
+                            entries.remove(name);
+                        }
+                    } else {
+                        let effect = parse_effect(effect).map_err(at)?;
+                        for name in names {
+                            entries.entry(name.clone()).or_default().insert(effect);
+                        }
+                    }
+                }
+                ("higher_order", key @ ("calls" | "pure")) => {
+                    let names = value.as_array().ok_or_else(|| at(format!("`{}` must be an array of names", key)))?;
+                    for name in names {
+                        if key == "calls" {
+                            db.higher_order.insert(name.clone());
+                        } else {
+                            db.higher_order.remove(name);
+                        }
+                    }
+                }
+                (table, key) if table.starts_with("patterns.") => {
+                    let name = &table["patterns.".len()..];
+                    let index = match tables.iter().position(|(_, t)| t.name == name) {
+                        Some(index) => index,
+                        None => {
+                            let table = PatternTable {
+                                name: name.to_string(),
+                                effect: EffectCategory::Io,
+                                patterns: Vec::new(),
+                                bare_macros: Vec::new(),
+                            };
+                            tables.push((None, table));
+                            tables.len() - 1
+                        }
+                    };
+                    let (effect, entry) = &mut tables[index];
+                    match key {
+                        "effect" => {
+                            let name = value.as_str().ok_or_else(|| at("`effect` must be a string".to_string()))?;
+                            *effect = Some(parse_effect(name).map_err(at)?);
+                        }
+                        "match" | "bare_macros" => {
+                            let list = value.as_array().ok_or_else(|| at(format!("`{}` must be an array of strings", key)))?;
+                            if key == "match" { entry.patterns = list.to_vec() } else { entry.bare_macros = list.to_vec() }
+                        }
+                        _ => return Err(at(format!("unknown key `{}` in [{}]", key, table))),
+                    }
+                }
+                ("", key) => return Err(at(format!("unknown key `{}`", key))),
+                (table, _) => return Err(at(format!("unknown table [{}]", table))),
+            }
+        }
+
+        if version.is_none() {
+            return Err("missing `version`".to_string());
+        }
+        for (effect, mut table) in tables {
+            table.effect = effect.ok_or_else(|| format!("[patterns.{}] has no `effect`", table.name))?;
+            match db.patterns.iter_mut().find(|t| t.name == table.name) {
+                Some(existing) => *existing = table,
+                None => db.patterns.push(table),
+            }
+        }
+        Ok(db)
+    }
+
+    /// Whether `method` calls the function passed to it
+    pub fn calls_argument(&self, method: &str) -> bool {
+        self.higher_order.contains(method)
+    }
+}
+
+fn parse_effect(name: &str) -> Result<EffectCategory, String> {
+    EffectCategory::parse(name).ok_or_else(|| format!("unknown effect `{}` (expected io, alloc, panic, concurrent or pure)", name))
+}
+
+fn global() -> &'static RwLock<EffectDb> {
+    static DB: OnceLock<RwLock<EffectDb>> = OnceLock::new();
+    DB.get_or_init(|| RwLock::new(EffectDb::builtin().clone()))
+}
+
+/// Run `f` with read access to the database the compiler uses
+pub fn with_effect_db<R>(f: impl FnOnce(&EffectDb) -> R) -> R {
+    f(&global().read().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Use `db` for every later analysis in the process: its pattern tables
+/// replace the shared detectors of the same name
+pub fn install_effect_db(db: EffectDb) {
+    for table in &db.patterns {
+        register_detector(PatternDetector::from_table(table));
+    }
+    *global().write().unwrap_or_else(|e| e.into_inner()) = db;
+}
+
+//=============================================================================
+// TOML SUBSET
+//=============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum TomlValue {
+    Str(String),
+    Int(u64),
+    Array(Vec<String>),
+}
+
+impl TomlValue {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            TomlValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[String]> {
+        match self {
+            TomlValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// `(table, key, value, line)` for every key of `text`, in order
+fn parse_toml(text: &str) -> Result<Vec<(String, String, TomlValue, usize)>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut entries = Vec::new();
+    let mut table = String::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line_num = i + 1;
+        let at = |msg: &str| format!("line {}: {}", line_num, msg);
+        let line = strip_toml_comment(lines[i]);
+        let line = line.trim();
+        i += 1;
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            if header.starts_with('[') {
+                return Err(at("arrays of tables are not supported"));
+            }
+            table = header.strip_suffix(']').ok_or_else(|| at("unclosed table header"))?.trim().to_string();
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or_else(|| at("expected `key = value`"))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let mut value = value.trim().to_string();
+        // A multi-line array continues until its `]`
+        if value.starts_with('[') {
+            while !value.trim_end().ends_with(']') {
+                let next = lines.get(i).ok_or_else(|| at("unclosed array"))?;
+                value.push(' ');
+                value.push_str(strip_toml_comment(next).trim());
+                i += 1;
+            }
+        }
+        entries.push((table.clone(), key, parse_toml_value(&value).map_err(|e| at(&e))?, line_num));
+    }
+    Ok(entries)
+}
+
+fn parse_toml_value(value: &str) -> Result<TomlValue, String> {
+    if let Some(items) = value.strip_prefix('[') {
+        let items = items.strip_suffix(']').ok_or("unclosed array")?;
+        let mut strings = Vec::new();
+        let mut rest = items.trim();
+        while !rest.is_empty() {
+            let (string, after) = parse_toml_string(rest)?;
+            strings.push(string);
+            rest = after.trim_start();
+            rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+        }
+        return Ok(TomlValue::Array(strings));
+    }
+    if value.starts_with(['"', '\'']) {
+        let (string, rest) = parse_toml_string(value)?;
+        return match rest.trim() {
+            "" => Ok(TomlValue::Str(string)),
+            _ => Err("unexpected text after string".to_string()),
+        };
+    }
+    value.parse().map(TomlValue::Int).map_err(|_| format!("unsupported value `{}`", value))
+}
+
+/// A basic (`"a\"b"`) or literal (`'a'`) string at the start of `s`, and the rest
+fn parse_toml_string(s: &str) -> Result<(String, &str), String> {
+    let quote = s.chars().next().filter(|c| *c == '"' || *c == '\'').ok_or_else(|| format!("expected a string at `{}`", s))?;
+    let mut string = String::new();
+    let mut chars = s.char_indices().skip(1);
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            c if c == quote => return Ok((string, &s[pos + 1..])),
+            '\\' if quote == '"' => match chars.next().map(|(_, e)| e) {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some(e @ ('"' | '\\')) => string.push(e),
+                other => return Err(format!("unsupported escape `\\{}`", other.unwrap_or(' '))),
+            },
+            c => string.push(c),
+        }
+    }
+    Err("unclosed string".to_string())
+}
+
+/// `line` without a `#` comment outside strings
+fn strip_toml_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (pos, c) in line.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..pos],
+            None => {}
+        }
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_database() {
+        let db = EffectDb::builtin();
+        assert!(db.functions["println"].contains(&EffectCategory::Io));
+        assert!(db.functions["Vec::new"].contains(&EffectCategory::Alloc));
+        assert!(db.methods["unwrap"].contains(&EffectCategory::Panic));
+        assert!(db.calls_argument("for_each") && !db.calls_argument("push"));
+        let console = db.patterns.iter().find(|t| t.name == "console-io").unwrap();
+        assert!(console.patterns.contains(&"stdin()".to_string()));
+        assert_eq!(console.bare_macros, vec!["println", "print", "eprintln", "eprint"]);
+        assert_eq!(db.patterns.last().unwrap().effect, EffectCategory::Concurrent);
+    }
+
+    #[test]
+    fn test_read_over() {
+        let user = "version = 1  # user table\n\n[functions]\nio = [\"log_info\",\n    'log#warn',  # trailing\n]\npure = [\"flush\"]\n\n[higher_order]\ncalls = [\"visit\"]\npure = [\"then\"]\n\n[patterns.fs-io]\neffect = \"io\"\nmatch = [\"File::\"]\n\n[patterns.app]\neffect = \"panic\"\nmatch = [\"die(\"]\n";
+        let db = EffectDb::builtin().read_over(user).unwrap();
+        assert!(db.functions["log_info"].contains(&EffectCategory::Io));
+        assert!(db.functions.contains_key("log#warn"));
+        assert!(!db.functions.contains_key("flush"));
+        assert!(db.calls_argument("visit") && !db.calls_argument("then"));
+        let fs = db.patterns.iter().find(|t| t.name == "fs-io").unwrap();
+        assert_eq!(fs.patterns, vec!["File::"]);
+        assert_eq!(db.patterns.last().unwrap().name, "app");
+        assert_eq!(db.patterns.len(), EffectDb::builtin().patterns.len() + 1);
+    }
+
+    #[test]
+    fn test_rejected_files() {
+        let base = EffectDb::builtin();
+        assert!(base.read_over("[functions]\nio = [\"x\"]\n").unwrap_err().contains("missing `version`"));
+        assert!(base.read_over("version = 2\n").unwrap_err().contains("newer"));
+        assert!(base.read_over("version = 1\n[functions]\nfast = [\"x\"]\n").unwrap_err().starts_with("line 3: unknown effect `fast`"));
+        assert!(base.read_over("version = 1\n[patterns.p]\nmatch = [\"x\"]\n").unwrap_err().contains("has no `effect`"));
+        assert!(base.read_over("version = 1\n[functions]\nio = [\"x\"\n").unwrap_err().contains("unclosed array"));
+    }
+}
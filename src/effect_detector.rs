@@ -0,0 +1,298 @@
+//! Pluggable Effect Detectors for RustS+
+//!
+//! Pattern-based effect detection (used by Stage 1 and `--analyze`) is driven
+//! by a single registry of [`EffectDetector`]s instead of per-module tables.
+//!
+//! ## Built-in detectors
+//!
+//! The built-in detectors are the `[patterns.*]` tables of the stdlib effect
+//! database (see [`crate::effect_db`]); `--effect-db` can replace or extend
+//! them without rebuilding the compiler.
+//!
+//! | Name         | Effect  | Examples                                   |
+//! |--------------|---------|--------------------------------------------|
+//! | `console-io` | `io`    | `println!`, `println(...)`, `stdin()`      |
+//! | `fs-io`      | `io`    | `File::open`, `fs::read`, `.exists()`      |
+//! | `net-io`     | `io`    | `TcpStream::connect`, `.send(`             |
+//! | `process-io` | `io`    | `env::var`, `Command::new`, `.spawn(`      |
+//! | `time`       | `io`    | `Instant::now`, `SystemTime::now`, `sleep` |
+//! | `rand`       | `io`    | `rand::random`, `thread_rng()`             |
+//! | `alloc`      | `alloc` | `Vec::new`, `format!`, `.push(`            |
+//! | `panic`      | `panic` | `panic!`, `.unwrap()`, `assert!`           |
+//! | `threads`    | `concurrent` | `thread::spawn(`, `.join()`           |
+//!
+//! ## Custom detectors
+//!
+//! ```text
+//! use rustsp::effect_detector::{register_detector, PatternDetector, EffectCategory};
+//!
+//! // Treat the project's logging helpers as io
+//! register_detector(PatternDetector::new("app-logging", EffectCategory::Io, &["log_info(", "log_warn("]));
+//! ```
+//!
+//! Registered detectors apply to every later Stage 1 run in the process.
+
+use std::sync::{OnceLock, RwLock};
+
+use crate::effect_db::{EffectDb, PatternTable};
+
+/// Effect a detector can report (the propagatable effects)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EffectCategory {
+    Io,
+    Alloc,
+    Panic,
+    Concurrent,
+}
+
+impl EffectCategory {
+    /// Name as written in `effects(...)`
+    pub fn name(&self) -> &'static str {
+        match self {
+            EffectCategory::Io => "io",
+            EffectCategory::Alloc => "alloc",
+            EffectCategory::Panic => "panic",
+            EffectCategory::Concurrent => "concurrent",
+        }
+    }
+    
+    /// Parse a name written in `effects(...)`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "io" => Some(EffectCategory::Io),
+            "alloc" => Some(EffectCategory::Alloc),
+            "panic" => Some(EffectCategory::Panic),
+            "concurrent" => Some(EffectCategory::Concurrent),
+            _ => None,
+        }
+    }
+}
+
+/// Detects one kind of effect on a single source line
+pub trait EffectDetector: Send + Sync {
+    /// Short identifier, e.g. `console-io`
+    fn name(&self) -> &str;
+    /// Effect reported when [`EffectDetector::detects`] matches
+    fn category(&self) -> EffectCategory;
+    /// Does `line` perform this effect?
+    fn detects(&self, line: &str) -> bool;
+    /// The construct of `line` that performs the effect, as quoted in
+    /// diagnostics (`println!`, `.unwrap()`); the detector name if unknown
+    fn find(&self, line: &str) -> Option<String> {
+        self.detects(line).then(|| self.name().to_string())
+    }
+}
+
+/// Detector that matches plain substrings, and optionally macros called
+/// without `!`
+#[derive(Debug, Clone)]
+pub struct PatternDetector {
+    name: String,
+    category: EffectCategory,
+    patterns: Vec<String>,
+    bare_macros: Vec<String>,
+}
+
+impl PatternDetector {
+    pub fn new(name: &str, category: EffectCategory, patterns: &[&str]) -> Self {
+        PatternDetector {
+            name: name.to_string(),
+            category,
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            bare_macros: Vec::new(),
+        }
+    }
+    
+    /// Detector for a `[patterns.NAME]` table of the effect database
+    pub fn from_table(table: &PatternTable) -> Self {
+        PatternDetector {
+            name: table.name.clone(),
+            category: table.effect,
+            patterns: table.patterns.clone(),
+            bare_macros: table.bare_macros.clone(),
+        }
+    }
+}
+
+impl EffectDetector for PatternDetector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn category(&self) -> EffectCategory {
+        self.category
+    }
+
+    fn detects(&self, line: &str) -> bool {
+        self.patterns.iter().any(|p| line.contains(p.as_str()))
+            || has_bare_macro_call(line, &self.bare_macros)
+    }
+
+    fn find(&self, line: &str) -> Option<String> {
+        let first = self.patterns.iter()
+            .filter_map(|p| line.find(p.as_str()).map(|pos| (pos, p.trim_end_matches('(').to_string())))
+            .chain(self.bare_macros.iter()
+                .filter_map(|name| bare_macro_call(line, name).map(|pos| (pos, format!("{}!", name)))))
+            .min_by_key(|(pos, _)| *pos);
+        first.map(|(_, construct)| construct)
+    }
+}
+
+/// Detect RustS+ macro calls written without `!`: `println("{}", x)`
+pub fn has_bare_macro_call<S: AsRef<str>>(line: &str, names: &[S]) -> bool {
+    names.iter().any(|name| bare_macro_call(line, name.as_ref()).is_some())
+}
+
+/// Byte offset of the first `name(` that is not a method or longer name
+fn bare_macro_call(line: &str, name: &str) -> Option<usize> {
+    let pattern = format!("{}(", name);
+    line.match_indices(&pattern).map(|(pos, _)| pos).find(|&pos| {
+        let prev = line[..pos].chars().next_back();
+        !matches!(prev, Some(c) if c.is_alphanumeric() || c == '_' || c == '.' || c == '!')
+    })
+}
+
+/// Ordered collection of detectors
+pub struct EffectDetectorRegistry {
+    detectors: Vec<Box<dyn EffectDetector>>,
+}
+
+impl EffectDetectorRegistry {
+    /// Registry without any detectors
+    pub fn empty() -> Self {
+        EffectDetectorRegistry { detectors: Vec::new() }
+    }
+
+    /// Registry with all built-in detectors (the embedded effect database)
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::empty();
+        for table in &EffectDb::builtin().patterns {
+            registry.register(PatternDetector::from_table(table));
+        }
+        registry
+    }
+
+    /// Add a detector. A detector with the same name replaces the old one.
+    pub fn register<D: EffectDetector + 'static>(&mut self, detector: D) {
+        self.detectors.retain(|d| d.name() != detector.name());
+        self.detectors.push(Box::new(detector));
+    }
+
+    /// Remove a detector by name. Returns true if one was removed.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let before = self.detectors.len();
+        self.detectors.retain(|d| d.name() != name);
+        self.detectors.len() != before
+    }
+
+    /// Names of registered detectors, in order
+    pub fn names(&self) -> Vec<String> {
+        self.detectors.iter().map(|d| d.name().to_string()).collect()
+    }
+
+    /// Does any detector of `category` match `line`?
+    pub fn detects(&self, line: &str, category: EffectCategory) -> bool {
+        self.detectors
+            .iter()
+            .any(|d| d.category() == category && d.detects(line))
+    }
+
+    /// The construct of `line` that performs `category`, from the first
+    /// detector that matches
+    pub fn find(&self, line: &str, category: EffectCategory) -> Option<String> {
+        self.detectors
+            .iter()
+            .filter(|d| d.category() == category)
+            .find_map(|d| d.find(line))
+    }
+
+    /// All effects detected on `line` (sorted, without duplicates)
+    pub fn detect_line(&self, line: &str) -> Vec<EffectCategory> {
+        let mut found: Vec<EffectCategory> = self.detectors
+            .iter()
+            .filter(|d| d.detects(line))
+            .map(|d| d.category())
+            .collect();
+        found.sort();
+        found.dedup();
+        found
+    }
+}
+
+impl Default for EffectDetectorRegistry {
+    fn default() -> Self {
+        Self::with_builtin()
+    }
+}
+
+fn global() -> &'static RwLock<EffectDetectorRegistry> {
+    static REGISTRY: OnceLock<RwLock<EffectDetectorRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(EffectDetectorRegistry::with_builtin()))
+}
+
+/// Add a detector to the shared registry used by the compiler
+pub fn register_detector<D: EffectDetector + 'static>(detector: D) {
+    global().write().unwrap_or_else(|e| e.into_inner()).register(detector);
+}
+
+/// Remove a detector from the shared registry
+pub fn unregister_detector(name: &str) -> bool {
+    global().write().unwrap_or_else(|e| e.into_inner()).unregister(name)
+}
+
+/// Run `f` with read access to the shared registry
+pub fn with_registry<R>(f: impl FnOnce(&EffectDetectorRegistry) -> R) -> R {
+    f(&global().read().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Shared-registry shorthand: does `line` perform `category`?
+pub fn line_has_effect(line: &str, category: EffectCategory) -> bool {
+    with_registry(|r| r.detects(line, category))
+}
+
+/// Shared-registry shorthand: the construct of `line` that performs
+/// `category` (see [`EffectDetector::find`])
+pub fn find_effect_construct(line: &str, category: EffectCategory) -> Option<String> {
+    with_registry(|r| r.find(line, category))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_detectors() {
+        let registry = EffectDetectorRegistry::with_builtin();
+        assert_eq!(registry.detect_line("println(\"{}\", x)"), vec![EffectCategory::Io]);
+        assert_eq!(registry.detect_line("let t = Instant::now();"), vec![EffectCategory::Io]);
+        assert_eq!(
+            registry.detect_line("let s = fs::read_to_string(p).unwrap();"),
+            vec![EffectCategory::Io, EffectCategory::Panic]
+        );
+        assert_eq!(registry.detect_line("v = Vec::new()"), vec![EffectCategory::Alloc]);
+        assert_eq!(registry.detect_line("items.push(x)"), vec![EffectCategory::Alloc]);
+        assert!(registry.detect_line("items.remove(0)").is_empty());
+        assert!(registry.detect_line("x = lock.read().unwrap_or(0)").is_empty());
+        assert!(!registry.detects("self.println(x)", EffectCategory::Io));
+    }
+
+    #[test]
+    fn test_find_construct() {
+        let registry = EffectDetectorRegistry::with_builtin();
+        assert_eq!(registry.find("self.println(a); println(\"{}\", x)", EffectCategory::Io).as_deref(), Some("println!"));
+        assert_eq!(registry.find("let s = fs::read_to_string(p).unwrap();", EffectCategory::Panic).as_deref(), Some(".unwrap()"));
+        assert_eq!(registry.find("items.push(x)", EffectCategory::Alloc).as_deref(), Some(".push"));
+        assert_eq!(registry.find("items.push(x)", EffectCategory::Io), None);
+    }
+
+    #[test]
+    fn test_custom_detector() {
+        let mut registry = EffectDetectorRegistry::with_builtin();
+        assert!(!registry.detects("log_info(\"start\")", EffectCategory::Io));
+        registry.register(PatternDetector::new("app-logging", EffectCategory::Io, &["log_info("]));
+        assert!(registry.detects("log_info(\"start\")", EffectCategory::Io));
+        assert!(registry.unregister("app-logging"));
+        assert!(!registry.detects("log_info(\"start\")", EffectCategory::Io));
+        assert!(registry.names().contains(&"console-io".to_string()));
+    }
+}
@@ -0,0 +1,231 @@
+//! Effect Diffing Between Source Versions (`rustsp effects-diff`)
+//!
+//! `rustsp effects-diff old.rss new.rss` runs the Stage 1 effect analysis on
+//! both versions of a file and reports, per function, which effects were
+//! added or removed and whether the function became (im)pure:
+//!
+//! ```text
+//! Effect changes old.rss → new.rss
+//!   fn audit                       (new)  io
+//!   fn checkout             [pub]  pure → effectful   +alloc   +io  ← effect creep
+//!   fn render                      effectful → pure   -alloc
+//! ```
+//!
+//! A function's effects are the union of what it declares and what the
+//! analysis detects; `read(..)` and internal call tracking are left out.
+//! Purity follows `analyze-dir`: no `io`, `alloc` or `panic`.
+//!
+//! Effect creep - a previously pure `pub fn` gaining effects - makes the
+//! command exit 1, so it can gate code review.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::anti_fail_logic::{analyze_functions, Effect, FunctionInfo};
+use crate::eir::EffectAlgebra;
+
+/// Effects of one function in one version
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionEffects {
+    pub is_public: bool,
+    pub effects: BTreeSet<String>,
+    pub is_pure: bool,
+}
+
+impl FunctionEffects {
+    pub(crate) fn from_info(info: &FunctionInfo) -> Self {
+        let all = info.declared_effects.union(&info.detected_effects);
+        FunctionEffects {
+            is_public: info.is_public,
+            effects: all.effects.iter()
+                .filter(|e| !matches!(e, Effect::Read(_) | Effect::Calls(_)))
+                .map(|e| e.display())
+                .collect(),
+            is_pure: all.propagatable().is_empty(),
+        }
+    }
+}
+
+/// How a function differs between the two versions
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionChange {
+    pub name: String,
+    /// `None` when the function is new
+    pub old: Option<FunctionEffects>,
+    /// `None` when the function was removed
+    pub new: Option<FunctionEffects>,
+}
+
+impl FunctionChange {
+    pub fn added_effects(&self) -> Vec<&str> {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => new.effects.difference(&old.effects).map(String::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn removed_effects(&self) -> Vec<&str> {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => old.effects.difference(&new.effects).map(String::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.new.as_ref().or(self.old.as_ref()).is_some_and(|f| f.is_public)
+    }
+
+    /// A previously pure public function that gained effects
+    pub fn is_effect_creep(&self) -> bool {
+        match (&self.old, &self.new) {
+            (Some(old), Some(new)) => new.is_public && old.is_pure && !self.added_effects().is_empty(),
+            _ => false,
+        }
+    }
+}
+
+/// Every function whose effects changed, by name
+#[derive(Debug, Clone, Default)]
+pub struct EffectsDiff {
+    pub changes: Vec<FunctionChange>,
+}
+
+impl EffectsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn effect_creep(&self) -> Vec<&FunctionChange> {
+        self.changes.iter().filter(|c| c.is_effect_creep()).collect()
+    }
+}
+
+/// Compare the effects of every function in `old_source` and `new_source`
+pub fn diff_effects(old_source: &str, new_source: &str) -> EffectsDiff {
+    let effects_by_name = |source: &str, file_name: &str| -> BTreeMap<String, FunctionEffects> {
+        analyze_functions(source, file_name).iter()
+            .map(|(name, info)| (name.clone(), FunctionEffects::from_info(info)))
+            .collect()
+    };
+    let mut old = effects_by_name(old_source, "old.rss");
+    let mut new = effects_by_name(new_source, "new.rss");
+    let names: BTreeSet<String> = old.keys().chain(new.keys()).cloned().collect();
+
+    let changes = names.into_iter()
+        .map(|name| FunctionChange { old: old.remove(&name), new: new.remove(&name), name })
+        .filter(|c| c.old != c.new)
+        .collect();
+    EffectsDiff { changes }
+}
+
+/// Plain-text report, one line per changed function
+pub fn format_effects_diff(diff: &EffectsDiff, old_name: &str, new_name: &str) -> String {
+    let mut out = format!("Effect changes {} → {}\n", old_name, new_name);
+    if diff.is_empty() {
+        out.push_str("  (no effect changes)\n");
+        return out;
+    }
+    for change in &diff.changes {
+        let visibility = if change.is_public() { "[pub]" } else { "" };
+        let detail = match (&change.old, &change.new) {
+            (None, Some(new)) => format!("(new)  {}", display_effects(&new.effects)),
+            (Some(_), None) => "(removed)".to_string(),
+            (Some(old), Some(new)) => {
+                let mut parts = Vec::new();
+                if old.is_pure != new.is_pure {
+                    parts.push(format!("{} → {}", purity(old.is_pure), purity(new.is_pure)));
+                }
+                parts.extend(change.added_effects().iter().map(|e| format!("+{}", e)));
+                parts.extend(change.removed_effects().iter().map(|e| format!("-{}", e)));
+                if old.is_public != new.is_public {
+                    parts.push(if new.is_public { "now pub" } else { "no longer pub" }.to_string());
+                }
+                parts.join("   ")
+            }
+            (None, None) => continue,
+        };
+        let marker = if change.is_effect_creep() { "  ← effect creep" } else { "" };
+        out.push_str(&format!("  fn {:<20} {:<5}  {}{}\n", change.name, visibility, detail, marker));
+    }
+    let creep = diff.effect_creep().len();
+    if creep > 0 {
+        out.push_str(&format!("\n{} previously pure public function(s) gained effects\n", creep));
+    }
+    out
+}
+
+fn purity(is_pure: bool) -> &'static str {
+    if is_pure { "pure" } else { "effectful" }
+}
+
+fn display_effects(effects: &BTreeSet<String>) -> String {
+    if effects.is_empty() {
+        return "pure".to_string();
+    }
+    effects.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"pub fn total(a i32, b i32) i32 {
+    a + b
+}
+
+fn log(msg String) effects(io) {
+    println("{}", msg)
+}
+
+fn gone() i32 {
+    1
+}
+"#;
+
+    const NEW: &str = r#"pub fn total(a i32, b i32) effects(io) i32 {
+    println("{}", a)
+    a + b
+}
+
+fn log(msg String) {
+    msg.len()
+}
+
+fn fresh() effects(io) {
+    println("hi")
+}
+"#;
+
+    #[test]
+    fn test_diff_reports_changes() {
+        let diff = diff_effects(OLD, NEW);
+        let names: Vec<&str> = diff.changes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["fresh", "gone", "log", "total"]);
+
+        let total = &diff.changes[3];
+        assert_eq!(total.added_effects(), vec!["io"]);
+        assert!(total.is_effect_creep());
+
+        let log = &diff.changes[2];
+        assert_eq!(log.removed_effects(), vec!["io"]);
+        assert!(!log.is_effect_creep());
+
+        assert_eq!(diff.effect_creep().len(), 1);
+        let report = format_effects_diff(&diff, "old.rss", "new.rss");
+        assert!(report.contains("pure → effectful   +io  ← effect creep"), "Bad report: {}", report);
+        assert!(report.contains("(removed)") && report.contains("(new)  io"), "Bad report: {}", report);
+    }
+
+    #[test]
+    fn test_private_function_gaining_effects_is_not_creep() {
+        let diff = diff_effects("fn f() i32 {\n    1\n}\n", "fn f() effects(io) i32 {\n    println(\"x\")\n    1\n}\n");
+        assert_eq!(diff.changes.len(), 1);
+        assert!(diff.effect_creep().is_empty());
+    }
+
+    #[test]
+    fn test_identical_sources_have_no_changes() {
+        let diff = diff_effects(OLD, OLD);
+        assert!(diff.is_empty());
+        assert!(format_effects_diff(&diff, "a", "b").contains("(no effect changes)"));
+    }
+}
@@ -121,7 +121,7 @@ impl Effect {
             EffectDecl::Read(name) => {
                 param_bindings.get(&name.name).map(|id| Effect::Read(*id))
             }
-            EffectDecl::Write(name) => {
+            EffectDecl::Write(name) | EffectDecl::WriteField { param: name, .. } => {
                 param_bindings.get(&name.name).map(|id| Effect::Write(*id))
             }
             EffectDecl::Io => Some(Effect::Io),
@@ -131,16 +131,101 @@ impl Effect {
     }
 }
 
+//=============================================================================
+// EFFECT SET ALGEBRA
+//=============================================================================
+
+/// An effect the shared set algebra works with: [`Effect`] here, and the
+/// name-based [`crate::anti_fail_logic::Effect`] of the legacy checker
+pub trait EffectItem: Ord + Clone {
+    /// Does the effect bubble up to callers (`io`, `alloc`, `panic`)?
+    fn is_propagatable(&self) -> bool;
+}
+
+impl EffectItem for Effect {
+    fn is_propagatable(&self) -> bool {
+        Effect::is_propagatable(self)
+    }
+}
+
+/// Set algebra shared by [`EffectSet`] and the legacy
+/// [`crate::anti_fail_logic::EffectSignature`], so the IR and legacy
+/// analyses combine, compare and print effects the same way
+pub trait EffectAlgebra: Sized {
+    type Item: EffectItem;
+
+    fn effects(&self) -> &BTreeSet<Self::Item>;
+    fn from_effects(effects: BTreeSet<Self::Item>) -> Self;
+
+    fn union(&self, other: &Self) -> Self {
+        Self::from_effects(self.effects().union(other.effects()).cloned().collect())
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        Self::from_effects(self.effects().intersection(other.effects()).cloned().collect())
+    }
+
+    /// Effects not in the other set
+    fn difference(&self, other: &Self) -> Self {
+        Self::from_effects(self.effects().difference(other.effects()).cloned().collect())
+    }
+
+    fn is_subset_of(&self, other: &Self) -> bool {
+        self.effects().is_subset(other.effects())
+    }
+
+    fn is_superset_of(&self, other: &Self) -> bool {
+        self.effects().is_superset(other.effects())
+    }
+
+    /// The effects that bubble up to callers
+    fn propagatable(&self) -> Self {
+        Self::from_effects(self.effects().iter().filter(|e| e.is_propagatable()).cloned().collect())
+    }
+
+    /// Comma-separated effects in a stable order - `read`, `write`, `io`,
+    /// `alloc`, `panic`, `calls`, then by name - or `pure` when empty
+    fn display_with(&self, label: impl Fn(&Self::Item) -> String) -> String {
+        if self.effects().is_empty() {
+            return "pure".to_string();
+        }
+        let mut labels: Vec<String> = self.effects().iter().map(label).collect();
+        labels.sort_by(|a, b| (label_rank(a), a).cmp(&(label_rank(b), b)));
+        labels.dedup();
+        labels.join(", ")
+    }
+}
+
+fn label_rank(label: &str) -> usize {
+    let kind = label.split('(').next().unwrap_or(label);
+    ["read", "write", "io", "alloc", "panic", "concurrent", "resource", "calls"].iter()
+        .position(|k| *k == kind)
+        .unwrap_or(usize::MAX)
+}
+
 //=============================================================================
 // EFFECT SET
 //=============================================================================
 
-/// A set of effects with convenience methods
+/// A set of effects with convenience methods (set operations come from
+/// [`EffectAlgebra`])
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct EffectSet {
     effects: BTreeSet<Effect>,
 }
 
+impl EffectAlgebra for EffectSet {
+    type Item = Effect;
+
+    fn effects(&self) -> &BTreeSet<Effect> {
+        &self.effects
+    }
+
+    fn from_effects(effects: BTreeSet<Effect>) -> Self {
+        EffectSet { effects }
+    }
+}
+
 impl EffectSet {
     pub fn new() -> Self {
         EffectSet { effects: BTreeSet::new() }
@@ -160,14 +245,12 @@ impl EffectSet {
         self.effects.insert(effect);
     }
     
-    pub fn extend(&mut self, other: &EffectSet) {
-        self.effects.extend(other.effects.clone());
+    pub fn remove(&mut self, effect: &Effect) -> bool {
+        self.effects.remove(effect)
     }
     
-    pub fn union(&self, other: &EffectSet) -> EffectSet {
-        let mut result = self.clone();
-        result.extend(other);
-        result
+    pub fn extend(&mut self, other: &EffectSet) {
+        self.effects.extend(other.effects.iter().cloned());
     }
     
     pub fn contains(&self, effect: &Effect) -> bool {
@@ -206,31 +289,9 @@ impl EffectSet {
         self.effects.contains(&Effect::Write(id))
     }
     
-    /// Get all propagatable effects
-    pub fn propagatable(&self) -> EffectSet {
-        let mut result = EffectSet::new();
-        for e in &self.effects {
-            if e.is_propagatable() {
-                result.insert(e.clone());
-            }
-        }
-        result
-    }
-    
-    /// Get effects not in the other set
-    pub fn difference(&self, other: &EffectSet) -> EffectSet {
-        let mut result = EffectSet::new();
-        for e in &self.effects {
-            if !other.contains(e) {
-                result.insert(e.clone());
-            }
-        }
-        result
-    }
-    
-    /// Check if this is a subset of other
-    pub fn is_subset_of(&self, other: &EffectSet) -> bool {
-        self.effects.is_subset(&other.effects)
+    /// Effects by name, in the same order as the legacy checker prints them
+    pub fn display(&self, bindings: &HashMap<BindingId, BindingInfo>) -> String {
+        self.display_with(|e| e.display(bindings))
     }
     
     pub fn into_inner(self) -> BTreeSet<Effect> {
@@ -265,6 +326,8 @@ pub struct EffectContext {
     alloc_functions: HashSet<String>,
     /// Known panicking functions
     panic_functions: HashSet<String>,
+    /// Methods that call the function passed to them (`map`, `for_each`)
+    higher_order_methods: HashSet<String>,
 }
 
 impl EffectContext {
@@ -330,6 +393,7 @@ impl EffectContext {
             io_functions,
             alloc_functions,
             panic_functions,
+            higher_order_methods: crate::effect_db::with_effect_db(|db| db.higher_order.iter().cloned().collect()),
         }
     }
     
@@ -369,6 +433,10 @@ impl EffectContext {
         self.panic_functions.contains(name)
     }
     
+    pub fn calls_argument(&self, method: &str) -> bool {
+        self.higher_order_methods.contains(method)
+    }
+    
     /// Create EffectContext from TypeEnv (bridge for type-driven inference)
     ///
     /// This allows gradual migration from pattern-based to type-driven inference.
@@ -423,6 +491,9 @@ impl<'a> EffectInference<'a> {
             // VARIABLE RULE
             HirExpr::Var(id) => self.infer_var(*id),
             
+            // FUNCTION VALUE: naming a function performs nothing
+            HirExpr::FnRef(_) => EffectSet::empty(),
+            
             // BINARY OP: E₁ ∪ E₂
             HirExpr::Binary { left, right, .. } => {
                 let e1 = self.infer_expr(left);
@@ -594,35 +665,49 @@ impl<'a> EffectInference<'a> {
         
         // Add function effects
         match target {
-            HirCallTarget::Function(path) => {
-                let func_name = path.to_string();
-                
-                // Check for known effect-producing functions
-                if self.ctx.is_io_function(&func_name) {
-                    effects.insert(Effect::Io);
-                }
-                if self.ctx.is_alloc_function(&func_name) {
-                    effects.insert(Effect::Alloc);
-                }
-                if self.ctx.is_panic_function(&func_name) {
-                    effects.insert(Effect::Panic);
-                }
-                
-                // Check registered functions
-                if let Some(func_effects) = self.ctx.get_function_effects(&func_name) {
-                    effects.extend(func_effects);
-                }
-            }
+            HirCallTarget::Function(path) => effects.extend(&self.function_effects(path)),
             HirCallTarget::Method { receiver, method } => {
                 // Method call - add receiver effects plus method-specific effects
                 effects.extend(&self.infer_expr(receiver));
                 effects.extend(&self.infer_method_effects(method));
+                
+                // `xs.map(parse)` calls `parse`
+                if self.ctx.calls_argument(&method.name) {
+                    for arg in args {
+                        if let HirExpr::FnRef(path) = &arg.node {
+                            effects.extend(&self.function_effects(path));
+                        }
+                    }
+                }
             }
         }
         
         effects
     }
     
+    /// Effects of calling the function at `path`
+    fn function_effects(&self, path: &Path) -> EffectSet {
+        let mut effects = EffectSet::new();
+        let func_name = path.to_string();
+        
+        // Check for known effect-producing functions
+        if self.ctx.is_io_function(&func_name) {
+            effects.insert(Effect::Io);
+        }
+        if self.ctx.is_alloc_function(&func_name) {
+            effects.insert(Effect::Alloc);
+        }
+        if self.ctx.is_panic_function(&func_name) {
+            effects.insert(Effect::Panic);
+        }
+        
+        // Check registered functions
+        if let Some(func_effects) = self.ctx.get_function_effects(&func_name) {
+            effects.extend(func_effects);
+        }
+        effects
+    }
+    
     /// Infer effects from method name
     fn infer_method_effects(&self, method: &Ident) -> EffectSet {
         let mut effects = EffectSet::new();
@@ -989,4 +1074,41 @@ mod tests {
         assert!(!prop.has_read(BindingId::new(0)));
         assert!(!prop.has_write(BindingId::new(1)));
     }
+    
+    #[test]
+    fn test_effect_set_intersection_and_subset() {
+        let declared: EffectSet = [Effect::Io, Effect::Panic].into_iter().collect();
+        let detected: EffectSet = [Effect::Io, Effect::Alloc].into_iter().collect();
+        
+        let both = declared.intersection(&detected);
+        assert_eq!(both, EffectSet::singleton(Effect::Io));
+        assert!(both.is_subset_of(&declared));
+        assert!(declared.is_superset_of(&both));
+        assert!(!detected.is_subset_of(&declared));
+        assert!(EffectSet::empty().is_subset_of(&declared));
+    }
+    
+    #[test]
+    fn test_effect_set_display_order() {
+        let mut bindings = HashMap::new();
+        bindings.insert(BindingId::new(0), BindingInfo {
+            id: BindingId::new(0),
+            name: "acc".to_string(),
+            ty: None,
+            mutable: false,
+            scope_depth: 0,
+            decl_span: Default::default(),
+            is_outer: false,
+            is_param: true,
+        });
+        let set: EffectSet = [
+            Effect::Calls { func_name: "log".to_string(), effects: BTreeSet::new() },
+            Effect::Panic,
+            Effect::Io,
+            Effect::Write(BindingId::new(0)),
+        ].into_iter().collect();
+        
+        assert_eq!(set.display(&bindings), "write(acc), io, panic, calls(log)");
+        assert_eq!(EffectSet::empty().display(&bindings), "pure");
+    }
 }
\ No newline at end of file
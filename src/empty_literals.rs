@@ -0,0 +1,184 @@
+//! Empty Literals and Trailing Commas
+//!
+//! The lowering of empty literals and trailing commas, the same in
+//! assignments, struct and enum literals (single- and multi-line), array
+//! mode and enum definitions:
+//!
+//! | RustS+                                | Rust                          |
+//! |---------------------------------------|-------------------------------|
+//! | `Config {}`, `Config { }`             | `Config {}`                   |
+//! | `Point { x = 1, y = 2, }`             | `Point { x: 1, y: 2 }`        |
+//! | `xs = []`, `xs Vec[i32] = []`         | `vec![]`                      |
+//! | `xs = [` / `]` on the next line       | `vec![` / `]`                 |
+//! | `tags = [],` in a literal             | `tags: vec![],`               |
+//! | `[1, 2,]`, `(1, 2,)`                  | unchanged                     |
+//! | `f(1, 2,)`, `fn f(a i32,)`            | `f(1, 2)`, `fn f(a: i32)`     |
+//! | `x = 1,` in a multi-line literal      | `x: 1,` (one comma)           |
+//! | `b = make(` in a multi-line literal   | `b: make(` (comma after `)`)  |
+//! | `Q { n i32, }`, `Q {}` variants       | `Q { n: i32 },`, `Q {},`      |
+//! | `n i32,` / `},` in a struct variant   | `n: i32,` / `},`              |
+//! | `Y = 2` unit variant                  | `Y = 2,`                      |
+//!
+//! An empty `[]` is the empty collection: a zero-length array has no type
+//! rustc could infer and no use a `Vec` does not cover. It stays `[]` as a
+//! slice pattern (`[] =>`, `let [] = ..`), behind `&` (`&[]`), in a
+//! comparison, and when the binding is annotated with an array type
+//! (`a [i32; 0] = []`).
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+
+/// `Name { fields }`, or `Name {}` when there are no fields
+pub fn braced(name: &str, fields: &str) -> String {
+    if fields.trim().is_empty() {
+        format!("{} {{}}", name)
+    } else {
+        format!("{} {{ {} }}", name, fields)
+    }
+}
+
+/// A field value that continues on the next line (`Point {`, `[`,
+/// `vec![`, `make(`): its comma comes with the line that closes it
+pub fn opens_multiline_value(value: &str) -> bool {
+    value.trim_end().ends_with(['{', '[', '('])
+}
+
+/// Lower every empty `[]` in value position to `vec![]`, including one
+/// opened at the end of a line and closed at the start of the next
+pub fn lower_empty_arrays(lines: Vec<String>) -> Vec<String> {
+    let mut out = lines;
+    for i in 0..out.len() {
+        out[i] = lower_inline_empty_arrays(&out[i]);
+        let closes_next = out.get(i + 1).is_some_and(|next| closes_array(next.trim()));
+        if closes_next {
+            let code = strip_inline_comment(&out[i]);
+            let code = code.trim_end();
+            if code.ends_with('[') && is_value_position(&code[..code.len() - 1]) {
+                let open = code.len() - 1;
+                out[i] = format!("{}vec!{}", &out[i][..open], &out[i][open..]);
+            }
+        }
+    }
+    out
+}
+
+/// `]`, `];`, `],`, `])`, `]);` ...
+fn closes_array(trimmed: &str) -> bool {
+    trimmed.strip_prefix(']').is_some_and(|rest| rest.chars().all(|c| matches!(c, ';' | ',' | ')')))
+}
+
+fn lower_inline_empty_arrays(line: &str) -> String {
+    let code = strip_inline_comment(line);
+    let brackets = delimiter_positions(&code, &['[', ']']);
+    let mut result = line.to_string();
+    // Right to left, so earlier positions stay valid
+    for pair in brackets.windows(2).rev() {
+        let ((open, '['), (close, ']')) = (pair[0], pair[1]) else {
+            continue;
+        };
+        let is_empty = code[open + 1..close].trim().is_empty();
+        let is_pattern = code[close + 1..].trim_start().starts_with("=>");
+        if is_empty && !is_pattern && is_value_position(&code[..open]) {
+            result.replace_range(open..close + 1, "vec![]");
+        }
+    }
+    result
+}
+
+/// Whether an expression starting after `before` is a value that may be a
+/// `Vec`: assigned, a field value, an argument, an element or returned
+fn is_value_position(before: &str) -> bool {
+    let prev = before.trim_end();
+    if prev.ends_with("=>") || prev.ends_with(['(', ',', '{']) || prev.is_empty() {
+        return true;
+    }
+    if let Some(rest) = prev.strip_suffix("return") {
+        return !rest.ends_with(|c: char| c.is_alphanumeric() || c == '_');
+    }
+    if let Some(lhs) = prev.strip_suffix('=') {
+        // Not `==`, `!=`, `<=` or `>=`; `a: [i32; 0] =` keeps its array
+        return !lhs.ends_with(['=', '!', '<', '>']) && !lhs.contains(": [");
+    }
+    prev.ends_with(':') && !prev.ends_with("::")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_rusts;
+
+    /// Lower `body` inside `fn main()` and return its lines
+    fn lower_body(body: &str) -> Vec<String> {
+        let source = format!(
+            "struct Config {{}}\n\nstruct Line {{\n    n i32,\n    tags Vec[String],\n}}\n\nfn make(a i32, b i32) i32 {{\n    a + b\n}}\n\nfn main() {{\n{}\n}}\n",
+            body
+        );
+        parse_rusts(&source).lines().map(|l| l.trim().to_string()).collect()
+    }
+
+    fn assert_lowers(body: &str, expected: &[&str]) {
+        let lines = lower_body(body);
+        let start = lines.iter().position(|l| l == "fn main() {").unwrap() + 1;
+        assert_eq!(&lines[start..start + expected.len()], expected, "\n{}", lines.join("\n"));
+    }
+
+    #[test]
+    fn test_empty_literal_matrix() {
+        assert_lowers("    c = Config {}", &["let c = Config {};"]);
+        assert_lowers("    c = Config { }", &["let c = Config {};"]);
+        assert_lowers("    c = Config {\n    }", &["let c = Config {", "};"]);
+        assert_lowers("    xs = []\n    xs.push(1)", &["let mut xs = vec![];", "xs.push(1);"]);
+        assert_lowers("    xs Vec[i32] = []", &["let xs: Vec<i32> = vec![];"]);
+        assert_lowers("    xs Vec[i32] = [\n    ]", &["let xs: Vec<i32> = vec![", "];"]);
+        assert_lowers("    a [i32; 0] = []", &["let a: [i32; 0] = [];"]);
+        assert_lowers("    l = Line { n = 1, tags = [] }", &["let l = Line { n: 1, tags: vec![] };"]);
+        assert_lowers(
+            "    l = Line {\n        n = 1,\n        tags = [\n        ],\n    }",
+            &["let l = Line {", "n: 1,", "tags: vec![", "],", "};"],
+        );
+    }
+
+    #[test]
+    fn test_empty_struct_declaration_matrix() {
+        for decl in ["struct Config {}", "struct Config { }", "struct Config {\n}", "struct Config;"] {
+            let rust = parse_rusts(&format!("{}\n\nfn make(a i32) i32 {{\n    a\n}}\n", decl));
+            assert!(rust.contains("fn make(a: i32) -> i32 {"), "{}", rust);
+            assert!(!rust.contains("fn: make"), "{}", rust);
+        }
+    }
+
+    #[test]
+    fn test_trailing_comma_matrix() {
+        assert_lowers("    l = Line { n = 1, tags = vec![], }", &["let l = Line { n: 1, tags: vec![] };"]);
+        assert_lowers("    xs = [1, 2,]", &["let xs = [1, 2,];"]);
+        assert_lowers("    t = (1, 2,)", &["let t = (1, 2,);"]);
+        assert_lowers("    r = make(1, 2,)", &["let r = make(1, 2);"]);
+        assert_lowers("    xs = [\n        1,\n        2,\n    ]", &["let xs = [", "1,", "2,", "];"]);
+        assert_lowers(
+            "    l = Line {\n        n = make(\n            1,\n            2,\n        ),\n        tags = vec![\n            \"a\".to_string(),\n        ],\n    }",
+            &["let l = Line {", "n: make(", "1,", "2,", "),", "tags: vec![", "\"a\".to_string(),", "],", "};"],
+        );
+        assert!(parse_rusts("fn f(a i32,) i32 {\n    a\n}\n").starts_with("fn f(a: i32) -> i32 {"));
+    }
+
+    #[test]
+    fn test_enum_definition_commas() {
+        let rust = parse_rusts("enum S {\n    P {\n        n i32,\n        m i32\n    },\n    Q { n i32, },\n    E {}\n    Y = 2\n}\n");
+        let lines: Vec<&str> = rust.lines().map(str::trim).collect();
+        assert_eq!(&lines[1..8], ["P {", "n: i32,", "m: i32,", "},", "Q { n: i32 },", "E {},", "Y = 2,"], "{}", rust);
+    }
+
+    #[test]
+    fn test_empty_array_kept_where_not_a_value() {
+        let lines = lower_empty_arrays(vec![
+            "match xs.as_slice() { [] => 0, _ => 1 }".to_string(),
+            "let [] = arr;".to_string(),
+            "f(&[]); g([]); if v == [] {}".to_string(),
+            "let s = \"[]\"; // []".to_string(),
+        ]);
+        assert_eq!(lines[0], "match xs.as_slice() { [] => 0, _ => 1 }");
+        assert_eq!(lines[1], "let [] = arr;");
+        assert_eq!(lines[2], "f(&[]); g(vec![]); if v == [] {}");
+        assert_eq!(lines[3], "let s = \"[]\"; // []");
+    }
+}
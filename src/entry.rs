@@ -0,0 +1,281 @@
+//! Entry Point & Test Harness Generation for RustS+
+//!
+//! Two features that let the compiler generate `fn main()`:
+//!
+//! ## Custom entry (`--entry start`)
+//! ```text
+//! fn start() effects(io) {        // RustS+
+//!     println("hi")
+//! }
+//! ```
+//! Lowers normally, then the generated Rust gets:
+//! ```text
+//! fn main() {
+//!     start();
+//! }
+//! ```
+//!
+//! ## Test blocks (`rustsp test file.rss`)
+//! ```text
+//! test "addition works" {
+//!     assert_eq!(add(1, 2), 3)
+//! }
+//! ```
+//! Each top-level `test` block is rewritten IN PLACE (line numbers are kept)
+//! to a RustS+ function `fn __rustsp_test_addition_works() {`. In test mode
+//! the user's `main` is renamed and a harness `main` runs every test.
+//! Outside test mode, test blocks are blanked out entirely.
+
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::function::{parse_function_line, FunctionParseResult};
+
+/// Prefix of functions generated from `test` blocks
+pub const TEST_FN_PREFIX: &str = "__rustsp_test_";
+
+/// Name the user's `main` is renamed to in test mode
+pub const USER_MAIN_RENAMED: &str = "__rustsp_user_main";
+
+/// A `test` block found in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestBlock {
+    /// Display name (as written by the user)
+    pub name: String,
+    /// Generated function name
+    pub fn_name: String,
+    /// 1-based source line of the `test` header
+    pub line: usize,
+}
+
+/// Check if a function name was generated from a `test` block
+pub fn is_test_fn_name(name: &str) -> bool {
+    name.starts_with(TEST_FN_PREFIX)
+}
+
+/// Parse a test block header: `test "name" {` or `test name {`
+fn parse_test_header(trimmed: &str) -> Option<String> {
+    let rest = trimmed.strip_prefix("test ")?.trim();
+    let rest = rest.strip_suffix('{')?.trim();
+
+    let name = if rest.starts_with('"') && rest.ends_with('"') && rest.len() >= 2 {
+        &rest[1..rest.len() - 1]
+    } else {
+        rest
+    };
+
+    if name.is_empty() {
+        return None;
+    }
+
+    // Bare names must be identifiers (rejects e.g. `test x == y {`)
+    if !rest.starts_with('"') && !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+
+    Some(name.to_string())
+}
+
+/// Turn a test display name into an identifier suffix
+fn sanitize_test_name(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    while ident.contains("__") {
+        ident = ident.replace("__", "_");
+    }
+    ident.trim_matches('_').to_string()
+}
+
+/// Rewrite top-level `test` blocks.
+///
+/// - `keep_tests == true`: headers become `fn __rustsp_test_<name>() {`
+/// - `keep_tests == false`: the whole block is replaced by empty lines
+///
+/// Line count is preserved either way so diagnostics keep pointing at the
+/// original source.
+pub fn rewrite_test_blocks(source: &str, keep_tests: bool) -> (String, Vec<TestBlock>) {
+    let mut out: Vec<String> = Vec::new();
+    let mut tests: Vec<TestBlock> = Vec::new();
+    let mut depth: usize = 0;
+    // Depth at which the current (blanked) test block started
+    let mut skipping_from: Option<usize> = None;
+
+    for (idx, line) in source.lines().enumerate() {
+        let trimmed = line.trim();
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+
+        if let Some(start) = skipping_from {
+            depth = (depth + opens).saturating_sub(closes);
+            out.push(String::new());
+            if depth <= start {
+                skipping_from = None;
+            }
+            continue;
+        }
+
+        if depth == 0 {
+            if let Some(name) = parse_test_header(trimmed) {
+                let base = format!("{}{}", TEST_FN_PREFIX, sanitize_test_name(&name));
+                // Disambiguate duplicate names: `_2`, `_3`, ...
+                let mut fn_name = base.clone();
+                let mut n = 1;
+                while tests.iter().any(|t| t.fn_name == fn_name) {
+                    n += 1;
+                    fn_name = format!("{}_{}", base, n);
+                }
+                tests.push(TestBlock { name, fn_name: fn_name.clone(), line: idx + 1 });
+
+                if keep_tests {
+                    let leading_ws: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+                    out.push(format!("{}fn {}() {{", leading_ws, fn_name));
+                } else {
+                    out.push(String::new());
+                    skipping_from = Some(depth);
+                }
+                depth = (depth + opens).saturating_sub(closes);
+                continue;
+            }
+        }
+
+        depth = (depth + opens).saturating_sub(closes);
+        out.push(line.to_string());
+    }
+
+    (out.join("\n"), tests)
+}
+
+/// Rename a top-level `fn main(` so the harness can own `main`
+pub fn rename_user_main(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if line.len() == trimmed.len() && trimmed.starts_with("fn main(") {
+                line.replacen("fn main(", &format!("fn {}(", USER_MAIN_RENAMED), 1)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Find a top-level RustS+ function and return its return type (None = unit).
+/// Returns Err if the function does not exist.
+pub fn find_function_return(source: &str, name: &str) -> Result<Option<String>, String> {
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if !(trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ")) {
+            continue;
+        }
+        if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(trimmed) {
+            if sig.name == name {
+                return Ok(sig.return_type);
+            }
+        }
+    }
+    Err(format!("entry function `{}` not found", name))
+}
+
+/// Check whether the source defines its own top-level `fn main`
+pub fn defines_main(source: &str) -> bool {
+    source.lines().any(|line| {
+        let trimmed = line.trim_start();
+        line.len() == trimmed.len()
+            && (trimmed.starts_with("fn main(") || trimmed.starts_with("pub fn main("))
+    })
+}
+
+/// Generate a Rust `main` that forwards to a custom entry function
+pub fn generate_entry_main(entry: &str, return_type: Option<&str>) -> String {
+    match return_type {
+        // `fn start() Result[(), String]` → main returns the same Result
+        Some(rt) if rt.starts_with("Result") => {
+            let rt = crate::helpers::transform_generic_brackets(rt);
+            format!("\nfn main() -> {} {{\n    {}()\n}}\n", rt, entry)
+        }
+        Some(_) => format!("\nfn main() {{\n    let _ = {}();\n}}\n", entry),
+        None => format!("\nfn main() {{\n    {}();\n}}\n", entry),
+    }
+}
+
+/// Generate a Rust `main` that runs every test block and reports results
+pub fn generate_test_harness(tests: &[TestBlock]) -> String {
+    let mut out = String::new();
+    out.push_str("\nfn main() {\n");
+    out.push_str("    let tests: &[(&str, fn())] = &[\n");
+    for t in tests {
+        out.push_str(&format!("        ({:?}, {}),\n", t.name, t.fn_name));
+    }
+    out.push_str("    ];\n");
+    out.push_str("    println!(\"running {} test(s)\", tests.len());\n");
+    out.push_str("    let mut failed = 0;\n");
+    out.push_str("    for (name, test_fn) in tests {\n");
+    out.push_str("        match std::panic::catch_unwind(*test_fn) {\n");
+    out.push_str("            Ok(()) => println!(\"test {} ... ok\", name),\n");
+    out.push_str("            Err(_) => {\n");
+    out.push_str("                println!(\"test {} ... FAILED\", name);\n");
+    out.push_str("                failed += 1;\n");
+    out.push_str("            }\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n");
+    out.push_str("    let status = if failed == 0 { \"ok\" } else { \"FAILED\" };\n");
+    out.push_str("    println!(\"\\ntest result: {}. {} passed; {} failed\", status, tests.len() - failed, failed);\n");
+    out.push_str("    if failed > 0 {\n");
+    out.push_str("        std::process::exit(101);\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_test_header() {
+        assert_eq!(parse_test_header("test \"adds numbers\" {"), Some("adds numbers".to_string()));
+        assert_eq!(parse_test_header("test basic {"), Some("basic".to_string()));
+        assert_eq!(parse_test_header("test x == y {"), None);
+        assert_eq!(parse_test_header("testing = 1"), None);
+    }
+
+    #[test]
+    fn test_rewrite_keeps_line_count() {
+        let src = "fn add(a i32, b i32) i32 {\n    a + b\n}\n\ntest \"adds\" {\n    assert_eq!(add(1, 2), 3)\n}\n\nfn main() {\n}";
+        let (kept, tests) = rewrite_test_blocks(src, true);
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].fn_name, "__rustsp_test_adds");
+        assert_eq!(tests[0].line, 5);
+        assert!(kept.contains("fn __rustsp_test_adds() {"));
+        assert_eq!(kept.lines().count(), src.lines().count());
+
+        let (stripped, _) = rewrite_test_blocks(src, false);
+        assert!(!stripped.contains("assert_eq!"));
+        assert!(stripped.contains("fn main() {"));
+        assert_eq!(stripped.lines().count(), src.lines().count());
+    }
+
+    #[test]
+    fn test_nested_test_word_not_rewritten() {
+        let src = "fn f() {\n    test thing {\n    }\n}";
+        let (out, tests) = rewrite_test_blocks(src, true);
+        assert!(tests.is_empty());
+        assert_eq!(out, src);
+    }
+
+    #[test]
+    fn test_entry_main_generation() {
+        assert_eq!(generate_entry_main("start", None), "\nfn main() {\n    start();\n}\n");
+        assert!(generate_entry_main("start", Some("Result[(), String]"))
+            .contains("fn main() -> Result<(), String> {\n    start()\n}"));
+    }
+
+    #[test]
+    fn test_rename_user_main() {
+        let src = "fn main() {\n    run()\n}";
+        assert!(rename_user_main(src).starts_with("fn __rustsp_user_main() {"));
+        assert!(defines_main(src));
+        assert!(!defines_main(&rename_user_main(src)));
+    }
+}
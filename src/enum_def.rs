@@ -5,18 +5,42 @@
 //! - Enum instantiation
 //! - Pattern matching (pass-through to Rust)
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+use crate::helpers::transform_generic_brackets;
+use crate::lowering::depth_tracking_lowering::split_top_level_types;
+use crate::empty_literals::braced;
 
 /// Registry of known enum names
 #[derive(Debug, Clone, Default)]
 pub struct EnumRegistry {
     pub names: HashSet<String>,
+    /// Payload types per variant, keyed by `Enum::Variant`.
+    /// Types are stored in Rust syntax (bracket generics already lowered):
+    /// - `Batch(Vec[Tx])` → `["Vec<Tx>"]`
+    /// - `Move { x i32, y i32 }` → `["i32", "i32"]`
+    /// - Unit variants are not recorded
+    pub variant_payloads: HashMap<String, Vec<String>>,
+    /// Declared shape of every variant, keyed by `Enum::Variant`
+    pub variants: HashMap<String, VariantDecl>,
+}
+
+/// How a variant was declared, for checking constructions against it
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDecl {
+    pub kind: VariantKind,
+    /// Number of payload values (tuple fields or struct fields)
+    pub arity: usize,
+    /// 1-based line of the declaration
+    pub line: usize,
 }
 
 impl EnumRegistry {
     pub fn new() -> Self {
         EnumRegistry {
             names: HashSet::new(),
+            variant_payloads: HashMap::new(),
+            variants: HashMap::new(),
         }
     }
     
@@ -27,6 +51,31 @@ impl EnumRegistry {
     pub fn is_enum(&self, name: &str) -> bool {
         self.names.contains(name)
     }
+    
+    /// Record a payload type for `enum_name::variant` (appends for struct fields)
+    pub fn register_payload(&mut self, enum_name: &str, variant: &str, ty: &str) {
+        self.variant_payloads
+            .entry(format!("{}::{}", enum_name, variant))
+            .or_default()
+            .push(transform_generic_brackets(ty));
+    }
+    
+    /// Payload types of `enum_name::variant`, if it carries data
+    pub fn variant_payload(&self, enum_name: &str, variant: &str) -> Option<&[String]> {
+        self.variant_payloads
+            .get(&format!("{}::{}", enum_name, variant))
+            .map(|v| v.as_slice())
+    }
+    
+    /// Record how `enum_name::variant` is declared
+    pub fn register_variant(&mut self, enum_name: &str, variant: &str, decl: VariantDecl) {
+        self.variants.insert(format!("{}::{}", enum_name, variant), decl);
+    }
+    
+    /// Declaration of `enum_name::variant`, if known
+    pub fn variant(&self, enum_name: &str, variant: &str) -> Option<&VariantDecl> {
+        self.variants.get(&format!("{}::{}", enum_name, variant))
+    }
 }
 
 /// Check if a line starts an enum definition
@@ -141,10 +190,10 @@ pub fn transform_enum_variant(line: &str, in_struct_variant: bool) -> String {
         return line.to_string();
     }
     
-    // Already has comma at end, likely processed
-    if trimmed.ends_with(',') {
-        return line.to_string();
-    }
+    // The trailing comma is optional in the source; every form below
+    // writes its own
+    let trimmed = trimmed.strip_suffix(',').unwrap_or(trimmed).trim_end();
+    let line = &format!("{}{}", leading_ws, trimmed);
     
     // Inside struct variant - transform fields
     if in_struct_variant && !trimmed.contains('{') && !trimmed.contains('}') {
@@ -161,6 +210,11 @@ pub fn transform_enum_variant(line: &str, in_struct_variant: bool) -> String {
         return transform_struct_variant_line(line);
     }
     
+    // Unit variant with a discriminant: `Name = 2`
+    if trimmed.contains('=') {
+        return format!("{}{},", leading_ws, trimmed);
+    }
+    
     // Unit variant: just Name
     let name: String = trimmed
         .chars()
@@ -213,7 +267,7 @@ fn transform_struct_variant_line(line: &str) -> String {
             
             // Transform fields
             let transformed_fields = transform_inline_struct_fields(fields_part);
-            return format!("{}{} {{ {} }},", leading_ws, name, transformed_fields);
+            return format!("{}{},", leading_ws, braced(name, &transformed_fields));
         }
     }
     
@@ -236,7 +290,7 @@ fn transform_struct_variant_line(line: &str) -> String {
 
 /// Transform inline struct fields: "x i32, y i32" → "x: i32, y: i32"
 fn transform_inline_struct_fields(fields: &str) -> String {
-    let parts: Vec<&str> = fields.split(',').collect();
+    let parts = split_top_level_types(fields, ',');
     let transformed: Vec<String> = parts.iter()
         .map(|p| {
             let trimmed = p.trim();
@@ -311,6 +365,118 @@ fn transform_enum_init_fields(content: &str) -> String {
     result
 }
 
+/// Split a comma-separated list at top level only
+/// (commas inside `()`, `[]`, `<>` and `{}` are kept)
+fn split_top_level_commas(s: &str) -> Vec<String> {
+    split_top_level_types(s, ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parse the payload of a single-line variant
+///
+/// - `Batch(Vec[Tx])` → `("Batch", ["Vec[Tx]"])`
+/// - `Move { x i32, y i32 }` → `("Move", ["i32", "i32"])`
+/// - `Move {` → `("Move", [])` (fields follow on later lines)
+///
+/// Types are returned in RustS+ syntax; `register_payload` lowers them.
+pub fn parse_variant_payload(line: &str) -> Option<(String, Vec<String>)> {
+    let trimmed = line.trim().trim_end_matches(',');
+    
+    if trimmed.starts_with("#[") || trimmed.starts_with("//") {
+        return None;
+    }
+    
+    let name: String = trimmed
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if name.is_empty() || !name.chars().next().unwrap().is_uppercase() {
+        return None;
+    }
+    
+    let rest = trimmed[name.len()..].trim();
+    
+    if rest.starts_with('(') && rest.ends_with(')') {
+        let inner = &rest[1..rest.len() - 1];
+        return Some((name, split_top_level_commas(inner)));
+    }
+    
+    if let Some(body) = rest.strip_prefix('{') {
+        let body = body.trim_end().trim_end_matches('}');
+        let types = split_top_level_commas(body)
+            .iter()
+            .filter_map(|f| parse_struct_variant_field_type(f))
+            .collect();
+        return Some((name, types));
+    }
+    
+    None
+}
+
+/// Parse the shape of a single-line variant declaration
+///
+/// - `Ping` / `Ping = 3` → `("Ping", Unit, 0)`
+/// - `Point(i32, i32)` → `("Point", Tuple, 2)`
+/// - `Move { x i32, y i32 }` → `("Move", Struct, 2)`
+/// - `Move {` → `("Move", Struct, 0)` (fields follow on later lines)
+pub fn parse_variant_shape(line: &str) -> Option<(String, VariantKind, usize)> {
+    if let Some((name, types)) = parse_variant_payload(line) {
+        let rest = line.trim()[name.len()..].trim_start();
+        let kind = if rest.starts_with('(') { VariantKind::Tuple } else { VariantKind::Struct };
+        return Some((name, kind, types.len()));
+    }
+    
+    let trimmed = line.trim().trim_end_matches(',');
+    let name: String = trimmed
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if name.is_empty() || !name.chars().next().unwrap().is_uppercase() {
+        return None;
+    }
+    let rest = trimmed[name.len()..].trim();
+    if rest.is_empty() || rest.starts_with('=') {
+        Some((name, VariantKind::Unit, 0))
+    } else {
+        None
+    }
+}
+
+/// Parse the type of one struct variant field: `x i32` / `x: i32` → `i32`
+pub fn parse_struct_variant_field_type(field: &str) -> Option<String> {
+    let field = field.trim().trim_end_matches(',');
+    if field.is_empty() || field.starts_with("//") || field.starts_with("#[") {
+        return None;
+    }
+    
+    if let Some(colon) = field.find(':') {
+        let ty = field[colon + 1..].trim();
+        return if ty.is_empty() { None } else { Some(ty.to_string()) };
+    }
+    
+    let (_, ty) = field.split_once(char::is_whitespace)?;
+    let ty = ty.trim();
+    if ty.is_empty() { None } else { Some(ty.to_string()) }
+}
+
+/// Transform a single-line enum body: `A(Vec[u8]), Named { x i32 }, B`
+/// → `A(Vec<u8>), Named { x: i32 }, B`
+pub fn transform_inline_enum_body(body: &str) -> String {
+    split_top_level_commas(body)
+        .iter()
+        .map(|variant| {
+            let transformed = transform_enum_variant(variant, false);
+            let transformed = transformed.trim().trim_end_matches(',').to_string();
+            transform_generic_brackets(&transformed)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 /// Context for tracking enum parsing state
 #[derive(Debug, Clone)]
 pub struct EnumParseContext {
@@ -386,6 +552,61 @@ mod tests {
         assert_eq!(transform_inline_struct_fields("x i32, y i32"), "x: i32, y: i32");
     }
     
+    #[test]
+    fn test_parse_variant_payload() {
+        assert_eq!(
+            parse_variant_payload("    Batch(Vec[Tx])"),
+            Some(("Batch".to_string(), vec!["Vec[Tx]".to_string()]))
+        );
+        assert_eq!(
+            parse_variant_payload("    Index(HashMap[String, Vec[u64]], u32)"),
+            Some(("Index".to_string(), vec!["HashMap[String, Vec[u64]]".to_string(), "u32".to_string()]))
+        );
+        assert_eq!(
+            parse_variant_payload("    Named { items Vec[Tx], count u32 }"),
+            Some(("Named".to_string(), vec!["Vec[Tx]".to_string(), "u32".to_string()]))
+        );
+        assert_eq!(parse_variant_payload("    Ping"), None);
+    }
+    
+    #[test]
+    fn test_parse_variant_shape() {
+        assert_eq!(parse_variant_shape("    Ping,"), Some(("Ping".to_string(), VariantKind::Unit, 0)));
+        assert_eq!(parse_variant_shape("    Low = 1"), Some(("Low".to_string(), VariantKind::Unit, 0)));
+        assert_eq!(
+            parse_variant_shape("    Point(i32, Vec[u8])"),
+            Some(("Point".to_string(), VariantKind::Tuple, 2))
+        );
+        assert_eq!(
+            parse_variant_shape("    Move { x i32, y i32 }"),
+            Some(("Move".to_string(), VariantKind::Struct, 2))
+        );
+        assert_eq!(parse_variant_shape("    #[default]"), None);
+        assert_eq!(parse_variant_shape("    x i32"), None);
+    }
+    
+    #[test]
+    fn test_registry_payloads_lowered() {
+        let mut registry = EnumRegistry::new();
+        registry.register("Msg");
+        registry.register_payload("Msg", "Batch", "Vec[Tx]");
+        registry.register_payload("Msg", "Index", "HashMap[String, Vec[u64]]");
+        assert_eq!(registry.variant_payload("Msg", "Batch"), Some(&["Vec<Tx>".to_string()][..]));
+        assert_eq!(
+            registry.variant_payload("Msg", "Index"),
+            Some(&["HashMap<String, Vec<u64>>".to_string()][..])
+        );
+        assert_eq!(registry.variant_payload("Msg", "Ping"), None);
+    }
+    
+    #[test]
+    fn test_inline_enum_body() {
+        assert_eq!(
+            transform_inline_enum_body(" A(Vec[u8]), Named { x i32 }, B "),
+            "A(Vec<u8>), Named { x: i32 }, B"
+        );
+    }
+    
     #[test]
     fn test_attribute_passthrough() {
         // CRITICAL: Attributes with braces must NOT be transformed
@@ -30,6 +30,8 @@
 
 use std::fmt;
 
+use crate::fixes::Fix;
+
 //=============================================================================
 // ERROR CATEGORIES
 //=============================================================================
@@ -103,6 +105,16 @@ pub enum ErrorCode {
     RSPL025,
     /// Missing type annotation where required
     RSPL026,
+    /// Enum variant constructed with the wrong number of values
+    RSPL027,
+    /// Construct not available in the selected Rust edition
+    RSPL028,
+    /// Input exceeds a nesting, line length or file size limit
+    RSPL029,
+    /// Item the lowering failed on (compiler bug); the rest still lowered
+    RSPL030,
+    /// Unbalanced braces in the source
+    RSPL031,
     
     // Expression errors (040-059)
     /// Expression used as statement (missing semicolon context)
@@ -119,6 +131,8 @@ pub enum ErrorCode {
     RSPL045,
     /// String literal where String expected
     RSPL046,
+    /// Integer and float (or two widths) mixed in one operation
+    RSPL047,
     
     // Control flow errors (060-079)
     /// If expression missing else branch (when used as value)
@@ -157,6 +171,10 @@ pub enum ErrorCode {
     RSPL084,
     /// Invalid outer mutation target
     RSPL085,
+    /// Rust keyword used as a binding name
+    RSPL086,
+    /// Binding named like a macro (warning)
+    RSPL087,
     
     // Ownership errors (100-119)
     /// Move after borrow
@@ -231,6 +249,10 @@ pub enum ErrorCode {
     RSPL315,
     /// Effect borrow violation
     RSPL316,
+    /// `memo` on a function that is not pure
+    RSPL317,
+    /// File or socket handle not closed on some path
+    RSPL318,
 }
 
 impl ErrorCode {
@@ -249,6 +271,11 @@ impl ErrorCode {
             ErrorCode::RSPL024 => "RSPL024",
             ErrorCode::RSPL025 => "RSPL025",
             ErrorCode::RSPL026 => "RSPL026",
+            ErrorCode::RSPL027 => "RSPL027",
+            ErrorCode::RSPL028 => "RSPL028",
+            ErrorCode::RSPL029 => "RSPL029",
+            ErrorCode::RSPL030 => "RSPL030",
+            ErrorCode::RSPL031 => "RSPL031",
             // Expression
             ErrorCode::RSPL040 => "RSPL040",
             ErrorCode::RSPL041 => "RSPL041",
@@ -257,6 +284,7 @@ impl ErrorCode {
             ErrorCode::RSPL044 => "RSPL044",
             ErrorCode::RSPL045 => "RSPL045",
             ErrorCode::RSPL046 => "RSPL046",
+            ErrorCode::RSPL047 => "RSPL047",
             // Control flow
             ErrorCode::RSPL060 => "RSPL060",
             ErrorCode::RSPL061 => "RSPL061",
@@ -275,6 +303,8 @@ impl ErrorCode {
             ErrorCode::RSPL083 => "RSPL083",
             ErrorCode::RSPL084 => "RSPL084",
             ErrorCode::RSPL085 => "RSPL085",
+            ErrorCode::RSPL086 => "RSPL086",
+            ErrorCode::RSPL087 => "RSPL087",
             // Ownership
             ErrorCode::RSPL100 => "RSPL100",
             ErrorCode::RSPL101 => "RSPL101",
@@ -312,6 +342,8 @@ impl ErrorCode {
             ErrorCode::RSPL314 => "RSPL314",
             ErrorCode::RSPL315 => "RSPL315",
             ErrorCode::RSPL316 => "RSPL316",
+            ErrorCode::RSPL317 => "RSPL317",
+            ErrorCode::RSPL318 => "RSPL318",
         }
     }
     
@@ -321,16 +353,18 @@ impl ErrorCode {
             ErrorCode::RSPL001 | ErrorCode::RSPL002 | ErrorCode::RSPL003 => ErrorCategory::Logic,
             ErrorCode::RSPL020 | ErrorCode::RSPL021 | ErrorCode::RSPL022 |
             ErrorCode::RSPL023 | ErrorCode::RSPL024 | ErrorCode::RSPL025 |
-            ErrorCode::RSPL026 => ErrorCategory::Structure,
+            ErrorCode::RSPL026 | ErrorCode::RSPL027 | ErrorCode::RSPL028 |
+            ErrorCode::RSPL029 | ErrorCode::RSPL030 | ErrorCode::RSPL031 => ErrorCategory::Structure,
             ErrorCode::RSPL040 | ErrorCode::RSPL041 | ErrorCode::RSPL042 |
             ErrorCode::RSPL043 | ErrorCode::RSPL044 | ErrorCode::RSPL045 |
-            ErrorCode::RSPL046 => ErrorCategory::Expression,
+            ErrorCode::RSPL046 | ErrorCode::RSPL047 => ErrorCategory::Expression,
             ErrorCode::RSPL060 | ErrorCode::RSPL061 | ErrorCode::RSPL062 |
             ErrorCode::RSPL063 | ErrorCode::RSPL064 | ErrorCode::RSPL065 |
             ErrorCode::RSPL066 | ErrorCode::RSPL067 | ErrorCode::RSPL068 => ErrorCategory::ControlFlow,
             ErrorCode::RSPL071 |
             ErrorCode::RSPL080 | ErrorCode::RSPL081 | ErrorCode::RSPL082 |
-            ErrorCode::RSPL083 | ErrorCode::RSPL084 | ErrorCode::RSPL085 => ErrorCategory::Scope,
+            ErrorCode::RSPL083 | ErrorCode::RSPL084 | ErrorCode::RSPL085 |
+            ErrorCode::RSPL086 | ErrorCode::RSPL087 => ErrorCategory::Scope,
             ErrorCode::RSPL100 | ErrorCode::RSPL101 | ErrorCode::RSPL102 |
             ErrorCode::RSPL103 | ErrorCode::RSPL104 | ErrorCode::RSPL105 => ErrorCategory::Ownership,
             ErrorCode::RSPL120 | ErrorCode::RSPL121 | ErrorCode::RSPL122 |
@@ -342,7 +376,8 @@ impl ErrorCode {
             ErrorCode::RSPL306 | ErrorCode::RSPL307 | ErrorCode::RSPL308 |
             ErrorCode::RSPL309 | ErrorCode::RSPL310 | ErrorCode::RSPL311 |
             ErrorCode::RSPL312 | ErrorCode::RSPL313 | ErrorCode::RSPL314 |
-            ErrorCode::RSPL315 | ErrorCode::RSPL316 => ErrorCategory::Effect,
+            ErrorCode::RSPL315 | ErrorCode::RSPL316 | ErrorCode::RSPL317 |
+            ErrorCode::RSPL318 => ErrorCategory::Effect,
         }
     }
     
@@ -359,6 +394,11 @@ impl ErrorCode {
             ErrorCode::RSPL024 => "duplicate definition",
             ErrorCode::RSPL025 => "invalid field syntax",
             ErrorCode::RSPL026 => "missing type annotation",
+            ErrorCode::RSPL027 => "wrong number of variant values",
+            ErrorCode::RSPL028 => "not available in this edition",
+            ErrorCode::RSPL029 => "input limit exceeded",
+            ErrorCode::RSPL030 => "item could not be lowered",
+            ErrorCode::RSPL031 => "unbalanced braces",
             ErrorCode::RSPL040 => "expression as statement",
             ErrorCode::RSPL041 => "statement as expression",
             ErrorCode::RSPL042 => "invalid assignment target",
@@ -366,6 +406,7 @@ impl ErrorCode {
             ErrorCode::RSPL044 => "type mismatch",
             ErrorCode::RSPL045 => "invalid operator",
             ErrorCode::RSPL046 => "string literal vs String",
+            ErrorCode::RSPL047 => "mixed numeric types",
             ErrorCode::RSPL060 => "if missing else",
             ErrorCode::RSPL061 => "match missing arms",
             ErrorCode::RSPL062 => "match arm type mismatch",
@@ -382,6 +423,8 @@ impl ErrorCode {
             ErrorCode::RSPL083 => "used before init",
             ErrorCode::RSPL084 => "scope leak",
             ErrorCode::RSPL085 => "invalid outer target",
+            ErrorCode::RSPL086 => "keyword as identifier",
+            ErrorCode::RSPL087 => "binding shadows macro",
             ErrorCode::RSPL100 => "move after borrow",
             ErrorCode::RSPL101 => "mutable borrow conflict",
             ErrorCode::RSPL102 => "multiple mutable borrows",
@@ -415,6 +458,8 @@ impl ErrorCode {
             ErrorCode::RSPL314 => "effect contract violation",
             ErrorCode::RSPL315 => "effect ownership violation",
             ErrorCode::RSPL316 => "effect borrow violation",
+            ErrorCode::RSPL317 => "memo on effectful function",
+            ErrorCode::RSPL318 => "unclosed resource",
         }
     }
 }
@@ -479,6 +524,8 @@ pub struct RsplError {
     pub suggestion: Option<String>,
     /// Additional labels for multi-span errors
     pub labels: Vec<(SourceLocation, String)>,
+    /// Edits that repair the error, applied by `--fix`
+    pub fix: Option<Fix>,
 }
 
 impl RsplError {
@@ -491,6 +538,7 @@ impl RsplError {
             explanation: None,
             suggestion: None,
             labels: Vec::new(),
+            fix: None,
         }
     }
     
@@ -524,6 +572,12 @@ impl RsplError {
         self
     }
     
+    /// Attach the machine-applicable fix, if there is one
+    pub fn with_fix(mut self, fix: Option<Fix>) -> Self {
+        self.fix = fix;
+        self
+    }
+    
     /// Add additional label
     pub fn label(mut self, location: SourceLocation, message: impl Into<String>) -> Self {
         self.labels.push((location, message.into()));
@@ -587,6 +641,9 @@ impl RsplError {
                 let line_num_width = loc.line.to_string().len();
                 let padding = " ".repeat(line_num_width);
                 
+                if loc.file != self.location.file && !loc.file.is_empty() {
+                    output.push_str(&format!("  ::: {}:{}:{}\n", loc.file, loc.line, loc.column));
+                }
                 output.push_str(&format!("{}  |\n", padding));
                 output.push_str(&format!("{} |   {}\n", loc.line, loc.source_line));
                 
@@ -740,6 +797,110 @@ impl ErrorCollector {
     }
 }
 
+//=============================================================================
+// ERROR BUILDERS - Structure Errors
+//=============================================================================
+
+pub mod structure_errors {
+    use super::*;
+    use crate::enum_def::VariantKind;
+    use crate::input_limits::LimitKind;
+    
+    pub fn malformed_signature(message: &str) -> RsplError {
+        let help = if message.starts_with("Parameter ") {
+            "write each parameter as `name Type`:\n\n    fn f(a i32, b String) { ... }"
+        } else {
+            "a function signature looks like:\n\n    fn name[T](param Type) effects(...) ReturnType { ... }"
+        };
+        RsplError::new(ErrorCode::RSPL020, message.to_string()).help(help)
+    }
+    
+    pub fn variant_arity_mismatch(path: &str, kind: &VariantKind, arity: usize, given: usize, decl_line: usize) -> RsplError {
+        let values = |n: usize| if n == 1 { "1 value".to_string() } else { format!("{} values", n) };
+        let (message, help) = match kind {
+            VariantKind::Unit => (
+                format!("`{}` is a unit variant and takes no values", path),
+                format!("write it without parentheses: `{}`", path),
+            ),
+            VariantKind::Struct => (
+                format!("`{}` is a struct variant and cannot be called with values", path),
+                format!("name its fields: `{} {{ field = value, ... }}`", path),
+            ),
+            VariantKind::Tuple => (
+                format!("`{}` takes {} but {} given", path, values(arity), if given == 1 { "1 was".to_string() } else { format!("{} were", given) }),
+                format!("pass exactly {}", values(arity)),
+            ),
+        };
+        RsplError::new(ErrorCode::RSPL027, message)
+            .note(format!("the variant is declared on line {}", decl_line))
+            .help(help)
+    }
+    
+    pub fn unavailable_in_edition(construct: &str, edition: &str, since: &str) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL028,
+            format!("`{}` is not available in Rust {}", construct, edition)
+        )
+        .note(format!("`{}` needs edition {} or later", construct, since))
+        .help(format!("compile with `--edition {}` or later", since))
+    }
+    
+    pub fn input_limit_exceeded(kind: LimitKind, limit: usize, actual: usize) -> RsplError {
+        let (title, note) = match kind {
+            LimitKind::NestingDepth => (
+                format!("brackets nested more than {} deep", limit),
+                "an unclosed `(`, `[` or `{` is the usual cause; nothing after this point was checked",
+            ),
+            LimitKind::LineLength => (
+                format!("line of {} bytes exceeds the limit of {}", actual, limit),
+                "generated or minified code is the usual cause",
+            ),
+            LimitKind::FileSize => (
+                format!("source of {} bytes exceeds the limit of {}", actual, limit),
+                "very large inputs can be lowered in pieces with `Compiler::lower_streaming`",
+            ),
+        };
+        RsplError::new(ErrorCode::RSPL029, title)
+            .note(note)
+            .help(format!("raise the limit with `{} <n>` if the input is intended", kind.option()))
+    }
+    
+    /// The lowering failed inside `item` (`why`); no Rust was kept for it
+    pub fn item_not_lowered(item: &str, why: &str) -> RsplError {
+        RsplError::new(ErrorCode::RSPL030, format!("`{}` could not be lowered", item))
+            .note(format!("{}; its partial output was dropped", why))
+            .help("this is a compiler bug: please report it with this item")
+    }
+    
+    /// A `}` with no `{` left open before it
+    pub fn extra_closing_brace() -> RsplError {
+        RsplError::new(ErrorCode::RSPL031, "unbalanced braces: `}` with no matching `{`")
+            .note("this is a source error, not a compiler bug")
+            .help("remove the extra `}` or add the `{` it was meant to close")
+    }
+    
+    /// `count` braces still open at the end of the file; `open_blocks` are
+    /// the items among them, as (line, header)
+    pub fn unclosed_braces(count: usize, open_blocks: &[(usize, String)]) -> RsplError {
+        let title = if count == 1 {
+            "unbalanced braces: 1 `{` is never closed".to_string()
+        } else {
+            format!("unbalanced braces: {} `{{` are never closed", count)
+        };
+        let note = if open_blocks.len() > 1 {
+            let blocks: Vec<String> = open_blocks.iter()
+                .map(|(line, header)| format!("line {}: {}", line, header))
+                .collect();
+            format!("still open at the end of the file:\n    {}", blocks.join("\n    "))
+        } else {
+            "this is a source error, not a compiler bug".to_string()
+        };
+        RsplError::new(ErrorCode::RSPL031, title)
+            .note(note)
+            .help("a function or impl block is missing its closing `}`")
+    }
+}
+
 //=============================================================================
 // ERROR BUILDERS - Scope Errors
 //=============================================================================
@@ -788,6 +949,27 @@ pub mod scope_errors {
         .help("remove `outer` or declare the variable in an outer scope first")
     }
     
+    /// `outer` on a variable the function declares, but not in a scope
+    /// enclosing the `outer` line
+    pub fn outer_target_not_enclosing(var_name: &str, decl_line: usize, outer_line: usize) -> RsplError {
+        let help = if decl_line > outer_line {
+            format!(
+                "`{}` is first assigned on line {}, after this line:\n\
+                 assign it before line {} in an enclosing block, or remove `outer`",
+                var_name, decl_line, outer_line
+            )
+        } else {
+            format!(
+                "the `{0}` assigned on line {1} is in this block or in a block that has\n\
+                 already closed, and `outer` only reaches enclosing blocks:\n\
+                 remove `outer` (declaring `mut {0}` on line {1} to reassign it),\n\
+                 or move that assignment into an enclosing block",
+                var_name, decl_line
+            )
+        };
+        outer_not_found(var_name).help(help)
+    }
+    
     pub fn used_before_init(var_name: &str) -> RsplError {
         RsplError::new(
             ErrorCode::RSPL083,
@@ -816,6 +998,19 @@ pub mod scope_errors {
             var_name, var_name
         ))
     }
+    
+    pub fn binding_shadows_macro(name: &str) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL087,
+            format!("binding `{}` shadows the `{}!` macro", name, name)
+        )
+        .note(format!(
+            "`{}(...)` in the scope of this binding calls the binding, so the lowering\n\
+             does not turn it into the macro call `{}!(...)`.",
+            name, name
+        ))
+        .help(format!("rename the binding if `{}(...)` should still call the macro", name))
+    }
 }
 
 //=============================================================================
@@ -860,6 +1055,19 @@ pub mod control_flow_errors {
         .help("ensure all arms return the same type")
     }
     
+    pub fn unreachable_match_arm(pattern: &str, earlier_pattern: &str, earlier_line: usize) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL063,
+            format!("unreachable match arm `{}`", pattern)
+        )
+        .note(format!(
+            "every value this arm matches is already matched by `{}` (line {}).\n\
+             arms are tried in order, so this arm can never be selected.",
+            earlier_pattern, earlier_line
+        ))
+        .help("remove this arm, or move it before the arm that covers it")
+    }
+    
     pub fn match_non_exhaustive(missing: &str) -> RsplError {
         RsplError::new(
             ErrorCode::RSPL064,
@@ -869,6 +1077,22 @@ pub mod control_flow_errors {
         .help("add a `_ { ... }` arm to handle remaining cases")
     }
     
+    pub fn bool_match_missing_arm(header: &str, missing: bool, is_value: bool) -> RsplError {
+        let note = if is_value {
+            "the match is used as a value, so it must produce one for `true` and for `false`.\n\
+             arms with an `if` guard do not count: the guard may be false."
+        } else {
+            "a match must handle both `true` and `false`.\n\
+             arms with an `if` guard do not count: the guard may be false."
+        };
+        RsplError::new(
+            ErrorCode::RSPL064,
+            format!("`{}` has no arm for `{}`", header, missing)
+        )
+        .note(note)
+        .help(format!("add a `{} {{ ... }}` arm, or a `_ {{ ... }}` arm", missing))
+    }
+    
     pub fn break_outside_loop() -> RsplError {
         RsplError::new(
             ErrorCode::RSPL066,
@@ -892,6 +1116,19 @@ pub mod control_flow_errors {
         )
         .note("`return` can only be used inside a function body")
     }
+    
+    pub fn loop_without_exit(function: Option<&str>) -> RsplError {
+        let title = match function {
+            Some(name) => format!("`loop` in `{}` has no exit", name),
+            None => "`loop` has no exit".to_string(),
+        };
+        RsplError::new(ErrorCode::RSPL003, title)
+            .note(
+                "the loop body contains no `break`, `return`, `?` or call that ends the\n\
+                 program (`exit`, `panic`, ...), so this loop never finishes."
+            )
+            .help("add an exit condition, or mark an intended endless loop with `// allow(infinite-loop)`")
+    }
 }
 
 //=============================================================================
@@ -924,6 +1161,18 @@ pub mod expression_errors {
         )
         .help("RustS+ automatically converts literals in assignments")
     }
+    
+    pub fn mixed_numeric_types(expr: &str, left: &str, right: &str, fixed: &str) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL047,
+            format!("`{}` mixes `{}` and `{}`", expr, left, right)
+        )
+        .note(
+            "Rust never converts between numeric types implicitly:\n\
+             rustc rejects this operation in the generated code (E0277)."
+        )
+        .help(format!("convert one side explicitly: `{}`", fixed))
+    }
 }
 
 //=============================================================================
@@ -995,6 +1244,21 @@ pub mod effect_errors {
         ))
     }
     
+    /// `effects(io when ..)` with a condition that does not parse
+    pub fn invalid_effect_condition(effect: &str, condition: &str, problem: &str) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL305,
+            format!("invalid target condition `{}` on effect `{}`", condition, effect)
+        )
+        .note(format!(
+            "{}\n\n\
+             a condition combines target names (`wasm`, `native`, `unix`, `windows`,\n\
+             `linux`, `x86_64`, ...) with `not`, `and`, `or` and parentheses.",
+            problem
+        ))
+        .help(format!("for example: effects({} when not wasm)", effect))
+    }
+    
     /// Missing effect propagation from called function
     pub fn missing_propagation(caller: &str, callee: &str, effect: &str) -> RsplError {
         RsplError::new(
@@ -1041,20 +1305,27 @@ pub mod effect_errors {
         ))
     }
     
-    /// Effect leak to closure
-    pub fn effect_leak_closure(func_name: &str, effect: &str) -> RsplError {
+    /// Effect leak to closure: an annotated closure performs an effect
+    /// its `effects(declared)` does not list
+    pub fn effect_leak_closure(func_name: &str, effect: &str, declared: &str) -> RsplError {
+        let widened = if declared.is_empty() { effect.to_string() } else { format!("{}, {}", declared, effect) };
         RsplError::new(
             ErrorCode::RSPL303,
             format!("effect `{}` leaks to closure in function `{}`", effect, func_name)
         )
         .note(format!(
             "Effect-02 VIOLATION: Effect Leak\n\n\
-             effects cannot leak to closures, lambdas, or callbacks.\n\
-             the effect `{}` is used inside a closure without proper capture.\n\n\
-             this prevents effects from escaping their intended scope.",
-            effect
+             the closure is annotated `effects({})` but its body performs `{}`.\n\
+             an annotated closure must declare every effect it performs, so\n\
+             the effect cannot escape through the closure unseen.",
+            declared, effect
+        ))
+        .help(format!(
+            "either:\n\
+             1. Declare it on the closure: `|..| effects({}) {{ .. }}`\n\
+             2. Or move the effectful operation outside the closure",
+            widened
         ))
-        .help("move the effectful operation outside the closure, or explicitly capture the effect")
     }
     
     /// Write effect on parameter mutation
@@ -1189,6 +1460,27 @@ pub mod effect_errors {
     }
     
     /// Effect borrow violation
+    pub fn concurrent_write_conflict(binding: &str, spawn_line: usize) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL309,
+            format!("spawned thread writes `{}`, which belongs to the spawning function", binding)
+        )
+        .note(format!(
+            "Effect-06 VIOLATION: Concurrent Effect Conflict\n\n\
+             the block spawned on line {} moves what it captures.\n\
+             writing `{}` there changes the thread's own copy, not the\n\
+             function's, or does not compile at all.",
+            spawn_line, binding
+        ))
+        .help(format!(
+            "send the new value back instead:\n\n    \
+             (tx, rx) = channel()\n    \
+             h = spawn {{ tx.send(..).unwrap() }}\n    \
+             {} = rx.recv().unwrap()",
+            binding
+        ))
+    }
+    
     pub fn effect_borrow_violation(effect: &str, block: &str) -> RsplError {
         RsplError::new(
             ErrorCode::RSPL316,
@@ -1203,6 +1495,41 @@ pub mod effect_errors {
         ))
         .help("move the effectful operation outside the nested scope")
     }
+    
+    /// `memo fn` whose body does more than compute its result: `reason` is
+    /// "declares `io`", "performs `io`" or "calls `log`, which has effect `io`"
+    pub fn memo_not_pure(func_name: &str, reason: &str) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL317,
+            format!("memo function `{}` is not pure: it {}", func_name, reason)
+        )
+        .note(format!(
+            "a `memo` function returns the cached result when it is called again\n\
+             with the same arguments, without running its body. an effect in\n\
+             `{}` would happen only on the first call.",
+            func_name
+        ))
+        .help("remove `memo`, or move the effect to the caller and pass its result in")
+    }
+    
+    /// Handle `binding` opened by the line this is reported at, still open
+    /// at `leaked_at` (a `return` or the end of its block)
+    pub fn unclosed_resource(binding: &str, kind: &str, leaked_at: usize) -> RsplError {
+        RsplError::new(
+            ErrorCode::RSPL318,
+            format!("{} handle `{}` is not closed before line {}", kind, binding, leaked_at)
+        )
+        .note(format!(
+            "`{}` is neither dropped, closed nor moved on the path that leaves\n\
+             its scope at line {}, so the {} stays open until Rust drops it there.",
+            binding, leaked_at, kind
+        ))
+        .help(format!(
+            "close it when done with `drop({})`, or mark a handle meant to live\n\
+             to the end of its scope with `// allow(unclosed-resource)`",
+            binding
+        ))
+    }
 }
 
 //=============================================================================
@@ -0,0 +1,129 @@
+//! Exit Status and Summary Line
+//!
+//! Every run that gets as far as reading its input ends with one plain
+//! summary line on stderr and an exit code naming the kind of failure, so
+//! CI scripts can branch without parsing the colored report:
+//!
+//! | Code | Status     | Meaning                                          |
+//! |------|------------|--------------------------------------------------|
+//! | 0    | `ok`       | success                                          |
+//! | 1    | `usage`    | bad arguments, unreadable input, unwritable output |
+//! | 2    | `logic`    | Stage 1/2 rejected the program (RSPL0xx-2xx)     |
+//! | 3    | `effect`   | only effect errors (RSPL3xx)                     |
+//! | 4    | `internal` | the lowering failed or produced invalid Rust (compiler bug) |
+//! | 5    | `rustc`    | rustc failed or could not be run                 |
+//!
+//! ```text
+//! rustsp: ok functions=12 violations=0
+//! rustsp: effect functions=12 violations=2
+//! ```
+
+use std::fmt;
+
+use crate::error_msg::{ErrorCategory, ErrorCode, RsplError};
+use crate::function::{parse_function_line, strip_fn_modifiers, FunctionParseResult};
+use crate::verbatim::mask_verbatim_blocks;
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Ok,
+    Usage,
+    Logic,
+    Effect,
+    Internal,
+    Rustc,
+}
+
+impl ExitStatus {
+    /// The process exit code
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Ok => 0,
+            ExitStatus::Usage => 1,
+            ExitStatus::Logic => 2,
+            ExitStatus::Effect => 3,
+            ExitStatus::Internal => 4,
+            ExitStatus::Rustc => 5,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExitStatus::Ok => "ok",
+            ExitStatus::Usage => "usage",
+            ExitStatus::Logic => "logic",
+            ExitStatus::Effect => "effect",
+            ExitStatus::Internal => "internal",
+            ExitStatus::Rustc => "rustc",
+        }
+    }
+
+    /// `Effect` when every error is an effect error, `Internal` when every
+    /// error is an item the lowering failed on (RSPL030), `Logic` otherwise
+    pub fn for_errors(errors: &[RsplError]) -> Self {
+        if errors.is_empty() {
+            ExitStatus::Ok
+        } else if errors.iter().all(|e| e.code == ErrorCode::RSPL030) {
+            ExitStatus::Internal
+        } else if errors.iter().all(|e| e.category() == ErrorCategory::Effect) {
+            ExitStatus::Effect
+        } else {
+            ExitStatus::Logic
+        }
+    }
+}
+
+/// The last line a run prints: `rustsp: ok functions=12 violations=0`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    pub status: ExitStatus,
+    pub functions: usize,
+    pub violations: usize,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rustsp: {} functions={} violations={}",
+            self.status.as_str(), self.functions, self.violations)
+    }
+}
+
+/// Functions defined in `source` (RustS+ and plain Rust signatures, outside
+/// `rust { }` blocks)
+pub fn count_functions(source: &str) -> usize {
+    let (masked, _) = mask_verbatim_blocks(source);
+    let (stripped, _) = strip_fn_modifiers(&masked);
+    stripped
+        .lines()
+        .filter(|line| !matches!(parse_function_line(line), FunctionParseResult::NotAFunction))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_msg::SourceLocation;
+
+    #[test]
+    fn test_summary_line() {
+        let summary = RunSummary { status: ExitStatus::Effect, functions: 12, violations: 2 };
+        assert_eq!(summary.to_string(), "rustsp: effect functions=12 violations=2");
+        assert_eq!(summary.status.code(), 3);
+    }
+
+    #[test]
+    fn test_status_for_errors() {
+        let error = |code| RsplError::new(code, "x").at(SourceLocation::new("", 1, 0));
+        assert_eq!(ExitStatus::for_errors(&[]), ExitStatus::Ok);
+        assert_eq!(ExitStatus::for_errors(&[error(ErrorCode::RSPL300)]), ExitStatus::Effect);
+        assert_eq!(ExitStatus::for_errors(&[error(ErrorCode::RSPL300), error(ErrorCode::RSPL001)]), ExitStatus::Logic);
+        assert_eq!(ExitStatus::for_errors(&[error(ErrorCode::RSPL030)]), ExitStatus::Internal);
+    }
+
+    #[test]
+    fn test_count_functions() {
+        let src = "inline fn a() i32 = 1\n\nfn b(x: i32) -> i32 {\n    x\n}\n\nfn main() {\n    s = \"fn c() {\"\n}";
+        assert_eq!(count_functions(src), 3);
+    }
+}
@@ -0,0 +1,458 @@
+//! Fallible Functions: `!T`
+//!
+//! A function whose return type is `!T` returns a `T` or fails:
+//!
+//! ```text
+//! fn load(path String) !Config {        fn load(path: String) -> Result<Config, Box<dyn std::error::Error>> {
+//!     text = fs::read_to_string(path)?      let text = fs::read_to_string(path)?;
+//!     parse_config(text)                    parse_config(text)
+//! }                                     }
+//! ```
+//!
+//! The error is boxed, so `?` propagates any error type. With
+//! [`crate::LoweringOptions::error_enums`] (`--thiserror`, for
+//! `--emit-cargo-workspace`) each fallible function gets an error enum of
+//! its own instead, with `thiserror` derives and one variant per distinct
+//! failure site, a `?` applied to a call:
+//!
+//! ```text
+//! fn load(path: String) -> Result<Config, LoadError> {
+//!     let text = fs::read_to_string(path).map_err(|e| LoadError::ReadToString(e.into()))?;
+//!     parse_config(text)
+//! }
+//! #[derive(Debug, thiserror::Error)]
+//! enum LoadError {
+//!     #[error("`read_to_string` failed")]
+//!     ReadToString(#[source] Box<dyn std::error::Error + Send + Sync>),
+//! }
+//! ```
+//!
+//! A call of another fallible function of the file keeps that function's
+//! enum as the source. A call of a function declared `Result[T, E]` gets a
+//! variant named after `E` that holds it, shared by every site with that
+//! error type. A method's enum is prefixed with the type of its `impl`
+//! (`ConfigParseError`). The enum follows the item the function is in (the
+//! function itself, or its `impl`) and has the function's visibility.
+
+use std::collections::HashMap;
+
+use crate::helpers::strip_inline_comment;
+use crate::ident::is_ident_continue;
+use crate::lowering::depth_tracking_lowering::{
+    count_braces_outside_strings, delimiter_positions, find_matching_close, find_matching_open,
+    find_outside_strings, split_top_level_types,
+};
+use crate::modules::parse_mod_decl;
+
+/// Error type of a fallible function without an error enum
+const BOXED_ERROR: &str = "Box[dyn std::error::Error]";
+
+/// Source of a variant whose error type is not known
+const BOXED_SOURCE: &str = "Box<dyn std::error::Error + Send + Sync>";
+
+/// Clauses that may stand between the parameters and the return type
+const CLAUSES: &[&str] = &["effects(", "requires(", "ensures("];
+
+/// Generated error enum of one fallible function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorEnum {
+    /// `LoadError`
+    pub name: String,
+    /// Visibility of the function, with its trailing space: `pub `
+    pub visibility: String,
+    /// One per distinct failure site
+    pub variants: Vec<ErrorVariant>,
+}
+
+/// One variant of an [`ErrorEnum`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorVariant {
+    /// `ReadToString`
+    pub name: String,
+    /// Function whose error it holds: `read_to_string`
+    pub callee: String,
+    /// Type of the error it holds
+    pub source: String,
+    /// `#[source]` of the enum; false for a user-declared `Result[T, E]`
+    /// error type, which need not implement `Error` and is shown with `Debug`
+    pub chained: bool,
+}
+
+impl ErrorEnum {
+    /// Rust definition of the enum, one line per element
+    pub fn to_rust(&self) -> Vec<String> {
+        let mut lines = vec![
+            "#[derive(Debug, thiserror::Error)]".to_string(),
+            format!("{}enum {} {{", self.visibility, self.name),
+        ];
+        for variant in &self.variants {
+            if variant.chained {
+                lines.push(format!("    #[error(\"`{}` failed\")]", variant.callee));
+                lines.push(format!("    {}(#[source] {}),", variant.name, variant.source));
+            } else {
+                lines.push("    #[error(\"{0:?}\")]".to_string());
+                lines.push(format!("    {}({}),", variant.name, variant.source));
+            }
+        }
+        lines.push("}".to_string());
+        lines
+    }
+
+    /// The variant for a site calling `callee` with error type `source`,
+    /// added if there is none yet
+    fn variant(&mut self, name: String, callee: &str, source: &str, chained: bool) -> String {
+        let mut candidate = name.clone();
+        for n in 2.. {
+            match self.variants.iter().find(|v| v.name == candidate) {
+                Some(v) if v.source == source => return candidate,
+                Some(_) => candidate = format!("{}{}", name, n),
+                None => break,
+            }
+        }
+        self.variants.push(ErrorVariant { name: candidate.clone(), callee: callee.to_string(), source: source.to_string(), chained });
+        candidate
+    }
+}
+
+/// A one-line function signature
+struct Signature<'a> {
+    name: &'a str,
+    /// `pub `, `pub(crate) ` or empty
+    visibility: &'a str,
+    /// Byte range of a `!T` return type, `!` included
+    fallible: Option<(usize, usize)>,
+    /// `E` of a `Result[T, E]` return type
+    error_type: Option<&'a str>,
+    /// Whether the body opens on this line
+    opens_body: bool,
+}
+
+fn parse_signature(code: &str) -> Option<Signature<'_>> {
+    let fn_pos = code.find("fn ")?;
+    let mut visibility = "";
+    for word in code[..fn_pos].split_whitespace() {
+        match word {
+            "async" | "const" | "unsafe" => {}
+            _ if word == "pub" || word.starts_with("pub(") => {
+                let start = code.find(word)?;
+                visibility = &code[start..start + word.len() + 1];
+            }
+            _ => return None,
+        }
+    }
+    let after = fn_pos + "fn ".len();
+    let name_len = code[after..].find(|c: char| !is_ident_continue(c)).unwrap_or(code.len() - after);
+    let name = &code[after..after + name_len];
+    let open = after + find_outside_strings(&code[after..], '(')?;
+    let mut pos = find_matching_close(code, open)? + 1;
+    loop {
+        pos += code[pos..].len() - code[pos..].trim_start().len();
+        match CLAUSES.iter().find(|clause| code[pos..].starts_with(**clause)) {
+            Some(clause) => pos = find_matching_close(code, pos + clause.len() - 1)? + 1,
+            None => break,
+        }
+    }
+    let rest = &code[pos..];
+    let body = find_outside_strings(rest, '{');
+    let mut sig = Signature { name, visibility, fallible: None, error_type: None, opens_body: body.is_some() };
+    let return_end = ["where ", "{"].iter()
+        .filter_map(|end| rest.find(end))
+        .min()
+        .unwrap_or(rest.len());
+    let return_type = rest[..return_end].trim_end();
+    if return_type.len() > 1 && return_type.starts_with('!') && !return_type.starts_with("!=") {
+        sig.fallible = Some((pos, pos + return_type.len()));
+    } else if let Some(args) = return_type.strip_prefix("Result").filter(|a| a.starts_with(['[', '<']) && a.ends_with([']', '>'])) {
+        let error = split_top_level_types(&args[1..args.len() - 1], ',').get(1).map(|e| e.trim());
+        sig.error_type = error.filter(|e| !e.is_empty());
+    }
+    Some(sig)
+}
+
+/// `read_to_string` → `ReadToString`
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Type an `impl` header is for: `impl[T] Display for Stack[T] {` → `Stack`
+fn impl_type(code: &str) -> Option<String> {
+    let rest = code.trim_start().strip_prefix("impl")?;
+    if !rest.starts_with([' ', '[', '<']) {
+        return None;
+    }
+    let rest = match rest.find(" for ") {
+        Some(pos) => &rest[pos + " for ".len()..],
+        None => rest,
+    };
+    let mut rest = rest.trim_start();
+    if rest.starts_with(['[', '<']) {
+        let close = split_top_level_types(rest, ' ').first()?.len();
+        rest = rest[close..].trim_start();
+    }
+    let name: String = rest.chars().take_while(|&c| is_ident_continue(c)).collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Functions of the source: fallible ones and their enum, and the error
+/// type of those declared `Result[T, E]`, by (impl type, name)
+#[derive(Default)]
+struct Declared {
+    enums: HashMap<(Option<String>, String), usize>,
+    errors: HashMap<(Option<String>, String), String>,
+}
+
+/// Walk the lines of `source` with the `impl` each one is in
+fn for_each_line<'a>(source: &'a str, mut visit: impl FnMut(&'a str, String, Option<&str>, usize)) {
+    let mut depth = 0usize;
+    // Depth inside each open `impl`, and its type
+    let mut impls: Vec<(usize, String)> = Vec::new();
+    for line in source.lines() {
+        let code = strip_inline_comment(line);
+        let before = depth;
+        let (opens, closes) = count_braces_outside_strings(&code);
+        depth = (depth + opens).saturating_sub(closes);
+        let header = impl_type(&code).filter(|_| depth > before);
+        visit(line, code, impls.last().map(|(_, ty)| ty.as_str()), before);
+        if let Some(ty) = header {
+            impls.push((before + 1, ty));
+        }
+        while impls.last().is_some_and(|&(inside, _)| depth < inside) {
+            impls.pop();
+        }
+    }
+}
+
+/// Rewrite the `!T` return types of `source`. With `error_enums`, also
+/// route each failure site through the function's error enum, returned in
+/// order of the functions.
+pub fn lower_fallible(source: &str, error_enums: bool) -> (String, Vec<ErrorEnum>) {
+    let mut enums: Vec<ErrorEnum> = Vec::new();
+    let mut declared = Declared::default();
+    for_each_line(source, |_, code, impl_ty, _| {
+        let Some(sig) = parse_signature(&code) else { return };
+        let key = (impl_ty.map(String::from), sig.name.to_string());
+        if sig.fallible.is_some() {
+            declared.enums.insert(key, enums.len());
+            enums.push(ErrorEnum {
+                name: format!("{}{}Error", impl_ty.unwrap_or(""), camel_case(sig.name)),
+                visibility: sig.visibility.to_string(),
+                variants: Vec::new(),
+            });
+        } else if let Some(error) = sig.error_type {
+            declared.errors.insert(key, error.to_string());
+        }
+    });
+    if enums.is_empty() {
+        return (source.to_string(), enums);
+    }
+
+    let mut next = 0;
+    // Depth inside each open function body, and its enum
+    let mut fns: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut lines = Vec::new();
+    for_each_line(source, |line, code, impl_ty, before| {
+        while fns.last().is_some_and(|&(inside, _)| before < inside) {
+            fns.pop();
+        }
+        let comment = &line[code.len()..];
+        let sig = parse_signature(&code);
+        let mut lowered = code.clone();
+        if let Some((start, end)) = sig.as_ref().and_then(|sig| sig.fallible) {
+            let error = if error_enums { enums[next].name.as_str() } else { BOXED_ERROR };
+            lowered = format!("{}Result[{}, {}]{}", &code[..start], &code[start + 1..end], error, &code[end..]);
+        }
+        match sig {
+            Some(sig) => {
+                let index = sig.fallible.map(|_| {
+                    next += 1;
+                    next - 1
+                });
+                if sig.opens_body {
+                    fns.push((before + 1, index));
+                }
+            }
+            None => {
+                if let Some(&(_, Some(index))) = fns.last().filter(|_| error_enums) {
+                    lowered = rewrite_failure_sites(&code, impl_ty, &mut enums, index, &declared);
+                }
+            }
+        }
+        lines.push(format!("{}{}", lowered, comment));
+    });
+    let mut out = lines.join("\n");
+    if source.ends_with('\n') {
+        out.push('\n');
+    }
+    if !error_enums {
+        enums.clear();
+    }
+    (out, enums)
+}
+
+/// Convert the error of every `call(..)?` in `code` for enum `index`
+fn rewrite_failure_sites(code: &str, impl_ty: Option<&str>, enums: &mut [ErrorEnum], index: usize, declared: &Declared) -> String {
+    let mut out = code.to_string();
+    for (question, _) in delimiter_positions(code, &['?']).into_iter().rev() {
+        if !code[..question].ends_with(')') {
+            continue;
+        }
+        let Some(mut end) = find_matching_open(code, question - 1) else { continue };
+        // `parse[i32]()`
+        if code[..end].ends_with(']') {
+            match find_matching_open(code, end - 1) {
+                Some(open) => end = open,
+                None => continue,
+            }
+        }
+        let start = code[..end].trim_end_matches(is_ident_continue).len();
+        let callee = &code[start..end];
+        if callee.is_empty() || callee.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let path = &code[..start];
+        let impl_key = if path.ends_with("self.") || path.ends_with("Self::") {
+            Some(impl_ty.map(String::from))
+        } else if !path.ends_with('.') && !path.ends_with("::") {
+            Some(None)
+        } else {
+            None
+        };
+        let key = impl_key.map(|ty| (ty, callee.to_string()));
+        let own = key.as_ref().and_then(|key| declared.enums.get(key)).map(|&i| enums[i].name.clone());
+        let user = key.as_ref().and_then(|key| declared.errors.get(key));
+        let variant = match (own, user) {
+            (Some(source), _) => enums[index].variant(camel_case(callee), callee, &source, true),
+            (None, Some(error)) => {
+                let name = error.rsplit("::").next().unwrap_or(error).split(['[', '<']).next().unwrap_or(error);
+                let source = error.replace('[', "<").replace(']', ">");
+                enums[index].variant(name.to_string(), callee, &source, false)
+            }
+            (None, None) => enums[index].variant(camel_case(callee), callee, BOXED_SOURCE, true),
+        };
+        out.insert_str(question, &format!(".map_err(|e| {}::{}(e.into()))", enums[index].name, variant));
+    }
+    out
+}
+
+/// Put each enum of `enums` after the item of the Rust output its function
+/// is in: the function itself, or the `impl` around it. With `same_line`
+/// (`preserve_lines`) the enum goes on the line closing that item.
+pub fn emit_error_enums(rust: &str, enums: &[ErrorEnum], same_line: bool) -> String {
+    if enums.is_empty() {
+        return rust.to_string();
+    }
+    let mut out: Vec<String> = Vec::new();
+    let mut placed = vec![false; enums.len()];
+    let mut depth = 0usize;
+    // Depth inside each open inline `mod`
+    let mut mods: Vec<usize> = Vec::new();
+    // Enums waiting for the item at this depth to close
+    let mut pending: Vec<(usize, usize)> = Vec::new();
+    for line in rust.lines() {
+        let code = strip_inline_comment(line);
+        let trimmed = code.trim();
+        let before = depth;
+        let (opens, closes) = count_braces_outside_strings(&code);
+        depth = (depth + opens).saturating_sub(closes);
+        if trimmed.contains("fn ") {
+            for (i, e) in enums.iter().enumerate() {
+                if !placed[i] && trimmed.contains(&format!(", {}>", e.name)) {
+                    placed[i] = true;
+                    pending.push((mods.last().copied().unwrap_or(0), i));
+                }
+            }
+        }
+        if trimmed.strip_suffix('{').and_then(|head| parse_mod_decl(head.trim_end())).is_some() {
+            mods.push(before + 1);
+        }
+        while mods.last().is_some_and(|&inside| depth < inside) {
+            mods.pop();
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut line = line.to_string();
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|&(host, _)| depth <= host);
+        pending = waiting;
+        let mut after = Vec::new();
+        for (_, i) in ready {
+            let definition = enums[i].to_rust();
+            if same_line {
+                line.push(' ');
+                line.push_str(&definition.iter().map(|l| l.trim()).collect::<Vec<_>>().join(" "));
+            } else {
+                after.extend(definition.into_iter().map(|l| format!("{}{}", indent, l)));
+            }
+        }
+        out.push(line);
+        out.extend(after);
+    }
+    // A function the output no longer shows: the enum goes at the end
+    for (e, _) in enums.iter().zip(&placed).filter(|(_, placed)| !**placed) {
+        out.extend(e.to_rust());
+    }
+    let mut joined = out.join("\n");
+    if rust.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOAD: &str = "fn parse_config(text String) !i32 {\n    text.trim().parse[i32]()?\n}\n\npub fn load(path String) effects(io) !i32 { // the entry\n    text = std::fs::read_to_string(path)?\n    n = parse_config(text)?\n    Ok(n + parse_config(String::from(\"?\"))?)\n}\n";
+
+    #[test]
+    fn test_boxed_by_default() {
+        let (lowered, enums) = lower_fallible(LOAD, false);
+        assert!(enums.is_empty());
+        assert!(lowered.contains("fn parse_config(text String) Result[i32, Box[dyn std::error::Error]] {"), "{}", lowered);
+        assert!(lowered.contains("pub fn load(path String) effects(io) Result[i32, Box[dyn std::error::Error]] { // the entry"), "{}", lowered);
+        assert!(lowered.contains("    text = std::fs::read_to_string(path)?\n"), "{}", lowered);
+    }
+
+    #[test]
+    fn test_error_enum_per_function() {
+        let (lowered, enums) = lower_fallible(LOAD, true);
+        assert!(lowered.contains("fn parse_config(text String) Result[i32, ParseConfigError] {"), "{}", lowered);
+        assert!(lowered.contains("    text.trim().parse[i32]().map_err(|e| ParseConfigError::Parse(e.into()))?\n"), "{}", lowered);
+        assert!(lowered.contains("    text = std::fs::read_to_string(path).map_err(|e| LoadError::ReadToString(e.into()))?\n"), "{}", lowered);
+        assert!(lowered.contains("    Ok(n + parse_config(String::from(\"?\")).map_err(|e| LoadError::ParseConfig(e.into()))?)"), "{}", lowered);
+        assert_eq!(enums[1].to_rust(), [
+            "#[derive(Debug, thiserror::Error)]",
+            "pub enum LoadError {",
+            "    #[error(\"`read_to_string` failed\")]",
+            "    ReadToString(#[source] Box<dyn std::error::Error + Send + Sync>),",
+            "    #[error(\"`parse_config` failed\")]",
+            "    ParseConfig(#[source] ParseConfigError),",
+            "}",
+        ]);
+    }
+
+    #[test]
+    fn test_declared_error_types_and_methods() {
+        let source = "fn check(x i32) Result[i32, ConfigError] {\n    Ok(x)\n}\n\nimpl Config {\n    fn limit(self) !i32 {\n        a = check(self.a)?\n        b = check(self.b)?\n        Ok(a + b)\n    }\n}\n";
+        let (lowered, enums) = lower_fallible(source, true);
+        assert!(lowered.contains("fn limit(self) Result[i32, ConfigLimitError] {"), "{}", lowered);
+        assert!(lowered.contains("check(self.b).map_err(|e| ConfigLimitError::ConfigError(e.into()))?"), "{}", lowered);
+        assert_eq!(enums[0].variants.len(), 1);
+        assert!(enums[0].to_rust().contains(&"    ConfigError(ConfigError),".to_string()));
+    }
+
+    #[test]
+    fn test_enum_follows_its_item() {
+        let rust = "impl Config {\n    fn limit(self) -> Result<i32, ConfigLimitError> {\n        Ok(1)\n    }\n}\nfn main() {\n}\n";
+        let e = ErrorEnum { name: "ConfigLimitError".to_string(), visibility: String::new(), variants: Vec::new() };
+        let emitted = emit_error_enums(rust, std::slice::from_ref(&e), false);
+        assert!(emitted.contains("    }\n}\n#[derive(Debug, thiserror::Error)]\nenum ConfigLimitError {\n}\nfn main() {"), "{}", emitted);
+        let aligned = emit_error_enums(rust, &[e], true);
+        assert_eq!(aligned.lines().count(), rust.lines().count());
+        assert!(aligned.contains("\n} #[derive(Debug, thiserror::Error)] enum ConfigLimitError { }\n"), "{}", aligned);
+    }
+}
@@ -3,24 +3,98 @@
 //! This module contains functions for the first pass of transpilation:
 //! - Registering struct/enum/function definitions
 //! - Tracking clone requirements for array elements
-//! - Transitive clone detection
+//! - Transitive clone detection, with the reason each type needs `Clone`
+//! - Types deriving `Copy` (their array elements need no `.clone()`)
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use crate::helpers::strip_inline_comment;
 use crate::detection::detect_array_literal_start;
-use crate::clone_helpers::{detect_type_from_element, extract_array_var_from_access, is_cloneable_array_access};
+use crate::clone_helpers::{
+    clone_requirements, detect_type_from_element, element_type,
+    extract_array_var_from_access, is_cloneable_array_access,
+};
 use crate::variable::{VariableTracker, parse_rusts_assignment_ext};
+use crate::for_loops::{plan_for_loop, Iteration};
 use crate::struct_def::{StructRegistry, is_struct_definition, parse_struct_header};
-use crate::enum_def::{EnumRegistry, is_enum_definition, parse_enum_header};
-use crate::function::{parse_function_line, FunctionParseResult, FunctionRegistry};
+use crate::constants::{parse_const_decl, ConstRegistry};
+use crate::generic_types::generic_type_header;
+use crate::enum_def::{
+    EnumRegistry, VariantDecl, is_enum_definition, parse_enum_header,
+    parse_variant_payload, parse_variant_shape, parse_struct_variant_field_type,
+};
+use crate::function::{parse_function_line, FunctionParseResult, FunctionRegistry, Parameter};
 
 /// Result of first pass analysis
+#[derive(Default)]
 pub struct FirstPassResult {
     pub fn_registry: FunctionRegistry,
     pub struct_registry: StructRegistry,
     pub enum_registry: EnumRegistry,
     pub types_need_clone: HashSet<String>,
+    /// Why each type in `types_need_clone` needs it (first reason found)
+    pub clone_reasons: BTreeMap<String, CloneReason>,
+    /// Structs/enums with `#[derive(Copy)]`
+    pub copy_types: HashSet<String>,
+    /// Structs/enums declared with type parameters (`struct Wrapper[T]`)
+    pub generic_types: HashSet<String>,
+    /// `const` / `static` names and their types
+    pub const_registry: ConstRegistry,
+}
+
+/// Why the lowering derives `Clone` for a type. Lines are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CloneReason {
+    /// `x = array[i]` on `line` copies an element out of `array`, whose
+    /// element type `element` was learned on `element_line`
+    ArrayAccess { line: usize, array: String, element: String, element_line: usize },
+    /// The `for` loop on `line` iterates a clone of `array` (see
+    /// [`crate::for_loops`]), whose element type was learned on `element_line`
+    LoopClone { line: usize, array: String, element: String, element_line: usize },
+    /// Arguments for by-value parameter `param` of `function` (declared on
+    /// `line`) are cloned at call sites
+    ByValueParam { line: usize, function: String, param: String, param_type: String },
+    /// The type appears on `line` inside `container`, which needs `Clone`
+    Contained { line: usize, container: String },
+}
+
+impl CloneReason {
+    /// Source line the reason points at
+    pub fn line(&self) -> usize {
+        match self {
+            CloneReason::ArrayAccess { line, .. }
+            | CloneReason::LoopClone { line, .. }
+            | CloneReason::ByValueParam { line, .. }
+            | CloneReason::Contained { line, .. } => *line,
+        }
+    }
+}
+
+impl FirstPassResult {
+    /// Merge the first pass over another piece of the same program
+    /// (used by the streaming lowering, which scans the input piecewise)
+    pub fn merge(&mut self, other: FirstPassResult) {
+        self.fn_registry.merge(other.fn_registry);
+        self.struct_registry.names.extend(other.struct_registry.names);
+        self.enum_registry.names.extend(other.enum_registry.names);
+        self.enum_registry.variant_payloads.extend(other.enum_registry.variant_payloads);
+        self.enum_registry.variants.extend(other.enum_registry.variants);
+        self.types_need_clone.extend(other.types_need_clone);
+        for (name, reason) in other.clone_reasons {
+            self.clone_reasons.entry(name).or_insert(reason);
+        }
+        self.copy_types.extend(other.copy_types);
+        self.generic_types.extend(other.generic_types);
+        self.const_registry.merge(other.const_registry);
+    }
+    
+    /// Re-run transitive clone detection once every piece is merged: a type
+    /// defined in one piece may contain a type defined in another. The
+    /// lines are not source lines, so no reasons are recorded.
+    pub fn propagate_clones(&mut self, type_def_lines: &[&str]) {
+        let type_contents = build_type_contents(type_def_lines, &self.struct_registry, &self.enum_registry);
+        propagate_clone_requirements(&mut self.types_need_clone, &type_contents, &mut BTreeMap::new());
+    }
 }
 
 /// Run the first pass analysis over source lines
@@ -28,41 +102,124 @@ pub fn run_first_pass(
     lines: &[&str],
     tracker: &mut VariableTracker,
 ) -> FirstPassResult {
-    let mut fn_registry = FunctionRegistry::new();
-    let mut struct_registry = StructRegistry::new();
-    let mut enum_registry = EnumRegistry::new();
+    let mut scan = FirstPassScan::default();
+    for (line_num, line) in lines.iter().enumerate() {
+        let clean_line = strip_inline_comment(line);
+        scan.scan_line(line_num, &clean_line, tracker);
+        scan.scan_for_loop(lines, line_num);
+    }
+    scan.finish()
+}
+
+/// Line-by-line state of the first pass, so a caller that already scans
+/// the source (see [`crate::program_index`]) can drive it
+#[derive(Default)]
+pub(crate) struct FirstPassScan {
+    fn_registry: FunctionRegistry,
+    struct_registry: StructRegistry,
+    enum_registry: EnumRegistry,
     
-    //=========================================================================
     // CLONE INJECTION TRACKING (L-04 Enhancement)
-    // Track: array_var → element_type, types that need Clone
-    //=========================================================================
-    let mut array_element_types: HashMap<String, String> = HashMap::new();
-    let mut types_need_clone: HashSet<String> = HashSet::new();
-    let mut current_array_var: Option<String> = None;
+    // Track: array_var → (element_type, line it was learned on), types that need Clone
+    array_element_types: HashMap<String, (String, usize)>,
+    types_need_clone: HashSet<String>,
+    clone_reasons: BTreeMap<String, CloneReason>,
+    /// Line of each function signature, for by-value parameter reasons
+    fn_lines: HashMap<String, usize>,
+    current_array_var: Option<String>,
+    copy_types: HashSet<String>,
+    /// Is there a `#[derive(.., Copy)]` above the upcoming definition?
+    pending_copy_derive: bool,
+    generic_types: HashSet<String>,
+    const_registry: ConstRegistry,
     
-    let mut brace_depth: usize = 0;
+    brace_depth: usize,
     
-    // CRITICAL FIX: Track multi-line function signatures in first pass
-    let mut first_pass_fn_acc: Option<String> = None;
+    /// CRITICAL FIX: Track multi-line function signatures in first pass
+    /// (with the line the signature starts on)
+    first_pass_fn_acc: Option<(String, usize)>,
     
-    // First pass: register structs, enums, functions, track assignments
-    for (line_num, line) in lines.iter().enumerate() {
-        let clean_line = strip_inline_comment(line);
+    /// Enum payload tracking: (enum_name, open multi-line struct variant)
+    current_enum: Option<(String, Option<String>)>,
+    
+    /// Lines inside struct/enum definitions, for transitive clone detection
+    type_defs: TypeDefLines,
+}
+
+impl FirstPassScan {
+    /// Register structs, enums, functions and track assignments on one line
+    /// (`clean_line` has its inline comment stripped)
+    pub(crate) fn scan_line(&mut self, line_num: usize, clean_line: &str, tracker: &mut VariableTracker) {
         let trimmed = clean_line.trim();
+        self.type_defs.scan_line(line_num, trimmed);
+        
+        tracker.scan_for_mut_borrows(clean_line);
+        
+        if trimmed.starts_with("#[derive(") && derives_copy(trimmed) {
+            self.pending_copy_derive = true;
+        } else if !trimmed.starts_with("#[") && !trimmed.is_empty() {
+            let copy_derived = std::mem::take(&mut self.pending_copy_derive);
+            let type_name = if is_struct_definition(trimmed) {
+                parse_struct_header(trimmed)
+            } else if is_enum_definition(trimmed) {
+                parse_enum_header(trimmed)
+            } else {
+                None
+            };
+            if let (true, Some(name)) = (copy_derived, type_name) {
+                self.copy_types.insert(name);
+            }
+        }
         
-        tracker.scan_for_mut_borrows(&clean_line);
+        if let Some(name) = generic_type_header(trimmed) {
+            self.generic_types.insert(name);
+        }
+        
+        if let Some(decl) = parse_const_decl(trimmed) {
+            self.const_registry.register(decl.name, decl.ty);
+        }
         
         // Register struct names
         if is_struct_definition(trimmed) {
             if let Some(name) = parse_struct_header(trimmed) {
-                struct_registry.register(&name);
+                self.struct_registry.register(&name);
             }
         }
         
         // Register enum names
         if is_enum_definition(trimmed) {
             if let Some(name) = parse_enum_header(trimmed) {
-                enum_registry.register(&name);
+                self.enum_registry.register(&name);
+                if trimmed.ends_with('{') {
+                    self.current_enum = Some((name, None));
+                } else if let Some((open, close)) = trimmed.find('{').zip(trimmed.rfind('}')).filter(|(open, close)| open < close) {
+                    // Single-line enum: `enum Kind { A(Vec[u8]), B }`
+                    record_inline_enum_payloads(&name, &trimmed[open + 1..close], line_num + 1, &mut self.enum_registry);
+                }
+            }
+        } else if let Some((enum_name, struct_variant)) = self.current_enum.as_mut() {
+            // Record variant payload types for later call-arg/coercion logic
+            if trimmed == "}" {
+                if struct_variant.is_some() {
+                    *struct_variant = None;
+                } else {
+                    self.current_enum = None;
+                }
+            } else if let Some(variant) = struct_variant.as_ref() {
+                if let Some(ty) = parse_struct_variant_field_type(trimmed) {
+                    self.enum_registry.register_payload(enum_name, variant, &ty);
+                    if let Some(decl) = self.enum_registry.variants.get_mut(&format!("{}::{}", enum_name, variant)) {
+                        decl.arity += 1;
+                    }
+                }
+            } else if let Some((variant, kind, arity)) = parse_variant_shape(trimmed) {
+                for ty in parse_variant_payload(trimmed).map(|(_, types)| types).unwrap_or_default() {
+                    self.enum_registry.register_payload(enum_name, &variant, &ty);
+                }
+                self.enum_registry.register_variant(enum_name, &variant, VariantDecl { kind, arity, line: line_num + 1 });
+                if trimmed.ends_with('{') {
+                    *struct_variant = Some(variant);
+                }
             }
         }
         
@@ -77,7 +234,7 @@ pub fn run_first_pass(
         //=====================================================================
         
         // Continue accumulating multi-line function
-        if let Some(ref mut acc) = first_pass_fn_acc {
+        if let Some((ref mut acc, start_line)) = self.first_pass_fn_acc {
             acc.push(' ');
             acc.push_str(trimmed);
             
@@ -87,11 +244,12 @@ pub fn run_first_pass(
             // Signature complete when parens balanced and contains `{`
             if paren_opens == paren_closes && acc.contains('{') {
                 if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(acc) {
-                    fn_registry.register(sig);
+                    self.fn_lines.insert(sig.name.clone(), start_line);
+                    self.fn_registry.register(sig);
                 }
-                first_pass_fn_acc = None;
+                self.first_pass_fn_acc = None;
             }
-            continue;
+            return;
         }
         
         // Register function signatures (single-line or start of multi-line)
@@ -102,11 +260,12 @@ pub fn run_first_pass(
             if paren_opens == paren_closes && trimmed.contains('{') {
                 // Complete single-line signature
                 if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(trimmed) {
-                    fn_registry.register(sig);
+                    self.fn_lines.insert(sig.name.clone(), line_num);
+                    self.fn_registry.register(sig);
                 }
             } else if paren_opens > paren_closes {
                 // Start of multi-line signature
-                first_pass_fn_acc = Some(trimmed.to_string());
+                self.first_pass_fn_acc = Some((trimmed.to_string(), line_num));
             }
         }
         
@@ -116,41 +275,59 @@ pub fn run_first_pass(
         
         // Detect array literal start: `events = [`
         if let Some((var_name, _, _)) = detect_array_literal_start(trimmed) {
-            current_array_var = Some(var_name);
+            self.current_array_var = Some(var_name);
         }
         
         // Detect array elements and extract type
-        if current_array_var.is_some() && !trimmed.starts_with('[') && !trimmed.is_empty() {
+        if self.current_array_var.is_some() && !trimmed.starts_with('[') && !trimmed.is_empty() {
             if trimmed == "]" {
-                current_array_var = None;
-            } else if let Some(ref var) = current_array_var {
+                self.current_array_var = None;
+            } else if let Some(ref var) = self.current_array_var {
                 // Try to detect type from this element
                 if let Some(elem_type) = detect_type_from_element(trimmed) {
-                    array_element_types.insert(var.clone(), elem_type);
+                    self.array_element_types.insert(var.clone(), (elem_type, line_num));
                 }
             }
         }
         
+        // Parsed once, for clone tracking and assignment tracking below
+        let assignment = parse_rusts_assignment_ext(clean_line);
+        
+        // Element type from an annotation: `users: Vec[Option[User]] = ...`
+        if let Some((var_name, Some(var_type), _, _, _)) = &assignment {
+            if let Some(elem_type) = element_type(var_type) {
+                self.array_element_types.insert(var_name.clone(), (elem_type, line_num));
+            }
+        }
+        
         // Detect array access that will get .clone(): `x = arr[i]`
-        if let Some((_, _, value, _, _)) = parse_rusts_assignment_ext(&clean_line) {
-            if is_cloneable_array_access(&value) {
-                if let Some(arr_var) = extract_array_var_from_access(&value) {
-                    // Mark the element type as needing Clone
-                    if let Some(elem_type) = array_element_types.get(&arr_var) {
-                        types_need_clone.insert(elem_type.clone());
+        if let Some((_, _, value, _, _)) = &assignment {
+            if is_cloneable_array_access(value) {
+                if let Some(arr_var) = extract_array_var_from_access(value) {
+                    // Mark the element type (or the type an `Option` wraps) as needing Clone
+                    if let Some((elem_type, elem_line)) = self.array_element_types.get(&arr_var) {
+                        for name in clone_requirements(elem_type) {
+                            self.clone_reasons.entry(name.clone()).or_insert_with(|| CloneReason::ArrayAccess {
+                                line: line_num + 1,
+                                array: arr_var.clone(),
+                                element: elem_type.clone(),
+                                element_line: elem_line + 1,
+                            });
+                            self.types_need_clone.insert(name);
+                        }
                     }
                 }
             }
         }
         
-        brace_depth += trimmed.matches('{').count();
-        brace_depth = brace_depth.saturating_sub(trimmed.matches('}').count());
+        self.brace_depth += trimmed.matches('{').count();
+        self.brace_depth = self.brace_depth.saturating_sub(trimmed.matches('}').count());
         
-        if trimmed.starts_with("let ") { continue; }
+        if trimmed.starts_with("let ") { return; }
         
         // CRITICAL: Use extended parser to detect explicit `mut` keyword
         // `mut x = 10` means x is DECLARED here, subsequent `x = ...` are mutations
-        if let Some((var_name, var_type, value, _is_outer, is_explicit_mut)) = parse_rusts_assignment_ext(&clean_line) {
+        if let Some((var_name, var_type, value, _is_outer, is_explicit_mut)) = assignment {
             tracker.track_assignment(line_num, &var_name, var_type, &value, false);
             // If explicit mut, mark variable as mutable immediately
             if is_explicit_mut {
@@ -159,19 +336,106 @@ pub fn run_first_pass(
         }
     }
     
-    //=========================================================================
-    // TRANSITIVE CLONE DETECTION
-    // If Event needs Clone and contains Node, then Node also needs Clone
-    // We scan type definitions to find nested type references
-    //=========================================================================
-    let type_contents = build_type_contents(lines, &struct_registry, &enum_registry);
-    propagate_clone_requirements(&mut types_need_clone, &type_contents);
+    /// A `for` loop that iterates a clone of an array clones its elements.
+    /// References are not known here, so a loop the lowering iterates by
+    /// value anyway may still count.
+    pub(crate) fn scan_for_loop(&mut self, lines: &[&str], line_num: usize) {
+        if !lines[line_num].trim_start().starts_with("for ") {
+            return;
+        }
+        let Some(plan) = plan_for_loop(lines, line_num, |_| false, |_| false) else {
+            return;
+        };
+        if plan.iteration != Iteration::Clone {
+            return;
+        }
+        let Some((elem_type, elem_line)) = self.array_element_types.get(&plan.iterable) else {
+            return;
+        };
+        for name in clone_requirements(elem_type) {
+            self.clone_reasons.entry(name.clone()).or_insert_with(|| CloneReason::LoopClone {
+                line: line_num + 1,
+                array: plan.iterable.clone(),
+                element: elem_type.clone(),
+                element_line: elem_line + 1,
+            });
+            self.types_need_clone.insert(name);
+        }
+    }
     
-    FirstPassResult {
-        fn_registry,
-        struct_registry,
-        enum_registry,
-        types_need_clone,
+    pub(crate) fn finish(mut self) -> FirstPassResult {
+        // Struct and `Option[Struct]` arguments passed by value are cloned;
+        // visited in source order so the recorded reasons are deterministic
+        let mut auto_cloned: Vec<(Option<usize>, &str, &Parameter)> = self.fn_registry.auto_cloned_params()
+            .map(|(function, param)| (self.fn_lines.get(function).copied(), function, param))
+            .collect();
+        auto_cloned.sort_by(|a, b| (a.0.is_none(), a.0, a.1, &a.2.name).cmp(&(b.0.is_none(), b.0, b.1, &b.2.name)));
+        for (line, function, param) in auto_cloned {
+            for name in clone_requirements(&param.param_type) {
+                if let Some(line) = line {
+                    self.clone_reasons.entry(name.clone()).or_insert_with(|| CloneReason::ByValueParam {
+                        line: line + 1,
+                        function: function.to_string(),
+                        param: param.name.clone(),
+                        param_type: param.param_type.clone(),
+                    });
+                }
+                self.types_need_clone.insert(name);
+            }
+        }
+        
+        //=====================================================================
+        // TRANSITIVE CLONE DETECTION
+        // If Event needs Clone and contains Node, then Node also needs Clone
+        // We scan type definitions to find nested type references
+        //=====================================================================
+        let type_contents = self.type_defs.contents(&self.struct_registry, &self.enum_registry);
+        propagate_clone_requirements(&mut self.types_need_clone, &type_contents, &mut self.clone_reasons);
+        
+        FirstPassResult {
+            fn_registry: self.fn_registry,
+            struct_registry: self.struct_registry,
+            enum_registry: self.enum_registry,
+            types_need_clone: self.types_need_clone,
+            clone_reasons: self.clone_reasons,
+            copy_types: self.copy_types,
+            generic_types: self.generic_types,
+            const_registry: self.const_registry,
+        }
+    }
+}
+
+/// Does a `#[derive(...)]` line list `Copy`?
+fn derives_copy(attr: &str) -> bool {
+    attr.trim_start_matches("#[derive(")
+        .split([',', ')'])
+        .any(|d| d.trim() == "Copy")
+}
+
+/// Record payloads of every variant in a single-line enum body
+fn record_inline_enum_payloads(enum_name: &str, body: &str, line: usize, registry: &mut EnumRegistry) {
+    let mut depth = 0i32;
+    let mut start = 0;
+    let bytes = body.as_bytes();
+    for i in 0..=bytes.len() {
+        let at_split = i == bytes.len() || (bytes[i] == b',' && depth == 0);
+        if at_split {
+            if let Some((variant, types)) = parse_variant_payload(&body[start..i]) {
+                for ty in &types {
+                    registry.register_payload(enum_name, &variant, ty);
+                }
+            }
+            if let Some((variant, kind, arity)) = parse_variant_shape(&body[start..i]) {
+                registry.register_variant(enum_name, &variant, VariantDecl { kind, arity, line });
+            }
+            start = i + 1;
+            continue;
+        }
+        match bytes[i] {
+            b'(' | b'[' | b'{' | b'<' => depth += 1,
+            b')' | b']' | b'}' | b'>' => depth -= 1,
+            _ => {}
+        }
     }
 }
 
@@ -180,68 +444,92 @@ fn build_type_contents(
     lines: &[&str],
     struct_registry: &StructRegistry,
     enum_registry: &EnumRegistry,
-) -> HashMap<String, Vec<String>> {
-    let mut in_type_def: Option<String> = None;  // Currently inside which type definition
-    let mut type_contents: HashMap<String, Vec<String>> = HashMap::new(); // type → contained types
-    
-    for line in lines.iter() {
-        let clean_line = strip_inline_comment(line);
-        let trimmed = clean_line.trim();
-        
+) -> HashMap<String, Vec<(String, usize)>> {
+    let mut type_defs = TypeDefLines::default();
+    for (line_num, line) in lines.iter().enumerate() {
+        type_defs.scan_line(line_num, strip_inline_comment(line).trim());
+    }
+    type_defs.contents(struct_registry, enum_registry)
+}
+
+/// Body lines of struct/enum definitions, by type
+#[derive(Default)]
+struct TypeDefLines {
+    /// Currently inside which type definition
+    in_type_def: Option<String>,
+    /// (type, line number, trimmed line)
+    lines: Vec<(String, usize, String)>,
+}
+
+impl TypeDefLines {
+    fn scan_line(&mut self, line_num: usize, trimmed: &str) {
         // Detect struct/enum definition start
         if is_struct_definition(trimmed) {
             if let Some(name) = parse_struct_header(trimmed) {
-                in_type_def = Some(name);
+                self.in_type_def = Some(name);
             }
         } else if is_enum_definition(trimmed) {
             if let Some(name) = parse_enum_header(trimmed) {
-                in_type_def = Some(name);
+                self.in_type_def = Some(name);
             }
-        } else if trimmed == "}" && in_type_def.is_some() {
-            in_type_def = None;
-        } else if let Some(ref type_name) = in_type_def {
+        } else if trimmed == "}" && self.in_type_def.is_some() {
+            self.in_type_def = None;
+        } else if let Some(ref type_name) = self.in_type_def {
+            self.lines.push((type_name.clone(), line_num, trimmed.to_string()));
+        }
+    }
+    
+    /// type → (contained type, line it appears on), once every type is registered
+    fn contents(&self, struct_registry: &StructRegistry, enum_registry: &EnumRegistry) -> HashMap<String, Vec<(String, usize)>> {
+        let mut type_contents: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+        for (type_name, line_num, trimmed) in &self.lines {
             // We're inside a type definition - look for references to other types
             // Check for patterns like: Init(Node), field Node, Node,
             for struct_name in struct_registry.names.iter() {
                 if trimmed.contains(struct_name) {
                     type_contents.entry(type_name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(struct_name.clone());
+                        .or_default()
+                        .push((struct_name.clone(), *line_num));
                 }
             }
             for enum_name in enum_registry.names.iter() {
                 if trimmed.contains(enum_name) && enum_name != type_name {
                     type_contents.entry(type_name.clone())
-                        .or_insert_with(Vec::new)
-                        .push(enum_name.clone());
+                        .or_default()
+                        .push((enum_name.clone(), *line_num));
                 }
             }
         }
+        for contained in type_contents.values_mut() {
+            contained.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+        }
+        type_contents
     }
-    
-    type_contents
 }
 
-/// Propagate Clone requirement transitively
-/// Repeat until no new types are added
+/// Propagate Clone requirement transitively, breadth-first in name order so
+/// each added type is credited to its nearest container deterministically
 fn propagate_clone_requirements(
     types_need_clone: &mut HashSet<String>,
-    type_contents: &HashMap<String, Vec<String>>,
+    type_contents: &HashMap<String, Vec<(String, usize)>>,
+    clone_reasons: &mut BTreeMap<String, CloneReason>,
 ) {
-    loop {
-        let mut added_any = false;
-        for type_name in types_need_clone.clone().iter() {
-            if let Some(contained) = type_contents.get(type_name) {
-                for contained_type in contained {
-                    if !types_need_clone.contains(contained_type) {
-                        types_need_clone.insert(contained_type.clone());
-                        added_any = true;
-                    }
+    let mut frontier: Vec<String> = types_need_clone.iter().cloned().collect();
+    frontier.sort();
+    while !frontier.is_empty() {
+        let mut next = Vec::new();
+        for type_name in &frontier {
+            for (contained_type, line_num) in type_contents.get(type_name).into_iter().flatten() {
+                if types_need_clone.insert(contained_type.clone()) {
+                    clone_reasons.entry(contained_type.clone()).or_insert_with(|| CloneReason::Contained {
+                        line: line_num + 1,
+                        container: type_name.clone(),
+                    });
+                    next.push(contained_type.clone());
                 }
             }
         }
-        if !added_any {
-            break;
-        }
+        next.sort();
+        frontier = next;
     }
 }
\ No newline at end of file
@@ -0,0 +1,387 @@
+//! Machine-Applicable Fixes (`--fix`)
+//!
+//! Some Stage 1 diagnostics have exactly one sensible repair. Those carry a
+//! [`Fix`]: the textual edits to the `.rss` file, placed by the spans the
+//! diagnostic already points at.
+//!
+//! | Diagnostic                          | Fix                                      |
+//! |-------------------------------------|------------------------------------------|
+//! | `RSPL071` reassignment without mut  | `mut ` before the first assignment       |
+//! | `RSPL081` unintended shadowing      | `outer ` before the inner assignment     |
+//! | `RSPL300` / `RSPL301` effects       | the effect added to `effects(..)`        |
+//! | `RSPL064` bool match missing an arm | a `_ {}` arm, for a match not used as a value |
+//!
+//! Only bool matches get the `_ {}` fix: a match on another type that
+//! misses a case has no Stage 1 diagnostic to carry one, and rustc reports
+//! it instead.
+//!
+//! `rustsp main.rss --fix` applies them in rounds, re-running Stage 1 after
+//! each, since one fix can reveal the next (an effect added to `f` must then
+//! be propagated to its callers). The file is kept as `main.rss.bak`;
+//! `--fix --dry-run` prints the unified diff instead of writing, and exits
+//! with the status Stage 1 would report on the fixed file.
+
+use crate::error_msg::RsplError;
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, delimiter_positions};
+
+/// Rounds of check-and-fix before giving up on reaching a fixed point
+const MAX_ROUNDS: usize = 16;
+
+/// Replace `delete` bytes at byte `offset` of 1-based `line` with `insert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub line: usize,
+    pub offset: usize,
+    pub delete: usize,
+    pub insert: String,
+}
+
+impl TextEdit {
+    pub fn insert(line: usize, offset: usize, text: impl Into<String>) -> Self {
+        TextEdit { line, offset, delete: 0, insert: text.into() }
+    }
+}
+
+/// The edits that repair one diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    /// What the fix does, e.g. "declare `x` as `mut`"
+    pub description: String,
+    pub edits: Vec<TextEdit>,
+}
+
+/// `mut ` before the assignment on `line` that first declares `name`
+pub fn declare_mut(lines: &[String], line: usize, name: &str) -> Option<Fix> {
+    let offset = assignment_start(lines.get(line.checked_sub(1)?)?, name, true)?;
+    Some(Fix {
+        description: format!("declare `{}` as `mut`", name),
+        edits: vec![TextEdit::insert(line, offset, "mut ")],
+    })
+}
+
+/// `outer ` before the assignment to `name` on `line`
+pub fn assign_outer(lines: &[String], line: usize, name: &str) -> Option<Fix> {
+    let offset = assignment_start(lines.get(line.checked_sub(1)?)?, name, false)?;
+    Some(Fix {
+        description: format!("assign the outer `{}`", name),
+        edits: vec![TextEdit::insert(line, offset, "outer ")],
+    })
+}
+
+/// Byte offset of `name` in a line that assigns it: `name = ..`, or with a
+/// type (`name T = ..`) when `typed` is allowed
+fn assignment_start(line: &str, name: &str, typed: bool) -> Option<usize> {
+    let offset = line.len() - line.trim_start().len();
+    let rest = line[offset..].strip_prefix(name)?;
+    if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let is_plain = rest.starts_with('=') && !rest.starts_with("==");
+    let is_typed = typed && !rest.is_empty() && !rest.starts_with(['=', '.', '[', '(']) && has_assignment(rest);
+    (is_plain || is_typed).then_some(offset)
+}
+
+/// Whether `rest` has an `=` that is not part of `==`, `!=`, `<=`, `>=` or `=>`
+fn has_assignment(rest: &str) -> bool {
+    let bytes = rest.as_bytes();
+    (0..bytes.len()).any(|i| {
+        bytes[i] == b'='
+            && bytes.get(i + 1).is_none_or(|next| !matches!(next, b'=' | b'>'))
+            && (i == 0 || !matches!(bytes[i - 1], b'=' | b'!' | b'<' | b'>'))
+    })
+}
+
+/// `effect` added to the `effects(..)` clause of the function declared on
+/// `line`, or a new clause right after its parameters
+pub fn declare_effect(lines: &[String], line: usize, function: &str, effect: &str) -> Option<Fix> {
+    let (params_line, close) = parameters_end(lines, line)?;
+    let text = &lines[params_line - 1];
+    let after = &text[close + 1..];
+    let edit = match after.find("effects(") {
+        Some(clause) => {
+            let open = close + 1 + clause + "effects".len();
+            let end = delimiter_positions(&text[open..], &['(', ')'])
+                .into_iter()
+                .scan(0usize, |depth, (pos, delimiter)| {
+                    *depth = if delimiter == '(' { *depth + 1 } else { depth.saturating_sub(1) };
+                    Some((pos, *depth))
+                })
+                .find(|(_, depth)| *depth == 0)?
+                .0;
+            let separator = if text[open + 1..open + end].trim().is_empty() { "" } else { ", " };
+            TextEdit::insert(params_line, open + end, format!("{}{}", separator, effect))
+        }
+        None => TextEdit::insert(params_line, close + 1, format!(" effects({})", effect)),
+    };
+    Some(Fix {
+        description: format!("declare effect `{}` on `{}`", effect, function),
+        edits: vec![edit],
+    })
+}
+
+/// 1-based line and byte offset of the `)` closing the parameters of the
+/// function whose signature starts on `line`
+fn parameters_end(lines: &[String], line: usize) -> Option<(usize, usize)> {
+    let first = lines.get(line.checked_sub(1)?)?;
+    let open = first.find("fn ")? + first[first.find("fn ")?..].find('(')?;
+    let mut depth = 0usize;
+    for (idx, text) in lines.iter().enumerate().skip(line - 1).take(16) {
+        let start = if idx == line - 1 { open } else { 0 };
+        for (pos, delimiter) in delimiter_positions(&text[start..], &['(', ')']) {
+            if delimiter == '(' {
+                depth += 1;
+            } else {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some((idx + 1, start + pos));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// A `_ {}` arm for the match on `line`: before the line closing it, or
+/// before the final `}` of a match written on one line
+pub fn add_catch_all_arm(lines: &[String], line: usize) -> Option<Fix> {
+    let header = lines.get(line.checked_sub(1)?)?;
+    let edit = match crate::bool_match::find_inline_match(header.trim()) {
+        Some(found) => {
+            let indent = header.len() - header.trim_start().len();
+            TextEdit::insert(line, indent + found.end - 1, "_ {} ")
+        }
+        None => {
+            let mut depth = 0usize;
+            let mut close = None;
+            for (idx, text) in lines.iter().enumerate().skip(line - 1) {
+                let (opens, closes) = count_braces_outside_strings(crate::helpers::strip_inline_comment(text).as_ref());
+                depth = (depth + opens).checked_sub(closes)?;
+                if depth == 0 {
+                    close = Some(idx);
+                    break;
+                }
+            }
+            let close = close.filter(|close| *close > line - 1 && lines[*close].trim_start().starts_with('}'))?;
+            let arm_indent = lines[line..close].iter()
+                .find(|text| !text.trim().is_empty())
+                .map(|text| &text[..text.len() - text.trim_start().len()])
+                .unwrap_or("    ");
+            TextEdit::insert(close + 1, 0, format!("{}_ {{}}\n", arm_indent))
+        }
+    };
+    Some(Fix { description: "add a `_ {}` arm".to_string(), edits: vec![edit] })
+}
+
+/// Apply `fixes` to `source`. A fix touching a line an earlier fix already
+/// edited is left for the next round; returns the new source and the fixes
+/// applied.
+pub fn apply_fixes<'a>(source: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> (String, Vec<&'a Fix>) {
+    let mut touched = std::collections::BTreeSet::new();
+    let mut applied: Vec<&Fix> = Vec::new();
+    for fix in fixes {
+        if applied.contains(&fix) || fix.edits.iter().any(|edit| touched.contains(&edit.line)) {
+            continue;
+        }
+        touched.extend(fix.edits.iter().map(|edit| edit.line));
+        applied.push(fix);
+    }
+
+    let mut lines: Vec<String> = source.split('\n').map(String::from).collect();
+    let mut edits: Vec<&TextEdit> = applied.iter().flat_map(|fix| &fix.edits).collect();
+    // Last first, so earlier positions stay valid
+    edits.sort_by_key(|edit| std::cmp::Reverse((edit.line, edit.offset)));
+    for edit in edits {
+        if let Some(text) = lines.get_mut(edit.line - 1) {
+            let end = (edit.offset + edit.delete).min(text.len());
+            text.replace_range(edit.offset.min(end)..end, &edit.insert);
+        }
+    }
+    (lines.join("\n"), applied)
+}
+
+/// Source and applied fixes after [`fix_source`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixOutcome {
+    pub source: String,
+    /// Description of every fix applied, in order
+    pub applied: Vec<String>,
+}
+
+/// Check `source` with `check` and apply the fixes of its diagnostics,
+/// round after round until no diagnostic has one left
+pub fn fix_source(source: &str, mut check: impl FnMut(&str) -> Vec<RsplError>) -> FixOutcome {
+    let mut outcome = FixOutcome { source: source.to_string(), applied: Vec::new() };
+    for _ in 0..MAX_ROUNDS {
+        let errors = check(&outcome.source);
+        let (fixed, applied) = apply_fixes(&outcome.source, errors.iter().filter_map(|error| error.fix.as_ref()));
+        if applied.is_empty() {
+            break;
+        }
+        outcome.applied.extend(applied.iter().map(|fix| fix.description.clone()));
+        outcome.source = fixed;
+    }
+    outcome
+}
+
+/// Unified diff of `before` and `after` with 3 lines of context
+pub fn unified_diff(path: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let ops = diff_lines(&old, &new);
+    let mut out = String::new();
+    if ops.iter().all(|op| matches!(op, DiffOp::Equal(..))) {
+        return out;
+    }
+    out.push_str(&format!("--- a/{}\n+++ b/{}\n", path, path));
+
+    const CONTEXT: usize = 3;
+    let changed: Vec<usize> = (0..ops.len()).filter(|&i| !matches!(ops[i], DiffOp::Equal(..))).collect();
+    // Changes closer than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in &changed {
+        match hunks.last_mut() {
+            Some((_, last)) if change - *last <= 2 * CONTEXT => *last = change,
+            _ => hunks.push((change, change)),
+        }
+    }
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT);
+        let end = (last + CONTEXT + 1).min(ops.len());
+        let (old_start, new_start) = ops[..start].iter().fold((0, 0), |(o, n), op| op.advance(o, n));
+        let (old_len, new_len) = ops[start..end].iter().fold((0, 0), |(o, n), op| op.advance(o, n));
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start + 1, old_len, new_start + 1, new_len));
+        for op in &ops[start..end] {
+            let (sign, text) = match op {
+                DiffOp::Equal(text) => (' ', text),
+                DiffOp::Delete(text) => ('-', text),
+                DiffOp::Insert(text) => ('+', text),
+            };
+            out.push_str(&format!("{}{}\n", sign, text));
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+impl DiffOp<'_> {
+    /// Old and new line counts after this op
+    fn advance(&self, old: usize, new: usize) -> (usize, usize) {
+        match self {
+            DiffOp::Equal(_) => (old + 1, new + 1),
+            DiffOp::Delete(_) => (old + 1, new),
+            DiffOp::Insert(_) => (old, new + 1),
+        }
+    }
+}
+
+/// Shortest edit script from `old` to `new` (Myers)
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<DiffOp<'a>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize;
+    let mut v = vec![0isize; 2 * max + 2];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    'search: for d in 0..=max as isize {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) { v[idx + 1] } else { v[idx - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                trace.push(v.clone());
+                break 'search;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len() as isize - 1).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + offset) as usize] < v[(k + 1 + offset) as usize]) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(DiffOp::Equal(old[x as usize]));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                ops.push(DiffOp::Insert(new[y as usize]));
+            } else {
+                x -= 1;
+                ops.push(DiffOp::Delete(old[x as usize]));
+            }
+        }
+    }
+    ops.reverse();
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(source: &str) -> Vec<String> {
+        source.lines().map(String::from).collect()
+    }
+
+    fn check(source: &str) -> Vec<RsplError> {
+        crate::anti_fail_logic::AntiFailLogicChecker::new("test.rss").check(source).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_fix_edits() {
+        let source = lines("fn f(a i32,\n     b i32) effects(io) i32 {\n    x i32 = 1\n    y == 2\n    if a {\n        x = 2\n    }\n    match ok {\n        true {\n        }\n    }\n    n = match ok { true { 1 } }\n}");
+        assert_eq!(declare_mut(&source, 3, "x").unwrap().edits, [TextEdit::insert(3, 4, "mut ")]);
+        assert!(declare_mut(&source, 4, "y").is_none());
+        assert_eq!(assign_outer(&source, 6, "x").unwrap().edits, [TextEdit::insert(6, 8, "outer ")]);
+        assert!(assign_outer(&source, 3, "x").is_none());
+        assert_eq!(declare_effect(&source, 1, "f", "panic").unwrap().edits, [TextEdit::insert(2, 22, ", panic")]);
+        assert_eq!(add_catch_all_arm(&source, 8).unwrap().edits, [TextEdit::insert(11, 0, "        _ {}\n")]);
+        assert_eq!(add_catch_all_arm(&source, 12).unwrap().edits, [TextEdit::insert(12, 30, "_ {} ")]);
+
+        let fixes = ["io", "panic"].map(|effect| declare_effect(&lines("fn g() {"), 1, "g", effect).unwrap());
+        let (fixed, applied) = apply_fixes("fn g() {\n}", &fixes);
+        assert_eq!(fixed, "fn g() effects(io) {\n}");
+        assert_eq!(applied.len(), 1);
+    }
+
+    #[test]
+    fn test_fix_source_reaches_fixed_point() {
+        let source = "fn inner() {\n    panic!(\"no\")\n}\n\nfn outer_fn() {\n    inner()\n}\n\nfn main() {\n    total = 0\n    total = 1\n    count = 0\n    if total > 0 {\n        count = 5\n    }\n    ok = true\n    match ok {\n        true {\n            println!(\"{}\", count)\n        }\n    }\n    outer_fn()\n}\n";
+        assert!(!check(source).is_empty());
+        let outcome = fix_source(source, check);
+        assert!(check(&outcome.source).is_empty(), "{:?}\n{}", check(&outcome.source), outcome.source);
+        for expected in ["fn inner() effects(panic) {", "fn outer_fn() effects(panic) {", "    mut total = 0", "        outer count = 5", "        _ {}"] {
+            assert!(outcome.source.lines().any(|line| line == expected), "missing {:?} in\n{}", expected, outcome.source);
+        }
+        assert_eq!(outcome.applied.len(), 5, "{:?}", outcome.applied);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let after = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        assert_eq!(
+            unified_diff("x.rss", before, after),
+            "--- a/x.rss\n+++ b/x.rss\n@@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n@@ -10,3 +10,4 @@\n j\n k\n l\n+m\n"
+        );
+        assert_eq!(unified_diff("x.rss", before, before), "");
+    }
+}
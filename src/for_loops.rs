@@ -0,0 +1,420 @@
+//! `for` Loops
+//!
+//! Iterating a collection by value moves it, so the Rust for a plain
+//! `for ev in events {` only compiles when `events` is not needed again.
+//! Like array access (L-04), the lowering picks one strategy per loop:
+//!
+//! ```text
+//! for ev in events {          →  for ev in events {           events not used again
+//! for ev in events {          →  for ev in &events {          events used later
+//! for ev in self.events {     →  for ev in self.events.iter() {
+//! for ev in events {          →  for ev in events.clone() {   body keeps `ev`
+//!     log.push(ev)
+//! for n in nums {             →  for mut n in nums.clone() {  body assigns `n`
+//!     n = n * 2
+//! ```
+//!
+//! Only a collection named by a path (`events`, `self.events`, `cfg.items`)
+//! is rewritten; ranges, calls and references are iterated as written. The
+//! loop bindings belong to the loop's scope, so assigning one in the body
+//! mutates it (`for mut n`) instead of declaring a new variable.
+
+use crate::helpers::strip_inline_comment;
+use crate::ident::{is_rust_keyword, is_valid_identifier};
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+use crate::variable::{mut_borrow_targets, mutating_method_targets, parse_rusts_assignment_ext};
+
+/// How a loop iterates its collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Iteration {
+    /// `for x in xs` - the collection is not needed after the loop
+    Move,
+    /// `for x in &xs`
+    Borrow,
+    /// `for x in self.xs.iter()` - a field, which cannot be moved out
+    Iter,
+    /// `for x in xs.clone()` - the body needs owned elements
+    Clone,
+}
+
+/// What the lowering decided for one `for` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForLoop {
+    /// Names bound by the pattern
+    pub bindings: Vec<String>,
+    /// Bindings the body reassigns or mutates in place
+    pub mutated: Vec<String>,
+    /// The collection path, as written
+    pub iterable: String,
+    pub iteration: Iteration,
+}
+
+impl ForLoop {
+    /// The `for` line with the chosen iteration and `mut` bindings
+    pub fn lower_header(&self, trimmed: &str) -> String {
+        let Some((pattern, iterable, rest)) = header_parts(trimmed) else {
+            return trimmed.to_string();
+        };
+        let iterable = match self.iteration {
+            Iteration::Move => iterable.to_string(),
+            Iteration::Borrow => format!("&{}", iterable),
+            Iteration::Iter => format!("{}.iter()", iterable),
+            Iteration::Clone => format!("{}.clone()", iterable),
+        };
+        let mut pattern = pattern.to_string();
+        for name in &self.mutated {
+            pattern = mark_binding_mut(&pattern, name);
+        }
+        format!("for {} in {}{}", pattern, iterable, rest)
+    }
+}
+
+/// `for (i, ev) in events {` → (`(i, ev)`, `events`)
+pub fn parse_for_header(trimmed: &str) -> Option<(&str, &str)> {
+    header_parts(trimmed).map(|(pattern, iterable, _)| (pattern, iterable))
+}
+
+/// Pattern, collection and what follows the collection (` {`)
+fn header_parts(trimmed: &str) -> Option<(&str, &str, &str)> {
+    let rest = trimmed.strip_prefix("for ")?;
+    let (in_pos, _) = delimiter_positions(rest, &[' '])
+        .into_iter()
+        .find(|&(pos, _)| rest[pos..].starts_with(" in "))?;
+    let after_in = in_pos + " in ".len();
+    let end = delimiter_positions(&rest[after_in..], &['{'])
+        .first()
+        .map_or(rest.len(), |&(pos, _)| after_in + pos);
+    let pattern = rest[..in_pos].trim();
+    let start = after_in + (end - after_in - rest[after_in..end].trim_start().len());
+    let iterable = rest[start..end].trim_end();
+    if pattern.is_empty() || iterable.is_empty() {
+        return None;
+    }
+    Some((pattern, iterable, &rest[start + iterable.len()..]))
+}
+
+/// Names bound by a loop pattern: `(i, (k, v))` → `i`, `k`, `v`
+pub fn loop_bindings(pattern: &str) -> Vec<String> {
+    pattern
+        .split(|c: char| !crate::ident::is_ident_continue(c))
+        .filter(|name| is_valid_identifier(name) && !is_rust_keyword(name))
+        .filter(|name| *name != "_" && !name.starts_with(char::is_uppercase))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Decide how the `for` loop on `line_num` of `lines` iterates.
+/// `is_reference` tells whether a variable already holds a reference;
+/// `has_copy_elements` whether a collection's elements are Copy (a borrowed
+/// element would be `&i32`, so such a collection is cloned instead).
+pub fn plan_for_loop(
+    lines: &[&str],
+    line_num: usize,
+    is_reference: impl Fn(&str) -> bool,
+    has_copy_elements: impl Fn(&str) -> bool,
+) -> Option<ForLoop> {
+    let header = strip_inline_comment(lines.get(line_num)?);
+    let (pattern, iterable) = parse_for_header(header.trim())?;
+    let bindings = loop_bindings(pattern);
+    let (body, after) = split_loop_body(lines, line_num)?;
+
+    let mutated: Vec<String> = bindings.iter()
+        .filter(|name| body.iter().any(|line| mutates(line, name)))
+        .cloned()
+        .collect();
+    let consumed = bindings.iter().any(|name| body.iter().any(|line| consumes(line, name)));
+
+    let Some(root) = collection_root(iterable) else {
+        return Some(ForLoop { bindings, mutated, iterable: iterable.to_string(), iteration: Iteration::Move });
+    };
+    let is_field = iterable.contains('.');
+    let reused = root == "self"
+        || in_enclosing_loop(lines, line_num)
+        || body.iter().chain(&after).any(|line| !word_positions(line, root).is_empty());
+
+    let iteration = if (root != "self" && is_reference(root)) || !reused {
+        Iteration::Move
+    } else if !mutated.is_empty() || consumed || has_copy_elements(iterable)
+        || body.iter().any(|line| mutates(line, root))
+    {
+        Iteration::Clone
+    } else if is_field {
+        Iteration::Iter
+    } else {
+        Iteration::Borrow
+    };
+    Some(ForLoop { bindings, mutated, iterable: iterable.to_string(), iteration })
+}
+
+/// `events`, `self.events`, `cfg.items` → the variable the path starts at
+fn collection_root(iterable: &str) -> Option<&str> {
+    let mut segments = iterable.split('.');
+    let root = segments.next()?;
+    let is_path = (root == "self" || (is_valid_identifier(root) && !is_rust_keyword(root)))
+        && segments.all(|segment| is_valid_identifier(segment) && !is_rust_keyword(segment));
+    is_path.then_some(root)
+}
+
+/// Code of the loop body, and of the rest of the enclosing function
+fn split_loop_body(lines: &[&str], line_num: usize) -> Option<(Vec<String>, Vec<String>)> {
+    let header = strip_inline_comment(lines[line_num]);
+    let open = delimiter_positions(&header, &['{']).first()?.0;
+    let enclosing = enclosing_headers(lines, line_num).len() as i64;
+
+    let mut body = Vec::new();
+    let mut after = Vec::new();
+    let mut depth = 0i64;
+    let mut body_closed = false;
+    for (i, line) in lines.iter().enumerate().skip(line_num) {
+        let code = strip_inline_comment(line);
+        let start = if i == line_num { open } else { 0 };
+        let mut segment_start = start;
+        for (pos, c) in delimiter_positions(&code, &['{', '}']) {
+            if pos < start {
+                continue;
+            }
+            depth += if c == '{' { 1 } else { -1 };
+            if !body_closed && depth == 0 {
+                body.push(code[segment_start..pos].to_string());
+                body_closed = true;
+                segment_start = pos + 1;
+            } else if body_closed && enclosing > 0 && depth <= -enclosing {
+                after.push(code[segment_start..pos].to_string());
+                return Some((body, after));
+            }
+        }
+        let rest = code[segment_start.min(code.len())..].to_string();
+        if body_closed {
+            after.push(rest);
+        } else {
+            body.push(rest);
+        }
+    }
+    body_closed.then_some((body, after))
+}
+
+/// Lines opening the blocks around `line_num`, innermost first, up to and
+/// including the body of the enclosing function
+fn enclosing_headers(lines: &[&str], line_num: usize) -> Vec<String> {
+    let mut headers = Vec::new();
+    let mut unmatched = 0usize;
+    for line in lines[..line_num].iter().rev() {
+        let code = strip_inline_comment(line);
+        for (_, c) in delimiter_positions(&code, &['{', '}']).into_iter().rev() {
+            if c == '}' {
+                unmatched += 1;
+            } else if unmatched > 0 {
+                unmatched -= 1;
+            } else {
+                let header = code.trim().to_string();
+                let is_fn = header.starts_with(')')
+                    || header.split('(').next().is_some_and(|head| head.split_whitespace().any(|word| word == "fn"));
+                headers.push(header);
+                if is_fn {
+                    return headers;
+                }
+            }
+        }
+    }
+    headers
+}
+
+/// Is the loop on `line_num` itself inside a loop of its function? Its
+/// collection is then iterated again on every outer iteration.
+fn in_enclosing_loop(lines: &[&str], line_num: usize) -> bool {
+    enclosing_headers(lines, line_num).iter().any(|header| {
+        let header = header.trim_start_matches('}').trim_start();
+        header.starts_with("for ") || header.starts_with("while ") || header.starts_with("loop ")
+    })
+}
+
+/// Does `line` reassign `name` or mutate it in place?
+fn mutates(line: &str, name: &str) -> bool {
+    let targets = mutating_method_targets(line);
+    if targets.iter().chain(&mut_borrow_targets(line)).any(|target| target == name) {
+        return true;
+    }
+    matches!(parse_rusts_assignment_ext(line), Some((target, _, _, false, _)) if target == name)
+}
+
+/// Does `line` move `name` (or one of its fields) into something else: a
+/// call argument, a collection, a struct field, a variable?
+fn consumes(line: &str, name: &str) -> bool {
+    let trimmed = line.trim();
+    if let Some((_, _, value, _, _)) = parse_rusts_assignment_ext(trimmed) {
+        if is_binding_path(value.trim(), name) {
+            return true;
+        }
+    }
+    if trimmed.strip_prefix("return ").is_some_and(|value| is_binding_path(value.trim().trim_end_matches(';'), name)) {
+        return true;
+    }
+    word_positions(trimmed, name).into_iter().any(|pos| {
+        let end = path_end(trimmed, pos);
+        let before = trimmed[..pos].trim_end();
+        let after = trimmed[end..].trim_start();
+        let is_element = before.ends_with(['(', ',', '[', ':']) && !before.ends_with("::")
+            && after.starts_with([')', ',', ']', '}']);
+        is_element && !inside_macro_call(&trimmed[..pos])
+    })
+}
+
+/// `ev`, `ev.name`
+fn is_binding_path(value: &str, name: &str) -> bool {
+    value.strip_prefix(name).is_some_and(|rest| {
+        rest.is_empty() || (rest.starts_with('.') && rest[1..].split('.').all(is_valid_identifier))
+    })
+}
+
+/// End of the field path starting at `pos`: `ev.meta.name` but not a
+/// method call (`ev.name.clone()` is no path)
+fn path_end(code: &str, pos: usize) -> usize {
+    let is_ident = |c: char| crate::ident::is_ident_continue(c);
+    let mut end = pos + code[pos..].find(|c: char| !is_ident(c)).unwrap_or(code.len() - pos);
+    while code[end..].starts_with('.') {
+        let field_len = code[end + 1..].find(|c: char| !is_ident(c)).unwrap_or(code.len() - end - 1);
+        if field_len == 0 {
+            break;
+        }
+        end += 1 + field_len;
+    }
+    end
+}
+
+/// Is the innermost unclosed `(` before the end of `before` a macro's
+/// (`println!(`)? Macros take their arguments by reference.
+fn inside_macro_call(before: &str) -> bool {
+    let mut unclosed = Vec::new();
+    for (pos, c) in delimiter_positions(before, &['(', ')', '[', ']']) {
+        match c {
+            '(' | '[' => unclosed.push((pos, c)),
+            _ => {
+                unclosed.pop();
+            }
+        }
+    }
+    unclosed.last().is_some_and(|&(pos, c)| c == '(' && before[..pos].ends_with('!'))
+}
+
+/// Positions of `word` as a whole identifier, outside strings
+fn word_positions(code: &str, word: &str) -> Vec<usize> {
+    let Some(first) = word.chars().next() else {
+        return Vec::new();
+    };
+    let is_ident = |c: char| crate::ident::is_ident_continue(c);
+    delimiter_positions(code, &[first])
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .filter(|&pos| {
+            code[pos..].starts_with(word)
+                && !code[..pos].ends_with(is_ident)
+                && !code[..pos].ends_with('.')
+                && !code[pos + word.len()..].starts_with(is_ident)
+        })
+        .collect()
+}
+
+/// `(i, n)` → `(i, mut n)`
+fn mark_binding_mut(pattern: &str, name: &str) -> String {
+    let Some(&pos) = word_positions(pattern, name).first() else {
+        return pattern.to_string();
+    };
+    if pattern[..pos].trim_end().ends_with("mut") {
+        return pattern.to_string();
+    }
+    format!("{}mut {}", &pattern[..pos], &pattern[pos..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan(source: &str, line: usize) -> ForLoop {
+        let lines: Vec<&str> = source.lines().collect();
+        plan_for_loop(&lines, line, |_| false, |_| false).unwrap()
+    }
+
+    #[test]
+    fn test_parse_for_header() {
+        assert_eq!(parse_for_header("for ev in events {"), Some(("ev", "events")));
+        assert_eq!(parse_for_header("for (i, x) in xs.iter().enumerate() {"), Some(("(i, x)", "xs.iter().enumerate()")));
+        assert_eq!(parse_for_header("for i in 0..3 { total += i }"), Some(("i", "0..3")));
+        assert_eq!(parse_for_header("format!(\"for x in y\")"), None);
+        assert_eq!(loop_bindings("(i, (k, mut v))"), vec!["i", "k", "v"]);
+        assert_eq!(loop_bindings("Point { x, y: _ }"), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_iteration_choice() {
+        let source = "fn main() {
+    events = load()
+    for ev in events {
+        println!(\"{}\", ev.name)
+    }
+    for ev in events {
+        log.push(ev)
+    }
+    for ev in events {
+        ev.seen = true
+    }
+    for ev in events {
+        println!(\"{}\", ev.name)
+    }
+    for i in 0..3 {
+    }
+}";
+        assert_eq!(plan(source, 2).iteration, Iteration::Borrow);
+        assert_eq!(plan(source, 5).iteration, Iteration::Clone);
+        let mutated = plan(source, 8);
+        assert_eq!((mutated.iteration, mutated.mutated), (Iteration::Clone, vec!["ev".to_string()]));
+        assert_eq!(plan(source, 11).iteration, Iteration::Move);
+        assert_eq!(plan(source, 14).iteration, Iteration::Move);
+    }
+
+    #[test]
+    fn test_fields_nested_loops_and_references() {
+        let source = "fn total(self, xs &Vec[i32]) i32 {
+    for item in self.items {
+        count += item.len()
+    }
+    while busy {
+        for job in jobs {
+            run(&job)
+        }
+    }
+    for x in xs {
+    }
+}";
+        let lines: Vec<&str> = source.lines().collect();
+        assert_eq!(plan(source, 1).iteration, Iteration::Iter);
+        assert_eq!(plan(source, 5).iteration, Iteration::Borrow);
+        let param = plan_for_loop(&lines, 9, |name| name == "xs", |_| false).unwrap();
+        assert_eq!(param.iteration, Iteration::Move);
+    }
+
+    #[test]
+    fn test_lower_header() {
+        let header = "for (i, n) in nums {  ";
+        let plan = ForLoop {
+            bindings: vec!["i".to_string(), "n".to_string()],
+            mutated: vec!["n".to_string()],
+            iterable: "nums".to_string(),
+            iteration: Iteration::Clone,
+        };
+        assert_eq!(plan.lower_header(header.trim()), "for (i, mut n) in nums.clone() {");
+        let plan = ForLoop { mutated: Vec::new(), iteration: Iteration::Borrow, ..plan };
+        assert_eq!(plan.lower_header("for (i, n) in nums { f(n) }"), "for (i, n) in &nums { f(n) }");
+    }
+
+    #[test]
+    fn test_consumes() {
+        assert!(consumes("names.push(ev.name)", "ev"));
+        assert!(consumes("kept = ev", "ev"));
+        assert!(consumes("send(tx, ev)", "ev"));
+        assert!(consumes("all = vec![ev]", "ev"));
+        assert!(!consumes("println!(\"{} {}\", i, ev)", "ev"));
+        assert!(!consumes("show(&ev)", "ev"));
+        assert!(!consumes("n = ev.name.len()", "ev"));
+        assert!(!consumes("total += ev.count", "ev"));
+    }
+}
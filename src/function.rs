@@ -12,7 +12,13 @@
 //! - Call coercion: `foo("lit")` where param is &String → `foo(&String::from("lit"))`
 //! - Tail return: last expr in non-() function has no semicolon
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use crate::clone_helpers::{extract_array_var_from_access, transform_array_access_clone};
+use crate::contracts::{split_contract_clauses, Contract};
+use crate::error_msg::SourceLocation;
+use crate::helpers::{transform_bracket_generic_calls, RUST_MACROS};
+use crate::lowering::depth_tracking_lowering::{find_matching_close, find_outside_strings, split_top_level, split_top_level_types};
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// A parsed function parameter
 #[derive(Debug, Clone)]
@@ -23,6 +29,9 @@ pub struct Parameter {
     pub is_mut_borrow: bool,
     /// Parameter has explicit `mut` modifier (e.g., `mut get_balance F1`)
     pub is_mut_param: bool,
+    /// Default value expression (`port i32 = 8080`), filled in at call sites
+    /// that omit the argument
+    pub default_value: Option<String>,
 }
 
 /// A parsed function signature
@@ -37,6 +46,10 @@ pub struct FunctionSignature {
     pub single_line_expr: Option<String>,
     /// Parameters that have `write` effect - these need `mut` in Rust output
     pub write_params: Vec<String>,
+    /// Attributes from modifiers: `inline fn` → `["#[inline]"]`
+    pub attributes: Vec<String>,
+    /// `requires(..)` / `ensures(..)` clauses (see [`crate::contracts`])
+    pub contract: Contract,
 }
 
 /// Result of parsing a function line
@@ -154,7 +167,7 @@ fn find_matching_bracket(s: &str) -> Option<usize> {
     }
     
     let mut depth = 0;
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         match c {
             '[' => depth += 1,
             ']' => {
@@ -206,8 +219,9 @@ fn transform_param_type(type_str: &str) -> String {
         return trimmed.to_string();
     }
     
-    // If it's a bare slice type [T], convert to &[T]
-    if trimmed.starts_with('[') && trimmed.ends_with(']') {
+    // If it's a bare slice type [T], convert to &[T]; an array `[T; N]`
+    // (`N` a literal or a constant) is passed by value
+    if trimmed.starts_with('[') && trimmed.ends_with(']') && !is_array_type(trimmed) && enabled(LoweringRule::L06) {
         return format!("&{}", trimmed);
     }
     
@@ -215,6 +229,20 @@ fn transform_param_type(type_str: &str) -> String {
     trimmed.to_string()
 }
 
+/// `[T; N]`: a `;` directly inside the outer brackets
+fn is_array_type(type_str: &str) -> bool {
+    let mut depth = 0usize;
+    for c in type_str.chars() {
+        match c {
+            '[' | '(' | '<' => depth += 1,
+            ']' | ')' | '>' => depth = depth.saturating_sub(1),
+            ';' if depth == 1 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
 // ============================================================================
 // FUNCTION REGISTRY - Tracks function signatures for call-site coercion
 // ============================================================================
@@ -242,6 +270,45 @@ impl FunctionRegistry {
     pub fn get(&self, name: &str) -> Option<&FunctionSignature> {
         self.functions.get(name)
     }
+    
+    /// Every registered signature, in no particular order
+    pub fn signatures(&self) -> impl Iterator<Item = &FunctionSignature> {
+        self.functions.values()
+    }
+    
+    /// Add every signature of `other` (already transformed by `register`)
+    pub fn merge(&mut self, other: FunctionRegistry) {
+        self.functions.extend(other.functions);
+    }
+    
+    /// Lowered default arguments for a call to `name` that supplies only
+    /// `supplied` arguments. Empty unless every omitted parameter has a default.
+    pub fn default_args(&self, name: &str, supplied: usize) -> Vec<String> {
+        let Some(sig) = self.functions.get(name) else { return Vec::new() };
+        let missing = sig.parameters.iter().skip(supplied);
+        if missing.clone().any(|p| p.default_value.is_none()) {
+            return Vec::new();
+        }
+        missing
+            .filter_map(|p| p.default_value.as_ref().map(|d| coerce_argument(d, &p.param_type)))
+            .collect()
+    }
+    
+    /// By-value parameters whose arguments get an automatic `.clone()` at
+    /// call sites (structs and `Option`s of them), with their function name
+    pub fn auto_cloned_params(&self) -> impl Iterator<Item = (&str, &Parameter)> {
+        self.functions.values()
+            .flat_map(|sig| sig.parameters.iter().map(move |p| (sig.name.as_str(), p)))
+            .filter(|(_, p)| should_auto_clone_for_param(&p.param_type))
+    }
+    
+    /// Register a binding that holds a function (`handler = match k { .. { parse } }`)
+    /// so calls through it are lowered like calls to the function itself
+    pub fn register_alias(&mut self, alias: &str, sig: &FunctionSignature) {
+        let mut aliased = sig.clone();
+        aliased.name = alias.to_string();
+        self.functions.insert(aliased.name.clone(), aliased);
+    }
 }
 
 // ============================================================================
@@ -254,6 +321,9 @@ pub struct CurrentFunctionContext {
     pub params: HashMap<String, String>,
     pub return_type: Option<String>,
     pub start_depth: usize,
+    /// Arrays whose elements are Copy (indexing them needs no `.clone()`)
+    pub copy_element_arrays: HashSet<String>,
+    pub contract: Contract,
 }
 
 impl CurrentFunctionContext {
@@ -263,6 +333,8 @@ impl CurrentFunctionContext {
             params: HashMap::new(),
             return_type: None,
             start_depth: 0,
+            copy_element_arrays: HashSet::new(),
+            contract: Contract::default(),
         }
     }
     
@@ -277,6 +349,7 @@ impl CurrentFunctionContext {
         }
         self.return_type = sig.return_type.clone();
         self.start_depth = depth;
+        self.contract = sig.contract.clone();
     }
     
     pub fn exit(&mut self) {
@@ -284,6 +357,8 @@ impl CurrentFunctionContext {
         self.params.clear();
         self.return_type = None;
         self.start_depth = 0;
+        self.copy_element_arrays.clear();
+        self.contract = Contract::default();
     }
     
     pub fn is_inside(&self) -> bool {
@@ -300,6 +375,17 @@ impl CurrentFunctionContext {
             .unwrap_or(false)
     }
     
+    /// Add `.clone()` to an array access unless the array holds Copy elements
+    pub fn clone_array_access(&self, value: &str) -> String {
+        // `text.parse[i32]()` is a generic call, not an index to clone
+        let value = &transform_bracket_generic_calls(value);
+        match extract_array_var_from_access(value) {
+            _ if !enabled(LoweringRule::L11) => value.to_string(),
+            Some(base) if self.copy_element_arrays.contains(&base) => value.to_string(),
+            _ => transform_array_access_clone(value),
+        }
+    }
+    
     /// Check if a parameter is a slice type (&[T])
     /// This is used to know when to add .to_vec() for struct field assignments
     pub fn is_slice_param(&self, name: &str) -> bool {
@@ -400,11 +486,20 @@ pub fn transform_call_args(line: &str, registry: &FunctionRegistry) -> String {
         return line.to_string();
     }
     
+    // Macro arguments are opaque: the list itself is never coerced like a
+    // call's (format strings stay literals), only calls nested in an
+    // argument are lowered
+    if let Some(open) = find_macro_call(line, registry) {
+        if let Some(close) = find_matching_paren(line, open) {
+            return transform_macro_args(line, open, close, registry);
+        }
+    }
+    
     let mut result = line.to_string();
     
-    if let Some((func_name, paren_pos)) = find_function_call(line) {
+    if let Some((func_name, paren_pos)) = find_function_call(line, registry) {
         if let Some(sig) = registry.get(&func_name) {
-            if let Some(close_paren) = find_matching_paren_from(line, paren_pos) {
+            if let Some(close_paren) = find_matching_paren(line, paren_pos) {
                 let before = &line[..paren_pos - func_name.len()];
                 let args_str = &line[paren_pos + 1..close_paren];
                 let after = &line[close_paren + 1..];
@@ -421,6 +516,9 @@ pub fn transform_call_args(line: &str, registry: &FunctionRegistry) -> String {
                     }
                 }
                 
+                // Omitted trailing arguments take the declared defaults
+                new_args.extend(registry.default_args(&func_name, args.len()));
+                
                 result = format!("{}{}({}){}", before, func_name, new_args.join(", "), after);
             }
         }
@@ -429,7 +527,14 @@ pub fn transform_call_args(line: &str, registry: &FunctionRegistry) -> String {
     result
 }
 
-fn find_function_call(expr: &str) -> Option<(String, usize)> {
+/// A std macro called without `!`, unless a user function has that name
+fn is_macro_name(name: &str, registry: &FunctionRegistry) -> bool {
+    RUST_MACROS.contains(&name) && registry.get(name).is_none()
+}
+
+/// Byte offset of the `(` of a macro invocation (`assert_eq(`, `dbg!(`)
+/// that comes before any function call in `expr`
+fn find_macro_call(expr: &str, registry: &FunctionRegistry) -> Option<usize> {
     let chars: Vec<char> = expr.chars().collect();
     let mut i = 0;
     
@@ -440,11 +545,15 @@ fn find_function_call(expr: &str) -> Option<(String, usize)> {
                 i += 1;
             }
             let name: String = chars[start..i].iter().collect();
+            let is_path_or_method = start > 0 && matches!(chars[start - 1], '.' | ':');
+            let byte_pos = |end: usize| chars[..end].iter().map(|c| c.len_utf8()).sum::<usize>();
             
-            if i < chars.len() && chars[i] == '(' {
-                if !matches!(name.as_str(), "if" | "while" | "for" | "match" | "let" | "return" | "println" | "print" | "eprintln" | "format" | "vec" | "panic" | "assert") {
-                    return Some((name, i));
-                }
+            match chars.get(i) {
+                Some('!') if chars.get(i + 1) == Some(&'(') => return Some(byte_pos(i + 1)),
+                Some('(') if matches!(name.as_str(), "if" | "while" | "for" | "match" | "let" | "return") => {}
+                Some('(') if is_macro_name(&name, registry) && !is_path_or_method => return Some(byte_pos(i)),
+                Some('(') => return None,
+                _ => {}
             }
         } else {
             i += 1;
@@ -453,66 +562,50 @@ fn find_function_call(expr: &str) -> Option<(String, usize)> {
     None
 }
 
-fn find_matching_paren_from(s: &str, start: usize) -> Option<usize> {
-    let mut depth = 0;
-    let mut in_string = false;
-    let mut prev = ' ';
-    
-    for (i, c) in s[start..].char_indices() {
-        if c == '"' && prev != '\\' {
-            in_string = !in_string;
-        }
-        if !in_string {
-            match c {
-                '(' => depth += 1,
-                ')' => {
-                    depth -= 1;
-                    if depth == 0 {
-                        return Some(start + i);
-                    }
-                }
-                _ => {}
-            }
-        }
-        prev = c;
+/// Lower the calls nested in each argument of the macro whose argument list
+/// spans `open..=close`. String literal arguments are left alone.
+fn transform_macro_args(line: &str, open: usize, close: usize, registry: &FunctionRegistry) -> String {
+    let args = split_top_level(&line[open + 1..close], ',');
+    let lowered: Vec<String> = args.iter()
+        .map(|arg| {
+            let arg = arg.trim();
+            if arg.starts_with('"') { arg.to_string() } else { transform_call_args(arg, registry) }
+        })
+        .collect();
+    if lowered.iter().zip(&args).all(|(l, a)| l == a.trim()) {
+        return line.to_string();
     }
-    None
+    format!("{}{}){}", &line[..=open], lowered.join(", "), &line[close + 1..])
 }
 
-fn split_call_args(s: &str) -> Vec<String> {
-    let mut result = Vec::new();
-    let mut current = String::new();
-    let mut depth = 0;
-    let mut in_string = false;
-    let mut prev = ' ';
+fn find_function_call(expr: &str, registry: &FunctionRegistry) -> Option<(String, usize)> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut i = 0;
     
-    for c in s.chars() {
-        if c == '"' && prev != '\\' {
-            in_string = !in_string;
-        }
-        
-        if !in_string {
-            match c {
-                '(' | '[' | '{' | '<' => { depth += 1; current.push(c); }
-                ')' | ']' | '}' | '>' => { depth -= 1; current.push(c); }
-                ',' if depth == 0 => {
-                    result.push(current.trim().to_string());
-                    current = String::new();
-                    prev = c;
-                    continue;
+    while i < chars.len() {
+        if chars[i].is_alphabetic() || chars[i] == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            
+            if i < chars.len() && chars[i] == '(' {
+                if !matches!(name.as_str(), "if" | "while" | "for" | "match" | "let" | "return") && !is_macro_name(&name, registry) {
+                    // Byte offset of `(` - callers slice with it (non-ASCII safe)
+                    let byte_pos: usize = chars[..i].iter().map(|c| c.len_utf8()).sum();
+                    return Some((name, byte_pos));
                 }
-                _ => current.push(c),
             }
         } else {
-            current.push(c);
+            i += 1;
         }
-        prev = c;
-    }
-    
-    if !current.trim().is_empty() {
-        result.push(current.trim().to_string());
     }
-    result
+    None
+}
+
+fn split_call_args(s: &str) -> Vec<String> {
+    split_top_level(s, ',').into_iter().map(|a| a.trim().to_string()).collect()
 }
 
 fn coerce_argument(arg: &str, param_type: &str) -> String {
@@ -525,7 +618,7 @@ fn coerce_argument(arg: &str, param_type: &str) -> String {
     
     // L-10: Handle &[T] parameters - add & to array arguments
     // When param is &[T] and arg is a plain identifier (array variable), add &
-    if param_type.starts_with("&[") {
+    if param_type.starts_with("&[") && enabled(LoweringRule::L10) {
         // Check if arg is an array literal like [x, y, z]
         if arg.starts_with('[') && arg.ends_with(']') && !arg.starts_with("&[") {
             // Array literal needs & to convert to slice reference
@@ -544,7 +637,7 @@ fn coerce_argument(arg: &str, param_type: &str) -> String {
     
     // L-11: Handle slice indexing - add .clone() when needed
     // When arg is slice[index] and param expects owned value (not &T), add .clone()
-    if is_slice_index_access(arg) && !param_type.starts_with('&') {
+    if is_slice_index_access(arg) && !param_type.starts_with('&') && enabled(LoweringRule::L11) {
         // Add .clone() for slice index access passed by value
         return format!("{}.clone()", arg);
     }
@@ -809,10 +902,12 @@ pub fn should_be_tail_return(
     ctx: &CurrentFunctionContext,
     is_before_closing_brace: bool,
 ) -> bool {
-    if !ctx.has_return_value() || !is_before_closing_brace {
-        return false;
-    }
-    
+    ctx.has_return_value() && is_before_closing_brace && is_tail_value_expr(line)
+}
+
+/// Whether the last line of a block is its value rather than a statement:
+/// not a declaration, loop, print, collection mutation or assignment
+pub fn is_tail_value_expr(line: &str) -> bool {
     let trimmed = line.trim();
     
     if trimmed.ends_with(';') {
@@ -922,7 +1017,83 @@ fn has_standalone_assignment_eq(s: &str) -> bool {
 // PARSING FUNCTIONS
 // ============================================================================
 
+/// RustS+ function modifiers and the attributes they lower to
+pub const FN_MODIFIERS: &[(&str, &str)] = &[
+    ("inline", "#[inline]"),
+    ("must_use", "#[must_use]"),
+    ("cold", "#[cold]"),
+    ("memo", crate::memo::MEMO_ATTRIBUTE),
+];
+
+/// Split modifiers off a signature line:
+/// `pub inline must_use fn f() i64 {` → (`["#[inline]", "#[must_use]"]`, `pub fn f() i64 {`).
+/// None when the line starts no function or has no modifier.
+pub fn split_fn_modifiers(line: &str) -> Option<(Vec<String>, String)> {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let mut rest = line.trim_start();
+    let mut is_pub = false;
+    let mut attributes: Vec<String> = Vec::new();
+    loop {
+        let (word, after) = rest.split_once(' ')?;
+        match word {
+            "fn" => break,
+            "pub" if !is_pub => is_pub = true,
+            _ => {
+                let (_, attribute) = FN_MODIFIERS.iter().find(|(modifier, _)| *modifier == word)?;
+                if !attributes.iter().any(|a| a == attribute) {
+                    attributes.push(attribute.to_string());
+                }
+            }
+        }
+        rest = after.trim_start();
+    }
+    if attributes.is_empty() {
+        return None;
+    }
+    Some((attributes, format!("{}{}{}", indent, if is_pub { "pub " } else { "" }, rest)))
+}
+
+/// Strip function modifiers from every line of `source`, so the signature
+/// reads `fn ..` / `pub fn ..` to every later pass. Returns the attributes
+/// by 0-based line.
+pub fn strip_fn_modifiers(source: &str) -> (String, HashMap<usize, Vec<String>>) {
+    let mut attributes = HashMap::new();
+    let lines: Vec<String> = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match split_fn_modifiers(line) {
+            Some((attrs, stripped)) => {
+                attributes.insert(i, attrs);
+                stripped
+            }
+            None => line.to_string(),
+        })
+        .collect();
+    (lines.join("\n"), attributes)
+}
+
+/// Put modifier attributes in front of a lowered signature (on its line,
+/// so output lines keep matching source lines)
+pub fn prefix_fn_attributes(signature: &str, attributes: &[String]) -> String {
+    if attributes.is_empty() {
+        return signature.to_string();
+    }
+    let code = signature.trim_start();
+    let indent = &signature[..signature.len() - code.len()];
+    format!("{}{} {}", indent, attributes.join(" "), code)
+}
+
 pub fn parse_function_line(line: &str) -> FunctionParseResult {
+    if let Some((attributes, stripped)) = split_fn_modifiers(line) {
+        return match parse_function_line(&stripped) {
+            FunctionParseResult::RustSPlusSignature(mut sig) => {
+                sig.attributes = attributes;
+                FunctionParseResult::RustSPlusSignature(sig)
+            }
+            other => other,
+        };
+    }
+    
     let trimmed = line.trim();
     
     let (is_pub, fn_part) = if trimmed.starts_with("pub fn ") {
@@ -943,6 +1114,132 @@ pub fn parse_function_line(line: &str) -> FunctionParseResult {
     }
 }
 
+/// Parse a Rust-syntax signature (`fn f(a: i32) -> String {`) into a
+/// [`FunctionSignature`] so calls to it can be coerced like RustS+ calls
+pub fn parse_rust_signature(line: &str) -> Option<FunctionSignature> {
+    let trimmed = line.trim();
+    let (is_pub, rest) = match trimmed.strip_prefix("pub fn ") {
+        Some(rest) => (true, rest),
+        None => (false, trimmed.strip_prefix("fn ")?),
+    };
+    
+    let name_end = rest.find(['(', '<'])?;
+    let name = rest[..name_end].trim().to_string();
+    let mut rest = &rest[name_end..];
+    
+    let generics = if rest.starts_with('<') {
+        let mut depth = 0;
+        let close = rest.char_indices().find(|&(_, c)| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?.0;
+        let gen = rest[1..close].trim().to_string();
+        rest = &rest[close + 1..];
+        Some(gen)
+    } else {
+        None
+    };
+    
+    let paren_end = find_matching_paren(rest, 0)?;
+    let mut parameters = Vec::new();
+    for part in split_by_comma(&rest[1..paren_end]) {
+        let param = match part.split_once(':') {
+            Some((name, ty)) if name.trim() != "self" => format!("{} {}", name.trim(), ty.trim()),
+            _ => part.clone(),
+        };
+        parameters.push(parse_single_param(&param).ok()?);
+    }
+    
+    let after = rest[paren_end + 1..].trim();
+    let return_type = after.strip_prefix("->").map(|ret| {
+        let end = ret.find(" where ").or_else(|| ret.find('{')).unwrap_or(ret.len());
+        ret[..end].trim().to_string()
+    }).filter(|ret| !ret.is_empty() && ret != "()");
+    
+    Some(FunctionSignature {
+        name,
+        generics,
+        parameters,
+        return_type,
+        is_pub,
+        is_single_line: false,
+        single_line_expr: None,
+        write_params: Vec::new(),
+        attributes: Vec::new(),
+        contract: Contract::default(),
+    })
+}
+
+/// Byte span `(start, len)` in `line` of the part of a malformed signature
+/// that `error` (from [`parse_function_line`]) refers to.
+///
+/// - `Parameter 'b' has no type annotation` → the parameter `b`
+/// - `unmatched '('` / `missing ']'` → the unclosed delimiter
+/// - anything else → the function name
+pub fn signature_error_span(line: &str, error: &str) -> (usize, usize) {
+    let fn_kw = line.find("fn ").map(|p| p + 3).unwrap_or(0);
+    let name_start = fn_kw + (line[fn_kw..].len() - line[fn_kw..].trim_start().len());
+    let name_len = line[name_start..]
+        .find(|c: char| c == '(' || c == '[' || c.is_whitespace())
+        .unwrap_or(line.len() - name_start);
+    let fallback = (name_start, name_len.max(1));
+    let open_paren = line[name_start..].find('(').map(|p| name_start + p);
+    
+    if error.contains("unmatched '('") {
+        return open_paren.map(|p| (p, 1)).unwrap_or(fallback);
+    }
+    if error.contains("missing ']'") {
+        return line[name_start..].find('[').map(|p| (name_start + p, 1)).unwrap_or(fallback);
+    }
+    
+    // `Parameter 'name' ...` - locate that parameter inside the parens
+    let param_name = error
+        .strip_prefix("Parameter '")
+        .and_then(|rest| rest.split('\'').next());
+    if let (Some(name), Some(open)) = (param_name, open_paren) {
+        let close = find_matching_paren(line, open).unwrap_or(line.len());
+        let mut offset = open + 1;
+        for param in line[open + 1..close].split(',') {
+            let trimmed = param.trim();
+            let lead = param.len() - param.trim_start().len();
+            let bare = trimmed.strip_prefix("mut ").unwrap_or(trimmed);
+            if bare.split_whitespace().next() == Some(name) || (name.is_empty() && trimmed.is_empty()) {
+                return (offset + lead, trimmed.len().max(1));
+            }
+            offset += param.len() + 1;
+        }
+    }
+    fallback
+}
+
+/// Source location of a signature error, mapped back from the joined
+/// signature to the source line it came from.
+///
+/// `pieces` holds `(line index, offset in joined)` for each source line
+/// that makes up `joined` (a single entry for one-line signatures).
+pub fn signature_error_location(
+    lines: &[&str],
+    pieces: &[(usize, usize)],
+    joined: &str,
+    error: &str,
+) -> SourceLocation {
+    let (start, len) = signature_error_span(joined, error);
+    let (line_idx, piece_start) = pieces.iter()
+        .rev()
+        .find(|(_, offset)| *offset <= start)
+        .copied()
+        .unwrap_or((pieces.first().map(|p| p.0).unwrap_or(0), 0));
+    let source_line = lines.get(line_idx).copied().unwrap_or("");
+    let indent = source_line.len() - source_line.trim_start().len();
+    let highlight_start = (indent + start - piece_start).min(source_line.len());
+    SourceLocation::new("", line_idx + 1, highlight_start + 1)
+        .with_source(source_line, highlight_start, len)
+}
+
 fn is_rust_syntax(line: &str) -> bool {
     // L-05 CRITICAL FIX: If the line contains "effects(" outside of strings/comments,
     // it's NOT pure Rust syntax - it needs transformation to strip effects!
@@ -992,30 +1289,11 @@ fn is_rust_syntax(line: &str) -> bool {
     false
 }
 
-fn find_matching_paren(s: &str, start: usize) -> Option<usize> {
-    let mut depth = 0;
-    let mut in_string = false;
-    let mut prev = ' ';
-    
-    for (i, c) in s[start..].char_indices() {
-        if c == '"' && prev != '\\' {
-            in_string = !in_string;
-        }
-        if !in_string {
-            match c {
-                '(' => depth += 1,
-                ')' => {
-                    depth -= 1;
-                    if depth == 0 {
-                        return Some(start + i);
-                    }
-                }
-                _ => {}
-            }
-        }
-        prev = c;
-    }
-    None
+/// Position of the `)` closing the first `(` at or after `start`, outside
+/// string and char literals
+pub(crate) fn find_matching_paren(s: &str, start: usize) -> Option<usize> {
+    let open = start + find_outside_strings(&s[start..], '(')?;
+    find_matching_close(s, open)
 }
 
 /// Strip effects clause from return type
@@ -1088,6 +1366,7 @@ pub fn strip_effects_clause(type_str: &str) -> String {
 /// - `effects(io, alloc)` → []
 /// - `effects(write(acc))` → ["acc"]  (parenthesized form)
 /// - `effects(write self)` → ["self"]
+/// - `effects(write acc.balance)` → ["acc"]
 pub fn extract_write_params(type_str: &str) -> Vec<String> {
     let trimmed = type_str.trim();
     
@@ -1144,6 +1423,13 @@ pub fn extract_write_params(type_str: &str) -> Vec<String> {
         }
     }
     
+    // A field write (`write acc.balance`) still needs the whole `acc` mutable
+    for param in &mut write_params {
+        if let Some(dot) = param.find('.') {
+            param.truncate(dot);
+        }
+    }
+    write_params.dedup();
     write_params
 }
 
@@ -1220,7 +1506,8 @@ fn parse_rustsplus_function(after_fn: &str, is_pub: bool) -> Result<FunctionSign
     
     let paren_end = find_matching_paren(rest, 0).ok_or("Invalid function: unmatched '('")?;
     let params_str = &rest[1..paren_end];
-    rest = rest[paren_end + 1..].trim();
+    let (contract, after_params) = split_contract_clauses(&rest[paren_end + 1..]);
+    rest = after_params.trim();
     
     let parameters = parse_parameters(params_str)?;
     
@@ -1252,10 +1539,10 @@ fn parse_rustsplus_function(after_fn: &str, is_pub: bool) -> Result<FunctionSign
         // The effects(...) clause must NOT appear in Rust output!
         let ret_type = strip_effects_clause(&raw_ret_type);
         
-        // If after stripping effects the return type is empty or "()", it's a unit function
+        // If after stripping effects the return type is empty, "()" or `unit`, it's a unit function
         // e.g., `fn log(msg String) effects(io) {` has no return type after stripping
         // In Rust, `fn foo()` is same as `fn foo() -> ()`, so we omit explicit "()"
-        let final_return_type = if ret_type.is_empty() || ret_type == "()" {
+        let final_return_type = if ret_type.is_empty() || ret_type == "()" || ret_type == "unit" {
             None
         } else {
             Some(ret_type)
@@ -1273,7 +1560,7 @@ fn parse_rustsplus_function(after_fn: &str, is_pub: bool) -> Result<FunctionSign
     
     Ok(FunctionSignature {
         name, generics, parameters, return_type, is_pub, is_single_line, single_line_expr,
-        write_params,
+        write_params, attributes: Vec::new(), contract,
     })
 }
 
@@ -1291,7 +1578,7 @@ fn find_type_end(s: &str) -> usize {
     let mut in_string = false;
     let mut prev_char = ' ';
     
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         // Handle string literals
         if c == '"' && prev_char != '\\' {
             in_string = !in_string;
@@ -1316,7 +1603,7 @@ fn find_type_end(s: &str) -> usize {
                 // `=` inside brackets is associated type syntax, NOT single-line fn marker
                 if depth == 0 {
                     // Also check it's not `==`, `!=`, `<=`, `>=`, `=>`
-                    let next_char = s.chars().nth(i + 1).unwrap_or(' ');
+                    let next_char = s[i + 1..].chars().next().unwrap_or(' ');
                     if prev_char != '!' && prev_char != '<' && prev_char != '>' 
                        && prev_char != '=' && next_char != '=' && next_char != '>' {
                         return i;
@@ -1343,33 +1630,23 @@ fn parse_parameters(params_str: &str) -> Result<Vec<Parameter>, String> {
     for part in parts {
         let part = part.trim();
         if part.is_empty() { continue; }
-        parameters.push(parse_single_param(part)?);
+        let param = parse_single_param(part)?;
+        let follows_default = parameters.last().is_some_and(|p: &Parameter| p.default_value.is_some());
+        if follows_default && param.default_value.is_none() {
+            return Err(format!(
+                "Parameter '{}' has no default value but follows a parameter with one. Defaulted parameters must come last.",
+                param.name
+            ));
+        }
+        parameters.push(param);
     }
     
     Ok(parameters)
 }
 
-fn split_by_comma(s: &str) -> Vec<String> {
-    let mut result = Vec::new();
-    let mut current = String::new();
-    let mut depth = 0;
-    
-    for c in s.chars() {
-        match c {
-            '<' | '[' | '(' => { depth += 1; current.push(c); }
-            '>' | ']' | ')' => { depth -= 1; current.push(c); }
-            ',' if depth == 0 => {
-                result.push(current.trim().to_string());
-                current = String::new();
-            }
-            _ => current.push(c),
-        }
-    }
-    
-    if !current.trim().is_empty() {
-        result.push(current.trim().to_string());
-    }
-    result
+pub(crate) fn split_by_comma(s: &str) -> Vec<String> {
+    // Default values may hold string literals (`sep String = ", "`)
+    split_top_level_types(s, ',').into_iter().map(|p| p.trim().to_string()).collect()
 }
 
 fn parse_single_param(param: &str) -> Result<Parameter, String> {
@@ -1385,6 +1662,7 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
             is_borrow: true,
             is_mut_borrow: false,
             is_mut_param: false,
+            default_value: None,
         });
     }
     if param == "&mut self" {
@@ -1394,6 +1672,7 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
             is_borrow: true,
             is_mut_borrow: true,
             is_mut_param: false,
+            default_value: None,
         });
     }
     if param == "self" {
@@ -1403,6 +1682,7 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
             is_borrow: false,
             is_mut_borrow: false,
             is_mut_param: false,
+            default_value: None,
         });
     }
     // CRITICAL FIX: Handle `mut self` (owned mutable self)
@@ -1413,6 +1693,7 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
             is_borrow: false,
             is_mut_borrow: false,
             is_mut_param: true,  // Mark as mutable parameter
+            default_value: None,
         });
     }
     // Also handle: self: Type (explicit self type)
@@ -1424,6 +1705,7 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
             is_borrow: false,
             is_mut_borrow: false,
             is_mut_param: false,
+            default_value: None,
         });
     }
     
@@ -1435,6 +1717,21 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
         (false, param)
     };
     
+    // Default value: `port i32 = 8080` - split at the first top-level `=`
+    let (param_to_parse, default_value) = match find_default_eq(param_to_parse) {
+        Some(eq) => {
+            let default = param_to_parse[eq + 1..].trim();
+            if default.is_empty() {
+                return Err(format!(
+                    "Parameter '{}' has an empty default value.",
+                    param_to_parse[..eq].trim()
+                ));
+            }
+            (param_to_parse[..eq].trim(), Some(default.to_string()))
+        }
+        None => (param_to_parse, None),
+    };
+    
     let first_space = param_to_parse.find(' ').ok_or_else(|| format!(
         "Parameter '{}' has no type annotation. All parameters must have explicit types in RustS+.",
         param_to_parse
@@ -1454,6 +1751,15 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
         ));
     }
     
+    if let Some(extra) = trailing_type_tokens(&type_str) {
+        return Err(format!(
+            "Parameter '{}' has unexpected `{}` after its type `{}`. Separate parameters with commas.",
+            name,
+            extra,
+            type_str[..type_str.len() - extra.len()].trim_end()
+        ));
+    }
+    
     let (is_borrow, is_mut_borrow) = if type_str.starts_with("&mut ") {
         (true, true)
     } else if type_str.starts_with('&') {
@@ -1462,7 +1768,75 @@ fn parse_single_param(param: &str) -> Result<Parameter, String> {
         (false, false)
     };
     
-    Ok(Parameter { name, param_type: type_str, is_borrow, is_mut_borrow, is_mut_param })
+    Ok(Parameter { name, param_type: type_str, is_borrow, is_mut_borrow, is_mut_param, default_value })
+}
+
+/// Tokens left over after a complete parameter type (`i32 y` -> `y`).
+/// Words joined by `&`, `mut`, `dyn`, `impl`, `fn`, `->` and `+` belong
+/// to the type, as do spaces inside brackets and the arrowless return type
+/// of `fn(Account) Account`.
+fn trailing_type_tokens(type_str: &str) -> Option<&str> {
+    let mut tokens: Vec<(usize, usize)> = Vec::new();
+    let mut depth = 0i32;
+    let mut start = None;
+    let mut prev = ' ';
+    for (i, c) in type_str.char_indices() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '>' if prev != '-' => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if let Some(s) = start.take() { tokens.push((s, i)); }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+        prev = c;
+    }
+    if let Some(s) = start { tokens.push((s, type_str.len())); }
+    
+    const JOINERS: [&str; 12] = ["&", "&mut", "mut", "dyn", "impl", "unsafe", "extern", "fn", "->", "+", "*const", "*mut"];
+    tokens.windows(2).find_map(|pair| {
+        let before = &type_str[pair[0].0..pair[0].1];
+        let token = &type_str[pair[1].0..pair[1].1];
+        let joined = JOINERS.contains(&before)
+            || before.ends_with('+')
+            || before.starts_with("&'")
+            || before.starts_with('"')
+            || (before.ends_with(')') && !before.starts_with('('))
+            || token.starts_with('+')
+            || token.starts_with("->");
+        (!joined).then(|| &type_str[pair[1].0..])
+    })
+}
+
+/// Position of the `=` introducing a parameter default, ignoring `==`/`=>`
+/// and anything inside brackets or string literals
+fn find_default_eq(param: &str) -> Option<usize> {
+    let bytes = param.as_bytes();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut prev = b' ';
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == b'"' && prev != b'\\' {
+            in_string = !in_string;
+        } else if !in_string {
+            match b {
+                b'<' | b'[' | b'(' => depth += 1,
+                b'>' | b']' | b')' => depth -= 1,
+                b'=' if depth == 0 => {
+                    let next = bytes.get(i + 1).copied();
+                    if !matches!(prev, b'=' | b'!' | b'<' | b'>') && !matches!(next, Some(b'=') | Some(b'>')) {
+                        return Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        prev = b;
+    }
+    None
 }
 
 /// Convert a RustS+ function signature to Rust syntax
@@ -1482,6 +1856,10 @@ pub fn signature_to_rust_with_where(sig: &FunctionSignature, has_where_clause: b
 fn signature_to_rust_impl(sig: &FunctionSignature, has_where_clause: bool) -> String {
     let mut result = String::new();
     
+    for attribute in &sig.attributes {
+        result.push_str(attribute);
+        result.push(' ');
+    }
     if sig.is_pub { result.push_str("pub "); }
     
     result.push_str("fn ");
@@ -1651,6 +2029,22 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_fn_modifiers() {
+        let (attributes, stripped) = split_fn_modifiers("    pub inline must_use inline fn f() i64 {").unwrap();
+        assert_eq!(attributes, vec!["#[inline]", "#[must_use]"]);
+        assert_eq!(stripped, "    pub fn f() i64 {");
+        assert!(split_fn_modifiers("fn f() {").is_none());
+        assert!(split_fn_modifiers("inline = 3").is_none());
+        assert!(split_fn_modifiers("fast fn f() {").is_none());
+        match parse_function_line("cold fn fail(msg String) {") {
+            FunctionParseResult::RustSPlusSignature(sig) => {
+                assert_eq!(signature_to_rust(&sig), "#[cold] fn fail(msg: String) {");
+            }
+            _ => panic!("Expected RustSPlusSignature"),
+        }
+    }
+    
     #[test]
     fn test_rust_passthrough() {
         let line = "fn add(a: i32, b: i32) -> i32 {";
@@ -1849,6 +2243,13 @@ mod tests {
         assert!(result.is_empty());
     }
     
+    #[test]
+    fn test_extract_write_params_field() {
+        // effects(write acc.balance, write acc.owner) -> ["acc"]
+        let result = extract_write_params("effects(write acc.balance, write acc.owner) Account");
+        assert_eq!(result, vec!["acc"]);
+    }
+    
     #[test]
     fn test_extract_write_params_self() {
         // effects(write self) -> ["self"]
@@ -2021,4 +2422,73 @@ mod tests {
         let result2 = transform_generic_brackets("Pin[Box[dyn Future[Output = Result[T, E]]]]");
         assert_eq!(result2, "Pin<Box<dyn Future<Output = Result<T, E>>>>");
     }
+    
+    #[test]
+    fn test_default_parameter_values() {
+        let line = r#"fn connect(host String, port i32 = 8080, sep String = ", ") {"#;
+        let sig = match parse_function_line(line) {
+            FunctionParseResult::RustSPlusSignature(sig) => sig,
+            _ => panic!("Expected RustSPlusSignature"),
+        };
+        assert_eq!(sig.parameters.len(), 3);
+        assert_eq!(sig.parameters[1].param_type, "i32");
+        assert_eq!(sig.parameters[1].default_value.as_deref(), Some("8080"));
+        assert!(signature_to_rust(&sig).contains("fn connect(host: String, port: i32, sep: String)"));
+        
+        let mut registry = FunctionRegistry::new();
+        registry.register(sig);
+        assert_eq!(
+            transform_call_args(r#"connect("h")"#, &registry),
+            r#"connect(String::from("h"), 8080, String::from(", "))"#
+        );
+        assert_eq!(
+            transform_call_args(r#"connect("h", 1)"#, &registry),
+            r#"connect(String::from("h"), 1, String::from(", "))"#
+        );
+        
+        // Defaulted parameters must be trailing
+        assert!(matches!(
+            parse_function_line("fn f(a i32 = 1, b i32) {"),
+            FunctionParseResult::Error(_)
+        ));
+    }
+    
+    #[test]
+    fn test_param_with_extra_tokens_is_malformed() {
+        let line = "fn broken(x i32 y) i32 {";
+        let FunctionParseResult::Error(e) = parse_function_line(line) else {
+            panic!("`{}` should not parse", line);
+        };
+        assert!(e.contains("unexpected `y` after its type `i32`"), "{}", e);
+        assert_eq!(signature_error_span(line, &e), (10, 7));
+        
+        for line in [
+            "fn f(a &mut Vec[i32], b &'a str, c Box[dyn Fn(i32) -> i32 + Send]) {",
+            "fn g(h impl Fn(i32) -> i32, p *const u8, q (i32, i32), r extern \"C\" fn(i32)) {",
+            "fn h(s dyn Display + Send, t [u8; 4]) {",
+        ] {
+            assert!(matches!(parse_function_line(line), FunctionParseResult::RustSPlusSignature(_)), "{}", line);
+        }
+    }
+    
+    #[test]
+    fn test_macro_arguments_are_opaque() {
+        let mut registry = FunctionRegistry::new();
+        for line in ["fn check(a i32, s String) i32 {", "fn write(s String) {"] {
+            if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(line) {
+                registry.register(sig);
+            }
+        }
+        // Format args stay as written; calls inside each argument are lowered
+        assert_eq!(
+            transform_call_args(r#"assert_eq(check(a, "s"), 1, "msg {} {}", a, check(b, "t"))"#, &registry),
+            r#"assert_eq(check(a, String::from("s")), 1, "msg {} {}", a, check(b, String::from("t")))"#
+        );
+        assert_eq!(
+            transform_call_args(r#"dbg!(x < y, "lit", z)"#, &registry),
+            r#"dbg!(x < y, "lit", z)"#
+        );
+        // A user function shadowing a macro name is still a call
+        assert_eq!(transform_call_args(r#"write("x")"#, &registry), r#"write(String::from("x"))"#);
+    }
 }
@@ -0,0 +1,177 @@
+//! User-Defined Generic Types
+//!
+//! Structs and enums take type parameters in brackets, like functions:
+//!
+//! ```text
+//! struct Wrapper[T] { value T }     →  struct Wrapper<T> { value: T }
+//! impl[T] Wrapper[T] {              →  impl<T> Wrapper<T> {
+//! w: Wrapper[i32] = make()          →  let w: Wrapper<i32> = make();
+//! x = Wrapper[i64] { value = 7 }    →  let x: Wrapper<i64> = Wrapper { value: 7 };
+//! m = Maybe[u8]::Just(3)            →  let m = Maybe::<u8>::Just(3);
+//! ```
+//!
+//! `Name[..]` is a type only for the std generics the lowering knows;
+//! anywhere else it is indexing. So the names of the structs and enums
+//! declared with parameters are collected first, and their brackets are
+//! rewritten on the whole source before lowering (line numbers do not
+//! change). A literal keeps its type arguments on the binding it is
+//! assigned to; a literal that is not assigned leaves them to inference.
+//!
+//! A generic type that needs `Clone` gets `#[derive(Clone)]` as usual. The
+//! derive implements `Clone` for `Wrapper<T>` only where `T: Clone`, so a
+//! wrapper around a non-Clone type still compiles as long as it is never
+//! cloned.
+
+use std::collections::HashSet;
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{delimiter_positions, find_matching_close};
+
+/// `struct Wrapper[T] {` / `pub enum Maybe<T> {` → the type name
+pub fn generic_type_header(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    let trimmed = trimmed.strip_prefix("pub(crate) ").or_else(|| trimmed.strip_prefix("pub ")).unwrap_or(trimmed);
+    let rest = trimmed.strip_prefix("struct ").or_else(|| trimmed.strip_prefix("enum "))?;
+    let name: String = rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+    let generic = rest[name.len()..].starts_with(['[', '<']);
+    (!name.is_empty() && generic).then_some(name)
+}
+
+/// Names of the structs and enums `source` declares with type parameters
+pub fn generic_type_names(source: &str) -> HashSet<String> {
+    source.lines().filter_map(generic_type_header).collect()
+}
+
+/// Rewrite the brackets of every type in `names` on every line of `source`
+pub fn lower_generic_types(source: &str, names: &HashSet<String>) -> String {
+    if names.is_empty() {
+        return source.to_string();
+    }
+    source
+        .split('\n')
+        .map(|line| lower_generic_types_line(line, names))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// One line of [`lower_generic_types`]
+pub fn lower_generic_types_line(line: &str, names: &HashSet<String>) -> String {
+    let code = strip_inline_comment(line);
+    let comment = &line[code.len()..];
+    let mut result = code.to_string();
+
+    // `impl[T: Clone] Wrapper[T] {`
+    let indent = result.len() - result.trim_start().len();
+    if result[indent..].starts_with("impl[") {
+        if let Some(close) = find_matching_close(&result, indent + 4) {
+            let inner = lower_generic_types_line(&result[indent + 5..close], names);
+            result = format!("{}impl<{}>{}", &result[..indent], inner, &result[close + 1..]);
+        }
+    }
+
+    // Left to right, restarting after each rewrite
+    let mut search_from = 0;
+    while let Some(open) = next_type_bracket(&result, search_from, names) {
+        let Some(close) = find_matching_close(&result, open) else {
+            break;
+        };
+        let name_start = result[..open].rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |p| p + 1);
+        let name = result[name_start..open].to_string();
+        let args = lower_generic_types_line(&result[open + 1..close], names);
+        let before = result[..name_start].trim_end();
+        let after = &result[close + 1..];
+        let after_trimmed = after.trim_start();
+
+        let is_expression = before.is_empty()
+            || before.ends_with(['=', '(', ',', '[', '{'])
+            || before.ends_with("return");
+        let replacement = if after_trimmed.starts_with("::") {
+            format!("{}::<{}>", name, args)
+        } else if is_expression && after_trimmed.starts_with('{') {
+            // Literal: the type arguments go onto the binding
+            match before.strip_suffix('=').map(str::trim) {
+                Some(binding) if is_plain_binding(binding) => {
+                    let binding_start = result[..name_start].rfind(binding).unwrap_or(0);
+                    let head = &result[..binding_start];
+                    let rebuilt = format!("{}{}: {}<{}> = {}", head, binding, name, args, name);
+                    search_from = rebuilt.len();
+                    result = format!("{}{}", rebuilt, after);
+                    continue;
+                }
+                _ => name.clone(),
+            }
+        } else {
+            format!("{}<{}>", name, args)
+        };
+        search_from = name_start + replacement.len();
+        result = format!("{}{}{}", &result[..name_start], replacement, after);
+    }
+    format!("{}{}", result, comment)
+}
+
+/// Position of the next `[` outside strings that directly follows a name in `names`
+fn next_type_bracket(line: &str, from: usize, names: &HashSet<String>) -> Option<usize> {
+    delimiter_positions(line, &['['])
+        .into_iter()
+        .map(|(pos, _)| pos)
+        .filter(|&pos| pos >= from)
+        .find(|&pos| {
+            let before = &line[..pos];
+            let start = before.rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map_or(0, |p| p + 1);
+            names.contains(&before[start..])
+        })
+}
+
+/// `x` or `mut x`: a binding the lowering declares with `let`
+fn is_plain_binding(binding: &str) -> bool {
+    let name = binding.strip_prefix("mut ").unwrap_or(binding).trim();
+    crate::ident::is_valid_identifier(name) && !binding.contains(':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> HashSet<String> {
+        generic_type_names("struct Wrapper[T] {\n}\npub enum Maybe<T> {\n}\nstruct Plain {\n}")
+    }
+
+    #[test]
+    fn test_generic_type_names() {
+        let mut found: Vec<String> = names().into_iter().collect();
+        found.sort();
+        assert_eq!(found, vec!["Maybe", "Wrapper"]);
+    }
+
+    #[test]
+    fn test_type_positions() {
+        let names = names();
+        assert_eq!(lower_generic_types_line("struct Wrapper[T: Clone] {", &names), "struct Wrapper<T: Clone> {");
+        assert_eq!(lower_generic_types_line("impl[T] Wrapper[T] {", &names), "impl<T> Wrapper<T> {");
+        assert_eq!(
+            lower_generic_types_line("fn f(w &Wrapper[Maybe[i32]]) Vec[Wrapper[u8]] {", &names),
+            "fn f(w &Wrapper<Maybe<i32>>) Vec[Wrapper<u8>] {"
+        );
+        assert_eq!(lower_generic_types_line("    w: Wrapper[i32] = make()", &names), "    w: Wrapper<i32> = make()");
+    }
+
+    #[test]
+    fn test_expression_positions() {
+        let names = names();
+        assert_eq!(lower_generic_types_line("    m = Maybe[u8]::Just(3)", &names), "    m = Maybe::<u8>::Just(3)");
+        assert_eq!(
+            lower_generic_types_line("    x = Wrapper[i64] { value = 7 }", &names),
+            "    x: Wrapper<i64> = Wrapper { value = 7 }"
+        );
+        assert_eq!(lower_generic_types_line("    mut n = Wrapper[String] {", &names), "    mut n: Wrapper<String> = Wrapper {");
+        assert_eq!(lower_generic_types_line("    Wrapper[i32] { value = 1 }", &names), "    Wrapper { value = 1 }");
+    }
+
+    #[test]
+    fn test_other_brackets_untouched() {
+        let names = names();
+        assert_eq!(lower_generic_types_line("x = items[i]", &names), "x = items[i]");
+        assert_eq!(lower_generic_types_line("s = \"Wrapper[T]\"  // Wrapper[T]", &names), "s = \"Wrapper[T]\"  // Wrapper[T]");
+        assert_eq!(lower_generic_types_line("x = Plain[0]", &names), "x = Plain[0]");
+    }
+}
@@ -35,12 +35,22 @@ pub fn strip_inline_comment(line: &str) -> String {
     result.trim_end().to_string()
 }
 
+/// Turn a source line into a `//` comment (blank lines stay blank)
+pub fn comment_out_line(leading_ws: &str, trimmed: &str) -> String {
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("{}// {}", leading_ws, trimmed)
+    }
+}
+
 /// Transform RustS+ generic syntax to Rust generic syntax
 /// RustS+ uses square brackets for generics: `Vec[String]`, `HashMap[K, V]`
 /// Rust uses angle brackets: `Vec<String>`, `HashMap<K, V>`
 /// 
 /// Also handles:
 /// - Turbofish syntax: `parse::[u64]()` → `parse::<u64>()`
+/// - Generic calls: `text.parse[i32]()` → `text.parse::<i32>()`
 /// - dyn trait objects: `dyn Future[Output = T]` → `dyn Future<Output = T>`
 /// - Associated types: `Output = Result[T, E]` (preserves `=`, only transforms brackets)
 /// - Lifetime parameters: `Formatter[_]` → `Formatter<'_>` (NOT `Formatter<_>`)
@@ -99,6 +109,8 @@ pub fn transform_generic_brackets(type_str: &str) -> String {
     // Pattern: `::[T]` → `::<T>`
     // This handles things like `val.parse::[u64]()` → `val.parse::<u64>()`
     result = transform_turbofish_brackets(&result);
+    // ... and the same call without `::`: `text.parse[i32]()`
+    result = transform_bracket_generic_calls(&result);
     
     // CRITICAL FIX 2: Loop until no more transformations are needed
     // This ensures ALL occurrences of each generic type are transformed
@@ -177,6 +189,234 @@ fn transform_turbofish_brackets(s: &str) -> String {
     result
 }
 
+/// Transform generic calls written without `::`: `name[T](...)` →
+/// `name::<T>(...)`
+///
+/// `name[...](...)` is either a generic call (`text.parse[i32]()`,
+/// `iter.collect[Vec[_]]()`) or a call through an indexed element
+/// (`handlers[i](x)`). The line is tokenized and the bracket is taken as
+/// generic arguments only when everything in it is a type: primitive or
+/// capitalized names, `_`, paths, references, tuples and nested brackets. An
+/// index - `i`, `0`, `n - 1`, `MAX` - never qualifies. Strings and comments
+/// are skipped by the lexer.
+///
+/// Examples:
+/// - `n = text.parse[i32]()` → `n = text.parse::<i32>()`
+/// - `v = xs.collect[Vec[i32]]()` → `v = xs.collect::<Vec<i32>>()`
+/// - `handlers[i](x)` is unchanged
+pub fn transform_bracket_generic_calls(s: &str) -> String {
+    if !s.contains("](") {
+        return s.to_string();
+    }
+    if s.contains('\n') {
+        return s.lines().map(transform_bracket_generic_calls).collect::<Vec<_>>().join("\n");
+    }
+    use crate::parser::{Lexer, Token};
+    
+    let tokens = Lexer::tokenize(s);
+    // (`[` char index, `]` char index) of each generic argument list
+    let mut generic_args = Vec::new();
+    let mut i = 0;
+    while i + 1 < tokens.len() {
+        if !matches!(tokens[i].0, Token::Ident(_)) || tokens[i + 1].0 != Token::LBracket {
+            i += 1;
+            continue;
+        }
+        let mut depth = 0;
+        let close = (i + 1..tokens.len()).find(|&j| {
+            match tokens[j].0 {
+                Token::LBracket => depth += 1,
+                Token::RBracket => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        });
+        let Some(close) = close else { break };
+        let inner = &tokens[i + 2..close];
+        let is_call = tokens.get(close + 1).map(|t| &t.0) == Some(&Token::LParen);
+        if is_call && !inner.is_empty() && is_type_tokens(inner) {
+            generic_args.push((tokens[i + 1].1.start_col - 1, tokens[close].1.start_col - 1));
+        }
+        i += 1;
+    }
+    
+    let chars: Vec<char> = s.chars().collect();
+    let mut result = String::new();
+    let mut pos = 0;
+    for (open, close) in generic_args {
+        // Nested in an argument list already rewritten
+        if open < pos {
+            continue;
+        }
+        let inner: String = chars[open + 1..close].iter().collect();
+        result.extend(&chars[pos..open]);
+        result.push_str(&format!("::<{}>", transform_generic_brackets(&inner)));
+        pos = close + 1;
+    }
+    result.extend(&chars[pos..]);
+    result
+}
+
+/// Lower the RustS+ `none` literal to `None`
+///
+/// A comparison against `none` becomes a variant check, so it also works for
+/// `Option`s of types without `PartialEq`:
+/// - `if user != none {` → `if user.is_some() {`
+/// - `found = none == cache.get(&k)` → `found = cache.get(&k).is_none()`
+/// - `x = none` → `x = None`
+///
+/// Fields, paths, calls and bindings named `none` (`s.none`, `m::none`,
+/// `none(x)`, `let none = ..`) and string contents are left alone.
+pub fn transform_none_literal(s: &str) -> String {
+    if !s.contains("none") {
+        return s.to_string();
+    }
+    if s.contains('\n') {
+        return s.lines().map(transform_none_literal).collect::<Vec<_>>().join("\n");
+    }
+    use crate::parser::{Lexer, Token};
+    
+    let tokens = Lexer::tokenize(s);
+    let chars: Vec<char> = s.chars().collect();
+    let col = |i: usize| tokens.get(i).map_or(chars.len(), |t| (t.1.start_col - 1).min(chars.len()));
+    let tok = |i: usize| tokens.get(i).map(|t| &t.0);
+    let text = |from: usize, to: usize| chars[from..to].iter().collect::<String>().trim().to_string();
+    
+    // (first char, end char, replacement)
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for i in 0..tokens.len() {
+        if !matches!(&tokens[i].0, Token::Ident(name) if name == "none") {
+            continue;
+        }
+        let prev = if i > 0 { tok(i - 1) } else { None };
+        if matches!(prev, Some(Token::Dot | Token::ColonColon | Token::Let | Token::Mut | Token::Fn))
+            || matches!(tok(i + 1), Some(Token::LParen | Token::ColonColon | Token::Colon | Token::Eq))
+        {
+            continue;
+        }
+        let check = |op: &Token| if *op == Token::EqEq { "is_none" } else { "is_some" };
+        match (prev, tok(i + 1)) {
+            // `lhs == none`
+            (Some(op @ (Token::EqEq | Token::Ne)), _) if i >= 2 => {
+                let start = operand_start(&tokens, i - 1);
+                let operand = text(col(start), col(i - 1));
+                edits.push((col(start), col(i) + 4, variant_check(&operand, check(op))));
+            }
+            // `none == rhs`
+            (_, Some(op @ (Token::EqEq | Token::Ne))) => {
+                let end = operand_end(&tokens, i + 2);
+                let operand = text(col(i + 2), col(end));
+                let replaced_end = if end >= tokens.len() || tokens[end].0 == Token::Eof {
+                    chars.len()
+                } else {
+                    col(end)
+                };
+                let trailing_ws = chars[col(i + 2)..replaced_end].iter().rev().take_while(|c| c.is_whitespace()).count();
+                edits.push((col(i), replaced_end - trailing_ws, variant_check(&operand, check(op))));
+            }
+            _ => edits.push((col(i), col(i) + 4, "None".to_string())),
+        }
+    }
+    
+    let mut result = String::new();
+    let mut pos = 0;
+    for (start, end, replacement) in edits {
+        if start < pos || start >= end {
+            continue;
+        }
+        result.extend(&chars[pos..start]);
+        result.push_str(&replacement);
+        pos = end;
+    }
+    result.extend(&chars[pos..]);
+    result
+}
+
+/// `operand.is_none()`, parenthesized unless the operand is a plain place
+/// or call chain (`user`, `self.cache.get(&k)`, `lookup(id)?`)
+fn variant_check(operand: &str, method: &str) -> String {
+    let mut depth = 0;
+    let simple = operand.chars().all(|c| {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            _ => {}
+        }
+        depth > 0 || c == ')' || c == ']' || c == '.' || c == '?' || c == ':'
+            || crate::ident::is_ident_continue(c)
+    });
+    if simple {
+        format!("{}.{}()", operand, method)
+    } else {
+        format!("({}).{}()", operand, method)
+    }
+}
+
+/// Index of the first token of the comparison operand ending before `op`
+fn operand_start(tokens: &[(crate::parser::Token, crate::ast::Span)], op: usize) -> usize {
+    use crate::parser::Token;
+    
+    let mut depth = 0;
+    let mut i = op;
+    while i > 0 {
+        match &tokens[i - 1].0 {
+            Token::RParen | Token::RBracket => depth += 1,
+            Token::LParen | Token::LBracket if depth == 0 => break,
+            Token::LParen | Token::LBracket => depth -= 1,
+            Token::And | Token::Or | Token::Comma | Token::Eq | Token::LBrace | Token::Semi
+            | Token::PlusEq | Token::MinusEq | Token::StarEq | Token::SlashEq | Token::PercentEq
+            | Token::FatArrow | Token::If | Token::While | Token::Return | Token::Let
+            | Token::Match if depth == 0 => break,
+            _ => {}
+        }
+        i -= 1;
+    }
+    i
+}
+
+/// Index one past the last token of the comparison operand starting at `from`
+fn operand_end(tokens: &[(crate::parser::Token, crate::ast::Span)], from: usize) -> usize {
+    use crate::parser::Token;
+    
+    let mut depth = 0;
+    let mut i = from;
+    while i < tokens.len() {
+        match &tokens[i].0 {
+            Token::LParen | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBracket if depth == 0 => break,
+            Token::RParen | Token::RBracket => depth -= 1,
+            Token::And | Token::Or | Token::Comma | Token::LBrace | Token::Semi
+            | Token::Eof if depth == 0 => break,
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+/// Could these tokens be the generic arguments of a call?
+fn is_type_tokens(tokens: &[(crate::parser::Token, crate::ast::Span)]) -> bool {
+    use crate::parser::Token;
+    
+    const PRIMITIVES: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize",
+        "u8", "u16", "u32", "u64", "u128", "usize",
+        "f32", "f64", "bool", "char", "str", "_", "dyn",
+    ];
+    tokens.iter().enumerate().all(|(i, (token, _))| match token {
+        Token::Ident(name) => {
+            let is_path_segment = tokens.get(i + 1).map(|t| &t.0) == Some(&Token::ColonColon);
+            let capitalized = name.starts_with(char::is_uppercase)
+                && (name.chars().count() == 1 || name.chars().any(char::is_lowercase));
+            is_path_segment || capitalized || PRIMITIVES.contains(&name.as_str())
+        }
+        Token::Mut | Token::ColonColon | Token::Comma | Token::Ampersand
+        | Token::LBracket | Token::RBracket | Token::LParen | Token::RParen
+        | Token::Lt | Token::Gt | Token::Shr => true,
+        _ => false,
+    })
+}
+
 /// Find the position of the matching closing bracket for a string starting with `[`
 pub fn find_matching_bracket(s: &str) -> Option<usize> {
     if !s.starts_with('[') {
@@ -184,7 +424,7 @@ pub fn find_matching_bracket(s: &str) -> Option<usize> {
     }
     
     let mut depth = 0;
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         match c {
             '[' => depth += 1,
             ']' => {
@@ -334,11 +574,29 @@ pub fn is_rust_block_start(line: &str) -> bool {
         || trimmed.starts_with("pub use ")
 }
 
+/// Std macros that RustS+ code may call without `!` (L-08)
+pub const RUST_MACROS: &[&str] = &[
+    "println", "print", "eprintln", "eprint",
+    "format", "panic", "todo", "unimplemented",
+    "vec", "dbg", "assert", "assert_eq", "assert_ne",
+    "debug_assert", "debug_assert_eq", "debug_assert_ne",
+    "write", "writeln", "format_args",
+    "include_str", "include_bytes", "concat", "stringify",
+    "env", "option_env", "line", "column", "file",
+    "module_path", "compile_error",
+];
+
 /// L-08: Transform RustS+ macro calls to Rust macro calls
 pub fn transform_macro_calls(line: &str) -> String {
+    transform_macro_calls_except(line, &[])
+}
+
+/// L-08 for a line where the names in `shadowed` are bindings (see
+/// [`crate::macro_shadowing`]): calls of those names stay plain calls
+pub fn transform_macro_calls_except(line: &str, shadowed: &[String]) -> String {
     let trimmed = line.trim();
     
-    if is_function_definition(trimmed) {
+    if is_function_definition(trimmed) || !crate::lowering_rules::enabled(crate::lowering_rules::LoweringRule::L08) {
         return line.to_string();
     }
     
@@ -346,17 +604,6 @@ pub fn transform_macro_calls(line: &str) -> String {
         return line.to_string();
     }
     
-    const MACROS: &[&str] = &[
-        "println", "print", "eprintln", "eprint",
-        "format", "panic", "todo", "unimplemented",
-        "vec", "dbg", "assert", "assert_eq", "assert_ne",
-        "debug_assert", "debug_assert_eq", "debug_assert_ne",
-        "write", "writeln", "format_args",
-        "include_str", "include_bytes", "concat", "stringify",
-        "env", "option_env", "line", "column", "file",
-        "module_path", "compile_error",
-    ];
-    
     // CRITICAL: Path-qualified macros like anyhow::bail, anyhow::anyhow
     // These must be transformed to anyhow::bail!, anyhow::anyhow! etc.
     const PATH_MACROS: &[&str] = &[
@@ -401,7 +648,10 @@ pub fn transform_macro_calls(line: &str) -> String {
     }
     
     // Then handle simple macros
-    for macro_name in MACROS {
+    for macro_name in RUST_MACROS {
+        if shadowed.iter().any(|name| name == macro_name) {
+            continue;
+        }
         let search_pattern = format!("{}(", macro_name);
         let correct_pattern = format!("{}!(", macro_name);
         
@@ -498,12 +748,15 @@ pub fn transform_struct_field_slice_to_vec(line: &str) -> String {
     line.to_string()
 }
 
-/// Check if a string is a valid Rust identifier
+/// Convert a char index (from `s.chars().collect::<Vec<char>>()` scanning)
+/// into a byte offset usable for slicing `s`
+pub fn char_to_byte_index(s: &str, char_idx: usize) -> usize {
+    s.char_indices().nth(char_idx).map(|(b, _)| b).unwrap_or(s.len())
+}
+
+/// Check if a string is a valid Rust identifier (Unicode aware, see `ident`)
 pub fn is_valid_identifier(s: &str) -> bool {
-    if s.is_empty() { return false; }
-    let first = s.chars().next().unwrap();
-    if !first.is_alphabetic() && first != '_' { return false; }
-    s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    crate::ident::is_valid_identifier(s)
 }
 
 /// Check if a string is a field access pattern (e.g., `self.field`, `obj.field`)
@@ -672,6 +925,47 @@ mod tests {
         );
     }
     
+    /// `name[T](..)` is a generic call, `name[i](..)` calls an element
+    #[test]
+    fn test_none_literal() {
+        assert_eq!(transform_none_literal("    x = none;"), "    x = None;");
+        assert_eq!(transform_none_literal("if user != none {"), "if user.is_some() {");
+        assert_eq!(transform_none_literal("while self.head == none && n > 0 {"), "while self.head.is_none() && n > 0 {");
+        assert_eq!(transform_none_literal("let found = none != cache.get(&k);"), "let found = cache.get(&k).is_some();");
+        assert_eq!(transform_none_literal("ok = (*slot == none)"), "ok = ((*slot).is_none())");
+        assert_eq!(transform_none_literal("return Some(a) == none"), "return Some(a).is_none()");
+        for unchanged in [
+            "let none = 1;",
+            "x = s.none + m::none",
+            "y = none(3)",
+            "println!(\"none\")",
+        ] {
+            assert_eq!(transform_none_literal(unchanged), unchanged);
+        }
+    }
+    
+    #[test]
+    fn test_bracket_generic_calls() {
+        assert_eq!(transform_bracket_generic_calls("n = text.parse[i32]()"), "n = text.parse::<i32>()");
+        assert_eq!(
+            transform_bracket_generic_calls("v = it.collect[HashMap[String, Vec[u8]]]().len()"),
+            "v = it.collect::<HashMap<String, Vec<u8>>>().len()"
+        );
+        assert_eq!(transform_bracket_generic_calls("v = it.collect[Vec<_>]()"), "v = it.collect::<Vec<_>>()");
+        assert_eq!(transform_bracket_generic_calls("x = mem::size_of[std::fs::File]()"), "x = mem::size_of::<std::fs::File>()");
+        assert_eq!(transform_bracket_generic_calls("a = xs.sum[i64]() + ys[0]"), "a = xs.sum::<i64>() + ys[0]");
+        for unchanged in [
+            "r = handlers[i](x)",
+            "r = handlers[0](x)",
+            "r = handlers[n - 1](x)",
+            "r = table[MAX](x)",
+            "xs[i] = f(y)",
+            "println!(\"{}\", \"a.parse[i32]()\")",
+        ] {
+            assert_eq!(transform_bracket_generic_calls(unchanged), unchanged);
+        }
+    }
+    
     /// CRITICAL: Nested generics in async types must be fully transformed
     #[test]
     fn test_nested_async_generics() {
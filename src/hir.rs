@@ -299,6 +299,9 @@ pub enum HirExpr {
     /// Variable reference (resolved to binding)
     Var(BindingId),
     
+    /// Function named as a value, not called: `parse` in `xs.map(parse)`
+    FnRef(Path),
+    
     /// Field access: expr.field
     Field {
         base: Box<Spanned<HirExpr>>,
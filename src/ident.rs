@@ -0,0 +1,134 @@
+//! Identifier Rules for RustS+
+//!
+//! Single source of truth for "is this an identifier?" and "is this a
+//! keyword?" - previously every module carried its own ASCII-ish copy.
+//!
+//! ## Unicode
+//!
+//! Rust identifiers follow Unicode XID (`XID_Start` / `XID_Continue`). The
+//! standard library does not expose those tables, so they are approximated
+//! with the Unicode `Alphabetic` / `Numeric` properties, which cover every
+//! letter-based script (`größe`, `名前`, `переменная`).
+//!
+//! ## Keywords
+//!
+//! Rust keywords cannot be binding names. RustS+ reports them in Stage 1
+//! (RSPL086) instead of letting rustc fail on the generated code. Raw
+//! identifiers (`r#type`) are accepted and passed through unchanged.
+
+/// Strict and reserved Rust keywords (2021 edition)
+pub const RUST_KEYWORDS: &[&str] = &[
+    // Strict
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn",
+    "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+    "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+    // Reserved
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv",
+    "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Keywords that cannot be used even as raw identifiers
+const NON_RAW_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Can `c` start an identifier?
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+/// Can `c` continue an identifier?
+pub fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+/// Check if a string is a syntactically valid identifier (Unicode aware).
+///
+/// Keywords are NOT rejected here - use [`is_rust_keyword`] for that.
+/// Raw identifiers (`r#type`) are accepted.
+pub fn is_valid_identifier(s: &str) -> bool {
+    let s = s.strip_prefix("r#").unwrap_or(s);
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) if is_ident_start(first) => chars.all(is_ident_continue),
+        _ => false,
+    }
+}
+
+/// Check if `s` is a Rust keyword (strict or reserved)
+pub fn is_rust_keyword(s: &str) -> bool {
+    RUST_KEYWORDS.contains(&s)
+}
+
+/// Check if `s` may be written as a raw identifier `r#s`
+pub fn can_be_raw_identifier(s: &str) -> bool {
+    is_rust_keyword(s) && !NON_RAW_KEYWORDS.contains(&s)
+}
+
+/// Suggest a non-keyword replacement name for a keyword binding
+pub fn keyword_rename_suggestion(keyword: &str) -> String {
+    match keyword {
+        "type" => "kind".to_string(),
+        "match" => "matched".to_string(),
+        "fn" => "func".to_string(),
+        "mod" => "module".to_string(),
+        "ref" => "reference".to_string(),
+        "move" => "movement".to_string(),
+        "struct" => "record".to_string(),
+        "impl" => "implementation".to_string(),
+        "loop" => "cycle".to_string(),
+        "self" | "Self" => "this".to_string(),
+        "crate" => "krate".to_string(),
+        _ => format!("{}_", keyword),
+    }
+}
+
+/// Split an annotated binding `name: Type` at the annotation colon (the
+/// first `:` that is not part of a `::` path). `None` unless `name` is an
+/// identifier and `Type` is non-empty.
+pub fn split_type_annotation(left: &str) -> Option<(&str, &str)> {
+    let bytes = left.as_bytes();
+    let colon = (0..bytes.len()).find(|&i| {
+        bytes[i] == b':'
+            && bytes.get(i + 1) != Some(&b':')
+            && (i == 0 || bytes[i - 1] != b':')
+    })?;
+    let name = left[..colon].trim();
+    let ty = left[colon + 1..].trim();
+    (is_valid_identifier(name) && !ty.is_empty()).then_some((name, ty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unicode_identifiers() {
+        assert!(is_valid_identifier("größe"));
+        assert!(is_valid_identifier("名前"));
+        assert!(is_valid_identifier("_x1"));
+        assert!(is_valid_identifier("r#type"));
+        assert!(!is_valid_identifier("1x"));
+        assert!(!is_valid_identifier("a-b"));
+        assert!(!is_valid_identifier(""));
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert!(is_rust_keyword("type"));
+        assert!(is_rust_keyword("yield"));
+        assert!(!is_rust_keyword("kind"));
+        assert!(can_be_raw_identifier("match"));
+        assert!(!can_be_raw_identifier("self"));
+        assert_eq!(keyword_rename_suggestion("type"), "kind");
+        assert_eq!(keyword_rename_suggestion("while"), "while_");
+    }
+
+    #[test]
+    fn test_split_type_annotation() {
+        assert_eq!(split_type_annotation("user: Option[User]"), Some(("user", "Option[User]")));
+        assert_eq!(split_type_annotation("r: std::io::Result<()>"), Some(("r", "std::io::Result<()>")));
+        assert_eq!(split_type_annotation("Foo::BAR"), None);
+        assert_eq!(split_type_annotation("a.b: i32"), None);
+    }
+}
@@ -0,0 +1,364 @@
+//! Inlining of Trivial Pure Functions (`--opt-level 1`)
+//!
+//! .rss programs are full of one-line arithmetic helpers. With
+//! [`crate::LoweringOptions::opt_level`] at 1 or above, calls to them are
+//! replaced by the helper's body in the generated Rust:
+//!
+//! ```text
+//! fn area(w: i64, h: i64) -> i64 {
+//!     w * h
+//! }
+//! let a = area(x, 3);   →   let a = { let (w, h): (i64, i64) = (x, 3); w * h };
+//! ```
+//!
+//! A function is inlined when it is a top-level function that declares no
+//! effects, takes and returns primitive types, and whose body is a single
+//! expression over its parameters and literals - operators, parentheses and
+//! `as` casts only, nothing that calls, borrows or diverges. Arguments are
+//! bound once by a typed `let`, so they are evaluated exactly once, in
+//! order, and keep the parameter types for inference.
+//!
+//! Left alone: calls spanning several lines, method calls and paths
+//! (`m::area(..)`), and names that are rebound with `let` somewhere in the
+//! output. The function itself is kept, since other crates or unlowered
+//! code may still call it.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ident::{is_ident_continue, is_ident_start, is_valid_identifier};
+use crate::lowering::depth_tracking_lowering::{find_matching_close, split_top_level};
+
+/// Parameter and return types an inlined function may have
+const PRIMITIVE_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize",
+    "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64", "bool", "char",
+];
+
+/// A function whose calls can be replaced by its body
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineFn {
+    pub name: String,
+    /// `(name, type)`
+    pub params: Vec<(String, String)>,
+    /// The single body expression
+    pub body: String,
+    pub is_public: bool,
+    /// Output line of the signature (0-based)
+    pub line: usize,
+}
+
+impl InlineFn {
+    /// The body as a block that binds `args` to the parameters, in
+    /// parentheses unless it is the right side of an assignment (a block at
+    /// the start of a statement would end the statement)
+    fn inlined(&self, args: &[String], assigned: bool) -> String {
+        let block = if let [(name, ty)] = self.params.as_slice() {
+            format!("{{ let {}: {} = {}; {} }}", name, ty, args[0], self.body)
+        } else {
+            let names: Vec<&str> = self.params.iter().map(|(n, _)| n.as_str()).collect();
+            let types: Vec<&str> = self.params.iter().map(|(_, t)| t.as_str()).collect();
+            format!("{{ let ({}): ({}) = ({}); {} }}", names.join(", "), types.join(", "), args.join(", "), self.body)
+        };
+        if assigned {
+            block
+        } else {
+            format!("({})", block)
+        }
+    }
+}
+
+/// Inline calls to trivial pure functions in lowered Rust. `source` is the
+/// .rss the output was lowered from: functions that declare effects there
+/// are never inlined.
+pub fn inline_trivial_functions(rust: &str, source: &str) -> String {
+    let effectful = functions_declaring_effects(source);
+    let lines: Vec<&str> = rust.lines().collect();
+    let candidates: HashMap<String, InlineFn> = find_inline_candidates(&lines).into_iter()
+        .filter(|f| !effectful.contains(&f.name) && !is_rebound(&lines, &f.name))
+        .map(|f| (f.name.clone(), f))
+        .collect();
+    if candidates.is_empty() {
+        return rust.to_string();
+    }
+
+    let mut inlined = HashSet::new();
+    let mut out: Vec<String> = lines.iter().map(|line| inline_calls(line, &candidates, &mut inlined)).collect();
+    // A private helper may have no calls left; same line, so line numbers hold
+    for f in candidates.values().filter(|f| !f.is_public && inlined.contains(&f.name)) {
+        out[f.line] = format!("#[allow(dead_code)] {}", out[f.line]);
+    }
+    let mut result = out.join("\n");
+    if rust.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Top-level functions of the form `fn name(p: T, ..) -> R {`, one trivial
+/// body expression, `}`
+pub fn find_inline_candidates(lines: &[&str]) -> Vec<InlineFn> {
+    lines.windows(3).enumerate()
+        .filter_map(|(line, w)| {
+            let f = parse_candidate(w[0], w[1].trim(), w[2].trim())?;
+            Some(InlineFn { line, ..f })
+        })
+        .collect()
+}
+
+fn parse_candidate(signature: &str, body: &str, close: &str) -> Option<InlineFn> {
+    if close != "}" {
+        return None;
+    }
+    let (is_public, rest) = match signature.strip_prefix("pub fn ") {
+        Some(rest) => (true, rest),
+        None => (false, signature.strip_prefix("fn ")?),
+    };
+    let rest = rest.trim_end().strip_suffix('{')?.trim_end();
+    let open = rest.find('(')?;
+    let close_paren = rest.rfind(')')?;
+    let name = &rest[..open];
+    if !is_valid_identifier(name) || name == "main" || close_paren < open {
+        return None;
+    }
+    let ret = rest[close_paren + 1..].trim().strip_prefix("->")?.trim();
+    if !PRIMITIVE_TYPES.contains(&ret) {
+        return None;
+    }
+    let params = rest[open + 1..close_paren].split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (name, ty) = p.split_once(':')?;
+            let (name, ty) = (name.trim(), ty.trim());
+            (is_valid_identifier(name) && PRIMITIVE_TYPES.contains(&ty))
+                .then(|| (name.to_string(), ty.to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if params.is_empty() || !is_trivial_expr(body, &params, ret) {
+        return None;
+    }
+    Some(InlineFn { name: name.to_string(), params, body: body.to_string(), is_public, line: 0 })
+}
+
+/// Operators, parentheses, literals, parameters and `as` casts. Every
+/// parameter must be used, and something must pin the result to `ret` (a
+/// parameter or cast of that type) so the inlined block infers the same
+/// type as the call did.
+fn is_trivial_expr(body: &str, params: &[(String, String)], ret: &str) -> bool {
+    let chars: Vec<char> = body.chars().collect();
+    let mut used = HashSet::new();
+    let mut typed = ret == "bool";
+    let mut after_as = false;
+    let mut depth = 0i32;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if is_ident_start(c) {
+            let start = i;
+            while i < chars.len() && is_ident_continue(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            if after_as {
+                if !PRIMITIVE_TYPES.contains(&word.as_str()) {
+                    return false;
+                }
+                typed |= word == ret;
+                after_as = false;
+            } else if word == "as" {
+                after_as = true;
+            } else if let Some((name, ty)) = params.iter().find(|(p, _)| *p == word) {
+                used.insert(name.as_str());
+                typed |= ty == ret;
+            } else if word != "true" && word != "false" {
+                return false;
+            }
+            continue;
+        }
+        if after_as {
+            return false;
+        }
+        if c.is_ascii_digit() {
+            // `1_000`, `2.5`, `3i64` - but not a range or a method call
+            while i < chars.len() && (is_ident_continue(chars[i])
+                || (chars[i] == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))) {
+                i += 1;
+            }
+            continue;
+        }
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+            }
+            '+' | '-' | '*' | '/' | '%' | '<' | '>' | '=' | '!' | '&' | '|' | '^' => {}
+            _ => return false,
+        }
+        i += 1;
+    }
+    !after_as && depth == 0 && typed && used.len() == params.len()
+}
+
+/// Names of functions whose .rss signature has an `effects(...)` clause
+fn functions_declaring_effects(source: &str) -> HashSet<String> {
+    source.lines()
+        .map(str::trim)
+        .filter(|line| line.contains("effects("))
+        .filter_map(|line| line.strip_prefix("pub fn ").or_else(|| line.strip_prefix("fn ")))
+        .map(|rest| rest.chars().take_while(|c| is_ident_continue(*c)).collect())
+        .collect()
+}
+
+/// Is `name` rebound by a `let`, which would shadow the function?
+fn is_rebound(lines: &[&str], name: &str) -> bool {
+    lines.iter().any(|line| {
+        ["let ", "let mut "].iter().any(|binding| {
+            line.match_indices(&format!("{}{}", binding, name)).any(|(at, matched)| {
+                !line[at + matched.len()..].starts_with(is_ident_continue)
+            })
+        })
+    })
+}
+
+/// Replace the calls to `fns` on one line; nested calls in the arguments
+/// are inlined too
+fn inline_calls(line: &str, fns: &HashMap<String, InlineFn>, inlined: &mut HashSet<String>) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' {
+            let end = string_end(&chars, i).unwrap_or(chars.len() - 1);
+            out.extend(&chars[i..=end]);
+            i = end + 1;
+            continue;
+        }
+        if c == '\'' {
+            // Char literal - `'"'` must not open a string; lifetimes pass through
+            let len = if chars.get(i + 1) == Some(&'\\') { 4 } else { 3 };
+            if chars.get(i + len - 1) == Some(&'\'') {
+                out.extend(&chars[i..i + len]);
+                i += len;
+                continue;
+            }
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            out.extend(&chars[i..]);
+            break;
+        }
+        if is_ident_continue(c) {
+            let start = i;
+            while i < chars.len() && is_ident_continue(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let call = fns.get(&word)
+                .filter(|_| is_ident_start(c) && chars.get(i) == Some(&'(') && is_call_position(&chars[..start]));
+            let open: usize = chars[..i].iter().map(|c| c.len_utf8()).sum();
+            if let Some((f, close)) = call.and_then(|f| Some((f, find_matching_close(line, open)?))) {
+                let args = split_top_level(&line[open + 1..close], ',');
+                if args.len() == f.params.len() {
+                    let args: Vec<String> = args.iter().map(|a| inline_calls(a.trim(), fns, inlined)).collect();
+                    out.push_str(&f.inlined(&args, is_assigned(&out)));
+                    inlined.insert(f.name.clone());
+                    i = line[..=close].chars().count();
+                    continue;
+                }
+            }
+            out.push_str(&word);
+            continue;
+        }
+        out.push(c);
+        i += 1;
+    }
+    out
+}
+
+/// Not a method call (`x.f(`), a path (`m::f(`) or the definition (`fn f(`)
+fn is_call_position(before: &[char]) -> bool {
+    if matches!(before.last(), Some('.') | Some(':')) {
+        return false;
+    }
+    let before: String = before.iter().collect();
+    let before = before.trim_end();
+    !(before == "fn" || before.ends_with(" fn"))
+}
+
+/// Does `before` end with `=` or a compound assignment, but not a comparison?
+fn is_assigned(before: &str) -> bool {
+    let before = before.trim_end();
+    before.ends_with('=') && !["==", "<=", ">=", "!="].iter().any(|op| before.ends_with(op))
+}
+
+/// Index of the `"` closing the string opened at `open`
+fn string_end(chars: &[char], open: usize) -> Option<usize> {
+    let mut i = open + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return Some(i),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELPERS: &str = "fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\nfn double(x: f64) -> f64 {\n    x * 2.0\n}\n";
+
+    #[test]
+    fn test_candidates() {
+        let lines: Vec<&str> = HELPERS.lines().collect();
+        let found = find_inline_candidates(&lines);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].name, "add");
+        assert_eq!(found[0].params, vec![("a".to_string(), "i32".to_string()), ("b".to_string(), "i32".to_string())]);
+        assert_eq!(found[1].line, 4);
+
+        // Calls, borrows, non-primitive types, unused parameters
+        for source in [
+            "fn f(a: i32) -> i32 {\n    g(a)\n}",
+            "fn f(a: i32) -> i32 {\n    a.abs()\n}",
+            "fn f(a: String) -> usize {\n    a\n}",
+            "fn f(a: i32, b: i32) -> i32 {\n    a\n}",
+            "fn f(a: i32) -> i64 {\n    5\n}",
+            "    fn f(a: i32) -> i32 {\n        a\n    }",
+        ] {
+            let lines: Vec<&str> = source.lines().collect();
+            assert!(find_inline_candidates(&lines).is_empty(), "inlined: {}", source);
+        }
+    }
+
+    #[test]
+    fn test_calls_inlined() {
+        let rust = format!("{}\nfn main() {{\n    let s = add(add(1, 2), x.y);\n    println!(\"add(1, 2) = {{}}\", add(n + 1, 3));\n    let z = p.add(1, 2) + m::add(1, 2);\n    let c = add(',' as i32, ')' as i32);\n}}", HELPERS);
+        let out = inline_trivial_functions(&rust, "");
+        assert!(out.contains("let s = { let (a, b): (i32, i32) = (({ let (a, b): (i32, i32) = (1, 2); a + b }), x.y); a + b };"), "{}", out);
+        assert!(out.contains("println!(\"add(1, 2) = {}\", ({ let (a, b): (i32, i32) = (n + 1, 3); a + b }));"), "{}", out);
+        assert!(out.contains("let z = p.add(1, 2) + m::add(1, 2);"), "{}", out);
+        assert!(out.contains("let c = { let (a, b): (i32, i32) = (',' as i32, ')' as i32); a + b };"), "{}", out);
+        assert!(out.contains("#[allow(dead_code)] fn add(a: i32, b: i32) -> i32 {"));
+        // Never called - no attribute
+        assert!(out.contains("\nfn double(x: f64) -> f64 {"));
+        assert_eq!(out.lines().count(), rust.lines().count());
+    }
+
+    #[test]
+    fn test_effects_and_shadowing_block_inlining() {
+        let rust = format!("{}\nfn main() {{\n    let d = double(1.5);\n    let add = 3;\n    add(1, 2)\n}}", HELPERS);
+        let source = "fn double(x f64) f64 effects(panic) {\n    x * 2.0\n}";
+        assert_eq!(inline_trivial_functions(&rust, source), rust);
+    }
+}
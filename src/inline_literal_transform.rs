@@ -4,6 +4,10 @@
 //! literals, as well as inline field transformation.
 
 use std::collections::HashMap;
+use crate::ast::{Expr, Spanned, Stmt};
+use crate::helpers::char_to_byte_index;
+use crate::lowering::depth_tracking_lowering::split_top_level;
+use crate::empty_literals::braced;
 use crate::transform_literal::{find_field_eq, find_field_colon_position, is_valid_field_name, is_string_literal, should_clone_field_value, transform_nested_struct_value};
 
 /// Transform single-line struct literal: `u = User { id = 1, name = "x" }`
@@ -20,13 +24,44 @@ pub fn transform_single_line_struct_literal(line: &str, var_name: &str) -> Strin
             
             let transformed_fields = transform_literal_fields_inline(fields_part);
             
-            return format!("let {} = {} {{ {} }};", var_name, struct_name, transformed_fields);
+            return format!("let {} = {};", var_name, braced(struct_name, &transformed_fields));
         }
     }
     
     format!("let {};", line)
 }
 
+/// Lower the value of a parsed one-line assignment that starts with a
+/// struct or enum literal, keeping what follows the literal:
+/// `a = P { x = 1 }.x` gives `P { x: 1 }.x`. `None` when the value does
+/// not start with a literal.
+pub fn lower_assigned_literal(stmt: &Stmt, line: &str) -> Option<String> {
+    let mut expr = match stmt {
+        Stmt::Expr(Spanned { node: Expr::Assign { value, .. }, .. }) => value.as_ref(),
+        Stmt::Let { init: Some(value), .. } => value,
+        _ => return None,
+    };
+    // The literal is the leftmost operand: `P {..}.x`, `P {..}.len() + 1`
+    loop {
+        expr = match &expr.node {
+            Expr::Struct { .. } => break,
+            Expr::Field { base, .. } | Expr::Index { base, .. } => base,
+            Expr::MethodCall { receiver, .. } => receiver,
+            Expr::Binary { left, .. } => left,
+            Expr::Cast { expr, .. } | Expr::Try(expr) => expr,
+            _ => return None,
+        };
+    }
+    
+    // Token spans are 1-based columns; a literal's span ends at its `}`
+    let start = char_to_byte_index(line, expr.span.start_col.checked_sub(1)?);
+    let close = char_to_byte_index(line, expr.span.end_col.checked_sub(1)?);
+    let open = start + line[start..close].find('{')?;
+    let fields = transform_literal_fields_inline(&line[open + 1..close]);
+    let rest = line[close + 1..].trim_end().trim_end_matches(';');
+    Some(format!("{}{}", braced(line[start..open].trim(), &fields), rest))
+}
+
 /// Transform single-line enum literal: `e = Event::Data { id = 1 }`
 pub fn transform_single_line_enum_literal(line: &str, var_name: &str, enum_path: &str) -> String {
     let trimmed = line.trim();
@@ -37,7 +72,7 @@ pub fn transform_single_line_enum_literal(line: &str, var_name: &str, enum_path:
         
         let transformed_fields = transform_literal_fields_inline(fields_part);
         
-        return format!("let {} = {} {{ {} }};", var_name, enum_path, transformed_fields);
+        return format!("let {} = {};", var_name, braced(enum_path, &transformed_fields));
     }
     
     format!("let {};", line)
@@ -55,7 +90,7 @@ pub fn transform_bare_struct_literal(line: &str) -> String {
         
         let transformed_fields = transform_literal_fields_inline(fields_part);
         
-        return format!("{} {{ {} }}", name_part, transformed_fields);
+        return braced(name_part, &transformed_fields);
     }
     
     trimmed.to_string()
@@ -64,31 +99,7 @@ pub fn transform_bare_struct_literal(line: &str) -> String {
 /// Transform inline literal fields: `id = 1, name = "x"` → `id: 1, name: String::from("x"),`
 pub fn transform_literal_fields_inline(fields: &str) -> String {
     let mut result = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut brace_depth: usize = 0;
-    
-    // First pass: collect all fields
-    let mut raw_fields = Vec::new();
-    for c in fields.chars() {
-        if c == '"' && !current.ends_with('\\') {
-            in_string = !in_string;
-        }
-        if !in_string {
-            if c == '{' { brace_depth += 1; }
-            if c == '}' { brace_depth = brace_depth.saturating_sub(1); }
-        }
-        
-        if c == ',' && !in_string && brace_depth == 0 {
-            raw_fields.push(current.clone());
-            current.clear();
-        } else {
-            current.push(c);
-        }
-    }
-    if !current.trim().is_empty() {
-        raw_fields.push(current);
-    }
+    let raw_fields = split_top_level(fields, ',');
     
     // CRITICAL FIX: Track field values to detect duplicates
     // Duplicate values (like from.address used twice) need .clone() on earlier uses
@@ -238,4 +249,4 @@ pub fn transform_single_literal_field_with_clone(field: &str, add_clone: bool) -
 /// Transform a single field: `id = 1` → `id: 1`
 pub fn transform_single_literal_field(field: &str) -> String {
     transform_single_literal_field_with_clone(field, false)
-}
\ No newline at end of file
+}
@@ -0,0 +1,141 @@
+//! Input Limits
+//!
+//! Malformed input has to fail with one clear diagnostic, not a cascade of
+//! follow-up errors or a mode stack growing with every unclosed brace.
+//! Stage 1 and lowering check the source against three limits first:
+//!
+//! | Limit                              | Default  | Option                  |
+//! |------------------------------------|----------|-------------------------|
+//! | Nesting depth of `(`, `[` and `{`  | 256      | `--max-nesting <n>`     |
+//! | Line length (bytes)                | 64 KiB   | `--max-line-length <n>` |
+//! | File size (bytes)                  | 16 MiB   | `--max-file-size <n>`   |
+//!
+//! The first limit exceeded is reported as `RSPL029`, and nothing else is.
+//! Brackets inside string literals and `//` comments do not nest.
+//! Streaming lowering ([`crate::streaming`]) checks each piece, so the
+//! file size limit applies per piece there.
+
+use crate::error_msg::{structure_errors, RsplError, SourceLocation};
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 * 1024;
+pub const DEFAULT_MAX_FILE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Lines longer than this are not quoted in the diagnostic
+const MAX_QUOTED_LINE: usize = 160;
+
+/// Limits the source is checked against before Stage 1 and lowering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLimits {
+    /// Deepest nesting of `(`, `[` and `{`
+    pub max_nesting_depth: usize,
+    /// Longest line, in bytes
+    pub max_line_length: usize,
+    /// Largest source, in bytes
+    pub max_file_size: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        InputLimits {
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+        }
+    }
+}
+
+/// Which limit a source exceeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    NestingDepth,
+    LineLength,
+    FileSize,
+}
+
+impl LimitKind {
+    /// Command line option that raises the limit
+    pub fn option(self) -> &'static str {
+        match self {
+            LimitKind::NestingDepth => "--max-nesting",
+            LimitKind::LineLength => "--max-line-length",
+            LimitKind::FileSize => "--max-file-size",
+        }
+    }
+}
+
+/// Check `source` against `limits`: the first limit exceeded, as `RSPL029`
+pub fn check_input_limits(source: &str, limits: &InputLimits) -> Result<(), Vec<RsplError>> {
+    if source.len() > limits.max_file_size {
+        return Err(vec![structure_errors::input_limit_exceeded(LimitKind::FileSize, limits.max_file_size, source.len())
+            .at(SourceLocation::new("", 1, 1))]);
+    }
+
+    let mut depth = 0usize;
+    for (idx, line) in source.lines().enumerate() {
+        if line.len() > limits.max_line_length {
+            return Err(vec![structure_errors::input_limit_exceeded(LimitKind::LineLength, limits.max_line_length, line.len())
+                .at(location(idx, line, limits.max_line_length))]);
+        }
+        let code = strip_inline_comment(line);
+        for (pos, delimiter) in delimiter_positions(&code, &['(', '[', '{', ')', ']', '}']) {
+            if matches!(delimiter, '(' | '[' | '{') {
+                depth += 1;
+                if depth > limits.max_nesting_depth {
+                    return Err(vec![structure_errors::input_limit_exceeded(LimitKind::NestingDepth, limits.max_nesting_depth, depth)
+                        .at(location(idx, line, pos))]);
+                }
+            } else {
+                depth = depth.saturating_sub(1);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Location of byte `pos` of line `idx`, quoting the line when it is short
+fn location(idx: usize, line: &str, pos: usize) -> SourceLocation {
+    let column = line[..pos.min(line.len())].chars().count();
+    let location = SourceLocation::new("", idx + 1, column + 1);
+    if line.len() <= MAX_QUOTED_LINE {
+        location.with_source(line, column, 1)
+    } else {
+        location
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_msg::ErrorCode;
+
+    #[test]
+    fn test_within_limits() {
+        let source = "fn main() {\n    x = vec![(1, 2)]  // ((((\n    s = \"[[[[\"\n}\n";
+        let limits = InputLimits { max_nesting_depth: 3, ..InputLimits::default() };
+        assert!(check_input_limits(source, &limits).is_ok());
+    }
+
+    #[test]
+    fn test_nesting_depth_exceeded_once() {
+        let source = format!("fn main() {{\n{}", "{\n".repeat(10_000));
+        let errors = check_input_limits(&source, &InputLimits::default()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, ErrorCode::RSPL029);
+        assert_eq!(errors[0].location.line, DEFAULT_MAX_NESTING_DEPTH + 1);
+        assert!(errors[0].suggestion.as_deref().unwrap().contains("--max-nesting"));
+    }
+
+    #[test]
+    fn test_line_length_and_file_size() {
+        let limits = InputLimits { max_line_length: 10, max_file_size: 40, ..InputLimits::default() };
+        let errors = check_input_limits("fn f() {\n    x = \"a long string\"\n}", &limits).unwrap_err();
+        assert_eq!(errors[0].location.line, 2);
+        assert!(errors[0].title.contains("line of 23 bytes"), "{}", errors[0].title);
+
+        let errors = check_input_limits(&"x = 1\n".repeat(10), &limits).unwrap_err();
+        assert!(errors[0].title.contains("source of 60 bytes"), "{}", errors[0].title);
+    }
+}
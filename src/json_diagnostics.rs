@@ -0,0 +1,143 @@
+//! Machine-Readable Diagnostics (`--error-format=json`)
+//!
+//! Like rustc's `--error-format=json`, every diagnostic is written to stderr
+//! as one JSON object on its own line, so language servers and CI tools can
+//! read them without parsing the colored report:
+//!
+//! ```text
+//! {"level":"error","code":"RSPL300","category":"effect","message":"function `log` performs effect `io` but does not declare it","file":"main.rss","line":2,"column":5,"note":"..","suggestion":"..","labels":[]}
+//! ```
+//!
+//! Stage 1/2 diagnostics carry their `RSPL` code and category. Lowering bugs
+//! caught by the sanity gate use the code `RUSTSP_INTERNAL` and have no
+//! position. rustc errors are reported at their `.rss` line with rustc's own
+//! code (`E0308`) and the category `rust-backend`. Unknown fields are `null`.
+//! Lines that do not start with `{` (the run summary) are not diagnostics.
+
+use crate::error_msg::RsplError;
+use crate::lowered_ir::{json_opt, json_str};
+use crate::source_map::RustcError;
+
+/// How diagnostics are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// The colored report
+    #[default]
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "human" => Some(ErrorFormat::Human),
+            "json" => Some(ErrorFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// A position as `"file":..,"line":..,"column":..`; `line` 0 is unknown
+fn json_position(file: &str, line: usize, column: usize) -> String {
+    let number = |n: usize| if n == 0 { "null".to_string() } else { n.to_string() };
+    format!("\"file\":{},\"line\":{},\"column\":{}", json_str(file), number(line), number(column))
+}
+
+/// One RSPL diagnostic; `level` is `error` or `warning`
+pub fn error_to_json(error: &RsplError, level: &str) -> String {
+    let labels: Vec<String> = error.labels.iter()
+        .map(|(location, message)| format!(
+            "{{{},\"message\":{}}}",
+            json_position(&location.file, location.line, location.column), json_str(message)
+        ))
+        .collect();
+    format!(
+        "{{\"level\":\"{}\",\"code\":\"{}\",\"category\":\"{}\",\"message\":{},{},\"note\":{},\"suggestion\":{},\"labels\":[{}]}}",
+        level,
+        error.code.code_str(),
+        error.category(),
+        json_str(&error.title),
+        json_position(&error.location.file, error.location.line, error.location.column),
+        json_opt(error.explanation.as_deref()),
+        json_opt(error.suggestion.as_deref()),
+        labels.join(",")
+    )
+}
+
+/// A rustc error already mapped to its `.rss` line
+pub fn rustc_error_to_json(error: &RustcError) -> String {
+    let joined = |lines: &[String]| (!lines.is_empty()).then(|| lines.join("\n"));
+    format!(
+        "{{\"level\":\"error\",\"code\":{},\"category\":\"rust-backend\",\"message\":{},{},\"note\":{},\"suggestion\":{},\"labels\":[]}}",
+        json_opt(error.error_code.as_deref()),
+        json_str(&error.message),
+        json_position(&error.file, error.line, error.column),
+        json_opt(joined(&error.notes).as_deref()),
+        json_opt(joined(&error.help).as_deref())
+    )
+}
+
+/// Invalid Rust from the lowering (a compiler bug), found by the sanity gate
+pub fn internal_error_to_json(file: &str, problem: &str) -> String {
+    format!(
+        "{{\"level\":\"error\",\"code\":\"RUSTSP_INTERNAL\",\"category\":\"internal\",\"message\":{},{},\"note\":{},\"suggestion\":{},\"labels\":[]}}",
+        json_str("invalid Rust code generated"),
+        json_position(file, 0, 0),
+        json_str(problem),
+        json_str("this is a compiler bug: please report it with your source code")
+    )
+}
+
+/// `errors` in `format`: the colored report, or one JSON line each
+pub fn render_errors(errors: &[RsplError], format: ErrorFormat) -> String {
+    match format {
+        ErrorFormat::Human => crate::anti_fail_logic::format_logic_errors(errors),
+        ErrorFormat::Json => errors.iter().map(|e| error_to_json(e, "error")).collect::<Vec<_>>().join("\n"),
+    }
+}
+
+/// Stage 1 warnings in `format`, ending in a newline when not empty
+pub fn render_warnings(warnings: &[RsplError], format: ErrorFormat) -> String {
+    match format {
+        ErrorFormat::Human => crate::anti_fail_logic::format_logic_warnings(warnings),
+        ErrorFormat::Json => warnings.iter().map(|e| format!("{}\n", error_to_json(e, "warning"))).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_msg::{effect_errors, SourceLocation};
+
+    #[test]
+    fn test_error_to_json() {
+        let error = effect_errors::undeclared_effect("log", "io")
+            .at(SourceLocation::new("main.rss", 2, 5))
+            .label(SourceLocation::new("main.rss", 1, 1), "declared \"here\"");
+        let json = error_to_json(&error, "error");
+        assert!(json.starts_with("{\"level\":\"error\",\"code\":\"RSPL300\",\"category\":\"effect\",\"message\":\"function `log` performs effect `io` but does not declare it\",\"file\":\"main.rss\",\"line\":2,\"column\":5,\"note\":\"Effect-01 VIOLATION: Undeclared Effect\\n"), "{}", json);
+        assert!(json.contains("\"suggestion\":\"add effect declaration"), "{}", json);
+        assert!(json.ends_with("\"labels\":[{\"file\":\"main.rss\",\"line\":1,\"column\":1,\"message\":\"declared \\\"here\\\"\"}]}"), "{}", json);
+        assert!(!json.contains('\n'));
+    }
+
+    #[test]
+    fn test_rustc_and_internal_errors_to_json() {
+        let error = RustcError {
+            file: "main.rss".to_string(),
+            line: 25,
+            column: 16,
+            message: "mismatched types".to_string(),
+            error_code: Some("E0308".to_string()),
+            notes: Vec::new(),
+            help: vec!["try `.to_string()`".to_string()],
+        };
+        assert_eq!(
+            rustc_error_to_json(&error),
+            "{\"level\":\"error\",\"code\":\"E0308\",\"category\":\"rust-backend\",\"message\":\"mismatched types\",\"file\":\"main.rss\",\"line\":25,\"column\":16,\"note\":null,\"suggestion\":\"try `.to_string()`\",\"labels\":[]}"
+        );
+        assert!(internal_error_to_json("main.rss", "unbalanced braces")
+            .contains("\"code\":\"RUSTSP_INTERNAL\",\"category\":\"internal\",\"message\":\"invalid Rust code generated\",\"file\":\"main.rss\",\"line\":null,\"column\":null,\"note\":\"unbalanced braces\""));
+    }
+}
@@ -20,6 +20,61 @@ pub mod semantic_check;
 pub mod anti_fail_logic;
 pub mod rust_sanity;
 pub mod hex_normalizer;
+pub mod untyped_report;
+pub mod derive_report;
+pub mod macro_shadowing;
+pub mod entry;
+pub mod sha256;
+pub mod rustc_cache;
+pub mod doctor;
+pub mod verbatim;
+pub mod dialect;
+pub mod batch_analysis;
+pub mod ident;
+pub mod rust_project;
+pub mod effect_detector;
+pub mod match_arms;
+pub mod variant_arity;
+pub mod method_sugar;
+pub mod map_literals;
+pub mod generic_types;
+pub mod statement_split;
+pub mod diagnostic_groups;
+pub mod concurrency;
+pub mod replay;
+pub mod numeric_mixing;
+pub mod effect_db;
+pub mod callgraph;
+pub mod registry_check;
+pub mod build_report;
+pub mod migrate;
+pub mod input_limits;
+pub mod empty_literals;
+pub mod bool_match;
+pub mod contracts;
+pub mod memo;
+pub mod closures;
+pub mod for_loops;
+pub mod constants;
+pub mod fixes;
+pub mod modules;
+pub mod edition;
+pub mod exit_status;
+pub mod json_diagnostics;
+pub mod loop_exits;
+pub mod resources;
+pub mod rename;
+pub mod symbol_index;
+pub mod streaming;
+pub mod lowering_hook;
+pub mod lowering_rules;
+pub mod compiler;
+pub mod output_target;
+pub mod effects_diff;
+pub mod effect_badges;
+pub mod target_cfg;
+pub mod cargo_workspace;
+pub mod fallible;
 
 // ============================================================================
 // IR-BASED MODULES
@@ -30,6 +85,8 @@ pub mod eir;
 pub mod parser;
 pub mod type_env;
 pub mod source_map;
+pub mod lowered_ir;
+pub mod cfg;
 
 // ============================================================================
 // EXISTING MODULAR COMPONENTS
@@ -42,9 +99,12 @@ pub mod transform_array;
 pub mod clone_helpers;
 pub mod postprocess;
 pub mod first_pass;
+pub mod program_index;
 pub mod parser_state;
 pub mod inline_literal_transform;
 pub mod postprocess_output;
+pub mod inline_fns;
+pub mod string_append;
 pub mod tests;
 
 // ============================================================================
@@ -91,7 +151,7 @@ pub mod transpile_main;
 // ============================================================================
 pub use ast::{Span, Spanned, EffectDecl};
 pub use hir::{BindingId, BindingInfo, ScopeResolver, HirModule};
-pub use eir::{Effect, EffectSet, EffectContext, EffectInference};
+pub use eir::{Effect, EffectSet, EffectAlgebra, EffectItem, EffectContext, EffectInference};
 pub use parser::{Lexer, FunctionParser, extract_function_signatures};
 
 pub use type_env::{
@@ -106,4 +166,11 @@ pub use type_env::{
 /// Main entry point for RustS+ to Rust transpilation
 pub fn parse_rusts(source: &str) -> String {
     transpile_main::parse_rusts(source)
-}
\ No newline at end of file
+}
+
+pub use transpile_main::{parse_rusts_with_options, lower_rusts, lower_rusts_mapped, LoweringOptions};
+pub use streaming::{lower_rusts_streaming, StreamingError};
+pub use lowering_hook::{LineCtx, LoweringHook, Rewrite};
+pub use lowering_rules::{LoweringRule, RuleSet};
+pub use compiler::{Compiler, CompilerBuilder};
+pub use output_target::{CompileInMemory, CompiledProgram, OutputError, OutputTarget, ReturnString, WriteToFile};
@@ -0,0 +1,412 @@
+//! Loops Without an Exit (RSPL003)
+//!
+//! A `loop { }` whose body can never leave it is almost always a bug: a
+//! missing `break` after the work is done, or a `break` that only leaves an
+//! inner loop. Stage 1 warns about such loops:
+//!
+//! ```text
+//! fn drain(queue Queue) effects(io) {
+//!     loop {                  // RSPL003: `loop` in `drain` has no exit
+//!         job = queue.pop()
+//!         for step in job.steps {
+//!             if step.done { break }    // leaves the `for` only
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! What counts as an exit:
+//! - `break` leaving this loop: an unlabelled `break` whose innermost loop
+//!   is this one, or `break 'label` naming this loop or one around it
+//! - `return` and the `?` operator
+//! - calls that end the program: `exit(..)`, `process::exit(..)`,
+//!   `panic(..)`, `unreachable(..)`, `todo(..)`, `unimplemented(..)`
+//!
+//! `return`, `?` and `break` inside a closure body leave the closure, not
+//! the loop around it. `while` and `for` loops end by themselves and are
+//! never reported.
+//!
+//! Intended endless loops (servers, event pumps) are silenced with an
+//! `// allow(infinite-loop)` comment on the `loop` line, on the line above
+//! it, or on (or above) the signature of the enclosing function.
+//!
+//! This is the line-based check Stage 1 runs; [`crate::cfg`] answers the
+//! same question on a HIR control-flow graph.
+
+use crate::helpers::strip_inline_comment;
+
+/// Marker that silences RSPL003
+pub const ALLOW_INFINITE_LOOP: &str = "allow(infinite-loop)";
+
+/// Calls that never return
+const DIVERGING_CALLS: &[&str] = &["exit", "panic", "unreachable", "todo", "unimplemented"];
+
+/// A `loop` whose body has no exit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoopWithoutExit {
+    /// 1-based line of the `loop` header
+    pub line: usize,
+    /// Enclosing function, if any
+    pub function: Option<String>,
+}
+
+#[derive(Debug)]
+enum FrameKind {
+    /// `loop { }`: the loops this check is about
+    Loop { line: usize, exits: bool, suppressed: bool },
+    /// `while` / `for`
+    OtherLoop,
+    Function { name: String, suppressed: bool },
+    Closure,
+}
+
+#[derive(Debug)]
+struct Frame {
+    kind: FrameKind,
+    label: Option<String>,
+    /// Brace depth of the body lines
+    body_depth: usize,
+}
+
+impl Frame {
+    fn is_boundary(&self) -> bool {
+        matches!(self.kind, FrameKind::Function { .. } | FrameKind::Closure)
+    }
+}
+
+/// Find every `loop` in `source` that has no exit and is not allowed
+pub fn find_loops_without_exit(source: &str) -> Vec<LoopWithoutExit> {
+    let raw_lines: Vec<&str> = source.lines().collect();
+    let mut found = Vec::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut depth = 0usize;
+
+    for (idx, raw) in raw_lines.iter().enumerate() {
+        let code = mask_literals(&strip_inline_comment(raw));
+        let trimmed = code.trim();
+        let allowed = |i: usize| {
+            raw.contains(ALLOW_INFINITE_LOOP)
+                || (i > 0 && raw_lines[i - 1].trim_start().starts_with("//") && raw_lines[i - 1].contains(ALLOW_INFINITE_LOOP))
+        };
+
+        if let Some(frame) = read_header(trimmed, idx + 1, depth + 1, allowed(idx)) {
+            frames.push(frame);
+        }
+        for exit in find_exits(trimmed) {
+            match exit {
+                Exit::Break(label) => mark_break(&mut frames, label.as_deref()),
+                Exit::Return => mark_return(&mut frames),
+            }
+        }
+
+        let opens = trimmed.matches('{').count();
+        let closes = trimmed.matches('}').count();
+        depth = (depth + opens).saturating_sub(closes);
+        while frames.last().is_some_and(|f| depth < f.body_depth) {
+            let frame = frames.pop().unwrap();
+            if let FrameKind::Loop { line, exits: false, suppressed: false } = frame.kind {
+                if !in_allowed_function(&frames) {
+                    found.push(LoopWithoutExit { line, function: enclosing_function(&frames) });
+                }
+            }
+        }
+    }
+
+    found
+}
+
+/// A frame opened by this line, if it starts a loop, function or closure
+fn read_header(trimmed: &str, line: usize, body_depth: usize, allowed: bool) -> Option<Frame> {
+    if !trimmed.ends_with('{') && !trimmed.contains("{ ") {
+        return None;
+    }
+    let (label, rest) = split_label(trimmed);
+    let kind = if is_keyword_at(rest, "loop") || trimmed.contains("= loop {") {
+        FrameKind::Loop { line, exits: false, suppressed: allowed }
+    } else if is_keyword_at(rest, "while") || is_keyword_at(rest, "for") {
+        FrameKind::OtherLoop
+    } else if let Some(name) = function_name(trimmed) {
+        FrameKind::Function { name, suppressed: allowed }
+    } else if is_closure_header(trimmed) {
+        FrameKind::Closure
+    } else {
+        return None;
+    };
+    Some(Frame { kind, label, body_depth })
+}
+
+/// `'outer: loop {` → (`Some("outer")`, `loop {`)
+fn split_label(trimmed: &str) -> (Option<String>, &str) {
+    if let Some(rest) = trimmed.strip_prefix('\'') {
+        if let Some((label, after)) = rest.split_once(':') {
+            if !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return (Some(label.to_string()), after.trim_start());
+            }
+        }
+    }
+    (None, trimmed)
+}
+
+fn is_keyword_at(s: &str, keyword: &str) -> bool {
+    s.strip_prefix(keyword)
+        .is_some_and(|rest| rest.starts_with(' ') || rest.starts_with('{'))
+}
+
+pub(crate) fn function_name(trimmed: &str) -> Option<String> {
+    let fn_pos = if trimmed.starts_with("fn ") {
+        0
+    } else if trimmed.starts_with("pub") || trimmed.starts_with("async ") {
+        trimmed.find(" fn ")? + 1
+    } else {
+        return None;
+    };
+    let name: String = trimmed[fn_pos + 3..].trim_start()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// `xs.iter().for_each(|x| {`, `move || {`
+fn is_closure_header(trimmed: &str) -> bool {
+    let Some(open) = trimmed.rfind('{') else { return false };
+    // `|x| -> T {` has its return type between the `|` and the body
+    let before = trimmed[..open].split(" -> ").next().unwrap_or_default();
+    before.trim_end().ends_with('|')
+}
+
+enum Exit {
+    Break(Option<String>),
+    /// `return`, `?` or a diverging call
+    Return,
+}
+
+/// Exits on a line with literals masked
+fn find_exits(code: &str) -> Vec<Exit> {
+    let mut exits = Vec::new();
+    let chars: Vec<char> = code.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let after: String = chars[i..].iter().collect();
+            let after = after.trim_start();
+            let preceded_by_dot = start > 0 && chars[start - 1] == '.';
+            match word.as_str() {
+                _ if preceded_by_dot => {}
+                "break" => {
+                    let label = after.strip_prefix('\'').map(|rest| {
+                        rest.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect()
+                    });
+                    exits.push(Exit::Break(label));
+                }
+                "return" => exits.push(Exit::Return),
+                w if DIVERGING_CALLS.contains(&w) && (after.starts_with('(') || after.starts_with("!(")) => {
+                    exits.push(Exit::Return);
+                }
+                _ => {}
+            }
+            continue;
+        }
+        // `?` operator: attached to the expression before it. The
+        // conditional sugar `a ? b : c` has spaces on both sides.
+        if c == '?' && i > 0 && !chars[i - 1].is_whitespace() {
+            exits.push(Exit::Return);
+        }
+        i += 1;
+    }
+    exits
+}
+
+/// `break` leaves the innermost loop, or every loop up to the labelled one
+fn mark_break(frames: &mut [Frame], label: Option<&str>) {
+    for frame in frames.iter_mut().rev() {
+        if frame.is_boundary() {
+            return;
+        }
+        let is_target = match label {
+            Some(label) => frame.label.as_deref() == Some(label),
+            None => true,
+        };
+        if let FrameKind::Loop { exits, .. } = &mut frame.kind {
+            *exits = true;
+        }
+        if is_target {
+            return;
+        }
+    }
+}
+
+/// `return` leaves every loop up to the enclosing function or closure
+fn mark_return(frames: &mut [Frame]) {
+    for frame in frames.iter_mut().rev() {
+        if frame.is_boundary() {
+            return;
+        }
+        if let FrameKind::Loop { exits, .. } = &mut frame.kind {
+            *exits = true;
+        }
+    }
+}
+
+fn enclosing_function(frames: &[Frame]) -> Option<String> {
+    frames.iter().rev().find_map(|f| match &f.kind {
+        FrameKind::Function { name, .. } => Some(name.clone()),
+        _ => None,
+    })
+}
+
+fn in_allowed_function(frames: &[Frame]) -> bool {
+    frames.iter().rev()
+        .find(|f| matches!(f.kind, FrameKind::Function { .. }))
+        .is_some_and(|f| matches!(f.kind, FrameKind::Function { suppressed: true, .. }))
+}
+
+/// Blank out the contents of string and char literals so keywords and
+/// braces inside them are not seen. Lifetimes and loop labels are kept.
+pub(crate) fn mask_literals(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                out.push('"');
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' {
+                        out.push(' ');
+                        i += 1;
+                    }
+                    if i < chars.len() {
+                        out.push(' ');
+                        i += 1;
+                    }
+                }
+                if i < chars.len() {
+                    out.push('"');
+                    i += 1;
+                }
+            }
+            // `'\''`, `'\n'`: the closing quote comes after the escape
+            '\'' if chars.get(i + 1) == Some(&'\\') => {
+                let end = chars.iter().skip(i + 3).position(|&c| c == '\'').map_or(chars.len(), |p| i + 3 + p);
+                out.push_str("' '");
+                i = end + 1;
+            }
+            '\'' if chars.get(i + 2) == Some(&'\'') => {
+                out.push_str("' '");
+                i += 3;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(source: &str) -> Vec<usize> {
+        find_loops_without_exit(source).into_iter().map(|l| l.line).collect()
+    }
+
+    #[test]
+    fn test_loop_without_exit() {
+        let source = r#"fn drain(queue Queue) effects(io) {
+    loop {
+        job = queue.pop()
+        for step in job.steps {
+            if step.done { break }
+        }
+    }
+}"#;
+        let found = find_loops_without_exit(source);
+        assert_eq!(found, vec![LoopWithoutExit { line: 2, function: Some("drain".to_string()) }]);
+    }
+
+    #[test]
+    fn test_loops_with_exits() {
+        let source = r#"fn run(items Vec[i32]) Result[i32, String] {
+    loop {
+        if items.len() > 3 { break }
+    }
+    loop {
+        n = parse(items)?
+    }
+    'outer: loop {
+        loop {
+            break 'outer
+        }
+    }
+    loop {
+        match next() {
+            None { return Ok(0) }
+            Some(x) { x }
+        }
+    }
+    loop { panic("stop") }
+    loop {
+        std::process::exit(1)
+    }
+    while true {
+        tick()
+    }
+    Ok(1)
+}"#;
+        assert!(lines(source).is_empty(), "{:?}", find_loops_without_exit(source));
+    }
+
+    #[test]
+    fn test_exits_that_do_not_leave_the_loop() {
+        let source = r#"fn f() {
+    loop {
+        items.iter().for_each(|x| {
+            return
+        })
+        println("break return {}", "?")   // break
+    }
+    x = loop {
+        obj.break_point()
+        ok = a ? b : c
+    }
+}"#;
+        assert_eq!(lines(source), vec![2, 8]);
+    }
+
+    #[test]
+    fn test_allow_infinite_loop() {
+        let source = r#"fn serve() effects(io) {
+    // allow(infinite-loop)
+    loop {
+        accept()
+    }
+    loop {   // allow(infinite-loop)
+        accept()
+    }
+}
+
+// allow(infinite-loop)
+fn pump() effects(io) {
+    loop {
+        poll()
+    }
+}
+
+fn spin() {
+    loop {
+        poll()
+    }
+}"#;
+        assert_eq!(lines(source), vec![19]);
+    }
+}
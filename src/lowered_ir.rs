@@ -0,0 +1,565 @@
+//! Lowered Program IR - Stable Serialization for External Backends
+//!
+//! `rustsp main.rss --emit-ir -o main.ir.json` writes the lowered program
+//! (after Stage 2, before Rust text is handed to rustc) as a versioned JSON
+//! document; `rustsp main.ir.json --from-ir` reads it back and continues
+//! with Stage 3. Alternative backends can consume or rewrite the document
+//! instead of scraping generated Rust.
+//!
+//! ## Format (version 1)
+//!
+//! ```text
+//! {
+//!   "format": "rustsp-ir",
+//!   "version": 1,
+//!   "source": "main.rss",
+//!   "items": [
+//!     {
+//!       "kind": "function",
+//!       "name": "deposit",
+//!       "pub": false,
+//!       "generics": null,
+//!       "params": [{"name": "acc", "type": "Account"}, {"name": "amount", "type": "i64"}],
+//!       "return_type": "Account",
+//!       "effects": ["write(acc)"],
+//!       "source_line": 7,
+//!       "body": ["    Account { balance: acc.balance + amount, ..acc }"]
+//!     },
+//!     { "kind": "rust", "text": "#[derive(Debug, Clone)]\nstruct Account {\n    balance: i64,\n}" }
+//!   ]
+//! }
+//! ```
+//!
+//! - Items appear in program order; emitting them in order reproduces the
+//!   lowered Rust exactly.
+//! - `function` items are top-level functions whose header could be
+//!   described structurally. `effects` are the declared RustS+ effects
+//!   (`io`, `alloc`, `panic`, `read(p)`, `write(p)`) and `source_line` the
+//!   1-based `.rss` line of the signature (`null` when unknown, e.g. the
+//!   generated test harness). `body` holds the lowered statements, one
+//!   string per line.
+//! - Everything else (structs, enums, impls, `use`, attributes, comments,
+//!   generic or `where`-constrained functions the header model cannot
+//!   express) is a `rust` item carrying its text verbatim.
+//! - Readers must reject documents with a different `format` or a newer
+//!   `version`. New optional keys may be added without a version bump.
+
+use std::collections::HashMap;
+
+use crate::anti_fail_logic::analyze_functions;
+use crate::function::{find_matching_paren, split_by_comma};
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::rust_project::escape_json;
+
+/// Value of the `format` key
+pub const IR_FORMAT: &str = "rustsp-ir";
+/// Newest format version this build reads and writes
+pub const IR_VERSION: u64 = 1;
+
+/// A function parameter in Rust form (`name: type`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrParam {
+    pub name: String,
+    pub param_type: String,
+}
+
+/// A top-level function with a structured header
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrFunction {
+    pub name: String,
+    pub is_pub: bool,
+    pub generics: Option<String>,
+    pub params: Vec<IrParam>,
+    pub return_type: Option<String>,
+    /// Declared RustS+ effects, e.g. `io`, `write(acc)`
+    pub effects: Vec<String>,
+    /// 1-based line of the signature in the `.rss` source
+    pub source_line: Option<usize>,
+    /// Lowered body lines, without the enclosing braces
+    pub body: Vec<String>,
+}
+
+/// One program item
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrItem {
+    Function(IrFunction),
+    /// Any other item, kept as Rust text
+    Rust(String),
+}
+
+/// A lowered program
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrProgram {
+    /// Name of the `.rss` file the program was lowered from
+    pub source: String,
+    pub items: Vec<IrItem>,
+}
+
+//=============================================================================
+// BUILDING
+//=============================================================================
+
+impl IrFunction {
+    /// Rust header line, `fn name(a: T) -> R {`
+    fn header(&self) -> String {
+        let params: Vec<String> = self.params.iter()
+            .map(|p| format!("{}: {}", p.name, p.param_type))
+            .collect();
+        format!(
+            "{}fn {}{}({}){} {{",
+            if self.is_pub { "pub " } else { "" },
+            self.name,
+            self.generics.as_ref().map(|g| format!("<{}>", g)).unwrap_or_default(),
+            params.join(", "),
+            self.return_type.as_ref().map(|r| format!(" -> {}", r)).unwrap_or_default(),
+        )
+    }
+}
+
+/// Parse a lowered Rust function header. Only returns `Some` when the
+/// structured form renders back to exactly the same line.
+fn parse_header(line: &str) -> Option<IrFunction> {
+    let (is_pub, rest) = match line.strip_prefix("pub fn ") {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix("fn ")?),
+    };
+    let name_end = rest.find(['(', '<'])?;
+    let name = rest[..name_end].to_string();
+    let mut rest = &rest[name_end..];
+
+    let generics = if rest.starts_with('<') {
+        let mut depth = 0;
+        let mut prev = ' ';
+        let close = rest.char_indices().find(|&(_, c)| {
+            match c {
+                '<' => depth += 1,
+                '>' if prev != '-' => depth -= 1,
+                _ => {}
+            }
+            prev = c;
+            depth == 0
+        })?.0;
+        let generics = rest[1..close].to_string();
+        rest = &rest[close + 1..];
+        Some(generics)
+    } else {
+        None
+    };
+
+    let paren_end = find_matching_paren(rest, 0)?;
+    let mut params = Vec::new();
+    for part in split_by_comma(&rest[1..paren_end]) {
+        let (name, ty) = part.split_once(':')?;
+        params.push(IrParam { name: name.trim().to_string(), param_type: ty.trim().to_string() });
+    }
+
+    let after = rest[paren_end + 1..].trim().strip_suffix('{')?.trim();
+    let return_type = match after.strip_prefix("->") {
+        Some(ret) if !ret.contains(" where ") => Some(ret.trim().to_string()),
+        None if after.is_empty() => None,
+        _ => return None,
+    };
+
+    let function = IrFunction {
+        name,
+        is_pub,
+        generics,
+        params,
+        return_type,
+        effects: Vec::new(),
+        source_line: None,
+        body: Vec::new(),
+    };
+    (function.header() == line).then_some(function)
+}
+
+/// Split lowered Rust into items and attach effect contracts from the
+/// RustS+ source
+pub fn build_ir(source: &str, rust_code: &str, file_name: &str) -> IrProgram {
+    let functions = analyze_functions(source, file_name);
+    let lines: Vec<&str> = rust_code.lines().collect();
+    let mut items = Vec::new();
+    let mut pending: Vec<&str> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        // Extent of the top-level item starting here
+        let mut depth: i64 = 0;
+        let mut end = i;
+        for (j, line) in lines.iter().enumerate().skip(i) {
+            let (opens, closes) = count_braces_outside_strings(line);
+            depth += opens as i64 - closes as i64;
+            end = j;
+            if depth <= 0 {
+                break;
+            }
+        }
+
+        let header = (end > i && lines[end] == "}").then(|| parse_header(lines[i])).flatten();
+        match header {
+            Some(mut function) => {
+                if !pending.is_empty() {
+                    items.push(IrItem::Rust(pending.join("\n")));
+                    pending.clear();
+                }
+                if let Some(info) = functions.get(&function.name) {
+                    function.effects = info.declared_effects.effects.iter().map(|e| e.display()).collect();
+                    function.source_line = Some(info.line_number);
+                }
+                function.body = lines[i + 1..end].iter().map(|l| l.to_string()).collect();
+                items.push(IrItem::Function(function));
+            }
+            None => pending.extend(&lines[i..=end]),
+        }
+        i = end + 1;
+    }
+    if !pending.is_empty() {
+        items.push(IrItem::Rust(pending.join("\n")));
+    }
+
+    IrProgram { source: file_name.to_string(), items }
+}
+
+//=============================================================================
+// EMISSION
+//=============================================================================
+
+impl IrProgram {
+    /// Render the program as Rust source
+    pub fn to_rust(&self) -> String {
+        let rendered: Vec<String> = self.items.iter().map(|item| match item {
+            IrItem::Function(f) => {
+                let mut lines = vec![f.header()];
+                lines.extend(f.body.iter().cloned());
+                lines.push("}".to_string());
+                lines.join("\n")
+            }
+            IrItem::Rust(text) => text.clone(),
+        }).collect();
+        rendered.join("\n")
+    }
+
+    /// Serialize to the versioned JSON format
+    pub fn to_json(&self) -> String {
+        let items: Vec<String> = self.items.iter().map(|item| match item {
+            IrItem::Function(f) => {
+                let params: Vec<String> = f.params.iter()
+                    .map(|p| format!("{{\"name\": {}, \"type\": {}}}", json_str(&p.name), json_str(&p.param_type)))
+                    .collect();
+                let effects: Vec<String> = f.effects.iter().map(|e| json_str(e)).collect();
+                let body: Vec<String> = f.body.iter().map(|l| format!("        {}", json_str(l))).collect();
+                format!(
+                    "    {{\n      \"kind\": \"function\",\n      \"name\": {},\n      \"pub\": {},\n      \"generics\": {},\n      \"params\": [{}],\n      \"return_type\": {},\n      \"effects\": [{}],\n      \"source_line\": {},\n      \"body\": [\n{}\n      ]\n    }}",
+                    json_str(&f.name), f.is_pub, json_opt(f.generics.as_deref()), params.join(", "),
+                    json_opt(f.return_type.as_deref()), effects.join(", "),
+                    f.source_line.map(|l| l.to_string()).unwrap_or_else(|| "null".to_string()),
+                    body.join(",\n")
+                )
+            }
+            IrItem::Rust(text) => format!("    {{\"kind\": \"rust\", \"text\": {}}}", json_str(text)),
+        }).collect();
+        format!(
+            "{{\n  \"format\": \"{}\",\n  \"version\": {},\n  \"source\": {},\n  \"items\": [\n{}\n  ]\n}}\n",
+            IR_FORMAT, IR_VERSION, json_str(&self.source), items.join(",\n")
+        )
+    }
+
+    /// Read a document written by [`IrProgram::to_json`] (or an external tool)
+    pub fn from_json(json: &str) -> Result<IrProgram, String> {
+        let doc = parse_json(json)?;
+        let format = doc.get("format").and_then(Json::as_str).ok_or("missing \"format\"")?;
+        if format != IR_FORMAT {
+            return Err(format!("unknown IR format \"{}\" (expected \"{}\")", format, IR_FORMAT));
+        }
+        let version = doc.get("version").and_then(Json::as_u64).ok_or("missing \"version\"")?;
+        if version > IR_VERSION {
+            return Err(format!("IR version {} is newer than this compiler supports ({})", version, IR_VERSION));
+        }
+        let source = doc.get("source").and_then(Json::as_str).unwrap_or("").to_string();
+
+        let mut items = Vec::new();
+        for (idx, item) in doc.get("items").and_then(Json::as_array).ok_or("missing \"items\"")?.iter().enumerate() {
+            let kind = item.get("kind").and_then(Json::as_str);
+            items.push(match kind {
+                Some("rust") => IrItem::Rust(
+                    item.get("text").and_then(Json::as_str).ok_or(format!("item {}: missing \"text\"", idx))?.to_string(),
+                ),
+                Some("function") => IrItem::Function(function_from_json(item).map_err(|e| format!("item {}: {}", idx, e))?),
+                other => return Err(format!("item {}: unknown kind {:?}", idx, other.unwrap_or("<missing>"))),
+            });
+        }
+        Ok(IrProgram { source, items })
+    }
+}
+
+fn function_from_json(item: &Json) -> Result<IrFunction, String> {
+    let string = |key: &str| item.get(key).and_then(Json::as_str).map(str::to_string);
+    let strings = |key: &str| -> Result<Vec<String>, String> {
+        item.get(key).and_then(Json::as_array).unwrap_or(&[]).iter()
+            .map(|v| v.as_str().map(str::to_string).ok_or(format!("\"{}\" must hold strings", key)))
+            .collect()
+    };
+    let mut params = Vec::new();
+    for param in item.get("params").and_then(Json::as_array).unwrap_or(&[]) {
+        let name = param.get("name").and_then(Json::as_str).ok_or("parameter without \"name\"")?;
+        let param_type = param.get("type").and_then(Json::as_str).ok_or("parameter without \"type\"")?;
+        params.push(IrParam { name: name.to_string(), param_type: param_type.to_string() });
+    }
+    Ok(IrFunction {
+        name: string("name").ok_or("missing \"name\"")?,
+        is_pub: matches!(item.get("pub"), Some(Json::Bool(true))),
+        generics: string("generics"),
+        params,
+        return_type: string("return_type"),
+        effects: strings("effects")?,
+        source_line: item.get("source_line").and_then(Json::as_u64).map(|l| l as usize),
+        body: strings("body")?,
+    })
+}
+
+pub(crate) fn json_str(s: &str) -> String {
+    format!("\"{}\"", escape_json(s))
+}
+
+pub(crate) fn json_opt(s: Option<&str>) -> String {
+    s.map(json_str).unwrap_or_else(|| "null".to_string())
+}
+
+//=============================================================================
+// MINIMAL JSON READER
+//=============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+impl Json {
+    pub(crate) fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a complete JSON document (also used by [`crate::symbol_index`])
+pub(crate) fn parse_json(json: &str) -> Result<Json, String> {
+    JsonParser { src: json.as_bytes(), pos: 0 }.parse_document()
+}
+
+struct JsonParser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn parse_document(mut self) -> Result<Json, String> {
+        let value = self.value()?;
+        self.skip_ws();
+        if self.pos != self.src.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, what: &str) -> String {
+        format!("invalid JSON at byte {}: {}", self.pos, what)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.src.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        self.skip_ws();
+        if self.src.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.src[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected token"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        match self.src.get(self.pos) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut map = HashMap::new();
+        self.skip_ws();
+        if self.src.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(map));
+        }
+        loop {
+            self.skip_ws();
+            let key = self.string()?;
+            self.expect(b':')?;
+            map.insert(key, self.value()?);
+            self.skip_ws();
+            match self.src.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(map));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.src.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_ws();
+            match self.src.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.src.get(self.pos).is_some_and(|b| matches!(b, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.src[start..self.pos]).ok()
+            .and_then(|s| s.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.src.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected string"));
+        }
+        self.pos += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&b) = self.src.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.src.get(self.pos).ok_or_else(|| self.error("unterminated escape"))?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'u' => {
+                            let hex = self.src.get(self.pos..self.pos + 4)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .ok_or_else(|| self.error("invalid \\u escape"))?;
+                            self.pos += 4;
+                            let c = char::from_u32(hex).unwrap_or('\u{fffd}');
+                            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_header_round_trip() {
+        let f = parse_header("pub fn add(a: i32, b: Vec<(i32, String)>) -> i32 {").unwrap();
+        assert!(f.is_pub);
+        assert_eq!(f.params[1].param_type, "Vec<(i32, String)>");
+        assert_eq!(f.return_type.as_deref(), Some("i32"));
+        assert!(parse_header("fn apply<F: Fn(i32) -> i32>(f: F) {").is_some());
+        // Not expressible structurally - stays a Rust item
+        assert!(parse_header("fn first<T>(v: &[T]) -> &T where T: Clone {").is_none());
+        assert!(parse_header("fn one() -> i32 { 1 }").is_none());
+    }
+
+    #[test]
+    fn test_ir_json_round_trip() {
+        let source = "fn deposit(acc i64, amount i64) effects(io) i64 {\n    println(\"{}\", amount)\n    acc + amount\n}\n";
+        let rust = "// header \"quoted\"\nfn deposit(acc: i64, amount: i64) -> i64 {\n    println!(\"{}\", amount);\n    acc + amount\n}\n\nstruct S {\n    x: i32,\n}";
+        let ir = build_ir(source, rust, "bank.rss");
+        let IrItem::Function(f) = &ir.items[1] else { panic!("expected function item: {:?}", ir.items) };
+        assert_eq!(f.effects, vec!["io"]);
+        assert_eq!(f.source_line, Some(1));
+        assert_eq!(ir.to_rust(), rust);
+
+        let decoded = IrProgram::from_json(&ir.to_json()).unwrap();
+        assert_eq!(decoded, ir);
+        assert!(IrProgram::from_json("{\"format\": \"rustsp-ir\", \"version\": 99, \"items\": []}").is_err());
+    }
+}
@@ -14,6 +14,8 @@ use crate::modes::{ArrayModeStack, LiteralModeStack, LiteralKind};
 use crate::transform_array::transform_array_element;
 use crate::detection::{detect_bare_struct_literal, detect_bare_enum_literal};
 use crate::struct_def::StructRegistry;
+use crate::lowering::depth_tracking_lowering::{count_brackets_outside_strings, delimiter_positions};
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// Result of processing a line in array mode
 pub enum ArrayModeResult {
@@ -26,31 +28,51 @@ pub enum ArrayModeResult {
 }
 
 /// Process array closing bracket
+///
+/// One line may close several nested levels (`4]]`): every entry whose
+/// depth is left is exited, innermost first. Rows close as elements (`],`).
 fn process_array_close(
     clean_line: &str,
     leading_ws: &str,
     bracket_depth: usize,
     array_mode: &mut ArrayModeStack,
 ) -> Option<String> {
-    if !array_mode.should_exit(bracket_depth) {
+    let mut exited = Vec::new();
+    while array_mode.should_exit(bracket_depth) {
+        exited.push(array_mode.exit()?);
+    }
+    if exited.is_empty() {
         return None;
     }
     
-    if let Some(entry) = array_mode.exit() {
-        let transformed = transform_array_element(clean_line);
-        let suffix = if entry.is_assignment { ";" } else { "" };
-        
-        let close_line = if transformed.trim() == "]" {
-            format!("{}]{}", leading_ws, suffix)
+    // Strip one `]` per exited level from the end of the line
+    let transformed = transform_array_element(clean_line);
+    let mut content = transformed.trim().trim_end_matches(';');
+    for entry in &exited {
+        content = content.trim_end_matches(|c: char| c == ',' || c.is_whitespace());
+        for _ in 0..entry.call_parens {
+            content = content.strip_suffix(')').unwrap_or(content);
+        }
+        content = content.strip_suffix(']').unwrap_or(content);
+    }
+    let content = content.trim_end_matches(|c: char| c == ',' || c.is_whitespace());
+    
+    let mut lines = Vec::new();
+    if !content.is_empty() {
+        lines.push(format!("{}    {},", leading_ws, content));
+    }
+    for entry in &exited {
+        let suffix = if entry.is_row {
+            ","
+        } else if entry.is_assignment {
+            ";"
         } else {
-            let without_bracket = transformed.trim().trim_end_matches(']').trim_end_matches(',');
-            format!("{}    {},\n{}]{}", leading_ws, without_bracket, leading_ws, suffix)
+            ""
         };
-        
-        return Some(close_line);
+        lines.push(format!("{}]{}{}", leading_ws, ")".repeat(entry.call_parens), suffix));
     }
     
-    None
+    Some(lines.join("\n"))
 }
 
 /// Process a line that might be part of array mode
@@ -81,7 +103,7 @@ pub fn process_array_mode_line(
         }
         
         // Check if this line starts a multi-line struct/enum literal
-        let starts_multiline_literal = if opens > closes {
+        let starts_multiline_literal = if opens > closes && enabled(LoweringRule::Literal) {
             if trimmed.contains("::") {
                 detect_bare_enum_literal(trimmed).is_some()
             } else {
@@ -91,6 +113,28 @@ pub fn process_array_mode_line(
             false
         };
         
+        // Nested row: `[` or `[1, 2,` opening a row that continues below
+        let (bracket_opens, bracket_closes) = count_brackets_outside_strings(trimmed);
+        if trimmed.starts_with('[') && bracket_opens > bracket_closes {
+            let new_rows = bracket_opens - bracket_closes;
+            for level in (0..new_rows).rev() {
+                array_mode.enter_row(bracket_depth - level);
+            }
+            let row_opens = trimmed.len() - trimmed.trim_start_matches('[').len();
+            let rest = trimmed[row_opens..].trim();
+            let first = if rest.is_empty() { String::new() } else { transform_array_element(rest).trim().to_string() };
+            return ArrayModeResult::Handled(format!("{}{}{}", leading_ws, &trimmed[..row_opens], first));
+        }
+        // Element opening a row after a prefix: `("odds", [`, `("y", HashMap::from([`
+        if trimmed.ends_with('[') && bracket_opens == bracket_closes + 1 {
+            let parens = delimiter_positions(trimmed, &['(', ')'])
+                .iter()
+                .fold(0isize, |depth, &(_, c)| if c == '(' { depth + 1 } else { depth - 1 });
+            array_mode.enter_row(bracket_depth);
+            array_mode.mark_in_call(parens.max(0) as usize);
+            return ArrayModeResult::Handled(format!("{}{}", leading_ws, trimmed));
+        }
+        
         if starts_multiline_literal {
             // Transform the start line and enter literal mode
             let transformed = transform_array_element(clean_line);
@@ -101,7 +145,7 @@ pub fn process_array_mode_line(
             } else { 
                 LiteralKind::Struct 
             };
-            literal_mode.enter(kind, prev_depth + opens, false);
+            literal_mode.enter_nested(kind, prev_depth, prev_depth + opens - closes);
             
             return ArrayModeResult::Handled(transformed);
         }
@@ -0,0 +1,78 @@
+//! Operator Line Continuation
+//!
+//! A line ending with a binary operator continues on the next line:
+//! ```text
+//! if a > 0 &&
+//!     b < 10 {
+//! ```
+//! Left alone, the `{` on the second line is seen without its `if` and enters
+//! the wrong mode. Conditions are joined into `if a > 0 && b < 10 {` before
+//! lowering; multi-line assignments keep accumulating while they end with an
+//! operator (see `multiline_assign_lowering`).
+
+use super::depth_tracking_lowering::{find_outside_strings, update_multiline_depth};
+
+/// Binary operators that can end a continued line, longest first
+const TRAILING_OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "|", "^",
+];
+
+/// Check if a line ends with a binary operator (and is not `=>` or `->`)
+pub fn ends_with_binary_operator(trimmed: &str) -> bool {
+    if trimmed.ends_with("=>") || trimmed.ends_with("->") {
+        return false;
+    }
+    // `|x|` closure parameters and `x|` patterns are not operators
+    if trimmed.ends_with('|') && !trimmed.ends_with("||") {
+        return false;
+    }
+    TRAILING_OPERATORS.iter().any(|op| trimmed.ends_with(op))
+}
+
+/// Check if an expression continues on the next line: it ends with a binary
+/// operator outside any open parens/brackets (those are handled by the
+/// multi-line expression depth tracking instead)
+pub fn continues_expression(trimmed: &str) -> bool {
+    let mut depth = 0;
+    update_multiline_depth(&mut depth, trimmed);
+    depth == 0 && ends_with_binary_operator(trimmed)
+}
+
+/// Check if a line starts an `if` / `while` condition that continues on the
+/// next line: `if a > 0 &&`, `} else if x ||`, `v = if ready &&`
+pub fn is_condition_continuation_start(trimmed: &str) -> bool {
+    let is_condition = trimmed.starts_with("if ")
+        || trimmed.starts_with("while ")
+        || trimmed.starts_with("else if ")
+        || trimmed.starts_with("} else if ")
+        || trimmed.contains("= if ");
+    is_condition && find_outside_strings(trimmed, '{').is_none() && continues_expression(trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ends_with_binary_operator() {
+        assert!(ends_with_binary_operator("if a > 0 &&"));
+        assert!(ends_with_binary_operator("total = a +"));
+        assert!(ends_with_binary_operator("while x <"));
+        assert!(!ends_with_binary_operator("Some(x) =>"));
+        assert!(!ends_with_binary_operator("fn f() ->"));
+        assert!(!ends_with_binary_operator("items.map(|x|"));
+        assert!(!ends_with_binary_operator("if a > 0 {"));
+    }
+
+    #[test]
+    fn test_is_condition_continuation_start() {
+        assert!(is_condition_continuation_start("if a > 0 &&"));
+        assert!(is_condition_continuation_start("} else if a ||"));
+        assert!(is_condition_continuation_start("while lo <"));
+        assert!(is_condition_continuation_start("v = if ready &&"));
+        assert!(!is_condition_continuation_start("if a > 0 {"));
+        assert!(!is_condition_continuation_start("if check(a &&"));
+        assert!(!is_condition_continuation_start("ok = a ||"));
+    }
+}
@@ -4,44 +4,92 @@
 //! These functions are CRITICAL for correctly handling nested constructs
 //! and avoiding miscounting delimiters inside string literals.
 
-/// Count opening and closing braces OUTSIDE of string literals
-/// 
-/// This is CRITICAL to avoid counting format placeholders like {} in "hello {} world"
-/// 
+/// Collect byte positions of the given delimiters OUTSIDE of string and char literals
+///
+/// This is the single source of truth for structural delimiter scanning.
+/// Skips:
+/// - String literals: `"closing } brace"`
+/// - Escaped quotes inside strings: `"say \"{\""`
+/// - Char literals: `'{'`, `'}'`, `'\''`
+///
+/// Lifetimes (`'a`) are NOT char literals and are left alone.
+///
 /// # Returns
-/// A tuple of (opening_count, closing_count)
-pub fn count_braces_outside_strings(s: &str) -> (usize, usize) {
-    let mut opens = 0;
-    let mut closes = 0;
+/// A vector of (byte_position, delimiter) pairs in source order
+pub fn delimiter_positions(s: &str, targets: &[char]) -> Vec<(usize, char)> {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut result = Vec::new();
     let mut in_string = false;
-    let mut escape_next = false;
+    let mut i = 0;
     
-    for c in s.chars() {
-        if escape_next {
-            escape_next = false;
-            continue;
-        }
+    while i < chars.len() {
+        let (pos, c) = chars[i];
         
-        if c == '\\' && in_string {
-            escape_next = true;
+        if in_string {
+            if c == '\\' {
+                i += 2;
+                continue;
+            }
+            if c == '"' {
+                in_string = false;
+            }
+            i += 1;
             continue;
         }
         
         if c == '"' {
-            in_string = !in_string;
+            in_string = true;
+            i += 1;
             continue;
         }
         
-        if !in_string {
-            match c {
-                '{' => opens += 1,
-                '}' => closes += 1,
-                _ => {}
+        // CRITICAL FIX: Char literals like '{' must not affect depth
+        if c == '\'' {
+            if let Some(skip) = char_literal_len(&chars[i..]) {
+                i += skip;
+                continue;
             }
         }
+        
+        if targets.contains(&c) {
+            result.push((pos, c));
+        }
+        i += 1;
     }
     
-    (opens, closes)
+    result
+}
+
+/// Length (in chars) of a char literal starting at `chars[0] == '\''`, if any
+///
+/// Returns None for lifetimes (`'a`, `'static`) and stray quotes.
+fn char_literal_len(chars: &[(usize, char)]) -> Option<usize> {
+    match chars.get(1).map(|&(_, c)| c) {
+        // Escaped char: '\n', '\'', '\\', '\u{7B}'
+        Some('\\') => {
+            for (k, &(_, c)) in chars.iter().enumerate().skip(3) {
+                if c == '\'' {
+                    return Some(k + 1);
+                }
+            }
+            None
+        }
+        Some(_) => match chars.get(2) {
+            Some(&(_, '\'')) => Some(3),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+/// Count opening and closing braces OUTSIDE of string literals
+/// 
+/// This is CRITICAL to avoid counting format placeholders like {} in "hello {} world"
+/// 
+/// # Returns
+/// A tuple of (opening_count, closing_count)
+pub fn count_braces_outside_strings(s: &str) -> (usize, usize) {
+    count_pair_outside_strings(s, '{', '}')
 }
 
 /// Count opening and closing brackets OUTSIDE of string literals
@@ -49,37 +97,129 @@ pub fn count_braces_outside_strings(s: &str) -> (usize, usize) {
 /// # Returns
 /// A tuple of (opening_count, closing_count)
 pub fn count_brackets_outside_strings(s: &str) -> (usize, usize) {
-    let mut opens = 0;
-    let mut closes = 0;
-    let mut in_string = false;
-    let mut escape_next = false;
-    
-    for c in s.chars() {
-        if escape_next {
-            escape_next = false;
-            continue;
-        }
-        
-        if c == '\\' && in_string {
-            escape_next = true;
-            continue;
-        }
-        
-        if c == '"' {
-            in_string = !in_string;
-            continue;
+    count_pair_outside_strings(s, '[', ']')
+}
+
+/// Count opening and closing parentheses OUTSIDE of string literals
+/// 
+/// # Returns
+/// A tuple of (opening_count, closing_count)
+pub fn count_parens_outside_strings(s: &str) -> (usize, usize) {
+    count_pair_outside_strings(s, '(', ')')
+}
+
+fn count_pair_outside_strings(s: &str, open: char, close: char) -> (usize, usize) {
+    let positions = delimiter_positions(s, &[open, close]);
+    let opens = positions.iter().filter(|&&(_, c)| c == open).count();
+    (opens, positions.len() - opens)
+}
+
+/// Find the first occurrence of `target` OUTSIDE of string and char literals
+pub fn find_outside_strings(s: &str, target: char) -> Option<usize> {
+    delimiter_positions(s, &[target]).first().map(|&(pos, _)| pos)
+}
+
+/// Find the last occurrence of `target` OUTSIDE of string and char literals
+pub fn rfind_outside_strings(s: &str, target: char) -> Option<usize> {
+    delimiter_positions(s, &[target]).last().map(|&(pos, _)| pos)
+}
+
+/// Find the `)`, `]` or `}` closing the delimiter at byte `open`, OUTSIDE of
+/// string and char literals
+///
+/// None when `s[open..]` does not start with `(`, `[` or `{`, or the
+/// delimiter is not closed in `s`.
+pub fn find_matching_close(s: &str, open: usize) -> Option<usize> {
+    let opener = s.get(open..)?.chars().next()?;
+    let closer = match opener {
+        '(' => ')',
+        '[' => ']',
+        '{' => '}',
+        _ => return None,
+    };
+    let mut depth = 0usize;
+    for (pos, c) in delimiter_positions(&s[open..], &[opener, closer]) {
+        if c == opener {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return Some(open + pos);
+            }
         }
-        
-        if !in_string {
-            match c {
-                '[' => opens += 1,
-                ']' => closes += 1,
-                _ => {}
+    }
+    None
+}
+
+/// Find the `(`, `[` or `{` opened by the delimiter at byte `close`,
+/// OUTSIDE of string and char literals; the reverse of
+/// [`find_matching_close`]
+pub fn find_matching_open(s: &str, close: usize) -> Option<usize> {
+    let closer = s.get(close..)?.chars().next()?;
+    let opener = match closer {
+        ')' => '(',
+        ']' => '[',
+        '}' => '{',
+        _ => return None,
+    };
+    let mut depth = 0usize;
+    for (pos, c) in delimiter_positions(&s[..=close], &[opener, closer]).into_iter().rev() {
+        if c == closer {
+            depth += 1;
+        } else {
+            depth -= 1;
+            if depth == 0 {
+                return Some(pos);
             }
         }
     }
+    None
+}
+
+/// Split `s` at every `sep` that is at the top level: not nested in `()`,
+/// `[]`, `{}` or a turbofish `::<...>`, and not inside a string or char
+/// literal (escapes included).
+///
+/// This is the single comma splitter for field lists, call arguments and
+/// array elements. Pieces are returned untrimmed; an empty input or a
+/// trailing separator does not produce a blank last piece.
+///
+/// `<` and `>` are comparisons here (`f(a < b, c > d)` has two arguments);
+/// use [`split_top_level_types`] for lists that contain generic types.
+pub fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    split_top_level_impl(s, sep, false)
+}
+
+/// Like [`split_top_level`], but `<...>` also nests (`x HashMap<K, V>, y i32`
+/// is two pieces). `->` and `=>` are not closing brackets.
+pub fn split_top_level_types(s: &str, sep: char) -> Vec<&str> {
+    split_top_level_impl(s, sep, true)
+}
+
+fn split_top_level_impl(s: &str, sep: char, generics: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut angle = 0usize;
+    let mut start = 0;
     
-    (opens, closes)
+    for (pos, c) in delimiter_positions(s, &['(', ')', '[', ']', '{', '}', '<', '>', sep]) {
+        let before = &s[..pos];
+        match c {
+            _ if c == sep && depth == 0 && angle == 0 => {
+                parts.push(&s[start..pos]);
+                start = pos + c.len_utf8();
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '<' if generics || angle > 0 || before.ends_with("::") => angle += 1,
+            '>' if angle > 0 && !before.ends_with('-') && !before.ends_with('=') => angle -= 1,
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        parts.push(&s[start..]);
+    }
+    parts
 }
 
 /// Update multiline expression depth based on parentheses and brackets
@@ -155,6 +295,49 @@ mod tests {
         assert_eq!(count_brackets_outside_strings("\"[not a bracket]\""), (0, 0));
     }
     
+    #[test]
+    fn test_count_braces_in_char_literal() {
+        assert_eq!(count_braces_outside_strings("c == '{' {"), (1, 0));
+        assert_eq!(count_braces_outside_strings("'}' { \"close\" }"), (1, 1));
+        assert_eq!(count_braces_outside_strings("'\\'' {"), (1, 0));
+        assert_eq!(count_brackets_outside_strings("'[' => x[0]"), (1, 1));
+        // Lifetimes are not char literals
+        assert_eq!(count_braces_outside_strings("fn f<'a>(x: &'a str) {"), (1, 0));
+    }
+    
+    #[test]
+    fn test_count_braces_escaped_quote() {
+        assert_eq!(count_braces_outside_strings("println(\"\\\" } \\\"\") {"), (1, 0));
+    }
+    
+    #[test]
+    fn test_find_outside_strings() {
+        let line = "\"{\" { \"}\" }";
+        assert_eq!(find_outside_strings(line, '{'), Some(4));
+        assert_eq!(rfind_outside_strings(line, '}'), Some(line.len() - 1));
+        assert_eq!(find_outside_strings("\"{}\"", '{'), None);
+    }
+    
+    #[test]
+    fn test_find_matching_close() {
+        assert_eq!(find_matching_close("f(a, (b), c)", 1), Some(11));
+        assert_eq!(find_matching_close("f(')', \")\")", 1), Some(10));
+        assert_eq!(find_matching_close("m = { 'a': [1] }", 4), Some(15));
+        assert_eq!(find_matching_close("xs[i]", 2), Some(4));
+        assert_eq!(find_matching_close("f(a", 1), None);
+        assert_eq!(find_matching_close("f(a)", 0), None);
+        assert_eq!(find_matching_close("f(a)", 9), None);
+    }
+
+    #[test]
+    fn test_find_matching_open() {
+        assert_eq!(find_matching_open("f(a, (b), c)?", 11), Some(1));
+        assert_eq!(find_matching_open("f('(', \"(\")", 10), Some(1));
+        assert_eq!(find_matching_open("parse[i32]()", 9), Some(5));
+        assert_eq!(find_matching_open("a)", 1), None);
+        assert_eq!(find_matching_open("f(a)", 1), None);
+    }
+
     #[test]
     fn test_multiline_depth() {
         let mut depth = 0;
@@ -167,4 +350,76 @@ mod tests {
         update_multiline_depth(&mut depth, ")");
         assert_eq!(depth, 0);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_split_top_level() {
+        assert_eq!(split_top_level("a, b, c", ','), vec!["a", " b", " c"]);
+        assert_eq!(split_top_level("f(a, b), [1, 2], S { x = 1, y = 2 }", ','), vec!["f(a, b)", " [1, 2]", " S { x = 1, y = 2 }"]);
+        assert_eq!(split_top_level("\"a, b\", ',', '\\'', x", ','), vec!["\"a, b\"", " ','", " '\\''", " x"]);
+        assert_eq!(split_top_level("\"say \\\", hi\", y", ','), vec!["\"say \\\", hi\"", " y"]);
+        assert_eq!(split_top_level("a < b, c > d", ','), vec!["a < b", " c > d"]);
+        assert_eq!(split_top_level("HashMap::<K, V>::new(), 1", ','), vec!["HashMap::<K, V>::new()", " 1"]);
+        assert_eq!(split_top_level("a, b,", ','), vec!["a", " b"]);
+        assert!(split_top_level("", ',').is_empty());
+    }
+    
+    #[test]
+    fn test_split_top_level_types() {
+        assert_eq!(split_top_level_types("m HashMap<K, V>, f fn(i32) -> i32, x i32", ','), vec!["m HashMap<K, V>", " f fn(i32) -> i32", " x i32"]);
+        assert_eq!(split_top_level_types("x &'a str, y Option<(u8, u8)>", ','), vec!["x &'a str", " y Option<(u8, u8)>"]);
+    }
+    
+    /// Deterministic xorshift generator for the property tests below
+    struct Rng(u64);
+    
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+    
+    /// Random expression that may contain commas only where they are nested
+    fn gen_piece(rng: &mut Rng, depth: usize) -> String {
+        let choice = if depth > 3 { rng.below(4) } else { rng.below(9) };
+        match choice {
+            0 => ["x", "a.b", "42", "y_2"][rng.below(4)].to_string(),
+            1 => ["\"a, b\"", "\"}{)(,\"", "\"q \\\" , \\\\\"", "\"\""][rng.below(4)].to_string(),
+            2 => ["','", "'('", "'\\''", "'}'"][rng.below(4)].to_string(),
+            3 => ["a < b", "c > d", "p -> q"][rng.below(3)].to_string(),
+            4 => format!("f({})", gen_list(rng, depth + 1)),
+            5 => format!("[{}]", gen_list(rng, depth + 1)),
+            6 => format!("S {{ {} }}", gen_list(rng, depth + 1)),
+            7 => ["HashMap::<K, V>::new()", "parse::<(u8, Option<i32>)>()", "Box::<dyn Fn(u8, u8) -> u8>::new(f)"][rng.below(3)].to_string(),
+            _ => format!("{} + {}", gen_piece(rng, depth + 1), gen_piece(rng, depth + 1)),
+        }
+    }
+    
+    fn gen_list(rng: &mut Rng, depth: usize) -> String {
+        let n = 1 + rng.below(3);
+        (0..n).map(|_| gen_piece(rng, depth)).collect::<Vec<_>>().join(", ")
+    }
+    
+    #[test]
+    fn test_split_top_level_recovers_pieces() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..2000 {
+            let pieces: Vec<String> = (0..1 + rng.below(5)).map(|_| gen_piece(&mut rng, 0)).collect();
+            let joined = pieces.join(",");
+            assert_eq!(split_top_level(&joined, ','), pieces, "Split mismatch for {}", joined);
+        }
+    }
+    
+    #[test]
+    fn test_split_top_level_rejoins_to_input() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for _ in 0..2000 {
+            let input = gen_list(&mut rng, 0);
+            let parts = split_top_level(&input, ',');
+            assert_eq!(parts.join(","), input, "Split lost text of {}", input);
+            assert!(parts.iter().all(|p| split_top_level(p, ',').len() <= 1), "Nested split of {}", input);
+        }
+    }
+}
@@ -50,19 +50,63 @@ fn process_literal_close(
     Some(format!("{}}}{}", leading_ws, suffix))
 }
 
+/// Process the line closing a literal and the call it is passed to:
+/// `})`, `}))`, `}).unwrap()`, `})?`. The call is a statement (`;`) unless
+/// it is an unassigned call ending the block, an array element or sits in
+/// another literal (`,`).
+fn process_call_literal_close(
+    trimmed: &str,
+    leading_ws: &str,
+    brace_depth: usize,
+    literal_mode: &mut LiteralModeStack,
+    array_mode: &ArrayModeStack,
+    is_before_closing_brace: bool,
+) -> Option<String> {
+    let parens = literal_mode.current_call_parens();
+    let was_assignment = literal_mode.current_is_assignment();
+    let after_brace = trimmed.strip_prefix('}')?;
+    let rest = after_brace.trim_start_matches(')');
+    if after_brace.len() - rest.len() != parens {
+        return None;
+    }
+    let tail = rest.trim_end_matches([';', ',']).trim_end();
+    if !(tail.is_empty() || tail.starts_with('.') || tail.starts_with('?')) {
+        return None;
+    }
+    if !literal_mode.should_exit(brace_depth) {
+        return None;
+    }
+    literal_mode.exit();
+    
+    let suffix = if array_mode.is_active() || literal_mode.is_active() {
+        ","
+    } else if is_before_closing_brace && !was_assignment {
+        ""  // Tail expression: `Ok(User { .. })`
+    } else {
+        ";"
+    };
+    Some(format!("{}}}{}{}{}", leading_ws, ")".repeat(parens), tail, suffix))
+}
+
 /// Process a line that might be part of literal mode
 pub fn process_literal_mode_line(
     trimmed: &str,
     clean_line: &str,
     leading_ws: &str,
     brace_depth: usize,
-    opens: usize,
-    closes: usize,
     prev_depth: usize,
     literal_mode: &mut LiteralModeStack,
     array_mode: &ArrayModeStack,
     current_fn_ctx: Option<&CurrentFunctionContext>,
+    is_before_closing_brace: bool,
 ) -> LiteralModeResult {
+    // Literal passed to a call spanning lines: `})` closes both
+    if literal_mode.is_active() && literal_mode.current_call_parens() > 0 {
+        if let Some(result) = process_call_literal_close(trimmed, leading_ws, brace_depth, literal_mode, array_mode, is_before_closing_brace) {
+            return LiteralModeResult::Handled(result);
+        }
+    }
+    
     // Check for literal closing brace
     // Handle both "}" and "}," (user may or may not include comma)
     if literal_mode.is_active() && (trimmed == "}" || trimmed == "},") {
@@ -78,16 +122,23 @@ pub fn process_literal_mode_line(
     
     // Process line inside literal mode (only for non-closing-brace lines)
     if literal_mode.is_active() {
-        let transformed = transform_literal_field_with_ctx(clean_line, current_fn_ctx);
+        let mut transformed = transform_literal_field_with_ctx(clean_line, current_fn_ctx);
         
-        // Check for nested literal start
-        if trimmed.contains('{') && opens > closes {
+        // Nested literals left open or closed by the line, by its net brace
+        // delta: `header = Header { id = 1 }` opens and closes one
+        if brace_depth > prev_depth {
             let kind = if trimmed.contains("::") { 
                 LiteralKind::EnumVariant 
             } else { 
                 LiteralKind::Struct 
             };
-            literal_mode.enter(kind, prev_depth + opens, false);
+            literal_mode.enter_nested(kind, prev_depth, brace_depth);
+        } else if brace_depth < prev_depth {
+            literal_mode.exit_closed(brace_depth);
+            // `} }` closing a nested literal is a field of the one still open
+            if literal_mode.is_active() && !transformed.ends_with(',') {
+                transformed.push(',');
+            }
         }
         
         return LiteralModeResult::Handled(transformed);
@@ -114,12 +165,11 @@ mod tests {
             "}",
             "    ",
             0,
-            0,
-            1,
             1,
             &mut literal_mode,
             &array_mode,
             None,
+            false,
         );
         
         // Should handle and add semicolon for assignment
@@ -146,12 +196,11 @@ mod tests {
             "}",
             "    ",
             0,
-            0,
-            1,
             1,
             &mut literal_mode,
             &array_mode,
             None,
+            false,
         );
         
         match result {
@@ -159,4 +208,28 @@ mod tests {
             _ => panic!("Expected Handled result"),
         }
     }
+    
+    #[test]
+    fn test_literal_mode_nested_by_net_depth() {
+        let mut literal_mode = LiteralModeStack::new();
+        let array_mode = ArrayModeStack::new();
+        literal_mode.enter(LiteralKind::Struct, 2, true);
+        let mut line = |trimmed: &str, depth: usize, prev_depth: usize| {
+            match process_literal_mode_line(trimmed, trimmed, "", depth, prev_depth, &mut literal_mode, &array_mode, None, false) {
+                LiteralModeResult::Handled(s) => s,
+                LiteralModeResult::NotHandled => panic!("`{}` not handled", trimmed),
+            }
+        };
+        
+        // Opens and closes on one line: no nested literal
+        assert_eq!(line("header = Header { id = 1 }", 2, 2), "header: Header { id: 1 },");
+        line("route = Route { from = Point { x = 1 }, to = Point {", 4, 2);
+        line("x = 2", 4, 4);
+        assert_eq!(line("}", 3, 4), "},");
+        assert_eq!(line("}", 2, 3), "},");
+        line("meta = Meta { tag = Tag {", 4, 2);
+        assert_eq!(line("} }", 2, 4), "} },");
+        line("size = 3", 2, 2);
+        assert_eq!(line("}", 1, 2), "};");
+    }
 }
\ No newline at end of file
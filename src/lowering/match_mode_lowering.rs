@@ -16,7 +16,11 @@ use crate::control_flow::{
 };
 use crate::clone_helpers::extract_arm_pattern;
 use crate::lowering::lookahead_lowering::detect_arm_has_if_expr;
+use crate::lowering::depth_tracking_lowering::{
+    count_braces_outside_strings, find_outside_strings, rfind_outside_strings,
+};
 use crate::function::CurrentFunctionContext;
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// Result of processing a line in match mode
 pub enum MatchModeResult {
@@ -52,11 +56,10 @@ fn is_multiline_destructure_start(lines: &[&str], current_line: usize) -> bool {
     }
     
     // Count braces on this line - must be exactly 1 unmatched `{`
-    let mut depth: i32 = 0;
-    for c in first_trimmed.chars() {
-        if c == '{' { depth += 1; }
-        if c == '}' { depth -= 1; }
-    }
+    // CRITICAL FIX: Use string-aware counting so `"}"` or '{' in a pattern
+    // doesn't corrupt the running depth
+    let (opens, closes) = count_braces_outside_strings(first_trimmed);
+    let depth = opens as i32 - closes as i32;
     if depth != 1 {
         return false;
     }
@@ -69,13 +72,9 @@ fn is_multiline_destructure_start(lines: &[&str], current_line: usize) -> bool {
     for i in (current_line + 1)..limit {
         let t = lines[i].trim();
         
-        // Count braces on this line
-        let mut line_opens = 0i32;
-        let mut line_closes = 0i32;
-        for c in t.chars() {
-            if c == '{' { line_opens += 1; }
-            if c == '}' { line_closes += 1; }
-        }
+        // Count braces on this line (outside string/char literals)
+        let (line_opens, line_closes) = count_braces_outside_strings(t);
+        let (line_opens, line_closes) = (line_opens as i32, line_closes as i32);
         
         running_depth = running_depth - line_closes + line_opens;
         
@@ -83,7 +82,7 @@ fn is_multiline_destructure_start(lines: &[&str], current_line: usize) -> bool {
         // and after processing, depth is back to 1 (one new `{` opened).
         // The `}` must come before the `{` on the line.
         if running_depth == 1 && line_closes > 0 && t.ends_with('{') {
-            if let (Some(close_pos), Some(open_pos)) = (t.find('}'), t.rfind('{')) {
+            if let (Some(close_pos), Some(open_pos)) = (find_outside_strings(t, '}'), rfind_outside_strings(t, '{')) {
                 if close_pos < open_pos {
                     return true; // Found `} {` → multi-line destructuring confirmed
                 }
@@ -106,7 +105,6 @@ fn is_multiline_destructure_start(lines: &[&str], current_line: usize) -> bool {
 fn process_destructuring_line(
     trimmed: &str,
     leading_ws: &str,
-    brace_depth: usize,
     match_mode: &mut MatchModeStack,
 ) -> Option<MatchModeResult> {
     if !match_mode.in_destructuring() {
@@ -115,14 +113,17 @@ fn process_destructuring_line(
     
     // Check if this line is `} {` (close destructure, open body)
     if trimmed.ends_with('{') && trimmed.contains('}') {
-        if let (Some(close_pos), Some(open_pos)) = (trimmed.find('}'), trimmed.rfind('{')) {
+        if let (Some(close_pos), Some(open_pos)) =
+            (find_outside_strings(trimmed, '}'), rfind_outside_strings(trimmed, '{'))
+        {
             if close_pos < open_pos {
                 // Extract the pattern part before `{` (the closing `}` and anything before it)
                 let pattern_part = trimmed[..open_pos].trim();
                 match_mode.exit_destructuring();
-                match_mode.enter_arm_body(brace_depth, false);
+                match_mode.enter_arm_body(false);
+                let arrow = if enabled(LoweringRule::L02) { " =>" } else { "" };
                 return Some(MatchModeResult::Handled(
-                    format!("{}{} => {{", leading_ws, pattern_part)
+                    format!("{}{}{} {{", leading_ws, pattern_part, arrow)
                 ));
             }
         }
@@ -141,10 +142,10 @@ fn process_match_close(
     match_mode: &mut MatchModeStack,
 ) -> Option<String> {
     // CRITICAL FIX: Check exit conditions BEFORE modifying state
-    let should_exit_arm = match_mode.should_exit_arm(brace_depth);
+    let should_exit_arm = match_mode.should_exit_arm();
     let should_exit_match = match_mode.should_exit_match(brace_depth);
     
-    // Priority 1: Exit arm body (depth < arm_body_depth)
+    // Priority 1: Exit arm body (this `}` closed the arm's own brace)
     if should_exit_arm {
         let uses_parens = match_mode.arm_uses_parens();
         match_mode.exit_arm_body();
@@ -167,7 +168,7 @@ fn process_multi_pattern_continuation(
     clean_line: &str,
     trimmed: &str,
     current_fn_ctx: &CurrentFunctionContext,
-    match_mode: &MatchModeStack,
+    match_mode: &mut MatchModeStack,
 ) -> Option<String> {
     if !match_mode.expecting_arm_pattern() {
         return None;
@@ -177,8 +178,24 @@ fn process_multi_pattern_continuation(
         return None;
     }
     
-    let ret_type = current_fn_ctx.return_type.as_deref();
-    Some(transform_multi_pattern_line(clean_line, ret_type))
+    let output = transform_multi_pattern_line(clean_line, arm_value_type(current_fn_ctx, match_mode));
+    
+    // `| Last {` opens a multi-line body
+    let (opens, closes) = count_braces_outside_strings(trimmed);
+    if trimmed.ends_with('{') && opens > closes {
+        match_mode.enter_arm_body(false);
+    }
+    
+    Some(output)
+}
+
+/// Type an arm value must have. A match used as a statement in a unit
+/// function discards its arm values, so they lower to statements (`()`).
+fn arm_value_type<'a>(current_fn_ctx: &'a CurrentFunctionContext, match_mode: &MatchModeStack) -> Option<&'a str> {
+    match current_fn_ctx.return_type.as_deref() {
+        None if !match_mode.any_is_assignment() => Some("()"),
+        ret_type => ret_type,
+    }
 }
 
 /// Process first pattern in multi-pattern sequence
@@ -197,6 +214,11 @@ fn process_first_multi_pattern(
         return None;
     }
     
+    // `pattern {` opens an arm body - a following `|x| ...` line is a closure
+    if line.trim_end().ends_with('{') {
+        return None;
+    }
+    
     // This is the first pattern in a multi-pattern arm
     // DO NOT transform, pass through as-is (no => {)
     Some(line.to_string())
@@ -211,7 +233,6 @@ fn process_regular_arm_pattern(
     line_num: usize,
     prev_depth: usize,
     opens: usize,
-    brace_depth: usize,
     current_fn_ctx: &CurrentFunctionContext,
     match_mode: &mut MatchModeStack,
 ) -> Option<String> {
@@ -225,8 +246,7 @@ fn process_regular_arm_pattern(
     
     // Check for single-line arm first
     if is_single_line_arm(trimmed) {
-        let ret_type = current_fn_ctx.return_type.as_deref();
-        return Some(transform_single_line_arm(clean_line, ret_type));
+        return Some(transform_single_line_arm(clean_line, arm_value_type(current_fn_ctx, match_mode)));
     }
     
     // CRITICAL FIX: Detect multi-line struct destructuring pattern
@@ -241,14 +261,14 @@ fn process_regular_arm_pattern(
     // Multi-line arm pattern (regular - `{` opens body)
     let arm_has_if_expr = detect_arm_has_if_expr(lines, line_num, prev_depth + opens);
     
-    let output = if arm_has_if_expr {
+    let output = if arm_has_if_expr && enabled(LoweringRule::L02) {
         let pattern = extract_arm_pattern(trimmed);
         format!("{}{} =>", leading_ws, pattern)
     } else {
         transform_arm_pattern(clean_line)
     };
     
-    match_mode.enter_arm_body(brace_depth, arm_has_if_expr);
+    match_mode.enter_arm_body(arm_has_if_expr);
     
     Some(output)
 }
@@ -277,7 +297,7 @@ pub fn process_match_mode_line(
     
     // CRITICAL FIX: Handle multi-line struct destructuring pattern
     // When inside destructuring, process field lines and detect `} {` to enter body
-    if let Some(result) = process_destructuring_line(trimmed, leading_ws, brace_depth, match_mode) {
+    if let Some(result) = process_destructuring_line(trimmed, leading_ws, match_mode) {
         return result;
     }
     
@@ -294,7 +314,7 @@ pub fn process_match_mode_line(
     // Handle regular single-pattern arms
     if let Some(result) = process_regular_arm_pattern(
         clean_line, trimmed, leading_ws, lines, line_num,
-        prev_depth, opens, brace_depth, current_fn_ctx, match_mode,
+        prev_depth, opens, current_fn_ctx, match_mode,
     ) {
         return MatchModeResult::Handled(result);
     }
@@ -362,7 +382,7 @@ mod tests {
         match_mode.enter_destructuring();
         
         // Regular field line → pass through
-        let result = process_destructuring_line("version,", "        ", 2, &mut match_mode);
+        let result = process_destructuring_line("version,", "        ", &mut match_mode);
         assert!(result.is_some());
         match result.unwrap() {
             MatchModeResult::Handled(s) => assert!(s.contains("version,")),
@@ -370,7 +390,7 @@ mod tests {
         }
         
         // `} {` line → transform to `} => {` and enter arm body
-        let result = process_destructuring_line("} {", "    ", 2, &mut match_mode);
+        let result = process_destructuring_line("} {", "    ", &mut match_mode);
         assert!(result.is_some());
         match result.unwrap() {
             MatchModeResult::Handled(s) => {
@@ -381,4 +401,25 @@ mod tests {
         assert!(match_mode.in_arm_body());
         assert!(!match_mode.in_destructuring());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_multiline_destructure_with_string_braces() {
+        // `}` inside a string in the body must not look like `} {`
+        let lines = vec![
+            "    Some(v) {",
+            "        println!(\"} {\")",
+            "    }",
+        ];
+        assert!(!is_multiline_destructure_start(&lines, 0));
+        
+        // `{` inside a char literal must not break the destructuring look-ahead
+        let lines = vec![
+            "    Token::Open {",
+            "        ch,",
+            "    } {",
+            "        assert_eq!(ch, '{');",
+            "    }",
+        ];
+        assert!(is_multiline_destructure_start(&lines, 0));
+    }
+}
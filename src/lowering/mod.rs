@@ -16,6 +16,7 @@ pub mod lookahead_lowering;
 // Multi-line construct handling
 pub mod multiline_fn_lowering;
 pub mod multiline_assign_lowering;
+pub mod continuation_lowering;
 
 // Mode handling
 pub mod use_import_lowering;
@@ -28,6 +29,8 @@ pub use transpiler_state::TranspilerState;
 pub use depth_tracking_lowering::{
     count_braces_outside_strings,
     count_brackets_outside_strings,
+    split_top_level,
+    split_top_level_types,
     update_multiline_depth,
 };
 pub use lookahead_lowering::{
@@ -45,6 +48,7 @@ pub use multiline_assign_lowering::{
     is_multiline_assign_complete,
     process_complete_multiline_assign,
 };
+pub use continuation_lowering::{ends_with_binary_operator, continues_expression, is_condition_continuation_start};
 pub use use_import_lowering::{process_use_import_line, UseImportResult};
 pub use array_mode_lowering::{process_array_mode_line, ArrayModeResult};
 pub use literal_mode_lowering::{process_literal_mode_line, LiteralModeResult};
@@ -10,16 +10,23 @@
 //! Should become: `mut x Type = value`
 
 use crate::variable::parse_rusts_assignment_ext;
+use super::continuation_lowering::{continues_expression, is_condition_continuation_start};
 use crate::helpers;
 use crate::translate::assignment_translate::process_assignment;
 use crate::scope::ScopeAnalyzer;
 use crate::variable::VariableTracker;
 use crate::function::{CurrentFunctionContext, FunctionRegistry};
 
-/// Check if a line ends with `=` (indicating multi-line assignment start)
+/// Check if a line ends with `=` (indicating multi-line assignment start),
+/// or is an assignment whose value continues after a trailing operator
+/// (`ok = a > 0 &&`)
 /// 
 /// Must not match `==`, `!=`, `<=`, `>=`, or `=>`
 pub fn is_multiline_assign_start(trimmed: &str) -> bool {
+    if trimmed.contains(" = ") && continues_expression(trimmed) && !is_condition_continuation_start(trimmed) {
+        return true;
+    }
+    
     if !trimmed.contains('=') || trimmed.contains("==") {
         return false;
     }
@@ -32,10 +39,15 @@ pub fn is_multiline_assign_start(trimmed: &str) -> bool {
         && !trimmed.ends_with("=>")
 }
 
-/// Check if accumulated assignment is complete (doesn't end with `=` anymore)
+/// Check if accumulated assignment is complete (doesn't end with `=` or a
+/// binary operator anymore)
 pub fn is_multiline_assign_complete(acc: &str) -> bool {
     let trimmed = acc.trim();
     
+    if continues_expression(trimmed) {
+        return false;
+    }
+    
     if !trimmed.ends_with('=') {
         return true;
     }
@@ -152,12 +164,11 @@ fn try_process_tuple_destructuring(
     }
     
     // Transform value using standard transformations
-    use crate::variable::expand_value;
-    use crate::clone_helpers::transform_array_access_clone;
-    use crate::function::{transform_string_concat, transform_call_args};
+    use crate::translate::conditional_translate::expand_conditional_value;
+        use crate::function::{transform_string_concat, transform_call_args};
     
-    let mut expanded_value = expand_value(value_part, None);
-    expanded_value = transform_array_access_clone(&expanded_value);
+    let mut expanded_value = expand_conditional_value(value_part, None);
+    expanded_value = current_fn_ctx.clone_array_access(&expanded_value);
     if current_fn_ctx.is_inside() {
         expanded_value = transform_string_concat(&expanded_value, current_fn_ctx);
     }
@@ -177,7 +188,7 @@ fn find_matching_paren(s: &str) -> Option<usize> {
     let mut in_string = false;
     let mut prev_char = ' ';
     
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         // Handle string literals
         if c == '"' && prev_char != '\\' {
             in_string = !in_string;
@@ -22,6 +22,7 @@ use crate::function::{
     CurrentFunctionContext,
 };
 use crate::lowering::lookahead_lowering::check_next_line_is_where;
+use crate::translate::function_def_translate::apply_signature_rules;
 
 /// Result of processing a multi-line function signature accumulation
 pub enum MultilineFnResult {
@@ -32,6 +33,12 @@ pub enum MultilineFnResult {
         output: String,
         has_body: bool,
     },
+    /// Signature failed to parse - the caller reports `error` and skips
+    /// the body (if any).
+    Malformed {
+        error: String,
+        has_body: bool,
+    },
 }
 
 /// Check if a line starts a multi-line function signature
@@ -108,19 +115,20 @@ pub fn process_multiline_fn_signature(
     // Generate output
     let output = match parse_function_line(acc) {
         FunctionParseResult::RustSPlusSignature(sig) => {
-            if is_trait_method {
+            let output = if is_trait_method {
                 // Trait method declaration - add semicolon
                 format!("{}{};", leading_ws, signature_to_rust_with_where(&sig, true))
             } else {
                 // Function with where clause or regular function
                 format!("{}{}", leading_ws, signature_to_rust_with_where(&sig, next_line_is_where))
-            }
+            };
+            apply_signature_rules(output, acc, leading_ws)
         }
         FunctionParseResult::RustPassthrough => {
             format!("{}{}", leading_ws, acc)
         }
-        FunctionParseResult::Error(e) => {
-            format!("{}// COMPILE ERROR: {}\n{}{}", leading_ws, e, leading_ws, acc)
+        FunctionParseResult::Error(error) => {
+            return MultilineFnResult::Malformed { error, has_body };
         }
         FunctionParseResult::NotAFunction => {
             format!("{}{}", leading_ws, acc)
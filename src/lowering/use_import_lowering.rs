@@ -1,9 +1,13 @@
 //! Use Import Lowering
 //!
-//! Handles multi-line `use` import statements in RustS+.
+//! Handles multi-line `use` import statements in RustS+ and the path sugar
+//! of `use` declarations: dots separate path segments and a group follows
+//! its prefix directly.
 //!
 //! Example:
 //! ```text
+//! use std.collections.HashMap      →  use std::collections::HashMap;
+//! use std.io { Read, Write }       →  use std::io::{Read, Write};
 //! use std::{
 //!     collections::HashMap,
 //!     sync::Arc,
@@ -48,19 +52,62 @@ pub fn process_use_import_line(
     
     // Process line inside use import mode
     if use_import_mode.is_active() {
-        let transformed = transform_use_import_item(clean_line);
+        let transformed = transform_use_import_item(&lower_use_path(clean_line));
         return UseImportResult::Handled(transformed);
     }
     
     // Check if this line starts a new use import block
     if let Some(is_pub) = is_multiline_use_import_start(trimmed) {
         use_import_mode.enter(brace_depth, is_pub);
-        return UseImportResult::Handled(format!("{}{}", leading_ws, trimmed));
+        return UseImportResult::Handled(format!("{}{}", leading_ws, lower_use_path(clean_line.trim())));
+    }
+    
+    // Single-line declaration written with path sugar; plain Rust `use`
+    // lines are passed through unchanged
+    let code = clean_line.trim();
+    if code.starts_with("use ") || code.starts_with("pub use ") {
+        let code = code.trim_end_matches(';').trim_end();
+        let lowered = lower_use_path(code);
+        if lowered != code {
+            return UseImportResult::Handled(format!("{}{};", leading_ws, lowered));
+        }
     }
     
     UseImportResult::NotHandled
 }
 
+/// Rewrite RustS+ path sugar in (part of) a `use` declaration: `.` between
+/// segments becomes `::`, and a `{ .. }` group written after its prefix
+/// gets the `::` and loses the padding spaces
+pub fn lower_use_path(code: &str) -> String {
+    let mut out = String::with_capacity(code.len() + 8);
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => out.push_str("::"),
+            '{' => {
+                let prefix_len = out.trim_end().len();
+                let after_segment = out[..prefix_len].ends_with(|p: char| crate::ident::is_ident_continue(p));
+                if after_segment {
+                    out.truncate(prefix_len);
+                    out.push_str("::");
+                }
+                out.push('{');
+                while chars.peek().is_some_and(|n| *n == ' ') {
+                    chars.next();
+                }
+            }
+            '}' => {
+                let content_len = out.trim_end_matches(' ').len();
+                out.truncate(content_len);
+                out.push('}');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -81,4 +128,26 @@ mod tests {
         mode.exit();
         assert!(!mode.is_active());
     }
+    
+    #[test]
+    fn test_lower_use_path() {
+        assert_eq!(lower_use_path("use std.collections.HashMap"), "use std::collections::HashMap");
+        assert_eq!(lower_use_path("use std.io { Read, Write }"), "use std::io::{Read, Write}");
+        assert_eq!(lower_use_path("pub use std.fmt.{self, Display}"), "pub use std::fmt::{self, Display}");
+        assert_eq!(lower_use_path("use std.sync {"), "use std::sync::{");
+        assert_eq!(lower_use_path("    atomic.AtomicUsize"), "    atomic::AtomicUsize");
+        assert_eq!(lower_use_path("use std::io::{Read, Write}"), "use std::io::{Read, Write}");
+    }
+    
+    #[test]
+    fn test_single_line_sugar() {
+        let mut mode = UseImportMode::new();
+        let lowered = |line: &str, mode: &mut UseImportMode| match process_use_import_line(line, line, "", 0, mode) {
+            UseImportResult::Handled(s) => Some(s),
+            UseImportResult::NotHandled => None,
+        };
+        assert_eq!(lowered("use std.io { Read, Write }", &mut mode).as_deref(), Some("use std::io::{Read, Write};"));
+        assert_eq!(lowered("use std::fmt", &mut mode), None);
+        assert!(!mode.is_active());
+    }
 }
\ No newline at end of file
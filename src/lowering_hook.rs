@@ -0,0 +1,179 @@
+//! Lowering Hooks for Project-Specific Rules
+//!
+//! Embedders can add their own line lowerings (custom DSL keywords, company
+//! macros) without forking the compiler. A hook is registered on the
+//! [`crate::compiler::Compiler`] builder:
+//!
+//! ```text
+//! use rustsp::{Compiler, LineCtx, LoweringHook, Rewrite};
+//!
+//! struct Audit;
+//!
+//! impl LoweringHook for Audit {
+//!     fn name(&self) -> &str { "audit" }
+//!     fn on_line(&self, ctx: &LineCtx) -> Option<Rewrite> {
+//!         let event = ctx.code.strip_prefix("audit ")?;
+//!         Some(Rewrite::line(format!("audit_log::record({});", event)))
+//!     }
+//! }
+//!
+//! let compiler = Compiler::builder().hook(Audit).build();
+//! ```
+//!
+//! ## Ordering
+//!
+//! Hooks see a line after the mode handlers (`use` groups, array/struct
+//! literals, match arms, `if`/`match` headers) and the item handlers
+//! (struct/enum/fn definitions) have passed on it, and before const/static,
+//! Rust passthrough and assignment translation. Lines consumed earlier never
+//! reach a hook. Hooks run in registration order; the first one that
+//! returns a [`Rewrite`] wins and the line is not lowered any further.
+//!
+//! ## Span accounting
+//!
+//! Every output line of a rewrite is attributed to the source line it
+//! replaces, so diagnostics and `--preserve-lines` output stay aligned (with
+//! `preserve_lines` the lines are joined onto that one line, so avoid `//`
+//! comment lines in a multi-line rewrite). Brace depth is tracked from the
+//! source line, so a rewrite must open and close the same braces as the
+//! line it replaces.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A source line offered to a [`LoweringHook`]
+#[derive(Debug, Clone)]
+pub struct LineCtx<'a> {
+    /// Source line number (1-based)
+    pub line: usize,
+    /// The line as written
+    pub source: &'a str,
+    /// Trimmed code: inline comment removed, conditions normalized
+    pub code: &'a str,
+    /// Leading whitespace, prepended to every rewritten line
+    pub indent: &'a str,
+    /// Brace depth after this line
+    pub depth: usize,
+    /// Name of the enclosing function, if any
+    pub function: Option<&'a str>,
+    /// Is the line inside a match arm body?
+    pub in_match_arm: bool,
+}
+
+/// Rust code that replaces a source line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rewrite {
+    lines: Vec<String>,
+}
+
+impl Rewrite {
+    /// Replace the line with one line of Rust
+    pub fn line(code: impl Into<String>) -> Self {
+        Rewrite { lines: vec![code.into()] }
+    }
+
+    /// Replace the line with several lines of Rust
+    pub fn lines<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Rewrite { lines: lines.into_iter().map(Into::into).collect() }
+    }
+
+    /// Drop the line from the output
+    pub fn remove() -> Self {
+        Rewrite { lines: Vec::new() }
+    }
+
+    /// Output lines (without indentation)
+    pub fn output_lines(&self) -> &[String] {
+        &self.lines
+    }
+}
+
+/// A project-specific lowering rule
+pub trait LoweringHook: Send + Sync {
+    /// Short identifier, e.g. `audit`
+    fn name(&self) -> &str;
+    /// Rewrite `ctx`, or `None` to leave the line to the compiler
+    fn on_line(&self, ctx: &LineCtx) -> Option<Rewrite>;
+}
+
+/// Registered hooks, in the order they run
+#[derive(Clone, Default)]
+pub struct LoweringHooks {
+    hooks: Vec<Arc<dyn LoweringHook>>,
+}
+
+impl LoweringHooks {
+    /// Add a hook at the end. A hook with the same name replaces the old one
+    /// (keeping its position).
+    pub fn register<H: LoweringHook + 'static>(&mut self, hook: H) {
+        match self.hooks.iter().position(|h| h.name() == hook.name()) {
+            Some(i) => self.hooks[i] = Arc::new(hook),
+            None => self.hooks.push(Arc::new(hook)),
+        }
+    }
+
+    /// Names of registered hooks, in order
+    pub fn names(&self) -> Vec<String> {
+        self.hooks.iter().map(|h| h.name().to_string()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// First rewrite offered for `ctx`, as indented output lines
+    pub(crate) fn rewrite(&self, ctx: &LineCtx) -> Option<Vec<String>> {
+        let rewrite = self.hooks.iter().find_map(|h| h.on_line(ctx))?;
+        Some(rewrite.lines.iter().map(|l| format!("{}{}", ctx.indent, l)).collect())
+    }
+}
+
+impl fmt::Debug for LoweringHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.hooks.iter().map(|h| h.name())).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Prefix(&'static str, &'static str);
+
+    impl LoweringHook for Prefix {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn on_line(&self, ctx: &LineCtx) -> Option<Rewrite> {
+            ctx.code.strip_prefix(self.1).map(|rest| Rewrite::line(format!("{}: {}", self.0, rest)))
+        }
+    }
+
+    fn ctx(code: &str) -> LineCtx<'_> {
+        LineCtx { line: 1, source: code, code, indent: "    ", depth: 1, function: None, in_match_arm: false }
+    }
+
+    #[test]
+    fn test_first_matching_hook_wins() {
+        let mut hooks = LoweringHooks::default();
+        hooks.register(Prefix("a", "x "));
+        hooks.register(Prefix("b", "x"));
+        assert_eq!(hooks.rewrite(&ctx("x 1")), Some(vec!["    a: 1".to_string()]));
+        assert_eq!(hooks.rewrite(&ctx("xy")), Some(vec!["    b: y".to_string()]));
+        assert_eq!(hooks.rewrite(&ctx("y")), None);
+    }
+
+    #[test]
+    fn test_same_name_replaces_in_place() {
+        let mut hooks = LoweringHooks::default();
+        hooks.register(Prefix("a", "x"));
+        hooks.register(Prefix("b", "x"));
+        hooks.register(Prefix("a", "y"));
+        assert_eq!(hooks.names(), vec!["a", "b"]);
+        assert_eq!(hooks.rewrite(&ctx("x1")), Some(vec!["    b: 1".to_string()]));
+    }
+}
@@ -0,0 +1,229 @@
+//! Lowering Rule Registry
+//!
+//! Every lowering rule (L-01 … L-12, see the README table) and the three
+//! multi-line modes can be switched off one at a time to narrow down a
+//! mis-compilation:
+//!
+//! ```text
+//! rustsp app.rss --emit-rs -o app.rs --disable-rule L-04 --disable-rule array
+//! ```
+//!
+//! A disabled rule leaves the construct it lowers as written; a disabled
+//! mode never starts, so the lines of an array literal, struct literal or
+//! `match` go through the line-by-line lowering on their own. The output is
+//! then usually not valid Rust. The sanity gate knows which rules were off
+//! (see [`crate::rust_sanity::check_rust_output_with_rules`]) and reports
+//! the failure as a consequence of them rather than as a compiler bug.
+//!
+//! The rule set is installed for one lowering by [`RuleSet::install`] and
+//! read with [`enabled`] where each rule is implemented, so the helpers need
+//! no extra parameter. It is per thread: compilations running at the same
+//! time do not see each other's set.
+
+use std::cell::Cell;
+use std::fmt;
+
+/// One lowering rule or mode that can be disabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoweringRule {
+    /// `fn f(x T) R` → `fn f(x: T) -> R`
+    L01,
+    /// Match arm `Pattern {` → `Pattern => {`
+    L02,
+    /// `x = 10` → `let x = 10;`
+    L03,
+    /// `mut x = 10` and inferred mutability → `let mut x = 10;`
+    L04,
+    /// `effects(..)` stripped from signatures
+    L05,
+    /// `[T]` parameter → `&[T]`
+    L06,
+    /// `effect write(x)` statements skipped
+    L07,
+    /// `println(..)` → `println!(..)`
+    L08,
+    /// `})` close of a parenthesized match arm body
+    L09,
+    /// `&arr` at call sites of `&[T]` parameters
+    L10,
+    /// `.clone()` of indexed elements and `for` loop borrowing
+    L11,
+    /// `#[derive(Clone)]` on cloned types
+    L12,
+    /// Multi-line struct and enum literals
+    Literal,
+    /// Multi-line array literals
+    Array,
+    /// `match` arms and bodies
+    Match,
+}
+
+/// Every rule, in the order they are listed
+pub const ALL_RULES: &[LoweringRule] = &[
+    LoweringRule::L01, LoweringRule::L02, LoweringRule::L03, LoweringRule::L04,
+    LoweringRule::L05, LoweringRule::L06, LoweringRule::L07, LoweringRule::L08,
+    LoweringRule::L09, LoweringRule::L10, LoweringRule::L11, LoweringRule::L12,
+    LoweringRule::Literal, LoweringRule::Array, LoweringRule::Match,
+];
+
+impl LoweringRule {
+    /// `L-04`, or `literal` / `array` / `match` for the modes
+    pub fn id(self) -> &'static str {
+        match self {
+            LoweringRule::L01 => "L-01",
+            LoweringRule::L02 => "L-02",
+            LoweringRule::L03 => "L-03",
+            LoweringRule::L04 => "L-04",
+            LoweringRule::L05 => "L-05",
+            LoweringRule::L06 => "L-06",
+            LoweringRule::L07 => "L-07",
+            LoweringRule::L08 => "L-08",
+            LoweringRule::L09 => "L-09",
+            LoweringRule::L10 => "L-10",
+            LoweringRule::L11 => "L-11",
+            LoweringRule::L12 => "L-12",
+            LoweringRule::Literal => "literal",
+            LoweringRule::Array => "array",
+            LoweringRule::Match => "match",
+        }
+    }
+
+    /// `L-04`, `l-4`, `L04`, `literal`, `array-mode`, …
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let name = s.trim().to_ascii_lowercase();
+        let mode = name.strip_suffix("-mode").unwrap_or(&name);
+        if let Some(rule) = ALL_RULES.iter().find(|rule| rule.id() == mode) {
+            return Ok(*rule);
+        }
+        let number = name.strip_prefix("l-").or_else(|| name.strip_prefix('l')).and_then(|n| n.parse::<usize>().ok());
+        match number {
+            Some(n @ 1..=12) => Ok(ALL_RULES[n - 1]),
+            _ => Err(format!(
+                "unknown lowering rule `{}` (expected L-01 … L-12, literal, array or match)",
+                s.trim()
+            )),
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << ALL_RULES.iter().position(|rule| *rule == self).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for LoweringRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// Which rules a lowering runs; all of them by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RuleSet {
+    disabled: u16,
+}
+
+thread_local! {
+    static ACTIVE: Cell<RuleSet> = const { Cell::new(RuleSet { disabled: 0 }) };
+}
+
+/// Does the lowering running on this thread apply `rule`?
+pub fn enabled(rule: LoweringRule) -> bool {
+    ACTIVE.with(|active| active.get().is_enabled(rule))
+}
+
+impl RuleSet {
+    pub fn disable(&mut self, rule: LoweringRule) {
+        self.disabled |= rule.bit();
+    }
+
+    pub fn is_enabled(&self, rule: LoweringRule) -> bool {
+        self.disabled & rule.bit() == 0
+    }
+
+    /// Is every rule on?
+    pub fn is_complete(&self) -> bool {
+        self.disabled == 0
+    }
+
+    pub fn active(&self) -> Vec<LoweringRule> {
+        ALL_RULES.iter().copied().filter(|rule| self.is_enabled(*rule)).collect()
+    }
+
+    pub fn disabled(&self) -> Vec<LoweringRule> {
+        ALL_RULES.iter().copied().filter(|rule| !self.is_enabled(*rule)).collect()
+    }
+
+    /// `active: L-01 L-02 … match` and, if any, `disabled: L-04`
+    pub fn summary(&self) -> String {
+        let ids = |rules: Vec<LoweringRule>| rules.iter().map(|rule| rule.id()).collect::<Vec<_>>().join(" ");
+        if self.is_complete() {
+            format!("active: {}", ids(self.active()))
+        } else {
+            format!("active: {}; disabled: {}", ids(self.active()), ids(self.disabled()))
+        }
+    }
+
+    /// Make this the set [`enabled`] reads on this thread until the guard
+    /// is dropped
+    pub fn install(self) -> RuleSetGuard {
+        RuleSetGuard { previous: ACTIVE.with(|active| active.replace(self)) }
+    }
+}
+
+/// Restores the previously installed [`RuleSet`] when dropped
+pub struct RuleSetGuard {
+    previous: RuleSet,
+}
+
+impl Drop for RuleSetGuard {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.set(self.previous));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(LoweringRule::parse("L-04"), Ok(LoweringRule::L04));
+        assert_eq!(LoweringRule::parse("l-4"), Ok(LoweringRule::L04));
+        assert_eq!(LoweringRule::parse("L12"), Ok(LoweringRule::L12));
+        assert_eq!(LoweringRule::parse("array"), Ok(LoweringRule::Array));
+        assert_eq!(LoweringRule::parse("Match-Mode"), Ok(LoweringRule::Match));
+        assert!(LoweringRule::parse("L-13").is_err());
+        assert!(LoweringRule::parse("L-0").is_err());
+        assert!(LoweringRule::parse("struct").is_err());
+        for rule in ALL_RULES {
+            assert_eq!(LoweringRule::parse(rule.id()), Ok(*rule));
+        }
+    }
+
+    #[test]
+    fn test_rule_set() {
+        let mut rules = RuleSet::default();
+        assert!(rules.is_complete());
+        assert_eq!(rules.active().len(), ALL_RULES.len());
+        rules.disable(LoweringRule::L04);
+        rules.disable(LoweringRule::Array);
+        assert!(!rules.is_enabled(LoweringRule::L04));
+        assert!(rules.is_enabled(LoweringRule::L05));
+        assert_eq!(rules.disabled(), vec![LoweringRule::L04, LoweringRule::Array]);
+        assert!(rules.summary().ends_with("match; disabled: L-04 array"), "{}", rules.summary());
+    }
+
+    #[test]
+    fn test_install_is_scoped() {
+        let mut rules = RuleSet::default();
+        rules.disable(LoweringRule::L08);
+        assert!(enabled(LoweringRule::L08));
+        {
+            let _guard = rules.install();
+            assert!(!enabled(LoweringRule::L08));
+            assert!(enabled(LoweringRule::L01));
+            std::thread::spawn(|| assert!(enabled(LoweringRule::L08))).join().unwrap();
+        }
+        assert!(enabled(LoweringRule::L08));
+    }
+}
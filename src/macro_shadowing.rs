@@ -0,0 +1,157 @@
+//! Bindings that shadow macro names
+//!
+//! RustS+ lets code call std macros without `!` (`format("{}", x)`), so the
+//! lowering appends `!` to every call of a known macro name (L-08). A
+//! binding with such a name - `format = compute()`, a parameter `vec Items`,
+//! `for print in printers` - turns later `format(...)` calls in its scope
+//! into calls of the binding. This module finds those scopes in the
+//! generated Rust so the macro passes leave the calls alone; Stage 1 warns
+//! about the declarations in the RustS+ source (RSPL087).
+
+use crate::helpers::{is_function_definition, RUST_MACROS};
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::translate::macro_translate::MACROS_TO_TRANSFORM;
+
+/// Is `name` called as a macro when written without `!`?
+pub fn is_macro_name(name: &str) -> bool {
+    RUST_MACROS.contains(&name) || MACROS_TO_TRANSFORM.contains(&name)
+}
+
+/// For each line of generated Rust, the macro names bound by a `let`,
+/// function parameter or `for` loop that is in scope on that line.
+///
+/// A binding is in scope from the line after its declaration (the value of
+/// `let format = format(..)` still calls the macro) until its block closes.
+pub fn shadowed_macros_per_line<S: AsRef<str>>(lines: &[S]) -> Vec<Vec<String>> {
+    // (name, brace depth of the block it lives in)
+    let mut bindings: Vec<(String, usize)> = Vec::new();
+    let mut depth = 0usize;
+    let mut result = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        let trimmed = line.as_ref().trim();
+        result.push(bindings.iter().map(|(name, _)| name.clone()).collect());
+
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        let new_depth = (depth + opens).saturating_sub(closes);
+
+        if let Some(name) = let_binding(trimmed) {
+            bindings.push((name, depth));
+        }
+        // Parameters and loop variables live in the block the line opens
+        for name in parameter_bindings(trimmed).into_iter().chain(for_binding(trimmed)) {
+            bindings.push((name, depth + 1));
+        }
+
+        bindings.retain(|(_, d)| *d <= new_depth);
+        depth = new_depth;
+    }
+    result
+}
+
+/// `let [mut] format = ...` / `let format: T = ...`
+fn let_binding(trimmed: &str) -> Option<String> {
+    let rest = trimmed.strip_prefix("let ")?.trim_start();
+    let rest = rest.strip_prefix("mut ").unwrap_or(rest).trim_start();
+    let name = leading_ident(rest);
+    let after = rest[name.len()..].trim_start();
+    let declares = after.starts_with(':') || after.starts_with('=') || after.starts_with(';');
+    (declares && is_macro_name(name)).then(|| name.to_string())
+}
+
+/// `for format in ...`
+fn for_binding(trimmed: &str) -> Option<String> {
+    let rest = trimmed.strip_prefix("for ")?;
+    let name = leading_ident(rest);
+    let declares = rest[name.len()..].trim_start().starts_with("in ");
+    (declares && is_macro_name(name)).then(|| name.to_string())
+}
+
+/// Parameters of a Rust signature: `fn show(format: Fmt, mut vec: Vec<u8>) {`
+fn parameter_bindings(trimmed: &str) -> Vec<String> {
+    if !is_function_definition(trimmed) {
+        return Vec::new();
+    }
+    let Some(open) = trimmed.find('(') else { return Vec::new() };
+    let mut depth = 0i32;
+    let mut end = trimmed.len();
+    for (i, c) in trimmed[open..].char_indices() {
+        match c {
+            '(' | '[' | '<' => depth += 1,
+            ')' | ']' | '>' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = open + i;
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut param_depth = 0i32;
+    let mut start = open + 1;
+    for (i, c) in trimmed[open + 1..end].char_indices() {
+        match c {
+            '(' | '[' | '<' => param_depth += 1,
+            ')' | ']' | '>' => param_depth -= 1,
+            ',' if param_depth == 0 => {
+                names.extend(parameter_name(&trimmed[start..open + 1 + i]));
+                start = open + 2 + i;
+            }
+            _ => {}
+        }
+    }
+    names.extend(parameter_name(&trimmed[start..end]));
+    names
+}
+
+fn parameter_name(param: &str) -> Option<String> {
+    let param = param.trim();
+    let param = param.strip_prefix("mut ").unwrap_or(param).trim_start();
+    let name = leading_ident(param);
+    let after = param[name.len()..].trim_start();
+    (after.starts_with(':') && !after.starts_with("::") && is_macro_name(name)).then(|| name.to_string())
+}
+
+fn leading_ident(s: &str) -> &str {
+    let end = s.find(|c: char| !crate::ident::is_ident_continue(c)).unwrap_or(s.len());
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binding_scopes() {
+        let lines = [
+            "fn main() {",
+            "    let format = format!(\"{}\", 1);",
+            "    if true {",
+            "        let vec = make();",
+            "        vec(1);",
+            "    }",
+            "    vec(2);",
+            "}",
+            "fn other(print: Printer) {",
+            "    print(1);",
+            "}",
+            "format(3);",
+        ];
+        let scopes = shadowed_macros_per_line(&lines);
+        assert!(scopes[1].is_empty());
+        assert_eq!(scopes[2], vec!["format"]);
+        assert_eq!(scopes[4], vec!["format", "vec"]);
+        assert_eq!(scopes[6], vec!["format"]);
+        assert_eq!(scopes[9], vec!["print"]);
+        assert!(scopes[11].is_empty());
+    }
+
+    #[test]
+    fn test_non_bindings() {
+        let lines = ["fn main() {", "    let formatted = format!(\"x\");", "    let (a, b) = pair;", "    for x in xs {", "    }", "}"];
+        assert!(shadowed_macros_per_line(&lines).iter().all(Vec::is_empty));
+    }
+}
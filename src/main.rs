@@ -19,6 +19,7 @@
 //! │    → Logic-04: Implicit mutation detection                          │
 //! │    → Logic-05: Unclear intent patterns                              │
 //! │    → Logic-06: Same-scope reassignment without mut                  │
+//! │    → Logic-07: `loop` without exit (warning only)                   │
 //! │    → Effect-01: Undeclared effect validation (STRUCTURAL)           │
 //! │    → Effect-02: Effect leak detection                               │
 //! │    → Effect-03: Pure calling effectful detection                    │
@@ -56,19 +57,57 @@ use std::env;
 use std::fs;
 use std::path::Path;
 use std::process::{Command, Stdio, exit};
+use std::time::Instant;
 use std::collections::HashMap;
 
-use rustsp::parse_rusts;
-use rustsp::error_msg::map_rust_error;
+use rustsp::{lower_rusts, lower_rusts_mapped, LoweringOptions, LoweringRule, RuleSet};
+use rustsp::error_msg::{effect_errors, map_rust_error, RsplError, SourceLocation};
 use rustsp::anti_fail_logic::{
-    check_logic, check_logic_no_effects, check_logic_custom,
-    format_logic_errors, ansi, analyze_functions
+    check_logic, check_logic_custom, parse_test_effects,
+    EffectSignature, DEFAULT_TEST_EFFECTS, logic_warnings, ansi, analyze_functions
+};
+use rustsp::rust_sanity::{
+    check_rust_output_with_rules, disabled_ids, format_internal_error, format_internal_error_with_rules,
+    audit_rust_output, AUDIT_BACKEND,
+};
+use rustsp::source_map::{format_mapped_error, map_rustc_errors_through, parse_rustc_errors, LineTable, SourceMap};
+use rustsp::symbol_index::{count_by_kind, SymbolIndex, INDEX_FILE};
+use rustsp::untyped_report::{find_untyped_bindings, format_untyped_report};
+use rustsp::derive_report::{explain_derives, format_derive_report};
+use rustsp::output_target::{rustc_command, OutputTarget, WriteToFile};
+use rustsp::rustc_cache::{RustcCache, CacheStatus, rustc_version};
+use rustsp::doctor::{run_checks, toolchain_preflight, Check, CheckStatus};
+use rustsp::verbatim::mask_verbatim_blocks;
+use rustsp::batch_analysis::{analyze_dir, BatchReport};
+use rustsp::effect_detector::{with_registry, EffectCategory};
+use rustsp::effect_db::{install_effect_db, EffectDb};
+use rustsp::rust_project::write_rust_project;
+use rustsp::rename::{rename_in_program, rename_symbol, SymbolKind};
+use rustsp::effects_diff::{diff_effects, format_effects_diff};
+use rustsp::target_cfg::Target;
+use rustsp::input_limits::{check_input_limits, InputLimits};
+use rustsp::edition::Edition;
+use rustsp::contracts::ContractMode;
+use rustsp::exit_status::{count_functions, ExitStatus, RunSummary};
+use rustsp::json_diagnostics::{internal_error_to_json, render_errors, render_warnings, rustc_error_to_json, ErrorFormat};
+use rustsp::replay::{recorded_args, Recording};
+use rustsp::fixes::{fix_source, unified_diff};
+use rustsp::modules::{declares_file_modules, ModuleMap, Program};
+use rustsp::cargo_workspace::{split_crates, write_cargo_workspace};
+use rustsp::callgraph::{CallGraph, CallGraphFormat};
+use rustsp::registry_check::check_registries;
+use rustsp::migrate::migrate;
+use rustsp::build_report::BuildReport;
+use rustsp::diagnostic_groups::{group_diagnostics, DEFAULT_DIAGNOSTIC_LOCATIONS};
+use rustsp::lowered_ir::{build_ir, IrProgram};
+use rustsp::entry::{
+    rewrite_test_blocks, rename_user_main, defines_main, find_function_return,
+    generate_entry_main, generate_test_harness,
 };
-use rustsp::rust_sanity::{check_rust_output, format_internal_error};
 
 // NEW: IR module imports
 use rustsp::ast::EffectDecl;
-use rustsp::eir::{Effect, EffectSet, EffectContext, EffectInference, EffectDependencyGraph};
+use rustsp::eir::{Effect, EffectAlgebra, EffectSet, EffectContext, EffectInference, EffectDependencyGraph};
 use rustsp::parser::{Lexer, FunctionParser, extract_function_signatures};
 use rustsp::hir::{BindingId, BindingInfo, ScopeResolver};
 
@@ -121,7 +160,7 @@ fn convert_effect_decl(decl: &EffectDecl) -> Option<Effect> {
         EffectDecl::Alloc => Some(Effect::Alloc),
         EffectDecl::Panic => Some(Effect::Panic),
         EffectDecl::Read(_) => Some(Effect::Read(BindingId::new(0))), // Placeholder
-        EffectDecl::Write(_) => Some(Effect::Write(BindingId::new(0))), // Placeholder
+        EffectDecl::Write(_) | EffectDecl::WriteField { .. } => Some(Effect::Write(BindingId::new(0))), // Placeholder
     }
 }
 
@@ -158,65 +197,69 @@ fn detect_function_effects(source: &str, func_name: &str, start_line: usize) ->
             break; // End of function
         }
         
-        // Detect I/O effects
-        if detect_io_pattern(trimmed) {
-            effects.insert(Effect::Io);
-        }
-        
-        // Detect allocation effects
-        if detect_alloc_pattern(trimmed) {
-            effects.insert(Effect::Alloc);
-        }
-        
-        // Detect panic effects
-        if detect_panic_pattern(trimmed) {
-            effects.insert(Effect::Panic);
+        for category in with_registry(|r| r.detect_line(trimmed)) {
+            effects.insert(match category {
+                EffectCategory::Io => Effect::Io,
+                EffectCategory::Alloc => Effect::Alloc,
+                EffectCategory::Panic => Effect::Panic,
+                // Not modelled by the IR
+                EffectCategory::Concurrent => continue,
+            });
         }
     }
     
     effects
 }
 
-fn detect_io_pattern(line: &str) -> bool {
-    let patterns = [
-        "println!", "print!", "eprintln!", "eprint!",
-        "std::io", "File::", "stdin()", "stdout()", "stderr()",
-        ".read_exact(", ".read_to_string(", ".read_to_end(",
-        ".write_all(", ".flush(",
-        "fs::read", "fs::write", "fs::create", "fs::open",
-    ];
-    patterns.iter().any(|p| line.contains(p))
-}
-
-fn detect_alloc_pattern(line: &str) -> bool {
-    let patterns = [
-        "Vec::new", "Vec::with_capacity",
-        "String::new", "String::from", ".to_string()", ".to_owned()",
-        "Box::new", "Rc::new", "Arc::new",
-        "HashMap::new", "HashSet::new", "BTreeMap::new", "BTreeSet::new",
-        "vec!", ".clone()", ".collect()",
-    ];
-    patterns.iter().any(|p| line.contains(p))
-}
-
-fn detect_panic_pattern(line: &str) -> bool {
-    let patterns = [
-        "panic!", ".unwrap()", ".expect(",
-        "assert!", "assert_eq!", "assert_ne!",
-        "unreachable!", "unimplemented!", "todo!",
-    ];
-    patterns.iter().any(|p| line.contains(p))
-}
-
 //=============================================================================
 // RUST SANITY CHECK (L-05 Validation)
 //=============================================================================
 
-fn rust_sanity_check(rust_code: &str) -> Option<String> {
+/// `--audit`: parse the generated Rust with the audit backend and report
+/// each error at its .rss line. Errors are located in a line-aligned
+/// lowering of the source; if only the final output fails (or it came from
+/// IR), the generated line is reported instead.
+fn audit_lowering(
+    rust_code: &str,
+    source: &str,
+    input_path: &str,
+    options: &LoweringOptions,
+    from_ir: bool,
+) -> Option<String> {
+    let result = audit_rust_output(rust_code);
+    if result.is_valid {
+        return None;
+    }
+    
+    let aligned = if options.preserve_lines {
+        Some(result.clone())
+    } else if from_ir {
+        None
+    } else {
+        let aligned_options = LoweringOptions { preserve_lines: true, ..options.clone() };
+        lower_rusts(source, &aligned_options).ok()
+            .map(|aligned_code| audit_rust_output(&aligned_code))
+            .filter(|aligned_result| !aligned_result.is_valid)
+    };
+    
+    let report = match aligned {
+        Some(aligned_result) => {
+            let map = SourceMap::aligned(Path::new(input_path).to_path_buf());
+            aligned_result.errors.iter()
+                .map(|e| map.format_error_context(e.line, &e.message))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        None => format_internal_error(&result),
+    };
+    Some(report)
+}
+
+fn rust_sanity_check(rust_code: &str, rules: &RuleSet) -> Option<String> {
     // Use the comprehensive rust_sanity module
-    let result = check_rust_output(rust_code);
+    let result = check_rust_output_with_rules(rust_code, rules);
     if !result.is_valid {
-        return Some(format_internal_error(&result));
+        return Some(format_internal_error_with_rules(&result, rules));
     }
     
     // Additional legacy checks for backward compatibility
@@ -304,7 +347,13 @@ fn rust_sanity_check(rust_code: &str) -> Option<String> {
         }
         
         // Check for effects leaking to Rust output (CRITICAL)
-        if trimmed.contains("effects(") && (trimmed.contains("fn ") || trimmed.contains("pub fn ")) {
+        // Comments (e.g. a commented-out malformed signature) and effect
+        // badges (`#[doc = "rustsp: effects(io)"] fn ..`) are not code
+        let code = trimmed.strip_prefix("#[doc = \"")
+            .and_then(|rest| rest.split_once("\"] "))
+            .map_or(trimmed, |(_, code)| code);
+        let effects_kept = !rules.is_enabled(LoweringRule::L01) || !rules.is_enabled(LoweringRule::L05);
+        if !effects_kept && !code.starts_with("//") && code.contains("effects(") && (code.contains("fn ") || code.contains("pub fn ")) {
             return Some(format!(
                 "effects clause leaked to Rust output at line {}", line_num
             ));
@@ -318,6 +367,16 @@ fn rust_sanity_check(rust_code: &str) -> Option<String> {
 // USAGE & HELP
 //=============================================================================
 
+/// Keep generated Rust that failed a check as `<input>_debug.rs` for the
+/// bug report
+fn save_debug_output(input_path: &str, rust_code: &str, what: &str) {
+    let debug_filename = format!("{}_debug.rs",
+        Path::new(input_path).file_stem().and_then(|s| s.to_str()).unwrap_or("output"));
+    let _ = fs::write(&debug_filename, rust_code);
+    eprintln!("{}note{}: {} saved to: {}",
+        ansi::CYAN, ansi::RESET, what, debug_filename);
+}
+
 fn print_usage() {
     eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}", 
         ansi::BOLD_CYAN, ansi::RESET);
@@ -329,18 +388,65 @@ fn print_usage() {
         ansi::BOLD_CYAN, ansi::RESET);
     
     eprintln!("{}USAGE:{}", ansi::BOLD_YELLOW, ansi::RESET);
-    eprintln!("    rustsp <input.rss> [options]\n");
+    eprintln!("    rustsp <input.rss> [options]");
+    eprintln!("    rustsp test <input.rss> [options]   {}Run all `test` blocks{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp analyze-dir <dir> [--json <file>] [--csv <file>]  {}Analyze every .rss below <dir>{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp doctor [--target <triple>]  {}Check rustc, cargo and installed targets{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp rename <old> <new> <file.rss> [--dry-run]  {}Rename a function, type or binding{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp index <dir> [--definition <file:line:col>] [--references <file:line:col>]  {}Update .rustsp-index and query it{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp effects-diff <old.rss> <new.rss>  {}Report per-function effect changes{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp migrate <file.rs> [-o <file.rss>]  {}Lift a Rust file into a skeleton .rss{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp replay <session.rsprec> [options]  {}Re-run a run saved with --record{}\n", ansi::CYAN, ansi::RESET);
     
     eprintln!("{}OPTIONS:{}", ansi::BOLD_YELLOW, ansi::RESET);
     eprintln!("    {}-o <file>{}        Specify output file (binary or .rs)", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--emit-rs{}        Only emit .rs file without compiling", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--check{}          Run Stages 0-2 and the sanity gate without writing output or running rustc", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--emit-ir{}        Emit the lowered program as versioned JSON IR", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--from-ir{}        Input is a JSON IR document (skips Stages 0-2)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--entry <fn>{}     Generate `main` that calls <fn>", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--raw-errors{}     Show raw Rust errors (no mapping)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--verbose-diagnostics{} Print every diagnostic instead of grouping repeats per function, code and message", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--error-format <human|json>{} Print diagnostics as one JSON object per line (implies -q)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--diagnostic-locations <n>{} Locations shown per diagnostic group (default 3)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--no-cache{}       Always run rustc (skip the output cache)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--build-report{}   Show binary size, rustc time and the largest generated functions", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--stats <file>{}   Write the build report as JSON", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--preserve-lines{} Keep .rs line numbers identical to the .rss source", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--derive-clone-all{} Derive Clone on every struct/enum, not only cloned ones", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--disable-rule <rule>{} Leave a lowering rule (L-01 … L-12, literal, array, match) off to diagnose a mis-compilation", ansi::YELLOW, ansi::RESET);
+    eprintln!("    {}--verbose{}        Print the active lowering rules", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--target <triple>{} Check `effects(.. when ..)` and `#[cfg]` for <triple> and pass it to rustc", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--edition <year>{}   Rust edition of the output (2015, 2018, 2021, 2024), passed to rustc", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--effect-badges{}  Document each function's effects (`/// rustsp: pure`) in the output", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--license <SPDX>{} Start the output with an SPDX-License-Identifier header", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--attribution <text>{} Add attribution comment lines below the license header", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--opt-level <0-3>{} Inline trivial pure functions (1+), append strings in place (2+) and pass the level to rustc", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--release{}        Strip `requires`/`ensures` contracts and default to --opt-level 3", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--check-contracts{} Keep contracts as assert! in every build, --release too", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--fix{}            Apply the machine-applicable fixes of Stage 1 errors to the .rss (original kept as .bak)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--dry-run{}        With --fix: print the fixes as a unified diff instead of writing", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--audit{}          Parse the generated Rust with syn (feature `syn-audit`) and report errors at .rss lines", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--record <file>{}  Save input, options and compiler version for `rustsp replay`", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--reproducible{}   Keep machine-specific paths out of the output (IR `source` is the file name)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--rust-project{}   With --emit-rs -o: write rust-project.json next to the .rs", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--rust-project-dir <dir>{} Write rust-project.json into <dir> instead", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--emit-cargo-workspace <dir>{} Write a Cargo workspace with one crate per module file into <dir>", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--thiserror{}      With --emit-cargo-workspace: give each `!T` function a thiserror error enum", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--skip-logic{}     Skip logic check (DANGEROUS)", ansi::BOLD_RED, ansi::RESET);
     eprintln!("    {}--skip-effects{}   Skip effect checking only", ansi::YELLOW, ansi::RESET);
     eprintln!("    {}--strict-effects{} Require ALL effects to be declared", ansi::YELLOW, ansi::RESET);
+    eprintln!("    {}--test-effects <list>{} Effects `test` blocks may perform undeclared (default io,panic,alloc; none for no grant)", ansi::YELLOW, ansi::RESET);
+    eprintln!("    {}--max-nesting <n>{} Reject input with brackets nested deeper than <n> (default 256)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--max-line-length <n>{} Reject input with a line longer than <n> bytes (default 65536)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--max-file-size <n>{} Reject input larger than <n> bytes (default 16777216)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--effect-db <file>{} Read stdlib effect tables (TOML) over the built-in ones", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--use-ir{}         Use IR-based effect inference (NEW)", ansi::BOLD_GREEN, ansi::RESET);
     eprintln!("    {}--analyze{}        Analyze and show function effects", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--analyze-ir{}     Analyze with IR-based inference (NEW)", ansi::BOLD_GREEN, ansi::RESET);
+    eprintln!("    {}--emit-callgraph <dot|json>{} Export the call graph with each function's effects", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--report-untyped{} List bindings whose type could not be inferred", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}--explain-derives{} Show the source lines behind each injected #[derive(Clone)]", ansi::GREEN, ansi::RESET);
     eprintln!("    {}--quiet, -q{}      Suppress success messages", ansi::GREEN, ansi::RESET);
     eprintln!("    {}-h, --help{}       Show this help message", ansi::GREEN, ansi::RESET);
     eprintln!("    {}-V, --version{}    Show version\n", ansi::GREEN, ansi::RESET);
@@ -348,9 +454,22 @@ fn print_usage() {
     eprintln!("{}EXAMPLES:{}", ansi::BOLD_YELLOW, ansi::RESET);
     eprintln!("    rustsp main.rss -o myprogram        {}Compile to binary{}", ansi::CYAN, ansi::RESET);
     eprintln!("    rustsp main.rss --emit-rs           {}Print Rust to stdout{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --check             {}Report errors without running rustc{}", ansi::CYAN, ansi::RESET);
     eprintln!("    rustsp main.rss --emit-rs -o out.rs {}Write Rust to file{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --emit-rs -o gen/main.rs --rust-project  {}Browse output in rust-analyzer{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --emit-cargo-workspace gen  {}Build each module file as its own crate{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --emit-ir -o main.ir.json  {}Export IR for another backend{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.ir.json --from-ir -o app  {}Compile from an IR document{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --entry start       {}Use `fn start()` as entry point{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp test main.rss                {}Compile and run test blocks{}", ansi::CYAN, ansi::RESET);
     eprintln!("    rustsp main.rss --use-ir            {}Use IR-based analysis{}", ansi::CYAN, ansi::RESET);
-    eprintln!("    rustsp main.rss --analyze-ir        {}Show IR effect analysis{}\n", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --analyze-ir        {}Show IR effect analysis{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --emit-callgraph dot | dot -Tsvg > calls.svg  {}Draw the call graph{}", ansi::CYAN, ansi::RESET);
+    eprintln!("    rustsp main.rss --report-untyped    {}Find bindings worth annotating{}\n", ansi::CYAN, ansi::RESET);
+    
+    eprintln!("{}EXIT CODES:{}", ansi::BOLD_YELLOW, ansi::RESET);
+    eprintln!("    0 ok, 1 usage, 2 logic errors, 3 effect errors, 4 internal lowering error, 5 rustc failure");
+    eprintln!("    The last stderr line sums up the run: rustsp: ok functions=12 violations=0\n");
     
     eprintln!("{}EFFECT SYSTEM:{}", ansi::BOLD_YELLOW, ansi::RESET);
     eprintln!("    RustS+ requires functions to declare their effects:");
@@ -369,6 +488,7 @@ fn print_usage() {
     eprintln!("    {}io{}        - I/O operations (println!, File::*, etc.)", ansi::GREEN, ansi::RESET);
     eprintln!("    {}alloc{}     - Memory allocation (Vec::new, Box::new, etc.)", ansi::GREEN, ansi::RESET);
     eprintln!("    {}panic{}     - May panic (unwrap, expect, panic!)", ansi::GREEN, ansi::RESET);
+    eprintln!("    {}concurrent{} - Spawns or joins threads (spawn {{ .. }}, join h)", ansi::GREEN, ansi::RESET);
     eprintln!("    {}read(x){}   - Reads from parameter x", ansi::GREEN, ansi::RESET);
     eprintln!("    {}write(x){}  - Mutates parameter x", ansi::GREEN, ansi::RESET);
     eprintln!("");
@@ -384,11 +504,48 @@ fn print_usage() {
     eprintln!("");
 }
 
+/// Print the summary line and exit with its status
+fn finish(status: ExitStatus, functions: usize, violations: usize) -> ! {
+    eprintln!("{}", RunSummary { status, functions, violations });
+    exit(status.code());
+}
+
+/// `--build-report` and `--stats` after a successful Stage 3
+fn report_build(report: &BuildReport, input_path: &str, print: bool, stats_file: Option<&str>) {
+    if print {
+        eprint!("{}", report.format(input_path));
+    }
+    if let Some(path) = stats_file {
+        if let Err(e) = WriteToFile::new(path).emit(&report.to_json()) {
+            eprintln!("{}warning{}: writing stats '{}': {}",
+                ansi::BOLD_YELLOW, ansi::RESET, path, e);
+        }
+    }
+}
+
+/// Run a compiled test-harness binary and exit with its status
+fn run_test_binary(output_binary: &str) -> ! {
+    let run_path = if Path::new(output_binary).is_absolute() || output_binary.starts_with("./") {
+        output_binary.to_string()
+    } else {
+        format!("./{}", output_binary)
+    };
+    match Command::new(&run_path).status() {
+        Ok(status) => exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("{}error{}: running test binary '{}': {}",
+                ansi::BOLD_RED, ansi::RESET, run_path, e);
+            exit(1);
+        }
+    }
+}
+
 fn print_version() {
     println!("RustS+ Compiler v1.0.0 (Stable version)");
 }
 
-fn print_analysis(source: &str, file_name: &str) {
+/// Returns the number of functions with undeclared effects
+fn print_analysis(source: &str, file_name: &str) -> usize {
     let functions = analyze_functions(source, file_name);
     
     eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}",
@@ -400,7 +557,7 @@ fn print_analysis(source: &str, file_name: &str) {
     
     if functions.is_empty() {
         eprintln!("  No functions found.");
-        return;
+        return 0;
     }
     
     for (name, info) in &functions {
@@ -444,7 +601,33 @@ fn print_analysis(source: &str, file_name: &str) {
         } else {
             eprintln!("  {}├─ Detected:{} (none)", ansi::BLUE, ansi::RESET);
         }
-        
+
+        for closure in &info.closures {
+            eprintln!("  {}├─ Closure:{} |{}| (line {})",
+                ansi::BLUE, ansi::RESET, closure.params, closure.line_number);
+            match closure.declared_effects {
+                Some(ref declared) if !declared.is_pure => {
+                    eprintln!("  │    {}├─ Declared:{} effects({})", ansi::BLUE, ansi::RESET, declared.display());
+                }
+                Some(_) => eprintln!("  │    {}├─ Declared:{} (none - pure)", ansi::BLUE, ansi::RESET),
+                None => eprintln!("  │    {}├─ Declared:{} (not annotated)", ansi::BLUE, ansi::RESET),
+            }
+            if closure.detected_effects.is_pure {
+                eprintln!("  │    {}└─ Detected:{} (none)", ansi::BLUE, ansi::RESET);
+            } else {
+                let honest = closure.declared_effects.as_ref().map(|declared| {
+                    closure.detected_effects.propagatable().is_subset_of(declared)
+                });
+                let status = match honest {
+                    Some(true) => format!("{}✓{} ", ansi::GREEN, ansi::RESET),
+                    Some(false) => format!("{}✗{} ", ansi::RED, ansi::RESET),
+                    None => String::new(),
+                };
+                eprintln!("  │    {}└─ Detected:{} {}effects({})",
+                    ansi::BLUE, ansi::RESET, status, closure.detected_effects.display());
+            }
+        }
+
         if !info.calls.is_empty() {
             eprintln!("  {}└─ Calls:{} {}", ansi::BLUE, ansi::RESET, info.calls.join(", "));
         }
@@ -453,7 +636,7 @@ fn print_analysis(source: &str, file_name: &str) {
         if !undeclared.is_empty() && name != "main" {
             eprintln!("     {}⚠ UNDECLARED:{} {}", 
                 ansi::BOLD_RED, ansi::RESET,
-                undeclared.iter().map(|e| e.display()).collect::<Vec<_>>().join(", "));
+                undeclared.display());
         }
         
         eprintln!("");
@@ -478,10 +661,436 @@ fn print_analysis(source: &str, file_name: &str) {
     } else {
         eprintln!("  {}All effects properly declared ✓{}", ansi::BOLD_GREEN, ansi::RESET);
     }
+    violations
+}
+
+fn print_check(check: &Check) {
+    let mark = match check.status {
+        CheckStatus::Ok => format!("{}✓{}", ansi::BOLD_GREEN, ansi::RESET),
+        CheckStatus::Warning => format!("{}!{}", ansi::BOLD_YELLOW, ansi::RESET),
+        CheckStatus::Error => format!("{}✗{}", ansi::BOLD_RED, ansi::RESET),
+    };
+    eprintln!("  {} {:<8} {}", mark, check.name, check.detail);
+    if let Some(ref fix) = check.fix {
+        eprintln!("      {}fix:{} {}", ansi::BOLD_YELLOW, ansi::RESET, fix);
+    }
+}
+
+/// `rustsp doctor [--target <triple>]`
+///
+/// Exits 1 when a check fails (warnings do not fail).
+fn run_doctor(args: &[String]) -> ! {
+    let mut target: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--target" => {
+                let Some(triple) = args.get(i + 1) else {
+                    eprintln!("{}error{}: --target requires a target triple",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                };
+                target = Some(triple);
+                i += 2;
+            }
+            other => {
+                eprintln!("{}error{}: unexpected argument '{}' for doctor",
+                    ansi::BOLD_RED, ansi::RESET, other);
+                exit(1);
+            }
+        }
+    }
+    
+    eprintln!("{}RustS+ toolchain check{}", ansi::BOLD_CYAN, ansi::RESET);
+    let checks = run_checks(target);
+    for check in &checks {
+        print_check(check);
+    }
+    
+    let errors = checks.iter().filter(|c| c.status == CheckStatus::Error).count();
+    if errors == 0 {
+        eprintln!("\n{}✓ Ready to compile RustS+{}", ansi::BOLD_GREEN, ansi::RESET);
+        exit(0);
+    }
+    eprintln!("\n{}✗ {} problem(s) found{}", ansi::BOLD_RED, errors, ansi::RESET);
+    exit(1);
+}
+
+/// `rustsp rename <old> <new> <file.rss> [--dry-run]`
+///
+/// Rewrites the file in place; `--dry-run` prints the result instead.
+fn run_rename(args: &[String]) -> ! {
+    let mut positional: Vec<&str> = Vec::new();
+    let mut dry_run = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            other if !other.starts_with('-') => positional.push(other),
+            other => {
+                eprintln!("{}error{}: unexpected argument '{}' for rename",
+                    ansi::BOLD_RED, ansi::RESET, other);
+                exit(1);
+            }
+        }
+    }
+    let [old, new, file] = positional[..] else {
+        eprintln!("{}error{}: usage: rustsp rename <old> <new> <file.rss>",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    
+    let source = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}error{}: reading '{}': {}", ansi::BOLD_RED, ansi::RESET, file, e);
+            exit(1);
+        }
+    };
+    // A file with `mod` declarations is renamed with its module files
+    let renamed = if declares_file_modules(&source) {
+        Program::load(Path::new(file), &source).and_then(|program| rename_in_program(&program, old, new))
+            .map(|(outcome, changed)| {
+                let files = changed.into_iter().map(|f| (f.path.display().to_string(), f.source)).collect();
+                (outcome, files)
+            })
+    } else {
+        rename_symbol(&source, old, new).map(|outcome| {
+            let files = vec![(file.to_string(), outcome.source.clone())];
+            (outcome, files)
+        })
+    };
+    let (outcome, files): (_, Vec<(String, String)>) = match renamed {
+        Ok(renamed) => renamed,
+        Err(e) => {
+            eprintln!("{}error{}: cannot rename `{}`: {}", ansi::BOLD_RED, ansi::RESET, old, e);
+            exit(1);
+        }
+    };
+    
+    for (path, renamed) in &files {
+        if dry_run {
+            if files.len() > 1 {
+                println!("==> {} <==", path);
+            }
+            print!("{}", renamed);
+        } else if let Err(e) = fs::write(path, renamed) {
+            eprintln!("{}error{}: writing '{}': {}", ansi::BOLD_RED, ansi::RESET, path, e);
+            exit(1);
+        }
+    }
+    if dry_run {
+        eprintln!("{}note{}: would rename {} `{}` to `{}` ({} occurrence(s) in {} file(s))",
+            ansi::CYAN, ansi::RESET, outcome.kind.as_str(), old, new, outcome.occurrences, files.len());
+    } else {
+        eprintln!("{}✓{} Renamed {} `{}` to `{}` ({} occurrence(s) in {} file(s))",
+            ansi::BOLD_GREEN, ansi::RESET, outcome.kind.as_str(), old, new, outcome.occurrences, files.len());
+    }
+    exit(0);
+}
+
+/// `rustsp index <dir> [--definition <file:line:col>] [--references <file:line:col>]`
+///
+/// Updates `<dir>/.rustsp-index`, then answers the queries (if any) from it.
+fn run_index(args: &[String]) -> ! {
+    let mut dir: Option<&str> = None;
+    let mut queries: Vec<(&str, &str)> = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--definition" | "--references") => {
+                let Some(position) = args.get(i + 1) else {
+                    eprintln!("{}error{}: {} requires a position <file.rss:line:column>",
+                        ansi::BOLD_RED, ansi::RESET, flag);
+                    exit(1);
+                };
+                queries.push((flag, position));
+                i += 2;
+            }
+            other if dir.is_none() && !other.starts_with('-') => {
+                dir = Some(other);
+                i += 1;
+            }
+            other => {
+                eprintln!("{}error{}: unexpected argument '{}' for index",
+                    ansi::BOLD_RED, ansi::RESET, other);
+                exit(1);
+            }
+        }
+    }
+    let Some(dir) = dir else {
+        eprintln!("{}error{}: usage: rustsp index <dir> [--definition <file:line:col>] [--references <file:line:col>]",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    if !Path::new(dir).is_dir() {
+        eprintln!("{}error{}: '{}' is not a directory", ansi::BOLD_RED, ansi::RESET, dir);
+        exit(1);
+    }
+    
+    let (index, stats) = match SymbolIndex::open(Path::new(dir)) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, e);
+            exit(1);
+        }
+    };
+    let counts = count_by_kind(&index);
+    eprintln!("{}✓{} {} updated: {} file(s) indexed, {} unchanged, {} removed",
+        ansi::BOLD_GREEN, ansi::RESET, Path::new(dir).join(INDEX_FILE).display(),
+        stats.indexed, stats.unchanged, stats.removed);
+    eprintln!("  {} function(s), {} type(s), {} constant(s), {} binding(s)",
+        counts.get(&SymbolKind::Function).unwrap_or(&0),
+        counts.get(&SymbolKind::Struct).unwrap_or(&0) + counts.get(&SymbolKind::Enum).unwrap_or(&0),
+        counts.get(&SymbolKind::Constant).unwrap_or(&0),
+        counts.get(&SymbolKind::Binding).unwrap_or(&0));
+    
+    let mut found_all = true;
+    for (flag, position) in queries {
+        let parsed = position.rsplitn(3, ':').collect::<Vec<_>>();
+        let (file, line, column) = match parsed[..] {
+            [column, line, file] => match (line.parse::<usize>(), column.parse::<usize>()) {
+                (Ok(line), Ok(column)) => (file, line, column),
+                _ => (file, 0, 0),
+            },
+            _ => ("", 0, 0),
+        };
+        if line == 0 {
+            eprintln!("{}error{}: invalid position '{}' (expected <file.rss:line:column>)",
+                ansi::BOLD_RED, ansi::RESET, position);
+            exit(1);
+        }
+        let file = file.replace('\\', "/");
+        let Some(symbol) = index.find_definition(&file, line, column) else {
+            eprintln!("{}note{}: no symbol at {}", ansi::CYAN, ansi::RESET, position);
+            found_all = false;
+            continue;
+        };
+        if flag == "--definition" {
+            let d = &symbol.definition;
+            let effects = if symbol.effects.is_empty() { String::new() } else { format!(" effects({})", symbol.effects.join(", ")) };
+            println!("{}:{}:{}  {} {}{}", d.file, d.line, d.column, symbol.kind.as_str(), symbol.name, effects);
+        } else {
+            for r in index.find_references(&file, line, column) {
+                println!("{}:{}:{}", r.file, r.line, r.column);
+            }
+        }
+    }
+    exit(if found_all { 0 } else { 1 });
+}
+
+/// `rustsp effects-diff <old.rss> <new.rss>`
+///
+/// Exits 1 when a previously pure public function gained effects.
+/// `rustsp replay <record> [options]`: the recording, and the command line
+/// it stands for (recorded options first, then `options`)
+fn load_replay(args: &[String]) -> (Recording, Vec<String>) {
+    let Some(record_path) = args.get(2) else {
+        eprintln!("{}error{}: usage: rustsp replay <session.rsprec> [options]",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    let recording = match fs::read_to_string(record_path).map_err(|e| e.to_string()).and_then(|json| Recording::from_json(&json)) {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("{}error{}: reading replay '{}': {}", ansi::BOLD_RED, ansi::RESET, record_path, e);
+            exit(1);
+        }
+    };
+    if recording.is_foreign() {
+        eprintln!("{}warning{}: '{}' was recorded by rustsp {}, this is rustsp {}",
+            ansi::BOLD_YELLOW, ansi::RESET, record_path, recording.compiler, env!("CARGO_PKG_VERSION"));
+    }
+    let command_line = std::iter::once(args[0].clone())
+        .chain(recording.args.iter().cloned())
+        .chain(args[3..].iter().cloned())
+        .collect();
+    (recording, command_line)
+}
+
+fn run_effects_diff(args: &[String]) -> ! {
+    let [old, new] = args else {
+        eprintln!("{}error{}: usage: rustsp effects-diff <old.rss> <new.rss>",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    let mut sources = Vec::new();
+    for file in [old, new] {
+        match fs::read_to_string(file) {
+            Ok(s) => sources.push(s),
+            Err(e) => {
+                eprintln!("{}error{}: reading '{}': {}", ansi::BOLD_RED, ansi::RESET, file, e);
+                exit(1);
+            }
+        }
+    }
+    
+    let diff = diff_effects(&sources[0], &sources[1]);
+    print!("{}", format_effects_diff(&diff, old, new));
+    if diff.effect_creep().is_empty() {
+        exit(0);
+    }
+    eprintln!("{}error{}: effects were added to previously pure public functions",
+        ansi::BOLD_RED, ansi::RESET);
+    exit(1);
+}
+
+/// `rustsp migrate <file.rs> [-o <file.rss>]`
+///
+/// Writes the skeleton next to the input unless `-o` names another file,
+/// and never replaces an existing file without `-o`.
+fn run_migrate(args: &[String]) -> ! {
+    let mut input: Option<&str> = None;
+    let mut output: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-o" => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("{}error{}: -o requires an output file name", ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                };
+                output = Some(path);
+                i += 2;
+            }
+            other if input.is_none() && !other.starts_with('-') => {
+                input = Some(other);
+                i += 1;
+            }
+            other => {
+                eprintln!("{}error{}: unexpected argument '{}' for migrate",
+                    ansi::BOLD_RED, ansi::RESET, other);
+                exit(1);
+            }
+        }
+    }
+    let Some(input) = input else {
+        eprintln!("{}error{}: usage: rustsp migrate <file.rs> [-o <file.rss>]",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    let output = match output {
+        Some(path) => path.to_string(),
+        None => {
+            let path = Path::new(input).with_extension("rss");
+            if path.exists() {
+                eprintln!("{}error{}: '{}' already exists; choose another name with -o",
+                    ansi::BOLD_RED, ansi::RESET, path.display());
+                exit(1);
+            }
+            path.to_string_lossy().into_owned()
+        }
+    };
+    
+    let source = match fs::read_to_string(input) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}error{}: reading '{}': {}", ansi::BOLD_RED, ansi::RESET, input, e);
+            exit(1);
+        }
+    };
+    let migration = migrate(&source);
+    print!("{}", migration.report(input));
+    if let Err(e) = fs::write(&output, migration.to_rss()) {
+        eprintln!("{}error{}: writing '{}': {}", ansi::BOLD_RED, ansi::RESET, output, e);
+        exit(1);
+    }
+    eprintln!("{}✓{} Skeleton written to {}", ansi::BOLD_GREEN, ansi::RESET, output);
+    exit(0);
+}
+
+/// `rustsp analyze-dir <dir> [--json <file>] [--csv <file>]`
+///
+/// Exits 2 (logic) or 3 (effect) when any file has a violation, so it can
+/// gate CI, and ends with the summary line.
+fn run_analyze_dir(args: &[String]) -> ! {
+    let mut dir: Option<&str> = None;
+    let mut json_out: Option<&str> = None;
+    let mut csv_out: Option<&str> = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--json" | "--csv") => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("{}error{}: {} requires an output file name",
+                        ansi::BOLD_RED, ansi::RESET, flag);
+                    exit(1);
+                };
+                if flag == "--json" { json_out = Some(path); } else { csv_out = Some(path); }
+                i += 2;
+            }
+            other if dir.is_none() && !other.starts_with('-') => {
+                dir = Some(other);
+                i += 1;
+            }
+            other => {
+                eprintln!("{}error{}: unexpected argument '{}' for analyze-dir",
+                    ansi::BOLD_RED, ansi::RESET, other);
+                exit(1);
+            }
+        }
+    }
+    let Some(dir) = dir else {
+        eprintln!("{}error{}: analyze-dir requires a directory",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    };
+    if !Path::new(dir).is_dir() {
+        eprintln!("{}error{}: '{}' is not a directory", ansi::BOLD_RED, ansi::RESET, dir);
+        exit(1);
+    }
+    
+    let report = analyze_dir(Path::new(dir));
+    print_batch_report(&report);
+    
+    for (path, contents) in [(json_out, report.to_json()), (csv_out, report.to_csv())] {
+        if let Some(path) = path {
+            if let Err(e) = fs::write(path, contents) {
+                eprintln!("{}error{}: writing '{}': {}", ansi::BOLD_RED, ansi::RESET, path, e);
+                exit(1);
+            }
+            eprintln!("  Report written to {}", path);
+        }
+    }
+    
+    let errors: Vec<_> = report.files.iter().flat_map(|f| f.errors.iter().cloned()).collect();
+    finish(ExitStatus::for_errors(&errors), report.function_count(), report.violation_count());
+}
+
+fn print_batch_report(report: &BatchReport) {
+    eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}",
+        ansi::BOLD_CYAN, ansi::RESET);
+    eprintln!("{}║              RustS+ Batch Analysis                            ║{}",
+        ansi::BOLD_CYAN, ansi::RESET);
+    eprintln!("{}╚═══════════════════════════════════════════════════════════════╝{}\n",
+        ansi::BOLD_CYAN, ansi::RESET);
+    
+    for file in &report.files {
+        let status = match (&file.read_error, file.errors.len()) {
+            (Some(e), _) => format!("{}unreadable: {}{}", ansi::BOLD_RED, e, ansi::RESET),
+            (None, 0) => format!("{}✓{}", ansi::GREEN, ansi::RESET),
+            (None, n) => format!("{}✗ {} violation(s){}", ansi::RED, n, ansi::RESET),
+        };
+        eprintln!("  {} {}({} fn){} {}", file.path.display(), ansi::BLUE, file.functions, ansi::RESET, status);
+    }
+    eprintln!();
+    
+    eprintln!("{}Summary:{}", ansi::BOLD_YELLOW, ansi::RESET);
+    eprintln!("  Files analyzed: {}", report.files.len());
+    eprintln!("  Functions: {} ({} pure, {:.1}%)",
+        report.function_count(), report.pure_count(), report.purity_ratio() * 100.0);
+    if report.violation_count() == 0 {
+        eprintln!("  {}No violations ✓{}", ansi::BOLD_GREEN, ansi::RESET);
+        return;
+    }
+    eprintln!("  {}Violations: {}{}", ansi::BOLD_RED, report.violation_count(), ansi::RESET);
+    for (code, (category, count)) in report.violations_by_code() {
+        eprintln!("    {:<8} {:<18} {}", code, format!("[{}]", category), count);
+    }
 }
 
-/// NEW: Print IR-based analysis
-fn print_analysis_ir(source: &str, file_name: &str) {
+/// NEW: Print IR-based analysis; returns the number of functions with
+/// undeclared effects
+fn print_analysis_ir(source: &str, file_name: &str) -> usize {
     let effects = analyze_effects_ir(source);
     
     eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}",
@@ -493,7 +1102,7 @@ fn print_analysis_ir(source: &str, file_name: &str) {
     
     if effects.is_empty() {
         eprintln!("  No functions found.");
-        return;
+        return 0;
     }
     
     let bindings = HashMap::new();
@@ -509,11 +1118,8 @@ fn print_analysis_ir(source: &str, file_name: &str) {
         eprintln!("  {}├─ Line:{} {}", ansi::BLUE, ansi::RESET, line);
         
         if !declared.is_empty() {
-            let effects_str: Vec<String> = declared.iter()
-                .map(|e| e.display(&bindings))
-                .collect();
             eprintln!("  {}├─ Declared:{} effects({})", 
-                ansi::BLUE, ansi::RESET, effects_str.join(", "));
+                ansi::BLUE, ansi::RESET, declared.display(&bindings));
         } else {
             eprintln!("  {}├─ Declared:{} (none - pure)", ansi::BLUE, ansi::RESET);
         }
@@ -524,21 +1130,15 @@ fn print_analysis_ir(source: &str, file_name: &str) {
             } else {
                 format!("{}✗{}", ansi::RED, ansi::RESET)
             };
-            let effects_str: Vec<String> = detected.iter()
-                .map(|e| e.display(&bindings))
-                .collect();
             eprintln!("  {}├─ Detected:{} {} effects({})", 
-                ansi::BLUE, ansi::RESET, status, effects_str.join(", "));
+                ansi::BLUE, ansi::RESET, status, detected.display(&bindings));
         } else {
             eprintln!("  {}├─ Detected:{} (none)", ansi::BLUE, ansi::RESET);
         }
         
         if !undeclared.is_empty() && name != "main" {
-            let effects_str: Vec<String> = undeclared.iter()
-                .map(|e| e.display(&bindings))
-                .collect();
             eprintln!("     {}⚠ UNDECLARED:{} {}", 
-                ansi::BOLD_RED, ansi::RESET, effects_str.join(", "));
+                ansi::BOLD_RED, ansi::RESET, undeclared.display(&bindings));
         }
         
         eprintln!("");
@@ -565,6 +1165,7 @@ fn print_analysis_ir(source: &str, file_name: &str) {
     }
     
     eprintln!("\n{}Inference Method:{} Structural (IR-based)", ansi::CYAN, ansi::RESET);
+    violations
 }
 
 //=============================================================================
@@ -572,7 +1173,7 @@ fn print_analysis_ir(source: &str, file_name: &str) {
 //=============================================================================
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
     
     // Version check
     if args.len() == 2 && (args[1] == "--version" || args[1] == "-V") {
@@ -586,20 +1187,99 @@ fn main() {
         exit(if args.len() < 2 { 1 } else { 0 });
     }
     
+    // `rustsp analyze-dir <dir>` - aggregate Stage 1 analysis over a tree
+    if args[1] == "analyze-dir" {
+        run_analyze_dir(&args[2..]);
+    }
+    
+    // `rustsp doctor` - check the toolchain Stage 3 depends on
+    if args[1] == "doctor" {
+        run_doctor(&args[2..]);
+    }
+    
+    // `rustsp rename <old> <new> <file>` - registry-aware rename
+    if args[1] == "rename" {
+        run_rename(&args[2..]);
+    }
+    
+    // `rustsp index <dir>` - persisted symbol index for tooling
+    if args[1] == "index" {
+        run_index(&args[2..]);
+    }
+    
+    // `rustsp effects-diff <old> <new>` - per-function effect changes
+    if args[1] == "effects-diff" {
+        run_effects_diff(&args[2..]);
+    }
+    
+    // `rustsp migrate <file.rs>` - lift a Rust file into a skeleton .rss
+    if args[1] == "migrate" {
+        run_migrate(&args[2..]);
+    }
+    
+    // `rustsp replay <record>` - re-run a recorded session on its recorded source
+    let replay = if args[1] == "replay" {
+        let (recording, command_line) = load_replay(&args);
+        args = command_line;
+        Some(recording)
+    } else {
+        None
+    };
+    
     // Parse arguments
     let mut input_file: Option<String> = None;
     let mut output_file: Option<String> = None;
     let mut emit_rs_only = false;
+    let mut emit_ir = false;
+    let mut check_only = false;
+    let mut from_ir = false;
     let mut raw_errors = false;
     let mut skip_logic = false;
     let mut skip_effects = false;
     let mut strict_effects = false;
+    let mut test_effects = EffectSignature::with_effects(DEFAULT_TEST_EFFECTS.into_iter().collect());
     let mut analyze_only = false;
     let mut analyze_ir = false;  // NEW
+    let mut report_untyped = false;
+    let mut explain_derives_only = false;
+    let mut emit_callgraph: Option<CallGraphFormat> = None;
     let mut use_ir = false;       // NEW
     let mut quiet = false;
+    let mut entry_fn: Option<String> = None;
+    let mut no_cache = false;
+    let mut build_report = false;
+    let mut stats_file: Option<String> = None;
+    let mut preserve_lines = false;
+    let mut derive_clone_all = false;
+    let mut rust_project = false;
+    let mut rust_project_dir: Option<String> = None;
+    let mut cargo_workspace: Option<String> = None;
+    let mut license: Option<String> = None;
+    let mut attribution: Option<String> = None;
+    let mut reproducible = false;
+    let mut opt_level: Option<u8> = None;
+    let mut release = false;
+    let mut check_contracts = false;
+    let mut effect_badges = false;
+    let mut error_enums = false;
+    let mut target: Option<String> = None;
+    let mut edition: Option<Edition> = None;
+    let mut limits = InputLimits::default();
+    let mut audit = false;
+    let mut record: Option<String> = None;
+    let mut input_index: Option<usize> = None;
+    let mut verbose_diagnostics = false;
+    let mut verbose = false;
+    let mut rules = RuleSet::default();
+    let mut error_format = ErrorFormat::Human;
+    let mut fix = false;
+    let mut dry_run = false;
+    let mut diagnostic_locations = DEFAULT_DIAGNOSTIC_LOCATIONS;
+    
+    // `rustsp test <file>` - compile with a generated test-harness main and run it
+    let test_mode = args.get(1).is_some_and(|a| a == "test");
     
-    let mut i = 1;
+    let mut i = if test_mode { 2 } else { 1 };
     while i < args.len() {
         match args[i].as_str() {
             "-o" => {
@@ -616,38 +1296,323 @@ fn main() {
                 emit_rs_only = true;
                 i += 1;
             }
-            "--raw-errors" => {
-                raw_errors = true;
+            "--emit-ir" => {
+                emit_ir = true;
                 i += 1;
             }
-            "--skip-logic" => {
-                skip_logic = true;
-                eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}",
-                    ansi::BOLD_YELLOW, ansi::RESET);
-                eprintln!("{}║  WARNING: --skip-logic flag is DANGEROUS                      ║{}",
-                    ansi::BOLD_YELLOW, ansi::RESET);
-                eprintln!("{}║  Logic errors will NOT be caught before Rust compilation!     ║{}",
-                    ansi::BOLD_YELLOW, ansi::RESET);
-                eprintln!("{}╚═══════════════════════════════════════════════════════════════╝{}",
-                    ansi::BOLD_YELLOW, ansi::RESET);
+            "--check" => {
+                check_only = true;
                 i += 1;
             }
-            "--skip-effects" => {
-                skip_effects = true;
-                if !quiet {
-                    eprintln!("{}note{}: Effect checking disabled. Effects will not be validated.",
-                        ansi::CYAN, ansi::RESET);
-                }
+            "--from-ir" => {
+                from_ir = true;
                 i += 1;
             }
-            "--strict-effects" => {
-                strict_effects = true;
+            "--entry" => {
+                if i + 1 < args.len() {
+                    entry_fn = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --entry requires a function name",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--raw-errors" => {
+                raw_errors = true;
+                i += 1;
+            }
+            "--verbose-diagnostics" => {
+                verbose_diagnostics = true;
+                i += 1;
+            }
+            arg if arg == "--error-format" || arg.starts_with("--error-format=") => {
+                let (value, used) = match arg.strip_prefix("--error-format=") {
+                    Some(value) => (Some(value), 1),
+                    None => (args.get(i + 1).map(String::as_str), 2),
+                };
+                match value.and_then(ErrorFormat::parse) {
+                    Some(format) => {
+                        error_format = format;
+                        i += used;
+                    }
+                    None => {
+                        eprintln!("{}error{}: --error-format requires human or json",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--diagnostic-locations" => {
+                match args.get(i + 1).and_then(|n| n.parse::<usize>().ok()).filter(|n| *n >= 1) {
+                    Some(n) => {
+                        diagnostic_locations = n;
+                        i += 2;
+                    }
+                    None => {
+                        eprintln!("{}error{}: --diagnostic-locations requires a number of 1 or more",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--no-cache" => {
+                no_cache = true;
+                i += 1;
+            }
+            "--build-report" => {
+                build_report = true;
+                i += 1;
+            }
+            "--stats" => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("{}error{}: --stats requires a file (e.g. stats.json)",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                };
+                stats_file = Some(path.clone());
+                i += 2;
+            }
+            "--preserve-lines" => {
+                preserve_lines = true;
+                i += 1;
+            }
+            "--derive-clone-all" => {
+                derive_clone_all = true;
+                i += 1;
+            }
+            "--disable-rule" => {
+                match args.get(i + 1).map(|r| LoweringRule::parse(r)) {
+                    Some(Ok(rule)) => {
+                        rules.disable(rule);
+                        i += 2;
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, e);
+                        exit(1);
+                    }
+                    None => {
+                        eprintln!("{}error{}: --disable-rule requires a rule (L-01 … L-12, literal, array or match)",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--verbose" => {
+                verbose = true;
+                i += 1;
+            }
+            "--rust-project" => {
+                rust_project = true;
+                i += 1;
+            }
+            "--rust-project-dir" => {
+                if i + 1 < args.len() {
+                    rust_project = true;
+                    rust_project_dir = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --rust-project-dir requires a directory",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--emit-cargo-workspace" => {
+                if i + 1 < args.len() {
+                    cargo_workspace = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --emit-cargo-workspace requires a directory",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--license" => {
+                match args.get(i + 1) {
+                    Some(spdx) if !spdx.trim().is_empty() && !spdx.contains('\n') => {
+                        license = Some(spdx.clone());
+                        i += 2;
+                    }
+                    _ => {
+                        eprintln!("{}error{}: --license requires a one-line SPDX expression (e.g. MIT OR Apache-2.0)",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--attribution" => {
+                if i + 1 < args.len() {
+                    attribution = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --attribution requires a text",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--reproducible" => {
+                reproducible = true;
+                i += 1;
+            }
+            "--audit" => {
+                audit = true;
+                i += 1;
+            }
+            "--fix" => {
+                fix = true;
+                i += 1;
+            }
+            "--dry-run" => {
+                dry_run = true;
+                i += 1;
+            }
+            "--record" => {
+                if i + 1 < args.len() {
+                    record = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --record requires a file name (e.g. session.rsprec)",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--release" => {
+                release = true;
+                i += 1;
+            }
+            "--check-contracts" => {
+                check_contracts = true;
+                i += 1;
+            }
+            "--effect-badges" => {
+                effect_badges = true;
+                i += 1;
+            }
+            "--thiserror" => {
+                error_enums = true;
+                i += 1;
+            }
+            "--target" => {
+                if i + 1 < args.len() {
+                    target = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    eprintln!("{}error{}: --target requires a target triple (e.g. wasm32-unknown-unknown)",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                }
+            }
+            "--edition" => {
+                match args.get(i + 1).and_then(|e| Edition::parse(e)) {
+                    Some(e) => {
+                        edition = Some(e);
+                        i += 2;
+                    }
+                    None => {
+                        eprintln!("{}error{}: --edition requires 2015, 2018, 2021 or 2024",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--emit-callgraph" => {
+                match args.get(i + 1).and_then(|f| CallGraphFormat::parse(f)) {
+                    Some(format) => {
+                        emit_callgraph = Some(format);
+                        i += 2;
+                    }
+                    None => {
+                        eprintln!("{}error{}: --emit-callgraph requires dot or json",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--effect-db" => {
+                let Some(path) = args.get(i + 1) else {
+                    eprintln!("{}error{}: --effect-db requires a file (e.g. effects.toml)",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                };
+                match EffectDb::load(path) {
+                    Ok(db) => install_effect_db(db),
+                    Err(e) => {
+                        eprintln!("{}error{}: reading effect database '{}': {}",
+                            ansi::BOLD_RED, ansi::RESET, path, e);
+                        exit(1);
+                    }
+                }
+                i += 2;
+            }
+            "--opt-level" => {
+                match args.get(i + 1).and_then(|level| level.parse::<u8>().ok()).filter(|level| *level <= 3) {
+                    Some(level) => {
+                        opt_level = Some(level);
+                        i += 2;
+                    }
+                    None => {
+                        eprintln!("{}error{}: --opt-level requires a level from 0 to 3",
+                            ansi::BOLD_RED, ansi::RESET);
+                        exit(1);
+                    }
+                }
+            }
+            "--max-nesting" | "--max-line-length" | "--max-file-size" => {
+                let Some(limit) = args.get(i + 1).and_then(|n| n.parse::<usize>().ok()).filter(|n| *n > 0) else {
+                    eprintln!("{}error{}: {} requires a positive number",
+                        ansi::BOLD_RED, ansi::RESET, args[i]);
+                    exit(1);
+                };
+                match args[i].as_str() {
+                    "--max-nesting" => limits.max_nesting_depth = limit,
+                    "--max-line-length" => limits.max_line_length = limit,
+                    _ => limits.max_file_size = limit,
+                }
+                i += 2;
+            }
+            "--skip-logic" => {
+                skip_logic = true;
+                eprintln!("{}╔═══════════════════════════════════════════════════════════════╗{}",
+                    ansi::BOLD_YELLOW, ansi::RESET);
+                eprintln!("{}║  WARNING: --skip-logic flag is DANGEROUS                      ║{}",
+                    ansi::BOLD_YELLOW, ansi::RESET);
+                eprintln!("{}║  Logic errors will NOT be caught before Rust compilation!     ║{}",
+                    ansi::BOLD_YELLOW, ansi::RESET);
+                eprintln!("{}╚═══════════════════════════════════════════════════════════════╝{}",
+                    ansi::BOLD_YELLOW, ansi::RESET);
+                i += 1;
+            }
+            "--skip-effects" => {
+                skip_effects = true;
+                if !quiet {
+                    eprintln!("{}note{}: Effect checking disabled. Effects will not be validated.",
+                        ansi::CYAN, ansi::RESET);
+                }
+                i += 1;
+            }
+            "--strict-effects" => {
+                strict_effects = true;
                 if !quiet {
                     eprintln!("{}note{}: Strict effect mode enabled. ALL effects must be declared.",
                         ansi::CYAN, ansi::RESET);
                 }
                 i += 1;
             }
+            "--test-effects" => {
+                let Some(list) = args.get(i + 1) else {
+                    eprintln!("{}error{}: --test-effects requires a list of effects (e.g. io,panic) or none",
+                        ansi::BOLD_RED, ansi::RESET);
+                    exit(1);
+                };
+                match parse_test_effects(list) {
+                    Ok(grant) => test_effects = grant,
+                    Err(e) => {
+                        eprintln!("{}error{}: --test-effects: {}", ansi::BOLD_RED, ansi::RESET, e);
+                        exit(1);
+                    }
+                }
+                i += 2;
+            }
             "--use-ir" => {
                 use_ir = true;
                 if !quiet {
@@ -664,6 +1629,14 @@ fn main() {
                 analyze_ir = true;
                 i += 1;
             }
+            "--report-untyped" => {
+                report_untyped = true;
+                i += 1;
+            }
+            "--explain-derives" => {
+                explain_derives_only = true;
+                i += 1;
+            }
             "--quiet" | "-q" => {
                 quiet = true;
                 i += 1;
@@ -676,37 +1649,261 @@ fn main() {
                 }
                 if input_file.is_none() {
                     input_file = Some(arg.to_string());
+                    input_index = Some(i);
                 }
                 i += 1;
             }
         }
     }
     
-    // Validate input file
-    let input_path = match input_file {
-        Some(p) => p,
+    // `--release` strips contracts and optimizes unless told otherwise
+    let opt_level = opt_level.unwrap_or(if release { 3 } else { 0 });
+    let contracts = if check_contracts {
+        ContractMode::Always
+    } else if release {
+        ContractMode::Off
+    } else {
+        ContractMode::Debug
+    };
+    
+    // Progress lines would interleave with the JSON records on stderr
+    if error_format == ErrorFormat::Json {
+        quiet = true;
+    }
+    
+    if dry_run && !fix {
+        eprintln!("{}error{}: --dry-run requires --fix", ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    }
+    if check_only && (emit_rs_only || emit_ir || output_file.is_some()) {
+        eprintln!("{}error{}: --check writes no output and cannot be used with --emit-rs, --emit-ir or -o",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    }
+    if cargo_workspace.is_some() && (check_only || emit_rs_only || emit_ir || output_file.is_some()) {
+        eprintln!("{}error{}: --emit-cargo-workspace writes its own files and cannot be used with --check, --emit-rs, --emit-ir or -o",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    }
+    if error_enums && cargo_workspace.is_none() {
+        eprintln!("{}error{}: --thiserror needs the thiserror crate and can only be used with --emit-cargo-workspace",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    }
+    if fix && (from_ir || replay.is_some()) {
+        eprintln!("{}error{}: --fix rewrites the .rss input and cannot be used with --from-ir or replay",
+            ansi::BOLD_RED, ansi::RESET);
+        exit(1);
+    }
+    
+    let replaying = replay.is_some();
+    let (input_path, source) = match replay {
+        // A replay reads the recorded source, not the file on disk
+        Some(recording) => {
+            if !quiet {
+                eprintln!("{}note{}: replaying '{}' recorded by rustsp {}",
+                    ansi::CYAN, ansi::RESET, recording.file, recording.compiler);
+            }
+            (recording.file, recording.source)
+        }
         None => {
-            eprintln!("{}error{}: No input file specified",
-                ansi::BOLD_RED, ansi::RESET);
-            print_usage();
+            // Validate input file
+            let input_path = match input_file {
+                Some(p) => p,
+                None => {
+                    eprintln!("{}error{}: No input file specified",
+                        ansi::BOLD_RED, ansi::RESET);
+                    print_usage();
+                    exit(1);
+                }
+            };
+            
+            if !Path::new(&input_path).exists() {
+                eprintln!("{}error{}: Input file '{}' not found",
+                    ansi::BOLD_RED, ansi::RESET, input_path);
+                exit(1);
+            }
+            
+            // Read source file
+            match fs::read_to_string(&input_path) {
+                Ok(content) => (input_path, content),
+                Err(e) => {
+                    eprintln!("{}error{}: reading '{}': {}",
+                        ansi::BOLD_RED, ansi::RESET, input_path, e);
+                    exit(1);
+                }
+            }
+        }
+    };
+    
+    //=========================================================================
+    // MODULE FILES
+    // Every `mod name` without a body is spliced in from `name.rss`; a
+    // recording already holds the spliced program
+    //=========================================================================
+    
+    let (source, module_map) = if !from_ir && !replaying && declares_file_modules(&source) {
+        if fix {
+            eprintln!("{}error{}: --fix rewrites a single .rss file and cannot be used on '{}', which includes module files",
+                ansi::BOLD_RED, ansi::RESET, input_path);
             exit(1);
         }
+        match Program::load(Path::new(&input_path), &source) {
+            Ok(program) => (program.source, Some(program.map)),
+            Err(e) => {
+                eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, e);
+                exit(1);
+            }
+        }
+    } else {
+        (source, None)
     };
+    let functions = if from_ir { 0 } else { count_functions(&source) };
     
-    if !Path::new(&input_path).exists() {
-        eprintln!("{}error{}: Input file '{}' not found",
-            ansi::BOLD_RED, ansi::RESET, input_path);
-        exit(1);
+    if let Some(ref record_path) = record {
+        let recording = Recording::new(&input_path, recorded_args(&args, input_index), &source);
+        if let Err(e) = fs::write(record_path, recording.to_json()) {
+            eprintln!("{}error{}: writing '{}': {}", ansi::BOLD_RED, ansi::RESET, record_path, e);
+            finish(ExitStatus::Usage, functions, 0);
+        }
+        if !quiet {
+            eprintln!("{}note{}: recorded this run in '{}' (re-run with `rustsp replay {}`)",
+                ansi::CYAN, ansi::RESET, record_path, record_path);
+        }
     }
     
-    // Read source file
-    let source = match fs::read_to_string(&input_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("{}error{}: reading '{}': {}",
-                ansi::BOLD_RED, ansi::RESET, input_path, e);
-            exit(1);
+    //=========================================================================
+    // FIX MODE
+    // Apply the machine-applicable fixes of Stage 1 diagnostics to the file
+    //=========================================================================
+    
+    let source = if fix {
+        let target = target.as_deref().map_or_else(Target::host, Target::from_triple);
+        let stage1 = |source: &str| {
+            let (source, _) = rewrite_test_blocks(source, false);
+            check_logic_custom(&source, &input_path, !skip_effects, strict_effects, &test_effects, &target, &limits, contracts)
+                .err()
+                .unwrap_or_default()
+        };
+        let outcome = fix_source(&source, stage1);
+        if dry_run {
+            print!("{}", unified_diff(&input_path, &source, &outcome.source));
+            eprintln!("{}note{}: {} fix(es) would be applied to '{}'",
+                ansi::CYAN, ansi::RESET, outcome.applied.len(), input_path);
+            // The status a real --fix would leave Stage 1 with
+            let remaining = stage1(&outcome.source);
+            if !remaining.is_empty() && !quiet {
+                eprintln!("{}note{}: {} error(s) would remain after the fixes",
+                    ansi::CYAN, ansi::RESET, remaining.len());
+            }
+            finish(ExitStatus::for_errors(&remaining), functions, remaining.len());
+        }
+        if !outcome.applied.is_empty() {
+            let backup = format!("{}.bak", input_path);
+            if let Err(e) = fs::write(&backup, &source).and_then(|_| fs::write(&input_path, &outcome.source)) {
+                eprintln!("{}error{}: writing '{}': {}", ansi::BOLD_RED, ansi::RESET, input_path, e);
+                finish(ExitStatus::Usage, functions, 0);
+            }
+            if !quiet {
+                for description in &outcome.applied {
+                    eprintln!("{}fixed{}: {}", ansi::BOLD_GREEN, ansi::RESET, description);
+                }
+                eprintln!("{}note{}: applied {} fix(es) to '{}' (original kept as '{}')",
+                    ansi::CYAN, ansi::RESET, outcome.applied.len(), input_path, backup);
+            }
+        } else if !quiet {
+            eprintln!("{}note{}: no machine-applicable fixes for '{}'", ansi::CYAN, ansi::RESET, input_path);
         }
+        outcome.source
+    } else {
+        source
+    };
+    
+    // Repeats are grouped per function, code and title unless --verbose-diagnostics
+    // and reported at the module file they are in
+    let diagnostics = |errors: &[RsplError]| -> Vec<RsplError> {
+        let mut shown = if verbose_diagnostics {
+            errors.to_vec()
+        } else {
+            group_diagnostics(errors, &source, diagnostic_locations)
+        };
+        if let Some(modules) = &module_map {
+            modules.relocate(&mut shown);
+        }
+        shown
+    };
+    
+    //=========================================================================
+    // INPUT LIMITS
+    // Pathological input gets one RSPL029, not a cascade from every stage
+    //=========================================================================
+    
+    if !from_ir {
+        if let Err(mut errors) = check_input_limits(&source, &limits) {
+            for error in &mut errors {
+                error.location.file = input_path.clone();
+            }
+            if let Some(modules) = &module_map {
+                modules.relocate(&mut errors);
+            }
+            eprintln!("{}", render_errors(&errors, error_format));
+            finish(ExitStatus::for_errors(&errors), functions, errors.len());
+        }
+    }
+    
+    //=========================================================================
+    // IR INPUT
+    // The document was produced after Stage 2, so Stages 0-2 are skipped.
+    //=========================================================================
+    
+    let ir_rust = if from_ir {
+        if test_mode || entry_fn.is_some() || emit_ir || analyze_only || analyze_ir || report_untyped || explain_derives_only || emit_callgraph.is_some() {
+            eprintln!("{}error{}: --from-ir only supports compiling or --emit-rs",
+                ansi::BOLD_RED, ansi::RESET);
+            finish(ExitStatus::Usage, functions, 0);
+        }
+        match IrProgram::from_json(&source) {
+            Ok(program) => Some(program.to_rust()),
+            Err(e) => {
+                eprintln!("{}error{}: reading IR '{}': {}",
+                    ansi::BOLD_RED, ansi::RESET, input_path, e);
+                finish(ExitStatus::Usage, functions, 0);
+            }
+        }
+    } else {
+        None
+    };
+    
+    //=========================================================================
+    // ENTRY POINT & TEST BLOCKS
+    // Test blocks become functions in test mode and are blanked otherwise.
+    // Line count is preserved so diagnostics still match the original file.
+    //=========================================================================
+    
+    let (source, test_blocks) = rewrite_test_blocks(&source, test_mode);
+    let source = if test_mode { rename_user_main(&source) } else { source };
+    
+    if test_mode && test_blocks.is_empty() && !quiet {
+        eprintln!("{}warning{}: no `test` blocks found in '{}'",
+            ansi::BOLD_YELLOW, ansi::RESET, input_path);
+    }
+    
+    let entry_return = match entry_fn {
+        Some(ref name) if !test_mode => {
+            if defines_main(&source) {
+                eprintln!("{}error{}: --entry {} given but '{}' already defines `fn main`",
+                    ansi::BOLD_RED, ansi::RESET, name, input_path);
+                finish(ExitStatus::Usage, functions, 0);
+            }
+            match find_function_return(&source, name) {
+                Ok(ret) => Some(ret),
+                Err(e) => {
+                    eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, e);
+                    finish(ExitStatus::Usage, functions, 0);
+                }
+            }
+        }
+        _ => None,
     };
     
     //=========================================================================
@@ -714,8 +1911,8 @@ fn main() {
     //=========================================================================
     
     if analyze_ir {
-        print_analysis_ir(&source, &input_path);
-        exit(0);
+        let violations = print_analysis_ir(&source, &input_path);
+        finish(if violations > 0 { ExitStatus::Effect } else { ExitStatus::Ok }, functions, violations);
     }
     
     //=========================================================================
@@ -723,15 +1920,72 @@ fn main() {
     //=========================================================================
     
     if analyze_only {
-        print_analysis(&source, &input_path);
-        exit(0);
+        let violations = print_analysis(&source, &input_path);
+        finish(if violations > 0 { ExitStatus::Effect } else { ExitStatus::Ok }, functions, violations);
+    }
+    
+    //=========================================================================
+    // CALL GRAPH EXPORT
+    //=========================================================================
+    
+    if let Some(format) = emit_callgraph {
+        let graph = CallGraph::build(&analyze_functions(&source, &input_path)).render(format);
+        match output_file {
+            Some(ref out_path) => {
+                if let Err(e) = WriteToFile::new(out_path).emit(&graph) {
+                    eprintln!("{}error{}: writing '{}': {}",
+                        ansi::BOLD_RED, ansi::RESET, out_path, e);
+                    finish(ExitStatus::Usage, functions, 0);
+                }
+                if !quiet {
+                    eprintln!("{}✓ Call graph written to{}: {}",
+                        ansi::BOLD_GREEN, ansi::RESET, out_path);
+                }
+            }
+            None => print!("{}", graph),
+        }
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    //=========================================================================
+    // GRADUAL TYPING REPORT
+    //=========================================================================
+    
+    if report_untyped {
+        let bindings = find_untyped_bindings(&source);
+        eprint!("{}", format_untyped_report(&bindings, &input_path));
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    //=========================================================================
+    // DERIVE EXPLANATIONS
+    //=========================================================================
+    
+    if explain_derives_only {
+        let explanations = explain_derives(&source);
+        eprint!("{}", format_derive_report(&explanations, &input_path));
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    //=========================================================================
+    // TOOLCHAIN PREFLIGHT
+    // A run that ends in Stage 3 needs rustc - fail before lowering, not after
+    //=========================================================================
+    
+    if !emit_rs_only && !emit_ir && !check_only {
+        if let Err(check) = toolchain_preflight() {
+            print_check(&check);
+            eprintln!("{}note{}: run `rustsp doctor` to check the whole toolchain",
+                ansi::CYAN, ansi::RESET);
+            finish(ExitStatus::Rustc, functions, 0);
+        }
     }
     
     //=========================================================================
     // STAGE 0 & 1: ANTI-FAIL LOGIC CHECK
     //=========================================================================
     
-    if !skip_logic {
+    if !skip_logic && ir_rust.is_none() {
         if !quiet {
             if use_ir {
                 eprintln!("{}[Stage 0]{} Building IR and effect context...", 
@@ -746,19 +2000,30 @@ fn main() {
         
         // Use IR-based checking if requested
         if use_ir && !skip_effects {
-            let effects = analyze_effects_ir(&source);
+            let effects = analyze_effects_ir(&mask_verbatim_blocks(&source).0);
             
             // Check for undeclared effects
-            let mut has_violations = false;
+            let mut violations = 0;
             let bindings = HashMap::new();
             
             for (name, (_, _, undeclared, line)) in &effects {
                 if !undeclared.is_empty() && name != "main" {
-                    has_violations = true;
+                    violations += 1;
+                    
+                    let (file, line) = match module_map.as_ref().and_then(|modules| modules.locate(*line)) {
+                        Some((file, file_line)) => (file.path.display().to_string(), file_line),
+                        None => (input_path.clone(), *line),
+                    };
+                    if error_format == ErrorFormat::Json {
+                        let error = effect_errors::undeclared_effect(name, &undeclared.display(&bindings))
+                            .at(SourceLocation::new(&file, line, 1));
+                        eprintln!("{}", render_errors(&[error], error_format));
+                        continue;
+                    }
                     
                     eprintln!("\n{}error[RSPL300]{}: undeclared effects in function `{}`",
                         ansi::BOLD_RED, ansi::RESET, name);
-                    eprintln!("  {}-->{} {}:{}", ansi::BOLD_BLUE, ansi::RESET, input_path, line);
+                    eprintln!("  {}-->{} {}:{}", ansi::BOLD_BLUE, ansi::RESET, file, line);
                     
                     for effect in undeclared.iter() {
                         eprintln!("       {}= detected:{} {} (not declared)",
@@ -767,28 +2032,29 @@ fn main() {
                     
                     eprintln!("\n{}help{}: add `effects({})` to function signature",
                         ansi::BOLD_YELLOW, ansi::RESET,
-                        undeclared.iter().map(|e| e.display(&bindings)).collect::<Vec<_>>().join(", "));
+                        undeclared.display(&bindings));
                 }
             }
             
-            if has_violations {
-                exit(1);
+            if violations > 0 {
+                finish(ExitStatus::Effect, functions, violations);
             }
         }
         
         // Still run the legacy checks for logic rules
-        let check_result = if skip_effects {
-            check_logic_no_effects(&source, &input_path)
-        } else if use_ir {
-            // Skip legacy effect checks if using IR
-            check_logic_no_effects(&source, &input_path)
-        } else {
-            check_logic_custom(&source, &input_path, true, strict_effects)
-        };
+        let target = target.as_deref().map_or_else(Target::host, Target::from_triple);
+        // Skip legacy effect checks if using IR
+        let effect_checking = !skip_effects && !use_ir;
+        let check_result = check_logic_custom(&source, &input_path, effect_checking, strict_effects, &test_effects, &target, &limits, contracts);
         
         if let Err(errors) = check_result {
-            eprintln!("{}", format_logic_errors(&errors));
-            exit(1);
+            eprintln!("{}", render_errors(&diagnostics(&errors), error_format));
+            finish(ExitStatus::for_errors(&errors), functions, errors.len());
+        }
+        
+        let warnings = logic_warnings(&source, &input_path);
+        if !warnings.is_empty() {
+            eprint!("{}", render_warnings(&diagnostics(&warnings), error_format));
         }
         
         if !quiet {
@@ -806,18 +2072,94 @@ fn main() {
     // STAGE 2: LOWERING (RustS+ → Rust)
     //=========================================================================
     
-    if !quiet {
-        eprintln!("{}[Stage 2]{} Lowering RustS+ to Rust...", 
-            ansi::BOLD_BLUE, ansi::RESET);
-    }
+    let lowering_options = LoweringOptions {
+        preserve_lines,
+        derive_clone_all,
+        license: license.clone(),
+        attribution: attribution.clone(),
+        opt_level,
+        effect_badges,
+        edition,
+        limits,
+        contracts,
+        rules,
+        error_enums,
+        ..Default::default()
+    };
     
-    let rust_code = parse_rusts(&source);
+    // Source line of each generated line, to point rustc errors at the .rss
+    let mut line_table: Option<LineTable> = None;
+    let mut rust_code = match ir_rust {
+        Some(rust_code) => rust_code,
+        None => {
+            if !quiet {
+                eprintln!("{}[Stage 2]{} Lowering RustS+ to Rust...", 
+                    ansi::BOLD_BLUE, ansi::RESET);
+            }
+            if verbose && error_format == ErrorFormat::Human {
+                eprintln!("{}[Stage 2]{} Lowering rules {}",
+                    ansi::BOLD_BLUE, ansi::RESET, rules.summary());
+            }
+            
+            match lower_rusts_mapped(&source, &lowering_options) {
+                Ok((rust_code, table)) => {
+                    line_table = Some(table);
+                    rust_code
+                }
+                Err(mut errors) => {
+                    for error in &mut errors {
+                        error.location.file = input_path.clone();
+                    }
+                    eprintln!("{}", render_errors(&diagnostics(&errors), error_format));
+                    finish(ExitStatus::for_errors(&errors), functions, errors.len());
+                }
+            }
+        }
+    };
+    
+    if test_mode {
+        rust_code.push_str(&generate_test_harness(&test_blocks));
+    } else if let (Some(ref name), Some(ref ret)) = (&entry_fn, &entry_return) {
+        rust_code.push_str(&generate_entry_main(name, ret.as_deref()));
+    }
     
     //=========================================================================
     // STAGE 2.5: RUST SANITY GATE
     //=========================================================================
     
-    if let Some(sanity_error) = rust_sanity_check(&rust_code) {
+    if audit {
+        if let Some(report) = audit_lowering(&rust_code, &source, &input_path, &lowering_options, from_ir) {
+            if error_format == ErrorFormat::Json {
+                eprintln!("{}", internal_error_to_json(&input_path, &report));
+            } else {
+                eprintln!("\n{}error[RUSTSP_INTERNAL][audit]{}: {} rejected the generated Rust\n",
+                    ansi::BOLD_RED, ansi::RESET, AUDIT_BACKEND);
+                eprintln!("{}", report);
+            }
+            
+            if !check_only {
+                save_debug_output(&input_path, &rust_code, "Generated (invalid) Rust");
+            }
+            
+            finish(ExitStatus::Internal, functions, 0);
+        }
+        if !quiet {
+            eprintln!("{}[Stage 2.5]{} ✓ Audit passed ({})", 
+                ansi::BOLD_GREEN, ansi::RESET, AUDIT_BACKEND);
+        }
+    } else if let Some(sanity_error) = rust_sanity_check(&rust_code, &rules) {
+        if error_format == ErrorFormat::Json {
+            let problems: Vec<String> = check_rust_output_with_rules(&rust_code, &rules).errors.into_iter()
+                .map(|e| format!("line {} of the generated Rust: {}", e.line, e.message))
+                .collect();
+            for problem in if problems.is_empty() { vec![sanity_error] } else { problems } {
+                eprintln!("{}", internal_error_to_json(&input_path, &problem));
+            }
+            if !check_only {
+                save_debug_output(&input_path, &rust_code, "Generated (invalid) Rust");
+            }
+            finish(ExitStatus::Internal, functions, 0);
+        }
         eprintln!("\n{}╔═══════════════════════════════════════════════════════════════╗{}",
             ansi::BOLD_RED, ansi::RESET);
         eprintln!("{}║   RUSTS+ INTERNAL ERROR (Lowering Bug Detected)              ║{}",
@@ -829,28 +2171,130 @@ fn main() {
             ansi::BOLD_RED, ansi::RESET);
         
         eprintln!("{}note{}:", ansi::BOLD_CYAN, ansi::RESET);
-        eprintln!("  RustS+ detected an internal lowering error.");
-        eprintln!("  This is a COMPILER BUG, not your fault.\n");
+        if rules.is_complete() {
+            eprintln!("  RustS+ detected an internal lowering error.");
+            eprintln!("  This is a COMPILER BUG, not your fault.\n");
+        } else {
+            eprintln!("  Lowering rules were disabled with --disable-rule: {}.", disabled_ids(&rules));
+            eprintln!("  The invalid output may come from them rather than from a compiler bug.\n");
+        }
         eprintln!("  Problem: {}\n", sanity_error);
         
         eprintln!("{}help{}:", ansi::BOLD_YELLOW, ansi::RESET);
-        eprintln!("  {}Please report this issue with your source code.{}\n",
-            ansi::GREEN, ansi::RESET);
+        if rules.is_complete() {
+            eprintln!("  {}Please report this issue with your source code.{}\n",
+                ansi::GREEN, ansi::RESET);
+        } else {
+            eprintln!("  {}Run again with every rule enabled before reporting this issue.{}\n",
+                ansi::GREEN, ansi::RESET);
+        }
         
-        let debug_filename = format!("{}_debug.rs", 
-            Path::new(&input_path).file_stem().and_then(|s| s.to_str()).unwrap_or("output"));
-        let _ = fs::write(&debug_filename, &rust_code);
-        eprintln!("{}note{}: Generated (invalid) Rust saved to: {}",
-            ansi::CYAN, ansi::RESET, debug_filename);
+        if !check_only {
+            save_debug_output(&input_path, &rust_code, "Generated (invalid) Rust");
+        }
         
-        exit(1);
+        finish(ExitStatus::Internal, functions, 0);
     }
     
-    if !quiet {
+    // The output must agree with the registries lowering worked from;
+    // a disabled lowering rule breaks that agreement on purpose
+    if !from_ir && rules.is_complete() {
+        let consistency = check_registries(&source, &rust_code, &lowering_options);
+        if !consistency.is_valid {
+            match error_format {
+                ErrorFormat::Human => eprintln!("{}", format_internal_error(&consistency)),
+                ErrorFormat::Json => for error in &consistency.errors {
+                    eprintln!("{}", internal_error_to_json(&input_path, &error.message));
+                },
+            }
+            if !check_only {
+                save_debug_output(&input_path, &rust_code, "Generated Rust");
+            }
+            finish(ExitStatus::Internal, functions, 0);
+        }
+    }
+    
+    if !quiet && !from_ir {
         eprintln!("{}[Stage 2]{} ✓ Lowering complete", 
             ansi::BOLD_GREEN, ansi::RESET);
     }
     
+    //=========================================================================
+    // CHECK MODE
+    // Stages 0-2.5 passed; nothing is written and rustc is not run
+    //=========================================================================
+    
+    if check_only {
+        if !quiet {
+            eprintln!("{}[Check]{} ✓ No errors in '{}' (rustc not run)",
+                ansi::BOLD_GREEN, ansi::RESET, input_path);
+        }
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    //=========================================================================
+    // EMIT IR MODE
+    //=========================================================================
+    
+    if emit_ir {
+        // The path as typed differs between machines and working directories
+        let ir_source = if reproducible {
+            Path::new(&input_path).file_name().and_then(|n| n.to_str()).unwrap_or(&input_path)
+        } else {
+            &input_path
+        };
+        let ir_json = build_ir(&source, &rust_code, ir_source).to_json();
+        match output_file {
+            Some(ref out_path) => {
+                if let Err(e) = WriteToFile::new(out_path).emit(&ir_json) {
+                    eprintln!("{}error{}: writing '{}': {}",
+                        ansi::BOLD_RED, ansi::RESET, out_path, e);
+                    finish(ExitStatus::Usage, functions, 0);
+                }
+                if !quiet {
+                    eprintln!("{}✓ IR written to{}: {}",
+                        ansi::BOLD_GREEN, ansi::RESET, out_path);
+                }
+            }
+            None => print!("{}", ir_json),
+        }
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    //=========================================================================
+    // EMIT CARGO WORKSPACE MODE
+    // One crate per module file
+    //=========================================================================
+    
+    if let Some(ref dir) = cargo_workspace {
+        let root = Path::new(&input_path)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("main");
+        let no_modules = ModuleMap::default();
+        let crates = match split_crates(&rust_code, module_map.as_ref().unwrap_or(&no_modules), root) {
+            Ok(crates) => crates,
+            Err(e) => {
+                eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, e);
+                finish(ExitStatus::Usage, functions, 0);
+            }
+        };
+        match write_cargo_workspace(Path::new(dir), &crates, edition.unwrap_or_default().as_str()) {
+            Ok(manifest) => {
+                if !quiet {
+                    eprintln!("{}✓ Cargo workspace written to{}: {} ({} crates)",
+                        ansi::BOLD_GREEN, ansi::RESET, manifest.display(), crates.len());
+                }
+            }
+            Err(e) => {
+                eprintln!("{}error{}: writing '{}': {}",
+                    ansi::BOLD_RED, ansi::RESET, dir, e);
+                finish(ExitStatus::Usage, functions, 0);
+            }
+        }
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
     //=========================================================================
     // EMIT RS MODE
     //=========================================================================
@@ -858,57 +2302,129 @@ fn main() {
     if emit_rs_only {
         match output_file {
             Some(ref out_path) => {
-                if let Err(e) = fs::write(out_path, &rust_code) {
+                if let Err(e) = WriteToFile::new(out_path).emit(&rust_code) {
                     eprintln!("{}error{}: writing '{}': {}",
                         ansi::BOLD_RED, ansi::RESET, out_path, e);
-                    exit(1);
+                    finish(ExitStatus::Usage, functions, 0);
                 }
                 if !quiet {
                     eprintln!("{}✓ Rust code written to{}: {}",
                         ansi::BOLD_GREEN, ansi::RESET, out_path);
                 }
+                if rust_project {
+                    let out = Path::new(out_path);
+                    let project_dir = match rust_project_dir {
+                        Some(ref dir) => Path::new(dir).to_path_buf(),
+                        None => out.parent()
+                            .filter(|p| !p.as_os_str().is_empty())
+                            .unwrap_or(Path::new("."))
+                            .to_path_buf(),
+                    };
+                    match write_rust_project(out, &project_dir, edition.unwrap_or_default().as_str()) {
+                        Ok(project_file) => {
+                            if !quiet {
+                                eprintln!("{}✓ rust-analyzer project written to{}: {}",
+                                    ansi::BOLD_GREEN, ansi::RESET, project_file.display());
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("{}error{}: writing rust-project.json: {}",
+                                ansi::BOLD_RED, ansi::RESET, e);
+                            finish(ExitStatus::Usage, functions, 0);
+                        }
+                    }
+                }
             }
             None => {
+                if rust_project {
+                    eprintln!("{}warning{}: --rust-project needs -o <file.rs>; no project file written",
+                        ansi::BOLD_YELLOW, ansi::RESET);
+                }
                 println!("{}", rust_code);
             }
         }
-        exit(0);
+        finish(ExitStatus::Ok, functions, 0);
     }
     
     //=========================================================================
     // STAGE 3: RUST COMPILATION
     //=========================================================================
     
-    if !quiet {
-        eprintln!("{}[Stage 3]{} Compiling with rustc...", 
-            ansi::BOLD_BLUE, ansi::RESET);
-    }
-    
     let input_stem = Path::new(&input_path)
         .file_stem()
         .and_then(|s| s.to_str())
         .unwrap_or("output");
     
+    let output_binary = output_file.unwrap_or_else(|| {
+        format!("./{}", input_stem)
+    });
+    
     let temp_rs_filename = format!("{}_rusts_temp.rs", input_stem);
     let temp_rs_path_str = temp_rs_filename.clone();
     
-    if let Err(e) = fs::write(&temp_rs_path_str, &rust_code) {
+    // Extra rustc flags - part of the cache key
+    let mut rustc_flags: Vec<String> = Vec::new();
+    if opt_level > 0 {
+        rustc_flags.push(format!("-Copt-level={}", opt_level));
+    }
+    if let Some(triple) = &target {
+        rustc_flags.push("--target".to_string());
+        rustc_flags.push(triple.clone());
+    }
+    if let Some(edition) = edition {
+        rustc_flags.push(format!("--edition={}", edition.as_str()));
+    }
+    
+    //-------------------------------------------------------------------------
+    // Rustc cache: skip rustc entirely when the generated code is unchanged
+    //-------------------------------------------------------------------------
+    let cache = RustcCache::default_location();
+    let cache_status = if no_cache {
+        CacheStatus::Disabled
+    } else {
+        // The source file name is baked into the binary (crate name, panic locations)
+        let mut key_flags = rustc_flags.clone();
+        key_flags.push(temp_rs_filename.clone());
+        let key = RustcCache::key(&rust_code, &key_flags, &rustc_version());
+        if cache.restore(&key, Path::new(&output_binary)) {
+            CacheStatus::Hit(key)
+        } else {
+            CacheStatus::Miss(key)
+        }
+    };
+    
+    if let CacheStatus::Hit(_) = cache_status {
+        if !quiet {
+            eprintln!("{}[Stage 3]{} ✓ Skipped rustc: {}", 
+                ansi::BOLD_GREEN, ansi::RESET, cache_status.describe());
+            eprintln!("{}✓ Successfully compiled{}: {}",
+                ansi::BOLD_GREEN, ansi::RESET, output_binary);
+        }
+        report_build(&BuildReport::new(&output_binary, &rust_code, &source, None),
+            &input_path, build_report, stats_file.as_deref());
+        if test_mode {
+            run_test_binary(&output_binary);
+        }
+        finish(ExitStatus::Ok, functions, 0);
+    }
+    
+    if !quiet {
+        eprintln!("{}[Stage 3]{} Compiling with rustc... ({})", 
+            ansi::BOLD_BLUE, ansi::RESET, cache_status.describe());
+    }
+    
+    if let Err(e) = WriteToFile::new(&temp_rs_path_str).emit(&rust_code) {
         eprintln!("{}error{}: writing temporary Rust file: {}",
             ansi::BOLD_RED, ansi::RESET, e);
-        exit(1);
+        finish(ExitStatus::Usage, functions, 0);
     }
     
-    let output_binary = output_file.unwrap_or_else(|| {
-        format!("./{}", input_stem)
-    });
-    
-    let rustc_output = Command::new("rustc")
-        .arg(&temp_rs_path_str)
-        .arg("-o")
-        .arg(&output_binary)
+    let rustc_start = Instant::now();
+    let rustc_output = rustc_command(Path::new(&temp_rs_path_str), Path::new(&output_binary), &rustc_flags)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .output();
+    let rustc_time = rustc_start.elapsed();
     
     match rustc_output {
         Ok(output) => {
@@ -922,11 +2438,46 @@ fn main() {
                         ansi::BOLD_GREEN, ansi::RESET);
                 }
                 let _ = fs::remove_file(&temp_rs_path_str);
+                
+                if let CacheStatus::Miss(ref key) = cache_status {
+                    if let Err(e) = cache.store(key, Path::new(&output_binary)) {
+                        if !quiet {
+                            eprintln!("{}note{}: could not store rustc output in cache: {}",
+                                ansi::CYAN, ansi::RESET, e);
+                        }
+                    }
+                }
+                report_build(&BuildReport::new(&output_binary, &rust_code, &source, Some(rustc_time)),
+                    &input_path, build_report, stats_file.as_deref());
+                
+                // Test mode: run the harness and forward its exit status
+                if test_mode {
+                    run_test_binary(&output_binary);
+                }
+                finish(ExitStatus::Ok, functions, 0);
             } else {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 
                 if raw_errors {
                     eprintln!("{}", stderr);
+                } else if error_format == ErrorFormat::Json {
+                    let errors = parse_rustc_errors(&stderr);
+                    let mapped = match &line_table {
+                        Some(table) => {
+                            let map = SourceMap::from_table(Path::new(&input_path).to_path_buf(), &source, table);
+                            map_rustc_errors_through(&errors, table, &temp_rs_path_str, &rust_code, &map).into_iter()
+                                .filter(|error| error.file == input_path)
+                                .map(|error| match module_map.as_ref().and_then(|modules| modules.relocate_rustc_error(&error)) {
+                                    Some((error, _)) => error,
+                                    None => error,
+                                })
+                                .collect()
+                        }
+                        None => errors,
+                    };
+                    for error in &mapped {
+                        eprintln!("{}", rustc_error_to_json(error));
+                    }
                 } else {
                     eprintln!("\n{}╔═══════════════════════════════════════════════════════════════╗{}",
                         ansi::BOLD_RED, ansi::RESET);
@@ -935,6 +2486,20 @@ fn main() {
                     eprintln!("{}╚═══════════════════════════════════════════════════════════════╝{}\n",
                         ansi::BOLD_RED, ansi::RESET);
                     
+                    if let Some(table) = &line_table {
+                        let map = SourceMap::from_table(Path::new(&input_path).to_path_buf(), &source, table);
+                        let errors = parse_rustc_errors(&stderr);
+                        for error in map_rustc_errors_through(&errors, table, &temp_rs_path_str, &rust_code, &map) {
+                            if error.file != input_path {
+                                continue;
+                            }
+                            match module_map.as_ref().and_then(|modules| modules.relocate_rustc_error(&error)) {
+                                Some((error, file_map)) => eprintln!("{}", format_mapped_error(&error, &file_map)),
+                                None => eprintln!("{}", format_mapped_error(&error, &map)),
+                            }
+                        }
+                    }
+                    
                     if let Some(mapped_error) = map_rust_error(&stderr, &source) {
                         eprintln!("{}error{}: {}", ansi::BOLD_RED, ansi::RESET, mapped_error.title);
                         if let Some(ref note) = mapped_error.explanation {
@@ -962,14 +2527,14 @@ fn main() {
                 
                 eprintln!("\n{}note{}: Generated Rust code saved at: {}",
                     ansi::CYAN, ansi::RESET, temp_rs_path_str);
-                exit(1);
+                finish(ExitStatus::Rustc, functions, 0);
             }
         }
         Err(e) => {
             eprintln!("{}error{}: Failed to run rustc: {}",
                 ansi::BOLD_RED, ansi::RESET, e);
             eprintln!("Make sure rustc is installed and in your PATH");
-            exit(1);
+            finish(ExitStatus::Rustc, functions, 0);
         }
     }
 }
@@ -0,0 +1,324 @@
+//! Map Literals
+//!
+//! A brace literal whose entries are `key: value` pairs with literal keys is
+//! a `HashMap`:
+//!
+//! ```text
+//! scores = { "ann": 31, "bob": 42 }   →  scores = HashMap::from([("ann", 31), ("bob", 42)])
+//!
+//! ages = {                            →  ages = HashMap::from([
+//!     "ann": 31,                      →      ("ann", 31),
+//!     "bob": 42,                      →      ("bob", 42),
+//! }                                   →  ])
+//! ```
+//!
+//! Keys must be string, char, integer or bool literals, which keeps the
+//! rewrite clear of blocks and struct literals. Values may be any
+//! expression, including another map. When the program does not import
+//! `HashMap`, [`lower_map_literals_importing`] returns the `use` to add, which
+//! the lowering emits as a hoisted helper item (see
+//! [`crate::postprocess_output::HOIST_MARKER`]); output that is not reordered
+//! (line-preserving or streamed) gets the full `std::collections::HashMap`
+//! path from [`lower_map_literals`] instead.
+//!
+//! The rewrite runs on the whole source before Stage 1 and before lowering,
+//! so a multi-line map takes the array literal path (`HashMap::from([`) from
+//! there on. Line numbers do not change.
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{delimiter_positions, find_matching_close, split_top_level};
+
+/// One multi-line map being rewritten
+struct Frame {
+    /// Delimiter depth inside the map; 1 between entries
+    depth: usize,
+    /// An entry's `(` has been written but not yet its `)`
+    entry_open: bool,
+}
+
+/// The import a lowered map literal needs
+pub const MAP_IMPORT: &str = "use std::collections::HashMap;";
+
+/// Rewrite every map literal of `source`
+pub fn lower_map_literals(source: &str) -> String {
+    let path = if imports_map(source) { "HashMap" } else { "std::collections::HashMap" };
+    lower_with_path(source, path)
+}
+
+/// Rewrite every map literal of `source` to `HashMap::from`, and return
+/// [`MAP_IMPORT`] if a map was rewritten in a program that does not import it
+pub fn lower_map_literals_importing(source: &str) -> (String, Option<&'static str>) {
+    let lowered = lower_with_path(source, "HashMap");
+    let import = (lowered != source && !imports_map(source)).then_some(MAP_IMPORT);
+    (lowered, import)
+}
+
+fn lower_with_path(source: &str, path: &str) -> String {
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| lower_inline_maps(line, path).unwrap_or_else(|| line.to_string()))
+        .collect();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut out = Vec::with_capacity(lines.len());
+
+    for (i, line) in lines.iter().enumerate() {
+        let code = strip_inline_comment(line);
+        let comment = &line[code.len()..];
+        let mut lowered = String::new();
+        let mut cursor = 0;
+
+        if let Some(frame) = frames.last_mut().filter(|f| f.depth == 1 && !f.entry_open) {
+            if let Some((key, value_start)) = split_entry(&code) {
+                let indent = &code[..code.len() - code.trim_start().len()];
+                lowered.push_str(&format!("{}({}, ", indent, key));
+                cursor = value_start;
+                frame.entry_open = true;
+            }
+        }
+
+        let last_brace = code.trim_end().strip_suffix('{').map(str::len);
+        for (pos, c) in delimiter_positions(&code, &['(', ')', '[', ']', '{', '}', ',']) {
+            if pos < cursor {
+                continue;
+            }
+            if c == '{' && Some(pos) == last_brace && value_position(&code[..pos]) && next_is_entry(&lines[i + 1..]) {
+                lowered.push_str(&code[cursor..pos]);
+                lowered.push_str(&format!("{}::from([", path));
+                cursor = pos + 1;
+                frames.push(Frame { depth: 1, entry_open: false });
+                continue;
+            }
+            let Some(frame) = frames.last_mut() else { continue };
+            match c {
+                '(' | '[' | '{' => frame.depth += 1,
+                ')' | ']' | '}' => {
+                    frame.depth -= 1;
+                    if frame.depth == 0 {
+                        if frame.entry_open {
+                            lowered.push_str(code[cursor..pos].trim_end());
+                            lowered.push(')');
+                        } else {
+                            lowered.push_str(&code[cursor..pos]);
+                        }
+                        lowered.push_str("])");
+                        cursor = pos + 1;
+                        frames.pop();
+                    }
+                }
+                ',' if frame.depth == 1 && frame.entry_open => {
+                    lowered.push_str(&code[cursor..pos]);
+                    lowered.push(')');
+                    cursor = pos;
+                    frame.entry_open = false;
+                }
+                _ => {}
+            }
+        }
+        lowered.push_str(&code[cursor..]);
+        if let Some(frame) = frames.last_mut().filter(|f| f.depth == 1 && f.entry_open) {
+            let trimmed = lowered.trim_end().len();
+            lowered.insert(trimmed, ')');
+            frame.entry_open = false;
+        }
+        lowered.push_str(comment);
+        out.push(lowered);
+    }
+    let mut result = out.join("\n");
+    if source.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// Rewrite the single-line maps of `line`; None when it has none
+pub fn lower_inline_maps(line: &str, path: &str) -> Option<String> {
+    let code = strip_inline_comment(line);
+    let comment = &line[code.len()..];
+    let lowered = lower_inline_code(&code, path)?;
+    Some(format!("{}{}", lowered, comment))
+}
+
+fn lower_inline_code(code: &str, path: &str) -> Option<String> {
+    for (pos, c) in delimiter_positions(code, &['{']) {
+        if c != '{' || !value_position(&code[..pos]) {
+            continue;
+        }
+        let Some(close) = find_matching_close(code, pos) else { continue };
+        let Some(entries) = map_entries(&code[pos + 1..close]) else { continue };
+        let pairs: Vec<String> = entries
+            .iter()
+            .map(|(key, value)| {
+                let value = lower_inline_code(value, path).unwrap_or_else(|| value.to_string());
+                format!("({}, {})", key, value)
+            })
+            .collect();
+        let rest = &code[close + 1..];
+        let rest = lower_inline_code(rest, path).unwrap_or_else(|| rest.to_string());
+        return Some(format!("{}{}::from([{}]){}", &code[..pos], path, pairs.join(", "), rest));
+    }
+    None
+}
+
+/// Whether the program imports `HashMap`
+pub(crate) fn imports_map(source: &str) -> bool {
+    source.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("use ") && (line.contains("HashMap") || line.contains("collections::*"))
+    })
+}
+
+/// Whether a `{` after `before` starts an expression rather than a block
+fn value_position(before: &str) -> bool {
+    let before = before.trim_end();
+    before.is_empty()
+        || before.ends_with(['=', '(', '[', ',', ':'])
+        || before == "return"
+        || before.ends_with(" return")
+}
+
+/// The `key: value` entries of a brace body; None unless every one is an entry
+fn map_entries(body: &str) -> Option<Vec<(&str, &str)>> {
+    let entries: Vec<(&str, &str)> = split_top_level(body, ',')
+        .into_iter()
+        .filter(|item| !item.trim().is_empty())
+        .map(|item| split_entry(item).map(|(key, start)| (key, item[start..].trim())))
+        .collect::<Option<_>>()?;
+    (!entries.is_empty()).then_some(entries)
+}
+
+/// Whether the next line with code is a map entry
+fn next_is_entry(lines: &[String]) -> bool {
+    lines
+        .iter()
+        .map(|line| strip_inline_comment(line))
+        .find(|code| !code.trim().is_empty())
+        .is_some_and(|code| split_entry(&code).is_some())
+}
+
+/// `"ann": 31` → the key and the byte offset where the value starts
+pub fn split_entry(text: &str) -> Option<(&str, usize)> {
+    let start = text.len() - text.trim_start().len();
+    let key_len = literal_key_len(&text[start..])?;
+    let key = &text[start..start + key_len];
+    let after = &text[start + key_len..];
+    let colon = after.len() - after.trim_start().len();
+    let value = after[colon..].strip_prefix(':').filter(|v| !v.starts_with(':'))?;
+    if value.trim().is_empty() {
+        return None;
+    }
+    let value_start = text.len() - value.trim_start().len();
+    Some((key, value_start))
+}
+
+/// Byte length of the string, char, integer or bool literal at the start of `s`
+fn literal_key_len(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    match bytes.first()? {
+        b'"' => {
+            let mut i = 1;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'\\' => i += 2,
+                    b'"' => return Some(i + 1),
+                    _ => i += 1,
+                }
+            }
+            None
+        }
+        b'\'' => {
+            let body = if bytes.get(1) == Some(&b'\\') { 3 } else { 1 + s[1..].chars().next()?.len_utf8() };
+            s.get(body..)?.find('\'').filter(|&off| off <= 8).map(|off| body + off + 1)
+        }
+        b'0'..=b'9' | b'-' => {
+            let digits = s.strip_prefix('-').unwrap_or(s);
+            if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+                return None;
+            }
+            let len = digits.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(digits.len());
+            Some(s.len() - digits.len() + len)
+        }
+        _ => ["true", "false"].into_iter().find_map(|word| {
+            let rest = s.strip_prefix(word)?;
+            (!rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')).then_some(word.len())
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_maps() {
+        let path = "HashMap";
+        assert_eq!(
+            lower_inline_maps(r#"    scores = { "a": 1, "b": 2 }  // two"#, path).unwrap(),
+            r#"    scores = HashMap::from([("a", 1), ("b", 2)])  // two"#
+        );
+        assert_eq!(
+            lower_inline_maps("m = { 'x': vec![1, 2], 'y': vec![] }", path).unwrap(),
+            "m = HashMap::from([('x', vec![1, 2]), ('y', vec![])])"
+        );
+        assert_eq!(lower_inline_maps("m = {}", path), None, "an empty brace is not a map");
+        assert_eq!(
+            lower_inline_maps(r#"m = { 1: { "x": true } }"#, path).unwrap(),
+            r#"m = HashMap::from([(1, HashMap::from([("x", true)]))])"#
+        );
+        assert_eq!(lower_inline_maps(r#"show({ true: "yes" })"#, path).unwrap(), r#"show(HashMap::from([(true, "yes")]))"#);
+
+        for line in [
+            "fn main() {",
+            "if x { y }",
+            "p = Point { x = 1, y = 2 }",
+            "match c { 'a' => 1, _ => 2 }",
+            r#"s = "{ \"a\": 1 }""#,
+            "outer: { a: 1 }",
+            "r = { a: 1 }",
+        ] {
+            assert_eq!(lower_inline_maps(line, path), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_multi_line_maps() {
+        let source = "use std::collections::HashMap\n\nfn main() {\n    ages = {\n        \"ann\": 31,\n        \"bob\": 42  // last\n    }\n}\n";
+        assert_eq!(
+            lower_map_literals(source),
+            "use std::collections::HashMap\n\nfn main() {\n    ages = HashMap::from([\n        (\"ann\", 31),\n        (\"bob\", 42)  // last\n    ])\n}\n"
+        );
+
+        let nested = "fn main() {\n    m = {\n        \"a\": {\n            1: [\n                2,\n            ],\n        },\n        \"b\": f(3).len() }\n}";
+        assert_eq!(
+            lower_map_literals(nested),
+            "fn main() {\n    m = std::collections::HashMap::from([\n        (\"a\", std::collections::HashMap::from([\n            (1, [\n                2,\n            ]),\n        ])),\n        (\"b\", f(3).len())])\n}"
+        );
+
+        let block = "fn main() {\n    x = {\n        y = 1\n        y + 1\n    }\n}";
+        assert_eq!(lower_map_literals(block), block);
+    }
+
+    #[test]
+    fn test_import() {
+        let source = "fn main() {\n    m = { 1: 2 }\n}";
+        assert_eq!(
+            lower_map_literals_importing(source),
+            ("fn main() {\n    m = HashMap::from([(1, 2)])\n}".to_string(), Some(MAP_IMPORT))
+        );
+        let imported = format!("use std::collections::HashMap\n{}", source);
+        assert_eq!(lower_map_literals_importing(&imported).1, None);
+        let block = "fn main() {\n    x = { y }\n}";
+        assert_eq!(lower_map_literals_importing(block), (block.to_string(), None));
+    }
+
+    #[test]
+    fn test_split_entry() {
+        assert_eq!(split_entry(r#"  "a\"b": 1"#), Some((r#""a\"b""#, 10)));
+        assert_eq!(split_entry("'\\n': x"), Some(("'\\n'", 6)));
+        assert_eq!(split_entry("10u8: x"), Some(("10u8", 6)));
+        assert_eq!(split_entry("false: x"), Some(("false", 7)));
+        assert_eq!(split_entry("\"a\":"), None);
+        assert_eq!(split_entry("\"a\"::b"), None);
+        assert_eq!(split_entry("falsey: x"), None);
+        assert_eq!(split_entry("'a: loop {"), None);
+    }
+}
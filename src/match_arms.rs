@@ -0,0 +1,525 @@
+//! Match Arm Checks (RSPL063, RSPL064)
+//!
+//! rustc only warns about unreachable arms, and only after RustS+ has lowered
+//! the whole file. Stage 1 reports them up front, pointing at both arms:
+//!
+//! ```text
+//! match code {
+//!     0..=9 { "digit" }
+//!     5 { "five" }        // RSPL063: already matched by `0..=9` (line 2)
+//!     _ { "other" }
+//!     "x" { "never" }     // RSPL063: already matched by `_` (line 4)
+//! }
+//! ```
+//!
+//! An arm is unreachable when every alternative of its pattern is covered by
+//! a single earlier arm without a guard. Coverage is only decided for what
+//! can be compared textually: `_` and bare bindings, string/bool literals,
+//! and integer or char literals and ranges. Anything else (enum variants,
+//! tuples, struct patterns) never counts as covered.
+//!
+//! A match whose patterns are all `true`, `false`, `_` or bindings is a
+//! bool match ([`crate::bool_match`]). Its arms without a guard have to
+//! cover both truth values, otherwise it is `RSPL064`:
+//!
+//! ```text
+//! level = match verbose {   // RSPL064: no arm for `false`
+//!     true { 2 }
+//!     true if quiet { 0 }
+//! }
+//! ```
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, find_outside_strings};
+use crate::bool_match::find_inline_match;
+
+/// An arm that can never be selected because an earlier arm already matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachableArm {
+    /// 1-based line of the unreachable arm
+    pub line: usize,
+    /// Pattern text of the unreachable arm
+    pub pattern: String,
+    /// 1-based line of the earlier arm that covers it
+    pub earlier_line: usize,
+    /// Pattern text of the earlier arm
+    pub earlier_pattern: String,
+}
+
+/// A bool match without an arm for one of the truth values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingBoolArm {
+    /// 1-based line of the `match`
+    pub line: usize,
+    /// `match <scrutinee>`
+    pub header: String,
+    /// The truth value no arm without a guard matches
+    pub missing: bool,
+    /// The match is assigned (`x = match ..`) or returned
+    pub is_value: bool,
+}
+
+/// One alternative of an arm pattern (`a | b` has two)
+#[derive(Debug, Clone, PartialEq)]
+enum PatternAlt {
+    /// `_` or a bare binding - matches everything
+    CatchAll,
+    Str(String),
+    Bool(bool),
+    /// Inclusive integer range; a single literal has `lo == hi`
+    Int(i128, i128),
+    /// Inclusive char range
+    Char(u32, u32),
+    /// Not analyzed (variants, tuples, struct patterns, ...)
+    Other,
+}
+
+impl PatternAlt {
+    fn covers(&self, later: &PatternAlt) -> bool {
+        match (self, later) {
+            (PatternAlt::CatchAll, _) => true,
+            (_, PatternAlt::Other) | (PatternAlt::Other, _) => false,
+            (PatternAlt::Str(a), PatternAlt::Str(b)) => a == b,
+            (PatternAlt::Bool(a), PatternAlt::Bool(b)) => a == b,
+            (PatternAlt::Int(lo, hi), PatternAlt::Int(l, h)) => lo <= l && h <= hi,
+            (PatternAlt::Char(lo, hi), PatternAlt::Char(l, h)) => lo <= l && h <= hi,
+            _ => false,
+        }
+    }
+}
+
+/// An arm seen so far in the enclosing match
+struct SeenArm {
+    line: usize,
+    pattern: String,
+    alts: Vec<PatternAlt>,
+    guarded: bool,
+}
+
+/// A `match` whose arms are being collected
+struct OpenMatch {
+    line: usize,
+    header: String,
+    is_value: bool,
+    /// Brace depth of the arm lines
+    arm_depth: usize,
+    arms: Vec<SeenArm>,
+    /// `| pattern` continuation lines waiting for the line with the body
+    pending: Option<(usize, String)>,
+}
+
+/// Find every unreachable match arm in `source`
+pub fn find_unreachable_arms(source: &str) -> Vec<UnreachableArm> {
+    scan(source).0
+}
+
+/// Find every bool match in `source` that misses `true` or `false`
+pub fn find_missing_bool_arms(source: &str) -> Vec<MissingBoolArm> {
+    scan(source).1
+}
+
+/// Whether arms with these patterns make a match on a `bool`: every
+/// alternative is `true`, `false`, `_` or a binding, and one is a literal
+pub fn is_bool_match(patterns: &[&str]) -> bool {
+    let arms: Vec<SeenArm> = patterns.iter().map(|pattern| seen_arm(0, pattern)).collect();
+    is_bool_arms(&arms)
+}
+
+fn is_bool_arms(arms: &[SeenArm]) -> bool {
+    let mut alts = arms.iter().flat_map(|arm| arm.alts.iter());
+    alts.clone().any(|alt| matches!(alt, PatternAlt::Bool(_)))
+        && alts.all(|alt| matches!(alt, PatternAlt::Bool(_) | PatternAlt::CatchAll))
+}
+
+fn scan(source: &str) -> (Vec<UnreachableArm>, Vec<MissingBoolArm>) {
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    let mut matches: Vec<OpenMatch> = Vec::new();
+    let mut depth = 0usize;
+
+    for (idx, raw) in source.lines().enumerate() {
+        let line = strip_inline_comment(raw);
+        let trimmed = line.trim();
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+
+        if let Some(open) = matches.last_mut() {
+            if depth == open.arm_depth && !trimmed.is_empty() && !trimmed.starts_with('}') {
+                if let Some(arm) = read_arm(open, trimmed, idx + 1) {
+                    check_arm(open, &arm, &mut found);
+                    open.arms.push(arm);
+                }
+            }
+        }
+
+        if is_match_header(trimmed) {
+            matches.push(OpenMatch {
+                line: idx + 1,
+                header: match_header(trimmed),
+                is_value: is_value_match(trimmed),
+                arm_depth: depth + 1,
+                arms: Vec::new(),
+                pending: None,
+            });
+        } else if let Some(inline) = find_inline_match(trimmed) {
+            let arms: Vec<SeenArm> = inline.arms.iter().map(|(pattern, _)| seen_arm(idx + 1, pattern)).collect();
+            if let Some(value) = missing_truth_value(&arms) {
+                missing.push(MissingBoolArm {
+                    line: idx + 1,
+                    header: format!("match {}", inline.scrutinee),
+                    missing: value,
+                    is_value: is_value_match(trimmed),
+                });
+            }
+        }
+
+        depth = (depth + opens).saturating_sub(closes);
+        while matches.last().is_some_and(|m| depth < m.arm_depth) {
+            let closed = matches.pop().expect("checked above");
+            if let Some(value) = missing_truth_value(&closed.arms) {
+                missing.push(MissingBoolArm {
+                    line: closed.line,
+                    header: closed.header,
+                    missing: value,
+                    is_value: closed.is_value,
+                });
+            }
+        }
+    }
+
+    missing.sort_by_key(|m| m.line);
+    (found, missing)
+}
+
+/// The truth value no unguarded arm of a bool match covers
+fn missing_truth_value(arms: &[SeenArm]) -> Option<bool> {
+    if !is_bool_arms(arms) {
+        return None;
+    }
+    let covered = |value: bool| arms.iter().filter(|arm| !arm.guarded)
+        .any(|arm| arm.alts.iter().any(|alt| alt.covers(&PatternAlt::Bool(value))));
+    [true, false].into_iter().find(|value| !covered(*value))
+}
+
+/// `match <scrutinee>` of a match header line
+fn match_header(trimmed: &str) -> String {
+    let start = trimmed.find("match ").unwrap_or(0);
+    trimmed[start..].trim_end_matches('{').trim().to_string()
+}
+
+fn is_value_match(trimmed: &str) -> bool {
+    trimmed.contains("= match ") || trimmed.starts_with("return match ")
+}
+
+fn is_match_header(trimmed: &str) -> bool {
+    trimmed.ends_with('{')
+        && (trimmed.starts_with("match ") || trimmed.contains(" match ") || trimmed.contains("= match "))
+}
+
+/// Read the pattern of an arm-depth line. Multi-line `| a` sequences are
+/// joined until the line carrying the body.
+fn read_arm(open: &mut OpenMatch, trimmed: &str, line: usize) -> Option<SeenArm> {
+    let end = find_arrow(trimmed).or_else(|| arm_body_start(trimmed));
+    let Some(end) = end else {
+        // Pattern without a body yet: `A` followed by `| B {`
+        let (start, mut text) = open.pending.take().unwrap_or((line, String::new()));
+        text.push(' ');
+        text.push_str(trimmed);
+        open.pending = Some((start, text));
+        return None;
+    };
+
+    let (line, mut pattern) = open.pending.take().unwrap_or((line, String::new()));
+    pattern.push(' ');
+    pattern.push_str(&trimmed[..end]);
+    Some(seen_arm(line, &pattern))
+}
+
+fn seen_arm(line: usize, pattern: &str) -> SeenArm {
+    let pattern = pattern.trim().trim_start_matches('|').trim();
+    let (pattern, guarded) = match split_guard(pattern) {
+        Some(pat) => (pat.to_string(), true),
+        None => (pattern.to_string(), false),
+    };
+    let alts = split_alternatives(&pattern).into_iter().map(classify).collect();
+    SeenArm { line, pattern, alts, guarded }
+}
+
+fn check_arm(open: &OpenMatch, arm: &SeenArm, found: &mut Vec<UnreachableArm>) {
+    let earlier = open.arms.iter().filter(|e| !e.guarded).find(|e| {
+        arm.alts.iter().all(|alt| e.alts.iter().any(|cover| cover.covers(alt)))
+    });
+    if let Some(earlier) = earlier {
+        found.push(UnreachableArm {
+            line: arm.line,
+            pattern: arm.pattern.clone(),
+            earlier_line: earlier.line,
+            earlier_pattern: earlier.pattern.clone(),
+        });
+    }
+}
+
+/// Position of the first `=>` outside string literals
+fn find_arrow(trimmed: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in trimmed.char_indices() {
+        if c == '"' && prev != '\\' {
+            in_string = !in_string;
+        }
+        if !in_string && trimmed[i..].starts_with("=>") {
+            return Some(i);
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Position of the `{` opening the arm body. Struct patterns
+/// (`Point { x, y } { ... }`) keep their own braces, so the body opens at the
+/// first `{` at brace depth zero that follows any closed pattern braces.
+fn arm_body_start(trimmed: &str) -> Option<usize> {
+    let first = find_outside_strings(trimmed, '{')?;
+    let before = trimmed[..first].trim_end();
+    let is_struct_pattern = before
+        .rsplit("::")
+        .next()
+        .and_then(|last| last.chars().next())
+        .is_some_and(|c| c.is_uppercase());
+    if !is_struct_pattern || (!trimmed.ends_with('{') && count_braces_outside_strings(trimmed).0 < 2) {
+        return Some(first);
+    }
+    // Skip the struct pattern's own braces
+    let mut depth = 0usize;
+    let mut offset = first;
+    while let Some(pos) = find_outside_strings(&trimmed[offset..], '{')
+        .into_iter()
+        .chain(find_outside_strings(&trimmed[offset..], '}'))
+        .min()
+    {
+        let at = offset + pos;
+        if trimmed[at..].starts_with('{') {
+            if depth == 0 && at != first {
+                return Some(at);
+            }
+            depth += 1;
+        } else {
+            depth = depth.saturating_sub(1);
+        }
+        offset = at + 1;
+    }
+    Some(first)
+}
+
+/// Strip an `if` guard, returning the bare pattern
+fn split_guard(pattern: &str) -> Option<&str> {
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in pattern.char_indices() {
+        if c == '"' && prev != '\\' {
+            in_string = !in_string;
+        }
+        if !in_string && pattern[i..].starts_with(" if ") {
+            return Some(pattern[..i].trim());
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Split a pattern on top-level `|`
+fn split_alternatives(pattern: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut in_string = false;
+    let mut prev = ' ';
+    for (i, c) in pattern.char_indices() {
+        match c {
+            '"' if prev != '\\' => in_string = !in_string,
+            '(' | '[' | '{' if !in_string => depth += 1,
+            ')' | ']' | '}' if !in_string => depth -= 1,
+            '|' if !in_string && depth == 0 => {
+                parts.push(pattern[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    parts.push(pattern[start..].trim());
+    parts.retain(|p| !p.is_empty());
+    parts
+}
+
+fn classify(alt: &str) -> PatternAlt {
+    if alt == "_" {
+        return PatternAlt::CatchAll;
+    }
+    if alt == "true" || alt == "false" {
+        return PatternAlt::Bool(alt == "true");
+    }
+    if alt.len() >= 2 && alt.starts_with('"') && alt.ends_with('"') {
+        return PatternAlt::Str(alt.to_string());
+    }
+    if let Some((lo, hi, inclusive)) = split_range(alt) {
+        if let (Some(lo), Some(hi)) = (lo.map(parse_int).unwrap_or(Some(i128::MIN)), hi.map(parse_int).unwrap_or(Some(i128::MAX))) {
+            let hi = if inclusive || hi == i128::MAX { hi } else { hi - 1 };
+            return PatternAlt::Int(lo, hi);
+        }
+        if let (Some(lo), Some(hi)) = (lo.map(parse_char).unwrap_or(Some(0)), hi.map(parse_char).unwrap_or(Some(u32::MAX))) {
+            let hi = if inclusive || hi == u32::MAX { hi } else { hi.saturating_sub(1) };
+            return PatternAlt::Char(lo, hi);
+        }
+        return PatternAlt::Other;
+    }
+    if let Some(n) = parse_int(alt) {
+        return PatternAlt::Int(n, n);
+    }
+    if let Some(c) = parse_char(alt) {
+        return PatternAlt::Char(c, c);
+    }
+    if is_binding(alt) {
+        return PatternAlt::CatchAll;
+    }
+    PatternAlt::Other
+}
+
+/// Split `a..=b`, `a..b`, `a..` and `..=b` into (lo, hi, inclusive)
+fn split_range(alt: &str) -> Option<(Option<&str>, Option<&str>, bool)> {
+    if alt.starts_with('"') {
+        return None;
+    }
+    let pos = alt.find("..")?;
+    let lo = alt[..pos].trim();
+    let rest = &alt[pos + 2..];
+    let (hi, inclusive) = match rest.strip_prefix('=') {
+        Some(hi) => (hi.trim(), true),
+        None => (rest.trim(), false),
+    };
+    Some(((!lo.is_empty()).then_some(lo), (!hi.is_empty()).then_some(hi), inclusive))
+}
+
+/// Parse an integer literal: `-5`, `0xFF`, `1_000`, `7u8`
+fn parse_int(text: &str) -> Option<i128> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest.trim()),
+        None => (false, text),
+    };
+    let digits = digits.replace('_', "");
+    let (radix, body) = if let Some(hex) = digits.strip_prefix("0x") {
+        (16, hex)
+    } else if let Some(oct) = digits.strip_prefix("0o") {
+        (8, oct)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        (2, bin)
+    } else {
+        (10, digits.as_str())
+    };
+    let end = body.find(|c: char| !c.is_digit(radix)).unwrap_or(body.len());
+    let suffix = &body[end..];
+    if end == 0 || !(suffix.is_empty() || is_int_suffix(suffix)) {
+        return None;
+    }
+    let value = i128::from_str_radix(&body[..end], radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+fn is_int_suffix(suffix: &str) -> bool {
+    matches!(
+        suffix,
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+    )
+}
+
+fn parse_char(text: &str) -> Option<u32> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let c = chars.next()?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(c as u32)
+}
+
+/// A lowercase identifier binds the value and matches everything
+fn is_binding(alt: &str) -> bool {
+    let alt = alt.strip_prefix("mut ").unwrap_or(alt);
+    let alt = alt.strip_prefix("ref ").unwrap_or(alt);
+    let mut chars = alt.chars();
+    matches!(chars.next(), Some(c) if c.is_lowercase() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(src: &str) -> Vec<(usize, usize)> {
+        find_unreachable_arms(src).into_iter().map(|a| (a.line, a.earlier_line)).collect()
+    }
+
+    #[test]
+    fn test_arm_after_wildcard() {
+        let src = "match x {\n    0 { a() }\n    _ { b() }\n    1 { c() }\n}";
+        assert_eq!(lines(src), vec![(4, 3)]);
+    }
+
+    #[test]
+    fn test_duplicate_string_literal() {
+        let src = "match mode {\n    \"fast\" { 1 }\n    \"slow\" { 2 }\n    \"fast\" | \"x\" { 3 }\n    \"fast\" { 4 }\n    _ { 0 }\n}";
+        assert_eq!(lines(src), vec![(5, 2)]);
+    }
+
+    #[test]
+    fn test_literal_inside_earlier_range() {
+        let src = "match n {\n    0..=9 {\n        small()\n    }\n    5 => five()\n    10..20 { mid() }\n    19 { edge() }\n    20 { big() }\n    _ { other() }\n}";
+        assert_eq!(lines(src), vec![(5, 2), (7, 6)]);
+    }
+
+    #[test]
+    fn test_guarded_arms_do_not_cover() {
+        let src = "match n {\n    x if x > 0 { pos() }\n    1 { one() }\n    _ { zero() }\n}";
+        assert!(lines(src).is_empty());
+    }
+
+    #[test]
+    fn test_nested_match_is_separate() {
+        let src = "match a {\n    1 {\n        match b {\n            1 { x() }\n            _ { y() }\n        }\n    }\n    _ { z() }\n}";
+        assert!(lines(src).is_empty());
+    }
+
+    #[test]
+    fn test_enum_variants_not_analyzed() {
+        let src = "match ev {\n    Event::Credit { id } { id }\n    Event::Debit { id } { id }\n    Event::Credit { id } { 0 }\n}";
+        assert!(lines(src).is_empty());
+    }
+
+    fn gaps(src: &str) -> Vec<(usize, bool, bool)> {
+        find_missing_bool_arms(src).into_iter().map(|m| (m.line, m.missing, m.is_value)).collect()
+    }
+
+    #[test]
+    fn test_bool_match_coverage() {
+        let complete = "match ok {\n    true { a() }\n    false { b() }\n}\nx = match ok {\n    false => 0,\n    _ => 1,\n}\ny = match ok { true | false { 1 } }";
+        assert!(gaps(complete).is_empty());
+
+        let src = "m = match ok {\n    true { 1 }\n}\nmatch ok {\n    true if n > 0 { a() }\n    false { b() }\n}\nlabel = match ok { false { \"off\" } }";
+        assert_eq!(gaps(src), vec![(1, false, true), (4, true, false), (8, true, true)]);
+    }
+
+    #[test]
+    fn test_bool_match_detection() {
+        assert!(is_bool_match(&["true", "false if x"]));
+        assert!(is_bool_match(&["true | false", "_"]));
+        assert!(!is_bool_match(&["_", "flag"]));
+        assert!(!is_bool_match(&["true", "0"]));
+        assert!(gaps("match (a, b) {\n    (true, _) { 1 }\n}").is_empty());
+        assert!(gaps("match n {\n    0 { 1 }\n    true { 2 }\n}").is_empty());
+    }
+
+    #[test]
+    fn test_char_ranges() {
+        let src = "match c {\n    'a'..='z' { lower() }\n    'q' { q() }\n    _ { other() }\n}";
+        assert_eq!(lines(src), vec![(3, 2)]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Memoized Functions
+//!
+//! `memo` in front of `fn` caches the result for each argument tuple in a
+//! thread-local `HashMap`. The body moves to a private `__rustsp_memo_`
+//! function, which recursive calls reach only through the cache:
+//!
+//! ```text
+//! memo fn fib(n u64) u64 {
+//!     if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+//! }
+//!
+//! fn fib(n: u64) -> u64 {
+//!     thread_local! {
+//!         static MEMO: std::cell::RefCell<std::collections::HashMap<(u64,), u64>> = ..;
+//!     }
+//!     let key = (n.clone(),);
+//!     if let Some(value) = MEMO.with(|memo| memo.borrow().get(&key).cloned()) {
+//!         return value;
+//!     }
+//!     let value = __rustsp_memo_fib(n);
+//!     MEMO.with(|memo| memo.borrow_mut().insert(key, value.clone()));
+//!     value
+//! }
+//! fn __rustsp_memo_fib(n: u64) -> u64 {
+//!     if n < 2 { n } else { fib(n - 1) + fib(n - 2) }
+//! }
+//! ```
+//!
+//! A cached call skips the body, so Stage 1 accepts `memo` only on pure
+//! functions (RSPL317): no declared or performed effect and no call to an
+//! effectful function. The cache itself allocates, so a `memo` function has
+//! the `alloc` effect without writing it, and callers propagate it like any
+//! other `alloc`.
+//!
+//! Parameters and the return type must be `Clone`, parameters also
+//! `Eq + Hash`, and the function must not be generic or take `self`.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::function::{find_matching_paren, parse_rust_signature};
+
+/// The marker `memo` leaves in the modifier attributes; it is consumed by
+/// [`lower_memo_fn`] and never emitted
+pub const MEMO_ATTRIBUTE: &str = "#[memo]";
+
+/// Prefix of the function that keeps the body of a `memo` function
+pub const MEMO_BODY_PREFIX: &str = "__rustsp_memo_";
+
+pub fn is_memo(attributes: &[String]) -> bool {
+    attributes.iter().any(|a| a == MEMO_ATTRIBUTE)
+}
+
+/// Signature lines (1-based) of `memo` functions, from the attributes
+/// [`crate::function::strip_fn_modifiers`] returns by 0-based line
+pub fn memo_lines(attributes: &HashMap<usize, Vec<String>>) -> BTreeSet<usize> {
+    attributes.iter()
+        .filter(|(_, attrs)| is_memo(attrs))
+        .map(|(line, _)| line + 1)
+        .collect()
+}
+
+/// Lower the Rust signature line of a `memo` function to the caching
+/// wrapper followed by the same signature renamed to the body function.
+/// None when the signature does not parse.
+pub fn lower_memo_fn(signature: &str) -> Option<String> {
+    let sig = parse_rust_signature(signature)?;
+    let code = signature.trim_start();
+    let indent = &signature[..signature.len() - code.len()];
+    let open = code.find('(')?;
+    let close = find_matching_paren(code, open)?;
+    let header = &code[..=close + code[close..].find('{')?];
+
+    let names: Vec<&str> = sig.parameters.iter().map(|p| p.name.as_str()).collect();
+    let key_type: String = sig.parameters.iter().map(|p| format!("{}, ", p.param_type)).collect();
+    let key: String = names.iter().map(|name| format!("{}.clone(), ", name)).collect();
+    let value_type = sig.return_type.as_deref().unwrap_or("()");
+    let body_fn = format!("{}{}", MEMO_BODY_PREFIX, sig.name);
+
+    let wrapper = [
+        header.to_string(),
+        "    thread_local! {".to_string(),
+        format!(
+            "        static MEMO: std::cell::RefCell<std::collections::HashMap<({}), {}>> = std::cell::RefCell::new(std::collections::HashMap::new());",
+            key_type.trim_end(), value_type
+        ),
+        "    }".to_string(),
+        format!("    let key = ({});", key.trim_end()),
+        "    if let Some(value) = MEMO.with(|memo| memo.borrow().get(&key).cloned()) {".to_string(),
+        "        return value;".to_string(),
+        "    }".to_string(),
+        format!("    let value = {}({});", body_fn, names.join(", ")),
+        "    MEMO.with(|memo| memo.borrow_mut().insert(key, value.clone()));".to_string(),
+        "    value".to_string(),
+        "}".to_string(),
+    ];
+    let body_signature = code.strip_prefix("pub ").unwrap_or(code)
+        .replacen(&format!("fn {}", sig.name), &format!("fn {}", body_fn), 1);
+
+    Some(wrapper.iter()
+        .chain(std::iter::once(&body_signature))
+        .map(|line| format!("{}{}", indent, line))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lower_memo_fn() {
+        let lowered = lower_memo_fn("pub fn fib(n: u64) -> u64 {").unwrap();
+        let lines: Vec<&str> = lowered.lines().collect();
+        assert_eq!(lines[0], "pub fn fib(n: u64) -> u64 {");
+        assert!(lines[2].contains("HashMap<(u64,), u64>"), "{}", lowered);
+        assert_eq!(lines[4], "    let key = (n.clone(),);");
+        assert_eq!(lines[8], "    let value = __rustsp_memo_fib(n);");
+        assert_eq!(lines[12], "fn __rustsp_memo_fib(n: u64) -> u64 {");
+
+        let single = lower_memo_fn("    fn area(w: i64, h: i64) -> i64 { w * h }").unwrap();
+        assert!(single.contains("HashMap<(i64, i64,), i64>"), "{}", single);
+        assert!(single.contains("    let value = __rustsp_memo_area(w, h);"), "{}", single);
+        assert!(single.ends_with("\n    fn __rustsp_memo_area(w: i64, h: i64) -> i64 { w * h }"), "{}", single);
+    }
+
+    #[test]
+    fn test_memo_lines() {
+        let (_, attributes) = crate::function::strip_fn_modifiers("inline fn a() {\n}\n\nmemo fn b(n u64) u64 {\n    n\n}\n");
+        assert_eq!(memo_lines(&attributes), BTreeSet::from([4]));
+    }
+}
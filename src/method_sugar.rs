@@ -0,0 +1,97 @@
+//! Collection Mutation Sugar
+//!
+//! The common in-place collection updates can be written without the dot
+//! and parentheses:
+//!
+//! ```text
+//! xs push 5               →  xs.push(5)
+//! scores insert name, 10  →  scores.insert(name, 10)
+//! self.queue remove 0     →  self.queue.remove(0)
+//! ```
+//!
+//! The rewrite runs on the whole source before Stage 1 and before lowering,
+//! so the sugared statement takes part in `let mut` inference and effect
+//! detection exactly like the method call it stands for. Only whole
+//! statements are rewritten, and only for the methods below; line numbers
+//! do not change.
+
+use crate::helpers::strip_inline_comment;
+use crate::ident::{is_rust_keyword, is_valid_identifier};
+
+/// Methods that may be called without the dot
+pub const SUGAR_METHODS: &[&str] = &["push", "push_str", "insert", "remove", "extend"];
+
+/// Rewrite every `target method args` statement of `source`
+pub fn lower_method_sugar(source: &str) -> String {
+    source
+        .lines()
+        .map(|line| lower_method_sugar_line(line).unwrap_or_else(|| line.to_string()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `xs push 5` → `xs.push(5)`; None when the line is no such statement
+pub fn lower_method_sugar_line(line: &str) -> Option<String> {
+    let code = strip_inline_comment(line);
+    let comment = &line[code.len()..];
+    let body = code.trim_start();
+    let indent = &code[..code.len() - body.len()];
+
+    let (target, rest) = body.split_once(char::is_whitespace)?;
+    if !is_mutation_target(target) {
+        return None;
+    }
+    let (method, args) = rest.trim_start().split_once(char::is_whitespace)?;
+    let args = args.trim();
+    if !SUGAR_METHODS.contains(&method)
+        || args.is_empty()
+        || args.starts_with(['=', '.'])
+        || args.ends_with(['{', '(', '[', ','])
+    {
+        return None;
+    }
+    Some(format!("{}{}.{}({}){}", indent, target, method, args, comment))
+}
+
+/// `xs`, `self.items`, `state.queue`
+fn is_mutation_target(target: &str) -> bool {
+    target.split('.').enumerate().all(|(i, segment)| {
+        (i == 0 && segment == "self") || (is_valid_identifier(segment) && !is_rust_keyword(segment))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sugar_lowered() {
+        assert_eq!(lower_method_sugar_line("    xs push 5").as_deref(), Some("    xs.push(5)"));
+        assert_eq!(
+            lower_method_sugar_line("scores insert name.clone(), 10   // first score").as_deref(),
+            Some("scores.insert(name.clone(), 10)   // first score")
+        );
+        assert_eq!(lower_method_sugar_line("self.queue remove 0").as_deref(), Some("self.queue.remove(0)"));
+        assert_eq!(lower_method_sugar_line("s push_str \"a b\"").as_deref(), Some("s.push_str(\"a b\")"));
+    }
+
+    #[test]
+    fn test_other_lines_untouched() {
+        assert_eq!(lower_method_sugar_line("xs.push(5)"), None);
+        assert_eq!(lower_method_sugar_line("x = push 5"), None);
+        assert_eq!(lower_method_sugar_line("return push"), None);
+        assert_eq!(lower_method_sugar_line("else insert x"), None);
+        assert_eq!(lower_method_sugar_line("xs push"), None);
+        assert_eq!(lower_method_sugar_line("xs extend [1,"), None);
+        assert_eq!(lower_method_sugar_line("// xs push 5"), None);
+        assert_eq!(lower_method_sugar_line("xs sort 5"), None);
+    }
+
+    #[test]
+    fn test_source_line_count_kept() {
+        let src = "fn f() {\n    xs = vec![1]\n    xs push 2\n}";
+        let lowered = lower_method_sugar(src);
+        assert_eq!(lowered.lines().count(), 4);
+        assert_eq!(lowered.lines().nth(2), Some("    xs.push(2)"));
+    }
+}
@@ -0,0 +1,630 @@
+//! Migration Assistant
+//!
+//! `rustsp migrate file.rs` turns an existing Rust file into a skeleton
+//! .rss, item by item, and reports which items could be written in RustS+:
+//!
+//! ```text
+//! Migration report for geometry.rs
+//!   lifted   use std::fmt      (geometry.rs:1)
+//!   lifted   struct Point      (geometry.rs:3)
+//!   rust {}  trait Shape       (geometry.rs:8)   no RustS+ form for `trait` items
+//!   rust {}  fn parse          (geometry.rs:21)  lowering does not give back the original
+//!   2 of 4 items lifted, 2 left in `rust { }` blocks marked TODO(migrate)
+//! ```
+//!
+//! Each top-level item is lifted line by line (`name: T` → `name T`, `<T>` →
+//! `[T]`, `let mut x = e;` → `mut x = e`, `outer` on assignments to a
+//! binding of an enclosing block, no `;`). A lifted item is kept only
+//! if it passes Stage 1 and Stage 2 lowers it back, in the context of the
+//! whole skeleton, to the original tokens; anything else stays as written
+//! inside a `rust { ... }` block under a `// TODO(migrate): ...` marker.
+//! Lifted functions get the effects Stage 1 detects declared on their
+//! signature. The check is deliberately strict: lowering that only adds a
+//! `.clone()` or `&` still sends the item to `rust { }`, where it keeps its
+//! exact meaning.
+
+use crate::anti_fail_logic::{analyze_functions_by_line, analyze_with_diagnostics};
+use crate::callgraph::undeclared_effects;
+use crate::dialect::{function_dialect, Dialect};
+use crate::error_msg::ErrorCategory;
+use crate::ident::is_valid_identifier;
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, delimiter_positions, find_matching_close, split_top_level_types};
+use crate::transpile_main::{lower_rusts, LoweringOptions};
+
+/// Marker put above every item left in a `rust { }` block
+pub const TODO_MARKER: &str = "// TODO(migrate):";
+
+/// How many times items are re-checked after one of them is sent back to
+/// `rust { }`: a demoted item can change how its neighbours lower
+const MAX_ROUNDS: usize = 8;
+
+/// One top-level item of the Rust file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigratedItem {
+    /// `fn`, `struct`, `enum`, `impl`, `use`, `trait`, ...
+    pub kind: String,
+    pub name: String,
+    /// 1-based line of the item in the Rust file
+    pub line: usize,
+    /// The item as written, attributes and comments above it included
+    pub original: Vec<String>,
+    /// The RustS+ form, or `None` when it stays in a `rust { }` block
+    pub lifted: Option<Vec<String>>,
+    /// Why the item could not be lifted
+    pub reason: Option<String>,
+}
+
+impl MigratedItem {
+    pub fn is_lifted(&self) -> bool {
+        self.lifted.is_some()
+    }
+
+    fn demote(&mut self, reason: impl Into<String>) {
+        self.lifted = None;
+        self.reason = Some(reason.into());
+    }
+
+    /// Lines of the item in the skeleton
+    fn skeleton_lines(&self) -> Vec<String> {
+        match &self.lifted {
+            Some(lines) => lines.clone(),
+            None => {
+                let mut lines = vec![format!("{} {}", TODO_MARKER, self.reason.as_deref().unwrap_or("not lifted")), "rust {".to_string()];
+                lines.extend(self.original.iter().cloned());
+                lines.push("}".to_string());
+                lines
+            }
+        }
+    }
+}
+
+/// A Rust file, item by item
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    pub items: Vec<MigratedItem>,
+}
+
+impl Migration {
+    /// The skeleton .rss
+    pub fn to_rss(&self) -> String {
+        self.layout().0
+    }
+
+    /// Skeleton text and, per item, its first line (0-based) in it
+    fn layout(&self) -> (String, Vec<usize>) {
+        let mut lines: Vec<String> = Vec::new();
+        let mut starts = Vec::with_capacity(self.items.len());
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            starts.push(lines.len());
+            lines.extend(item.skeleton_lines());
+        }
+        let mut text = lines.join("\n");
+        text.push('\n');
+        (text, starts)
+    }
+
+    pub fn lifted_count(&self) -> usize {
+        self.items.iter().filter(|item| item.is_lifted()).count()
+    }
+
+    /// Construct-by-construct report; `file` names the Rust file
+    pub fn report(&self, file: &str) -> String {
+        let mut out = format!("Migration report for {}\n", file);
+        let rows: Vec<(String, String)> = self.items.iter()
+            .map(|item| (format!("{} {}", item.kind, item.name), format!("({}:{})", file, item.line)))
+            .collect();
+        let construct_width = rows.iter().map(|(construct, _)| construct.chars().count()).max().unwrap_or(0);
+        let location_width = rows.iter().map(|(_, location)| location.len()).max().unwrap_or(0);
+        for (item, (construct, location)) in self.items.iter().zip(&rows) {
+            let status = if item.is_lifted() { "lifted " } else { "rust {}" };
+            let line = match &item.reason {
+                Some(reason) => format!("  {}  {:<cw$}  {:<lw$}  {}", status, construct, location, reason, cw = construct_width, lw = location_width),
+                None => format!("  {}  {:<cw$}  {}", status, construct, location, cw = construct_width),
+            };
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        let left = self.items.len() - self.lifted_count();
+        out.push_str(&format!("  {} of {} items lifted", self.lifted_count(), self.items.len()));
+        if left > 0 {
+            out.push_str(&format!(", {} left in `rust {{ }}` blocks marked TODO(migrate)", left));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Migrate a Rust source file
+pub fn migrate(rust: &str) -> Migration {
+    let mut migration = Migration {
+        items: split_items(rust).into_iter()
+            .map(|(line, original)| {
+                let (kind, name) = item_head(&original);
+                let lifted = lift_item(&kind, &original);
+                let reason = match &lifted {
+                    Ok(_) => None,
+                    Err(reason) => Some(reason.clone()),
+                };
+                MigratedItem { kind, name, line, original, lifted: lifted.ok(), reason }
+            })
+            .collect(),
+    };
+
+    for _ in 0..MAX_ROUNDS {
+        if !demote_failing_items(&mut migration) {
+            break;
+        }
+    }
+    declare_effects(&mut migration);
+    migration
+}
+
+/// Check every lifted item against Stage 1 and Stage 2; returns whether any
+/// item was sent back to `rust { }`
+fn demote_failing_items(migration: &mut Migration) -> bool {
+    let (skeleton, starts) = migration.layout();
+    let item_at = |line: usize| starts.iter().rposition(|&start| start <= line);
+    let mut failures: Vec<(usize, String)> = Vec::new();
+
+    let (_, errors) = analyze_with_diagnostics(&skeleton, "migrate.rss");
+    for error in errors.iter().filter(|e| e.category() != ErrorCategory::Effect) {
+        if let Some(i) = error.location.line.checked_sub(1).and_then(item_at) {
+            failures.push((i, format!("Stage 1: {}", error.title)));
+        }
+    }
+
+    let options = LoweringOptions { preserve_lines: true, ..LoweringOptions::default() };
+    match lower_rusts(&skeleton, &options) {
+        Err(errors) => {
+            for error in &errors {
+                match error.location.line.checked_sub(1).and_then(item_at) {
+                    Some(i) => failures.push((i, format!("lowering failed: {}", error.title))),
+                    None => failures.extend((0..migration.items.len()).map(|i| (i, format!("lowering failed: {}", error.title)))),
+                }
+            }
+        }
+        Ok(lowered) => {
+            let lowered: Vec<&str> = lowered.lines().collect();
+            for (i, item) in migration.items.iter().enumerate() {
+                let Some(lifted) = &item.lifted else { continue };
+                let start = starts[i];
+                let round_trip = lowered.get(start..start + lifted.len());
+                if round_trip.is_none_or(|round_trip| !gives_back(round_trip, &item.original)) {
+                    failures.push((i, "lowering does not give back the original".to_string()));
+                }
+            }
+        }
+    }
+
+    let mut demoted = false;
+    for (i, reason) in failures {
+        if migration.items[i].is_lifted() {
+            migration.items[i].demote(reason);
+            demoted = true;
+        }
+    }
+    demoted
+}
+
+/// Declare on each lifted function the effects Stage 1 detects in it
+fn declare_effects(migration: &mut Migration) {
+    let (skeleton, starts) = migration.layout();
+    for (line, info) in analyze_functions_by_line(&skeleton, "migrate.rss") {
+        let effects = undeclared_effects(&info);
+        let Some(i) = starts.iter().rposition(|&start| start < line) else { continue };
+        if effects.is_empty() {
+            continue;
+        }
+        let offset = line - 1 - starts[i];
+        let effects: Vec<String> = effects.into_iter().collect();
+        if let Some(signature) = migration.items[i].lifted.as_mut().and_then(|lines| lines.get_mut(offset)) {
+            if let Some(with_effects) = add_effects_clause(signature, &effects) {
+                *signature = with_effects;
+            }
+        }
+    }
+}
+
+/// `fn f(x T) R {` → `fn f(x T) effects(io) R {`
+fn add_effects_clause(signature: &str, effects: &[String]) -> Option<String> {
+    let close = find_matching_close(signature, signature.find('(')?)?;
+    Some(format!("{} effects({}){}", &signature[..=close], effects.join(", "), &signature[close + 1..]))
+}
+
+/// Whether lowering gave back the original item, layout aside. An
+/// assignment may lose its `;`: it is `()` either way
+fn gives_back(lowered: &[&str], original: &[String]) -> bool {
+    if normalize(&lowered.join("\n")) == normalize(&original.join("\n")) {
+        return true;
+    }
+    lowered.len() == original.len() && lowered.iter().zip(original).all(|(lowered, original)| {
+        let (lowered, original) = (normalize(lowered), normalize(original));
+        lowered == original || (original == format!("{};", lowered) && is_assignment(&original))
+    })
+}
+
+/// `target = ..` or `target op= ..`, `*` and fields included
+fn is_assignment(code: &str) -> bool {
+    let Some((pos, _)) = delimiter_positions(code, &['=']).into_iter().next() else {
+        return false;
+    };
+    let before = &code[..pos];
+    if before.trim_start().starts_with("let ") || code[pos + 1..].starts_with(['=', '>']) || before.ends_with(['=', '!']) {
+        return false;
+    }
+    // `<=` and `>=` compare, `<<=` and `>>=` assign
+    !(before.ends_with(['<', '>']) && !before.ends_with("<<") && !before.ends_with(">>"))
+}
+
+/// Compare code ignoring layout
+fn normalize(code: &str) -> String {
+    code.lines()
+        .map(code_part)
+        .flat_map(|line| line.split_whitespace())
+        .collect()
+}
+
+/// A line without its `//` comment
+fn code_part(line: &str) -> &str {
+    delimiter_positions(line, &['/'])
+        .into_iter()
+        .find(|&(pos, _)| line[pos + 1..].starts_with('/'))
+        .map_or(line, |(pos, _)| &line[..pos])
+}
+
+fn brace_delta(line: &str) -> i32 {
+    let (open, close) = count_braces_outside_strings(code_part(line));
+    open as i32 - close as i32
+}
+
+/// Top-level items with the comments and attributes above them, as
+/// `(1-based line of the first line, lines)`
+fn split_items(rust: &str) -> Vec<(usize, Vec<String>)> {
+    let mut items = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut start = 0;
+    let mut depth = 0i32;
+    let mut in_block_comment = false;
+    for (idx, line) in rust.lines().enumerate() {
+        let trimmed = line.trim();
+        if current.is_empty() {
+            if trimmed.is_empty() {
+                continue;
+            }
+            start = idx + 1;
+        }
+        current.push(line.to_string());
+        if in_block_comment {
+            in_block_comment = !trimmed.contains("*/");
+            continue;
+        }
+        if depth == 0 && trimmed.starts_with("/*") {
+            in_block_comment = !trimmed.contains("*/");
+            continue;
+        }
+        let code = code_part(line).trim();
+        depth += brace_delta(line);
+        let is_prefix = code.is_empty() || (code.starts_with("#[") && code.ends_with(']')) || code.starts_with("#![");
+        if depth <= 0 && !is_prefix && (code.ends_with('}') || code.ends_with(';')) {
+            items.push((start, std::mem::take(&mut current)));
+            depth = 0;
+        }
+    }
+    if current.iter().any(|line| !line.trim().is_empty()) {
+        items.push((start, current));
+    }
+    items
+}
+
+/// Leading qualifiers of an item or function
+fn strip_qualifiers(code: &str) -> &str {
+    let mut code = code;
+    loop {
+        let before = code;
+        for qualifier in ["pub(crate) ", "pub(super) ", "pub ", "const ", "async ", "unsafe ", "extern \"C\" "] {
+            code = code.strip_prefix(qualifier).unwrap_or(code);
+        }
+        if code == before {
+            return code;
+        }
+    }
+}
+
+/// Index of the first line of an item that is not a comment or attribute
+fn head_index(lines: &[String]) -> Option<usize> {
+    let mut in_block_comment = false;
+    lines.iter().position(|line| {
+        let trimmed = line.trim();
+        if in_block_comment {
+            in_block_comment = !trimmed.contains("*/");
+            return false;
+        }
+        if trimmed.starts_with("/*") {
+            in_block_comment = !trimmed.contains("*/");
+            return false;
+        }
+        !(trimmed.is_empty() || trimmed.starts_with("//") || trimmed.starts_with("#[") || trimmed.starts_with("#!["))
+    })
+}
+
+/// Keyword and name of an item: `("fn", "main")`, `("impl", "Display for Point")`
+fn item_head(lines: &[String]) -> (String, String) {
+    let Some(head) = head_index(lines) else {
+        return ("comment".to_string(), String::new());
+    };
+    let code = lines[head].trim();
+    // `const fn` is a function, `const X` is not
+    let rest = match strip_qualifiers(code) {
+        rest if rest.starts_with("fn ") => rest,
+        _ => ["pub(crate) ", "pub(super) ", "pub "].iter().fold(code, |code, v| code.strip_prefix(v).unwrap_or(code)),
+    };
+    let (keyword, after) = rest.split_once(' ').unwrap_or((rest, ""));
+    let name = match keyword {
+        "impl" => {
+            let header = after.trim_end_matches('{').trim();
+            header.to_string()
+        }
+        "use" | "mod" => after.trim_end_matches([';', '{']).trim().to_string(),
+        _ => identifier_prefix(after),
+    };
+    (keyword.to_string(), name)
+}
+
+fn identifier_prefix(text: &str) -> String {
+    text.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect()
+}
+
+/// The RustS+ form of an item, or why there is none
+fn lift_item(kind: &str, lines: &[String]) -> Result<Vec<String>, String> {
+    let Some(head) = head_index(lines) else {
+        return Ok(lines.to_vec());
+    };
+    let mut lifted: Vec<String> = lines[..head].to_vec();
+    let mut depth = 0i32;
+    // `let` bindings of the current function and the depth they live at
+    let mut locals: Vec<(String, i32)> = Vec::new();
+    for line in &lines[head..] {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let code = line.trim();
+        let is_signature = match kind {
+            "fn" => depth == 0 && lifted.len() == head,
+            "impl" => depth == 1 && strip_qualifiers(code).starts_with("fn "),
+            _ => false,
+        };
+        let lifted_line = if is_signature {
+            locals.clear();
+            lift_signature(code)
+        } else if depth == 0 && lifted.len() == head {
+            match kind {
+                "use" => Some(code.trim_end_matches(';').to_string()),
+                "struct" | "enum" | "impl" => lift_type_header(code),
+                _ => None,
+            }
+        } else {
+            match kind {
+                "impl" if depth == 1 => Some(code.to_string()),
+                "fn" | "impl" => lift_statement(code).map(|statement| {
+                    if let Some(name) = let_binding(code) {
+                        locals.push((name.to_string(), depth));
+                    }
+                    // Assigning a binding of an enclosing block needs `outer`
+                    let declared_at = assignment_target(code)
+                        .and_then(|target| locals.iter().rev().find(|(name, _)| name == target))
+                        .map(|&(_, at)| at);
+                    match declared_at {
+                        Some(at) if at < depth => format!("outer {}", statement),
+                        _ => statement,
+                    }
+                }),
+                "struct" | "enum" => lift_field(code),
+                _ => None,
+            }
+        };
+        let Some(lifted_line) = lifted_line else {
+            return Err(match kind {
+                "fn" | "struct" | "enum" | "impl" | "use" => format!("cannot lift `{}`", code),
+                other => format!("no RustS+ form for `{}` items", other),
+            });
+        };
+        lifted.push(format!("{}{}", indent, lifted_line));
+        depth += brace_delta(line);
+        locals.retain(|&(_, at)| at <= depth);
+    }
+
+    if kind == "fn" {
+        let body: Vec<&str> = lifted[head + 1..].iter().map(String::as_str).collect();
+        if function_dialect(&lifted[head], &body) == Dialect::Rust {
+            return Err("still reads as Rust once lifted".to_string());
+        }
+    }
+    Ok(lifted)
+}
+
+/// `<` and `>` of generics to `[` and `]`, leaving `->` alone
+fn lift_type(ty: &str) -> String {
+    let mut out = String::with_capacity(ty.len());
+    let mut prev = ' ';
+    for c in ty.chars() {
+        out.push(match c {
+            '<' => '[',
+            '>' if prev != '-' => ']',
+            c => c,
+        });
+        prev = c;
+    }
+    out
+}
+
+/// `pub struct Wrapper<T> {` → `pub struct Wrapper[T] {`; also `impl` headers
+fn lift_type_header(code: &str) -> Option<String> {
+    if code.contains(" where ") || code.starts_with("where") {
+        return None;
+    }
+    Some(lift_type(code.trim_end_matches(';')))
+}
+
+/// `fn f<T>(a: T, mut b: &str) -> R {` → `fn f[T](a T, mut b &str) R {`
+fn lift_signature(code: &str) -> Option<String> {
+    let body = code.strip_suffix('{')?.trim_end();
+    let fn_pos = code.len() - strip_qualifiers(code).len();
+    let qualifiers = &code[..fn_pos];
+    let rest = code[fn_pos..].strip_prefix("fn ")?;
+    let name_end = rest.find(['<', '('])?;
+    let name = &rest[..name_end];
+    if !is_valid_identifier(name) {
+        return None;
+    }
+    let open = fn_pos + 3 + rest.find('(')?;
+    let close = find_matching_close(body, open)?;
+    let generics = lift_type(&body[fn_pos + 3 + name_end..open]);
+    let params: Vec<String> = split_top_level_types(&body[open + 1..close], ',')
+        .into_iter()
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .map(lift_param)
+        .collect::<Option<_>>()?;
+    let after = body[close + 1..].trim();
+    let ret = match after.strip_prefix("->") {
+        Some(ret) if !ret.contains(" where ") => format!(" {}", lift_type(ret.trim())),
+        Some(_) => return None,
+        None if after.is_empty() => String::new(),
+        None => return None,
+    };
+    Some(format!("{}fn {}{}({}){} {{", qualifiers, name, generics, params.join(", "), ret))
+}
+
+fn lift_param(param: &str) -> Option<String> {
+    if param.ends_with("self") {
+        return Some(param.to_string());
+    }
+    let (pattern, ty) = param.split_once(':')?;
+    Some(format!("{} {}", pattern.trim(), lift_type(ty.trim())))
+}
+
+/// `let mut x: T = e;` → `mut x T = e`; other statements lose their `;`
+fn lift_statement(code: &str) -> Option<String> {
+    let Some(binding) = code.strip_prefix("let ") else {
+        return Some(code.strip_suffix(';').unwrap_or(code).to_string());
+    };
+    let (is_mut, binding) = match binding.strip_prefix("mut ") {
+        Some(rest) => (true, rest),
+        None => (false, binding),
+    };
+    let eq = binding.find(" = ")?;
+    let (pattern, init) = (&binding[..eq], &binding[eq + 3..]);
+    let pattern = match pattern.split_once(':') {
+        Some((name, ty)) if is_valid_identifier(name.trim()) => format!("{} {}", name.trim(), lift_type(ty.trim())),
+        Some(_) => return None,
+        None => pattern.trim().to_string(),
+    };
+    let init = init.strip_suffix(';').unwrap_or(init);
+    Some(format!("{}{} = {}", if is_mut { "mut " } else { "" }, pattern, init))
+}
+
+/// Name bound by `let [mut] name[: T] = ..`
+fn let_binding(code: &str) -> Option<&str> {
+    let rest = code.strip_prefix("let ")?;
+    let rest = rest.strip_prefix("mut ").unwrap_or(rest);
+    let name = &rest[..rest.find([':', ' ', '='])?];
+    is_valid_identifier(name).then_some(name)
+}
+
+/// Binding assigned by `name = ..` or `name += ..`
+fn assignment_target(code: &str) -> Option<&str> {
+    let end = code.find(|c: char| !c.is_alphanumeric() && c != '_')?;
+    let (name, rest) = code.split_at(end);
+    let rest = rest.trim_start();
+    let operator = ["<<=", ">>=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "="]
+        .into_iter()
+        .find(|op| rest.starts_with(op))?;
+    let assigns = !rest[operator.len()..].starts_with(['=', '>']);
+    (assigns && is_valid_identifier(name)).then_some(name)
+}
+
+/// Struct fields and enum variants: `pub x: T,` → `pub x T`, `A(T),` → `A(T)`
+fn lift_field(code: &str) -> Option<String> {
+    let code = code.strip_suffix(',').unwrap_or(code);
+    if code.is_empty() || code.starts_with("//") || code.starts_with("#[") || code.starts_with('}') {
+        return Some(code.to_string());
+    }
+    // `Move { x: i32, y: i32 }` on one line
+    if let (Some(open), true) = (code.find('{'), code.ends_with('}')) {
+        let fields: Vec<String> = split_top_level_types(&code[open + 1..code.len() - 1], ',')
+            .into_iter()
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(lift_field)
+            .collect::<Option<_>>()?;
+        return Some(format!("{}{{ {} }}", &code[..open], fields.join(", ")));
+    }
+    let declaration = code.trim_end_matches('{').trim_end();
+    let visibility = declaration.len() - strip_qualifiers(declaration).len();
+    match declaration[visibility..].split_once(':') {
+        Some((name, ty)) if is_valid_identifier(name.trim()) && !ty.starts_with(':') => {
+            let opens = if code.ends_with('{') { " {" } else { "" };
+            Some(format!("{}{} {}{}", &declaration[..visibility], name.trim(), lift_type(ty.trim()), opens))
+        }
+        _ => Some(lift_type(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = "use std::fmt;\n\n#[derive(Debug)]\nstruct Point {\n    x: i32,\n    y: i32,\n}\n\ntrait Shape {\n    fn area(&self) -> f64;\n}\n\nfn origin() -> Point {\n    let p = Point { x: 0, y: 0 };\n    p\n}\n\nfn sum(xs: &[i32]) -> i32 {\n    let mut t = 0;\n    for x in xs {\n        t += x;\n        println!(\"{}\", x);\n    }\n    t\n}\n\nfn main() {\n    let mut n = 1;\n    n += 1;\n    println!(\"{}\", n);\n}\n";
+
+    #[test]
+    fn test_split_items() {
+        let items = split_items(RUST);
+        let heads: Vec<(String, String)> = items.iter().map(|(_, lines)| item_head(lines)).collect();
+        assert_eq!(heads.iter().map(|(k, n)| format!("{} {}", k, n)).collect::<Vec<_>>(),
+            vec!["use std::fmt", "struct Point", "trait Shape", "fn origin", "fn sum", "fn main"]);
+        assert_eq!(items[1].0, 3);
+        assert_eq!(items[1].1.len(), 5);
+    }
+
+    #[test]
+    fn test_lifting_lines() {
+        assert_eq!(lift_signature("pub fn f<T: Clone>(a: Vec<T>, mut b: &str) -> Option<T> {").as_deref(),
+            Some("pub fn f[T: Clone](a Vec[T], mut b &str) Option[T] {"));
+        assert_eq!(lift_signature("fn area(&self) -> f64;"), None);
+        assert_eq!(lift_statement("let mut total: u64 = 0;").as_deref(), Some("mut total u64 = 0"));
+        assert_eq!(lift_statement("total += x;").as_deref(), Some("total += x"));
+        assert_eq!(assignment_target("total += x;"), Some("total"));
+        assert_eq!(assignment_target("x => 1,"), None);
+        assert_eq!(assignment_target("x == y"), None);
+        assert!(is_assignment("*counts.entry(w).or_insert(0) += 1;"));
+        assert!(!is_assignment("a <= b") && !is_assignment("let x = 1;"));
+        assert_eq!(lift_field("pub items: Vec<String>,").as_deref(), Some("pub items Vec[String]"));
+        assert_eq!(lift_field("Move { x: i32, y: i32 },").as_deref(), Some("Move { x i32, y i32 }"));
+        assert_eq!(add_effects_clause("fn f(x i32) i32 {", &["io".to_string()]).as_deref(), Some("fn f(x i32) effects(io) i32 {"));
+    }
+
+    #[test]
+    fn test_migrate_lifts_and_falls_back() {
+        let migration = migrate(RUST);
+        let unlifted: Vec<String> = migration.items.iter()
+            .filter(|item| !item.is_lifted())
+            .map(|item| format!("{} {}", item.kind, item.name))
+            .collect();
+        assert!(unlifted == ["trait Shape"], "{:?}", unlifted);
+
+        let rss = migration.to_rss();
+        assert!(rss.contains("struct Point {\n    x i32\n    y i32\n}"), "{}", rss);
+        assert!(rss.contains("// TODO(migrate): no RustS+ form for `trait` items\nrust {\ntrait Shape {"), "{}", rss);
+        assert!(rss.contains("fn main() {\n    mut n = 1\n    n += 1\n"), "{}", rss);
+        // Effects are declared and enclosing bindings assigned through `outer`
+        assert!(rss.contains("fn sum(xs &[i32]) effects(io) i32 {\n    mut t = 0\n    for x in xs {\n        outer t += x\n"), "{}", rss);
+        // The skeleton is accepted as it stands
+        assert!(crate::anti_fail_logic::check_logic(&rss, "geo.rss").is_ok(), "{}", rss);
+        assert!(lower_rusts(&rss, &LoweringOptions::default()).is_ok());
+
+        let report = migration.report("geo.rs");
+        assert!(report.contains("trait Shape"), "{}", report);
+        assert!(report.ends_with("left in `rust { }` blocks marked TODO(migrate)\n"), "{}", report);
+    }
+}
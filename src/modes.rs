@@ -22,6 +22,7 @@ pub struct LiteralModeEntry {
     pub kind: LiteralKind,
     pub start_depth: usize, // Brace depth when we entered
     pub is_assignment: bool, // true = `x = Struct {}`, false = bare `Struct {}` (return expr)
+    pub call_parens: usize, // Call parens left open before the literal: `f(a, Struct {` = 1
 }
 
 #[derive(Debug, Clone)]
@@ -35,7 +36,47 @@ impl LiteralModeStack {
     }
     
     pub fn enter(&mut self, kind: LiteralKind, depth: usize, is_assignment: bool) {
-        self.stack.push(LiteralModeEntry { kind, start_depth: depth, is_assignment });
+        self.stack.push(LiteralModeEntry { kind, start_depth: depth, is_assignment, call_parens: 0 });
+    }
+    
+    /// Enter the literals a line inside a literal leaves open, one entry per
+    /// level of its net brace delta: `to = Route { from = Point { x = 1 }, to = Point {`
+    /// goes from `prev_depth` to `depth` = `prev_depth + 2`, and each of the
+    /// two closing `}` lines exits one of them
+    pub fn enter_nested(&mut self, kind: LiteralKind, prev_depth: usize, depth: usize) {
+        for level in prev_depth + 1..=depth {
+            self.stack.push(LiteralModeEntry { kind, start_depth: level, is_assignment: false, call_parens: 0 });
+        }
+    }
+    
+    /// Exit the nested literals a line closed after its first token:
+    /// `x = 2 }`. A line that also closes the outermost literal cannot be
+    /// lowered; every literal is left open so the item is reported.
+    pub fn exit_closed(&mut self, depth: usize) {
+        if self.stack.first().is_some_and(|entry| entry.start_depth > depth) {
+            return;
+        }
+        while self.stack.last().is_some_and(|entry| entry.start_depth > depth) {
+            self.stack.pop();
+        }
+    }
+    
+    /// Enter a literal that is an argument of a call spanning lines:
+    /// `register(User {` ... `})` (`call_parens` = 1)
+    pub fn enter_in_call(&mut self, kind: LiteralKind, depth: usize, call_parens: usize) {
+        self.stack.push(LiteralModeEntry { kind, start_depth: depth, is_assignment: false, call_parens });
+    }
+    
+    /// The current literal's value is assigned: its closing line ends a statement
+    pub fn mark_assignment(&mut self) {
+        if let Some(entry) = self.stack.last_mut() {
+            entry.is_assignment = true;
+        }
+    }
+    
+    /// Call parens the current literal's closing line must also close
+    pub fn current_call_parens(&self) -> usize {
+        self.stack.last().map(|e| e.call_parens).unwrap_or(0)
     }
     
     pub fn is_active(&self) -> bool {
@@ -86,6 +127,8 @@ pub struct ArrayModeEntry {
     pub var_type: Option<String>,   // Explicit type annotation if any
     pub needs_let: bool,            // Whether to emit `let`
     pub needs_mut: bool,            // Whether to emit `mut`
+    pub is_row: bool,               // Nested row `[...]` of an enclosing array
+    pub call_parens: usize,         // Call parens the closing `]` also closes: `Vec::from([` = 1
 }
 
 #[derive(Debug, Clone)]
@@ -107,6 +150,31 @@ impl ArrayModeStack {
             var_type,
             needs_let,
             needs_mut,
+            is_row: false,
+            call_parens: 0,
+        });
+    }
+    
+    /// The current array is the argument of a call opened on the same line
+    /// (`Vec::from([`, `HashMap::from([`): its closing line is `])`
+    pub fn mark_in_call(&mut self, call_parens: usize) {
+        if let Some(entry) = self.stack.last_mut() {
+            entry.call_parens = call_parens;
+        }
+    }
+    
+    /// Enter a nested row of the current array (`grid = [` → `[`).
+    /// `bracket_depth` is the depth inside the row; it closes as an element (`],`).
+    pub fn enter_row(&mut self, bracket_depth: usize) {
+        self.stack.push(ArrayModeEntry {
+            start_bracket_depth: bracket_depth,
+            is_assignment: false,
+            var_name: String::new(),
+            var_type: None,
+            needs_let: false,
+            needs_mut: false,
+            is_row: true,
+            call_parens: 0,
         });
     }
     
@@ -259,6 +327,31 @@ mod tests {
         assert!(!stack.is_active());
     }
     
+    #[test]
+    fn test_literal_mode_stack_nested_levels() {
+        let mut stack = LiteralModeStack::new();
+        stack.enter(LiteralKind::Struct, 2, true);
+        // `route = Route { from = Point { x = 1 }, to = Point {`: depth 2 -> 4
+        stack.enter_nested(LiteralKind::Struct, 2, 4);
+        for closed in [3, 2] {
+            assert!(stack.should_exit(closed));
+            stack.exit();
+            assert!(stack.is_active());
+        }
+        assert!(stack.current_is_assignment());
+        
+        // `x = 2 } }` closes both on a field line
+        stack.enter_nested(LiteralKind::Struct, 2, 4);
+        stack.exit_closed(2);
+        assert!(stack.current_is_assignment());
+        assert!(!stack.should_exit(3));
+        
+        // Closing the outer literal mid-line too leaves everything open
+        stack.enter_nested(LiteralKind::Struct, 2, 3);
+        stack.exit_closed(1);
+        assert!(!stack.current_is_assignment());
+    }
+    
     #[test]
     fn test_array_mode_stack() {
         let mut stack = ArrayModeStack::new();
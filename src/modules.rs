@@ -0,0 +1,370 @@
+//! Multi-File Programs
+//!
+//! `mod name` without a body (a trailing `;` is optional) at the top level
+//! of a file includes another `.rss` file as module `name`, found where
+//! rustc looks for module files:
+//!
+//! | Declared in                  | `mod util` is read from                    |
+//! |------------------------------|--------------------------------------------|
+//! | the input file, a `mod.rss`  | `util.rss` or `util/mod.rss` next to it    |
+//! | `shapes.rss`                 | `shapes/util.rss` or `shapes/util/mod.rss` |
+//!
+//! Each file is spliced into its parent as an inline `mod util { .. }`, so
+//! Stages 1-3 see one program: an effect performed in one file has to be
+//! declared by callers in every other (`util::log(x)` is a call like any
+//! other), and the output is a single Rust crate. The [`ModuleMap`] of the
+//! program takes each diagnostic back to the file and line it came from.
+//!
+//! ```text
+//! main.rss                 util.rss                 program
+//! mod util                 pub fn log(x i32) ...    mod util {
+//! fn main() effects(io) {                           pub fn log(x i32) ...
+//!     util::log(1)                                  }
+//! }                                                 fn main() effects(io) {
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error_msg::{RsplError, SourceLocation};
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+use crate::source_map::{LineTable, RustcError, SourceMap};
+
+/// `[pub] mod name` without a body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModDecl<'a> {
+    /// `mod name`, `pub(crate) mod name`, ...
+    pub head: &'a str,
+    pub name: &'a str,
+}
+
+/// `[pub[(..)]] mod name[;]`; `None` for an inline `mod name {`
+pub fn parse_mod_decl(trimmed: &str) -> Option<ModDecl<'_>> {
+    let code = trimmed.trim_end_matches(';').trim_end();
+    let visibility = ["pub(crate) ", "pub(super) ", "pub "]
+        .iter()
+        .find_map(|prefix| code.strip_prefix(prefix).map(|_| prefix.len()))
+        .unwrap_or(0);
+    let name = code[visibility..].strip_prefix("mod ")?.trim();
+    let is_identifier = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    is_identifier.then_some(ModDecl { head: code, name })
+}
+
+/// Whether `source` includes any module from another file
+pub fn declares_file_modules(source: &str) -> bool {
+    top_level_lines(source).any(|(_, line)| parse_mod_decl(line.trim()).is_some())
+}
+
+/// Lines of `source` outside every `{ }` block, with their 0-based index
+fn top_level_lines(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut depth = 0usize;
+    source.lines().enumerate().filter(move |(_, line)| {
+        let at_top = depth == 0;
+        for (_, delimiter) in delimiter_positions(&strip_inline_comment(line), &['{', '}']) {
+            depth = if delimiter == '{' { depth + 1 } else { depth.saturating_sub(1) };
+        }
+        at_top
+    })
+}
+
+/// One file of a program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleFile {
+    pub path: PathBuf,
+    /// `util::shapes`; empty for the input file
+    pub module: String,
+    pub source: String,
+}
+
+impl ModuleFile {
+    /// Directory that holds the files of this module's `mod` declarations
+    fn child_dir(&self) -> PathBuf {
+        let dir = self.path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let is_mod_root = self.module.is_empty() || self.path.file_name().is_some_and(|name| name == "mod.rss");
+        match self.path.file_stem() {
+            Some(stem) if !is_mod_root => dir.join(stem),
+            _ => dir,
+        }
+    }
+}
+
+/// File and line of every line of a program spliced from several files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleMap {
+    pub files: Vec<ModuleFile>,
+    /// `origins[i]`: (index into `files`, 1-based line) of program line `i + 1`
+    origins: Vec<(usize, usize)>,
+}
+
+impl ModuleMap {
+    /// File and 1-based line that 1-based program line `line` came from
+    pub fn locate(&self, line: usize) -> Option<(&ModuleFile, usize)> {
+        let (file, file_line) = *self.origins.get(line.checked_sub(1)?)?;
+        Some((&self.files[file], file_line))
+    }
+
+    /// Point the locations of `errors`, reported against the program, at
+    /// the files they are in
+    pub fn relocate(&self, errors: &mut [RsplError]) {
+        for error in errors {
+            self.relocate_location(&mut error.location);
+            for (location, _) in &mut error.labels {
+                self.relocate_location(location);
+            }
+            if let Some(explanation) = &mut error.explanation {
+                *explanation = self.relocate_line_refs(explanation);
+            }
+        }
+    }
+
+    fn relocate_location(&self, location: &mut SourceLocation) {
+        if let Some((file, line)) = self.locate(location.line) {
+            location.file = file.path.display().to_string();
+            location.line = line;
+        }
+    }
+
+    /// `(line 12)` in a note → `(util.rss:3)`
+    fn relocate_line_refs(&self, text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("(line ") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + "(line ".len()..];
+            let digits = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+            let located = after[digits..].starts_with(')')
+                .then(|| after[..digits].parse().ok().and_then(|line| self.locate(line)))
+                .flatten();
+            match located {
+                Some((file, line)) => {
+                    out.push_str(&format!("({}:{})", file.path.display(), line));
+                    rest = &after[digits + 1..];
+                }
+                None => {
+                    out.push_str("(line ");
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The source of each file, in the order of `files`, from `program`:
+    /// the program's source rewritten without adding or removing lines.
+    /// The lines a `mod` declaration was spliced into are not taken back.
+    pub fn split(&self, program: &str) -> Vec<String> {
+        self.files.iter().enumerate().map(|(index, file)| {
+            let declarations: Vec<usize> = top_level_lines(&file.source)
+                .filter(|(_, line)| parse_mod_decl(line.trim()).is_some())
+                .map(|(i, _)| i)
+                .collect();
+            let mut lines: Vec<String> = file.source.lines().map(String::from).collect();
+            for (line, &(origin, file_line)) in program.lines().zip(&self.origins) {
+                if origin == index && !declarations.contains(&(file_line - 1)) {
+                    lines[file_line - 1] = line.to_string();
+                }
+            }
+            let mut source = lines.join("\n");
+            if file.source.ends_with('\n') {
+                source.push('\n');
+            }
+            source
+        }).collect()
+    }
+
+    /// A rustc error mapped to a program line, moved to its file, with the
+    /// source map that shows that file's line
+    pub fn relocate_rustc_error(&self, error: &RustcError) -> Option<(RustcError, SourceMap)> {
+        let (file, line) = self.locate(error.line)?;
+        let lines = file.source.lines().count();
+        let map = SourceMap::from_table(file.path.clone(), &file.source, &LineTable::identity(lines));
+        Some((RustcError { file: file.path.display().to_string(), line, ..error.clone() }, map))
+    }
+}
+
+/// A program read from an input file and the module files it includes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// Every file spliced into one source
+    pub source: String,
+    pub map: ModuleMap,
+}
+
+impl Program {
+    /// The program whose input file `path` holds `source`, reading module
+    /// files from disk
+    pub fn load(path: &Path, source: &str) -> Result<Self, String> {
+        Program::load_with(path, source, &|path| std::fs::read_to_string(path))
+    }
+
+    /// [`Program::load`] with module files read by `read`
+    pub fn load_with(path: &Path, source: &str, read: &dyn Fn(&Path) -> io::Result<String>) -> Result<Self, String> {
+        let root = ModuleFile { path: path.to_path_buf(), module: String::new(), source: source.to_string() };
+        let mut map = ModuleMap { files: vec![root], origins: Vec::new() };
+        let mut lines = Vec::new();
+        splice(0, &mut map, &mut lines, &mut vec![0], read)?;
+        let mut source = lines.join("\n");
+        if !lines.is_empty() {
+            source.push('\n');
+        }
+        Ok(Program { source, map })
+    }
+
+    /// Whether any module came from another file
+    pub fn is_multi_file(&self) -> bool {
+        self.map.files.len() > 1
+    }
+}
+
+/// Append the lines of `files[file]` to `out`, each `mod name` replaced by
+/// the file of module `name` in braces
+fn splice(
+    file: usize,
+    map: &mut ModuleMap,
+    out: &mut Vec<String>,
+    ancestors: &mut Vec<usize>,
+    read: &dyn Fn(&Path) -> io::Result<String>,
+) -> Result<(), String> {
+    let source = map.files[file].source.clone();
+    let declarations: Vec<usize> = top_level_lines(&source)
+        .filter(|(_, line)| parse_mod_decl(line.trim()).is_some())
+        .map(|(i, _)| i)
+        .collect();
+    for (i, line) in source.lines().enumerate() {
+        if !declarations.contains(&i) {
+            out.push(line.to_string());
+            map.origins.push((file, i + 1));
+            continue;
+        }
+        let Some(decl) = parse_mod_decl(line.trim()) else {
+            continue;
+        };
+        let child = load_module_file(&map.files[file], decl.name, i + 1, read)?;
+        if let Some(&cycle) = ancestors.iter().find(|&&a| map.files[a].path == child.path) {
+            return Err(format!(
+                "{}:{}: module `{}` includes `{}` again",
+                map.files[file].path.display(), i + 1, decl.name, map.files[cycle].path.display()
+            ));
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        out.push(format!("{}{} {{", indent, decl.head));
+        map.origins.push((file, i + 1));
+        map.files.push(child);
+        let child = map.files.len() - 1;
+        ancestors.push(child);
+        splice(child, map, out, ancestors, read)?;
+        ancestors.pop();
+        out.push(format!("{}}}", indent));
+        map.origins.push((file, i + 1));
+    }
+    Ok(())
+}
+
+/// Read module `name`, declared on line `line` of `parent`, from
+/// `name.rss` or `name/mod.rss`
+fn load_module_file(
+    parent: &ModuleFile,
+    name: &str,
+    line: usize,
+    read: &dyn Fn(&Path) -> io::Result<String>,
+) -> Result<ModuleFile, String> {
+    let dir = parent.child_dir();
+    let candidates = [dir.join(format!("{}.rss", name)), dir.join(name).join("mod.rss")];
+    let mut found: Vec<(PathBuf, String)> = Vec::new();
+    for candidate in candidates.iter() {
+        match read(candidate) {
+            Ok(source) => found.push((candidate.clone(), source)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("{}: {}", candidate.display(), e)),
+        }
+    }
+    let location = format!("{}:{}", parent.path.display(), line);
+    if found.len() > 1 {
+        return Err(format!(
+            "{}: module `{}` is in both `{}` and `{}`; remove one of them",
+            location, name, candidates[0].display(), candidates[1].display()
+        ));
+    }
+    let Some((path, source)) = found.pop() else {
+        return Err(format!(
+            "{}: file not found for module `{}` (looked for `{}` and `{}`)",
+            location, name, candidates[0].display(), candidates[1].display()
+        ));
+    };
+    let module = match parent.module.as_str() {
+        "" => name.to_string(),
+        outer => format!("{}::{}", outer, name),
+    };
+    Ok(ModuleFile { path, module, source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Read files from `files` instead of the disk
+    fn reader(files: &[(&str, &str)]) -> impl Fn(&Path) -> io::Result<String> {
+        let files: HashMap<PathBuf, String> = files.iter().map(|(p, s)| (PathBuf::from(p), s.to_string())).collect();
+        move |path| files.get(path).cloned().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    #[test]
+    fn test_parse_mod_decl() {
+        assert_eq!(parse_mod_decl("mod util"), Some(ModDecl { head: "mod util", name: "util" }));
+        assert_eq!(parse_mod_decl("pub(crate) mod shapes;"), Some(ModDecl { head: "pub(crate) mod shapes", name: "shapes" }));
+        assert!(parse_mod_decl("mod util {").is_none());
+        assert!(parse_mod_decl("module = 1").is_none());
+        assert!(declares_file_modules("pub mod util\n\nfn main() {\n}\n"));
+        assert!(!declares_file_modules("mod tests {\n    mod inner\n}\n"));
+    }
+
+    #[test]
+    fn test_program_spliced_from_files() {
+        let read = reader(&[
+            ("app/util.rss", "pub mod shapes\n\npub fn log(x i32) effects(io) {\n    println!(\"{}\", x)\n}\n"),
+            ("app/util/shapes/mod.rss", "pub fn area(w i32, h i32) i32 {\n    w * h\n}\n"),
+        ]);
+        let program = Program::load_with(Path::new("app/main.rss"), "mod util\n\nfn main() effects(io) {\n    util::log(util::shapes::area(2, 3))\n}\n", &read).unwrap();
+        assert!(program.is_multi_file());
+        let lines: Vec<&str> = program.source.lines().collect();
+        assert_eq!(&lines[..6], ["mod util {", "pub mod shapes {", "pub fn area(w i32, h i32) i32 {", "    w * h", "}", "}"]);
+
+        let (file, line) = program.map.locate(3).unwrap();
+        assert_eq!((file.path.as_path(), file.module.as_str(), line), (Path::new("app/util/shapes/mod.rss"), "util::shapes", 1));
+        let (file, line) = program.map.locate(lines.len()).unwrap();
+        assert_eq!((file.path.as_path(), line), (Path::new("app/main.rss"), 5));
+
+        let single = Program::load_with(Path::new("main.rss"), "fn main() {\n}\n", &read).unwrap();
+        assert!(!single.is_multi_file());
+        assert_eq!(single.source, "fn main() {\n}\n");
+    }
+
+    #[test]
+    fn test_module_file_errors() {
+        let missing = Program::load_with(Path::new("main.rss"), "fn f() {\n}\nmod util\n", &reader(&[])).unwrap_err();
+        assert_eq!(missing, "main.rss:3: file not found for module `util` (looked for `util.rss` and `util/mod.rss`)");
+
+        let both = reader(&[("util.rss", ""), ("util/mod.rss", "")]);
+        assert!(Program::load_with(Path::new("main.rss"), "mod util\n", &both).unwrap_err().contains("is in both"));
+
+        let itself = reader(&[("main.rss", "mod main\n")]);
+        assert!(Program::load_with(Path::new("main.rss"), "mod main\n", &itself).unwrap_err().contains("includes `main.rss` again"));
+    }
+
+    #[test]
+    fn test_effects_checked_across_files() {
+        let read = reader(&[("util.rss", "pub fn log(x i32) effects(io) {\n    println!(\"{}\", x)\n}\n")]);
+        let program = Program::load_with(Path::new("main.rss"), "mod util\n\nfn show(x i32) {\n    util::log(x)\n}\n", &read).unwrap();
+        let mut errors = crate::anti_fail_logic::check_logic(&program.source, "main.rss").unwrap_err();
+        program.map.relocate(&mut errors);
+        let error = errors.iter().find(|e| e.code == crate::error_msg::ErrorCode::RSPL301).unwrap();
+        assert_eq!((error.location.file.as_str(), error.location.line), ("main.rss", 3));
+        assert!(error.labels.iter().any(|(l, _)| l.file == "util.rss" && l.line == 2), "{:?}", error.labels);
+        assert!(error.explanation.as_deref().unwrap().contains("(util.rss:2)"), "{:?}", error.explanation);
+        assert!(crate::anti_fail_logic::format_logic_errors(&errors).contains("::: util.rss:2:"));
+    }
+}
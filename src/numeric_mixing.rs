@@ -0,0 +1,401 @@
+//! Mixed Numeric Types (RSPL047)
+//!
+//! Rust never converts between numeric types implicitly, so an integer
+//! binding used with a float only fails in rustc, as E0277 on the generated
+//! code. Stage 1 tracks the numeric type of every binding it can infer and
+//! checks arithmetic, compound assignments and annotated assignments:
+//!
+//! ```text
+//! count = 0
+//! total = count + 1.5         // RSPL047: help: `count as f64 + 1.5`
+//! ratio f64 = 0.5             // (parameter)
+//! scaled = ratio * 2          // RSPL047: help: `ratio * 2.0`
+//! big: i64 = count            // RSPL047: help: `big: i64 = count as i64`
+//! ```
+//!
+//! Only operands whose type is known are compared: number literals, and
+//! bindings from numeric parameters, annotations, literals and arithmetic
+//! on known operands. Unsuffixed literals take any width of their kind, as
+//! in Rust. A binding that is rebound by an unknown value, a pattern or a
+//! closure parameter becomes unknown again.
+
+use std::collections::HashMap;
+
+use crate::function::{parse_function_line, FunctionParseResult};
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::variable::parse_rusts_assignment;
+
+const NUMERIC_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize",
+    "u8", "u16", "u32", "u64", "u128", "usize",
+    "f32", "f64",
+];
+
+/// The numeric type of an operand: its kind, and its width when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericType {
+    pub float: bool,
+    /// `i64`, `f32`, ...; None for an unsuffixed literal
+    pub name: Option<String>,
+}
+
+impl NumericType {
+    fn named(name: &str) -> Option<Self> {
+        NUMERIC_TYPES.contains(&name).then(|| NumericType { float: name.starts_with('f'), name: Some(name.to_string()) })
+    }
+
+    /// Can a value of this type meet one of `other` in an operation?
+    fn mixes_with(&self, other: &NumericType) -> bool {
+        self.float != other.float || matches!((&self.name, &other.name), (Some(a), Some(b)) if a != b)
+    }
+
+    /// `f64`, or rustc's `{integer}` / `{float}` for an unsuffixed literal
+    pub fn display(&self) -> String {
+        match (&self.name, self.float) {
+            (Some(name), _) => name.clone(),
+            (None, true) => "{float}".to_string(),
+            (None, false) => "{integer}".to_string(),
+        }
+    }
+}
+
+/// An operation on two numeric types that do not mix
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumericMix {
+    /// 1-based line
+    pub line: usize,
+    /// The operation, e.g. `count + 1.5`
+    pub expr: String,
+    /// The operation with one operand converted, e.g. `count as f64 + 1.5`
+    pub fixed: String,
+    /// The operand that `fixed` converts, as written
+    pub operand: String,
+    pub left: NumericType,
+    pub right: NumericType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str,
+    Punct(String),
+}
+
+impl Token {
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self, Token::Punct(q) if q == p)
+    }
+}
+
+fn tokenize(code: &str) -> Vec<Token> {
+    const PAIRS: &[&str] = &["->", "=>", "::", "..", "+=", "-=", "*=", "/=", "%=", "==", "!=", "<=", ">=", "&&", "||"];
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let word_end = |mut j: usize| {
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                j += 1;
+            }
+            j
+        };
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            tokens.push(Token::Str);
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let mut end = word_end(i);
+            if end + 1 < chars.len() && chars[end] == '.' && chars[end + 1].is_ascii_digit() {
+                end = word_end(end + 1);
+            }
+            tokens.push(Token::Number(chars[i..end].iter().collect()));
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let end = word_end(i);
+            tokens.push(Token::Ident(chars[i..end].iter().collect()));
+            i = end;
+        } else {
+            let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if PAIRS.contains(&pair.as_str()) {
+                tokens.push(Token::Punct(pair));
+                i += 2;
+            } else {
+                tokens.push(Token::Punct(c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// `1_000` / `2u8` / `1.5` / `3f32` → its type
+fn literal_type(number: &str) -> NumericType {
+    if let Some(suffix) = NUMERIC_TYPES.iter().find(|t| number.ends_with(*t) && !number.starts_with("0x")) {
+        return NumericType::named(suffix).unwrap();
+    }
+    let hex = number.starts_with("0x") || number.starts_with("0b") || number.starts_with("0o");
+    NumericType { float: !hex && (number.contains('.') || number.contains('e')), name: None }
+}
+
+/// Block-scoped numeric types of the bindings in one function
+struct Bindings {
+    scopes: Vec<HashMap<String, Option<NumericType>>>,
+}
+
+impl Bindings {
+    fn new() -> Self {
+        Bindings { scopes: vec![HashMap::new()] }
+    }
+
+    fn get(&self, name: &str) -> Option<&NumericType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).and_then(Option::as_ref)
+    }
+
+    fn set(&mut self, name: &str, ty: Option<NumericType>) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), ty);
+    }
+
+    fn forget_all(&mut self, tokens: &[Token]) {
+        for token in tokens {
+            if let Token::Ident(name) = token {
+                self.set(name, None);
+            }
+        }
+    }
+}
+
+/// An operand next to an operator: its text and type, if known
+struct Operand {
+    text: String,
+    ty: NumericType,
+    literal: bool,
+}
+
+/// The operand ending at `tokens[end]` (e.g. `count`, `2`, `x as i64`)
+fn operand_before(tokens: &[Token], end: usize, bindings: &Bindings) -> Option<Operand> {
+    let before = |back: usize| end.checked_sub(back).map(|i| &tokens[i]);
+    if let (Some(Token::Ident(ty)), Some(Token::Ident(kw)), Some(Token::Ident(value))) = (before(0), before(1), before(2)) {
+        if kw == "as" {
+            let ty = NumericType::named(ty)?;
+            return (!before(3).is_some_and(|t| t.is_punct(".") || t.is_punct("::")))
+                .then(|| Operand { text: format!("{} as {}", value, ty.display()), ty, literal: false });
+        }
+    }
+    if before(1).is_some_and(|t| t.is_punct(".") || t.is_punct("::") || matches!(t, Token::Ident(kw) if kw == "as")) {
+        return None;
+    }
+    single_operand(&tokens[end], bindings)
+}
+
+/// The operand starting at `tokens[start]`
+fn operand_after(tokens: &[Token], start: usize, bindings: &Bindings) -> Option<Operand> {
+    let operand = single_operand(tokens.get(start)?, bindings)?;
+    match tokens.get(start + 1) {
+        Some(Token::Ident(kw)) if kw == "as" => {
+            let Some(Token::Ident(ty)) = tokens.get(start + 2) else {
+                return None;
+            };
+            let ty = NumericType::named(ty)?;
+            Some(Operand { text: format!("{} as {}", operand.text, ty.display()), ty, literal: false })
+        }
+        Some(t) if t.is_punct(".") || t.is_punct("(") || t.is_punct("[") || t.is_punct("::") || t.is_punct("!") => None,
+        _ => Some(operand),
+    }
+}
+
+fn single_operand(token: &Token, bindings: &Bindings) -> Option<Operand> {
+    match token {
+        Token::Number(number) => Some(Operand { text: number.clone(), ty: literal_type(number), literal: true }),
+        Token::Ident(name) => bindings.get(name).map(|ty| Operand { text: name.clone(), ty: ty.clone(), literal: false }),
+        _ => None,
+    }
+}
+
+/// `(expr, fixed, converted operand)`: convert the integer side to the float
+/// side (an unsuffixed literal becomes `2.0`), or the right side to the left
+/// side's width
+fn conversion(left: &Operand, op: &str, right: &Operand) -> (String, String, String) {
+    let expr = format!("{} {} {}", left.text, op, right.text);
+    let (target, into) = if left.ty.float != right.ty.float && !left.ty.float { (left, right) } else { (right, left) };
+    let converted = if target.literal && target.ty.name.is_none() && into.ty.float {
+        format!("{}.0", target.text)
+    } else {
+        let into_name = into.ty.name.clone().unwrap_or_else(|| if into.ty.float { "f64" } else { "i32" }.to_string());
+        format!("{} as {}", target.text, into_name)
+    };
+    let fixed = if std::ptr::eq(target, left) {
+        format!("{} {} {}", converted, op, right.text)
+    } else {
+        format!("{} {} {}", left.text, op, converted)
+    };
+    (expr, fixed, target.text.clone())
+}
+
+/// The type of a value made only of known operands and arithmetic
+fn value_type(tokens: &[Token], bindings: &Bindings) -> Option<NumericType> {
+    let mut found: Option<NumericType> = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Punct(p) if ["+", "-", "*", "/", "%", "(", ")"].contains(&p.as_str()) => i += 1,
+            _ => {
+                let operand = operand_after(tokens, i, bindings)?;
+                let width = if operand.text.contains(" as ") { 3 } else { 1 };
+                if found.as_ref().is_none_or(|f| f.name.is_none() && operand.ty.name.is_some()) {
+                    found = Some(operand.ty);
+                }
+                i += width;
+            }
+        }
+    }
+    found
+}
+
+/// Numeric parameters of a one-line function header
+fn parameter_types(line: &str) -> Option<Vec<(String, Option<NumericType>)>> {
+    match parse_function_line(line) {
+        FunctionParseResult::RustSPlusSignature(sig) => Some(
+            sig.parameters
+                .iter()
+                .map(|p| (p.name.clone(), NumericType::named(p.param_type.trim())))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// Find every operation on numeric types that do not mix
+pub fn find_numeric_mixes(source: &str) -> Vec<NumericMix> {
+    let mut mixes = Vec::new();
+    let mut bindings = Bindings::new();
+    for (idx, line) in source.lines().enumerate() {
+        let code = strip_inline_comment(line);
+        let trimmed = code.trim();
+        if let Some(params) = parameter_types(trimmed) {
+            bindings = Bindings::new();
+            for (name, ty) in params {
+                bindings.set(&name, ty);
+            }
+        } else if !trimmed.starts_with("#[") {
+            let tokens = tokenize(trimmed);
+            let mut found = |left: &Operand, op: &str, right: &Operand| {
+                if left.ty.mixes_with(&right.ty) {
+                    let (expr, fixed, operand) = conversion(left, op, right);
+                    mixes.push(NumericMix { line: idx + 1, expr, fixed, operand, left: left.ty.clone(), right: right.ty.clone() });
+                }
+            };
+
+            for (i, token) in tokens.iter().enumerate() {
+                let Token::Punct(op) = token else { continue };
+                let arithmetic = i > 0 && ["+", "-", "*", "/", "%"].contains(&op.as_str());
+                let compound = i == 1 && ["+=", "-=", "*=", "/=", "%="].contains(&op.as_str());
+                if !arithmetic && !compound {
+                    continue;
+                }
+                if let (Some(left), Some(right)) = (operand_before(&tokens, i - 1, &bindings), operand_after(&tokens, i + 1, &bindings)) {
+                    let whole_right = compound && tokens.len() == i + if right.text.contains(" as ") { 4 } else { 2 };
+                    if arithmetic || whole_right {
+                        found(&left, op, &right);
+                    }
+                }
+            }
+
+            // `big: i64 = count`
+            if let Some((name, Some(annotation), value)) = parse_rusts_assignment(trimmed) {
+                let value_tokens = tokenize(&value);
+                if let (Some(target), [token]) = (NumericType::named(annotation.trim()), value_tokens.as_slice()) {
+                    if let Some(value) = single_operand(token, &bindings) {
+                        let left = Operand { text: format!("{}: {}", name, annotation.trim()), ty: target, literal: false };
+                        found(&left, "=", &value);
+                    }
+                }
+            }
+
+            // Pattern and closure bindings shadow with unknown types
+            if let Some(pattern) = trimmed.strip_prefix("for ").and_then(|rest| rest.split(" in ").next()) {
+                bindings.forget_all(&tokenize(pattern));
+            }
+            if let Some(arrow) = tokens.iter().position(|t| t.is_punct("=>")) {
+                bindings.forget_all(&tokens[..arrow]);
+            }
+            if let Some(let_at) = tokens.iter().position(|t| *t == Token::Ident("let".to_string())) {
+                let end = tokens[let_at..].iter().position(|t| t.is_punct("=")).map_or(tokens.len(), |p| let_at + p);
+                bindings.forget_all(&tokens[let_at + 1..end]);
+            }
+            let pipes: Vec<usize> = tokens.iter().enumerate().filter(|(_, t)| t.is_punct("|")).map(|(i, _)| i).collect();
+            if let [open, close, ..] = pipes[..] {
+                bindings.forget_all(&tokens[open + 1..close]);
+            }
+
+            if let Some((name, annotation, value)) = parse_rusts_assignment(trimmed) {
+                let ty = match annotation {
+                    Some(annotation) => NumericType::named(annotation.trim()),
+                    None => value_type(&tokenize(&value), &bindings),
+                };
+                bindings.set(&name, ty);
+            }
+        }
+
+        let (opens, closes) = count_braces_outside_strings(&code);
+        for _ in 0..closes {
+            if bindings.scopes.len() > 1 {
+                bindings.scopes.pop();
+            }
+        }
+        for _ in 0..opens {
+            bindings.scopes.push(HashMap::new());
+        }
+    }
+    mixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixes(source: &str) -> Vec<(usize, String)> {
+        find_numeric_mixes(source).into_iter().map(|m| (m.line, m.fixed)).collect()
+    }
+
+    #[test]
+    fn test_integer_binding_with_float() {
+        let src = "fn main() {\n    count = 0\n    total = count + 1.5\n    half = 0.5 * count\n}";
+        assert_eq!(fixes(src), vec![
+            (3, "count as f64 + 1.5".to_string()),
+            (4, "0.5 * count as f64".to_string()),
+        ]);
+        let mix = &find_numeric_mixes(src)[0];
+        assert_eq!((mix.expr.as_str(), mix.operand.as_str()), ("count + 1.5", "count"));
+        assert_eq!((mix.left.display(), mix.right.display()), ("{integer}".to_string(), "{float}".to_string()));
+    }
+
+    #[test]
+    fn test_parameters_annotations_and_compound() {
+        let src = "fn scale(ratio f64, n i32) f64 {\n    scaled = ratio * 2\n    mut big: i64 = 0\n    big += n\n    wide: i64 = n\n    ratio * n as f64\n}";
+        assert_eq!(fixes(src), vec![
+            (2, "ratio * 2.0".to_string()),
+            (4, "big += n as i64".to_string()),
+            (5, "wide: i64 = n as i64".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_unknown_and_rebound_operands_untouched() {
+        let src = "fn main() {\n    count = 0\n    a = count.len() + 1.5\n    b = s.count + 1.5\n    for count in weights {\n        c = count * 1.5\n    }\n    d = xs.iter().map(|count| count * 0.5)\n    e = \"count + 1.5\"\n    f = count + 2\n}";
+        assert_eq!(fixes(src), vec![]);
+    }
+
+    #[test]
+    fn test_inferred_through_arithmetic() {
+        let src = "fn main() {\n    n = 3\n    total = n * 2\n    if total > 0 {\n        total = 1.5\n        x = total * 2.0\n    }\n    y = total / 2.5\n}";
+        assert_eq!(fixes(src), vec![(8, "total as f64 / 2.5".to_string())]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Output Targets for Lowered Rust
+//!
+//! Where the Rust produced by Stage 2 goes: back to the caller as a
+//! `String`, to a file, or through rustc into a binary (Stage 3). Every
+//! target takes explicit paths and never changes the process working
+//! directory, so embedders and tests can run the whole pipeline from any
+//! thread:
+//!
+//! ```text
+//! let compiler = rustsp::Compiler::builder().build();
+//! let program = compiler.emit(&source, &mut CompileInMemory::new())?;
+//! let output = program.run()?;
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error_msg::RsplError;
+
+/// Failure to produce a target's output
+#[derive(Debug)]
+pub enum OutputError {
+    /// The source could not be lowered (see [`crate::lower_rusts`])
+    Lowering(Vec<RsplError>),
+    /// Writing files or starting rustc failed
+    Io(io::Error),
+    /// rustc rejected the generated code
+    Rustc { stderr: String },
+}
+
+impl fmt::Display for OutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::Lowering(errors) => {
+                write!(f, "{} lowering error(s)", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  line {}: {}", error.location.line, error.title)?;
+                }
+                Ok(())
+            }
+            OutputError::Io(e) => write!(f, "{}", e),
+            OutputError::Rustc { stderr } => write!(f, "rustc failed:\n{}", stderr),
+        }
+    }
+}
+
+impl From<io::Error> for OutputError {
+    fn from(e: io::Error) -> Self {
+        OutputError::Io(e)
+    }
+}
+
+/// Destination for lowered Rust code
+pub trait OutputTarget {
+    type Output;
+
+    fn emit(&mut self, rust_code: &str) -> Result<Self::Output, OutputError>;
+}
+
+/// Hand the Rust code back unchanged
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReturnString;
+
+impl OutputTarget for ReturnString {
+    type Output = String;
+
+    fn emit(&mut self, rust_code: &str) -> Result<String, OutputError> {
+        Ok(rust_code.to_string())
+    }
+}
+
+/// Write the Rust code to a file; the output is its path
+#[derive(Debug, Clone)]
+pub struct WriteToFile {
+    path: PathBuf,
+}
+
+impl WriteToFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        WriteToFile { path: path.into() }
+    }
+}
+
+impl OutputTarget for WriteToFile {
+    type Output = PathBuf;
+
+    fn emit(&mut self, rust_code: &str) -> Result<PathBuf, OutputError> {
+        fs::write(&self.path, rust_code)?;
+        Ok(self.path.clone())
+    }
+}
+
+/// Compile the Rust code with rustc in a fresh temporary directory. The
+/// directory (source and binary) is removed when the [`CompiledProgram`]
+/// is dropped, or right away when rustc fails.
+#[derive(Debug, Clone)]
+pub struct CompileInMemory {
+    crate_name: String,
+    rustc_flags: Vec<String>,
+}
+
+impl Default for CompileInMemory {
+    fn default() -> Self {
+        CompileInMemory { crate_name: "main".to_string(), rustc_flags: Vec::new() }
+    }
+}
+
+impl CompileInMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name of the source file and binary (baked into panic messages)
+    pub fn crate_name(mut self, name: impl Into<String>) -> Self {
+        self.crate_name = name.into();
+        self
+    }
+
+    /// Extra rustc flag, e.g. `-Copt-level=2`
+    pub fn flag(mut self, flag: impl Into<String>) -> Self {
+        self.rustc_flags.push(flag.into());
+        self
+    }
+}
+
+impl OutputTarget for CompileInMemory {
+    type Output = CompiledProgram;
+
+    fn emit(&mut self, rust_code: &str) -> Result<CompiledProgram, OutputError> {
+        let program = CompiledProgram {
+            dir: unique_temp_dir("rustsp-build")?,
+            crate_name: self.crate_name.clone(),
+        };
+        fs::write(program.source(), rust_code)?;
+        let output = rustc_command(&program.source(), &program.binary(), &self.rustc_flags)
+            .current_dir(&program.dir)
+            .output()?;
+        if !output.status.success() {
+            return Err(OutputError::Rustc { stderr: String::from_utf8_lossy(&output.stderr).into_owned() });
+        }
+        Ok(program)
+    }
+}
+
+/// A binary built by [`CompileInMemory`], living in its own temporary directory
+#[derive(Debug)]
+pub struct CompiledProgram {
+    dir: PathBuf,
+    crate_name: String,
+}
+
+impl CompiledProgram {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The generated Rust source that was compiled
+    pub fn source(&self) -> PathBuf {
+        self.dir.join(format!("{}.rs", self.crate_name))
+    }
+
+    pub fn binary(&self) -> PathBuf {
+        self.dir.join(format!("{}{}", self.crate_name, std::env::consts::EXE_SUFFIX))
+    }
+
+    /// Run the binary inside its directory and capture its output
+    pub fn run(&self) -> io::Result<Output> {
+        Command::new(self.binary()).current_dir(&self.dir).output()
+    }
+}
+
+impl Drop for CompiledProgram {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// `rustc <flags> <source> -o <output>`, for Stage 3 and [`CompileInMemory`]
+pub fn rustc_command(source: &Path, output: &Path, flags: &[String]) -> Command {
+    let mut command = Command::new("rustc");
+    command.args(flags).arg(source).arg("-o").arg(output);
+    command
+}
+
+/// A new, empty directory under the system temp dir that no other thread
+/// or process is using
+fn unique_temp_dir(prefix: &str) -> io::Result<PathBuf> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{}-{}", prefix, std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            // Left over from an earlier process with the same id
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_return_string_and_write_to_file() {
+        let code = "fn main() {}\n";
+        assert_eq!(ReturnString.emit(code).unwrap(), code);
+
+        let dir = unique_temp_dir("rustsp-output-test").unwrap();
+        let path = WriteToFile::new(dir.join("out.rs")).emit(code).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), code);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unique_temp_dirs() {
+        let a = unique_temp_dir("rustsp-output-test").unwrap();
+        let b = unique_temp_dir("rustsp-output-test").unwrap();
+        assert_ne!(a, b);
+        let _ = fs::remove_dir_all(&a);
+        let _ = fs::remove_dir_all(&b);
+    }
+}
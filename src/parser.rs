@@ -1,15 +1,17 @@
 //! RustS+ Parser - Converts source text to AST
 //!
-//! This is a simplified parser that works with the existing lowering system.
-//! For full IR-based compilation, this would be replaced with a proper
-//! recursive descent or PEG parser.
+//! A recursive descent parser that works with the existing lowering system.
 //!
 //! ## Current Approach
 //!
 //! Since RustS+ is very close to Rust, we use a hybrid approach:
 //! 1. Parse function signatures and effects declarations properly
-//! 2. Extract structure information (structs, enums, impls)
-//! 3. Use existing line-based analysis for body content
+//! 2. Parse statements, expressions and patterns into `ast::Stmt`/`Expr`/
+//!    `Pattern`, including RustS+ forms (`Pattern { body }` match arms,
+//!    `x T = value` bindings, struct literal fields without commas)
+//! 3. Use existing line-based analysis for body content; [`parse_line`]
+//!    tells it which brackets a line leaves open, so literal, array and
+//!    match modes start from the parse instead of counting characters
 //!
 //! This allows gradual migration to full AST-based parsing.
 
@@ -38,13 +40,14 @@ pub enum Token {
     
     // Operators
     Plus, Minus, Star, Slash, Percent,
+    PlusEq, MinusEq, StarEq, SlashEq, PercentEq,
     Eq, EqEq, Ne, Lt, Le, Gt, Ge,
     And, Or, Not, BitAnd, BitOr, BitXor,
     Shl, Shr,
     
     // Delimiters
     LParen, RParen, LBrace, RBrace, LBracket, RBracket,
-    Comma, Colon, ColonColon, Semi, Dot, Arrow, FatArrow,
+    Comma, Colon, ColonColon, Semi, Dot, DotDot, DotDotEq, Arrow, FatArrow,
     Question, At, Hash, Dollar,
     
     // Special
@@ -141,6 +144,28 @@ impl<'a> Lexer<'a> {
         s.push(first);
         let mut is_float = false;
         
+        // `0x1F`, `0o17`, `0b1010`
+        let radix = match (first, self.peek_char()) {
+            ('0', Some('x')) => 16,
+            ('0', Some('o')) => 8,
+            ('0', Some('b')) => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.next_char();
+            let mut digits = String::new();
+            while let Some(c) = self.peek_char() {
+                if c.is_ascii_hexdigit() || c == '_' {
+                    digits.push(c);
+                    self.next_char();
+                } else {
+                    break;
+                }
+            }
+            self.read_number_suffix();
+            return Token::IntLit(i64::from_str_radix(&digits.replace('_', ""), radix).unwrap_or(0));
+        }
+        
         while let Some(c) = self.peek_char() {
             if c.is_ascii_digit() {
                 s.push(c);
@@ -167,6 +192,11 @@ impl<'a> Lexer<'a> {
             }
         }
         
+        // Type suffix: `0u8`, `1i64`, `2.5f32`
+        if self.read_number_suffix().starts_with('f') {
+            is_float = true;
+        }
+        
         if is_float {
             Token::FloatLit(s.parse().unwrap_or(0.0))
         } else {
@@ -174,6 +204,17 @@ impl<'a> Lexer<'a> {
         }
     }
     
+    /// The type suffix of a number literal, empty when there is none
+    fn read_number_suffix(&mut self) -> String {
+        match self.peek_char() {
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                self.next_char();
+                self.read_ident(c)
+            }
+            _ => String::new(),
+        }
+    }
+    
     fn read_string(&mut self) -> Token {
         let mut s = String::new();
         while let Some(c) = self.next_char() {
@@ -219,6 +260,16 @@ impl<'a> Lexer<'a> {
         Token::CharLit(result)
     }
     
+    /// `op`, or `assign` when an `=` follows (`+` / `+=`)
+    fn compound(&mut self, op: Token, assign: Token) -> Token {
+        if self.peek_char() == Some('=') {
+            self.next_char();
+            assign
+        } else {
+            op
+        }
+    }
+    
     pub fn next_token(&mut self) -> (Token, Span) {
         self.skip_whitespace();
         let span = self.span();
@@ -238,12 +289,31 @@ impl<'a> Lexer<'a> {
             ']' => Token::RBracket,
             ',' => Token::Comma,
             ';' => Token::Semi,
-            '.' => Token::Dot,
+            '.' => {
+                if self.peek_char() != Some('.') {
+                    Token::Dot
+                } else {
+                    self.next_char();
+                    if self.peek_char() == Some('=') {
+                        self.next_char();
+                        Token::DotDotEq
+                    } else {
+                        Token::DotDot
+                    }
+                }
+            }
             '?' => Token::Question,
             '@' => Token::At,
             '#' => Token::Hash,
             '$' => Token::Dollar,
-            '|' => Token::Pipe,
+            '|' => {
+                if self.peek_char() == Some('|') {
+                    self.next_char();
+                    Token::Or
+                } else {
+                    Token::Pipe
+                }
+            }
             
             // Operators that might be multi-char
             ':' => {
@@ -284,11 +354,10 @@ impl<'a> Lexer<'a> {
                 }
             }
             '-' => {
-                if self.peek_char() == Some('>') {
-                    self.next_char();
-                    Token::Arrow
-                } else {
-                    Token::Minus
+                match self.peek_char() {
+                    Some('>') => { self.next_char(); Token::Arrow }
+                    Some('=') => { self.next_char(); Token::MinusEq }
+                    _ => Token::Minus
                 }
             }
             '&' => {
@@ -300,10 +369,10 @@ impl<'a> Lexer<'a> {
                 }
             }
             '^' => Token::BitXor,
-            '+' => Token::Plus,
-            '*' => Token::Star,
-            '/' => Token::Slash,
-            '%' => Token::Percent,
+            '+' => self.compound(Token::Plus, Token::PlusEq),
+            '*' => self.compound(Token::Star, Token::StarEq),
+            '/' => self.compound(Token::Slash, Token::SlashEq),
+            '%' => self.compound(Token::Percent, Token::PercentEq),
             
             // String literal
             '"' => self.read_string(),
@@ -373,15 +442,23 @@ impl<'a> Lexer<'a> {
 // FUNCTION SIGNATURE PARSER
 //=============================================================================
 
-/// Parse function signature from tokens
+/// Parse function signatures, bodies, statements and expressions from tokens
 pub struct FunctionParser<'a> {
     tokens: &'a [(Token, Span)],
     pos: usize,
+    /// In an `if`/`while`/`match`/`for` head, where `x {` starts the body,
+    /// not a struct literal
+    no_struct: bool,
+    /// Brackets entered and not yet closed, outermost first. Left as they
+    /// are when a parse fails, so [`parse_line`] can report them.
+    opens: Vec<Open>,
+    /// A statement's `=` was consumed outside any bracket
+    assigned: bool,
 }
 
 impl<'a> FunctionParser<'a> {
     pub fn new(tokens: &'a [(Token, Span)]) -> Self {
-        FunctionParser { tokens, pos: 0 }
+        FunctionParser { tokens, pos: 0, no_struct: false, opens: Vec::new(), assigned: false }
     }
     
     fn current(&self) -> &Token {
@@ -457,6 +534,20 @@ impl<'a> FunctionParser<'a> {
         while *self.current() != Token::RParen && *self.current() != Token::Eof {
             let param_span = self.current_span();
             
+            // Method receiver: `self`, `mut self`, `&self`, `&mut self`.
+            // Bound as a parameter named `self` so `effects(write self)` resolves.
+            if let Some(ty) = self.parse_self_receiver() {
+                params.push(FnParam {
+                    name: Ident::new("self"),
+                    ty,
+                    span: param_span,
+                });
+                if !self.expect(&Token::Comma) {
+                    break;
+                }
+                continue;
+            }
+            
             // Parameter name
             let param_name = match self.expect_ident() {
                 Some(n) => Ident::new(n),
@@ -490,6 +581,10 @@ impl<'a> FunctionParser<'a> {
             // Check for -> or direct type
             if self.expect(&Token::Arrow) {
                 Some(self.parse_type()?)
+            } else if *self.current() == Token::Ident("unit".to_string()) {
+                // `unit` keyword: explicit `()` return
+                self.advance();
+                Some(Type::Unit)
             } else if let Token::Ident(_) = self.current() {
                 Some(self.parse_type()?)
             } else {
@@ -499,9 +594,13 @@ impl<'a> FunctionParser<'a> {
             None
         };
         
-        // Body (simplified - just skip to end of function)
-        // In full implementation, this would recursively parse the body
-        let body = None;
+        // Body, when it is all there (`{ a + b }` on the signature line, or
+        // the whole function given to `parse_module`)
+        let body = if *self.current() == Token::LBrace {
+            self.attempt(|p| p.parse_block())
+        } else {
+            None
+        };
         
         let end_span = self.current_span();
         
@@ -555,13 +654,40 @@ impl<'a> FunctionParser<'a> {
             "write" => {
                 self.expect(&Token::LParen);
                 let param = Ident::new(self.expect_ident()?);
+                let mut fields = Vec::new();
+                while self.expect(&Token::Dot) {
+                    fields.push(Ident::new(self.expect_ident()?));
+                }
                 self.expect(&Token::RParen);
-                Some(EffectDecl::Write(param))
+                if fields.is_empty() {
+                    Some(EffectDecl::Write(param))
+                } else {
+                    Some(EffectDecl::WriteField { param, fields })
+                }
             }
             _ => None,
         }
     }
     
+    /// Parse a method receiver, returning its type (`Self`, `&Self`, `&mut Self`).
+    /// Leaves the position untouched if the parameter is not a receiver.
+    fn parse_self_receiver(&mut self) -> Option<Type> {
+        let start = self.pos;
+        let is_ref = self.expect(&Token::Ampersand);
+        let mutable = self.expect(&Token::Mut);
+        if matches!(self.current(), Token::Ident(name) if name == "self") {
+            self.advance();
+            let self_ty = Type::Path(Path::single("Self"));
+            return Some(if is_ref {
+                Type::Reference { mutable, inner: Box::new(self_ty) }
+            } else {
+                self_ty
+            });
+        }
+        self.pos = start;
+        None
+    }
+    
     /// Parse a type
     fn parse_type(&mut self) -> Option<Type> {
         // Handle optional colon (Rust style)
@@ -617,29 +743,979 @@ impl<'a> FunctionParser<'a> {
         }
         
         // Simple type or generic
-        let base_name = self.expect_ident()?;
-        let base_path = Path::single(base_name);
+        let mut base_path = Path::single(self.expect_ident()?);
+        while self.expect(&Token::ColonColon) {
+            base_path.segments.push(Ident::new(self.expect_ident()?));
+        }
         
-        // Check for generic arguments
-        if self.expect(&Token::Lt) {
-            let mut args = Vec::new();
-            while *self.current() != Token::Gt && *self.current() != Token::Eof {
-                if let Some(ty) = self.parse_type() {
-                    args.push(ty);
+        // Check for generic arguments: `Vec<T>`, RustS+ `Vec[T]`
+        let close = match self.current() {
+            Token::Lt => Token::Gt,
+            Token::LBracket => Token::RBracket,
+            _ => return Some(Type::Path(base_path)),
+        };
+        self.advance();
+        let mut args = Vec::new();
+        while *self.current() != close && *self.current() != Token::Eof {
+            if let Some(ty) = self.parse_type() {
+                args.push(ty);
+            }
+            if !self.expect(&Token::Comma) {
+                break;
+            }
+        }
+        self.expect(&close);
+        
+        Some(Type::Generic {
+            base: base_path,
+            args,
+        })
+    }
+}
+
+//=============================================================================
+// EXPRESSION AND STATEMENT PARSER
+//=============================================================================
+//
+// Recursive descent over the same tokens, producing `ast::Expr`/`Stmt`.
+// RustS+ needs no `;`: a statement ends at the end of its line, except
+// inside `(..)`/`[..]`, after a binary operator, or before a `.method()`
+// continuing on the next line. `x = value` is an assignment expression;
+// whether it declares `x` is decided later by scope analysis. `mut x = ..`,
+// `outer x = ..`, `x T = ..` and Rust's `let` are bindings.
+
+/// A bracket still open where the input ends, see [`parse_line`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Open {
+    /// Struct or enum variant literal: `User {`, `Shape::Circle {`
+    Struct(Path),
+    /// Array literal or index: `[`, `vec![`
+    Array,
+    /// Match arms: `match x {`
+    Match,
+    /// Block: `if x {`, `loop {`, `|x| {`
+    Block,
+    /// Parentheses, call or macro arguments: `(`, `f(`, `println!(`
+    Paren,
+}
+
+/// Fields and `..base` of a struct literal
+type StructLiteralFields = (Vec<(Ident, Spanned<Expr>)>, Option<Box<Spanned<Expr>>>);
+/// Fields and `..` of a struct pattern
+type StructPatternFields = (Vec<(Ident, Option<Spanned<Pattern>>)>, bool);
+
+/// Binding power of `..` / `..=`
+const RANGE_PREC: u8 = 2;
+/// Binding power of `as`
+const CAST_PREC: u8 = 12;
+
+/// Binary operator of an infix token, with its binding power
+fn binary_op(token: &Token) -> Option<(BinOp, u8)> {
+    Some(match token {
+        Token::Or => (BinOp::Or, 3),
+        Token::And => (BinOp::And, 4),
+        Token::EqEq => (BinOp::Eq, 5),
+        Token::Ne => (BinOp::Ne, 5),
+        Token::Lt => (BinOp::Lt, 5),
+        Token::Le => (BinOp::Le, 5),
+        Token::Gt => (BinOp::Gt, 5),
+        Token::Ge => (BinOp::Ge, 5),
+        Token::Pipe => (BinOp::BitOr, 6),
+        Token::BitXor => (BinOp::BitXor, 7),
+        Token::Ampersand => (BinOp::BitAnd, 8),
+        Token::Shl => (BinOp::Shl, 9),
+        Token::Shr => (BinOp::Shr, 9),
+        Token::Plus => (BinOp::Add, 10),
+        Token::Minus => (BinOp::Sub, 10),
+        Token::Star => (BinOp::Mul, 11),
+        Token::Slash => (BinOp::Div, 11),
+        Token::Percent => (BinOp::Rem, 11),
+        _ => return None,
+    })
+}
+
+/// Operator of a compound assignment token (`+=` → `Add`)
+fn compound_assign_op(token: &Token) -> Option<BinOp> {
+    Some(match token {
+        Token::PlusEq => BinOp::Add,
+        Token::MinusEq => BinOp::Sub,
+        Token::StarEq => BinOp::Mul,
+        Token::SlashEq => BinOp::Div,
+        Token::PercentEq => BinOp::Rem,
+        _ => return None,
+    })
+}
+
+/// Source spelling of a token
+fn token_text(token: &Token) -> String {
+    let text = match token {
+        Token::IntLit(n) => return n.to_string(),
+        Token::FloatLit(f) => return format!("{:?}", f),
+        Token::StringLit(s) => return format!("{:?}", s),
+        Token::CharLit(c) => return format!("{:?}", c),
+        Token::BoolLit(b) => return b.to_string(),
+        Token::Ident(name) => return name.clone(),
+        Token::Fn => "fn", Token::Pub => "pub", Token::Let => "let", Token::Mut => "mut",
+        Token::If => "if", Token::Else => "else", Token::Match => "match", Token::While => "while",
+        Token::For => "for", Token::Loop => "loop", Token::Return => "return", Token::Break => "break",
+        Token::Continue => "continue", Token::Struct => "struct", Token::Enum => "enum",
+        Token::Impl => "impl", Token::Trait => "trait", Token::Mod => "mod", Token::Use => "use",
+        Token::Const => "const", Token::Static => "static", Token::Outer => "outer",
+        Token::Effects => "effects", Token::Move => "move",
+        Token::Plus => "+", Token::Minus => "-", Token::Star => "*", Token::Slash => "/",
+        Token::Percent => "%", Token::PlusEq => "+=", Token::MinusEq => "-=", Token::StarEq => "*=",
+        Token::SlashEq => "/=", Token::PercentEq => "%=", Token::Eq => "=", Token::EqEq => "==",
+        Token::Ne => "!=", Token::Lt => "<", Token::Le => "<=", Token::Gt => ">", Token::Ge => ">=",
+        Token::And => "&&", Token::Or => "||", Token::Not => "!", Token::BitAnd | Token::Ampersand => "&",
+        Token::BitOr | Token::Pipe => "|", Token::BitXor => "^", Token::Shl => "<<", Token::Shr => ">>",
+        Token::LParen => "(", Token::RParen => ")", Token::LBrace => "{", Token::RBrace => "}",
+        Token::LBracket => "[", Token::RBracket => "]", Token::Comma => ",", Token::Colon => ":",
+        Token::ColonColon => "::", Token::Semi => ";", Token::Dot => ".", Token::DotDot => "..",
+        Token::DotDotEq => "..=", Token::Arrow => "->", Token::FatArrow => "=>", Token::Question => "?",
+        Token::At => "@", Token::Hash => "#", Token::Dollar => "$", Token::Eof => "",
+    };
+    text.to_string()
+}
+
+/// Tokens spelled back as source, spaced where the source had a gap
+fn spell(tokens: &[(Token, Span)]) -> String {
+    let mut text = String::new();
+    let mut end = None;
+    for (token, span) in tokens {
+        let spelled = token_text(token);
+        if end.is_some_and(|end| end != (span.start_line, span.start_col)) {
+            text.push(' ');
+        }
+        end = Some((span.start_line, span.start_col + spelled.chars().count()));
+        text.push_str(&spelled);
+    }
+    text
+}
+
+impl<'a> FunctionParser<'a> {
+    fn peek(&self, ahead: usize) -> &Token {
+        self.tokens.get(self.pos + ahead).map(|(t, _)| t).unwrap_or(&Token::Eof)
+    }
+    
+    /// Span of the last consumed token
+    fn prev_span(&self) -> Span {
+        self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)).map(|(_, s)| *s).unwrap_or_default()
+    }
+    
+    /// From `start` to the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.merge(&self.prev_span())
+    }
+    
+    /// The current token starts a new line
+    fn at_line_start(&self) -> bool {
+        self.pos > 0 && self.current_span().start_line != self.prev_span().start_line
+    }
+    
+    /// A new line ends the expression here (it does not inside `(..)`/`[..]`)
+    fn line_break(&self) -> bool {
+        !matches!(self.opens.last(), Some(Open::Paren | Open::Array)) && self.at_line_start()
+    }
+    
+    /// Run `parse`; on failure, rewind to where it started
+    fn attempt<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let (pos, opens, no_struct, assigned) = (self.pos, self.opens.len(), self.no_struct, self.assigned);
+        let result = parse(self);
+        if result.is_none() {
+            self.pos = pos;
+            self.opens.truncate(opens);
+            self.no_struct = no_struct;
+            self.assigned = assigned;
+        }
+        result
+    }
+    
+    /// Run `parse` inside a bracket just consumed, where struct literals are
+    /// allowed again
+    fn nested<T>(&mut self, open: Open, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let no_struct = std::mem::replace(&mut self.no_struct, false);
+        self.opens.push(open);
+        let result = parse(self)?;
+        self.opens.pop();
+        self.no_struct = no_struct;
+        Some(result)
+    }
+    
+    /// Run `parse` in an `if`/`while`/`match`/`for` head
+    fn head<T>(&mut self, parse: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let no_struct = std::mem::replace(&mut self.no_struct, true);
+        let result = parse(self)?;
+        self.no_struct = no_struct;
+        Some(result)
+    }
+    
+    /// Items up to `close`, separated by `,` (optional when `commas_optional`)
+    fn parse_list<T>(
+        &mut self,
+        close: Token,
+        commas_optional: bool,
+        mut item: impl FnMut(&mut Self) -> Option<T>,
+    ) -> Option<Vec<T>> {
+        let mut items = Vec::new();
+        while *self.current() != close {
+            if *self.current() == Token::Eof {
+                return None;
+            }
+            items.push(item(self)?);
+            if !self.expect(&Token::Comma) && !commas_optional && *self.current() != close {
+                return None;
+            }
+        }
+        self.advance();
+        Some(items)
+    }
+    
+    fn can_start_expr(&self) -> bool {
+        !matches!(
+            self.current(),
+            Token::Eof | Token::RParen | Token::RBracket | Token::RBrace | Token::Comma
+            | Token::Semi | Token::FatArrow | Token::Eq | Token::Else
+        ) && (!self.no_struct || *self.current() != Token::LBrace)
+    }
+    
+    //-------------------------------------------------------------------------
+    // Statements
+    //-------------------------------------------------------------------------
+    
+    /// Parse a statement, with its optional `;`
+    pub fn parse_stmt(&mut self) -> Option<Spanned<Stmt>> {
+        let start = self.current_span();
+        let stmt = match self.current() {
+            // `let mut x: T = value` (Rust)
+            Token::Let => {
+                self.advance();
+                let pattern = self.parse_pattern()?;
+                let ty = if self.expect(&Token::Colon) { Some(self.parse_type()?) } else { None };
+                let init = if self.expect(&Token::Eq) {
+                    self.assigned |= self.opens.is_empty();
+                    Some(self.parse_expr()?)
+                } else {
+                    None
+                };
+                let mutable = matches!(pattern.node, Pattern::Binding { mutable: true, .. });
+                Stmt::Let { pattern, ty, init, mutable, outer: false }
+            }
+            // `mut x = value`, `outer x = value`
+            Token::Mut | Token::Outer => {
+                let outer = self.expect(&Token::Outer);
+                let pattern = self.parse_single_pattern(false)?;
+                let ty = if *self.current() != Token::Eq { Some(self.parse_type()?) } else { None };
+                if !self.expect(&Token::Eq) {
+                    return None;
                 }
-                if !self.expect(&Token::Comma) {
-                    break;
+                self.assigned |= self.opens.is_empty();
+                let init = Some(self.parse_expr()?);
+                let mutable = matches!(pattern.node, Pattern::Binding { mutable: true, .. });
+                Stmt::Let { pattern, ty, init, mutable, outer }
+            }
+            _ => match self.attempt(Self::parse_typed_binding) {
+                // `x T = value`
+                Some((pattern, ty)) => {
+                    self.assigned |= self.opens.is_empty();
+                    let init = Some(self.parse_expr()?);
+                    Stmt::Let { pattern, ty: Some(ty), init, mutable: false, outer: false }
+                }
+                None => Stmt::Expr(self.parse_expr()?),
+            },
+        };
+        self.expect(&Token::Semi);
+        Some(Spanned::new(stmt, self.span_from(start)))
+    }
+    
+    /// `name Type =` of a typed RustS+ binding. The type is apart from the
+    /// name, so `grid[i] = v` stays an index assignment.
+    fn parse_typed_binding(&mut self) -> Option<(Spanned<Pattern>, Type)> {
+        let span = self.current_span();
+        let name = self.expect_ident()?;
+        let type_span = self.current_span();
+        let adjacent = type_span.start_line == span.start_line
+            && type_span.start_col == span.start_col + name.chars().count();
+        let starts_type = matches!(self.current(), Token::Ident(_) | Token::Ampersand | Token::LBracket | Token::LParen);
+        if (adjacent && *self.current() != Token::Colon) || !(starts_type || *self.current() == Token::Colon) {
+            return None;
+        }
+        let ty = self.parse_type()?;
+        if !self.expect(&Token::Eq) {
+            return None;
+        }
+        let binding = Pattern::Binding { name: Ident::new(name), mutable: false, subpattern: None };
+        Some((Spanned::new(binding, span), ty))
+    }
+    
+    /// Parse `{ stmts }`; a last expression without `;` is the block's value
+    pub fn parse_block(&mut self) -> Option<Spanned<Block>> {
+        let start = self.current_span();
+        if !self.expect(&Token::LBrace) {
+            return None;
+        }
+        let (stmts, expr) = self.nested(Open::Block, |p| {
+            let mut stmts: Vec<Spanned<Stmt>> = Vec::new();
+            let mut terminated = true;
+            loop {
+                while p.expect(&Token::Semi) {
+                    terminated = true;
                 }
+                match p.current() {
+                    Token::RBrace => break,
+                    Token::Eof => return None,
+                    // Two statements on one line need a `;` between them
+                    _ if !terminated && !p.at_line_start() => return None,
+                    _ => {}
+                }
+                stmts.push(p.parse_stmt()?);
+                terminated = p.prev_token() == Some(&Token::Semi);
             }
-            self.expect(&Token::Gt);
-            
-            return Some(Type::Generic {
-                base: base_path,
-                args,
+            p.advance();
+            let expr = match stmts.last() {
+                Some(Spanned { node: Stmt::Expr(_), .. }) if !terminated => match stmts.pop() {
+                    Some(Spanned { node: Stmt::Expr(expr), .. }) => Some(expr),
+                    _ => None,
+                },
+                _ => None,
+            };
+            Some((stmts, expr))
+        })?;
+        Some(Spanned::new(Block { stmts, expr }, self.span_from(start)))
+    }
+    
+    fn prev_token(&self) -> Option<&Token> {
+        self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)).map(|(t, _)| t)
+    }
+    
+    fn block_expr(&mut self) -> Option<Spanned<Expr>> {
+        let block = self.parse_block()?;
+        let span = block.span;
+        Some(Spanned::new(Expr::Block(Box::new(block)), span))
+    }
+    
+    //-------------------------------------------------------------------------
+    // Expressions
+    //-------------------------------------------------------------------------
+    
+    /// Parse an expression, including assignments (`x = v`, `x += v`)
+    pub fn parse_expr(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let target = self.parse_binary(0)?;
+        if *self.current() == Token::Eq && !self.line_break() {
+            self.advance();
+            self.assigned |= self.opens.is_empty();
+            let value = self.parse_expr()?;
+            let expr = Expr::Assign { target: Box::new(target), value: Box::new(value) };
+            return Some(Spanned::new(expr, self.span_from(start)));
+        }
+        if let Some(op) = compound_assign_op(self.current()).filter(|_| !self.line_break()) {
+            self.advance();
+            let value = self.parse_expr()?;
+            let expr = Expr::AssignOp { op, target: Box::new(target), value: Box::new(value) };
+            return Some(Spanned::new(expr, self.span_from(start)));
+        }
+        Some(target)
+    }
+    
+    /// Binary operators binding at least as tight as `min_prec`
+    fn parse_binary(&mut self, min_prec: u8) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let mut left = if matches!(self.current(), Token::DotDot | Token::DotDotEq) {
+            self.parse_range(None, start)?
+        } else {
+            self.parse_unary()?
+        };
+        loop {
+            if self.line_break() {
+                break;
+            }
+            match self.current() {
+                Token::DotDot | Token::DotDotEq if min_prec <= RANGE_PREC => {
+                    left = self.parse_range(Some(left), start)?;
+                }
+                Token::Ident(kw) if kw == "as" && min_prec <= CAST_PREC => {
+                    self.advance();
+                    let target_type = self.parse_type()?;
+                    let expr = Expr::Cast { expr: Box::new(left), target_type };
+                    left = Spanned::new(expr, self.span_from(start));
+                }
+                token => {
+                    let Some((op, prec)) = binary_op(token).filter(|(_, prec)| *prec >= min_prec) else {
+                        break;
+                    };
+                    self.advance();
+                    let right = self.parse_binary(prec + 1)?;
+                    let expr = Expr::Binary { op, left: Box::new(left), right: Box::new(right) };
+                    left = Spanned::new(expr, self.span_from(start));
+                }
+            }
+        }
+        Some(left)
+    }
+    
+    /// `start..end`, `start..`, `..end`, `start..=end` at the `..`
+    fn parse_range(&mut self, start_expr: Option<Spanned<Expr>>, start: Span) -> Option<Spanned<Expr>> {
+        let inclusive = *self.current() == Token::DotDotEq;
+        self.advance();
+        let end = if self.can_start_expr() && *self.current() != Token::LBrace && !self.line_break() {
+            Some(Box::new(self.parse_binary(RANGE_PREC + 1)?))
+        } else {
+            None
+        };
+        let expr = Expr::Range { start: start_expr.map(Box::new), end, inclusive };
+        Some(Spanned::new(expr, self.span_from(start)))
+    }
+    
+    fn parse_unary(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let op = match self.current() {
+            Token::Minus => UnaryOp::Neg,
+            Token::Not => UnaryOp::Not,
+            Token::Star => UnaryOp::Deref,
+            Token::Ampersand | Token::And => {
+                // `&&x` is `& &x`
+                let double = *self.current() == Token::And;
+                self.advance();
+                let op = if self.expect(&Token::Mut) { UnaryOp::RefMut } else { UnaryOp::Ref };
+                let operand = self.parse_unary()?;
+                let mut expr = Spanned::new(Expr::Unary { op, operand: Box::new(operand) }, self.span_from(start));
+                if double {
+                    expr = Spanned::new(Expr::Unary { op: UnaryOp::Ref, operand: Box::new(expr) }, self.span_from(start));
+                }
+                return Some(expr);
+            }
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+        let operand = self.parse_unary()?;
+        Some(Spanned::new(Expr::Unary { op, operand: Box::new(operand) }, self.span_from(start)))
+    }
+    
+    /// Field access, method calls, calls, indexing and `?`
+    fn parse_postfix(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let mut expr = self.parse_primary()?;
+        loop {
+            let node = match self.current().clone() {
+                // `.method()` may continue on the next line
+                Token::Dot => {
+                    self.advance();
+                    match self.current().clone() {
+                        Token::Ident(name) => {
+                            self.advance();
+                            if *self.current() == Token::ColonColon && *self.peek(1) == Token::Lt {
+                                self.advance();
+                                self.skip_generic_args()?;
+                            }
+                            if *self.current() == Token::LParen && !self.line_break() {
+                                self.advance();
+                                let args = self.nested(Open::Paren, |p| p.parse_list(Token::RParen, false, Self::parse_expr))?;
+                                Expr::MethodCall { receiver: Box::new(expr), method: Ident::new(name), args }
+                            } else {
+                                Expr::Field { base: Box::new(expr), field: Ident::new(name) }
+                            }
+                        }
+                        // `pair.0`
+                        Token::IntLit(index) => {
+                            self.advance();
+                            Expr::Field { base: Box::new(expr), field: Ident::new(index.to_string()) }
+                        }
+                        // `nested.0.1`, lexed as a float
+                        Token::FloatLit(indices) => {
+                            self.advance();
+                            let indices = format!("{:?}", indices);
+                            let (outer, inner) = indices.split_once('.')?;
+                            let base = Spanned::new(Expr::Field { base: Box::new(expr), field: Ident::new(outer) }, self.span_from(start));
+                            Expr::Field { base: Box::new(base), field: Ident::new(inner) }
+                        }
+                        _ => return None,
+                    }
+                }
+                Token::LParen if !self.line_break() => {
+                    self.advance();
+                    let args = self.nested(Open::Paren, |p| p.parse_list(Token::RParen, false, Self::parse_expr))?;
+                    Expr::Call { func: Box::new(expr), args }
+                }
+                Token::LBracket if !self.line_break() => {
+                    self.advance();
+                    let index = self.nested(Open::Array, |p| {
+                        let index = p.parse_expr()?;
+                        p.expect(&Token::RBracket).then_some(index)
+                    })?;
+                    Expr::Index { base: Box::new(expr), index: Box::new(index) }
+                }
+                Token::Question if !self.line_break() => {
+                    self.advance();
+                    Expr::Try(Box::new(expr))
+                }
+                _ => break,
+            };
+            expr = Spanned::new(node, self.span_from(start));
+        }
+        Some(expr)
+    }
+    
+    /// Skip `<..>` generic arguments (a turbofish)
+    fn skip_generic_args(&mut self) -> Option<()> {
+        self.advance();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.current() {
+                Token::Lt => depth += 1,
+                Token::Gt => depth -= 1,
+                Token::Shr => depth -= 2,
+                Token::Eof => return None,
+                _ => {}
+            }
+            self.advance();
+        }
+        (depth == 0).then_some(())
+    }
+    
+    fn parse_primary(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let expr = match self.current().clone() {
+            Token::IntLit(n) => { self.advance(); Expr::Literal(Literal::Int(n)) }
+            Token::FloatLit(f) => { self.advance(); Expr::Literal(Literal::Float(f)) }
+            Token::StringLit(s) => { self.advance(); Expr::Literal(Literal::String(s)) }
+            Token::CharLit(c) => { self.advance(); Expr::Literal(Literal::Char(c)) }
+            Token::BoolLit(b) => { self.advance(); Expr::Literal(Literal::Bool(b)) }
+            Token::Ident(name) if (name == "unsafe" || name == "async") && *self.peek(1) == Token::LBrace => {
+                self.advance();
+                return self.block_expr();
+            }
+            Token::Ident(_) => return self.parse_path_expr(),
+            Token::LParen => {
+                self.advance();
+                let (mut items, trailing_comma) = self.nested(Open::Paren, |p| {
+                    let items = p.parse_list(Token::RParen, false, Self::parse_expr)?;
+                    Some((items, p.tokens.get(p.pos.saturating_sub(2)).map(|(t, _)| t) == Some(&Token::Comma)))
+                })?;
+                match items.len() {
+                    0 => Expr::Literal(Literal::Unit),
+                    1 if !trailing_comma => return items.pop(),
+                    _ => Expr::Tuple(items),
+                }
+            }
+            Token::LBracket => {
+                self.advance();
+                self.nested(Open::Array, |p| {
+                    if *p.current() == Token::RBracket {
+                        p.advance();
+                        return Some(Expr::Array(Vec::new()));
+                    }
+                    let first = p.parse_expr()?;
+                    // `[value; count]`
+                    if p.expect(&Token::Semi) {
+                        let count = p.parse_expr()?;
+                        return p.expect(&Token::RBracket)
+                            .then(|| Expr::Repeat { value: Box::new(first), count: Box::new(count) });
+                    }
+                    let mut items = vec![first];
+                    if !p.expect(&Token::Comma) && *p.current() != Token::RBracket {
+                        return None;
+                    }
+                    items.extend(p.parse_list(Token::RBracket, false, Self::parse_expr)?);
+                    Some(Expr::Array(items))
+                })?
+            }
+            Token::LBrace => return self.block_expr(),
+            Token::If => return self.parse_if(),
+            Token::Match => return self.parse_match(),
+            Token::While => {
+                self.advance();
+                let condition = self.head(Self::parse_condition)?;
+                let body = self.parse_block()?;
+                Expr::While { condition: Box::new(condition), body: Box::new(body) }
+            }
+            Token::For => {
+                self.advance();
+                let pattern = self.parse_pattern()?;
+                if !matches!(self.current(), Token::Ident(kw) if kw == "in") {
+                    return None;
+                }
+                self.advance();
+                let iter = self.head(|p| p.parse_binary(0))?;
+                let body = self.parse_block()?;
+                Expr::For { pattern: Box::new(pattern), iter: Box::new(iter), body: Box::new(body) }
+            }
+            Token::Loop => {
+                self.advance();
+                Expr::Loop(Box::new(self.parse_block()?))
+            }
+            Token::Return | Token::Break => {
+                let is_return = *self.current() == Token::Return;
+                self.advance();
+                let value = if self.can_start_expr() && !self.line_break() {
+                    Some(Box::new(self.parse_expr()?))
+                } else {
+                    None
+                };
+                if is_return { Expr::Return(value) } else { Expr::Break(value) }
+            }
+            Token::Continue => { self.advance(); Expr::Continue }
+            Token::Pipe | Token::Or | Token::Move => return self.parse_closure(),
+            _ => return None,
+        };
+        Some(Spanned::new(expr, self.span_from(start)))
+    }
+    
+    /// A path: variable, `Enum::Variant`, struct literal or macro call
+    fn parse_path_expr(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let mut path = Path::single(self.expect_ident()?);
+        while self.expect(&Token::ColonColon) {
+            if *self.current() == Token::Lt {
+                self.skip_generic_args()?;
+            } else {
+                path.segments.push(Ident::new(self.expect_ident()?));
+            }
+        }
+        
+        // `name!(..)`, `name![..]`, `name!{..}`
+        if *self.current() == Token::Not && !self.line_break() {
+            let open = match self.peek(1) {
+                Token::LParen => Open::Paren,
+                Token::LBracket => Open::Array,
+                Token::LBrace => Open::Block,
+                _ => return Some(Spanned::new(path_expr(path), self.span_from(start))),
+            };
+            self.advance();
+            let args = self.parse_macro_args(open)?;
+            let name = path.last().cloned()?;
+            return Some(Spanned::new(Expr::Macro { name, args }, self.span_from(start)));
+        }
+        
+        if *self.current() == Token::LBrace && !self.no_struct && !self.line_break() {
+            self.advance();
+            let (fields, spread) = self.nested(Open::Struct(path.clone()), Self::parse_struct_fields)?;
+            return Some(Spanned::new(Expr::Struct { path, fields, spread }, self.span_from(start)));
+        }
+        Some(Spanned::new(path_expr(path), self.span_from(start)))
+    }
+    
+    /// Macro arguments from the opening delimiter, spelled back as source
+    fn parse_macro_args(&mut self, open: Open) -> Option<String> {
+        self.advance();
+        self.nested(open, |p| {
+            let from = p.pos;
+            let mut depth = 0usize;
+            loop {
+                match p.current() {
+                    Token::LParen | Token::LBracket | Token::LBrace => depth += 1,
+                    Token::RParen | Token::RBracket | Token::RBrace if depth == 0 => break,
+                    Token::RParen | Token::RBracket | Token::RBrace => depth -= 1,
+                    Token::Eof => return None,
+                    _ => {}
+                }
+                p.advance();
+            }
+            let args = spell(&p.tokens[from..p.pos]);
+            p.advance();
+            Some(args)
+        })
+    }
+    
+    /// Fields of a struct literal after its `{`: `name = value` (or Rust's
+    /// `name: value`), shorthand `name`, and `..base`. Commas are optional
+    /// between fields on separate lines.
+    fn parse_struct_fields(&mut self) -> Option<StructLiteralFields> {
+        let mut fields = Vec::new();
+        let mut spread = None;
+        while *self.current() != Token::RBrace {
+            if self.expect(&Token::DotDot) {
+                spread = Some(Box::new(self.parse_expr()?));
+                self.expect(&Token::Comma);
+                continue;
+            }
+            let start = self.current_span();
+            let name = self.expect_ident()?;
+            let value = if self.expect(&Token::Colon) || self.expect(&Token::Eq) {
+                self.parse_expr()?
+            } else {
+                Spanned::new(Expr::Var(Ident::new(name.clone())), start)
+            };
+            fields.push((Ident::new(name), value));
+            self.expect(&Token::Comma);
+        }
+        self.advance();
+        Some((fields, spread))
+    }
+    
+    /// `|a, b T| body`, `move |x| body`, `|| body`
+    fn parse_closure(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        let capture_by_move = self.expect(&Token::Move);
+        let params = if self.expect(&Token::Or) {
+            Vec::new()
+        } else {
+            if !self.expect(&Token::Pipe) {
+                return None;
+            }
+            self.nested(Open::Paren, |p| p.parse_list(Token::Pipe, false, |p| {
+                let from = p.pos;
+                let pattern = p.parse_single_pattern(false)?;
+                let name = match pattern.node {
+                    Pattern::Binding { name, .. } => name,
+                    _ => Ident::new(spell(&p.tokens[from..p.pos])),
+                };
+                let ty = match p.current() {
+                    Token::Colon | Token::Ident(_) | Token::Ampersand | Token::LParen | Token::LBracket => Some(p.parse_type()?),
+                    _ => None,
+                };
+                Some((name, ty))
+            }))?
+        };
+        if self.expect(&Token::Arrow) {
+            self.parse_type()?;
+        }
+        let body = if *self.current() == Token::LBrace { self.block_expr()? } else { self.parse_expr()? };
+        let expr = Expr::Closure { capture_by_move, params, body: Box::new(body) };
+        Some(Spanned::new(expr, self.span_from(start)))
+    }
+    
+    /// `if cond { .. } else if .. else { .. }`
+    fn parse_if(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        self.advance();
+        let condition = self.head(Self::parse_condition)?;
+        let then_branch = self.parse_block()?;
+        let else_branch = if self.expect(&Token::Else) {
+            Some(Box::new(if *self.current() == Token::If { self.parse_if()? } else { self.block_expr()? }))
+        } else {
+            None
+        };
+        let expr = Expr::If { condition: Box::new(condition), then_branch: Box::new(then_branch), else_branch };
+        Some(Spanned::new(expr, self.span_from(start)))
+    }
+    
+    /// An `if`/`while` condition, or a `let pattern = value` test
+    fn parse_condition(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        if !self.expect(&Token::Let) {
+            return self.parse_binary(0);
+        }
+        let pattern = self.parse_pattern()?;
+        if !self.expect(&Token::Eq) {
+            return None;
+        }
+        let value = self.parse_binary(0)?;
+        Some(Spanned::new(Expr::Let { pattern: Box::new(pattern), value: Box::new(value) }, self.span_from(start)))
+    }
+    
+    /// `match value { arms }`
+    fn parse_match(&mut self) -> Option<Spanned<Expr>> {
+        let start = self.current_span();
+        self.advance();
+        let value = self.head(|p| p.parse_binary(0))?;
+        if !self.expect(&Token::LBrace) {
+            return None;
+        }
+        let arms = self.nested(Open::Match, |p| p.parse_list(Token::RBrace, true, Self::parse_arm))?;
+        Some(Spanned::new(Expr::Match { value: Box::new(value), arms }, self.span_from(start)))
+    }
+    
+    /// A match arm: `Pattern { body }` (RustS+) or `Pattern => expr`, with
+    /// an optional `if guard`
+    fn parse_arm(&mut self) -> Option<MatchArm> {
+        let start = self.current_span();
+        let pattern = self.parse_or_pattern(true)?;
+        let guard = if self.expect(&Token::If) { Some(self.head(|p| p.parse_binary(0))?) } else { None };
+        let body = if self.expect(&Token::FatArrow) {
+            self.parse_expr()?
+        } else if *self.current() == Token::LBrace {
+            self.block_expr()?
+        } else {
+            return None;
+        };
+        Some(MatchArm { pattern, guard, body, span: self.span_from(start) })
+    }
+    
+    //-------------------------------------------------------------------------
+    // Patterns
+    //-------------------------------------------------------------------------
+    
+    /// Parse a pattern, including `A | B` alternatives
+    pub fn parse_pattern(&mut self) -> Option<Spanned<Pattern>> {
+        self.parse_or_pattern(false)
+    }
+    
+    /// `before_body`: a match arm pattern, where `Unit {` opens the arm body
+    fn parse_or_pattern(&mut self, before_body: bool) -> Option<Spanned<Pattern>> {
+        let start = self.current_span();
+        self.expect(&Token::Pipe);
+        let mut alternatives = vec![self.parse_single_pattern(before_body)?];
+        while self.expect(&Token::Pipe) {
+            alternatives.push(self.parse_single_pattern(before_body)?);
+        }
+        if alternatives.len() == 1 {
+            return alternatives.pop();
+        }
+        Some(Spanned::new(Pattern::Or(alternatives), self.span_from(start)))
+    }
+    
+    fn parse_single_pattern(&mut self, before_body: bool) -> Option<Spanned<Pattern>> {
+        let start = self.current_span();
+        let pattern = match self.current().clone() {
+            Token::Ident(name) if name == "_" => { self.advance(); Pattern::Wildcard }
+            Token::Ampersand => {
+                self.advance();
+                let mutable = self.expect(&Token::Mut);
+                Pattern::Ref { mutable, inner: Box::new(self.parse_single_pattern(before_body)?) }
+            }
+            Token::Mut => {
+                self.advance();
+                let name = Ident::new(self.expect_ident()?);
+                Pattern::Binding { name, mutable: true, subpattern: None }
+            }
+            Token::LParen => {
+                self.advance();
+                let items = self.nested(Open::Paren, |p| p.parse_list(Token::RParen, false, Self::parse_pattern))?;
+                Pattern::Tuple(items)
+            }
+            Token::IntLit(_) | Token::FloatLit(_) | Token::StringLit(_) | Token::CharLit(_)
+            | Token::BoolLit(_) | Token::Minus => {
+                let literal = self.parse_unary()?;
+                if !matches!(self.current(), Token::DotDot | Token::DotDotEq) {
+                    return match literal.node {
+                        Expr::Literal(lit) => Some(Spanned::new(Pattern::Literal(lit), literal.span)),
+                        // `-1`
+                        _ => Some(Spanned::new(Pattern::Range { start: Some(Box::new(literal.clone())), end: Some(Box::new(literal)), inclusive: true }, self.span_from(start))),
+                    };
+                }
+                let inclusive = *self.current() == Token::DotDotEq;
+                self.advance();
+                let end = match self.current() {
+                    Token::IntLit(_) | Token::FloatLit(_) | Token::CharLit(_) | Token::Minus => Some(Box::new(self.parse_unary()?)),
+                    _ => None,
+                };
+                Pattern::Range { start: Some(Box::new(literal)), end, inclusive }
+            }
+            Token::Ident(_) => return self.parse_path_pattern(before_body),
+            _ => return None,
+        };
+        Some(Spanned::new(pattern, self.span_from(start)))
+    }
+    
+    /// Binding, `Some(x)`, `Enum::Unit`, `Point { x, y }` or `x @ pattern`
+    fn parse_path_pattern(&mut self, before_body: bool) -> Option<Spanned<Pattern>> {
+        let start = self.current_span();
+        let mut path = Path::single(self.expect_ident()?);
+        while self.expect(&Token::ColonColon) {
+            path.segments.push(Ident::new(self.expect_ident()?));
+        }
+        
+        if *self.current() == Token::LParen {
+            self.advance();
+            let items = self.nested(Open::Paren, |p| p.parse_list(Token::RParen, false, Self::parse_pattern))?;
+            let pattern = Pattern::Variant { path, fields: VariantFields::Tuple(items) };
+            return Some(Spanned::new(pattern, self.span_from(start)));
+        }
+        
+        // `Name { fields }`, unless the `{` is the arm body of a unit pattern:
+        // then no `{`/`=>`/`if`/`|` follows the closing `}`
+        if *self.current() == Token::LBrace && !self.line_break() {
+            let fields = self.attempt(|p| {
+                p.advance();
+                let fields = p.nested(Open::Struct(path.clone()), Self::parse_struct_pattern_fields)?;
+                let body_follows = matches!(p.current(), Token::LBrace | Token::FatArrow | Token::If | Token::Pipe);
+                (!before_body || body_follows).then_some(fields)
             });
+            if let Some((fields, rest)) = fields {
+                let pattern = if path.is_single() {
+                    Pattern::Struct { path, fields, rest }
+                } else {
+                    Pattern::Variant { path, fields: VariantFields::Struct(fields) }
+                };
+                return Some(Spanned::new(pattern, self.span_from(start)));
+            }
         }
         
-        Some(Type::Path(base_path))
+        let name = path.last().cloned()?;
+        let is_binding = path.is_single() && !name.name.starts_with(char::is_uppercase);
+        let pattern = if !is_binding {
+            Pattern::Variant { path, fields: VariantFields::Unit }
+        } else if self.expect(&Token::At) {
+            Pattern::Binding { name, mutable: false, subpattern: Some(Box::new(self.parse_single_pattern(before_body)?)) }
+        } else {
+            Pattern::Binding { name, mutable: false, subpattern: None }
+        };
+        Some(Spanned::new(pattern, self.span_from(start)))
+    }
+    
+    /// `{ a, b: pattern, .. }` after the `{`
+    fn parse_struct_pattern_fields(&mut self) -> Option<StructPatternFields> {
+        let mut fields = Vec::new();
+        let mut rest = false;
+        while *self.current() != Token::RBrace {
+            if self.expect(&Token::DotDot) {
+                rest = true;
+            } else {
+                self.expect(&Token::Mut);
+                let name = Ident::new(self.expect_ident()?);
+                let pattern = if self.expect(&Token::Colon) { Some(self.parse_pattern()?) } else { None };
+                fields.push((name, pattern));
+            }
+            if !self.expect(&Token::Comma) && *self.current() != Token::RBrace {
+                return None;
+            }
+        }
+        self.advance();
+        Some((fields, rest))
+    }
+}
+
+/// `x` as a variable, `a::b` as a path
+fn path_expr(path: Path) -> Expr {
+    match path.segments.as_slice() {
+        [single] => Expr::Var(single.clone()),
+        _ => Expr::Path(path),
+    }
+}
+
+//=============================================================================
+// LINE SHAPE (FOR THE LINE-BASED LOWERING)
+//=============================================================================
+
+/// How one source line parses as a statement, see [`parse_line`]
+#[derive(Debug, Clone)]
+pub enum LineShape {
+    /// A whole statement
+    Complete(Box<Spanned<Stmt>>),
+    /// A statement cut off by the end of the line with brackets still open,
+    /// outermost first. `assigned`: they are on the right of the
+    /// statement's `=`.
+    Open { opens: Vec<Open>, assigned: bool },
+    /// Not a statement this parser understands: items, `}` lines, match
+    /// arm heads, unsupported syntax
+    Unparsed,
+}
+
+/// Parse one line of a function body to see which brackets it leaves open.
+/// The lowering works line by line; this tells it that `x = Some(User {`
+/// opens a struct literal inside a call and `names = ["]",` an array,
+/// where counting bracket characters is fooled by strings. A complete
+/// statement is lowered from its tree where the lowering supports it
+/// (`a = P { x = 1 }.x`, see `lower_assigned_literal`).
+pub fn parse_line(line: &str) -> LineShape {
+    let line = line.trim();
+    if line.is_empty() || line.contains('\n') {
+        return LineShape::Unparsed;
+    }
+    let tokens = Lexer::tokenize(line);
+    // The lexer also stops at characters it does not know (`'a`, `~`)
+    let lexed_all = tokens.last().is_some_and(|(_, span)| span.start_col > line.chars().count());
+    if !lexed_all {
+        return LineShape::Unparsed;
+    }
+    
+    let mut parser = FunctionParser::new(&tokens);
+    match parser.parse_stmt() {
+        Some(stmt) if *parser.current() == Token::Eof => LineShape::Complete(Box::new(stmt)),
+        None if *parser.current() == Token::Eof && !parser.opens.is_empty() => {
+            LineShape::Open { opens: parser.opens, assigned: parser.assigned }
+        }
+        _ => LineShape::Unparsed,
     }
 }
 
@@ -732,6 +1808,27 @@ mod tests {
         assert!(func.effects.is_empty());
     }
     
+    #[test]
+    fn test_parse_method_receiver() {
+        let tokens = Lexer::tokenize("fn bump(&mut self, by i32) effects(write self)");
+        let mut parser = FunctionParser::new(&tokens);
+        let func = parser.parse_function().unwrap();
+        
+        assert_eq!(func.params.len(), 2);
+        assert_eq!(func.params[0].name.name, "self");
+        assert!(matches!(func.params[0].ty, Type::Reference { mutable: true, .. }));
+        assert!(matches!(&func.effects[0], EffectDecl::Write(p) if p.name == "self"));
+    }
+    
+    #[test]
+    fn test_parse_unit_return_keyword() {
+        let tokens = Lexer::tokenize("fn touch(v i32) effects(io) unit {");
+        let mut parser = FunctionParser::new(&tokens);
+        let func = parser.parse_function().unwrap();
+        
+        assert!(matches!(func.return_type, Some(Type::Unit)));
+    }
+    
     #[test]
     fn test_parse_function_with_effects() {
         let tokens = Lexer::tokenize("fn transfer(acc Account, amount i64) effects(write acc, io) Account");
@@ -744,6 +1841,17 @@ mod tests {
         assert!(func.effects.iter().any(|e| matches!(e, EffectDecl::Io)));
     }
     
+    #[test]
+    fn test_parse_field_write_effect() {
+        let tokens = Lexer::tokenize("fn pay(acc Account, amount i64) effects(write acc.balance, io)");
+        let mut parser = FunctionParser::new(&tokens);
+        let func = parser.parse_function().unwrap();
+        
+        assert_eq!(func.effects.len(), 2);
+        assert_eq!(func.effects[0].to_string(), "write(acc.balance)");
+        assert!(matches!(&func.effects[0], EffectDecl::WriteField { param, fields } if param.name == "acc" && fields.len() == 1));
+    }
+    
     #[test]
     fn test_extract_functions() {
         let source = r#"
@@ -764,4 +1872,101 @@ fn transfer(acc Account) effects(write acc) Account { acc }
         // transfer has write effect
         assert!(funcs[2].1.iter().any(|e| matches!(e, EffectDecl::Write(_))));
     }
+    
+    fn expr(source: &str) -> Spanned<Expr> {
+        let tokens = Lexer::tokenize(source);
+        let mut parser = FunctionParser::new(&tokens);
+        let expr = parser.parse_expr().unwrap();
+        assert_eq!(*parser.current(), Token::Eof, "{}", source);
+        expr
+    }
+    
+    #[test]
+    fn test_lexer_operators() {
+        let tokens: Vec<Token> = Lexer::tokenize("a += 1..=n || b .. c 0xFF 2u8").into_iter().map(|(t, _)| t).collect();
+        assert_eq!(tokens[1], Token::PlusEq);
+        assert_eq!(tokens[3], Token::DotDotEq);
+        assert_eq!(tokens[5], Token::Or);
+        assert_eq!(tokens[7], Token::DotDot);
+        assert_eq!(tokens[9], Token::IntLit(255));
+        assert_eq!(tokens[10], Token::IntLit(2));
+    }
+    
+    #[test]
+    fn test_parse_expr_precedence() {
+        let Expr::Binary { op: BinOp::Or, left, .. } = expr("a + b * c > d || done").node else { panic!() };
+        let Expr::Binary { op: BinOp::Gt, left, .. } = left.node else { panic!() };
+        let Expr::Binary { op: BinOp::Add, right, .. } = left.node else { panic!() };
+        assert!(matches!(right.node, Expr::Binary { op: BinOp::Mul, .. }));
+        
+        let Expr::Assign { target, value } = expr("self.total = xs.iter().map(|x| x * 2).sum()").node else { panic!() };
+        assert!(matches!(target.node, Expr::Field { ref field, .. } if field.name == "total"));
+        assert!(matches!(value.node, Expr::MethodCall { ref method, .. } if method.name == "sum"));
+        
+        assert!(matches!(expr("n as f64 / 2.0").node, Expr::Binary { op: BinOp::Div, .. }));
+        assert!(matches!(expr("0..len - 1").node, Expr::Range { inclusive: false, end: Some(_), .. }));
+        assert!(matches!(expr("parse(s)?").node, Expr::Try(_)));
+        assert!(matches!(expr("[0; 64]").node, Expr::Repeat { .. }));
+        assert!(matches!(expr("println!(\"{} }\", x)").node, Expr::Macro { ref args, .. } if args == "\"{} }\", x"));
+    }
+    
+    #[test]
+    fn test_parse_rusts_function_body() {
+        let source = r#"fn settle(w Wallet, tx Transaction) Wallet {
+    mut total i64 = 0
+    for t in history(w.id) {
+        total += t.amount
+    }
+    match tx {
+        Transaction::Deposit { amount } {
+            Wallet {
+                id = w.id
+                balance = w.balance + amount
+            }
+        }
+        None {
+            w
+        }
+        _ => w,
+    }
+}"#;
+        let tokens = Lexer::tokenize(source);
+        let func = FunctionParser::new(&tokens).parse_function().unwrap();
+        let body = func.body.unwrap().node;
+        assert_eq!(body.stmts.len(), 2);
+        assert!(matches!(&body.stmts[0].node, Stmt::Let { mutable: true, ty: Some(_), .. }));
+        assert!(matches!(&body.stmts[1].node, Stmt::Expr(Spanned { node: Expr::For { .. }, .. })));
+        
+        let Some(Spanned { node: Expr::Match { arms, .. }, .. }) = body.expr else { panic!() };
+        assert_eq!(arms.len(), 3);
+        assert!(matches!(&arms[0].pattern.node, Pattern::Variant { fields: VariantFields::Struct(fields), .. } if fields.len() == 1));
+        let Expr::Block(block) = &arms[0].body.node else { panic!() };
+        assert!(matches!(&block.node.expr, Some(Spanned { node: Expr::Struct { fields, .. }, .. }) if fields.len() == 2));
+        // `None {` is a unit pattern followed by its body
+        assert!(matches!(&arms[1].pattern.node, Pattern::Variant { fields: VariantFields::Unit, .. }));
+        assert!(matches!(arms[2].pattern.node, Pattern::Wildcard));
+    }
+    
+    #[test]
+    fn test_parse_line_shape() {
+        let opens = |line: &str| match parse_line(line) {
+            LineShape::Open { opens, assigned } => Some((opens, assigned)),
+            _ => None,
+        };
+        assert_eq!(opens("user = User {"), Some((vec![Open::Struct(Path::single("User"))], true)));
+        assert_eq!(opens("items.push(Item { label = \"}\","), Some((vec![Open::Paren, Open::Struct(Path::single("Item"))], false)));
+        assert_eq!(opens("names Vec[String] = [\"]\","), Some((vec![Open::Array], true)));
+        assert_eq!(opens("kind = match code {"), Some((vec![Open::Match], true)));
+        assert_eq!(opens("if a == b {"), Some((vec![Open::Block], false)));
+        
+        assert!(matches!(parse_line("names = [\"[\", \"a\"]"), LineShape::Complete(_)));
+        // A literal's span runs to its `}`, so the line can be lowered from it
+        let LineShape::Complete(stmt) = parse_line("a = P { x = 1 }.x") else { panic!() };
+        let Stmt::Expr(Spanned { node: Expr::Assign { value, .. }, .. }) = stmt.node else { panic!() };
+        let Expr::Field { base, .. } = value.node else { panic!() };
+        assert_eq!((base.span.start_col, base.span.end_col), (5, 15));
+        assert!(matches!(parse_line("grid[i] = v"), LineShape::Complete(stmt) if matches!(stmt.node, Stmt::Expr(_))));
+        assert!(matches!(parse_line("fn main() {"), LineShape::Unparsed));
+        assert!(matches!(parse_line("}"), LineShape::Unparsed));
+    }
 }
\ No newline at end of file
@@ -7,11 +7,16 @@
 //! - Single-line literal transformations
 
 use crate::helpers::is_valid_identifier;
+use crate::lowering_rules::{enabled, LoweringRule};
+use crate::lowering::depth_tracking_lowering::split_top_level;
 use crate::transform_literal::{find_field_eq, is_valid_field_name};
 
 /// L-05 POST-PROCESSING: Fix bare `mut` declarations
 /// Transform `mut x = 10` → `let mut x = 10;`
 pub fn fix_bare_mut_declaration(line: &str) -> String {
+    if !enabled(LoweringRule::L04) {
+        return line.to_string();
+    }
     let trimmed = line.trim();
     let leading_ws: String = line.chars().take_while(|c| c.is_whitespace()).collect();
     
@@ -60,7 +65,7 @@ pub fn fix_bare_mut_declaration(line: &str) -> String {
 /// Effect annotations like `effects(...)` must not appear in Rust output.
 pub fn strip_effects_from_line(line: &str) -> String {
     // Quick check
-    if !line.contains("effects(") {
+    if !line.contains("effects(") || !enabled(LoweringRule::L05) {
         return line.to_string();
     }
     
@@ -74,6 +79,8 @@ pub fn strip_effects_from_line(line: &str) -> String {
     let mut in_string = false;
     let mut escape_next = false;
     let chars: Vec<char> = line.chars().collect();
+    let byte_offsets: Vec<usize> = line.char_indices().map(|(pos, _)| pos).collect();
+    // Byte offsets, for slicing `line`
     let mut effects_positions: Vec<usize> = Vec::new();
     
     for (i, &c) in chars.iter().enumerate() {
@@ -96,7 +103,7 @@ pub fn strip_effects_from_line(line: &str) -> String {
         if !in_string && i + 8 <= chars.len() {
             let slice: String = chars[i..i+8].iter().collect();
             if slice == "effects(" {
-                effects_positions.push(i);
+                effects_positions.push(byte_offsets[i]);
             }
         }
     }
@@ -205,32 +212,11 @@ pub fn transform_single_line_struct_literal(line: &str) -> String {
 
 /// Transform fields inside braces from = to : syntax
 fn transform_fields_in_braces(fields: &str) -> String {
-    let mut result = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut brace_depth: usize = 0;
-    
-    for c in fields.chars() {
-        if c == '"' && !current.ends_with('\\') {
-            in_string = !in_string;
-        }
-        if !in_string {
-            if c == '{' { brace_depth += 1; }
-            if c == '}' { brace_depth = brace_depth.saturating_sub(1); }
-        }
-        
-        if c == ',' && !in_string && brace_depth == 0 {
-            result.push(transform_single_field(&current));
-            current.clear();
-        } else {
-            current.push(c);
-        }
-    }
-    
-    // Last field
-    if !current.trim().is_empty() {
-        result.push(transform_single_field(&current));
-    }
+    let result: Vec<String> = split_top_level(fields, ',')
+        .into_iter()
+        .filter(|f| !f.trim().is_empty())
+        .map(transform_single_field)
+        .collect();
     
     if result.is_empty() {
         String::new()
@@ -4,15 +4,41 @@
 //! transpiled output before returning it.
 
 use crate::helpers::transform_generic_brackets;
-use crate::helpers::transform_macro_calls;
+use crate::helpers::transform_macro_calls_except;
+use crate::macro_shadowing::shadowed_macros_per_line;
+use crate::helpers::transform_none_literal;
 use crate::postprocess::{fix_bare_mut_declaration, strip_effects_from_line, strip_outer_keyword};
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::empty_literals::lower_empty_arrays;
+use crate::bool_match::lower_inline_matches;
+
+/// Appended to the first line of a helper item injected by a lowering pass
+/// (`use` for a literal's collection type, a hoisted constant, ...). The
+/// item-ordering phase moves the whole item to the top of the file, so a
+/// helper produced while inside a function or impl never lands there.
+pub const HOIST_MARKER: &str = "// @rustsp-hoist";
 
 /// Apply all post-processing transformations to the output lines
 pub fn apply_postprocessing(output_lines: Vec<String>) -> String {
-    // L-08: Transform macro calls (println -> println!, etc.)
+    finish_postprocessing(postprocess_lines(output_lines))
+}
+
+/// Item ordering and import deduplication over lines already passed
+/// through [`postprocess_lines`], joined
+pub fn finish_postprocessing(lines: Vec<String>) -> String {
+    dedup_use_items(order_items(lines)).join("\n")
+}
+
+/// Post-process output lines without joining them (line-preserving mode
+/// needs the individual entries to realign them with the source)
+pub fn postprocess_lines(output_lines: Vec<String>) -> Vec<String> {
+    // L-08: Transform macro calls (println -> println!, etc.), except calls
+    // of bindings that shadow a macro name
+    let shadowed = shadowed_macros_per_line(&output_lines);
     let transformed_lines: Vec<String> = output_lines
         .into_iter()
-        .map(|line| transform_macro_calls(&line))
+        .zip(shadowed)
+        .map(|(line, shadowed)| transform_macro_calls_except(&line, &shadowed))
         .collect();
     
     //==========================================================================
@@ -55,5 +81,377 @@ pub fn apply_postprocessing(output_lines: Vec<String>) -> String {
         .map(|line| transform_generic_brackets(&line))
         .collect();
     
-    generic_transformed.join("\n")
+    //==========================================================================
+    // `none` literal: `x = none` → `x = None`, `x != none` → `x.is_some()`
+    //==========================================================================
+    let none_transformed: Vec<String> = generic_transformed
+        .into_iter()
+        .map(|line| transform_none_literal(&line))
+        .collect();
+    
+    //==========================================================================
+    // Empty collections: `xs = []` → `let xs = vec![];` (see empty_literals)
+    //==========================================================================
+    let arrays_lowered = lower_empty_arrays(none_transformed);
+    
+    //==========================================================================
+    // One-line bool or value match: `match ok { true { a } false { b } }` →
+    // `match ok { true => a, false => b }` (see bool_match)
+    //==========================================================================
+    lower_inline_matches(arrays_lowered)
+}
+
+//==========================================================================
+// ITEM ORDERING
+// Source order is kept for user items; only top-level `use` declarations
+// that appear after other items and hoist-marked helpers are moved up.
+// Not applied with --preserve-lines (it would break the line mapping).
+//==========================================================================
+
+fn is_use_item(trimmed: &str) -> bool {
+    trimmed.starts_with("use ") || trimmed.starts_with("pub use ")
+}
+
+/// Start of the file that is already prelude: inner attributes, comments,
+/// blank lines and `use` declarations
+fn prelude_end(lines: &[String]) -> usize {
+    let mut depth: i64 = 0;
+    let mut end = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let in_prelude_item = depth > 0
+            || trimmed.is_empty()
+            || trimmed.starts_with("//")
+            || trimmed.starts_with("#![")
+            || is_use_item(trimmed);
+        if !in_prelude_item {
+            break;
+        }
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        depth += opens as i64 - closes as i64;
+        end = i + 1;
+    }
+    end
+}
+
+/// Last line of the item starting at `start`: braces balance again and the
+/// line ends the item
+fn item_end(lines: &[String], start: usize) -> usize {
+    let mut depth: i64 = 0;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let code = line.split(HOIST_MARKER).next().unwrap_or("").trim();
+        let (opens, closes) = count_braces_outside_strings(code);
+        depth += opens as i64 - closes as i64;
+        if depth <= 0 && (code.ends_with(';') || code.ends_with('}')) {
+            return i;
+        }
+    }
+    lines.len() - 1
+}
+
+/// Item-ordering phase: late top-level `use` declarations and hoist-marked
+/// helper items move to the top (after the existing prelude), deduplicated.
+/// Everything else keeps its source order.
+pub fn order_items(output_lines: Vec<String>) -> Vec<String> {
+    let lines: Vec<String> = output_lines
+        .iter()
+        .flat_map(|entry| entry.split('\n'))
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        return lines;
+    }
+    let prelude = prelude_end(&lines);
+    
+    let mut uses: Vec<String> = Vec::new();
+    let mut helpers: Vec<String> = Vec::new();
+    let mut rest: Vec<String> = Vec::new();
+    let mut moved = false;
+    let mut depth: i64 = 0;
+    let mut i = prelude;
+    
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        let prev_is_attr = rest.last().is_some_and(|l| l.trim_start().starts_with("#["));
+        
+        if lines[i].contains(HOIST_MARKER) {
+            let end = item_end(&lines, i);
+            // An injected import joins the other imports (and is
+            // deduplicated against them)
+            if is_use_item(trimmed) {
+                let item = lines[i..=end].iter()
+                    .map(|l| l.replacen(HOIST_MARKER, "", 1).trim().to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                if !uses.contains(&item) && !lines[..prelude].iter().any(|l| l.trim() == item) {
+                    uses.push(item);
+                }
+                moved = true;
+                i = end + 1;
+                continue;
+            }
+            let indent = lines[i].len() - lines[i].trim_start().len();
+            let item: Vec<String> = lines[i..=end].iter().map(|l| {
+                let l = l.replacen(HOIST_MARKER, "", 1);
+                let ws = l.len() - l.trim_start().len();
+                l[indent.min(ws)..].trim_end().to_string()
+            }).collect();
+            let item = item.join("\n");
+            if !helpers.contains(&item) {
+                helpers.push(item);
+            }
+            moved = true;
+            i = end + 1;
+            continue;
+        }
+        
+        if depth == 0 && is_use_item(trimmed) && !prev_is_attr {
+            let end = item_end(&lines, i);
+            let item: Vec<&str> = lines[i..=end].iter().map(|l| l.trim_end()).collect();
+            let item = item.join("\n");
+            if !uses.contains(&item) && !lines[..prelude].iter().any(|l| l.trim() == item) {
+                uses.push(item);
+            }
+            moved = true;
+            i = end + 1;
+            continue;
+        }
+        
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        depth += opens as i64 - closes as i64;
+        rest.push(lines[i].clone());
+        i += 1;
+    }
+    
+    if !moved {
+        return lines;
+    }
+    
+    let mut ordered: Vec<String> = lines[..prelude].to_vec();
+    ordered.extend(uses);
+    if !helpers.is_empty() {
+        if ordered.last().is_some_and(|l| !l.trim().is_empty()) {
+            ordered.push(String::new());
+        }
+        for helper in helpers {
+            ordered.push(helper);
+            ordered.push(String::new());
+        }
+    }
+    ordered.extend(rest);
+    ordered
+}
+
+/// Drop imports an earlier top-level `use` already made: a repeated
+/// declaration disappears and a group loses the names imported before
+/// (`use std::io::Read;` after `use std::io::{Read, Write};`). Nested
+/// groups and `#[cfg]`-gated imports are left alone.
+pub fn dedup_use_items(lines: Vec<String>) -> Vec<String> {
+    let mut imported: Vec<String> = Vec::new();
+    let mut out = Vec::with_capacity(lines.len());
+    let mut depth: i64 = 0;
+    let mut i = 0;
+    
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        let prev_is_attr = out.last().is_some_and(|l: &String| l.trim_start().starts_with("#["));
+        if depth == 0 && is_use_item(trimmed) && !prev_is_attr {
+            let end = item_end(&lines, i);
+            let joined = lines[i..=end].iter().map(|l| l.trim()).collect::<Vec<_>>().join(" ");
+            if let Some(tree) = UseTree::parse(&joined) {
+                let kept: Vec<&String> = tree.names.iter()
+                    .filter(|name| !imported.contains(&tree.path_of(name)))
+                    .collect();
+                imported.extend(kept.iter().map(|name| tree.path_of(name)));
+                if kept.len() == tree.names.len() {
+                    out.extend_from_slice(&lines[i..=end]);
+                } else if !kept.is_empty() {
+                    let indent = &lines[i][..lines[i].len() - trimmed.len()];
+                    out.push(format!("{}{}", indent, tree.render(&kept)));
+                }
+                i = end + 1;
+                continue;
+            }
+        }
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        depth += opens as i64 - closes as i64;
+        out.push(lines[i].clone());
+        i += 1;
+    }
+    out
+}
+
+/// A `use` declaration importing names from one prefix:
+/// `pub use a::b::{C, D as E};` or `use a::b::C;`
+struct UseTree {
+    keyword: String,
+    /// Ends with `::` unless empty
+    prefix: String,
+    names: Vec<String>,
+}
+
+impl UseTree {
+    fn parse(item: &str) -> Option<UseTree> {
+        let (keyword, rest) = item.split_once("use ")?;
+        let keyword = format!("{}use", keyword);
+        let rest = rest.trim().strip_suffix(';')?.trim();
+        let (prefix, names) = match rest.strip_suffix('}') {
+            Some(grouped) => {
+                let (prefix, names) = grouped.split_once('{')?;
+                (prefix.to_string(), names.split(',').map(str::trim).filter(|n| !n.is_empty()).map(String::from).collect())
+            }
+            None => match rest.rsplit_once("::") {
+                Some((prefix, name)) => (format!("{}::", prefix), vec![name.to_string()]),
+                None => (String::new(), vec![rest.to_string()]),
+            },
+        };
+        let nested = names.iter().any(|n: &String| n.contains('{'));
+        (!nested && (prefix.is_empty() || prefix.ends_with("::"))).then_some(UseTree { keyword, prefix, names })
+    }
+    
+    /// Full path a name imports; `self` imports the prefix itself
+    fn path_of(&self, name: &str) -> String {
+        if name == "self" {
+            self.prefix.trim_end_matches("::").to_string()
+        } else {
+            format!("{}{}", self.prefix, name)
+        }
+    }
+    
+    fn render(&self, names: &[&String]) -> String {
+        match names {
+            [name] if name.as_str() == "self" => format!("{} {};", self.keyword, self.path_of(name)),
+            [name] => format!("{} {}{};", self.keyword, self.prefix, name),
+            _ => format!("{} {}{{{}}};", self.keyword, self.prefix, names.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", ")),
+        }
+    }
+}
+
+/// Realign output entries 1:1 with source lines.
+///
+/// `origins[i]` is the 0-based source line that produced `entries[i]`.
+/// Everything produced by one source line is joined onto a single output
+/// line (full-line comments become block comments so they don't swallow
+/// the code after them); source lines that produced nothing stay empty.
+pub fn align_to_source_lines(entries: &[String], origins: &[usize], source_len: usize) -> String {
+    let mut slots: Vec<Vec<String>> = vec![Vec::new(); source_len.max(1)];
+    
+    for (entry, &origin) in entries.iter().zip(origins) {
+        let slot = origin.min(slots.len() - 1);
+        for piece in entry.split('\n') {
+            if piece.trim().is_empty() {
+                continue;
+            }
+            slots[slot].push(piece.to_string());
+        }
+    }
+    
+    slots
+        .into_iter()
+        .map(|pieces| {
+            let count = pieces.len();
+            pieces
+                .into_iter()
+                .enumerate()
+                .map(|(i, piece)| {
+                    let trimmed = piece.trim_start();
+                    if i + 1 < count && trimmed.starts_with("//") {
+                        format!("/* {} */", trimmed.trim_start_matches('/').trim())
+                    } else if i > 0 {
+                        trimmed.to_string()
+                    } else {
+                        piece
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_align_to_source_lines() {
+        let entries = vec![
+            "fn main() {".to_string(),
+            "// COMPILE ERROR: bad\nlet x = 1;".to_string(),
+            "let y = 2;".to_string(),
+            "}".to_string(),
+        ];
+        // Source line 2 produced nothing (e.g. an `effect` statement)
+        let aligned = align_to_source_lines(&entries, &[0, 1, 1, 3], 4);
+        assert_eq!(aligned, "fn main() {\n/* COMPILE ERROR: bad */ let x = 1; let y = 2;\n\n}");
+    }
+
+    #[test]
+    fn test_order_items() {
+        let lines = vec![
+            "use std::fmt;".to_string(),
+            "fn main() {".to_string(),
+            format!("    const LIMIT: i32 = 10; {}", HOIST_MARKER),
+            "    let x = LIMIT;".to_string(),
+            "}".to_string(),
+            "impl S {".to_string(),
+            format!("    const LIMIT: i32 = 10; {}", HOIST_MARKER),
+            "}".to_string(),
+            "use std::collections::HashMap;".to_string(),
+            "use std::fmt;".to_string(),
+        ];
+        assert_eq!(order_items(lines), vec![
+            "use std::fmt;",
+            "use std::collections::HashMap;",
+            "",
+            "const LIMIT: i32 = 10;",
+            "",
+            "fn main() {",
+            "    let x = LIMIT;",
+            "}",
+            "impl S {",
+            "}",
+        ]);
+
+        // Nothing to move - output untouched
+        let untouched = vec!["fn f() {".to_string(), "    use std::fmt;".to_string(), "}".to_string()];
+        assert_eq!(order_items(untouched.clone()), untouched);
+    }
+
+    #[test]
+    fn test_dedup_use_items() {
+        let lines: Vec<String> = [
+            "use std::io::{Read, Write};",
+            "use std::io::Read;",
+            "use std::fmt;",
+            "use std::fmt::{self, Display, Read};",
+            "#[cfg(test)]",
+            "use std::io::Write;",
+            "fn main() {",
+            "    use std::io::Read;",
+            "}",
+        ].iter().map(|l| l.to_string()).collect();
+        assert_eq!(dedup_use_items(lines), vec![
+            "use std::io::{Read, Write};",
+            "use std::fmt;",
+            "use std::fmt::{Display, Read};",
+            "#[cfg(test)]",
+            "use std::io::Write;",
+            "fn main() {",
+            "    use std::io::Read;",
+            "}",
+        ]);
+    }
+
+    #[test]
+    fn test_hoisted_use_joins_imports() {
+        let lines = vec![
+            "use std::collections::HashMap;".to_string(),
+            "fn main() {".to_string(),
+            format!("    use std::collections::HashMap; {}", HOIST_MARKER),
+            "}".to_string(),
+        ];
+        assert_eq!(order_items(lines), vec!["use std::collections::HashMap;", "fn main() {", "}"]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,215 @@
+//! Program Index - One Scan Before Lowering
+//!
+//! The second pass relies on several whole-source analyses:
+//! - scope analysis: declarations, shadowing, `mut` ([`ScopeAnalyzer`])
+//! - the first-pass registries: functions, structs, enums, clone and `Copy`
+//!   requirements ([`FirstPassResult`])
+//! - per function, the variables its body mutates through methods,
+//!   compound assignment or `&mut` borrows
+//!
+//! Each of these used to walk the source on its own, and the per-function
+//! scan re-walked every body when the second pass reached its signature.
+//! [`ProgramIndex::build`] walks the source once: every line has its comment
+//! stripped and its braces counted a single time, and that line stream
+//! drives all of the analyses.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::first_pass::{FirstPassResult, FirstPassScan};
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+use crate::scope::{ScopeAnalyzer, ScopeScan};
+use crate::variable::{mut_borrow_targets, mutating_method_targets, resizing_method_targets, VariableTracker};
+
+/// A source line, classified once
+#[derive(Debug, Clone)]
+pub struct IndexedLine {
+    /// The line without its inline comment
+    pub code: String,
+    /// `{` outside strings
+    pub opens: usize,
+    /// `}` outside strings
+    pub closes: usize,
+}
+
+impl IndexedLine {
+    fn new(line: &str) -> Self {
+        // Most lines have no comment or no braces; skip the char-level scans
+        let code = if line.contains("//") {
+            strip_inline_comment(line)
+        } else {
+            line.trim_end().to_string()
+        };
+        let (opens, closes) = if code.contains(['{', '}']) {
+            count_braces_outside_strings(code.trim())
+        } else {
+            (0, 0)
+        };
+        IndexedLine { code, opens, closes }
+    }
+
+    pub fn trimmed(&self) -> &str {
+        self.code.trim()
+    }
+
+    fn is_function_start(&self) -> bool {
+        let trimmed = self.trimmed();
+        trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ")
+    }
+}
+
+/// What a function body mutates, collected before lowering
+#[derive(Debug, Clone, Default)]
+pub struct FunctionScan {
+    /// Body lines (0-based), from the line with the opening `{` to the one
+    /// with the closing `}`
+    pub body: Vec<usize>,
+    /// Variables mutated through a method call or compound assignment
+    pub mutated_via_method: HashSet<String>,
+    /// Variables borrowed as `&mut`
+    pub mut_borrowed: HashSet<String>,
+    /// Variables grown or shrunk with `.push(`, `.pop()`, ...
+    pub resized: HashSet<String>,
+    depth: usize,
+}
+
+impl FunctionScan {
+    /// Add a line; `true` once the body is closed
+    fn scan_line(&mut self, line_num: usize, line: &IndexedLine, mutated: &[String], borrowed: &[String], resized: &[String]) -> bool {
+        let started = !self.body.is_empty() || line.opens > 0;
+        if !started {
+            return false;
+        }
+        self.depth = (self.depth + line.opens).saturating_sub(line.closes);
+        self.body.push(line_num);
+        self.mutated_via_method.extend(mutated.iter().cloned());
+        self.mut_borrowed.extend(borrowed.iter().cloned());
+        self.resized.extend(resized.iter().cloned());
+        self.depth == 0
+    }
+}
+
+/// Everything the second pass needs to know about the whole source
+pub struct ProgramIndex {
+    pub lines: Vec<IndexedLine>,
+    pub scope: ScopeAnalyzer,
+    pub first_pass: FirstPassResult,
+    /// Function body scans, by signature line (0-based)
+    functions: HashMap<usize, FunctionScan>,
+}
+
+impl ProgramIndex {
+    /// Scan `lines` once, running scope analysis, the first pass (which also
+    /// records assignments in `tracker`) and the per-function mutation scan
+    pub fn build(lines: &[&str], tracker: &mut VariableTracker) -> Self {
+        let mut scope = ScopeAnalyzer::new();
+        let mut scope_scan = ScopeScan::default();
+        let mut first_pass = FirstPassScan::default();
+        let mut functions = HashMap::new();
+        // Functions whose body has not closed yet (nested functions overlap)
+        let mut open_functions: Vec<(usize, FunctionScan)> = Vec::new();
+        let mut indexed = Vec::with_capacity(lines.len());
+
+        for (line_num, line) in lines.iter().enumerate() {
+            let line = IndexedLine::new(line);
+            scope.analyze_line(&mut scope_scan, line_num, line.trimmed(), line.opens, line.closes);
+            first_pass.scan_line(line_num, &line.code, tracker);
+            first_pass.scan_for_loop(lines, line_num);
+
+            if line.is_function_start() {
+                open_functions.push((line_num, FunctionScan::default()));
+            }
+            if !open_functions.is_empty() {
+                let mutated = mutating_method_targets(&line.code);
+                let borrowed = mut_borrow_targets(&line.code);
+                let resized = resizing_method_targets(&line.code);
+                let mut i = 0;
+                while i < open_functions.len() {
+                    if open_functions[i].1.scan_line(line_num, &line, &mutated, &borrowed, &resized) {
+                        let (start, scan) = open_functions.remove(i);
+                        functions.insert(start, scan);
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            indexed.push(line);
+        }
+        // Bodies left open at the end of the source (unbalanced braces)
+        functions.extend(open_functions);
+
+        scope.finish(scope_scan);
+        ProgramIndex {
+            lines: indexed,
+            scope,
+            first_pass: first_pass.finish(),
+            functions,
+        }
+    }
+
+    /// Body scan of the function whose signature starts on `line_num`
+    pub fn function(&self, line_num: usize) -> Option<&FunctionScan> {
+        self.functions.get(&line_num)
+    }
+
+    /// Trimmed code of a function's body lines
+    pub fn body_lines(&self, scan: &FunctionScan) -> Vec<&str> {
+        scan.body.iter().map(|&i| self.lines[i].trimmed()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"struct Point {
+    x i32
+}
+
+fn fill(items Vec[i32]) {
+    items.push(1)   // comment {
+    total = 0
+    total += items.len()
+    helper(&mut buf)
+}
+
+fn other() {
+    p = Point { x = 1 }
+    println("{}", p.x)
+}"#;
+
+    #[test]
+    fn test_single_scan_matches_separate_passes() {
+        let lines: Vec<&str> = SOURCE.lines().collect();
+        let index = ProgramIndex::build(&lines, &mut VariableTracker::new());
+
+        let mut scope = ScopeAnalyzer::new();
+        scope.analyze(SOURCE);
+        assert_eq!(index.scope.decl_lines, scope.decl_lines);
+        assert_eq!(index.scope.mut_vars, scope.mut_vars);
+
+        let first_pass = crate::first_pass::run_first_pass(&lines, &mut VariableTracker::new());
+        assert!(index.first_pass.struct_registry.names.contains("Point"));
+        assert_eq!(index.first_pass.struct_registry.names, first_pass.struct_registry.names);
+        assert!(index.first_pass.fn_registry.get("fill").is_some());
+    }
+
+    #[test]
+    fn test_function_bodies_scanned() {
+        let lines: Vec<&str> = SOURCE.lines().collect();
+        let index = ProgramIndex::build(&lines, &mut VariableTracker::new());
+
+        let fill = index.function(4).expect("fill scanned");
+        assert_eq!(fill.body, (4..10).collect::<Vec<_>>());
+        assert!(fill.mutated_via_method.contains("items"));
+        assert!(fill.mutated_via_method.contains("total"));
+        assert!(fill.mut_borrowed.contains("buf"));
+        assert!(fill.resized.contains("items"));
+
+        // Braces in strings and comments do not end a body early
+        let other = index.function(11).expect("other scanned");
+        assert_eq!(index.body_lines(other).last(), Some(&"}"));
+        assert!(other.mutated_via_method.is_empty());
+        assert!(index.function(0).is_none());
+    }
+}
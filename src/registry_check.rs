@@ -0,0 +1,289 @@
+//! Registry Consistency Check (Stage 2.5)
+//!
+//! Lowering decides much of the output from the first-pass registries:
+//! which names are types, which calls need defaults filled in, which types
+//! get `#[derive(Clone)]`. When the output and the registries disagree,
+//! the program usually still parses and rustc reports something confusing
+//! far from the cause. This check compares them directly after lowering:
+//!
+//! - every registered struct and enum is defined exactly once in the output
+//! - every registered function is emitted with the same name, number of
+//!   parameters and return type (a name defined several times, such as
+//!   `new` in several `impl` blocks, matches if any definition does)
+//! - every `#[derive(..)]` with `Clone` sits on an emitted struct or enum
+//!
+//! A mismatch is a lowering bug and is reported as an internal error
+//! ([`SanityErrorKind::RegistryMismatch`]). `rust { .. }` blocks and
+//! functions written in Rust are not registered and not checked, nor is
+//! the `compile_error!` output of a source that could not be lowered.
+
+use std::collections::HashMap;
+
+use crate::first_pass::run_first_pass;
+use crate::function::{strip_effects_clause, FunctionSignature};
+use crate::ident::{is_ident_continue, is_valid_identifier};
+use crate::lowering::depth_tracking_lowering::{find_matching_close, split_top_level_types};
+use crate::rust_sanity::{SanityCheckResult, SanityError, SanityErrorKind};
+use crate::transpile_main::{preprocess, LoweringOptions};
+use crate::variable::VariableTracker;
+
+/// A function definition found in the output
+#[derive(Debug, Clone, PartialEq)]
+struct EmittedFn {
+    /// 1-based output line
+    line: usize,
+    arity: usize,
+    return_type: Option<String>,
+}
+
+/// Cross-check lowered `rust` against the registries of the .rss `source`
+/// it was lowered from with `options`
+pub fn check_registries(source: &str, rust: &str, options: &LoweringOptions) -> SanityCheckResult {
+    // Stand-in output for a source that could not be lowered
+    if rust.lines().any(|line| line.starts_with("compile_error!(")) {
+        return SanityCheckResult::ok();
+    }
+    let preprocessed = preprocess(source, options);
+    let lines: Vec<&str> = preprocessed.source.lines().collect();
+    let first_pass = run_first_pass(&lines, &mut VariableTracker::new());
+
+    let mut type_lines: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut functions: HashMap<String, Vec<EmittedFn>> = HashMap::new();
+    let rust_lines: Vec<&str> = rust.lines().collect();
+    for (idx, line) in rust_lines.iter().enumerate() {
+        let code = strip_attributes(line);
+        if let Some(name) = type_definition(code) {
+            type_lines.entry(name).or_default().push(idx + 1);
+        } else if let Some((name, emitted)) = fn_definition(code, idx + 1) {
+            functions.entry(name).or_default().push(emitted);
+        }
+    }
+
+    let mut errors = Vec::new();
+    let mut types: Vec<(&String, &str)> = first_pass.struct_registry.names.iter().map(|n| (n, "struct"))
+        .chain(first_pass.enum_registry.names.iter().map(|n| (n, "enum")))
+        .collect();
+    types.sort();
+    for (name, kind) in types {
+        let found = type_lines.get(name).map(Vec::as_slice).unwrap_or_default();
+        if found.len() != 1 {
+            let line = found.get(1).copied().unwrap_or(1);
+            errors.push(mismatch(line, format!(
+                "registered {} `{}` is defined {} times in the output (expected once)", kind, name, found.len()
+            )));
+        }
+    }
+
+    let mut signatures: Vec<&FunctionSignature> = first_pass.fn_registry.signatures().collect();
+    signatures.sort_by(|a, b| a.name.cmp(&b.name));
+    for sig in signatures {
+        let expected_return = registered_return_type(sig.return_type.as_deref());
+        match functions.get(&sig.name) {
+            None => errors.push(mismatch(1, format!("registered function `{}` is not in the output", sig.name))),
+            Some(found) if !found.iter().any(|f| f.arity == sig.parameters.len() && f.return_type == expected_return) => {
+                errors.push(mismatch(found[0].line, format!(
+                    "`fn {}` is emitted as ({} parameters) -> {} but registered as ({} parameters) -> {}",
+                    sig.name, found[0].arity, found[0].return_type.as_deref().unwrap_or("()"),
+                    sig.parameters.len(), expected_return.as_deref().unwrap_or("()")
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+
+    errors.extend(misplaced_derives(&rust_lines));
+
+    if errors.is_empty() {
+        SanityCheckResult::ok()
+    } else {
+        SanityCheckResult::error(errors)
+    }
+}
+
+fn mismatch(line: usize, message: String) -> SanityError {
+    SanityError { line, column: 1, message, kind: SanityErrorKind::RegistryMismatch }
+}
+
+/// `#[derive(.., Clone, ..)]` lines not followed by a struct or enum
+fn misplaced_derives(lines: &[&str]) -> Vec<SanityError> {
+    let mut errors = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let Some(derives) = trimmed.strip_prefix("#[derive(") else { continue };
+        if !derives.split([',', ')']).any(|d| d.trim() == "Clone") {
+            continue;
+        }
+        // The derive may share its line with the definition (`--preserve-lines`)
+        let after = trimmed.find(")]").map(|end| strip_attributes(&trimmed[end + 2..])).unwrap_or("");
+        let target = if after.is_empty() {
+            lines[idx + 1..].iter()
+                .map(|l| strip_attributes(l))
+                .find(|l| !l.is_empty() && !l.starts_with("//"))
+        } else {
+            Some(after)
+        };
+        if target.and_then(type_definition).is_none() {
+            errors.push(mismatch(idx + 1, "`#[derive(Clone)]` is not followed by a struct or enum definition".to_string()));
+        }
+    }
+    errors
+}
+
+/// Leading `#[..]` attributes (badges, `#[inline]`, ...) off a line
+fn strip_attributes(line: &str) -> &str {
+    let mut code = line.trim();
+    while code.starts_with("#[") {
+        match code.find("] ") {
+            Some(end) => code = code[end + 1..].trim_start(),
+            None => break,
+        }
+    }
+    code
+}
+
+fn strip_qualifiers(code: &str) -> &str {
+    let mut code = code;
+    loop {
+        let before = code;
+        for qualifier in ["pub(crate) ", "pub(super) ", "pub ", "const ", "async ", "unsafe ", "extern \"C\" "] {
+            code = code.strip_prefix(qualifier).unwrap_or(code);
+        }
+        if code == before {
+            return code;
+        }
+    }
+}
+
+/// Name of the struct or enum defined on an output line
+fn type_definition(code: &str) -> Option<String> {
+    let code = strip_qualifiers(code);
+    let rest = code.strip_prefix("struct ").or_else(|| code.strip_prefix("enum "))?;
+    let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+    let name = &rest[..end];
+    is_valid_identifier(name).then(|| name.to_string())
+}
+
+/// Name, arity and return type of a function defined on an output line
+fn fn_definition(code: &str, line: usize) -> Option<(String, EmittedFn)> {
+    let rest = strip_qualifiers(code).strip_prefix("fn ")?;
+    let name_end = rest.find(['<', '('])?;
+    let name = rest[..name_end].trim();
+    if !is_valid_identifier(name) {
+        return None;
+    }
+    let mut angle = 0i32;
+    let mut open = None;
+    for (i, c) in rest.char_indices().skip_while(|&(i, _)| i < name_end) {
+        match c {
+            '<' => angle += 1,
+            '>' if !rest[..i].ends_with('-') => angle -= 1,
+            '(' if angle == 0 => {
+                open = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let open = open?;
+    let close = find_matching_close(rest, open)?;
+    let arity = split_top_level_types(&rest[open + 1..close], ',').iter().filter(|p| !p.trim().is_empty()).count();
+    let after = rest[close + 1..].trim_start();
+    let return_type = after.strip_prefix("->").map(|ret| {
+        let ret = ret.trim_start();
+        let end = return_type_end(ret);
+        ret[..end].to_string()
+    });
+    Some((name.to_string(), EmittedFn { line, arity, return_type: return_type.and_then(|r| normalize_type(&r)) }))
+}
+
+/// Where the return type ends: the body, a `where` clause or `;`
+fn return_type_end(ret: &str) -> usize {
+    let mut depth = 0i32;
+    for (i, c) in ret.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' if ret[..i].ends_with('-') => {}
+            '>' | ')' | ']' => depth -= 1,
+            '{' | ';' if depth == 0 => return i,
+            'w' if depth == 0 && ret[i..].starts_with("where") && ret[..i].ends_with(' ') => return i,
+            _ => {}
+        }
+    }
+    ret.len()
+}
+
+/// Return type as registered, in the form [`normalize_type`] gives emitted ones
+fn registered_return_type(ret: Option<&str>) -> Option<String> {
+    let ret = strip_effects_clause(ret?);
+    let ret = match ret.find(" effects(") {
+        Some(pos) => &ret[..pos],
+        None => ret.as_str(),
+    };
+    let ret = match ret.find(" where ") {
+        Some(pos) => &ret[..pos],
+        None => ret,
+    };
+    normalize_type(ret)
+}
+
+/// Spelling-insensitive form of a type: RustS+ brackets and Rust angle
+/// brackets compare equal, as do `&dyn T` and `&T`, `'_` and `_`, and
+/// paths that end the same (lowering writes `Result` in a `Display` impl
+/// as `std::fmt::Result`)
+fn normalize_type(ty: &str) -> Option<String> {
+    let mut normalized = String::new();
+    for c in ty.split_whitespace().filter(|word| *word != "dyn").collect::<String>().chars() {
+        match c {
+            '\'' => {}
+            '[' => normalized.push('<'),
+            ']' => normalized.push('>'),
+            ':' if normalized.ends_with(':') => {
+                normalized.pop();
+                while normalized.ends_with(is_ident_continue) {
+                    normalized.pop();
+                }
+            }
+            c => normalized.push(c),
+        }
+    }
+    (!normalized.is_empty() && normalized != "()").then_some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "struct Point {\n    x i32\n}\n\nimpl Point {\n    fn new(x i32) Point {\n        Point { x = x }\n    }\n}\n\nfn make(n i32 = 3) Vec[Point] effects(alloc) {\n    vec![Point::new(n)]\n}\n\nfn main() {\n    ps = make()\n    println(\"{}\", ps.len())\n}\n";
+
+    #[test]
+    fn test_lowered_output_is_consistent() {
+        let rust = crate::parse_rusts(SRC);
+        let result = check_registries(SRC, &rust, &LoweringOptions::default());
+        assert!(result.is_valid, "{:?}\n{}", result.errors, rust);
+    }
+
+    #[test]
+    fn test_desync_reported() {
+        let rust = crate::parse_rusts(SRC)
+            .replace("fn make(n: i32) -> Vec<Point>", "fn make(n: i32, m: i32) -> Vec<Point>")
+            .replacen("struct Point {", "#[derive(Clone)]\nfn stray() {}\nstruct Point {}\nstruct Point {", 1);
+        let messages: Vec<String> = check_registries(SRC, &rust, &LoweringOptions::default()).errors.into_iter().map(|e| e.message).collect();
+        assert!(messages.iter().any(|m| m.contains("struct `Point` is defined 2 times")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("`fn make` is emitted as (2 parameters) -> Vec<Point> but registered as (1 parameters)")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.contains("not followed by a struct or enum")), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_emitted_signatures() {
+        let (name, f) = fn_definition("pub fn apply<F: Fn(i32) -> i32>(f: F, x: i32, ys: [u8; 2]) -> Result<(), String> {", 4).unwrap();
+        assert_eq!(name, "apply");
+        assert_eq!(f.arity, 3);
+        assert_eq!(f.return_type.as_deref(), Some("Result<(),String>"));
+        assert_eq!(fn_definition("fn total(&self) -> i32 where Self: Sized {", 1).unwrap().1.return_type.as_deref(), Some("i32"));
+        assert_eq!(registered_return_type(Some("Vec[Point] effects(alloc)")).as_deref(), Some("Vec<Point>"));
+        assert_eq!(registered_return_type(Some("effects(io) ()")), None);
+        assert_eq!(fn_definition("fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {", 1).unwrap().1.return_type, registered_return_type(Some("Result")));
+        assert_eq!(fn_definition("fn fläche(p: Punkt) -> i32 {", 1).unwrap().1.arity, 1);
+        assert_eq!(fn_definition("fn f(m: HashMap<String, Vec<u8>>, cb: impl Fn(&str, char) -> bool) {", 1).unwrap().1.arity, 2);
+    }
+}
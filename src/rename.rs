@@ -0,0 +1,739 @@
+//! Rename Refactoring (`rustsp rename`)
+//!
+//! `rustsp rename old_name new_name file.rss` renames a function, struct,
+//! enum, constant or local binding. What `old_name` refers to is decided from
+//! the first-pass registries, not from the text:
+//!
+//! - functions, structs, enums and constants are renamed file-wide
+//! - bindings are renamed inside every function that declares them (as a
+//!   parameter, an assignment target or a `for` variable)
+//!
+//! Occurrences inside string literals and comments are never touched, and
+//! neither are field names (`p.x`, `Point { x = 1 }`) or method calls.
+//!
+//! A rename is rejected when `new_name` is not a usable identifier or is
+//! already used where the renamed symbol is visible - the result would
+//! silently change meaning. A file that includes module files with `mod`
+//! ([`crate::modules`]) is renamed as the whole program: the symbol is
+//! renamed in every file, `util::log` included. The same resolution, run
+//! over a whole directory, feeds the symbol index ([`crate::symbol_index`]).
+
+use crate::first_pass::run_first_pass;
+use crate::ident::{is_rust_keyword, is_valid_identifier};
+use crate::modules::{ModuleFile, Program};
+use crate::variable::VariableTracker;
+
+/// What the renamed name refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Function,
+    Struct,
+    Enum,
+    Constant,
+    Binding,
+}
+
+impl SymbolKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SymbolKind::Function => "function",
+            SymbolKind::Struct => "struct",
+            SymbolKind::Enum => "enum",
+            SymbolKind::Constant => "constant",
+            SymbolKind::Binding => "binding",
+        }
+    }
+
+    /// Inverse of [`SymbolKind::as_str`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        [SymbolKind::Function, SymbolKind::Struct, SymbolKind::Enum, SymbolKind::Constant, SymbolKind::Binding]
+            .into_iter()
+            .find(|kind| kind.as_str() == name)
+    }
+}
+
+/// Result of a successful rename
+#[derive(Debug, Clone)]
+pub struct RenameOutcome {
+    pub kind: SymbolKind,
+    /// The rewritten source
+    pub source: String,
+    /// Number of occurrences replaced
+    pub occurrences: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LexemeKind {
+    Ident,
+    Punct(char),
+}
+
+/// An identifier or punctuation character outside strings and comments
+#[derive(Debug, Clone, Copy)]
+struct Lexeme {
+    kind: LexemeKind,
+    start: usize,
+    end: usize,
+    line: usize,
+}
+
+/// What a `{` opened
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BraceKind {
+    /// `struct Name {` / `enum Name {` - field and variant declarations
+    Definition,
+    /// `Name { ... }` - struct literal or struct pattern
+    Literal,
+    Block,
+}
+
+/// Rename `old` to `new` in `source`
+pub fn rename_symbol(source: &str, old: &str, new: &str) -> Result<RenameOutcome, String> {
+    if !is_valid_identifier(new) || is_rust_keyword(new) {
+        return Err(format!("`{}` is not a valid identifier", new));
+    }
+    if old == new {
+        return Err(format!("`{}` is already named `{}`", old, new));
+    }
+
+    let lexemes = scan(source);
+    let roles = classify_positions(source, &lexemes);
+    let functions = function_spans(source, &lexemes);
+    let registries = registries(source);
+    let kind = symbol_kind(source, &lexemes, &roles, &functions, &registries, old)?;
+
+    // Occurrences that refer to the symbol, and the range they live in
+    let (targets, ranges): (Vec<usize>, Vec<(usize, usize)>) = match kind {
+        SymbolKind::Binding => {
+            let declaring: Vec<&FunctionSpan> = functions.iter()
+                .filter(|f| f.declares(source, &lexemes, &roles, old))
+                .collect();
+            let ranges = declaring.iter().map(|f| (f.start, f.end)).collect();
+            let targets = declaring.iter()
+                .flat_map(|f| (f.start..f.end).filter(|&i| roles[i] == Role::Plain && text(source, &lexemes[i]) == old))
+                .collect();
+            (targets, ranges)
+        }
+        _ => {
+            let targets = (0..lexemes.len())
+                .filter(|&i| matches!(roles[i], Role::Plain | Role::Definition) && text(source, &lexemes[i]) == old)
+                .collect();
+            (targets, vec![(0, lexemes.len())])
+        }
+    };
+
+    if kind == SymbolKind::Binding {
+        if let Some(i) = ranges.iter()
+            .flat_map(|&(start, end)| start..end)
+            .find(|&i| roles[i] == Role::Shorthand && text(source, &lexemes[i]) == old)
+        {
+            return Err(format!(
+                "`{}` is used as struct field shorthand at line {}; write the field explicitly first",
+                old, lexemes[i].line
+            ));
+        }
+    }
+
+    // Collisions: `new` already visible where the symbol is
+    if registries.contains(new) {
+        return Err(format!("`{}` is already defined in this file", new));
+    }
+    if let Some(i) = ranges.iter()
+        .flat_map(|&(start, end)| start..end)
+        .find(|&i| roles[i] != Role::Member && roles[i] != Role::Shorthand && text(source, &lexemes[i]) == new)
+    {
+        return Err(format!("`{}` is already used at line {}", new, lexemes[i].line));
+    }
+
+    let mut out = source.to_string();
+    for &i in targets.iter().rev() {
+        out.replace_range(lexemes[i].start..lexemes[i].end, new);
+    }
+    Ok(RenameOutcome { kind, source: out, occurrences: targets.len() })
+}
+
+/// [`rename_symbol`] over a program and every module file its `mod`
+/// declarations include. The outcome's source is the whole program; the
+/// files the rename changed come back with their new source.
+pub fn rename_in_program(program: &Program, old: &str, new: &str) -> Result<(RenameOutcome, Vec<ModuleFile>), String> {
+    let outcome = rename_symbol(&program.source, old, new)?;
+    let changed = program.map.files.iter()
+        .zip(program.map.split(&outcome.source))
+        .filter(|(file, source)| file.source != *source)
+        .map(|(file, source)| ModuleFile { source, ..file.clone() })
+        .collect();
+    Ok((outcome, changed))
+}
+
+//=============================================================================
+// SYMBOL COLLECTION (see [`crate::symbol_index`])
+//=============================================================================
+
+/// Every symbol a file defines, resolved the same way `rename_symbol`
+/// resolves one name. Offsets are byte offsets of identifier starts.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FileSymbols {
+    pub symbols: Vec<CollectedSymbol>,
+    /// Identifiers not resolved in this file (items defined elsewhere),
+    /// by name
+    pub unresolved: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CollectedSymbol {
+    pub kind: SymbolKind,
+    pub name: String,
+    /// Function that declares a binding
+    pub scope: Option<String>,
+    pub definition: usize,
+    /// Other occurrences, in source order
+    pub references: Vec<usize>,
+}
+
+pub(crate) fn collect_symbols(source: &str) -> FileSymbols {
+    let lexemes = scan(source);
+    let roles = classify_positions(source, &lexemes);
+    let functions = function_spans(source, &lexemes);
+    let registries = registries(source);
+    let is_reference = |i: usize| lexemes[i].kind == LexemeKind::Ident && matches!(roles[i], Role::Plain | Role::Definition);
+    let mut resolved = vec![false; lexemes.len()];
+    let mut symbols = Vec::new();
+
+    // Items: defined after their keyword, referenced file-wide
+    for i in 0..lexemes.len() {
+        let name = text(source, &lexemes[i]);
+        let keyword = (0..i).rev().map(|p| text(source, &lexemes[p])).find(|&t| t != "mut");
+        let kind = match keyword {
+            Some("fn") if registries.functions.get(name).is_some() => SymbolKind::Function,
+            Some("struct") if registries.structs.is_struct(name) => SymbolKind::Struct,
+            Some("enum") if registries.enums.is_enum(name) => SymbolKind::Enum,
+            Some("const" | "static") if registries.constants.iter().any(|c| c == name) => SymbolKind::Constant,
+            _ => continue,
+        };
+        if lexemes[i].kind != LexemeKind::Ident || symbols.iter().any(|s: &CollectedSymbol| s.name == name) {
+            continue;
+        }
+        let occurrences: Vec<usize> = (0..lexemes.len())
+            .filter(|&j| is_reference(j) && text(source, &lexemes[j]) == name)
+            .collect();
+        for &j in &occurrences {
+            resolved[j] = true;
+        }
+        resolved[i] = true;
+        symbols.push(CollectedSymbol {
+            kind,
+            name: name.to_string(),
+            scope: None,
+            definition: lexemes[i].start,
+            references: occurrences.into_iter().filter(|&j| j != i).map(|j| lexemes[j].start).collect(),
+        });
+    }
+
+    // Bindings: declared and used inside one function
+    for function in &functions {
+        let Some(fn_name) = lexemes.get(function.start + 1).map(|l| text(source, l)) else { continue };
+        let mut seen: Vec<&str> = Vec::new();
+        for i in function.params.0..function.end {
+            let name = text(source, &lexemes[i]);
+            if lexemes[i].kind != LexemeKind::Ident
+                || !matches!(roles[i], Role::Plain | Role::Shorthand)
+                || resolved[i]
+                || seen.contains(&name)
+                || is_rust_keyword(name)
+                || registries.contains(name)
+            {
+                continue;
+            }
+            seen.push(name);
+            if !function.declares(source, &lexemes, &roles, name) {
+                continue;
+            }
+            let occurrences: Vec<usize> = (i..function.end)
+                .filter(|&j| matches!(roles[j], Role::Plain | Role::Shorthand) && text(source, &lexemes[j]) == name)
+                .collect();
+            for &j in &occurrences {
+                resolved[j] = true;
+            }
+            symbols.push(CollectedSymbol {
+                kind: SymbolKind::Binding,
+                name: name.to_string(),
+                scope: Some(fn_name.to_string()),
+                definition: lexemes[i].start,
+                references: occurrences[1..].iter().map(|&j| lexemes[j].start).collect(),
+            });
+        }
+    }
+
+    let unresolved = (0..lexemes.len())
+        .filter(|&i| is_reference(i) && !resolved[i] && !is_rust_keyword(text(source, &lexemes[i])))
+        .map(|i| (text(source, &lexemes[i]).to_string(), lexemes[i].start))
+        .collect();
+    FileSymbols { symbols, unresolved }
+}
+
+//=============================================================================
+// SYMBOL LOOKUP
+//=============================================================================
+
+/// Item names known to the first pass, plus top-level constants
+struct Registries {
+    functions: crate::function::FunctionRegistry,
+    structs: crate::struct_def::StructRegistry,
+    enums: crate::enum_def::EnumRegistry,
+    constants: Vec<String>,
+}
+
+impl Registries {
+    fn contains(&self, name: &str) -> bool {
+        self.functions.get(name).is_some()
+            || self.structs.is_struct(name)
+            || self.enums.is_enum(name)
+            || self.constants.iter().any(|c| c == name)
+    }
+}
+
+fn registries(source: &str) -> Registries {
+    let lines: Vec<&str> = source.lines().collect();
+    let result = run_first_pass(&lines, &mut VariableTracker::new());
+    let constants = lines.iter()
+        .filter_map(|line| {
+            let trimmed = line.trim_start().strip_prefix("pub ").unwrap_or(line.trim_start());
+            let rest = trimmed.strip_prefix("const ").or_else(|| trimmed.strip_prefix("static "))?;
+            let rest = rest.strip_prefix("mut ").unwrap_or(rest);
+            let end = rest.find(|c: char| !crate::ident::is_ident_continue(c)).unwrap_or(rest.len());
+            Some(rest[..end].to_string())
+        })
+        .collect();
+    Registries {
+        functions: result.fn_registry,
+        structs: result.struct_registry,
+        enums: result.enum_registry,
+        constants,
+    }
+}
+
+fn symbol_kind(
+    source: &str,
+    lexemes: &[Lexeme],
+    roles: &[Role],
+    functions: &[FunctionSpan],
+    registries: &Registries,
+    old: &str,
+) -> Result<SymbolKind, String> {
+    let item = if registries.functions.get(old).is_some() {
+        Some(SymbolKind::Function)
+    } else if registries.structs.is_struct(old) {
+        Some(SymbolKind::Struct)
+    } else if registries.enums.is_enum(old) {
+        Some(SymbolKind::Enum)
+    } else if registries.constants.iter().any(|c| c == old) {
+        Some(SymbolKind::Constant)
+    } else {
+        None
+    };
+    let is_binding = functions.iter().any(|f| f.declares(source, lexemes, roles, old));
+
+    match (item, is_binding) {
+        (Some(kind), false) => Ok(kind),
+        (None, true) => Ok(SymbolKind::Binding),
+        (Some(kind), true) => Err(format!(
+            "`{}` is both a {} and a local binding; rename one of them by hand first",
+            old, kind.as_str()
+        )),
+        (None, false) => Err(format!("no function, type, constant or binding named `{}`", old)),
+    }
+}
+
+//=============================================================================
+// FUNCTION SPANS
+//=============================================================================
+
+/// Lexeme range of a function (signature through body)
+struct FunctionSpan {
+    start: usize,
+    end: usize,
+    /// Lexeme range of the parameter list
+    params: (usize, usize),
+}
+
+impl FunctionSpan {
+    /// Is `name` a parameter, assignment target, `for` variable or struct
+    /// pattern binding here?
+    fn declares(&self, source: &str, lexemes: &[Lexeme], roles: &[Role], name: &str) -> bool {
+        let is_name = |i: usize| lexemes[i].kind == LexemeKind::Ident && text(source, &lexemes[i]) == name;
+        // Punctuation at `i` written directly after the lexeme before it
+        let joined = |i: usize| lexemes.get(i).filter(|l| l.start == lexemes[i - 1].end).map(|l| l.kind);
+
+        // Parameters: first identifier of each comma-separated entry
+        let (p_start, p_end) = self.params;
+        let mut expect_name = true;
+        for (i, lexeme) in lexemes.iter().enumerate().take(p_end).skip(p_start) {
+            match lexeme.kind {
+                LexemeKind::Punct(',') => expect_name = true,
+                LexemeKind::Ident if expect_name && text(source, lexeme) != "mut" => {
+                    if is_name(i) {
+                        return true;
+                    }
+                    expect_name = false;
+                }
+                _ => {}
+            }
+        }
+
+        (self.params.1..self.end).any(|i| {
+            if !is_name(i) || roles[i] == Role::Member {
+                return false;
+            }
+            if roles[i] == Role::Shorthand {
+                return true;
+            }
+            let prev = i.checked_sub(1).map(|p| text(source, &lexemes[p]));
+            // `x = v` but not `x == v` / `x => v`
+            let assigned = lexemes.get(i + 1).is_some_and(|n| n.kind == LexemeKind::Punct('='))
+                && !matches!(joined(i + 2), Some(LexemeKind::Punct('=' | '>')));
+            // `x Type = v`
+            let annotated = lexemes.get(i + 1).is_some_and(|n| n.kind == LexemeKind::Ident && n.line == lexemes[i].line);
+            let starts_statement = i == 0
+                || lexemes[i - 1].line < lexemes[i].line
+                || matches!(prev, Some("mut" | "outer"));
+            (starts_statement && (assigned || annotated)) || prev == Some("for")
+        })
+    }
+}
+
+fn function_spans(source: &str, lexemes: &[Lexeme]) -> Vec<FunctionSpan> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < lexemes.len() {
+        if lexemes[i].kind != LexemeKind::Ident || text(source, &lexemes[i]) != "fn" {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        // Parameter list
+        let Some(open) = (i..lexemes.len()).find(|&j| lexemes[j].kind == LexemeKind::Punct('(')) else { break };
+        let Some(close) = matching(lexemes, open, '(', ')') else { break };
+        // Body: `{ ... }` or `= expr` to the end of the line
+        let mut end = close + 1;
+        let mut depth = 0i32;
+        while end < lexemes.len() {
+            match lexemes[end].kind {
+                LexemeKind::Punct('(') | LexemeKind::Punct('[') => depth += 1,
+                LexemeKind::Punct(')') | LexemeKind::Punct(']') => depth -= 1,
+                LexemeKind::Punct('{') if depth == 0 => {
+                    end = matching(lexemes, end, '{', '}').map_or(lexemes.len(), |e| e + 1);
+                    break;
+                }
+                LexemeKind::Punct('=') if depth == 0 => {
+                    let line = lexemes[end].line;
+                    while end < lexemes.len() && lexemes[end].line == line {
+                        end += 1;
+                    }
+                    break;
+                }
+                _ => {}
+            }
+            end += 1;
+        }
+        spans.push(FunctionSpan { start, end: end.min(lexemes.len()), params: (open + 1, close) });
+        i = close + 1;
+    }
+    spans
+}
+
+fn matching(lexemes: &[Lexeme], open: usize, open_c: char, close_c: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (j, lexeme) in lexemes.iter().enumerate().skip(open) {
+        if lexeme.kind == LexemeKind::Punct(open_c) {
+            depth += 1;
+        } else if lexeme.kind == LexemeKind::Punct(close_c) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(j);
+            }
+        }
+    }
+    None
+}
+
+//=============================================================================
+// POSITION ROLES
+//=============================================================================
+
+/// How an identifier occurrence relates to the names being renamed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    /// Ordinary reference
+    Plain,
+    /// Inside a `struct`/`enum` body but not a field or variant name (types)
+    Definition,
+    /// Field, variant or method name, `.member` or `path::member`
+    Member,
+    /// `Point { x, y }` shorthand - both a field and a binding
+    Shorthand,
+    /// Punctuation
+    Punct,
+}
+
+fn classify_positions(source: &str, lexemes: &[Lexeme]) -> Vec<Role> {
+    let mut roles = Vec::with_capacity(lexemes.len());
+    let mut braces: Vec<BraceKind> = Vec::new();
+    // `util::log` names `log` itself when `util` is a module of the program
+    let modules: Vec<&str> = lexemes.windows(2)
+        .filter(|pair| pair[1].kind == LexemeKind::Ident && text(source, &pair[0]) == "mod")
+        .map(|pair| text(source, &pair[1]))
+        .collect();
+
+    for (i, lexeme) in lexemes.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|p| lexemes[p]);
+        let next = lexemes.get(i + 1).copied();
+        match lexeme.kind {
+            LexemeKind::Punct('{') => {
+                // Struct variants inside an enum body are definitions too
+                let kind = match braces.last() {
+                    Some(BraceKind::Definition) => BraceKind::Definition,
+                    _ => brace_kind(source, lexemes, i),
+                };
+                braces.push(kind);
+                roles.push(Role::Punct);
+            }
+            LexemeKind::Punct('}') => {
+                braces.pop();
+                roles.push(Role::Punct);
+            }
+            LexemeKind::Punct(_) => roles.push(Role::Punct),
+            LexemeKind::Ident => {
+                let after_dot = prev.is_some_and(|p| p.kind == LexemeKind::Punct('.'));
+                let after_path = i >= 2
+                    && lexemes[i - 1].kind == LexemeKind::Punct(':')
+                    && lexemes[i - 2].kind == LexemeKind::Punct(':')
+                    && lexemes.get(i.wrapping_sub(3)).map(|l| text(source, l))
+                        .is_none_or(|segment| !matches!(segment, "crate" | "self" | "super") && !modules.contains(&segment));
+                let first_on_line = prev.is_none_or(|p| p.line < lexeme.line);
+                let field_position = prev.is_some_and(|p| matches!(p.kind, LexemeKind::Punct('{') | LexemeKind::Punct(',')))
+                    || first_on_line;
+
+                let role = if after_dot || after_path {
+                    Role::Member
+                } else {
+                    match braces.last() {
+                        Some(BraceKind::Definition) if first_on_line || field_position => Role::Member,
+                        Some(BraceKind::Definition) => Role::Definition,
+                        Some(BraceKind::Literal) if field_position => match next.map(|n| n.kind) {
+                            Some(LexemeKind::Punct('=')) | Some(LexemeKind::Punct(':')) => Role::Member,
+                            Some(LexemeKind::Punct(',')) | Some(LexemeKind::Punct('}')) => Role::Shorthand,
+                            _ => Role::Plain,
+                        },
+                        _ => Role::Plain,
+                    }
+                };
+                roles.push(role);
+            }
+        }
+    }
+    roles
+}
+
+/// Decide what the `{` at `open` starts from the tokens before it
+fn brace_kind(source: &str, lexemes: &[Lexeme], open: usize) -> BraceKind {
+    // Only the line holding the `{` decides (multi-line signatures close
+    // with `) Type {`, which is a block)
+    let line = lexemes[open].line;
+    let mut j = open;
+    while j > 0
+        && lexemes[j - 1].line == line
+        && !matches!(lexemes[j - 1].kind, LexemeKind::Punct('{') | LexemeKind::Punct('}') | LexemeKind::Punct(';'))
+    {
+        j -= 1;
+    }
+    let first = (j..open)
+        .find(|&k| lexemes[k].kind == LexemeKind::Ident)
+        .map(|k| text(source, &lexemes[k]));
+    match first {
+        Some("struct" | "enum" | "union") => return BraceKind::Definition,
+        Some("pub") if (j..open).any(|k| matches!(text(source, &lexemes[k]), "struct" | "enum")) => {
+            return BraceKind::Definition;
+        }
+        _ => {}
+    }
+    let is_block_keyword = (j..open).any(|k| {
+        lexemes[k].kind == LexemeKind::Ident
+            && matches!(text(source, &lexemes[k]), "fn" | "impl" | "trait" | "mod" | "if" | "while" | "for" | "match" | "loop" | "else" | "unsafe")
+    });
+    let prev_is_type = open > 0
+        && lexemes[open - 1].kind == LexemeKind::Ident
+        && text(source, &lexemes[open - 1]).starts_with(|c: char| c.is_uppercase());
+    if prev_is_type && !is_block_keyword {
+        BraceKind::Literal
+    } else {
+        BraceKind::Block
+    }
+}
+
+//=============================================================================
+// SCANNER
+//=============================================================================
+
+fn text<'a>(source: &'a str, lexeme: &Lexeme) -> &'a str {
+    &source[lexeme.start..lexeme.end]
+}
+
+/// Identifiers and punctuation outside string/char literals and comments
+fn scan(source: &str) -> Vec<Lexeme> {
+    let bytes = source.as_bytes();
+    let mut lexemes = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < source.len() {
+        let c = source[i..].chars().next().unwrap_or(' ');
+        let rest = &source[i..];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            let len = rest.find("*/").map_or(rest.len(), |e| e + 2);
+            line += rest[..len].matches('\n').count();
+            i += len;
+        } else if let Some(len) = raw_string_len(rest) {
+            line += rest[..len].matches('\n').count();
+            i += len;
+        } else if c == '"' {
+            let mut j = i + 1;
+            while j < bytes.len() && bytes[j] != b'"' {
+                if bytes[j] == b'\\' {
+                    j += 1;
+                }
+                j += 1;
+            }
+            let end = (j + 1).min(source.len());
+            line += source[i..end].matches('\n').count();
+            i = end;
+        } else if c == '\'' {
+            // Char literal, or a lifetime (`'a` not closed right after)
+            i += char_literal_len(rest).unwrap_or(1);
+        } else if crate::ident::is_ident_start(c) {
+            let len = rest.find(|ch: char| !crate::ident::is_ident_continue(ch)).unwrap_or(rest.len());
+            lexemes.push(Lexeme { kind: LexemeKind::Ident, start: i, end: i + len, line });
+            i += len;
+        } else if c.is_ascii_digit() {
+            // Numbers (with suffixes like `5u8`) are not identifiers
+            i += rest.find(|ch: char| !ch.is_alphanumeric() && ch != '_').unwrap_or(rest.len());
+        } else {
+            if !c.is_whitespace() {
+                lexemes.push(Lexeme { kind: LexemeKind::Punct(c), start: i, end: i + c.len_utf8(), line });
+            }
+            i += c.len_utf8();
+        }
+    }
+    lexemes
+}
+
+/// Length of a raw string literal (`r"..."`, `r#"..."#`) at the start of `rest`
+fn raw_string_len(rest: &str) -> Option<usize> {
+    let after_r = rest.strip_prefix('r').or_else(|| rest.strip_prefix("br"))?;
+    let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+    let body = after_r[hashes..].strip_prefix('"')?;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let end = body.find(&closing)?;
+    Some(rest.len() - body.len() + end + closing.len())
+}
+
+fn char_literal_len(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    let (_, c) = chars.next()?;
+    if c == '\\' {
+        let close = rest[2..].find('\'')?;
+        return Some(close + 3);
+    }
+    let (pos, close) = chars.next()?;
+    (close == '\'').then_some(pos + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"struct Point {
+    x i32
+    y i32
+}
+
+fn shift(p Point, dx i32) Point {
+    // shift moves a point
+    moved = p.x + dx
+    Point { x = moved, y = p.y }
+}
+
+fn main() effects(io) {
+    dx = 3
+    p = shift(Point { x = 1, y = 2 }, dx)
+    println!("shift {}", p.x)
+}
+"#;
+
+    #[test]
+    fn test_rename_function_skips_strings_and_comments() {
+        let out = rename_symbol(SOURCE, "shift", "translate").unwrap();
+        assert_eq!(out.kind, SymbolKind::Function);
+        assert_eq!(out.occurrences, 2);
+        assert!(out.source.contains("fn translate(p Point"));
+        assert!(out.source.contains("p = translate(Point"));
+        assert!(out.source.contains("// shift moves a point"));
+        assert!(out.source.contains("\"shift {}\""));
+    }
+
+    #[test]
+    fn test_rename_across_module_files() {
+        let read = |path: &std::path::Path| match path.to_str() {
+            Some("app/util.rss") => Ok("pub fn log(x i32) effects(io) {\n    println!(\"log {}\", x)\n}\n".to_string()),
+            _ => Err(std::io::Error::from(std::io::ErrorKind::NotFound)),
+        };
+        let main = "mod util\n\nfn main() effects(io) {\n    util::log(1)\n}\n";
+        let program = Program::load_with(std::path::Path::new("app/main.rss"), main, &read).unwrap();
+        let (outcome, changed) = rename_in_program(&program, "log", "trace").unwrap();
+        assert_eq!(outcome.occurrences, 2);
+        assert_eq!(changed.len(), 2);
+        assert_eq!(changed[0].source, "mod util\n\nfn main() effects(io) {\n    util::trace(1)\n}\n");
+        assert_eq!(changed[1].path, std::path::Path::new("app/util.rss"));
+        assert_eq!(changed[1].source, "pub fn trace(x i32) effects(io) {\n    println!(\"log {}\", x)\n}\n");
+    }
+
+    #[test]
+    fn test_rename_struct() {
+        let out = rename_symbol(SOURCE, "Point", "Vec2").unwrap();
+        assert_eq!(out.kind, SymbolKind::Struct);
+        assert_eq!(out.occurrences, 5);
+        assert!(out.source.contains("struct Vec2 {"));
+        assert!(out.source.contains("fn shift(p Vec2, dx i32) Vec2 {"));
+    }
+
+    #[test]
+    fn test_rename_binding_leaves_fields() {
+        let out = rename_symbol(SOURCE, "p", "pt").unwrap();
+        assert_eq!(out.kind, SymbolKind::Binding);
+        assert!(out.source.contains("moved = pt.x + dx"));
+        assert!(out.source.contains("Point { x = moved, y = pt.y }"));
+        assert!(out.source.contains("pt = shift(Point { x = 1, y = 2 }, dx)"));
+
+        let out = rename_symbol(SOURCE, "moved", "x").unwrap();
+        assert!(out.source.contains("x = p.x + dx"));
+        assert!(out.source.contains("Point { x = x, y = p.y }"));
+    }
+
+    #[test]
+    fn test_rename_rejects_collisions() {
+        assert!(rename_symbol(SOURCE, "shift", "main").unwrap_err().contains("already defined"));
+        assert!(rename_symbol(SOURCE, "dx", "p").unwrap_err().contains("already used"));
+        assert!(rename_symbol(SOURCE, "dx", "match").unwrap_err().contains("not a valid identifier"));
+        assert!(rename_symbol(SOURCE, "nothing", "x").is_err());
+    }
+
+    #[test]
+    fn test_rename_rejects_shorthand_field() {
+        let src = "fn f(x i32) Point {\n    Point { x, y = 0 }\n}\n";
+        assert!(rename_symbol(src, "x", "a").unwrap_err().contains("shorthand"));
+    }
+}
@@ -0,0 +1,138 @@
+//! Replay Files
+//!
+//! `--record session.rsprec` saves everything a run depends on — the
+//! input file's name and content, the command-line options and the
+//! compiler version — into one JSON file:
+//!
+//! ```text
+//! {
+//!   "format": "rustsp-replay",
+//!   "version": 1,
+//!   "compiler": "1.0.0",
+//!   "file": "src/main.rss",
+//!   "args": ["--emit-rs", "-o", "out.rs"],
+//!   "source": "fn main() {\n ..."
+//! }
+//! ```
+//!
+//! `rustsp replay session.rsprec [options]` runs the pipeline again with
+//! the recorded options (plus any given after the file) on the recorded
+//! source, read from the record rather than from disk, so diagnostics name
+//! the original file. A record is a complete reproduction to attach to an
+//! issue about mis-lowering.
+
+use crate::lowered_ir::{json_str, parse_json, Json};
+
+/// Value of the `"format"` field
+pub const REPLAY_FORMAT: &str = "rustsp-replay";
+/// Newest record version this compiler reads and the one it writes
+pub const REPLAY_VERSION: u64 = 1;
+
+/// One recorded run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Version of the compiler that made the record
+    pub compiler: String,
+    /// Input file as it was given
+    pub file: String,
+    /// Command-line arguments without the input file and `--record`
+    pub args: Vec<String>,
+    /// Content of the input file
+    pub source: String,
+}
+
+impl Recording {
+    /// Record a run of this compiler
+    pub fn new(file: &str, args: Vec<String>, source: &str) -> Self {
+        Recording {
+            compiler: env!("CARGO_PKG_VERSION").to_string(),
+            file: file.to_string(),
+            args,
+            source: source.to_string(),
+        }
+    }
+
+    /// Was the record made by a different compiler version?
+    pub fn is_foreign(&self) -> bool {
+        self.compiler != env!("CARGO_PKG_VERSION")
+    }
+
+    /// Serialize to the versioned JSON format
+    pub fn to_json(&self) -> String {
+        let args: Vec<String> = self.args.iter().map(|a| json_str(a)).collect();
+        format!(
+            "{{\n  \"format\": \"{}\",\n  \"version\": {},\n  \"compiler\": {},\n  \"file\": {},\n  \"args\": [{}],\n  \"source\": {}\n}}\n",
+            REPLAY_FORMAT, REPLAY_VERSION, json_str(&self.compiler), json_str(&self.file),
+            args.join(", "), json_str(&self.source)
+        )
+    }
+
+    /// Read a record written by [`Recording::to_json`]
+    pub fn from_json(json: &str) -> Result<Recording, String> {
+        let doc = parse_json(json)?;
+        let format = doc.get("format").and_then(Json::as_str).ok_or("missing \"format\"")?;
+        if format != REPLAY_FORMAT {
+            return Err(format!("unknown replay format \"{}\" (expected \"{}\")", format, REPLAY_FORMAT));
+        }
+        let version = doc.get("version").and_then(Json::as_u64).ok_or("missing \"version\"")?;
+        if version > REPLAY_VERSION {
+            return Err(format!("replay version {} is newer than this compiler supports ({})", version, REPLAY_VERSION));
+        }
+        let string = |key: &str| doc.get(key).and_then(Json::as_str).map(str::to_string).ok_or(format!("missing \"{}\"", key));
+        let args = doc.get("args").and_then(Json::as_array).ok_or("missing \"args\"")?
+            .iter()
+            .map(|a| a.as_str().map(str::to_string).ok_or("\"args\" must hold strings".to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Recording {
+            compiler: string("compiler")?,
+            file: string("file")?,
+            args,
+            source: string("source")?,
+        })
+    }
+}
+
+/// The arguments to record from a command line (`args[0]` is the program):
+/// everything but the input file at `input_index` and `--record <file>`
+pub fn recorded_args(args: &[String], input_index: Option<usize>) -> Vec<String> {
+    let mut recorded = Vec::new();
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--record" {
+            i += 2;
+            continue;
+        }
+        if Some(i) != input_index {
+            recorded.push(args[i].clone());
+        }
+        i += 1;
+    }
+    recorded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let recording = Recording::new("src/main.rss", vec!["--emit-rs".to_string(), "-o".to_string(), "out \"a\".rs".to_string()], "fn main() {\n\tx = \"hi\"\n}\n");
+        let json = recording.to_json();
+        assert!(json.contains("\"format\": \"rustsp-replay\""));
+        assert_eq!(Recording::from_json(&json), Ok(recording.clone()));
+        assert!(!recording.is_foreign());
+    }
+
+    #[test]
+    fn test_rejects_other_documents() {
+        assert!(Recording::from_json("{\"format\": \"rustsp-ir\", \"version\": 1}").unwrap_err().contains("unknown replay format"));
+        assert!(Recording::from_json("{\"format\": \"rustsp-replay\", \"version\": 9}").unwrap_err().contains("newer"));
+    }
+
+    #[test]
+    fn test_recorded_args() {
+        let args: Vec<String> = ["rustsp", "test", "--record", "s.rsprec", "main.rss", "-o", "main.rss.out", "-q"]
+            .iter().map(|a| a.to_string()).collect();
+        assert_eq!(recorded_args(&args, Some(4)), vec!["test", "-o", "main.rss.out", "-q"]);
+    }
+}
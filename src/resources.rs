@@ -0,0 +1,716 @@
+//! Resource Effects and Unclosed Handles (RSPL318)
+//!
+//! Opening a file or a socket is an effect of its own, `resource(kind)`,
+//! on top of the `io` the open performs. A function that opens one declares
+//! it, so the handles a function may hold show in its signature:
+//!
+//! ```text
+//! fn first_line(path String) effects(io, alloc, resource(file)) String {
+//!     file = File::open(path).unwrap()
+//!     reader = BufReader::new(file)      // `file` moves into the reader
+//!     ...
+//! }
+//! ```
+//!
+//! | Kind     | Opened by                                                  |
+//! |----------|------------------------------------------------------------|
+//! | `file`   | `File::open`, `File::create`, `OpenOptions::new()..open()` |
+//! | `socket` | `TcpStream::connect`, `TcpListener::bind`, `UdpSocket::bind`, `UnixStream::connect`, `UnixListener::bind` |
+//!
+//! `resource` does not propagate: a caller of `first_line` holds no handle.
+//! `main` may open resources without declaring them, like `io`.
+//!
+//! ## Unclosed handles
+//!
+//! Rust closes a handle when its owner goes out of scope, so a forgotten
+//! handle stays open until then, possibly for the rest of a long loop or
+//! server. Stage 1 warns when a binding holding an opened handle reaches a
+//! `return` or the end of its block without being closed:
+//!
+//! - `drop(file)`, `file.close()` or `sock.shutdown(..)` closes it
+//! - moving it closes it for this function: passing it by value
+//!   (`BufReader::new(file)`), returning it, or storing it in a struct,
+//!   tuple or another binding. `&file`, `file.read(..)` and macro
+//!   arguments only borrow it.
+//!
+//! The check is linear: closing a handle inside an `if` or `match` branch
+//! covers a `return` in that branch, but not the code after the branches.
+//! Close it after the branches, or in every branch that returns. Error
+//! paths through `?` are left to Rust's drop.
+//!
+//! A handle meant to live until the end of its scope is allowed with an
+//! `// allow(unclosed-resource)` comment on (or above) the line opening it,
+//! or on (or above) the signature of the enclosing function.
+//!
+//! [`find_unclosed_handles`] is the line-based check Stage 1 runs;
+//! [`unclosed_handles`] runs the same linear check over a HIR function.
+
+use crate::helpers::strip_inline_comment;
+use crate::hir::{BindingId, HirBlock, HirCallTarget, HirExpr, HirFnDef, HirStmt, Path};
+use crate::loop_exits::{function_name, mask_literals};
+
+/// Marker that silences RSPL318
+pub const ALLOW_UNCLOSED_RESOURCE: &str = "allow(unclosed-resource)";
+
+/// Kinds of resources `resource(kind)` may name
+pub const RESOURCE_KINDS: &[&str] = &["file", "socket"];
+
+/// Calls that open a handle, and the resource kind they open
+const OPENERS: &[(&str, &str)] = &[
+    ("File::open", "file"),
+    ("File::create", "file"),
+    ("File::create_new", "file"),
+    ("OpenOptions::new", "file"),
+    ("TcpStream::connect", "socket"),
+    ("TcpListener::bind", "socket"),
+    ("UdpSocket::bind", "socket"),
+    ("UnixStream::connect", "socket"),
+    ("UnixListener::bind", "socket"),
+];
+
+/// Methods that close the handle they are called on
+const CLOSING_METHODS: &[&str] = &["close", "shutdown"];
+
+/// Methods that pass the opened handle through: `File::open(p).unwrap()`
+const PASS_THROUGH_METHODS: &[&str] = &["unwrap", "expect"];
+
+/// The resource a line opens: its kind and the opening call as quoted in
+/// diagnostics (`File::open`)
+pub fn opened_resource(line: &str) -> Option<(&'static str, &'static str)> {
+    OPENERS.iter()
+        .filter_map(|&(call, kind)| opener_position(line, call).map(|pos| (pos, kind, call)))
+        .min_by_key(|&(pos, _, _)| pos)
+        .map(|(_, kind, call)| (kind, call))
+}
+
+/// Position of `call(` in `line`, not as the tail of a longer name
+/// (`MyFile::open(`); `fs::File::open(` matches
+fn opener_position(line: &str, call: &str) -> Option<usize> {
+    let pattern = format!("{}(", call);
+    line.match_indices(&pattern)
+        .map(|(pos, _)| pos)
+        .find(|&pos| !line[..pos].ends_with(crate::ident::is_ident_continue))
+}
+
+//=============================================================================
+// LINE-BASED CHECK
+//=============================================================================
+
+/// A handle that is still open on some path out of its scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnclosedHandle {
+    /// 1-based line the handle is opened on
+    pub line: usize,
+    pub binding: String,
+    pub kind: &'static str,
+    /// 1-based line of the `return` or closing brace it is still open at
+    pub leaked_at: usize,
+    /// Enclosing function, if any
+    pub function: Option<String>,
+}
+
+#[derive(Debug)]
+struct OpenHandle {
+    binding: String,
+    kind: &'static str,
+    line: usize,
+    /// Brace depth of the block the binding lives in
+    depth: usize,
+    /// Depth of the branch that closed the handle, while inside it
+    closed_in_branch: Option<usize>,
+}
+
+/// Find every handle binding in `source` that is not closed or moved on
+/// some path, and is not allowed
+pub fn find_unclosed_handles(source: &str) -> Vec<UnclosedHandle> {
+    let raw_lines: Vec<&str> = source.lines().collect();
+    let mut found = Vec::new();
+    let mut open: Vec<OpenHandle> = Vec::new();
+    // (name, allowed)
+    let mut function: Option<(String, bool)> = None;
+    // Open braces: true for blocks, false for struct literals
+    let mut braces: Vec<bool> = Vec::new();
+
+    for (idx, raw) in raw_lines.iter().enumerate() {
+        let code = mask_literals(&strip_inline_comment(raw));
+        let trimmed = code.trim();
+        let allowed = raw.contains(ALLOW_UNCLOSED_RESOURCE)
+            || (idx > 0 && raw_lines[idx - 1].trim_start().starts_with("//") && raw_lines[idx - 1].contains(ALLOW_UNCLOSED_RESOURCE));
+        let depth = block_depth(&braces);
+        let mut leak = |handle: OpenHandle, function: &Option<(String, bool)>| {
+            found.push(UnclosedHandle {
+                line: handle.line,
+                binding: handle.binding,
+                kind: handle.kind,
+                leaked_at: idx + 1,
+                function: function.as_ref().map(|(name, _)| name.clone()),
+            });
+        };
+
+        if trimmed.contains('{') {
+            if let Some(name) = function_name(trimmed) {
+                open.clear();
+                function = Some((name, allowed));
+            }
+        }
+
+        for handle in std::mem::take(&mut open) {
+            match closing_depth(trimmed, &handle.binding) {
+                Some(at) if depth + at <= handle.depth => {}
+                Some(at) => open.push(OpenHandle { closed_in_branch: Some(depth + at), ..handle }),
+                None => open.push(handle),
+            }
+        }
+        for at in return_depths(trimmed) {
+            for handle in std::mem::take(&mut open) {
+                if handle.closed_in_branch.is_some_and(|branch| depth + at >= branch) {
+                    open.push(handle);
+                } else {
+                    leak(handle, &function);
+                }
+            }
+        }
+
+        let function_allowed = function.as_ref().is_some_and(|(_, allowed)| *allowed);
+        let opened = opened_binding(trimmed).filter(|_| !allowed && !function_allowed && depth > 0);
+
+        // Branches end at the lowest depth the line reaches (`} else {`)
+        let mut low = depth;
+        for (pos, c) in trimmed.char_indices() {
+            match c {
+                '{' => braces.push(opens_block(&trimmed[..pos])),
+                '}' => {
+                    braces.pop();
+                    low = low.min(block_depth(&braces));
+                }
+                _ => {}
+            }
+        }
+        for handle in open.iter_mut() {
+            if handle.closed_in_branch.is_some_and(|branch| low < branch) {
+                handle.closed_in_branch = None;
+            }
+        }
+        for handle in std::mem::take(&mut open) {
+            if low < handle.depth {
+                leak(handle, &function);
+            } else {
+                open.push(handle);
+            }
+        }
+
+        if let Some((binding, kind)) = opened {
+            open.push(OpenHandle { binding, kind, line: idx + 1, depth, closed_in_branch: None });
+        }
+        if braces.is_empty() {
+            function = None;
+        }
+    }
+
+    found
+}
+
+/// `file = File::open(path)?` → (`file`, `file`): a binding holding the
+/// handle itself, not something computed from it
+fn opened_binding(trimmed: &str) -> Option<(String, &'static str)> {
+    let (kind, call) = opened_resource(trimmed)?;
+    let eq = assignment_eq(trimmed)?;
+    let lhs = trimmed[..eq].trim();
+    let lhs = lhs.strip_prefix("let ").unwrap_or(lhs).trim_start();
+    let lhs = lhs.strip_prefix("mut ").unwrap_or(lhs).trim_start();
+    let name = lhs.split(':').next().unwrap_or_default().trim();
+    if name.is_empty() || name == "_" || !name.chars().all(crate::ident::is_ident_continue) {
+        return None;
+    }
+    let rhs = trimmed[eq + 1..].trim();
+    let start = opener_position(rhs, call)?;
+    if !rhs[..start].chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':') {
+        return None;
+    }
+    let mut rest = after_parens(&rhs[start + call.len()..])?;
+    if call == "OpenOptions::new" {
+        // Builder calls up to and including `.open(..)`
+        rest = after_parens(&rest[rest.find(".open(")? + ".open".len()..])?;
+    }
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix('?') {
+            rest = r;
+        } else if let Some(method) = PASS_THROUGH_METHODS.iter().find(|m| rest.starts_with(&format!(".{}(", m))) {
+            rest = after_parens(&rest[method.len() + 1..])?;
+        } else {
+            break;
+        }
+    }
+    rest.is_empty().then(|| (name.to_string(), kind))
+}
+
+/// Position of the `=` of an assignment or `let` (not `==`, `<=`, `+=`, `=>`)
+fn assignment_eq(code: &str) -> Option<usize> {
+    let bytes = code.as_bytes();
+    (0..bytes.len()).find(|&i| {
+        bytes[i] == b'='
+            && bytes.get(i + 1).is_none_or(|c| *c != b'=' && *c != b'>')
+            && (i == 0 || !b"=!<>+-*/%&|^".contains(&bytes[i - 1]))
+    })
+}
+
+/// `(a, (b)).rest` → `.rest`
+fn after_parens(s: &str) -> Option<&str> {
+    if !s.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0usize;
+    for (pos, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&s[pos + 1..]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Brace depth, relative to the start of the line, of the first place
+/// `line` closes or moves `binding`
+fn closing_depth(line: &str, binding: &str) -> Option<usize> {
+    word_positions(line, binding).into_iter()
+        .find(|&pos| closes_or_moves(line, pos, binding))
+        .map(|pos| relative_depth(&line[..pos]))
+}
+
+/// Is the use of `binding` at `pos` a close or a move?
+fn closes_or_moves(line: &str, pos: usize, binding: &str) -> bool {
+    let before = line[..pos].trim_end();
+    let after = line[pos + binding.len()..].trim_start();
+    if let Some(method) = after.strip_prefix('.') {
+        return CLOSING_METHODS.iter().any(|m| method.starts_with(&format!("{}(", m)));
+    }
+    let borrowed = before.ends_with('&') || before.strip_suffix("mut").is_some_and(|b| b.trim_end().ends_with('&'));
+    let assigned = assignment_eq(after) == Some(0);
+    let field_name = after.starts_with(':') && !after.starts_with("::");
+    !borrowed && !assigned && !field_name && !after.starts_with('[') && !after.starts_with("::")
+        && !in_macro_arguments(before)
+}
+
+/// Is the end of `before` inside the arguments of a macro call
+/// (`println!(..`, or `println(..` written without `!`)?
+fn in_macro_arguments(before: &str) -> bool {
+    let mut unclosed = Vec::new();
+    for (pos, c) in before.char_indices() {
+        match c {
+            '(' => unclosed.push(pos),
+            ')' => {
+                unclosed.pop();
+            }
+            _ => {}
+        }
+    }
+    unclosed.iter().any(|&open| {
+        let callee = before[..open].strip_suffix('!').unwrap_or(&before[..open]);
+        let name_start = callee.rfind(|c: char| !crate::ident::is_ident_continue(c)).map_or(0, |p| p + 1);
+        before[..open].ends_with('!') || crate::macro_shadowing::is_macro_name(&callee[name_start..])
+    })
+}
+
+/// Relative brace depths of the `return`s on a line
+fn return_depths(line: &str) -> Vec<usize> {
+    word_positions(line, "return").into_iter()
+        .map(|pos| relative_depth(&line[..pos]))
+        .collect()
+}
+
+/// Blocks opened and not closed in `prefix`
+fn relative_depth(prefix: &str) -> usize {
+    let mut braces = Vec::new();
+    for (pos, c) in prefix.char_indices() {
+        match c {
+            '{' => braces.push(opens_block(&prefix[..pos])),
+            '}' => {
+                braces.pop();
+            }
+            _ => {}
+        }
+    }
+    block_depth(&braces)
+}
+
+fn block_depth(braces: &[bool]) -> usize {
+    braces.iter().filter(|&&block| block).count()
+}
+
+/// Does a `{` after `prefix` open a block rather than a struct literal
+/// (`Conn { stream: s }`)? Items and `if`/`while`/`match` headers cannot
+/// end in a struct literal.
+fn opens_block(prefix: &str) -> bool {
+    let before = prefix.trim_end();
+    let name_start = before.rfind(|c: char| !crate::ident::is_ident_continue(c) && c != ':').map_or(0, |p| p + 1);
+    let is_type_name = before[name_start..].rsplit("::").next()
+        .and_then(|last| last.chars().next())
+        .is_some_and(|c| c.is_uppercase());
+    let head = prefix.trim_start().trim_start_matches('}').trim_start();
+    !is_type_name
+        || function_name(head).is_some()
+        || ["if ", "while ", "match ", "for ", "else ", "impl", "struct ", "enum ", "trait ", "mod "]
+            .iter()
+            .any(|keyword| head.starts_with(keyword))
+}
+
+/// Positions of `word` as a whole identifier, not a field or method
+fn word_positions(code: &str, word: &str) -> Vec<usize> {
+    code.match_indices(word)
+        .map(|(pos, _)| pos)
+        .filter(|&pos| {
+            !code[..pos].ends_with(crate::ident::is_ident_continue)
+                && !code[..pos].ends_with('.')
+                && !code[pos + word.len()..].starts_with(crate::ident::is_ident_continue)
+        })
+        .collect()
+}
+
+//=============================================================================
+// HIR CHECK
+//=============================================================================
+
+/// Handles `func` opens with a `let` in its body that reach a `return` or
+/// the end of the body without being closed or moved
+pub fn unclosed_handles(func: &HirFnDef) -> Vec<BindingId> {
+    let body = &func.body.node;
+    let mut leaked = Vec::new();
+    let mut open: Vec<BindingId> = Vec::new();
+    for stmt in &body.stmts {
+        open.retain(|&id| !stmt_moves(&stmt.node, id));
+        open.retain(|&id| {
+            let leaks = stmt_returns(&stmt.node, id);
+            if leaks {
+                leaked.push(id);
+            }
+            !leaks
+        });
+        if let HirStmt::Let { binding, init: Some(init), .. } = &stmt.node {
+            if opens_resource(&init.node) {
+                open.push(*binding);
+            }
+        }
+    }
+    if let Some(expr) = &body.expr {
+        open.retain(|&id| !moves(&expr.node, id));
+        open.retain(|&id| {
+            let leaks = returns_without(&expr.node, id);
+            if leaks {
+                leaked.push(id);
+            }
+            !leaks
+        });
+    }
+    leaked.extend(open);
+    leaked
+}
+
+/// `File::open(p)`, `File::open(p).unwrap()`, `OpenOptions::new()..open(p)`
+fn opens_resource(expr: &HirExpr) -> bool {
+    match expr {
+        HirExpr::Call { target: HirCallTarget::Function(path), .. } => is_opener(path),
+        HirExpr::Call { target: HirCallTarget::Method { receiver, method }, .. } => {
+            (PASS_THROUGH_METHODS.contains(&method.name.as_str()) || method.name == "open" || chained_builder(receiver))
+                && opens_resource(&receiver.node)
+        }
+        _ => false,
+    }
+}
+
+/// Is `receiver` part of an `OpenOptions::new()` builder chain?
+fn chained_builder(receiver: &crate::hir::Spanned<HirExpr>) -> bool {
+    match &receiver.node {
+        HirExpr::Call { target: HirCallTarget::Function(path), .. } => path.to_string().ends_with("OpenOptions::new"),
+        HirExpr::Call { target: HirCallTarget::Method { receiver, .. }, .. } => chained_builder(receiver),
+        _ => false,
+    }
+}
+
+fn is_opener(path: &Path) -> bool {
+    let path = path.to_string();
+    OPENERS.iter().any(|(call, _)| path == *call || path.ends_with(&format!("::{}", call)))
+}
+
+fn is_var(expr: &HirExpr, id: BindingId) -> bool {
+    matches!(expr, HirExpr::Var(v) if *v == id)
+}
+
+fn stmt_moves(stmt: &HirStmt, id: BindingId) -> bool {
+    match stmt {
+        HirStmt::Let { init: Some(init), .. } => is_var(&init.node, id) || moves(&init.node, id),
+        HirStmt::Let { init: None, .. } => false,
+        HirStmt::Expr(expr) => moves(&expr.node, id),
+        HirStmt::Assign { target, value } => is_var(&value.node, id) || moves(&target.node, id) || moves(&value.node, id),
+        HirStmt::While { condition, body } => moves(&condition.node, id) || block_moves(&body.node, id),
+        HirStmt::For { iter, body, .. } => is_var(&iter.node, id) || moves(&iter.node, id) || block_moves(&body.node, id),
+        HirStmt::Loop { body } => block_moves(&body.node, id),
+    }
+}
+
+fn block_moves(block: &HirBlock, id: BindingId) -> bool {
+    block.stmts.iter().any(|s| stmt_moves(&s.node, id))
+        || block.expr.as_ref().is_some_and(|e| is_var(&e.node, id) || moves(&e.node, id))
+}
+
+/// Does `expr` close `id` or move it somewhere: a by-value argument, a
+/// returned value, a struct field or tuple/array element?
+fn moves(expr: &HirExpr, id: BindingId) -> bool {
+    let arg = |e: &crate::hir::Spanned<HirExpr>| is_var(&e.node, id) || moves(&e.node, id);
+    match expr {
+        HirExpr::Call { target, args } => {
+            let closed = matches!(target, HirCallTarget::Method { receiver, method }
+                if is_var(&receiver.node, id) && CLOSING_METHODS.contains(&method.name.as_str()));
+            let receiver_moves = match target {
+                HirCallTarget::Method { receiver, .. } => moves(&receiver.node, id),
+                HirCallTarget::Function(_) => false,
+            };
+            closed || receiver_moves || args.iter().any(arg)
+        }
+        HirExpr::Return(Some(value)) | HirExpr::Break(Some(value)) => arg(value),
+        HirExpr::Struct { fields, .. } => fields.iter().any(|(_, value)| arg(value)),
+        HirExpr::Array(items) | HirExpr::Tuple(items) => items.iter().any(arg),
+        HirExpr::If { condition, then_branch, else_branch } => {
+            moves(&condition.node, id)
+                || block_moves(&then_branch.node, id)
+                || else_branch.as_ref().is_some_and(|b| block_moves(&b.node, id))
+        }
+        HirExpr::Match { scrutinee, arms } => {
+            arg(scrutinee) || arms.iter().any(|a| is_var(&a.body.node, id) || moves(&a.body.node, id))
+        }
+        HirExpr::Block(block) => block_moves(&block.node, id),
+        HirExpr::Closure { captures, .. } => captures.contains(&id),
+        HirExpr::Field { base, .. } => moves(&base.node, id),
+        HirExpr::Index { base, index } => moves(&base.node, id) || moves(&index.node, id),
+        HirExpr::Binary { left, right, .. } => moves(&left.node, id) || moves(&right.node, id),
+        HirExpr::Unary { operand, .. } => moves(&operand.node, id),
+        HirExpr::Ref { expr, .. } | HirExpr::Deref(expr) => moves(&expr.node, id),
+        HirExpr::Range { start, end, .. } => {
+            start.as_ref().is_some_and(|e| moves(&e.node, id)) || end.as_ref().is_some_and(|e| moves(&e.node, id))
+        }
+        HirExpr::Literal(_) | HirExpr::Var(_) | HirExpr::FnRef(_) | HirExpr::Return(None)
+        | HirExpr::Break(None) | HirExpr::Continue => false,
+    }
+}
+
+/// Does `stmt` contain a `return` that leaves with `id` still open? A
+/// branch that closes `id` before returning is fine.
+fn stmt_returns(stmt: &HirStmt, id: BindingId) -> bool {
+    match stmt {
+        HirStmt::Let { init: Some(e), .. } | HirStmt::Expr(e) => returns_without(&e.node, id),
+        HirStmt::Let { init: None, .. } => false,
+        HirStmt::Assign { target, value } => returns_without(&target.node, id) || returns_without(&value.node, id),
+        HirStmt::While { condition, body } => returns_without(&condition.node, id) || block_returns(&body.node, id),
+        HirStmt::For { iter, body, .. } => returns_without(&iter.node, id) || block_returns(&body.node, id),
+        HirStmt::Loop { body } => block_returns(&body.node, id),
+    }
+}
+
+fn block_returns(block: &HirBlock, id: BindingId) -> bool {
+    let mut open = true;
+    for stmt in &block.stmts {
+        if open && stmt_returns(&stmt.node, id) {
+            return true;
+        }
+        open = open && !stmt_moves(&stmt.node, id);
+    }
+    open && block.expr.as_ref().is_some_and(|e| returns_without(&e.node, id))
+}
+
+fn returns_without(expr: &HirExpr, id: BindingId) -> bool {
+    let any = |e: &crate::hir::Spanned<HirExpr>| returns_without(&e.node, id);
+    match expr {
+        HirExpr::Return(value) => !value.as_ref().is_some_and(|v| is_var(&v.node, id) || moves(&v.node, id)),
+        HirExpr::If { condition, then_branch, else_branch } => {
+            any(condition)
+                || block_returns(&then_branch.node, id)
+                || else_branch.as_ref().is_some_and(|b| block_returns(&b.node, id))
+        }
+        HirExpr::Match { scrutinee, arms } => any(scrutinee) || arms.iter().any(|a| any(&a.body)),
+        HirExpr::Block(block) => block_returns(&block.node, id),
+        HirExpr::Call { target, args } => {
+            matches!(target, HirCallTarget::Method { receiver, .. } if any(receiver)) || args.iter().any(any)
+        }
+        HirExpr::Struct { fields, .. } => fields.iter().any(|(_, value)| any(value)),
+        HirExpr::Array(items) | HirExpr::Tuple(items) => items.iter().any(any),
+        HirExpr::Field { base, .. } => any(base),
+        HirExpr::Index { base, index } => any(base) || any(index),
+        HirExpr::Binary { left, right, .. } => any(left) || any(right),
+        HirExpr::Unary { operand, .. } => any(operand),
+        HirExpr::Ref { expr, .. } | HirExpr::Deref(expr) => any(expr),
+        HirExpr::Range { start, end, .. } => start.as_ref().is_some_and(|e| any(e)) || end.as_ref().is_some_and(|e| any(e)),
+        HirExpr::Break(Some(value)) => any(value),
+        // A closure's `return` leaves the closure
+        HirExpr::Closure { .. } | HirExpr::Literal(_) | HirExpr::Var(_) | HirExpr::FnRef(_)
+        | HirExpr::Break(None) | HirExpr::Continue => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Ident, Span, Type};
+    use crate::hir::Spanned;
+
+    fn leaks(source: &str) -> Vec<(usize, String, usize)> {
+        find_unclosed_handles(source).into_iter().map(|h| (h.line, h.binding, h.leaked_at)).collect()
+    }
+
+    #[test]
+    fn test_opened_resource() {
+        assert_eq!(opened_resource("f = std::fs::File::open(path)?"), Some(("file", "File::open")));
+        assert_eq!(opened_resource("s = TcpStream::connect(addr).unwrap()"), Some(("socket", "TcpStream::connect")));
+        assert_eq!(opened_resource("f = MyFile::open(path)"), None);
+        assert_eq!(opened_binding("let mut f = File::create(p).expect(\"x\")"), Some(("f".to_string(), "file")));
+        assert_eq!(opened_binding("f = OpenOptions::new().append(true).open(p)?"), Some(("f".to_string(), "file")));
+        assert_eq!(opened_binding("n = File::open(p)?.metadata()?.len()"), None);
+        assert_eq!(opened_binding("ok = File::open(p).is_ok()"), None);
+    }
+
+    #[test]
+    fn test_unclosed_handle() {
+        let source = r#"fn size(path String) effects(io, resource(file)) u64 {
+    file = File::open(path).unwrap()
+    n = file.metadata().unwrap().len()
+    println("{:?} {}", file, n)
+    n
+}"#;
+        let found = find_unclosed_handles(source);
+        assert_eq!(found, vec![UnclosedHandle {
+            line: 2,
+            binding: "file".to_string(),
+            kind: "file",
+            leaked_at: 6,
+            function: Some("size".to_string()),
+        }]);
+    }
+
+    #[test]
+    fn test_closed_and_moved_handles() {
+        let source = r#"fn copy(a String, b String) effects(io, resource(file), resource(socket)) Result[Conn, Error] {
+    src = File::open(a)?
+    reader = BufReader::new(src)
+    mut dst = File::create(b)?
+    write(&mut dst, reader)?
+    drop(dst)
+    sock = TcpStream::connect("host")?
+    sock.shutdown(Shutdown::Both)?
+    conn = TcpStream::connect("other")?
+    Ok(Conn { stream: conn })
+}"#;
+        assert!(leaks(source).is_empty(), "{:?}", find_unclosed_handles(source));
+    }
+
+    #[test]
+    fn test_return_paths() {
+        let source = r#"fn head(path String, skip bool) effects(io, resource(file)) Option[File] {
+    f = File::open(path).unwrap()
+    if skip {
+        drop(f)
+        return None
+    }
+    g = File::open(path).unwrap()
+    if skip { drop(f); return None }
+    drop(g)
+    Some(f)
+}"#;
+        assert_eq!(leaks(source), vec![(7, "g".to_string(), 8)]);
+    }
+
+    #[test]
+    fn test_closed_in_one_branch_only() {
+        let source = r#"fn f(p String, quick bool) effects(io, resource(file)) {
+    file = File::open(p).unwrap()
+    if quick {
+        drop(file)
+    } else {
+        log(&file)
+    }
+}"#;
+        assert_eq!(leaks(source), vec![(2, "file".to_string(), 8)]);
+    }
+
+    #[test]
+    fn test_allow_unclosed_resource() {
+        let source = r#"fn serve() effects(io, resource(socket)) {
+    // allow(unclosed-resource)
+    listener = TcpListener::bind("0.0.0.0:80").unwrap()
+    accept(&listener)
+}
+
+// allow(unclosed-resource)
+fn keep(p String) effects(io, resource(file)) {
+    f = File::open(p).unwrap()
+}
+
+fn leak(p String) effects(io, resource(file)) {
+    f = File::open(p).unwrap()   // allow(unclosed-resource)
+    h = File::open(p).unwrap()
+}"#;
+        assert_eq!(leaks(source), vec![(14, "h".to_string(), 15)]);
+    }
+
+    fn span(line: usize) -> Span {
+        Span { start_line: line, start_col: 1, end_line: line, end_col: 1 }
+    }
+
+    fn spanned<T>(node: T, line: usize) -> Spanned<T> {
+        Spanned::new(node, span(line))
+    }
+
+    fn call(name: &str, args: Vec<HirExpr>) -> HirExpr {
+        HirExpr::Call {
+            target: HirCallTarget::Function(Path { segments: name.split("::").map(Ident::new).collect() }),
+            args: args.into_iter().map(|a| spanned(a, 0)).collect(),
+        }
+    }
+
+    fn open(id: u32, line: usize) -> Spanned<HirStmt> {
+        let init = call("File::open", vec![HirExpr::Var(BindingId::new(9))]);
+        spanned(HirStmt::Let { binding: BindingId::new(id), ty: None, init: Some(spanned(init, line)) }, line)
+    }
+
+    fn stmt(expr: HirExpr, line: usize) -> Spanned<HirStmt> {
+        spanned(HirStmt::Expr(spanned(expr, line)), line)
+    }
+
+    fn func(stmts: Vec<Spanned<HirStmt>>) -> HirFnDef {
+        HirFnDef {
+            name: Ident::new("f"),
+            params: vec![(BindingId::new(9), Ident::new("p"), Type::Path(crate::ast::Path::single("String")))],
+            return_type: None,
+            effects: Vec::new(),
+            body: spanned(HirBlock { stmts, expr: None }, 0),
+            local_bindings: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_hir_unclosed_handles() {
+        let (a, b, c) = (BindingId::new(0), BindingId::new(1), BindingId::new(2));
+        let early_return = HirExpr::If {
+            condition: Box::new(spanned(HirExpr::Var(BindingId::new(9)), 5)),
+            then_branch: Box::new(spanned(HirBlock {
+                stmts: vec![stmt(HirExpr::Return(None), 5)],
+                expr: None,
+            }, 5)),
+            else_branch: None,
+        };
+        let body = vec![
+            open(0, 1),
+            open(1, 2),
+            open(2, 3),
+            stmt(call("drop", vec![HirExpr::Var(a)]), 4),
+            stmt(early_return, 5),
+            stmt(call("consume", vec![HirExpr::Var(b)]), 6),
+        ];
+        // `b` leaks through the return on line 5, `c` is never closed
+        assert_eq!(unclosed_handles(&func(body)), vec![b, c]);
+    }
+}
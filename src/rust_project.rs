@@ -0,0 +1,215 @@
+//! rust-analyzer Project File for RustS+
+//!
+//! `rustsp foo.rss --emit-rs -o gen/foo.rs --rust-project` also writes
+//! `gen/rust-project.json` so rust-analyzer can index the generated Rust
+//! (type hints, go-to-definition) without a Cargo manifest.
+//!
+//! ```text
+//! {
+//!   "sysroot": "/home/me/.rustup/toolchains/stable-x86_64-unknown-linux-gnu",
+//!   "crates": [
+//!     { "display_name": "foo", "root_module": "foo.rs", "edition": "2021",
+//!       "deps": [], "is_workspace_member": true }
+//!   ]
+//! }
+//! ```
+//!
+//! Emitting several files into the same directory accumulates one crate per
+//! generated file: the crate list of an existing project file is kept and
+//! the new root module is added if missing.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File name rust-analyzer looks for in the workspace root
+pub const PROJECT_FILE_NAME: &str = "rust-project.json";
+
+/// One crate entry of the project file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectCrate {
+    pub display_name: String,
+    /// Path of the crate root, relative to the project file when possible
+    pub root_module: String,
+    pub edition: String,
+}
+
+impl ProjectCrate {
+    /// Crate for a generated `.rs` file, as seen from `project_dir`
+    pub fn for_generated_file(rs_path: &Path, project_dir: &Path, edition: &str) -> Self {
+        let display_name = rs_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("main")
+            .to_string();
+        ProjectCrate {
+            display_name,
+            root_module: relative_path(rs_path, project_dir),
+            edition: edition.to_string(),
+        }
+    }
+}
+
+/// Express `path` relative to `base` if it lives below it, absolute otherwise
+fn relative_path(path: &Path, base: &Path) -> String {
+    let abs = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+    let path_abs = abs(path);
+    let base_abs = abs(base);
+    let shown = match path_abs.strip_prefix(&base_abs) {
+        Ok(rel) => rel.to_path_buf(),
+        Err(_) => path_abs,
+    };
+    shown.to_string_lossy().replace('\\', "/")
+}
+
+/// Escape `s` for use inside a JSON string literal
+pub fn escape_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape_json(s: &str) -> String {
+    s.replace("\\\\", "\u{0}").replace("\\\"", "\"").replace("\\n", "\n").replace('\u{0}', "\\")
+}
+
+/// Render the project file
+pub fn render_project_json(sysroot: Option<&str>, crates: &[ProjectCrate]) -> String {
+    let mut out = String::from("{\n");
+    if let Some(sysroot) = sysroot {
+        out.push_str(&format!("  \"sysroot\": \"{}\",\n", escape_json(sysroot)));
+    }
+    out.push_str("  \"crates\": [");
+    for (i, krate) in crates.iter().enumerate() {
+        out.push_str(if i == 0 { "\n" } else { ",\n" });
+        out.push_str(&format!(
+            "    {{ \"display_name\": \"{}\", \"root_module\": \"{}\", \"edition\": \"{}\", \"deps\": [], \"is_workspace_member\": true }}",
+            escape_json(&krate.display_name),
+            escape_json(&krate.root_module),
+            escape_json(&krate.edition)
+        ));
+    }
+    out.push_str(if crates.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+    out
+}
+
+/// Extract the string value following `"key":` starting at `from`
+fn string_field(json: &str, key: &str, from: usize, until: usize) -> Option<String> {
+    let needle = format!("\"{}\"", key);
+    let pos = json[from..until].find(&needle)? + from + needle.len();
+    let rest = json[pos..until].trim_start().strip_prefix(':')?.trim_start();
+    let rest = rest.strip_prefix('"')?;
+    let mut end = None;
+    let mut escaped = false;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => {
+                end = Some(i);
+                break;
+            }
+            _ => escaped = false,
+        }
+    }
+    Some(unescape_json(&rest[..end?]))
+}
+
+/// Read the crate list of a project file previously written by rustsp.
+/// Entries that cannot be read back are dropped.
+pub fn parse_project_crates(json: &str) -> Vec<ProjectCrate> {
+    let mut crates = Vec::new();
+    let Some(list_start) = json.find("\"crates\"") else {
+        return crates;
+    };
+    let mut pos = list_start;
+    while let Some(open) = json[pos..].find('{').map(|p| p + pos) {
+        let Some(close) = json[open..].find('}').map(|p| p + open) else {
+            break;
+        };
+        if let Some(root_module) = string_field(json, "root_module", open, close) {
+            crates.push(ProjectCrate {
+                display_name: string_field(json, "display_name", open, close)
+                    .unwrap_or_else(|| root_module.clone()),
+                edition: string_field(json, "edition", open, close).unwrap_or_else(|| "2021".to_string()),
+                root_module,
+            });
+        }
+        pos = close + 1;
+    }
+    crates
+}
+
+/// Active toolchain sysroot (`rustc --print sysroot`), if rustc is available
+pub fn detect_sysroot() -> Option<String> {
+    let output = Command::new("rustc").args(["--print", "sysroot"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let sysroot = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if sysroot.is_empty() { None } else { Some(sysroot) }
+}
+
+/// Write (or update) `rust-project.json` in `project_dir` so it covers
+/// `generated_rs`. Returns the path of the project file.
+pub fn write_rust_project(generated_rs: &Path, project_dir: &Path, edition: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(project_dir)?;
+    let project_file = project_dir.join(PROJECT_FILE_NAME);
+
+    let mut crates = fs::read_to_string(&project_file)
+        .map(|existing| parse_project_crates(&existing))
+        .unwrap_or_default();
+    let krate = ProjectCrate::for_generated_file(generated_rs, project_dir, edition);
+    match crates.iter_mut().find(|c| c.root_module == krate.root_module) {
+        Some(existing) => *existing = krate,
+        None => crates.push(krate),
+    }
+
+    fs::write(&project_file, render_project_json(detect_sysroot().as_deref(), &crates))?;
+    Ok(project_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let crates = vec![
+            ProjectCrate { display_name: "app".into(), root_module: "app.rs".into(), edition: "2021".into() },
+            ProjectCrate { display_name: "tool".into(), root_module: "bin/t\"ool.rs".into(), edition: "2021".into() },
+        ];
+        let json = render_project_json(Some("/opt/rust"), &crates);
+        assert!(json.contains("\"sysroot\": \"/opt/rust\""));
+        assert!(json.contains("\"root_module\": \"app.rs\""));
+        assert_eq!(parse_project_crates(&json), crates);
+        assert!(parse_project_crates(&render_project_json(None, &[])).is_empty());
+    }
+
+    #[test]
+    fn test_write_accumulates_crates() {
+        let dir = std::env::temp_dir().join(format!("rustsp-project-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.rs"), "fn main() {}").unwrap();
+        fs::write(dir.join("b.rs"), "fn main() {}").unwrap();
+
+        write_rust_project(&dir.join("a.rs"), &dir, "2021").unwrap();
+        let file = write_rust_project(&dir.join("b.rs"), &dir, "2021").unwrap();
+        write_rust_project(&dir.join("a.rs"), &dir, "2021").unwrap();
+
+        let crates = parse_project_crates(&fs::read_to_string(file).unwrap());
+        let roots: Vec<&str> = crates.iter().map(|c| c.root_module.as_str()).collect();
+        assert_eq!(roots, vec!["a.rs", "b.rs"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
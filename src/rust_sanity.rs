@@ -12,6 +12,8 @@
 //! - No unclosed strings/chars
 //! - Valid expression structure
 
+use crate::lowering_rules::{LoweringRule, RuleSet};
+
 /// Result of sanity check
 #[derive(Debug, Clone)]
 pub struct SanityCheckResult {
@@ -36,6 +38,11 @@ pub enum SanityErrorKind {
     InternalLoweringError,
     /// L-05: Effect annotations leaked into Rust output
     EffectAnnotationLeakage,
+    /// Rejected by syn's parser (audit mode)
+    SyntaxError,
+    /// Output disagrees with the first-pass registries
+    /// (see [`crate::registry_check`])
+    RegistryMismatch,
 }
 
 impl SanityCheckResult {
@@ -83,6 +90,26 @@ pub fn check_rust_output(rust_code: &str) -> SanityCheckResult {
     }
 }
 
+/// [`check_rust_output`] for a lowering that ran with `rules`. Problems a
+/// disabled rule leaves behind by design are not reported: `effects(..)` in
+/// a signature with L-01 or L-05 off, a bare `mut x = ..` with L-03 or L-04
+/// off (see [`crate::lowering_rules`])
+pub fn check_rust_output_with_rules(rust_code: &str, rules: &RuleSet) -> SanityCheckResult {
+    let mut result = check_rust_output(rust_code);
+    result.errors.retain(|error| !caused_by_disabled_rule(error, rules));
+    result.is_valid = result.errors.is_empty();
+    result
+}
+
+fn caused_by_disabled_rule(error: &SanityError, rules: &RuleSet) -> bool {
+    let off = |rule| !rules.is_enabled(rule);
+    match error.kind {
+        SanityErrorKind::EffectAnnotationLeakage => off(LoweringRule::L01) || off(LoweringRule::L05),
+        SanityErrorKind::IllegalToken => off(LoweringRule::L03) || off(LoweringRule::L04),
+        _ => false,
+    }
+}
+
 /// Check for balanced delimiters: (), [], {}
 fn check_balanced_delimiters(code: &str) -> Option<SanityError> {
     let mut paren_stack: Vec<(char, usize, usize)> = Vec::new();
@@ -127,6 +154,13 @@ fn check_balanced_delimiters(code: &str) -> Option<SanityError> {
                 if col + 1 < chars.len() {
                     let next = chars[col + 1];
                     
+                    // Regular char literal: 'c' where c is a single char.
+                    // Checked first - `'a'` is a char, not the lifetime `'a`
+                    if col + 2 < chars.len() && chars[col + 2] == '\'' {
+                        col += 3; // skip 'c'
+                        continue;
+                    }
+                    
                     // Check for lifetime: 'ident (identifier starts with letter or _)
                     if next.is_alphabetic() || next == '_' {
                         // This is likely a lifetime like 'static, 'a, '_
@@ -154,12 +188,6 @@ fn check_balanced_delimiters(code: &str) -> Option<SanityError> {
                         }
                         continue;
                     }
-                    
-                    // Regular char literal: 'c' where c is a single char
-                    if col + 2 < chars.len() && chars[col + 2] == '\'' {
-                        col += 3; // skip 'c'
-                        continue;
-                    }
                 }
                 
                 // Fallback: toggle in_char mode (legacy behavior)
@@ -495,8 +523,46 @@ fn check_effect_annotation_leakage(code: &str) -> Vec<SanityError> {
     errors
 }
 
+/// Name of the checker behind `audit_rust_output`
+#[cfg(feature = "syn-audit")]
+pub const AUDIT_BACKEND: &str = "syn";
+#[cfg(not(feature = "syn-audit"))]
+pub const AUDIT_BACKEND: &str = "rust_sanity";
+
+/// Audit generated Rust with syn's full parser (feature `syn-audit`),
+/// reporting the exact line/column rustc's grammar rejects
+#[cfg(feature = "syn-audit")]
+pub fn audit_rust_output(rust_code: &str) -> SanityCheckResult {
+    match syn::parse_file(rust_code) {
+        Ok(_) => SanityCheckResult::ok(),
+        Err(err) => SanityCheckResult::error(err.into_iter()
+            .map(|e| {
+                let start = e.span().start();
+                SanityError {
+                    line: start.line,
+                    column: start.column + 1,
+                    message: e.to_string(),
+                    kind: SanityErrorKind::SyntaxError,
+                }
+            })
+            .collect()),
+    }
+}
+
+/// Without the `syn-audit` feature the audit is the lightweight checker
+#[cfg(not(feature = "syn-audit"))]
+pub fn audit_rust_output(rust_code: &str) -> SanityCheckResult {
+    check_rust_output(rust_code)
+}
+
 /// Format internal compiler error for display
 pub fn format_internal_error(result: &SanityCheckResult) -> String {
+    format_internal_error_with_rules(result, &RuleSet::default())
+}
+
+/// [`format_internal_error`] for a lowering that ran with `rules`: with a
+/// rule disabled the invalid output is blamed on it, not on the compiler
+pub fn format_internal_error_with_rules(result: &SanityCheckResult, rules: &RuleSet) -> String {
     let mut output = String::new();
     
     output.push_str("\n");
@@ -505,7 +571,12 @@ pub fn format_internal_error(result: &SanityCheckResult) -> String {
     output.push_str("╚══════════════════════════════════════════════════════════════════╝\n");
     output.push_str("\n");
     output.push_str("note:\n");
-    output.push_str("  This is a compiler bug, not your fault.\n");
+    if rules.is_complete() {
+        output.push_str("  This is a compiler bug, not your fault.\n");
+    } else {
+        output.push_str(&format!("  Lowering rules were disabled: {}.\n", disabled_ids(rules)));
+        output.push_str("  The invalid output may come from them rather than from a compiler bug.\n");
+    }
     output.push_str("\n");
     
     for error in &result.errors {
@@ -516,12 +587,21 @@ pub fn format_internal_error(result: &SanityCheckResult) -> String {
     }
     
     output.push_str("help:\n");
-    output.push_str("  Please report this issue to the RustS+ developers.\n");
-    output.push_str("  Include your RustS+ source code for debugging.\n");
+    if rules.is_complete() {
+        output.push_str("  Please report this issue to the RustS+ developers.\n");
+        output.push_str("  Include your RustS+ source code for debugging.\n");
+    } else {
+        output.push_str("  Run again with every rule enabled before reporting this issue.\n");
+    }
     
     output
 }
 
+/// `L-04, array`
+pub fn disabled_ids(rules: &RuleSet) -> String {
+    rules.disabled().iter().map(|rule| rule.id()).collect::<Vec<_>>().join(", ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -753,4 +833,41 @@ fn main() {
         let result = check_rust_output(code);
         assert!(result.is_valid, "Char literals should still work: {:?}", result.errors);
     }
+    
+    #[test]
+    fn test_audit_accepts_valid_and_rejects_unbalanced() {
+        let valid = "fn main() {\n    let x = [1, 2, 3];\n}\n";
+        assert!(audit_rust_output(valid).is_valid);
+        let invalid = "fn main() {\n    let x = (1;\n}\n";
+        assert!(!audit_rust_output(invalid).is_valid);
+    }
+    
+    #[cfg(feature = "syn-audit")]
+    #[test]
+    fn test_syn_audit_locates_error() {
+        let code = "fn main() {\n    let y = 1 + ;\n}\n";
+        let result = audit_rust_output(code);
+        assert!(!result.is_valid);
+        let error = &result.errors[0];
+        assert_eq!((error.line, error.column), (2, 17));
+        assert_eq!(error.kind, SanityErrorKind::SyntaxError);
+    }
+    
+    #[test]
+    fn test_disabled_rules_are_expected() {
+        let code = "fn log(msg: String) effects(io) {\n    mut x = 1;\n}\n";
+        assert!(!check_rust_output(code).is_valid);
+        
+        let mut rules = RuleSet::default();
+        rules.disable(LoweringRule::L05);
+        let result = check_rust_output_with_rules(code, &rules);
+        assert!(result.errors.iter().all(|e| e.kind == SanityErrorKind::IllegalToken), "{:?}", result.errors);
+        assert!(!result.is_valid);
+        let report = format_internal_error_with_rules(&result, &rules);
+        assert!(report.contains("Lowering rules were disabled: L-05."), "{}", report);
+        assert!(!report.contains("compiler bug, not your fault"));
+        
+        rules.disable(LoweringRule::L04);
+        assert!(check_rust_output_with_rules(code, &rules).is_valid);
+    }
 }
\ No newline at end of file
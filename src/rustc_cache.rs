@@ -0,0 +1,159 @@
+//! Rustc Output Cache for RustS+
+//!
+//! Stage 3 often recompiles byte-identical generated Rust during iterative
+//! work (e.g. editing comments, re-running `rustsp test`). This cache skips
+//! rustc when nothing that affects the binary has changed.
+//!
+//! ## Cache Key
+//!
+//! ```text
+//! SHA-256( rustc --version  \0  flag1 \0 flag2 ...  \0  generated .rs )
+//! ```
+//!
+//! ## Layout
+//!
+//! ```text
+//! $TMPDIR/rustsp-rustc-cache/
+//! └── <key>.bin        # Compiled binary for that key
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::sha256;
+
+/// Result of a cache lookup, used for stage progress messages
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheStatus {
+    /// Cached binary found and copied to the output path
+    Hit(String),
+    /// No cached binary - rustc must run
+    Miss(String),
+    /// Caching disabled via `--no-cache`
+    Disabled,
+}
+
+impl CacheStatus {
+    /// Short description for progress output: `cache hit (1a2b3c4d)`
+    pub fn describe(&self) -> String {
+        match self {
+            CacheStatus::Hit(key) => format!("cache hit ({})", short_key(key)),
+            CacheStatus::Miss(key) => format!("cache miss ({})", short_key(key)),
+            CacheStatus::Disabled => "cache disabled".to_string(),
+        }
+    }
+}
+
+fn short_key(key: &str) -> &str {
+    &key[..key.len().min(8)]
+}
+
+/// Content-addressed store of rustc outputs
+pub struct RustcCache {
+    dir: PathBuf,
+}
+
+impl RustcCache {
+    pub fn new(dir: PathBuf) -> Self {
+        RustcCache { dir }
+    }
+
+    /// Default cache location: `$TMPDIR/rustsp-rustc-cache`
+    pub fn default_location() -> Self {
+        RustcCache::new(std::env::temp_dir().join("rustsp-rustc-cache"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compute the cache key for generated code + rustc flags
+    pub fn key(rust_code: &str, flags: &[String], rustc_version: &str) -> String {
+        let mut material = String::with_capacity(rust_code.len() + 128);
+        material.push_str(rustc_version);
+        material.push('\0');
+        for flag in flags {
+            material.push_str(flag);
+            material.push('\0');
+        }
+        material.push_str(rust_code);
+        sha256::hash_str(&material)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+
+    /// Copy a cached binary to `output` if present. Returns true on hit.
+    pub fn restore(&self, key: &str, output: &Path) -> bool {
+        let entry = self.entry_path(key);
+        if !entry.is_file() {
+            return false;
+        }
+        if fs::copy(&entry, output).is_err() {
+            return false;
+        }
+        // fs::copy preserves permissions on unix, so the binary stays executable
+        true
+    }
+
+    /// Store a freshly compiled binary under `key`
+    pub fn store(&self, key: &str, binary: &Path) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Write to a temp name first so a concurrent reader never sees a partial file
+        let tmp = self.dir.join(format!("{}.tmp{}", key, std::process::id()));
+        fs::copy(binary, &tmp)?;
+        fs::rename(&tmp, self.entry_path(key))
+    }
+}
+
+/// `rustc --version` output (part of the cache key). Empty if rustc is missing.
+pub fn rustc_version() -> String {
+    Command::new("rustc")
+        .arg("--version")
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_depends_on_code_flags_and_version() {
+        let flags = vec!["-o".to_string(), "out".to_string()];
+        let base = RustcCache::key("fn main() {}", &flags, "rustc 1.80.0");
+        assert_eq!(base, RustcCache::key("fn main() {}", &flags, "rustc 1.80.0"));
+        assert_ne!(base, RustcCache::key("fn main() { }", &flags, "rustc 1.80.0"));
+        assert_ne!(base, RustcCache::key("fn main() {}", &[], "rustc 1.80.0"));
+        assert_ne!(base, RustcCache::key("fn main() {}", &flags, "rustc 1.81.0"));
+    }
+
+    #[test]
+    fn test_store_and_restore() {
+        let dir = std::env::temp_dir().join(format!("rustsp-cache-test-{}", std::process::id()));
+        let cache = RustcCache::new(dir.join("cache"));
+        fs::create_dir_all(&dir).unwrap();
+
+        let binary = dir.join("bin");
+        fs::write(&binary, b"binary").unwrap();
+        let out = dir.join("restored");
+
+        assert!(!cache.restore("k1", &out));
+        cache.store("k1", &binary).unwrap();
+        assert!(cache.restore("k1", &out));
+        assert_eq!(fs::read(&out).unwrap(), b"binary");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_status_describe() {
+        let key = "0123456789abcdef".to_string();
+        assert_eq!(CacheStatus::Hit(key.clone()).describe(), "cache hit (01234567)");
+        assert_eq!(CacheStatus::Miss(key).describe(), "cache miss (01234567)");
+    }
+}
@@ -14,7 +14,10 @@
 //! - Map legacy scope variables to HIR BindingIds
 //! - Support effect analysis through HIR
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use crate::error_msg::{scope_errors, RsplError};
+use crate::for_loops::{loop_bindings, parse_for_header};
+use crate::lowering::depth_tracking_lowering::split_top_level_types;
 
 //=============================================================================
 // HIR INTEGRATION TYPES (NEW)
@@ -524,6 +527,21 @@ pub fn analyze_outer_assignment(
 // SCOPE ANALYZER
 //=============================================================================
 
+/// State carried from line to line by [`ScopeAnalyzer::analyze_line`]
+#[derive(Debug, Default)]
+pub(crate) struct ScopeScan {
+    stack: ScopeStack,
+    /// Was the previous non-empty content a control flow keyword?
+    pending_control_flow: bool,
+    /// Multi-line function signature waiting for its `{`
+    pending_function_def: bool,
+    pending_function_params: Vec<(String, Option<String>)>,
+    pending_function_line: usize,
+    /// When inside a struct literal, `field = value` is NOT a variable
+    /// assignment - struct literal depth is tracked separately from code blocks
+    struct_literal_depth: usize,
+}
+
 /// Two-pass scope analyzer for a source file
 #[derive(Debug)]
 pub struct ScopeAnalyzer {
@@ -539,6 +557,10 @@ pub struct ScopeAnalyzer {
     pub hir_bindings: HashMap<BindingId, HirBindingInfo>,
     /// NEW: Parameter bindings for effect analysis
     pub param_bindings: Vec<BindingId>,
+    /// `outer` assignments with no outer variable: (line, var_name)
+    pub outer_errors: Vec<(usize, String)>,
+    /// Bindings of `for` patterns: (var_name, line of the `for`)
+    pub loop_bindings: HashSet<(String, usize)>,
 }
 
 impl ScopeAnalyzer {
@@ -550,281 +572,271 @@ impl ScopeAnalyzer {
             outer_lines: HashMap::new(),
             hir_bindings: HashMap::new(),
             param_bindings: Vec::new(),
+            outer_errors: Vec::new(),
+            loop_bindings: HashSet::new(),
         }
     }
     
     /// Analyze source and build scope information
     pub fn analyze(&mut self, source: &str) {
-        let lines: Vec<&str> = source.lines().collect();
-        let mut stack = ScopeStack::new();
+        let mut scan = ScopeScan::default();
+        for (line_num, line) in source.lines().enumerate() {
+            let clean = strip_comment(line);
+            let trimmed = clean.trim();
+            let (opens, closes) = count_braces_outside_strings(trimmed);
+            self.analyze_line(&mut scan, line_num, trimmed, opens, closes);
+        }
+        self.finish(scan);
+    }
+    
+    /// Analyze one line: `trimmed` has its comment stripped, `opens` /
+    /// `closes` count braces outside strings. Lets a caller that already
+    /// scans the source (see [`crate::program_index`]) drive the analysis.
+    pub(crate) fn analyze_line(&mut self, scan: &mut ScopeScan, line_num: usize, trimmed: &str, opens: usize, closes: usize) {
+        // Check if this line is or contains control flow OR function definition
+        let is_control_flow_line = trimmed.starts_with("if ")
+            || trimmed.starts_with("} else")
+            || trimmed.starts_with("else")
+            || trimmed.starts_with("while ")
+            || trimmed.starts_with("for ")
+            || trimmed.starts_with("loop")
+            || trimmed.starts_with("match ")
+            || trimmed.contains("} else")
+            || trimmed.contains("else {");
         
-        // Track if previous non-empty content was a control flow keyword
-        let mut pending_control_flow = false;
-        // NEW: Track if we're in a function definition (for parameter detection)
-        let mut in_function_signature = false;
-        let mut current_function_line: Option<usize> = None;
-        // CRITICAL FIX: Track pending function def for multi-line signatures
-        let mut pending_function_def = false;
-        let mut pending_function_params: Vec<(String, Option<String>)> = Vec::new();
-        let mut pending_function_line: usize = 0;
+        // Function definitions open a normal (non-bare) scope
+        let is_function_def = trimmed.starts_with("fn ") 
+            || trimmed.starts_with("pub fn ");
         
-        //=====================================================================
-        // CRITICAL FIX: Track struct literal mode
-        // When inside a struct literal, `field = value` is NOT a variable assignment!
-        // We track depth of struct literals separately from code blocks.
+        // NEW: Detect closure
+        let is_closure = trimmed.contains("|") && 
+            (trimmed.contains("||") || (trimmed.matches('|').count() >= 2));
+        
+        //=================================================================
+        // CRITICAL FIX: Detect struct literal start
+        // Patterns:
+        //   - `var = StructName {` (assignment)
+        //   - `Some(StructName {` (function call containing struct)
+        //   - `StructName {` (bare return expression)
+        //   - `vec![StructName {` (macro call)
+        //   - Match arm: `Pattern { ... } {` followed by struct
         //
-        // struct_literal_depth > 0 means we're inside a struct literal and should
-        // NOT treat `field = value` as variable assignment.
-        //=====================================================================
-        let mut struct_literal_depth: usize = 0;
+        // A struct literal starts when:
+        //   1. Line contains `{`
+        //   2. NOT a control flow statement
+        //   3. NOT a function/impl/trait/mod/enum/struct definition
+        //   4. Has a PascalCase identifier before `{`
+        //=================================================================
+        let is_struct_literal_start = detect_struct_literal_start(trimmed) 
+            && !is_control_flow_line 
+            && !is_function_def
+            && !is_closure
+            && !trimmed.starts_with("impl ")
+            && !trimmed.starts_with("trait ")
+            && !trimmed.starts_with("mod ")
+            && !trimmed.starts_with("pub mod ")
+            && !trimmed.starts_with("struct ")
+            && !trimmed.starts_with("pub struct ")
+            && !trimmed.starts_with("enum ")
+            && !trimmed.starts_with("pub enum ");
         
-        for (line_num, line) in lines.iter().enumerate() {
-            let clean = strip_comment(line);
-            let trimmed = clean.trim();
-            
-            // Check if this line is or contains control flow OR function definition
-            let is_control_flow_line = trimmed.starts_with("if ")
-                || trimmed.starts_with("} else")
-                || trimmed.starts_with("else")
-                || trimmed.starts_with("while ")
-                || trimmed.starts_with("for ")
-                || trimmed.starts_with("loop")
-                || trimmed.starts_with("match ")
-                || trimmed.contains("} else")
-                || trimmed.contains("else {");
-            
-            // Function definitions open a normal (non-bare) scope
-            let is_function_def = trimmed.starts_with("fn ") 
-                || trimmed.starts_with("pub fn ");
-            
-            // NEW: Detect closure
-            let is_closure = trimmed.contains("|") && 
-                (trimmed.contains("||") || (trimmed.matches('|').count() >= 2));
-            
-            //=================================================================
-            // CRITICAL FIX: Detect struct literal start
-            // Patterns:
-            //   - `var = StructName {` (assignment)
-            //   - `Some(StructName {` (function call containing struct)
-            //   - `StructName {` (bare return expression)
-            //   - `vec![StructName {` (macro call)
-            //   - Match arm: `Pattern { ... } {` followed by struct
-            //
-            // A struct literal starts when:
-            //   1. Line contains `{`
-            //   2. NOT a control flow statement
-            //   3. NOT a function/impl/trait/mod/enum/struct definition
-            //   4. Has a PascalCase identifier before `{`
-            //=================================================================
-            let is_struct_literal_start = detect_struct_literal_start(trimmed) 
-                && !is_control_flow_line 
-                && !is_function_def
-                && !is_closure
-                && !trimmed.starts_with("impl ")
-                && !trimmed.starts_with("trait ")
-                && !trimmed.starts_with("mod ")
-                && !trimmed.starts_with("pub mod ")
-                && !trimmed.starts_with("struct ")
-                && !trimmed.starts_with("pub struct ")
-                && !trimmed.starts_with("enum ")
-                && !trimmed.starts_with("pub enum ");
-            
-            // Count braces (moved up for function scope handling)
-            // CRITICAL FIX: Count braces OUTSIDE strings only!
-            // The old code used trimmed.matches('{').count() which counted braces
-            // inside string literals like format!("{}", e), causing scope depth
-            // misalignment across functions. Note: comments are already stripped
-            // by strip_comment() above, so we only need to handle strings here.
-            let (opens, closes) = count_braces_outside_strings(trimmed);
+        // NEW: Extract function parameters
+        // CRITICAL FIX: Push function scope BEFORE declaring parameters
+        // so parameters are in the function's scope, not the parent (impl) scope
+        // Track if we pushed a function scope in this iteration
+        let mut function_scope_pushed = false;
+        
+        if is_function_def {
             
-            // NEW: Extract function parameters
-            // CRITICAL FIX: Push function scope BEFORE declaring parameters
-            // so parameters are in the function's scope, not the parent (impl) scope
-            // Track if we pushed a function scope in this iteration
-            let mut function_scope_pushed = false;
+            // Parse parameters from function signature
+            let params = extract_function_params(trimmed).unwrap_or_default();
             
-            if is_function_def {
-                in_function_signature = true;
-                current_function_line = Some(line_num);
-                
-                // Parse parameters from function signature
-                let params = extract_function_params(trimmed).unwrap_or_default();
+            if opens > 0 {
+                // Single-line function def with `{` - push scope and declare params now
+                let new_level = scan.stack.scopes.len();
+                scan.stack.scopes.push(Scope::new(new_level));
+                function_scope_pushed = true;
                 
-                if opens > 0 {
-                    // Single-line function def with `{` - push scope and declare params now
-                    let new_level = stack.scopes.len();
-                    stack.scopes.push(Scope::new(new_level));
-                    function_scope_pushed = true;
-                    
-                    for (param_name, param_type) in params {
-                        stack.declare_param(&param_name, param_type, line_num);
-                    }
-                    in_function_signature = false;
-                } else {
-                    // Multi-line function def - `{` comes later
-                    // Store params for later when we see `{`
-                    pending_function_def = true;
-                    pending_function_params = params;
-                    pending_function_line = line_num;
+                for (param_name, param_type) in params {
+                    scan.stack.declare_param(&param_name, param_type, line_num);
                 }
+            } else {
+                // Multi-line function def - `{` comes later
+                // Store params for later when we see `{`
+                scan.pending_function_def = true;
+                scan.pending_function_params = params;
+                scan.pending_function_line = line_num;
             }
+        }
+        
+        // Handle pending function def when we see `{`
+        if scan.pending_function_def && opens > 0 && !is_function_def {
+            // Push function scope and declare stored params
+            let new_level = scan.stack.scopes.len();
+            scan.stack.scopes.push(Scope::new(new_level));
             
-            // Handle pending function def when we see `{`
-            if pending_function_def && opens > 0 && !is_function_def {
-                // Push function scope and declare stored params
-                let new_level = stack.scopes.len();
-                stack.scopes.push(Scope::new(new_level));
-                
-                for (param_name, param_type) in pending_function_params.drain(..) {
-                    stack.declare_param(&param_name, param_type, pending_function_line);
-                }
-                pending_function_def = false;
-                in_function_signature = false;
-                function_scope_pushed = true;
+            for (param_name, param_type) in scan.pending_function_params.drain(..) {
+                scan.stack.declare_param(&param_name, param_type, scan.pending_function_line);
             }
-            
-            //=================================================================
-            // CRITICAL FIX: Update struct_literal_depth for closing braces
-            // Decrement depth for each `}` that closes a struct literal
-            //=================================================================
-            if struct_literal_depth > 0 {
-                // Each close potentially closes a struct literal
-                // But we need to be careful: some closes might be from match arms
-                for _ in 0..closes {
-                    if struct_literal_depth > 0 {
-                        struct_literal_depth -= 1;
-                    }
+            scan.pending_function_def = false;
+            function_scope_pushed = true;
+        }
+        
+        //=================================================================
+        // CRITICAL FIX: Update scan.struct_literal_depth for closing braces
+        // Decrement depth for each `}` that closes a struct literal
+        //=================================================================
+        if scan.struct_literal_depth > 0 {
+            // Each close potentially closes a struct literal
+            // But we need to be careful: some closes might be from match arms
+            for _ in 0..closes {
+                if scan.struct_literal_depth > 0 {
+                    scan.struct_literal_depth -= 1;
                 }
             }
-            
-            // Pop for leading `}` BEFORE checking control flow
-            let leading_closes = if trimmed.starts_with('}') {
-                let mut count = 0;
-                for c in trimmed.chars() {
-                    if c == '}' { count += 1; }
-                    else { break; }
-                }
-                count
-            } else {
-                0
-            };
-            
-            for _ in 0..leading_closes {
-                stack.pop();
+        }
+        
+        // Pop for leading `}` BEFORE checking control flow
+        let leading_closes = if trimmed.starts_with('}') {
+            let mut count = 0;
+            for c in trimmed.chars() {
+                if c == '}' { count += 1; }
+                else { break; }
             }
-            
-            //=================================================================
-            // CRITICAL FIX: SKIP parse_assignment when inside struct literal
-            // Inside struct literals, `field = value` is a field initialization,
-            // NOT a variable assignment!
-            //=================================================================
-            let should_parse_assignment = struct_literal_depth == 0;
-            
-            // Parse assignment AFTER handling leading closes
-            if should_parse_assignment {
-                if let Some((var_name, var_type, value, is_outer)) = parse_assignment(trimmed) {
-                    // CRITICAL FIX: Skip `_` (wildcard/discard pattern).
-                    // `_` is not a real variable - tracking it causes false
-                    // shadowing detection and incorrect mut marking.
-                    if var_name == "_" {
-                        // Still mark as declaration so transpiler emits `let _ = expr;`
+            count
+        } else {
+            0
+        };
+        
+        for _ in 0..leading_closes {
+            scan.stack.pop();
+        }
+        
+        //=================================================================
+        // CRITICAL FIX: SKIP parse_assignment when inside struct literal
+        // Inside struct literals, `field = value` is a field initialization,
+        // NOT a variable assignment!
+        //=================================================================
+        let should_parse_assignment = scan.struct_literal_depth == 0;
+        
+        // Parse assignment AFTER handling leading closes
+        if should_parse_assignment {
+            if let Some((var_name, var_type, value, is_outer)) = parse_assignment(trimmed) {
+                // CRITICAL FIX: Skip `_` (wildcard/discard pattern).
+                // `_` is not a real variable - tracking it causes false
+                // shadowing detection and incorrect mut marking.
+                if var_name == "_" {
+                    // Still mark as declaration so transpiler emits `let _ = expr;`
+                    self.decl_lines.insert(line_num, (var_name, false));
+                } else {
+                let inferred = var_type.clone().or_else(|| infer_type(&value));
+                
+                // Use different analysis for outer vs regular assignment
+                let kind = if is_outer {
+                    analyze_outer_assignment(&scan.stack, &var_name)
+                } else {
+                    analyze_assignment(&scan.stack, &var_name, &inferred)
+                };
+                
+                match kind {
+                    AssignKind::NewDecl => {
+                        scan.stack.declare(&var_name, inferred, line_num);
                         self.decl_lines.insert(line_num, (var_name, false));
-                    } else {
-                    let inferred = var_type.clone().or_else(|| infer_type(&value));
-                    
-                    // Use different analysis for outer vs regular assignment
-                    let kind = if is_outer {
-                        analyze_outer_assignment(&stack, &var_name)
-                    } else {
-                        analyze_assignment(&stack, &var_name, &inferred)
-                    };
-                    
-                    match kind {
-                        AssignKind::NewDecl => {
-                            stack.declare(&var_name, inferred, line_num);
-                            self.decl_lines.insert(line_num, (var_name, false));
-                        }
-                        AssignKind::Shadow => {
-                            stack.declare(&var_name, inferred, line_num);
-                            self.decl_lines.insert(line_num, (var_name, true));
-                        }
-                        AssignKind::Mutation { decl_line } => {
-                            stack.mark_mut(&var_name, decl_line);
-                            self.mut_vars.insert((var_name.clone(), decl_line), true);
-                            self.mut_lines.insert(line_num, (var_name, decl_line));
-                        }
-                        AssignKind::OuterMutation { decl_line } => {
-                            stack.mark_mut(&var_name, decl_line);
-                            self.mut_vars.insert((var_name.clone(), decl_line), true);
-                            self.mut_lines.insert(line_num, (var_name, decl_line));
-                            self.outer_lines.insert(line_num, true);
-                        }
-                        AssignKind::OuterError(msg) => {
-                            eprintln!("// COMPILE ERROR at line {}: {}", line_num + 1, msg);
-                        }
                     }
-                    } // end else (non-underscore)
+                    AssignKind::Shadow => {
+                        scan.stack.declare(&var_name, inferred, line_num);
+                        self.decl_lines.insert(line_num, (var_name, true));
+                    }
+                    AssignKind::Mutation { decl_line } => {
+                        scan.stack.mark_mut(&var_name, decl_line);
+                        self.mut_vars.insert((var_name.clone(), decl_line), true);
+                        self.mut_lines.insert(line_num, (var_name, decl_line));
+                    }
+                    AssignKind::OuterMutation { decl_line } => {
+                        scan.stack.mark_mut(&var_name, decl_line);
+                        self.mut_vars.insert((var_name.clone(), decl_line), true);
+                        self.mut_lines.insert(line_num, (var_name, decl_line));
+                        self.outer_lines.insert(line_num, true);
+                    }
+                    AssignKind::OuterError(_) => {
+                        self.outer_errors.push((line_num, var_name));
+                    }
                 }
+                } // end else (non-underscore)
             }
-            
-            //=================================================================
-            // CRITICAL FIX: Update struct_literal_depth for opening braces
-            // Increment depth for each `{` that starts a struct literal
-            //=================================================================
-            if is_struct_literal_start {
-                // This line starts a struct literal
-                struct_literal_depth += opens;
-            } else if struct_literal_depth > 0 && opens > 0 {
-                // Already inside struct literal, nested struct opens more
-                // Check if this line also contains a nested struct start
-                if detect_struct_literal_start(trimmed) {
-                    struct_literal_depth += opens;
-                }
+        }
+        
+        //=================================================================
+        // CRITICAL FIX: Update scan.struct_literal_depth for opening braces
+        // Increment depth for each `{` that starts a struct literal
+        //=================================================================
+        if is_struct_literal_start {
+            // This line starts a struct literal
+            scan.struct_literal_depth += opens;
+        } else if scan.struct_literal_depth > 0 && opens > 0 {
+            // Already inside struct literal, nested struct opens more
+            // Check if this line also contains a nested struct start
+            if detect_struct_literal_start(trimmed) {
+                scan.struct_literal_depth += opens;
             }
-            
-            // Push for `{` - determine if bare or control flow or function or closure
-            // CRITICAL FIX: Skip function_def case here because we already pushed
-            // the function scope earlier (before declaring parameters)
-            for i in 0..opens {
-                // Skip the first brace if we already pushed a function scope
-                if i == 0 && function_scope_pushed {
-                    continue;
-                }
-                
-                if is_closure {
-                    stack.push_closure(); // NEW: Closure scope
-                } else if is_control_flow_line || pending_control_flow {
-                    stack.push(); // Control flow block - allows mutation
-                } else if is_function_def {
-                    // Function scope already pushed before parameter declaration
-                    // Do nothing here - just skip
-                } else {
-                    stack.push_bare(); // Bare block
-                }
+        }
+        
+        // Push for `{` - determine if bare or control flow or function or closure
+        // CRITICAL FIX: Skip function_def case here because we already pushed
+        // the function scope earlier (before declaring parameters)
+        for i in 0..opens {
+            // Skip the first brace if we already pushed a function scope
+            if i == 0 && function_scope_pushed {
+                continue;
             }
             
-            // Track pending control flow
-            if (is_control_flow_line || is_function_def) && opens == 0 {
-                pending_control_flow = is_control_flow_line;
-            } else if opens > 0 {
-                pending_control_flow = false;
+            if is_closure {
+                scan.stack.push_closure(); // NEW: Closure scope
+            } else if is_control_flow_line || scan.pending_control_flow {
+                scan.stack.push(); // Control flow block - allows mutation
+            } else if is_function_def {
+                // Function scope already pushed before parameter declaration
+                // Do nothing here - just skip
+            } else {
+                scan.stack.push_bare(); // Bare block
             }
-            
-            // Pop for trailing `}` (not leading)
-            let trailing_closes = closes.saturating_sub(leading_closes);
-            for _ in 0..trailing_closes {
-                stack.pop();
+        }
+        
+        // `for` bindings live in the loop's scope, so the body assigning
+        // one mutates it (`for mut n in ..`) rather than shadowing it
+        if opens > closes {
+            if let Some((pattern, _)) = parse_for_header(trimmed) {
+                for name in loop_bindings(pattern) {
+                    scan.stack.declare(&name, None, line_num);
+                    self.loop_bindings.insert((name, line_num));
+                }
             }
         }
         
+        // Track pending control flow
+        if (is_control_flow_line || is_function_def) && opens == 0 {
+            scan.pending_control_flow = is_control_flow_line;
+        } else if opens > 0 {
+            scan.pending_control_flow = false;
+        }
+        
+        // Pop for trailing `}` (not leading)
+        let trailing_closes = closes.saturating_sub(leading_closes);
+        for _ in 0..trailing_closes {
+            scan.stack.pop();
+        }
+    }
+    
+    /// Collect the results once every line has been analyzed
+    pub(crate) fn finish(&mut self, scan: ScopeScan) {
         // Copy all mut requirements
-        for (key, val) in &stack.mut_needed {
+        for (key, val) in &scan.stack.mut_needed {
             self.mut_vars.insert(key.clone(), *val);
         }
         
         // NEW: Store HIR bindings
-        self.hir_bindings = stack.to_hir_bindings();
-        self.param_bindings = stack.get_param_bindings();
+        self.hir_bindings = scan.stack.to_hir_bindings();
+        self.param_bindings = scan.stack.get_param_bindings();
     }
     
     /// Is this line a declaration?
@@ -837,6 +849,11 @@ impl ScopeAnalyzer {
         self.mut_lines.contains_key(&line)
     }
     
+    /// Does this line assign the binding of an enclosing `for` loop?
+    pub fn assigns_loop_binding(&self, line: usize) -> bool {
+        self.mut_lines.get(&line).is_some_and(|key| self.loop_bindings.contains(key))
+    }
+    
     /// Is this line an outer mutation?
     pub fn is_outer(&self, line: usize) -> bool {
         self.outer_lines.contains_key(&line)
@@ -856,6 +873,25 @@ impl ScopeAnalyzer {
     pub fn get_params(&self) -> &[BindingId] {
         &self.param_bindings
     }
+    
+    /// RSPL082 for the `outer` assignment of `var_name` on (0-based) `line`.
+    /// `functions` names the function of each line (see
+    /// [`crate::diagnostic_groups::enclosing_functions`]); when that function
+    /// declares the variable elsewhere, the help points there.
+    pub fn outer_error(&self, line: usize, var_name: &str, functions: &[Option<String>]) -> RsplError {
+        let function_of = |l: usize| functions.get(l).cloned().flatten();
+        let declared: Vec<usize> = self.decl_lines
+            .iter()
+            .filter(|(decl_line, (name, _))| name == var_name && function_of(**decl_line) == function_of(line))
+            .map(|(decl_line, _)| *decl_line)
+            .collect();
+        let before = declared.iter().filter(|l| **l < line).max();
+        let after = declared.iter().filter(|l| **l > line).min();
+        match before.or(after) {
+            Some(decl_line) => scope_errors::outer_target_not_enclosing(var_name, decl_line + 1, line + 1),
+            None => scope_errors::outer_not_found(var_name),
+        }
+    }
 }
 
 impl Default for ScopeAnalyzer {
@@ -993,20 +1029,12 @@ fn parse_assignment(line: &str) -> Option<(String, Option<String>, String, bool)
         return None;
     }
     
-    if left.contains('(') || left.contains('[') || left.contains('{') {
-        return None;
+    // Type annotation (the type may contain `[`: `user: Option[User]`)
+    if let Some((var, typ)) = crate::ident::split_type_annotation(left) {
+        return Some((var.to_string(), Some(typ.to_string()), right.to_string(), is_outer));
     }
     
-    // Type annotation
-    if left.contains(':') {
-        let tp: Vec<&str> = left.splitn(2, ':').collect();
-        if tp.len() == 2 {
-            let var = tp[0].trim();
-            let typ = tp[1].trim();
-            if is_valid_ident(var) {
-                return Some((var.to_string(), Some(typ.to_string()), right.to_string(), is_outer));
-            }
-        }
+    if left.contains('(') || left.contains('[') || left.contains('{') || left.contains(':') {
         return None;
     }
     
@@ -1018,14 +1046,7 @@ fn parse_assignment(line: &str) -> Option<(String, Option<String>, String, bool)
 }
 
 fn is_valid_ident(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
-    }
-    let first = s.chars().next().unwrap();
-    if !first.is_alphabetic() && first != '_' {
-        return false;
-    }
-    s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    crate::ident::is_valid_identifier(s)
 }
 
 //=============================================================================
@@ -1192,7 +1213,7 @@ fn extract_function_params(line: &str) -> Option<Vec<(String, Option<String>)>>
     
     let mut params = Vec::new();
     
-    for param in params_str.split(',') {
+    for param in split_top_level_types(params_str, ',') {
         let param = param.trim();
         if param.is_empty() {
             continue;
@@ -1257,6 +1278,19 @@ mod tests {
         assert!(analyzer.needs_mut("i", 0));
     }
     
+    #[test]
+    fn test_for_binding_mutated_in_body() {
+        let source = "n = 10\nfor n in nums {\n    n = n * 2\n}\nfor (i, x) in pairs {\n    total = x\n}";
+        let mut analyzer = ScopeAnalyzer::new();
+        analyzer.analyze(source);
+        
+        assert!(analyzer.is_mut(2));
+        assert!(analyzer.assigns_loop_binding(2));
+        assert!(analyzer.needs_mut("n", 1));
+        assert!(!analyzer.needs_mut("n", 0));
+        assert!(analyzer.is_decl(5));
+    }
+    
     #[test]
     fn test_same_scope_mutation() {
         let source = "a = 10\na = 20";
@@ -1278,6 +1312,30 @@ mod tests {
         assert!(analyzer.is_mut(2));
         assert!(analyzer.is_outer(2));
         assert!(analyzer.needs_mut("x", 0));
+        assert!(analyzer.outer_errors.is_empty());
+    }
+    
+    #[test]
+    fn test_outer_keyword_without_parent_recorded() {
+        let source = "{\n    outer y = 3\n}";
+        let mut analyzer = ScopeAnalyzer::new();
+        analyzer.analyze(source);
+        
+        assert_eq!(analyzer.outer_errors, vec![(1, "y".to_string())]);
+    }
+    
+    #[test]
+    fn test_outer_error_points_at_declaration() {
+        let source = "fn f() {\n    if a {\n        outer n = 1\n    }\n    n = 0\n}\nfn g() {\n    outer n = 2\n}";
+        let mut analyzer = ScopeAnalyzer::new();
+        analyzer.analyze(source);
+        let functions = crate::diagnostic_groups::enclosing_functions(source);
+        assert_eq!(analyzer.outer_errors.len(), 2);
+        
+        let later = analyzer.outer_error(2, "n", &functions);
+        assert!(later.suggestion.unwrap().contains("first assigned on line 5"));
+        let elsewhere = analyzer.outer_error(7, "n", &functions);
+        assert_eq!(elsewhere.suggestion.as_deref(), Some("remove `outer` or declare the variable in an outer scope first"));
     }
     
     // NEW: Test HIR integration
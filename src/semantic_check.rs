@@ -307,14 +307,7 @@ impl SemanticChecker {
     
     /// Check if string is a valid identifier
     fn is_valid_identifier(&self, s: &str) -> bool {
-        if s.is_empty() {
-            return false;
-        }
-        let first = s.chars().next().unwrap();
-        if !first.is_alphabetic() && first != '_' {
-            return false;
-        }
-        s.chars().all(|c| c.is_alphanumeric() || c == '_')
+        crate::ident::is_valid_identifier(s)
     }
     
     /// Check if a line is a macro call (not an assignment)
@@ -0,0 +1,203 @@
+//! SHA-256 (Pure Rust, FIPS 180-4 compliant)
+//!
+//! Shared by `cargo-rustsp` (incremental manifest hashing) and the
+//! rustc output cache. Zero external dependencies.
+
+use std::fmt::Write;
+
+/// Initial hash values: first 32 bits of fractional parts of
+/// the square roots of the first 8 primes (2..19)
+const H_INIT: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+    0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Round constants: first 32 bits of fractional parts of
+/// the cube roots of the first 64 primes (2..311)
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5,
+    0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+    0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc,
+    0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+    0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+    0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3,
+    0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5,
+    0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+    0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[inline(always)]
+fn ch(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ (!x & z)
+}
+
+#[inline(always)]
+fn maj(x: u32, y: u32, z: u32) -> u32 {
+    (x & y) ^ (x & z) ^ (y & z)
+}
+
+#[inline(always)]
+fn big_sigma0(x: u32) -> u32 {
+    x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22)
+}
+
+#[inline(always)]
+fn big_sigma1(x: u32) -> u32 {
+    x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25)
+}
+
+#[inline(always)]
+fn small_sigma0(x: u32) -> u32 {
+    x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3)
+}
+
+#[inline(always)]
+fn small_sigma1(x: u32) -> u32 {
+    x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10)
+}
+
+/// Compute SHA-256 digest of input bytes, returning 32 bytes
+pub fn digest(data: &[u8]) -> [u8; 32] {
+    let bit_len = (data.len() as u64) * 8;
+
+    // Pre-processing: padding
+    // message + 1-bit + zeros + 64-bit length
+    // Total must be multiple of 64 bytes (512 bits)
+    let mut msg = data.to_vec();
+    msg.push(0x80); // append bit '1' (as byte 0x80)
+    while msg.len() % 64 != 56 {
+        msg.push(0x00);
+    }
+    // Append original length as 64-bit big-endian
+    msg.extend_from_slice(&bit_len.to_be_bytes());
+
+    // Initialize hash state
+    let mut h = H_INIT;
+
+    // Process each 512-bit (64-byte) block
+    for chunk in msg.chunks_exact(64) {
+        // Build message schedule W[0..64]
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([
+                chunk[i * 4],
+                chunk[i * 4 + 1],
+                chunk[i * 4 + 2],
+                chunk[i * 4 + 3],
+            ]);
+        }
+        for i in 16..64 {
+            w[i] = small_sigma1(w[i - 2])
+                .wrapping_add(w[i - 7])
+                .wrapping_add(small_sigma0(w[i - 15]))
+                .wrapping_add(w[i - 16]);
+        }
+
+        // Working variables
+        let mut a = h[0];
+        let mut b = h[1];
+        let mut c = h[2];
+        let mut d = h[3];
+        let mut e = h[4];
+        let mut f = h[5];
+        let mut g = h[6];
+        let mut hh = h[7];
+
+        // 64 rounds of compression
+        for i in 0..64 {
+            let t1 = hh
+                .wrapping_add(big_sigma1(e))
+                .wrapping_add(ch(e, f, g))
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let t2 = big_sigma0(a).wrapping_add(maj(a, b, c));
+
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        // Update hash state
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+        h[5] = h[5].wrapping_add(f);
+        h[6] = h[6].wrapping_add(g);
+        h[7] = h[7].wrapping_add(hh);
+    }
+
+    // Produce final digest
+    let mut result = [0u8; 32];
+    for i in 0..8 {
+        let bytes = h[i].to_be_bytes();
+        result[i * 4] = bytes[0];
+        result[i * 4 + 1] = bytes[1];
+        result[i * 4 + 2] = bytes[2];
+        result[i * 4 + 3] = bytes[3];
+    }
+    result
+}
+
+/// Compute SHA-256 of bytes and return hex string
+pub fn hash_bytes(data: &[u8]) -> String {
+    let d = digest(data);
+    let mut hex = String::with_capacity(64);
+    for byte in &d {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Compute SHA-256 of a string and return hex string
+pub fn hash_str(s: &str) -> String {
+    hash_bytes(s.as_bytes())
+}
+
+/// Compute SHA-256 of file content
+pub fn hash_file(path: &std::path::Path) -> std::io::Result<String> {
+    let data = std::fs::read(path)?;
+    Ok(hash_bytes(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_string() {
+        assert_eq!(
+            hash_str(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn test_abc() {
+        assert_eq!(
+            hash_str("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_longer_message() {
+        assert_eq!(
+            hash_str("abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+    }
+}
@@ -33,6 +33,29 @@ impl SourceMap {
         }
     }
     
+    /// Map for `preserve_lines` output, where generated line N is source
+    /// line N
+    pub fn aligned(source_file: PathBuf) -> Self {
+        let mut map = SourceMap::new(source_file);
+        map.map_line(1, 1);
+        map
+    }
+    
+    /// Map of lowered output whose lines are listed in `table`
+    pub fn from_table(source_file: PathBuf, original_content: &str, table: &LineTable) -> Self {
+        let mut map = SourceMap {
+            source_file,
+            original_content: original_content.to_string(),
+            ..SourceMap::default()
+        };
+        for (i, origin) in table.origins.iter().enumerate() {
+            if let Some(line) = origin {
+                map.map_line(i + 1, *line);
+            }
+        }
+        map
+    }
+    
     /// Record a line mapping
     pub fn map_line(&mut self, generated: usize, original: usize) {
         self.line_map.insert(generated, original);
@@ -105,6 +128,152 @@ impl SourceMap {
     }
 }
 
+/// Line mapping produced by the lowering: the `.rss` line each generated
+/// line came from, and back
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineTable {
+    /// `origins[i]`: 1-based source line of generated line `i + 1`, `None`
+    /// for lines no source line produced (blank lines, injected items)
+    origins: Vec<Option<usize>>,
+}
+
+impl LineTable {
+    pub fn new(origins: Vec<Option<usize>>) -> Self {
+        LineTable { origins }
+    }
+    
+    /// Table of `preserve_lines` output: generated line N is source line N
+    pub fn identity(lines: usize) -> Self {
+        LineTable::new((1..=lines).map(Some).collect())
+    }
+    
+    /// Number of generated lines
+    pub fn len(&self) -> usize {
+        self.origins.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.origins.is_empty()
+    }
+    
+    /// Source line of 1-based generated line `generated`. A line without
+    /// an origin of its own belongs to the nearest mapped line above it:
+    /// the body of a `rust { }` block continues its header line.
+    pub fn original_line(&self, generated: usize) -> Option<usize> {
+        let above = self.origins.get(..generated)?;
+        above.iter().rev().enumerate().find_map(|(distance, origin)| origin.map(|line| line + distance))
+    }
+    
+    /// Generated lines (1-based) that source line `original` produced
+    pub fn generated_lines(&self, original: usize) -> Vec<usize> {
+        self.origins.iter().enumerate()
+            .filter(|(_, origin)| **origin == Some(original))
+            .map(|(i, _)| i + 1)
+            .collect()
+    }
+    
+    /// Shift every generated line down by `lines` (a header was prepended)
+    pub fn offset(&mut self, lines: usize) {
+        self.origins.splice(0..0, std::iter::repeat_n(None, lines));
+    }
+}
+
+/// Match the final output back to the lowered lines it came from.
+/// `lowered` holds each line right after lowering with its 1-based source
+/// line; item ordering, inlining and `rust { }` blocks later move, drop or
+/// add lines. Lines that occur once on both sides and keep their relative
+/// order anchor the match (as in a patience diff), the lines between two
+/// anchors are matched in order, and a distinctive line left over (an
+/// import moved to the top) is looked up anywhere. Lines found nowhere get
+/// no origin.
+pub fn align_origins(lowered_lines: &[(String, usize)], output: &str) -> LineTable {
+    let lowered: Vec<&str> = lowered_lines.iter().map(|(line, _)| line.trim()).collect();
+    let output: Vec<&str> = output.lines().map(str::trim).collect();
+    let mut matched: Vec<Option<usize>> = vec![None; output.len()];
+    let mut used = vec![false; lowered.len()];
+    
+    // Anchors: unique on both sides, longest run in the same order
+    let mut counts: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+    for (i, line) in lowered.iter().enumerate() {
+        let entry = counts.entry(line).or_default();
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    for line in &output {
+        counts.entry(line).or_default().1 += 1;
+    }
+    let unique: Vec<(usize, usize)> = output.iter().enumerate()
+        .filter(|(_, line)| !line.is_empty() && matches!(counts.get(*line), Some((1, 1, _))))
+        .map(|(j, line)| (counts[line].2, j))
+        .collect();
+    for (i, j) in longest_increasing(&unique) {
+        matched[j] = Some(i);
+        used[i] = true;
+    }
+    
+    // Between anchors, in order
+    let mut next_anchor = vec![lowered.len(); output.len()];
+    let mut anchor = lowered.len();
+    for j in (0..output.len()).rev() {
+        anchor = matched[j].unwrap_or(anchor);
+        next_anchor[j] = anchor;
+    }
+    let mut cursor = 0;
+    for j in 0..output.len() {
+        if let Some(i) = matched[j] {
+            cursor = i + 1;
+            continue;
+        }
+        if output[j].is_empty() {
+            continue;
+        }
+        if let Some(i) = (cursor..next_anchor[j]).find(|&i| !used[i] && lowered[i] == output[j]) {
+            matched[j] = Some(i);
+            used[i] = true;
+            cursor = i + 1;
+        }
+    }
+    
+    // Moved lines; only a distinctive one, not `}`
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, line) in lowered.iter().enumerate().rev() {
+        if !used[i] {
+            positions.entry(line).or_default().push(i);
+        }
+    }
+    for j in 0..output.len() {
+        if matched[j].is_none() && output[j].chars().any(char::is_alphanumeric) {
+            matched[j] = positions.get_mut(output[j]).and_then(Vec::pop);
+        }
+    }
+    LineTable::new(matched.into_iter().map(|i| Some(lowered_lines[i?].1)).collect())
+}
+
+/// Longest subsequence of `pairs` (ordered by their second element) whose
+/// first elements increase
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // tails[k]: index in `pairs` ending the best run of length k + 1
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+    for (n, &(i, _)) in pairs.iter().enumerate() {
+        let k = tails.partition_point(|&t| pairs[t].0 < i);
+        previous[n] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(n);
+        } else {
+            tails[k] = n;
+        }
+    }
+    let mut run = Vec::new();
+    let mut next = tails.last().copied();
+    while let Some(n) = next {
+        run.push(pairs[n]);
+        next = previous[n];
+    }
+    run.reverse();
+    run
+}
+
 /// Builder for source maps during code generation
 #[derive(Debug, Default)]
 pub struct SourceMapBuilder {
@@ -185,9 +354,10 @@ pub fn parse_rustc_errors(stderr: &str) -> Vec<RustcError> {
                 help: Vec::new(),
             });
         }
-        // Match location: --> src/main.rs:10:5
+        // Match location: --> src/main.rs:10:5 (the first one; later ones
+        // belong to notes such as "function defined here")
         else if line.trim().starts_with("-->") {
-            if let Some(ref mut err) = current_error {
+            if let Some(err) = current_error.as_mut().filter(|err| err.line == 0) {
                 if let Some((file, line_num, col)) = parse_location(line) {
                     err.file = file;
                     err.line = line_num;
@@ -271,6 +441,63 @@ pub fn map_rustc_errors(
     }).collect()
 }
 
+/// Map the errors rustc reported in `generated_file` back to the `.rss`
+/// source through `table`. Errors in other files, or on lines the lowering
+/// did not produce (a test harness appended afterwards), are kept as they
+/// are. The column points at the same token in the source line when it can
+/// be found there, else at the start of the statement.
+pub fn map_rustc_errors_through(
+    errors: &[RustcError],
+    table: &LineTable,
+    generated_file: &str,
+    generated: &str,
+    source_map: &SourceMap,
+) -> Vec<RustcError> {
+    let generated_lines: Vec<&str> = generated.lines().collect();
+    errors.iter().map(|err| {
+        let in_generated = Path::new(&err.file).file_name() == Path::new(generated_file).file_name();
+        let origin = table.original_line(err.line).filter(|_| in_generated && err.line <= table.len());
+        let (Some(line), Some(source_line)) = (origin, origin.and_then(|l| source_map.get_source_line(l))) else {
+            return err.clone();
+        };
+        let generated_line = generated_lines.get(err.line - 1).copied().unwrap_or("");
+        RustcError {
+            file: source_map.source_file.to_string_lossy().to_string(),
+            line,
+            column: original_column(generated_line, err.column, source_line),
+            ..err.clone()
+        }
+    }).collect()
+}
+
+/// 1-based column in `source_line` of the token at 1-based `column` of
+/// `generated_line`, told apart from other occurrences by as much of the
+/// text before it as the source line shares
+fn original_column(generated_line: &str, column: usize, source_line: &str) -> usize {
+    let statement_start = source_line.len() - source_line.trim_start().len() + 1;
+    let Some(start) = generated_line.char_indices().nth(column.saturating_sub(1)).map(|(i, _)| i) else {
+        return statement_start;
+    };
+    let (before, rest) = generated_line.split_at(start);
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let token_len = match rest.chars().next() {
+        Some(c) if is_word(c) => rest.find(|c: char| !is_word(c)).unwrap_or(rest.len()),
+        _ => rest.find(char::is_whitespace).unwrap_or(rest.len()),
+    };
+    if token_len == 0 {
+        return statement_start;
+    }
+    let token = &rest[..token_len];
+    before.char_indices().map(|(i, _)| i).chain([before.len()])
+        .filter(|&i| before.len() - i <= 32)
+        .find_map(|i| {
+            let context = &before[i..];
+            let found = source_line.find(&format!("{}{}", context, token))?;
+            Some(source_line[..found + context.len()].chars().count() + 1)
+        })
+        .unwrap_or(statement_start)
+}
+
 /// Format a mapped error for display
 pub fn format_mapped_error(err: &RustcError, source_map: &SourceMap) -> String {
     let mut output = String::new();
@@ -353,4 +580,75 @@ mod tests {
         assert_eq!(map.get_original_line(2), Some(2));
         assert_eq!(map.get_original_line(4), Some(3));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_aligned_source_map() {
+        let map = SourceMap::aligned(PathBuf::from("test.rss"));
+        assert_eq!(map.get_original_line(1), Some(1));
+        assert_eq!(map.get_original_line(42), Some(42));
+    }
+    
+    #[test]
+    fn test_line_table() {
+        let mut table = LineTable::new(vec![Some(1), None, Some(3), Some(3), None]);
+        assert_eq!(table.original_line(1), Some(1));
+        assert_eq!(table.original_line(2), Some(2));
+        assert_eq!(table.original_line(4), Some(3));
+        assert_eq!(table.original_line(6), None);
+        assert_eq!(table.generated_lines(3), vec![3, 4]);
+        table.offset(2);
+        assert_eq!(table.original_line(1), None);
+        assert_eq!(table.generated_lines(1), vec![3]);
+        assert_eq!(LineTable::identity(3).original_line(2), Some(2));
+    }
+    
+    #[test]
+    fn test_align_origins() {
+        let lowered: Vec<(String, usize)> = [("fn a() {", 1), ("}", 2), ("use std::fmt;", 4), ("fn b() {", 6), ("}", 7)]
+            .iter().map(|(line, origin)| (line.to_string(), *origin)).collect();
+        let table = align_origins(&lowered, "use std::fmt;\nfn a() {\n}\n\nfn b() {\n    }");
+        assert_eq!(table.len(), 6);
+        assert_eq!((1..=6).map(|l| table.original_line(l)).collect::<Vec<_>>(),
+            [Some(4), Some(1), Some(2), Some(3), Some(6), Some(7)]);
+    }
+    
+    #[test]
+    fn test_lowered_lines_map_to_source() {
+        let source = "fn now() u64 effects(io) {\n    rust effects(io) {\n        let t = 1;\n        t\n    }\n}\n\nfn main() effects(io) {\n    a = 1; b = 2\n    println(\"{}\", now() + a + b)\n}\n\nuse std::fmt;\n";
+        let (rust, table) = crate::lower_rusts_mapped(source, &Default::default()).unwrap();
+        let lines: Vec<&str> = rust.lines().map(str::trim).collect();
+        let origin = |text: &str| table.original_line(lines.iter().position(|l| *l == text).unwrap() + 1);
+        assert_eq!(lines[0], "use std::fmt;", "{}", rust);
+        assert_eq!(origin("use std::fmt;"), Some(13));
+        assert_eq!(origin("let t = 1;"), Some(3));
+        assert_eq!(origin("t"), Some(4));
+        assert_eq!(origin("let b = 2;"), Some(9));
+        assert_eq!(origin("println!(\"{}\", now() + a + b);"), Some(10));
+        assert_eq!(table.generated_lines(9).len(), 2);
+        
+        let options = crate::LoweringOptions { license: Some("MIT".to_string()), ..Default::default() };
+        let (rust, table) = crate::lower_rusts_mapped(source, &options).unwrap();
+        let line = rust.lines().position(|l| l.trim() == "let a = 1;").unwrap() + 1;
+        assert_eq!(table.original_line(line), Some(9));
+    }
+    
+    #[test]
+    fn test_rustc_errors_mapped_to_source() {
+        let source = "fn greet(name String) String {\n    name\n}\n\nfn main() {\n    s = 1; g = greet(s)\n}\n";
+        let (rust, table) = crate::lower_rusts_mapped(source, &Default::default()).unwrap();
+        let call = rust.lines().position(|l| l.contains("greet(s")).unwrap() + 1;
+        let column = rust.lines().nth(call - 1).unwrap().find("s.clone").unwrap() + 1;
+        let stderr = format!(
+            "error[E0308]: mismatched types\n  --> prog_rusts_temp.rs:{}:{}\n   |\nnote: function defined here\n  --> prog_rusts_temp.rs:1:4\n\nerror: aborting due to 1 previous error\n",
+            call, column
+        );
+        let errors = parse_rustc_errors(&stderr);
+        assert_eq!(errors[0].line, call);
+        
+        let map = SourceMap::from_table(PathBuf::from("prog.rss"), source, &table);
+        let mapped = map_rustc_errors_through(&errors, &table, "prog_rusts_temp.rs", &rust, &map);
+        assert_eq!((mapped[0].file.as_str(), mapped[0].line, mapped[0].column), ("prog.rss", 6, 22));
+        assert_eq!(mapped[1].line, 0);
+        assert!(format_mapped_error(&mapped[0], &map).contains("  6 |     s = 1; g = greet(s)"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Several Statements on One Line
+//!
+//! Code written the Rust way puts short statements on one line:
+//!
+//! ```text
+//! a = 1; b = 2          →  let a = 1;
+//!                          let b = 2;
+//! ```
+//!
+//! The lowering reads one statement per line, so such a line is split on
+//! its top-level semicolons (outside strings, parentheses, brackets and
+//! braces) before lowering, and every statement gets its own assignment
+//! detection. The origin of each resulting line is kept: diagnostics still
+//! name the source line, and `--preserve-lines` puts the statements back on
+//! one output line.
+
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+
+/// Split every multi-statement line of `source`. Returns the new source and
+/// the (0-based) source line of each of its lines.
+pub fn split_statements(source: &str) -> (String, Vec<usize>) {
+    let mut lines = Vec::new();
+    let mut origins = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        match split_statement_line(line) {
+            Some(statements) => {
+                origins.extend(std::iter::repeat_n(i, statements.len()));
+                lines.extend(statements);
+            }
+            None => {
+                origins.push(i);
+                lines.push(line.to_string());
+            }
+        }
+    }
+    (lines.join("\n"), origins)
+}
+
+/// `a = 1; b = 2` → `["a = 1", "b = 2"]` (indented like the line, the
+/// inline comment on the last one); None for a line with one statement
+pub fn split_statement_line(line: &str) -> Option<Vec<String>> {
+    let code = strip_inline_comment(line);
+    let comment = line[code.len()..].trim_end();
+    let body = code.trim_start();
+    let indent = &code[..code.len() - body.len()];
+    if body.starts_with("#[") {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut statements: Vec<&str> = Vec::new();
+    let mut start = 0;
+    for (pos, c) in delimiter_positions(body, &['(', ')', '[', ']', '{', '}', ';']) {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ if depth == 0 => {
+                statements.push(&body[start..pos]);
+                start = pos + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&body[start..]);
+    let statements: Vec<&str> = statements.into_iter().map(str::trim).filter(|s| !s.is_empty()).collect();
+    if statements.len() < 2 {
+        return None;
+    }
+
+    let last = statements.len() - 1;
+    Some(statements
+        .iter()
+        .enumerate()
+        .map(|(i, statement)| match i == last && !comment.is_empty() {
+            true => format!("{}{} {}", indent, statement, comment.trim_start()),
+            false => format!("{}{}", indent, statement),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_statement_line() {
+        assert_eq!(split_statement_line("    a = 1; b = 2"), Some(vec!["    a = 1".to_string(), "    b = 2".to_string()]));
+        assert_eq!(
+            split_statement_line("x = f(a; b); y = \"p; q\";  // two"),
+            Some(vec!["x = f(a; b)".to_string(), "y = \"p; q\" // two".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_single_statement_untouched() {
+        assert_eq!(split_statement_line("c += b;"), None);
+        assert_eq!(split_statement_line("for i in 0..3 { c += i; d += 1; }"), None);
+        assert_eq!(split_statement_line("arr = [0; 8]"), None);
+        assert_eq!(split_statement_line("s = \"a; b\""), None);
+        assert_eq!(split_statement_line("// a = 1; b = 2"), None);
+    }
+
+    #[test]
+    fn test_origins() {
+        let (split, origins) = split_statements("fn f() {\n    a = 1; b = 2\n}");
+        assert_eq!(split, "fn f() {\n    a = 1\n    b = 2\n}");
+        assert_eq!(origins, vec![0, 1, 1, 2]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Streaming Lowering for Very Large Inputs
+//!
+//! [`crate::parse_rusts`] holds the whole source, every output line and the
+//! joined result in memory at once. Generated `.rss` files of several hundred
+//! MB make that impractical, so [`lower_rusts_streaming`] works in pieces:
+//!
+//! 1. A preliminary scan reads the input once, piece by piece, and merges
+//!    the first pass of every piece (function signatures, types, clone
+//!    requirements) into whole-program registries.
+//! 2. The input is rewound and each piece is lowered against those
+//!    registries and written to the sink before the next one is read.
+//!
+//! A piece is a run of complete top-level items of at least
+//! [`STREAM_CHUNK_LINES`] lines, so memory is bounded by the piece size (or
+//! the largest single item) plus the registries.
+//!
+//! Item ordering only applies within a piece: a `use` placed after the
+//! piece that needs it is not hoisted. Code generators can emit items in
+//! order to begin with. The one exception is the `HashMap` import of map
+//! literals, which the scan finds and writes once, first.
+
+use std::io::{self, BufRead, Seek, SeekFrom, Write};
+
+use crate::enum_def::is_enum_definition;
+use crate::error_msg::RsplError;
+use crate::first_pass::{run_first_pass, FirstPassResult};
+use crate::helpers::strip_inline_comment;
+use crate::map_literals::imports_map;
+use crate::lowering::continuation_lowering::ends_with_binary_operator;
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, update_multiline_depth};
+use crate::struct_def::is_struct_definition;
+use crate::transpile_main::{lower_rusts_in_program, output_header, preprocess, LoweringOptions};
+use crate::variable::VariableTracker;
+
+/// Minimum number of source lines lowered at a time
+pub const STREAM_CHUNK_LINES: usize = 4096;
+
+/// Failure of a streaming lowering run
+#[derive(Debug)]
+pub enum StreamingError {
+    /// Reading the input or writing the sink failed
+    Io(io::Error),
+    /// Some pieces could not be lowered. They were written as
+    /// `compile_error!` lines; the rest of the output is complete.
+    Lowering(Vec<RsplError>),
+}
+
+impl From<io::Error> for StreamingError {
+    fn from(e: io::Error) -> Self {
+        StreamingError::Io(e)
+    }
+}
+
+/// Lower RustS+ read from `input` to Rust written to `output`, one piece at
+/// a time. `input` is read twice (registry scan, then lowering), hence `Seek`.
+pub fn lower_rusts_streaming<R: BufRead + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    options: &LoweringOptions,
+) -> Result<(), StreamingError> {
+    lower_streaming_in_chunks(input, output, options, STREAM_CHUNK_LINES)
+}
+
+fn lower_streaming_in_chunks<R: BufRead + Seek, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    options: &LoweringOptions,
+    chunk_lines: usize,
+) -> Result<(), StreamingError> {
+    // Pass 1: whole-program registries
+    let mut program = FirstPassResult::default();
+    let mut type_def_lines: Vec<String> = Vec::new();
+    // Map literals lowered without their import, and a user import of it
+    let (mut needs_map_import, mut imports_map_type) = (None, false);
+    for_each_chunk(input, chunk_lines, |chunk, _| {
+        let preprocessed = preprocess(chunk, options);
+        needs_map_import = needs_map_import.or(preprocessed.map_import);
+        imports_map_type |= imports_map(chunk);
+        let lines: Vec<&str> = preprocessed.source.lines().collect();
+        let mut piece = run_first_pass(&lines, &mut VariableTracker::new());
+        for sig in preprocessed.rust_fn_signatures {
+            piece.fn_registry.register(sig);
+        }
+        program.merge(piece);
+        type_def_lines.extend(type_definition_lines(&lines));
+        Ok(())
+    })?;
+    let type_def_lines: Vec<&str> = type_def_lines.iter().map(String::as_str).collect();
+    program.propagate_clones(&type_def_lines);
+
+    // Pass 2: lower and write each piece
+    input.seek(SeekFrom::Start(0))?;
+    if let Some(header) = output_header(options) {
+        write!(output, "{}", header)?;
+    }
+    if let Some(import) = needs_map_import.filter(|_| !imports_map_type) {
+        writeln!(output, "{}", import)?;
+    }
+    let mut errors = Vec::new();
+    for_each_chunk(input, chunk_lines, |chunk, first_line| {
+        match lower_rusts_in_program(chunk, options, &mut program) {
+            Ok(rust) => writeln!(output, "{}", rust)?,
+            Err(piece_errors) => {
+                for mut error in piece_errors {
+                    shift_lines(&mut error, first_line);
+                    writeln!(output, "compile_error!({:?});", format!(
+                        "RustS+ lowering error at line {}: {}", error.location.line, error.title
+                    ))?;
+                    errors.push(error);
+                }
+            }
+        }
+        Ok(())
+    })?;
+    output.flush()?;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(StreamingError::Lowering(errors))
+    }
+}
+
+/// Call `f` with each piece of complete top-level items and the 0-based
+/// line number of its first line
+pub(crate) fn for_each_chunk<R: BufRead>(
+    input: &mut R,
+    chunk_lines: usize,
+    mut f: impl FnMut(&str, usize) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut chunk = String::new();
+    let mut chunk_len = 0;
+    let mut first_line = 0;
+    let mut brace_depth = 0usize;
+    let mut paren_depth = 0i32;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        chunk.push_str(&line);
+        chunk_len += 1;
+
+        let clean = strip_inline_comment(line.trim_end_matches(['\n', '\r']));
+        let trimmed = clean.trim();
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        brace_depth = (brace_depth + opens).saturating_sub(closes);
+        update_multiline_depth(&mut paren_depth, trimmed);
+
+        if chunk_len >= chunk_lines && at_item_boundary(trimmed, brace_depth, paren_depth) {
+            f(&chunk, first_line)?;
+            first_line += chunk_len;
+            chunk.clear();
+            chunk_len = 0;
+        }
+    }
+    if !chunk.is_empty() {
+        f(&chunk, first_line)?;
+    }
+    Ok(())
+}
+
+/// Can the input be split after this line without cutting an item?
+fn at_item_boundary(trimmed: &str, brace_depth: usize, paren_depth: i32) -> bool {
+    brace_depth == 0
+        && paren_depth == 0
+        && !trimmed.ends_with('=')
+        && !ends_with_binary_operator(trimmed)
+        // Attributes and doc comments belong to the item that follows
+        && !trimmed.starts_with("#[")
+        && !trimmed.starts_with("///")
+}
+
+/// Lines of every struct/enum definition in a piece (for cross-piece
+/// transitive clone detection)
+fn type_definition_lines(lines: &[&str]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    let mut inside = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if depth == 0 && (is_struct_definition(trimmed) || is_enum_definition(trimmed)) {
+            inside = true;
+        }
+        if inside {
+            out.push(line.to_string());
+        }
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        depth = (depth + opens).saturating_sub(closes);
+        if depth == 0 {
+            inside = false;
+        }
+    }
+    out
+}
+
+/// Make a piece-relative diagnostic refer to the whole input
+fn shift_lines(error: &mut RsplError, offset: usize) {
+    error.location.line += offset;
+    for (location, _) in &mut error.labels {
+        location.line += offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn stream(source: &str, chunk_lines: usize) -> (String, Result<(), StreamingError>) {
+        let mut out = Vec::new();
+        let result = lower_streaming_in_chunks(
+            &mut Cursor::new(source.as_bytes()), &mut out, &LoweringOptions::default(), chunk_lines,
+        );
+        (String::from_utf8(out).unwrap(), result)
+    }
+
+    const PROGRAM: &str = r#"struct Event {
+    id u32
+}
+
+fn main() {
+    events = [
+        Event { id = 1 },
+        Event { id = 2 }
+    ]
+    first = events[0]
+    total = add(1, 2)
+    label = describe("x")
+}
+
+fn add(a i32, b i32) i32 {
+    a + b
+}
+
+fn describe(s String) String {
+    s
+}
+"#;
+
+    #[test]
+    fn test_streaming_matches_whole_file_lowering() {
+        let whole = crate::parse_rusts(PROGRAM);
+        let (streamed, result) = stream(PROGRAM, 1);
+        assert!(result.is_ok());
+        // Item-by-item pieces still see every signature and clone requirement
+        assert!(streamed.contains("#[derive(Clone)]"), "Clone derive lost: {}", streamed);
+        assert!(streamed.contains("describe(String::from(\"x\"))"), "Later signature not used: {}", streamed);
+        assert_eq!(
+            streamed.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>(),
+            whole.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_streaming_runs_the_lowering_pre_passes() {
+        let source = "fn ages() HashMap[String, i32] {\n    { \"ann\": 31 }\n}\n\nfn parse(s String) !i32 {\n    n = s.parse[i32]()?\n    Ok(n)\n}\n\nfn main() {\n    n = parse(String::from(\"1\"))\n}\n";
+        let whole = crate::parse_rusts(source);
+        let (streamed, result) = stream(source, 1);
+        assert!(result.is_ok(), "{}", streamed);
+        assert!(streamed.starts_with("use std::collections::HashMap;\n"), "Map import not written first: {}", streamed);
+        assert_eq!(streamed.matches("use std::collections::HashMap;").count(), 1, "{}", streamed);
+        assert!(streamed.contains("fn parse(s: String) -> Result<i32, Box<dyn std::error::Error>> {"), "{}", streamed);
+        assert!(whole.contains("fn parse(s: String) -> Result<i32, Box<dyn std::error::Error>> {"), "{}", whole);
+
+        let (imported, _) = stream(&format!("use std::collections::HashMap\n\n{}", source), 1);
+        assert_eq!(imported.matches("use std::collections::HashMap;").count(), 1, "{}", imported);
+    }
+
+    #[test]
+    fn test_chunks_split_only_between_items() {
+        let mut starts = Vec::new();
+        for_each_chunk(&mut Cursor::new(PROGRAM.as_bytes()), 1, |chunk, first| {
+            starts.push((first, chunk.lines().next().unwrap_or("").to_string()));
+            Ok(())
+        }).unwrap();
+        let firsts: Vec<usize> = starts.iter().map(|(f, _)| *f).collect();
+        assert_eq!(firsts, vec![0, 3, 4, 13, 14, 17, 18]);
+        assert!(starts.iter().all(|(_, l)| !l.starts_with(' ')), "Chunk split inside an item: {:?}", starts);
+    }
+
+    #[test]
+    fn test_streaming_error_lines_are_absolute() {
+        let source = "fn ok() i32 {\n    1\n}\n\nfn bad(x) {\n    x\n}\n";
+        let (out, result) = stream(source, 1);
+        let Err(StreamingError::Lowering(errors)) = result else { panic!("Expected lowering error") };
+        assert_eq!(errors[0].location.line, 5);
+        assert!(out.contains("fn ok() -> i32"), "Good piece not written: {}", out);
+        assert!(out.contains("compile_error!(\"RustS+ lowering error at line 5"), "Error not emitted: {}", out);
+    }
+}
@@ -0,0 +1,273 @@
+//! In-Place String Building (`--opt-level 2`)
+//!
+//! `msg = msg + name` builds a new `String` on every pass of a loop. With
+//! [`crate::LoweringOptions::opt_level`] at 2 or above, self-appends on
+//! `String` bindings are lowered to `push_str` instead:
+//!
+//! ```text
+//! msg = msg + &name + ", ";   →   msg.push_str(&name); msg.push_str(", ");
+//! let s = s + "a";            →   s.push_str("a");
+//! ```
+//!
+//! A binding counts as a `String` when it is declared in the same function
+//! with a `String` annotation or a `String::..`, `format!` or `.to_string()`
+//! initializer, or when a string literal is appended to it (`+` on anything
+//! else does not take a `&str`). Its declaration is made `mut` if it is not.
+//! `push_str` may grow the buffer, so the function keeps its `alloc` effect;
+//! effects are checked on the .rss source, which is unchanged.
+//!
+//! Left alone: appends spanning several lines, lines holding more than one
+//! statement, and bindings declared by a pattern.
+
+use std::collections::HashMap;
+
+use crate::ident::is_valid_identifier;
+
+/// Where a binding of the current function is declared
+#[derive(Debug, Clone, Copy)]
+struct Declaration {
+    /// Output line (0-based)
+    line: usize,
+    is_mut: bool,
+    is_string: bool,
+    is_param: bool,
+}
+
+/// Lower `X = X + a + b;` on `String` bindings to `push_str` calls
+pub fn append_in_place(rust: &str) -> String {
+    let mut out: Vec<String> = rust.lines().map(String::from).collect();
+    let mut declarations: HashMap<String, Declaration> = HashMap::new();
+    for i in 0..out.len() {
+        if let Some(params) = fn_params(&out[i]) {
+            declarations = params.into_iter()
+                .map(|(name, is_mut, ty)| (name, Declaration { line: i, is_mut, is_string: ty == "String", is_param: true }))
+                .collect();
+            continue;
+        }
+        if let Some(append) = parse_self_append(&out[i]) {
+            let known = declarations.get(&append.target).copied();
+            if let Some(decl) = known.filter(|d| d.is_string || append.proves_string()) {
+                let leading_ws = &out[i][..out[i].len() - out[i].trim_start().len()];
+                let calls: Vec<String> = append.operands.iter()
+                    .map(|operand| format!("{}.push_str({});", append.target, operand))
+                    .collect();
+                out[i] = format!("{}{}", leading_ws, calls.join(" "));
+                if !decl.is_mut {
+                    out[decl.line] = mark_mut(&out[decl.line], &append.target, decl.is_param);
+                    declarations.insert(append.target, Declaration { is_mut: true, ..decl });
+                }
+                continue;
+            }
+        }
+        if let Some((name, decl)) = parse_let(&out[i], i) {
+            declarations.insert(name, decl);
+        }
+    }
+    let mut result = out.join("\n");
+    if rust.ends_with('\n') {
+        result.push('\n');
+    }
+    result
+}
+
+/// `[let] X[: String] = X + a + b;`
+#[derive(Debug, Clone, PartialEq)]
+struct SelfAppend {
+    target: String,
+    operands: Vec<String>,
+}
+
+impl SelfAppend {
+    /// Only `String` takes a string literal on the right of `+`
+    fn proves_string(&self) -> bool {
+        self.operands.first().is_some_and(|op| op.starts_with('"'))
+    }
+}
+
+fn parse_self_append(line: &str) -> Option<SelfAppend> {
+    let stmt = line.trim().strip_suffix(';')?;
+    let (lhs, rhs) = split_assignment(stmt)?;
+    let lhs = lhs.trim();
+    let target = match lhs.strip_prefix("let ") {
+        Some(rest) => match rest.split_once(':') {
+            Some((name, ty)) if ty.trim() == "String" => name.trim(),
+            Some(_) => return None,
+            None => rest.trim(),
+        },
+        None => lhs,
+    };
+    if !is_valid_identifier(target) {
+        return None;
+    }
+    let mut terms = split_top_level_plus(rhs.trim())?.into_iter();
+    if terms.next()? != target {
+        return None;
+    }
+    let operands: Vec<String> = terms.collect();
+    if operands.is_empty() || operands.iter().any(|op| op.is_empty() || op.contains(';')) {
+        return None;
+    }
+    Some(SelfAppend { target: target.to_string(), operands })
+}
+
+/// Split `lhs = rhs` at the first `=` that is not part of an operator
+fn split_assignment(stmt: &str) -> Option<(&str, &str)> {
+    let bytes = stmt.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'"' | b'(' | b'[' | b'{' => return None,
+            b'=' => {
+                let prev = i.checked_sub(1).map(|p| bytes[p]);
+                let next = bytes.get(i + 1).copied();
+                if next == Some(b'=') || matches!(prev, Some(b'=' | b'!' | b'<' | b'>' | b'+' | b'-' | b'*' | b'/' | b'%' | b'&' | b'|' | b'^')) {
+                    return None;
+                }
+                return Some((&stmt[..i], &stmt[i + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Terms of a sum, or `None` when the expression has another top-level
+/// operator or unbalanced brackets
+fn split_top_level_plus(expr: &str) -> Option<Vec<String>> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0i32;
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                current.push(c);
+                let mut escaped = false;
+                loop {
+                    let s = chars.next()?;
+                    current.push(s);
+                    if escaped {
+                        escaped = false;
+                    } else if s == '\\' {
+                        escaped = true;
+                    } else if s == '"' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth < 0 {
+                    return None;
+                }
+            }
+            '+' if depth == 0 => {
+                terms.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            '-' | '*' | '/' | '%' | '<' | '>' | '|' | '^' | '=' | '?' if depth == 0 => return None,
+            '&' if depth == 0 && chars.peek() == Some(&'&') => return None,
+            _ => {}
+        }
+        current.push(c);
+    }
+    if depth != 0 {
+        return None;
+    }
+    terms.push(current.trim().to_string());
+    Some(terms)
+}
+
+/// `let [mut] X[: T] = init;` on one line
+fn parse_let(line: &str, line_idx: usize) -> Option<(String, Declaration)> {
+    let rest = line.trim().strip_prefix("let ")?;
+    let (is_mut, rest) = match rest.strip_prefix("mut ") {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let (binding, init) = split_assignment(rest.trim_end().strip_suffix(';')?)?;
+    let (name, ty) = match binding.split_once(':') {
+        Some((name, ty)) => (name.trim(), Some(ty.trim())),
+        None => (binding.trim(), None),
+    };
+    if !is_valid_identifier(name) {
+        return None;
+    }
+    let init = init.trim();
+    let is_string = ty == Some("String")
+        || (ty.is_none() && (init.starts_with("String::") || init.starts_with("format!(") || init.ends_with(".to_string()")));
+    Some((name.to_string(), Declaration { line: line_idx, is_mut, is_string, is_param: false }))
+}
+
+/// Parameters of a function signature line: `(name, is_mut, type)`
+fn fn_params(line: &str) -> Option<Vec<(String, bool, String)>> {
+    let mut rest = line.trim();
+    for qualifier in ["pub(crate) ", "pub ", "const ", "async ", "unsafe "] {
+        rest = rest.strip_prefix(qualifier).unwrap_or(rest);
+    }
+    let rest = rest.strip_prefix("fn ")?;
+    let open = rest.find('(')?;
+    let close = rest.rfind(')')?;
+    if close < open {
+        return None;
+    }
+    Some(rest[open + 1..close].split(',')
+        .filter_map(|param| {
+            let (name, ty) = param.split_once(':')?;
+            let name = name.trim();
+            let (is_mut, name) = match name.strip_prefix("mut ") {
+                Some(name) => (true, name.trim()),
+                None => (false, name),
+            };
+            is_valid_identifier(name).then(|| (name.to_string(), is_mut, ty.trim().to_string()))
+        })
+        .collect())
+}
+
+/// Make the declaration of `name` on `line` mutable
+fn mark_mut(line: &str, name: &str, is_param: bool) -> String {
+    let (from, to) = if is_param {
+        (format!("({}:", name), format!("(mut {}:", name))
+    } else {
+        (format!("let {}", name), format!("let mut {}", name))
+    };
+    if line.contains(&from) {
+        return line.replacen(&from, &to, 1);
+    }
+    // A parameter after the first
+    line.replacen(&format!(", {}:", name), &format!(", mut {}:", name), 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_append_becomes_push_str() {
+        let rust = "fn greet(names: Vec<String>) -> String {\nlet mut msg = String::from(\"hi\");\nfor name in names {\nmsg = msg + &name + \", \";\n}\nmsg\n}\n";
+        let out = append_in_place(rust);
+        assert!(out.contains("msg.push_str(&name); msg.push_str(\", \");"), "{}", out);
+        assert!(out.ends_with("msg\n}\n"));
+    }
+
+    #[test]
+    fn test_shadowing_append_marks_declaration_mut() {
+        let rust = "fn tag(name: &str) -> String {\nlet label = name.to_string();\nlet label = label + \"!\";\nlabel\n}";
+        let out = append_in_place(rust);
+        assert_eq!(out, "fn tag(name: &str) -> String {\nlet mut label = name.to_string();\nlabel.push_str(\"!\");\nlabel\n}");
+
+        let param = append_in_place("fn f(n: i32, s: String) -> String {\ns = s + \"x\";\ns\n}");
+        assert!(param.starts_with("fn f(n: i32, mut s: String)"), "{}", param);
+    }
+
+    #[test]
+    fn test_non_string_bindings_left_alone() {
+        let rust = "fn f(x: i32) -> i32 {\nlet mut total = 1;\ntotal = total + &x;\nlet mut s = String::new();\ns = s + a - b;\ntotal\n}";
+        assert_eq!(append_in_place(rust), rust);
+        // A binding of another function
+        let other = "fn a() {\nlet mut s = String::new();\n}\nfn b(s: Vec<u8>) {\ns = s + &t;\n}";
+        assert_eq!(append_in_place(other), other);
+    }
+}
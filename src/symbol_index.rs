@@ -0,0 +1,447 @@
+//! Workspace Symbol Index (`.rustsp-index`)
+//!
+//! `rustsp index <dir>` records every function, struct, enum, constant and
+//! local binding of the `.rss` files below `<dir>` - where it is defined,
+//! where it is referenced and, for functions, the declared effects - in
+//! `<dir>/.rustsp-index`. Tooling (the language server, project-wide rename)
+//! queries the index instead of re-analyzing the project on every request:
+//!
+//! - [`SymbolIndex::find_definition`]: the symbol under a position
+//! - [`SymbolIndex::find_references`]: every other occurrence of it
+//!
+//! Symbols are resolved per file exactly like `rustsp rename` resolves them
+//! (see [`crate::rename`]). A name a file uses but does not define is kept
+//! as *unresolved*; it counts as a reference to the function, type or
+//! constant of that name defined in another file.
+//!
+//! ## Incremental updates
+//!
+//! Every file entry stores the SHA-256 of the source it was built from.
+//! [`SymbolIndex::update`] re-indexes only files whose hash changed, indexes
+//! new files and drops deleted ones.
+//!
+//! ## Format (version 1)
+//!
+//! ```text
+//! {
+//!   "format": "rustsp-index",
+//!   "version": 1,
+//!   "files": [
+//!     {
+//!       "path": "bank/account.rss",
+//!       "hash": "9f86d081884c7d65...",
+//!       "symbols": [
+//!         {"name": "deposit", "kind": "function", "scope": null, "line": 3, "column": 4,
+//!          "effects": ["write(acc)"], "references": [[12, 10]]},
+//!         {"name": "total", "kind": "binding", "scope": "deposit", "line": 4, "column": 5,
+//!          "effects": [], "references": [[5, 5]]}
+//!       ],
+//!       "unresolved": [["audit", 6, 5]]
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are relative to the indexed directory with `/` separators;
+//! positions are 1-based lines and character columns of the identifier.
+//! Readers must reject a different `format` or a newer `version`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::anti_fail_logic::analyze_functions;
+use crate::batch_analysis::find_rss_files;
+use crate::lowered_ir::{json_opt, json_str, parse_json, Json};
+use crate::rename::{collect_symbols, SymbolKind};
+use crate::sha256;
+
+/// File name of the index, written into the indexed directory
+pub const INDEX_FILE: &str = ".rustsp-index";
+
+const INDEX_FORMAT: &str = "rustsp-index";
+const INDEX_VERSION: u64 = 1;
+
+/// Position of an identifier: file relative to the indexed directory,
+/// 1-based line and character column
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A function, type, constant or binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Function declaring a binding (`None` for items)
+    pub scope: Option<String>,
+    pub definition: Location,
+    /// Declared effects of a function (`io`, `write(acc)`, ...)
+    pub effects: Vec<String>,
+    /// Occurrences other than the definition, within the defining file
+    pub references: Vec<Location>,
+}
+
+impl Symbol {
+    /// Is `location` inside this symbol's definition or one of its references?
+    fn covers(&self, file: &str, line: usize, column: usize) -> bool {
+        let width = self.name.chars().count();
+        std::iter::once(&self.definition)
+            .chain(&self.references)
+            .any(|loc| loc.file == file && loc.line == line && (loc.column..loc.column + width).contains(&column))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct FileEntry {
+    hash: String,
+    symbols: Vec<Symbol>,
+    /// Names used but not defined in the file: (name, line, column)
+    unresolved: Vec<(String, usize, usize)>,
+}
+
+/// What [`SymbolIndex::update`] did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpdateStats {
+    /// New or changed files (re-indexed)
+    pub indexed: usize,
+    /// Files whose entry was kept
+    pub unchanged: usize,
+    /// Entries of deleted files
+    pub removed: usize,
+}
+
+/// Symbols of every `.rss` file in a directory tree, by relative path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolIndex {
+    files: BTreeMap<String, FileEntry>,
+}
+
+impl SymbolIndex {
+    /// Load `<root>/.rustsp-index` (if any), bring it up to date with the
+    /// sources and write it back. An unreadable index is rebuilt.
+    pub fn open(root: &Path) -> Result<(SymbolIndex, UpdateStats), String> {
+        let mut index = SymbolIndex::load(root).unwrap_or_default();
+        let stats = index.update(root);
+        index.save(root).map_err(|e| format!("writing {}: {}", root.join(INDEX_FILE).display(), e))?;
+        Ok((index, stats))
+    }
+
+    /// Read `<root>/.rustsp-index`; an empty index when there is none
+    pub fn load(root: &Path) -> Result<SymbolIndex, String> {
+        match fs::read_to_string(root.join(INDEX_FILE)) {
+            Ok(json) => SymbolIndex::from_json(&json),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SymbolIndex::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Write `<root>/.rustsp-index`
+    pub fn save(&self, root: &Path) -> io::Result<()> {
+        fs::write(root.join(INDEX_FILE), self.to_json())
+    }
+
+    /// Re-index the `.rss` files below `root` that changed since they were
+    /// last indexed, and forget the ones that no longer exist
+    pub fn update(&mut self, root: &Path) -> UpdateStats {
+        let mut stats = UpdateStats::default();
+        let mut present = Vec::new();
+        for path in find_rss_files(root) {
+            let Ok(source) = fs::read_to_string(&path) else { continue };
+            let rel = path.strip_prefix(root).unwrap_or(&path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if self.files.get(&rel).is_some_and(|entry| entry.hash == sha256::hash_str(&source)) {
+                stats.unchanged += 1;
+            } else {
+                self.index_file(&rel, &source);
+                stats.indexed += 1;
+            }
+            present.push(rel);
+        }
+        let before = self.files.len();
+        self.files.retain(|rel, _| present.contains(rel));
+        stats.removed = before - self.files.len();
+        stats
+    }
+
+    /// (Re)index one file from its source, e.g. an editor buffer
+    pub fn index_file(&mut self, file: &str, source: &str) {
+        let collected = collect_symbols(source);
+        let functions = analyze_functions(source, file);
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let position = |offset: usize| {
+            let line = line_starts.partition_point(|&start| start <= offset);
+            let column = source[line_starts[line - 1]..offset].chars().count() + 1;
+            (line, column)
+        };
+        let location = |offset: usize| {
+            let (line, column) = position(offset);
+            Location { file: file.to_string(), line, column }
+        };
+
+        let symbols = collected.symbols.iter()
+            .map(|s| {
+                let effects = match (s.kind, functions.get(&s.name)) {
+                    (SymbolKind::Function, Some(info)) if !info.declared_effects.effects.is_empty() => {
+                        info.declared_effects.display().split(", ").map(str::to_string).collect()
+                    }
+                    _ => Vec::new(),
+                };
+                Symbol {
+                    name: s.name.clone(),
+                    kind: s.kind,
+                    scope: s.scope.clone(),
+                    definition: location(s.definition),
+                    effects,
+                    references: s.references.iter().map(|&r| location(r)).collect(),
+                }
+            })
+            .collect();
+        let unresolved = collected.unresolved.iter()
+            .map(|(name, offset)| {
+                let (line, column) = position(*offset);
+                (name.clone(), line, column)
+            })
+            .collect();
+        self.files.insert(file.to_string(), FileEntry { hash: sha256::hash_str(source), symbols, unresolved });
+    }
+
+    /// Every indexed symbol, by file
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.files.values().flat_map(|entry| &entry.symbols)
+    }
+
+    /// Indexed files (relative paths)
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// The symbol whose definition or reference is at `file:line:column`.
+    /// A name the file does not define resolves to the function, type or
+    /// constant of that name in another file.
+    pub fn find_definition(&self, file: &str, line: usize, column: usize) -> Option<&Symbol> {
+        let entry = self.files.get(file)?;
+        if let Some(symbol) = entry.symbols.iter().find(|s| s.covers(file, line, column)) {
+            return Some(symbol);
+        }
+        let (name, _, _) = entry.unresolved.iter().find(|(name, l, c)| {
+            *l == line && (*c..*c + name.chars().count()).contains(&column)
+        })?;
+        self.symbols().find(|s| s.kind != SymbolKind::Binding && &s.name == name)
+    }
+
+    /// Every occurrence of the symbol at `file:line:column` except its
+    /// definition, across all indexed files, sorted by position
+    pub fn find_references(&self, file: &str, line: usize, column: usize) -> Vec<Location> {
+        let Some(symbol) = self.find_definition(file, line, column) else {
+            return Vec::new();
+        };
+        let mut references = symbol.references.clone();
+        if symbol.kind != SymbolKind::Binding {
+            for (path, entry) in &self.files {
+                references.extend(entry.unresolved.iter()
+                    .filter(|(name, _, _)| *name == symbol.name)
+                    .map(|(_, line, column)| Location { file: path.clone(), line: *line, column: *column }));
+            }
+        }
+        references.sort();
+        references
+    }
+
+    /// Serialize to the versioned JSON format
+    pub fn to_json(&self) -> String {
+        let location = |l: &Location| format!("[{}, {}]", l.line, l.column);
+        let files: Vec<String> = self.files.iter().map(|(path, entry)| {
+            let symbols: Vec<String> = entry.symbols.iter().map(|s| {
+                let effects: Vec<String> = s.effects.iter().map(|e| json_str(e)).collect();
+                let references: Vec<String> = s.references.iter().map(location).collect();
+                format!(
+                    "        {{\"name\": {}, \"kind\": \"{}\", \"scope\": {}, \"line\": {}, \"column\": {}, \"effects\": [{}], \"references\": [{}]}}",
+                    json_str(&s.name), s.kind.as_str(),
+                    json_opt(s.scope.as_deref()),
+                    s.definition.line, s.definition.column, effects.join(", "), references.join(", ")
+                )
+            }).collect();
+            let unresolved: Vec<String> = entry.unresolved.iter()
+                .map(|(name, line, column)| format!("[{}, {}, {}]", json_str(name), line, column))
+                .collect();
+            format!(
+                "    {{\n      \"path\": {},\n      \"hash\": \"{}\",\n      \"symbols\": [\n{}\n      ],\n      \"unresolved\": [{}]\n    }}",
+                json_str(path), entry.hash, symbols.join(",\n"), unresolved.join(", ")
+            )
+        }).collect();
+        format!(
+            "{{\n  \"format\": \"{}\",\n  \"version\": {},\n  \"files\": [\n{}\n  ]\n}}\n",
+            INDEX_FORMAT, INDEX_VERSION, files.join(",\n")
+        )
+    }
+
+    /// Read a document written by [`SymbolIndex::to_json`]
+    pub fn from_json(json: &str) -> Result<SymbolIndex, String> {
+        let doc = parse_json(json)?;
+        let format = doc.get("format").and_then(Json::as_str).ok_or("missing \"format\"")?;
+        if format != INDEX_FORMAT {
+            return Err(format!("unknown index format \"{}\" (expected \"{}\")", format, INDEX_FORMAT));
+        }
+        let version = doc.get("version").and_then(Json::as_u64).ok_or("missing \"version\"")?;
+        if version > INDEX_VERSION {
+            return Err(format!("index version {} is newer than this compiler supports ({})", version, INDEX_VERSION));
+        }
+
+        let mut files = BTreeMap::new();
+        for file in doc.get("files").and_then(Json::as_array).ok_or("missing \"files\"")? {
+            let path = file.get("path").and_then(Json::as_str).ok_or("file: missing \"path\"")?;
+            let entry = file_from_json(path, file).map_err(|e| format!("{}: {}", path, e))?;
+            files.insert(path.to_string(), entry);
+        }
+        Ok(SymbolIndex { files })
+    }
+}
+
+fn file_from_json(path: &str, file: &Json) -> Result<FileEntry, String> {
+    let number = |value: Option<&Json>| value.and_then(Json::as_u64).map(|n| n as usize);
+    let array = |value: &Json, key: &str| value.get(key).and_then(Json::as_array).unwrap_or(&[]).to_vec();
+    let location = |pair: &Json| -> Option<Location> {
+        let [line, column] = pair.as_array()? else { return None };
+        Some(Location { file: path.to_string(), line: number(Some(line))?, column: number(Some(column))? })
+    };
+
+    let mut symbols = Vec::new();
+    for symbol in array(file, "symbols") {
+        let name = symbol.get("name").and_then(Json::as_str).ok_or("symbol: missing \"name\"")?;
+        let kind = symbol.get("kind").and_then(Json::as_str).and_then(SymbolKind::from_name)
+            .ok_or(format!("`{}`: unknown kind", name))?;
+        let definition = Location {
+            file: path.to_string(),
+            line: number(symbol.get("line")).ok_or(format!("`{}`: missing \"line\"", name))?,
+            column: number(symbol.get("column")).ok_or(format!("`{}`: missing \"column\"", name))?,
+        };
+        symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            scope: symbol.get("scope").and_then(Json::as_str).map(str::to_string),
+            definition,
+            effects: array(&symbol, "effects").iter().filter_map(|e| e.as_str().map(str::to_string)).collect(),
+            references: array(&symbol, "references").iter().filter_map(location).collect(),
+        });
+    }
+    let unresolved = array(file, "unresolved").iter()
+        .filter_map(|item| {
+            let [name, line, column] = item.as_array()? else { return None };
+            Some((name.as_str()?.to_string(), number(Some(line))?, number(Some(column))?))
+        })
+        .collect();
+    Ok(FileEntry {
+        hash: file.get("hash").and_then(Json::as_str).unwrap_or("").to_string(),
+        symbols,
+        unresolved,
+    })
+}
+
+/// Symbols per kind, for the `rustsp index` summary
+pub fn count_by_kind(index: &SymbolIndex) -> HashMap<SymbolKind, usize> {
+    let mut counts = HashMap::new();
+    for symbol in index.symbols() {
+        *counts.entry(symbol.kind).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: &str = r#"struct Account {
+    balance i64
+}
+
+fn deposit(acc Account, amount i64) Account effects(write(acc)) {
+    total = acc.balance + amount
+    Account { balance = total }
+}
+"#;
+
+    const MAIN: &str = r#"fn main() effects(io) {
+    acc = Account { balance = 0 }
+    acc = deposit(acc, 5)
+    println("{}", acc.balance)
+}
+"#;
+
+    fn index() -> SymbolIndex {
+        let mut index = SymbolIndex::default();
+        index.index_file("bank/account.rss", ACCOUNT);
+        index.index_file("main.rss", MAIN);
+        index
+    }
+
+    #[test]
+    fn test_definitions_and_effects() {
+        let index = index();
+        let deposit = index.find_definition("bank/account.rss", 5, 5).unwrap();
+        assert_eq!((deposit.name.as_str(), deposit.kind), ("deposit", SymbolKind::Function));
+        assert_eq!(deposit.effects, vec!["write(acc)"]);
+
+        let total = index.find_definition("bank/account.rss", 7, 25).unwrap();
+        assert_eq!(total.kind, SymbolKind::Binding);
+        assert_eq!(total.scope.as_deref(), Some("deposit"));
+        assert_eq!((total.definition.line, total.definition.column), (6, 5));
+
+        // `deposit(` in main.rss is defined in another file
+        let called = index.find_definition("main.rss", 3, 11).unwrap();
+        assert_eq!(called.definition.file, "bank/account.rss");
+    }
+
+    #[test]
+    fn test_references_across_files() {
+        let index = index();
+        let refs = index.find_references("bank/account.rss", 1, 8);
+        let positions: Vec<(&str, usize, usize)> = refs.iter().map(|l| (l.file.as_str(), l.line, l.column)).collect();
+        assert_eq!(positions, vec![
+            ("bank/account.rss", 5, 16),
+            ("bank/account.rss", 5, 37),
+            ("bank/account.rss", 7, 5),
+            ("main.rss", 2, 11),
+        ]);
+        // Bindings stay inside their function
+        assert_eq!(index.find_references("main.rss", 2, 5).len(), 3);
+        assert!(index.find_definition("main.rss", 4, 5).is_none());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let index = index();
+        assert_eq!(SymbolIndex::from_json(&index.to_json()).unwrap(), index);
+        assert!(SymbolIndex::from_json("{\"format\": \"rustsp-ir\", \"version\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let dir = std::env::temp_dir().join(format!("rustsp-index-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("bank")).unwrap();
+        fs::write(dir.join("bank/account.rss"), ACCOUNT).unwrap();
+        fs::write(dir.join("main.rss"), MAIN).unwrap();
+
+        let (_, stats) = SymbolIndex::open(&dir).unwrap();
+        assert_eq!(stats, UpdateStats { indexed: 2, unchanged: 0, removed: 0 });
+
+        fs::write(dir.join("main.rss"), MAIN.replace("5)", "7)")).unwrap();
+        fs::remove_file(dir.join("bank/account.rss")).unwrap();
+        let (index, stats) = SymbolIndex::open(&dir).unwrap();
+        assert_eq!(stats, UpdateStats { indexed: 1, unchanged: 0, removed: 1 });
+        assert_eq!(index.files().collect::<Vec<_>>(), vec!["main.rss"]);
+        assert_eq!(SymbolIndex::load(&dir).unwrap(), index);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
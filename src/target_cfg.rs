@@ -0,0 +1,238 @@
+//! Target Conditions
+//!
+//! An effect can be declared for some targets only:
+//!
+//! ```text
+//! fn log(msg String) effects(io when not wasm) {
+//!     #[cfg(not(target_arch = "wasm32"))]
+//!     println("{}", msg)
+//! }
+//! ```
+//!
+//! Stage 1 evaluates `when` conditions against the `--target` triple (the
+//! host when none is given): on wasm `log` declares no effects. A statement
+//! behind a `#[cfg(..)]` that is false for the target is compiled out, so
+//! its effects are not counted either and the function stays honest.
+//!
+//! Conditions combine atoms with `not`, `and`, `or` and parentheses. An
+//! atom names the target's architecture (`wasm32`, `x86_64`), operating
+//! system (`linux`, `windows`, `macos`, `wasi`) or family (`wasm`, `unix`,
+//! `windows`); `native` is `not wasm`.
+
+/// The target Stage 1 checks effects for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub triple: String,
+    pub arch: String,
+    pub os: String,
+}
+
+impl Target {
+    /// `x86_64-unknown-linux-gnu`, `wasm32-unknown-unknown`, `aarch64-apple-darwin`, ...
+    pub fn from_triple(triple: &str) -> Self {
+        let parts: Vec<&str> = triple.split('-').collect();
+        let arch = parts.first().copied().unwrap_or("").to_string();
+        let os = if parts.contains(&"darwin") {
+            "macos"
+        } else {
+            ["linux", "windows", "wasi", "android", "freebsd", "netbsd", "openbsd", "ios"]
+                .into_iter()
+                .find(|os| parts.iter().any(|p| p.starts_with(os)))
+                .unwrap_or("unknown")
+        };
+        Target { triple: triple.to_string(), arch, os: os.to_string() }
+    }
+
+    /// The machine rustsp runs on
+    pub fn host() -> Self {
+        let os = match std::env::consts::OS {
+            "macos" => "apple-darwin".to_string(),
+            "windows" => "pc-windows-msvc".to_string(),
+            os => format!("unknown-{}", os),
+        };
+        Target::from_triple(&format!("{}-{}", std::env::consts::ARCH, os))
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        self.arch.starts_with("wasm")
+    }
+
+    /// `target_family` values: `wasm`, `unix`, `windows`
+    pub fn families(&self) -> Vec<&'static str> {
+        let mut families = Vec::new();
+        if self.is_wasm() {
+            families.push("wasm");
+        }
+        match self.os.as_str() {
+            "windows" => families.push("windows"),
+            "linux" | "macos" | "android" | "freebsd" | "netbsd" | "openbsd" | "ios" => families.push("unix"),
+            _ => {}
+        }
+        families
+    }
+
+    fn matches_atom(&self, atom: &str) -> bool {
+        match atom {
+            "native" => !self.is_wasm(),
+            _ => atom == self.arch || atom == self.os || self.families().contains(&atom),
+        }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::host()
+    }
+}
+
+/// `io when not wasm` → (`io`, Some(`not wasm`))
+pub fn split_effect_condition(effect: &str) -> (&str, Option<&str>) {
+    match effect.split_once(" when ") {
+        Some((effect, condition)) => (effect.trim(), Some(condition.trim())),
+        None => (effect.trim(), None),
+    }
+}
+
+/// Evaluate a `when` condition; `Err` describes a malformed condition
+pub fn eval_condition(condition: &str, target: &Target) -> Result<bool, String> {
+    let tokens = tokenize(condition);
+    let mut pos = 0;
+    let value = parse_or(&tokens, &mut pos, target)?;
+    match tokens.get(pos) {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected `{}` in condition `{}`", token, condition)),
+    }
+}
+
+fn tokenize(condition: &str) -> Vec<String> {
+    condition
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+fn parse_or(tokens: &[String], pos: &mut usize, target: &Target) -> Result<bool, String> {
+    let mut value = parse_and(tokens, pos, target)?;
+    while tokens.get(*pos).is_some_and(|t| t == "or") {
+        *pos += 1;
+        value |= parse_and(tokens, pos, target)?;
+    }
+    Ok(value)
+}
+
+fn parse_and(tokens: &[String], pos: &mut usize, target: &Target) -> Result<bool, String> {
+    let mut value = parse_not(tokens, pos, target)?;
+    while tokens.get(*pos).is_some_and(|t| t == "and") {
+        *pos += 1;
+        value &= parse_not(tokens, pos, target)?;
+    }
+    Ok(value)
+}
+
+fn parse_not(tokens: &[String], pos: &mut usize, target: &Target) -> Result<bool, String> {
+    let Some(token) = tokens.get(*pos) else {
+        return Err("condition ends early".to_string());
+    };
+    *pos += 1;
+    match token.as_str() {
+        "not" => Ok(!parse_not(tokens, pos, target)?),
+        "(" => {
+            let value = parse_or(tokens, pos, target)?;
+            if tokens.get(*pos).is_some_and(|t| t == ")") {
+                *pos += 1;
+                Ok(value)
+            } else {
+                Err("missing `)` in condition".to_string())
+            }
+        }
+        atom if atom.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => Ok(target.matches_atom(atom)),
+        other => Err(format!("unexpected `{}` in condition", other)),
+    }
+}
+
+/// Evaluate the predicate of a `#[cfg(..)]` attribute line for the target.
+/// `None` when the line is no cfg attribute or uses a key the target does
+/// not decide (features, `test`, `debug_assertions`, ...).
+pub fn eval_cfg_attribute(line: &str, target: &Target) -> Option<bool> {
+    let predicate = line.trim().strip_prefix("#[cfg(")?.strip_suffix(")]")?;
+    eval_cfg_predicate(predicate.trim(), target)
+}
+
+fn eval_cfg_predicate(predicate: &str, target: &Target) -> Option<bool> {
+    if let Some((name, args)) = predicate.split_once('(') {
+        let args = split_cfg_args(args.trim_end().strip_suffix(')')?);
+        let mut values = args.iter().map(|a| eval_cfg_predicate(a, target));
+        return match name.trim() {
+            "not" if args.len() == 1 => values.next()?.map(|v| !v),
+            "all" => values.try_fold(true, |acc, v| v.map(|v| acc && v)),
+            "any" => values.try_fold(false, |acc, v| v.map(|v| acc || v)),
+            _ => None,
+        };
+    }
+    match predicate.split_once('=') {
+        Some((key, value)) => {
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "target_arch" => Some(target.arch == value),
+                "target_os" => Some(target.os == value),
+                "target_family" => Some(target.families().contains(&value)),
+                _ => None,
+            }
+        }
+        None => match predicate {
+            "unix" | "windows" => Some(target.families().contains(&predicate)),
+            _ => None,
+        },
+    }
+}
+
+fn split_cfg_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(args[start..].trim());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions() {
+        let wasm = Target::from_triple("wasm32-unknown-unknown");
+        let linux = Target::from_triple("x86_64-unknown-linux-gnu");
+        assert_eq!(eval_condition("not wasm", &wasm), Ok(false));
+        assert_eq!(eval_condition("not wasm", &linux), Ok(true));
+        assert_eq!(eval_condition("unix and (x86_64 or aarch64)", &linux), Ok(true));
+        assert_eq!(eval_condition("windows or wasm32", &wasm), Ok(true));
+        assert!(eval_condition("not", &linux).is_err());
+        assert!(eval_condition("(unix", &linux).is_err());
+        assert_eq!(split_effect_condition("io when not wasm"), ("io", Some("not wasm")));
+        assert_eq!(split_effect_condition(" alloc "), ("alloc", None));
+    }
+
+    #[test]
+    fn test_cfg_attributes() {
+        let wasm = Target::from_triple("wasm32-unknown-unknown");
+        let mac = Target::from_triple("aarch64-apple-darwin");
+        assert_eq!(eval_cfg_attribute("#[cfg(not(target_arch = \"wasm32\"))]", &wasm), Some(false));
+        assert_eq!(eval_cfg_attribute("#[cfg(target_family = \"wasm\")]", &wasm), Some(true));
+        assert_eq!(eval_cfg_attribute("#[cfg(all(unix, target_os = \"macos\"))]", &mac), Some(true));
+        assert_eq!(eval_cfg_attribute("#[cfg(any(windows, feature = \"x\"))]", &mac), None);
+        assert_eq!(eval_cfg_attribute("#[derive(Debug)]", &mac), None);
+    }
+}
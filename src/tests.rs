@@ -56,6 +56,21 @@ mod tests {
         assert!(output.contains("name: String::from(\"kian\"),"));
     }
 
+    #[test]
+    fn test_struct_literal_followed_by_access() {
+        let input = r#"fn main() {
+    x = P { x = 1, y = 2 }.x + 1
+    s = P { x = x, y = 3 }.sum()
+    e = Event::Data { id = 1, label = "a" }.id()
+    println!("{} {}", s, e)
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let x = P { x: 1, y: 2 }.x + 1;"), "{}", output);
+        assert!(output.contains("let s = P { x: x, y: 3 }.sum();"), "{}", output);
+        assert!(output.contains("let e = Event::Data { id: 1, label: String::from(\"a\") }.id();"), "{}", output);
+        assert!(output.contains("println!(\"{} {}\", s, e);"), "{}", output);
+    }
+
     #[test]
     fn test_struct_literal_multiline() {
         let input = r#"u = User {
@@ -111,7 +126,272 @@ fn apply_tx(w Wallet, tx Transaction) Wallet {
         assert!(output.contains("id:"), "Missing 'id:' field syntax in output: {}", output);
         assert!(output.contains("balance:"), "Missing 'balance:' field syntax in output: {}", output);
     }
-    
+
+    /// Field and argument lists split only at top-level commas
+    #[test]
+    fn test_top_level_comma_splitting() {
+        let input = r#"struct Pair {
+    a i32
+    c char
+}
+
+fn greet(flag bool, name String) {
+    println!("{} {}", flag, name)
+}
+
+fn main() {
+    n = 3
+    greet(n < 5, "x")
+    p = Pair { a = i32::max(1, 2), c = ',' }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("greet(n < 5, String::from(\"x\"));"), "Comparison split the arguments: {}", output);
+        assert!(output.contains("Pair { a: i32::max(1, 2), c: ',' }"), "Char literal comma mangled: {}", output);
+    }
+
+    /// Indexing arrays of Copy elements gets no `.clone()`
+    #[test]
+    fn test_copy_element_access_not_cloned() {
+        let input = r#"#[derive(Debug, Clone, Copy)]
+struct P {
+    x i32
+}
+
+fn first(pts [P], names [String]) {
+    p = pts[0]
+    n = names[0]
+}
+
+fn main() {
+    nums = [1, 2, 3]
+    i = 1
+    x = nums[i]
+    fixed [u8; 4] = [0; 4]
+    b = fixed[2]
+    names = vec![String::from("a")]
+    s = names[0]
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let p = pts[0];"), "Copy struct element cloned: {}", output);
+        assert!(output.contains("let x = nums[i];"), "Integer element cloned: {}", output);
+        assert!(output.contains("let b = fixed[2];"), "Declared [u8; 4] element cloned: {}", output);
+        assert!(output.contains("let n = names[0].clone();"), "String param element not cloned: {}", output);
+        assert!(output.contains("let s = names[0].clone();"), "String element not cloned: {}", output);
+    }
+
+    /// `c ? a : b` and `a if c else b` lower to `if` expressions
+    #[test]
+    fn test_conditional_assignment_sugar() {
+        let input = r#"fn label(n i32) String {
+    sign = n < 0 ? -1 : 1
+    kind = "big" if n > 100 else "small"
+    mut title String = n > 0 ? "pos" : "neg"
+    title = n == 0 ? "zero" : title
+    r = "7".parse::<i32>().unwrap_or(0)
+    title
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let sign = if n < 0 { -1 } else { 1 };"), "? : not lowered: {}", output);
+        assert!(output.contains("let kind = if n > 100 { \"big\" } else { \"small\" };"), "if else not lowered: {}", output);
+        assert!(output.contains("let mut title: String = if n > 0 { String::from(\"pos\") } else { String::from(\"neg\") };"),
+            "String branches not converted: {}", output);
+        assert!(output.contains("if n == 0 { String::from(\"zero\") } else { title };"), "Declared type not used on reassignment: {}", output);
+        assert!(output.contains("let r = \"7\".parse::<i32>().unwrap_or(0);"), "Turbofish mangled: {}", output);
+    }
+
+    /// Macro argument lists keep their format args; nested calls are lowered
+    #[test]
+    fn test_macro_arguments_keep_format_args() {
+        let input = r#"fn compute(a i32, b i32 = 5) i32 {
+    a + b
+}
+
+fn main() {
+    a = 1
+    assert_eq(compute(a), 6, "msg {}", a)
+    dbg(a < 2, compute(a))
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("assert_eq!(compute(a, 5), 6, \"msg {}\", a);"), "Macro args rewritten: {}", output);
+        assert!(output.contains("dbg!(a < 2, compute(a, 5));"), "Nested call not lowered: {}", output);
+    }
+
+    /// `effects(write acc.balance)` still makes the whole parameter `mut`
+    #[test]
+    fn test_field_write_effect_makes_param_mut() {
+        let input = r#"struct Account {
+    balance i64
+}
+
+fn pay(acc Account, amount i64) effects(write acc.balance) Account {
+    acc.balance += amount
+    acc
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("fn pay(mut acc: Account, amount: i64) -> Account {"), "Field write lost `mut`: {}", output);
+        assert!(!output.contains("effects"), "Effects clause leaked: {}", output);
+    }
+
+    /// `obj.field = match ...` / `= if ...` assign the field; the container
+    /// becomes `mut`
+    #[test]
+    fn test_control_flow_assigned_to_field() {
+        let input = r#"struct Response {
+    status String
+    code i32
+}
+
+fn classify(code i32) Response {
+    res = Response { status = String::from("?"), code = 0 }
+    res.status = match code {
+        200 { String::from("ok") }
+        _ { String::from("error") }
+    }
+    res.code = if code > 400 {
+        1
+    } else {
+        0
+    }
+    res
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let mut res = Response {"), "Container not mut: {}", output);
+        assert!(output.contains("res.status = match code {"), "Field match assignment: {}", output);
+        assert!(output.contains("res.code = (if code > 400 {"), "Field if assignment: {}", output);
+        assert!(!output.contains("let res."), "Field declared with let: {}", output);
+        assert_eq!(count_lines(&output, "};"), 1, "Match not terminated once: {}", output);
+        assert_eq!(count_lines(&output, "});"), 1, "If not terminated once: {}", output);
+    }
+
+    /// `parse[i32]()` is a generic call, `handlers[i](x)` an index
+    #[test]
+    fn test_bracket_generic_method_call() {
+        let input = r#"fn main() effects(io) {
+    text = "42"
+    n = text.parse[i32]().unwrap()
+    total = vec![1, 2].iter().sum[i64]()
+    r = handlers[i](n)
+    println("{} {} {}", n, total, r)
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let n = text.parse::<i32>().unwrap();"), "Generic call not lowered: {}", output);
+        assert!(output.contains("let total = vec![1, 2].iter().sum::<i64>();"), "Generic call not lowered: {}", output);
+        assert!(output.contains("handlers[i](n)"), "Index call rewritten: {}", output);
+    }
+
+    #[test]
+    fn test_option_annotations_and_none() {
+        let input = r#"struct User {
+    name String,
+}
+
+fn describe(u Option[User]) String {
+    match u {
+        Some(x) {
+            x.name
+        }
+        None {
+            "nobody".to_string()
+        }
+    }
+}
+
+fn main() effects(io) {
+    user: Option[User] = none
+    r: Result[User, String] = Err("missing".to_string())
+    if user != none {
+        println("{}", describe(user))
+    }
+    found = user == none
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let user: Option<User> = None;"), "Option annotation not declared: {}", output);
+        assert!(output.contains("let r: Result<User, String> = Err("), "Result annotation not declared: {}", output);
+        assert!(output.contains("if user.is_some() {"), "none comparison not lowered: {}", output);
+        assert!(output.contains("let found = user.is_none();"), "none comparison not lowered: {}", output);
+        assert!(output.contains("#[derive(Clone)]\nstruct User"), "Option[User] argument is cloned without Clone: {}", output);
+    }
+
+    #[test]
+    fn test_binding_shadowing_macro_name() {
+        let input = "fn main() {\n    format = compute()\n    s = format(5)\n    if true {\n        vec = make()\n        v = vec(1)\n    }\n    w = vec(2)\n}\n\nfn other() String {\n    format(\"{}\", 1)\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("let s = format(5);"), "Shadowed call became a macro: {}", output);
+        assert!(output.contains("let v = vec(1);"), "Shadowed call became a macro: {}", output);
+        assert!(output.contains("let w = vec!(2);") || output.contains("let w = vec![2];"), "Macro outside the scope not lowered: {}", output);
+        assert!(output.contains("format!(\"{}\", 1)"), "Macro in another function not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_multiline_struct_literal_in_call() {
+        let input = "struct User {\n    id i32\n}\n\nfn make() Option[User] {\n    Some(User {\n        id = 1\n    })\n}\n\nfn main() {\n    register(User {\n        id = 2\n    })\n    total = wrap(3, User {\n        id = 3\n    })\n    opt = Some(User {\n        id = 4\n    })\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("register(User {"), "Call not kept: {}", output);
+        assert!(output.contains("let total = wrap(3, User {"), "Call with leading argument not assigned: {}", output);
+        assert!(output.contains("let opt = Some(User {"), "Wrapped literal not assigned: {}", output);
+        assert_eq!(output.matches("});").count(), 3, "Each call closes once as a statement: {}", output);
+        assert!(!output.contains("})\n;") && !output.contains("}\n)"), "Literal and call closed apart: {}", output);
+        let tail = output.find("id: 1,").unwrap();
+        assert!(output[tail..].trim_start_matches("id: 1,").trim_start().starts_with("})\n"), "Tail expression got a semicolon: {}", output);
+    }
+
+    #[test]
+    fn test_use_path_sugar() {
+        let input = "use std.collections.HashMap\nuse std.io { Read, Write }\nuse std::io::Read\npub use std.sync {\n    Arc\n    atomic.AtomicUsize\n}\n\nfn main() {\n    m HashMap[String, i32] = HashMap::new()\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("use std::collections::HashMap;"), "Dot path not lowered: {}", output);
+        assert!(output.contains("use std::io::{Read, Write};"), "Group not lowered: {}", output);
+        assert_eq!(output.matches("Read").count(), 1, "Duplicate import kept: {}", output);
+        assert!(output.contains("pub use std::sync::{\nArc,\natomic::AtomicUsize,\n};"), "Multi-line group not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_method_sugar_infers_mut() {
+        let input = "fn collect() effects(alloc) Vec[i32] {\n    xs = vec![1, 2]\n    xs push 3\n    seen = HashSet::new()\n    seen insert 3\n    xs\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("let mut xs = vec![1, 2];"), "xs not inferred mut: {}", output);
+        assert!(output.contains("xs.push(3);"), "Sugar not lowered: {}", output);
+        assert!(output.contains("let mut seen = HashSet::new();"), "seen not inferred mut: {}", output);
+        assert!(output.contains("seen.insert(3);"), "Sugar not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_fn_modifiers_lowered_to_attributes() {
+        let input = "inline fn double(x i32) i32 {\n    x * 2\n}\n\npub must_use fn sum(\n    a i64,\n    b i64,\n) i64 {\n    a + b\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("#[inline] fn double(x: i32) -> i32 {"), "inline not lowered: {}", output);
+        assert!(output.contains("#[must_use] pub fn sum(a: i64, b: i64) -> i64 {"), "must_use not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_generic_struct_lowered() {
+        let input = "struct Wrapper[T] {\n    value T\n}\n\nimpl[T] Wrapper[T] {\n    fn get(&self) &T {\n        &self.value\n    }\n}\n\nfn main() {\n    w = Wrapper[i64] { value = 7 }\n    m = Wrapper[u8]::get(&Wrapper { value = 1 })\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("struct Wrapper<T> {"), "Header not lowered: {}", output);
+        assert!(output.contains("impl<T> Wrapper<T> {"), "impl not lowered: {}", output);
+        assert!(output.contains("let w: Wrapper<i64> = Wrapper { value: 7 };"), "Literal not lowered: {}", output);
+        assert!(output.contains("Wrapper::<u8>::get("), "Path not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_statements_split_on_semicolons() {
+        let input = "fn main() {\n    a = 1; b = \"x; y\"\n    for i in 0..3 { a += i; }\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("let a = 1;"), "First statement not lowered: {}", output);
+        assert!(output.contains("let b = \"x; y\";"), "Second statement not lowered: {}", output);
+        assert!(output.contains("for i in 0..3 { a += i; }"), "Block split: {}", output);
+    }
+
+    #[test]
+    fn test_concurrency_sugar_lowered() {
+        let input = "fn main() {\n    (tx, rx) = channel()\n    h = spawn {\n        tx.send(1).unwrap()\n    }\n    join h\n    got = rx.recv().unwrap()\n}";
+        let output = parse_rusts(input);
+        assert!(output.contains("let (tx, rx) = std::sync::mpsc::channel();"), "channel not lowered: {}", output);
+        assert!(output.contains("let h = std::thread::spawn(move || {"), "spawn not lowered: {}", output);
+        assert!(output.contains("});"), "spawn block not closed: {}", output);
+        assert!(output.contains("h.join().unwrap();"), "join not lowered: {}", output);
+    }
+
     //=========================================================================
     // ARRAY LITERAL TESTS
     //=========================================================================
@@ -166,6 +446,23 @@ fn apply_tx(w Wallet, tx Transaction) Wallet {
         assert!(output.contains("let arr = [1, 2, 3];"));
     }
     
+    #[test]
+    fn test_array_literal_nested_rows() {
+        let input = r#"grid = [
+    [
+        1,
+        2
+    ],
+    [3,
+     4]]"#;
+        let output = parse_rusts(input);
+        let output: Vec<&str> = output.lines().map(str::trim).collect();
+        let output = output.join("\n");
+        assert!(!output.contains("[,"), "Row opener got a comma: {}", output);
+        assert!(output.contains("2,\n],"), "Row not closed as element: {}", output);
+        assert!(output.contains("[3,\n4,\n],\n];"), "Double close not split: {}", output);
+    }
+    
     //=========================================================================
     // BUG FIX TESTS - Critical regression tests for the three main bugs
     //=========================================================================
@@ -245,7 +542,28 @@ fn apply_tx(w Wallet, tx Transaction) Wallet {
         assert!(!output.contains(".as_str()"), 
             "BUG C: Should not add .as_str() for non-string patterns: {}", output);
     }
-    
+
+    /// BUG C: String literals inside arm bodies are not patterns
+    #[test]
+    fn test_bug_c_range_patterns_with_string_bodies() {
+        let input = r#"fn kind(code i32, mode String) String {
+    match code {
+        0..=59 {
+            match mode {
+                "strict" { "fail" }
+                _ { "weak" }
+            }
+        }
+        _ { "pass" }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("match code {"),
+            "BUG C: Range patterns must not get .as_str(): {}", output);
+        assert!(output.contains("match mode.as_str() {"),
+            "BUG C: Nested string match still needs .as_str(): {}", output);
+    }
+
     //=========================================================================
     // 5 ATURAN LOWERING FINAL - COMPREHENSIVE REGRESSION TESTS
     //=========================================================================
@@ -336,6 +654,80 @@ fn apply_tx(w Wallet, tx Transaction) Wallet {
             "L-04: Array access must add .clone(): {}", output);
     }
     
+    /// L-12: Clone is derived only for types that get a generated .clone()
+    #[test]
+    fn test_l12_clone_derive_only_when_cloned() {
+        let input = r#"struct Event {
+    id i32
+}
+
+struct Counter {
+    hits AtomicU64
+}
+
+fn main() {
+    events = [
+        Event { id = 1 }
+    ]
+    e = events[0]
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("#[derive(Clone)]\nstruct Event {"), "Cloned type not derived: {}", output);
+        assert_eq!(output.matches("derive(Clone)").count(), 1, "Clone derived on uncloned type: {}", output);
+        
+        use crate::transpile_main::{parse_rusts_with_options, LoweringOptions};
+        let options = LoweringOptions { derive_clone_all: true, ..Default::default() };
+        let output = parse_rusts_with_options(input, &options);
+        assert_eq!(output.matches("derive(Clone)").count(), 2, "Blanket derive not applied: {}", output);
+    }
+    
+    #[test]
+    fn test_unit_function_tail_expressions() {
+        let input = r#"fn touch(v Vec[i32]) unit {
+    v.len()
+}
+
+fn pick(k i32) {
+    match k {
+        1 { helper(1) }
+        _ { helper(2) }
+    }
+}
+
+fn nested(a i32, b i32) {
+    x = match a {
+        1 {
+            match b {
+                1 { 10 }
+                _ { 20 }
+            }
+        }
+        _ { 0 }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("fn touch(v: Vec<i32>) {"), "`unit` not stripped: {}", output);
+        assert!(output.contains("v.len();"), "Unit tail not terminated: {}", output);
+        assert!(output.contains("1 => { helper(1); },"), "Arm value not discarded: {}", output);
+        assert!(output.contains("1 => { 10 },"), "Value arm terminated: {}", output);
+    }
+    
+    #[test]
+    fn test_default_params_filled_at_call_site() {
+        let input = r#"fn connect(host String, port i32 = 8080) {
+    println("{}:{}", host, port)
+}
+
+fn main() {
+    connect("localhost")
+    connect("example.org", 443)
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("fn connect(host: String, port: i32)"), "Default leaked into signature: {}", output);
+        assert!(output.contains(r#"connect(String::from("localhost"), 8080);"#), "Default not filled: {}", output);
+        assert!(output.contains(r#"connect(String::from("example.org"), 443);"#), "Explicit arg replaced: {}", output);
+    }
+    
     /// L-04: Array access that already has method call should NOT add .clone()
     #[test]
     fn test_l04_array_access_with_method() {
@@ -536,4 +928,552 @@ fn apply_tx(w Wallet, tx Transaction) Wallet {
         assert!(output.contains("Result;") || output.trim().ends_with(";"), 
             "Simple use should have semicolon: {}", output);
     }
-}
\ No newline at end of file
+    
+    //=========================================================================
+    // MATCH MODE: BRACES INSIDE STRING / CHAR LITERALS
+    //=========================================================================
+    
+    /// A `}` inside a string in an arm body must not close the arm early
+    #[test]
+    fn test_match_arm_body_string_with_brace() {
+        let input = r#"fn f(x i32) {
+    match x {
+        0 {
+            println!("closing } brace")
+            println!("still in arm")
+        }
+        _ {
+            println!("open { brace")
+        }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("0 => {"), "Arm pattern lost: {}", output);
+        assert!(output.contains("_ => {"), "Wildcard arm lost: {}", output);
+        let (opens, closes) = crate::lowering::count_braces_outside_strings(&output);
+        assert_eq!(opens, closes, "Unbalanced braces in output: {}", output);
+    }
+    
+    /// Char literal patterns like '{' must not shift match depth
+    #[test]
+    fn test_match_char_literal_brace_patterns() {
+        let input = r#"fn f(c char) -> i32 {
+    match c {
+        '{' { 1 }
+        '}' { -1 }
+        '[' {
+            2
+        }
+        _ { 0 }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("'{' => { 1 },"), "Char arm not lowered: {}", output);
+        assert!(output.contains("'}' => { -1 },"), "Char arm not lowered: {}", output);
+        assert!(output.contains("'[' => {"), "Multi-line char arm not lowered: {}", output);
+        assert!(output.contains("_ => { 0 },"), "Wildcard arm not lowered: {}", output);
+    }
+
+    //=========================================================================
+    // MATCH MODE: NESTED CONTROL FLOW INSIDE ARM BODIES
+    //=========================================================================
+
+    /// Lines of `output` equal to `line` after trimming
+    fn count_lines(output: &str, line: &str) -> usize {
+        output.lines().filter(|l| l.trim() == line).count()
+    }
+
+    /// `} else {` closes back to the arm's own level without closing the arm
+    #[test]
+    fn test_match_arm_nested_if_else() {
+        let input = r#"fn classify(v Option<i32>, flag bool) i32 {
+    match v {
+        Some(x) {
+            if x > 0 {
+                if flag {
+                    1
+                } else {
+                    2
+                }
+            } else {
+                3
+            }
+        }
+        None {
+            0
+        }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(!output.contains("else =>"), "Nested else taken for an arm: {}", output);
+        assert_eq!(count_lines(&output, "} else {"), 2, "Nested else blocks mangled: {}", output);
+        assert_eq!(count_lines(&output, "},"), 2, "Arms not closed exactly once: {}", output);
+        assert!(output.contains("None => {"), "Arm after nested blocks lost: {}", output);
+    }
+
+    /// Loops, else-if chains and an inner match several blocks deep
+    #[test]
+    fn test_match_arm_deeply_nested_loops_and_match() {
+        let input = r#"fn run(v Option<i32>) {
+    match v {
+        Some(x) {
+            for i in 0..x {
+                while i > 100 {
+                    if i == 101 {
+                        break
+                    }
+                }
+                if i % 2 == 0 {
+                    println!("even")
+                } else if i == 3 {
+                    println!("three")
+                } else {
+                    match i {
+                        5 { println!("five") }
+                        _ {
+                            if i > 6 {
+                                println!("big")
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        None {
+            println!("none")
+        }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("} else if i == 3 {"), "else-if chain mangled: {}", output);
+        assert!(output.contains("5 => { println!(\"five\"); },"), "Inner single-line arm lost: {}", output);
+        // Inner `_` arm, outer `Some` arm and outer `None` arm
+        assert_eq!(count_lines(&output, "},"), 3, "Arms closed early or twice: {}", output);
+        assert!(output.contains("None => {"), "Outer match ended early: {}", output);
+        let (opens, closes) = crate::lowering::count_braces_outside_strings(&output);
+        assert_eq!(opens, closes, "Unbalanced braces in output: {}", output);
+    }
+
+    /// Assignment match: the semicolon goes after the match, not a nested block
+    #[test]
+    fn test_match_assignment_arm_nested_if() {
+        let input = r#"fn label(v Option<i32>) String {
+    s = match v {
+        Some(x) {
+            if x > 10 {
+                if x > 100 {
+                    "huge".to_string()
+                } else {
+                    "big".to_string()
+                }
+            } else {
+                "small".to_string()
+            }
+        }
+        None {
+            "none".to_string()
+        }
+    }
+    s
+}"#;
+        let output = parse_rusts(input);
+        assert_eq!(count_lines(&output, "};"), 1, "Match terminated at the wrong brace: {}", output);
+        assert_eq!(count_lines(&output, "},"), 2, "Arms not closed exactly once: {}", output);
+        assert!(output.contains("\"huge\".to_string()\n"), "Nested arm value got a semicolon: {}", output);
+    }
+
+    /// `| Last {` opens a multi-line body that can hold nested blocks
+    #[test]
+    fn test_multi_pattern_arm_multiline_body() {
+        let input = r#"enum Op {
+    Add
+    Sub
+    Mul
+}
+
+fn apply(op Op, a i32, b i32) i32 {
+    match op {
+        Op::Add
+        | Op::Sub {
+            if a > b {
+                a - b
+            } else {
+                b - a
+            }
+        }
+        Op::Mul {
+            a * b
+        }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("| Op::Sub => {"), "Multi-pattern body not opened: {}", output);
+        assert!(!output.contains("else =>"), "Nested else taken for an arm: {}", output);
+        assert!(output.contains("Op::Mul => {"), "Arm after multi-pattern body lost: {}", output);
+        assert_eq!(count_lines(&output, "},"), 2, "Arms not closed exactly once: {}", output);
+    }
+
+    //=========================================================================
+    // ENUM PAYLOADS WITH BRACKET GENERICS
+    //=========================================================================
+    
+    #[test]
+    fn test_enum_variant_bracket_generic_payloads() {
+        let input = r#"enum Msg {
+    Batch(Vec[Tx])
+    Named { items Vec[Tx], map HashMap[String, u64] }
+    Multi {
+        list Vec[Option[Tx]]
+    }
+}
+
+enum Inline { A(Vec[u8]), B }
+
+fn main() {
+    x = 1
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("Batch(Vec<Tx>),"), "Tuple payload not lowered: {}", output);
+        assert!(output.contains("items: Vec<Tx>, map: HashMap<String, u64>"), "Struct payload not lowered: {}", output);
+        assert!(output.contains("list: Vec<Option<Tx>>,"), "Multi-line payload not lowered: {}", output);
+        assert!(output.contains("enum Inline { A(Vec<u8>), B }"), "Inline enum not lowered: {}", output);
+        assert!(output.contains("let x = 1;"), "Single-line enum swallowed following code: {}", output);
+    }
+    
+    #[test]
+    fn test_first_pass_records_enum_payloads() {
+        let source = "enum Msg {\n    Batch(Vec[Tx])\n    Multi {\n        list Vec[u8]\n        n u32\n    }\n    Ping\n}";
+        let lines: Vec<&str> = source.lines().collect();
+        let mut tracker = crate::variable::VariableTracker::new();
+        let result = crate::first_pass::run_first_pass(&lines, &mut tracker);
+        let registry = &result.enum_registry;
+        assert_eq!(registry.variant_payload("Msg", "Batch"), Some(&["Vec<Tx>".to_string()][..]));
+        assert_eq!(
+            registry.variant_payload("Msg", "Multi"),
+            Some(&["Vec<u8>".to_string(), "u32".to_string()][..])
+        );
+        assert_eq!(registry.variant_payload("Msg", "Ping"), None);
+    }
+    
+    //=========================================================================
+    // TRAIT IMPLS WITH RUSTS+ METHOD BODIES
+    //=========================================================================
+    
+    #[test]
+    fn test_display_impl_with_rusts_body() {
+        let input = r#"struct User {
+    name String
+}
+
+impl Display for User {
+    fn fmt(self, f Formatter) Result {
+        write(f, "{}", self.name)
+    }
+}
+
+impl User {
+    fn greet(self) String {
+        format!("hi {}", self.name)
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(
+            output.contains("fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {"),
+            "Display signature not adapted: {}", output
+        );
+        assert!(output.contains("write!(f, \"{}\", self.name)"), "Body not lowered: {}", output);
+        assert!(output.contains("fn greet(self) -> String {"), "Inherent impl changed: {}", output);
+        assert!(!output.contains("derive(Clone)"), "Clone derive injected: {}", output);
+    }
+    
+    //=========================================================================
+    // VERBATIM RUST BLOCKS
+    //=========================================================================
+    
+    #[test]
+    fn test_verbatim_rust_block_passthrough() {
+        let input = r#"rust {
+    impl<'a> From<&'a Holder> for i64 {
+        fn from(h: &'a Holder) -> i64 { h.v as i64 }
+    }
+}
+
+fn now() u128 effects(io) {
+    x = 1
+    rust effects(io) {
+        let v: Vec<u8> = Vec::new();
+        std::time::SystemTime::now().elapsed().unwrap().as_millis() + v.len() as u128
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("    impl<'a> From<&'a Holder> for i64 {\n        fn from(h: &'a Holder) -> i64 { h.v as i64 }\n    }"),
+            "Verbatim item altered: {}", output);
+        assert!(output.contains("        let v: Vec<u8> = Vec::new();\n        std::time::SystemTime::now().elapsed().unwrap().as_millis() + v.len() as u128\n"),
+            "Verbatim body altered: {}", output);
+        assert!(output.contains("let x = 1;"), "Surrounding code not lowered: {}", output);
+        assert!(!output.contains("@rustsp-verbatim"), "Marker leaked: {}", output);
+    }
+    
+    #[test]
+    fn test_rust_dialect_function_passthrough() {
+        let input = r#"fn describe(kind: &str, prefix: String) -> String {
+    let label = match kind {
+        "a" => "alpha",
+        _ => "other",
+    };
+    format!("{}{}", prefix, label)
+}
+
+fn main() {
+    k = describe("a", "x")
+}"#;
+        let output = parse_rusts(input);
+        let rust_fn = input.split("\n\nfn main").next().unwrap();
+        assert!(output.contains(rust_fn), "Rust function altered: {}", output);
+        assert!(output.contains(r#"let k = describe("a", String::from("x"));"#), "Call not coerced: {}", output);
+    }
+    
+    //=========================================================================
+    // LINE-PRESERVING LOWERING
+    //=========================================================================
+    
+    #[test]
+    fn test_preserve_lines_matches_source_line_numbers() {
+        use crate::transpile_main::{parse_rusts_with_options, LoweringOptions};
+        
+        let input = r#"fn total(items Vec[i64]) i64 {
+    effect x
+    mut sum = 0
+    for it in items {
+        sum = sum + it
+    }
+    sum
+}
+
+fn main() {
+    rust {
+        let v: Vec<i64> = vec![1, 2];
+    }
+    println!("{}", total(v))
+}"#;
+        let options = LoweringOptions { preserve_lines: true, ..Default::default() };
+        let output = parse_rusts_with_options(input, &options);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), input.lines().count(), "Line count changed: {}", output);
+        assert!(lines[0].starts_with("fn total(items: Vec<i64>) -> i64 {"), "{}", output);
+        assert_eq!(lines[1], "", "Skipped line not kept empty: {}", output);
+        assert!(lines[2].contains("let mut sum = 0;"), "{}", output);
+        assert_eq!(lines[11], "        let v: Vec<i64> = vec![1, 2];", "{}", output);
+        assert!(lines[13].starts_with("println!(\"{}\", total(v"), "{}", output);
+    }
+
+    #[test]
+    fn test_unicode_identifiers_lowered() {
+        let input = r#"struct Punkt {
+    höhe i32
+}
+
+fn fläche(p Punkt) i32 {
+    p.höhe * 2
+}
+
+fn main() {
+    p = Punkt { höhe = 2 }
+    größe = fläche(p)
+    名前 = "x"
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("höhe: i32"), "Field not lowered: {}", output);
+        assert!(output.contains("fn fläche(p: Punkt) -> i32"), "Signature not lowered: {}", output);
+        assert!(output.contains("let p = Punkt { höhe: 2 };"), "Literal not lowered: {}", output);
+        assert!(output.contains("let größe = fläche(p"), "Call not lowered: {}", output);
+        assert!(output.contains("let 名前 = \"x\""), "CJK binding not lowered: {}", output);
+    }
+
+    #[test]
+    fn test_chained_comparison_lowering() {
+        let input = r#"fn main() {
+    x = 5
+    if 0 < x < 10 {
+        println("small")
+    } else if (10 <= x <= 20) {
+        println("mid")
+    }
+    label = match x {
+        n if 0 < n <= 9 { "digit" }
+        _ { "other" }
+    }
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("if 0 < x && x < 10 {"), "if chain not expanded: {}", output);
+        assert!(output.contains("} else if 10 <= x && x <= 20 {"), "else-if chain not expanded: {}", output);
+        assert!(output.contains("n if 0 < n && n <= 9 =>"), "guard chain not expanded: {}", output);
+    }
+
+    #[test]
+    fn test_condition_line_continuation() {
+        let input = r#"fn pick(a i32, b i32) i32 {
+    v = if a > 0 &&
+        b < 10 {
+        1
+    } else {
+        2
+    }
+    while 0 < a < 10 ||
+        b == 3 {
+        return v
+    }
+    ok = a > 1 ||
+        b > 2
+    v
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let v = (if a > 0 && b < 10 {"), "if-expression condition not joined: {}", output);
+        assert!(output.contains("});"), "if-expression not terminated: {}", output);
+        assert!(output.contains("while 0 < a && a < 10 || b == 3 {"), "while condition not joined: {}", output);
+        assert!(output.contains("let ok = a > 1 || b > 2;"), "assignment continuation not joined: {}", output);
+    }
+
+    #[test]
+    fn test_malformed_signature_lowering_error() {
+        let input = r#"fn bad(x) effects(io) {
+    y = x + 1
+    println("{}", y)
+}
+
+fn worse(
+    a i32,
+    b
+) {
+    a
+}
+
+fn good(a i32) i32 {
+    a + 1
+}"#;
+        use crate::transpile_main::{lower_rusts, LoweringOptions};
+        let errors = lower_rusts(input, &LoweringOptions::default()).expect_err("Malformed signatures lowered");
+        let spans: Vec<(usize, usize)> = errors.iter().map(|e| (e.location.line, e.location.column)).collect();
+        assert_eq!(spans, vec![(1, 8), (8, 5)], "Wrong spans: {:?}", errors);
+        assert!(errors[0].title.starts_with("Parameter 'x'"), "Wrong error: {}", errors[0].title);
+        
+        let output = parse_rusts(input);
+        assert!(output.contains("compile_error!(\"RustS+ lowering error at line 1: Parameter 'x'"), "Error not emitted: {}", output);
+        assert!(!output.contains("COMPILE ERROR") && !output.contains("fn good"), "Partial output generated: {}", output);
+    }
+
+    #[test]
+    fn test_unclosed_mode_rolls_back_item() {
+        use crate::error_msg::ErrorCode;
+        use crate::transpile_main::{lower_rusts, LoweringOptions};
+        let literal = "struct Q {\n    z i32\n}\n\nfn main() {\n    p = P { x = [\n        1,\n    ], y = Q {\n        z = 1 } }\n}\n\nfn g() {\n}\n";
+        let imports = "use std::{\n    io,\n    fmt }\nfn g() {\n}\n";
+        for (input, line, title) in [(literal, 5, "`fn main()` could not be lowered"), (imports, 1, "`use std::{` could not be lowered")] {
+            let errors = lower_rusts(input, &LoweringOptions::default()).expect_err("Unclosed mode lowered");
+            assert_eq!(errors.len(), 1, "{:?}", errors);
+            assert_eq!((errors[0].code, errors[0].location.line, errors[0].title.as_str()), (ErrorCode::RSPL030, line, title));
+            assert!(!parse_rusts(input).contains("fn g"), "Partial output generated");
+        }
+    }
+
+    #[test]
+    fn test_literal_brackets_in_strings() {
+        let input = "struct Item {\n    label &'static str\n}\n\nfn main() {\n    closers = [\"]\",\n        \"}\",\n    ]\n    items = Vec::new()\n    items.push(Item { label = \"}\",\n    })\n}\n";
+        let output = parse_rusts(input);
+        assert!(output.contains("let closers = [\n    \"]\",\n"), "{}", output);
+        assert!(output.contains("\n];\n"), "{}", output);
+        assert!(output.contains("items.push(Item { label: \"}\",\n})"), "{}", output);
+    }
+
+    #[test]
+    fn test_single_line_literals_inside_multi_line() {
+        let input = "struct Point {\n    x i32\n}\n\nstruct Route {\n    from Point\n    to Point\n}\n\nstruct Trip {\n    start Point\n    route Route\n    km i32\n}\n\nfn main() {\n    t = Trip {\n        start = Point { x = 0 }\n        route = Route { from = Point { x = 1 }, to = Point {\n            x = 2\n        }\n        }\n        km = 3\n    }\n}\n";
+        let output = parse_rusts(input);
+        assert!(output.contains("start: Point { x: 0 },\n"), "{}", output);
+        assert!(output.contains("route: Route { from: Point { x: 1 }, to: Point {\nx: 2,\n},\n},\nkm: 3,\n};"), "{}", output);
+    }
+
+    #[test]
+    fn test_closure_forms() {
+        let input = "fn main() effects(io) {\n    double = fn(x i32) i32 { x * 2 }\n    shout = |s &str| effects(io) {\n        println!(\"{}\", s)\n    }\n    offset = fn(x i32) i32 {\n        y = x + 1\n        y * 2\n    }\n    shout(\"hi\")\n    println!(\"{} {}\", double(2), offset(3))\n}\n";
+        let output = parse_rusts(input);
+        assert!(output.contains("let double = |x: i32| -> i32 { x * 2 };"), "{}", output);
+        assert!(output.contains("let shout = |s: &str| {\nprintln!(\"{}\", s);\n};"), "{}", output);
+        assert!(output.contains("let offset = |x: i32| -> i32 {\nlet y = x + 1;\ny * 2\n};"), "{}", output);
+    }
+
+    #[test]
+    fn test_for_loop_iteration() {
+        let input = "fn main() {\n    events Vec[String] = load()\n    for ev in events {\n        println!(\"{}\", ev)\n    }\n    for ev in events {\n        kept.push(ev)\n    }\n    nums = vec![1, 2, 3]\n    for n in nums {\n        n = n * 2\n        println!(\"{}\", n)\n    }\n    println!(\"{}\", events.len())\n}\n";
+        let output = parse_rusts(input);
+        assert!(output.contains("for ev in &events {"), "{}", output);
+        assert!(output.contains("for ev in events.clone() {"), "{}", output);
+        assert!(output.contains("for mut n in nums {\nn = n * 2;"), "{}", output);
+    }
+
+    #[test]
+    fn test_lowering_panic_is_item_error() {
+        use crate::error_msg::ErrorCode;
+        use crate::lowering_hook::{LineCtx, LoweringHook, Rewrite};
+        use crate::transpile_main::{lower_rusts, LoweringOptions};
+        struct Boom;
+        impl LoweringHook for Boom {
+            fn name(&self) -> &str { "boom" }
+            fn on_line(&self, ctx: &LineCtx) -> Option<Rewrite> {
+                assert!(!ctx.code.contains("boom"), "hook failed");
+                None
+            }
+        }
+        let mut options = LoweringOptions::default();
+        options.hooks.register(Boom);
+        let input = "fn ok() {\n    x = 1\n}\n\nfn bad() {\n    boom = 1\n}\n";
+        let errors = lower_rusts(input, &options).expect_err("Panicking item lowered");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].code, errors[0].location.line), (ErrorCode::RSPL030, 5));
+        assert!(errors[0].explanation.as_deref().unwrap_or("").contains("panicked: hook failed"), "{:?}", errors[0]);
+    }
+
+    #[test]
+    fn test_unbalanced_braces_are_source_errors() {
+        use crate::error_msg::ErrorCode;
+        use crate::transpile_main::{lower_rusts, LoweringOptions};
+        let options = LoweringOptions::default();
+        let extra = "fn main() {\n    x = 1\n}\n}\n";
+        let errors = lower_rusts(extra, &options).expect_err("Extra brace lowered");
+        assert_eq!((errors.len(), errors[0].code, errors[0].location.line), (1, ErrorCode::RSPL031, 4));
+        let unclosed = "impl Counter {\n    fn get(self) i32 {\n        a = 1; b = 2\n        a + b\n}\n\nfn main() {\n    x = 1\n";
+        let errors = lower_rusts(unclosed, &options).expect_err("Unclosed brace lowered");
+        assert_eq!((errors.len(), errors[0].code, errors[0].location.line), (1, ErrorCode::RSPL031, 1));
+        assert_eq!(errors[0].title, "unbalanced braces: 2 `{` are never closed");
+        assert!(errors[0].explanation.as_deref().unwrap_or("").contains("line 7: fn main() {"), "{:?}", errors[0]);
+    }
+
+    #[test]
+    fn test_match_dispatch_table() {
+        let input = r#"fn parse_json(s String) i32 {
+    1
+}
+
+fn parse_text(s String) i32 {
+    2
+}
+
+fn main() {
+    kind = 2
+    handler = match kind {
+        1 {
+            parse_json
+        }
+        _ {
+            |s| 3
+        }
+    }
+    n = handler("x")
+}"#;
+        let output = parse_rusts(input);
+        assert!(output.contains("let handler: fn(String) -> i32 = match kind {"), "fn type not inferred: {}", output);
+        assert!(output.contains("parse_json\n"), "Arm value got a semicolon: {}", output);
+        assert!(output.contains("_ => {\n|s| 3\n}"), "Closure arm mangled: {}", output);
+        assert!(output.contains("let n = handler(String::from(\"x\"));"), "Call through alias not lowered: {}", output);
+    }
+}
@@ -7,7 +7,9 @@
 //! - Single-line enum variants: `Event::Data { id = 1, body = b }`
 //! - Multi-line literals (handled by literal_mode in main parser)
 
+use crate::lowering::depth_tracking_lowering::split_top_level;
 use crate::transform_literal::{find_field_eq, is_string_literal, transform_nested_struct_value};
+use crate::empty_literals::braced;
 
 /// Transform an array element line
 /// 
@@ -106,50 +108,18 @@ fn transform_complete_literal_element(line: &str) -> String {
     //
     // This caused `vec![]` elements to lose commas, making rustc fail with
     // "no rules expected this token in macro call" on the NEXT element.
-    format!("{} {{ {} }},", type_part, transformed_fields)
+    format!("{},", braced(type_part, &transformed_fields))
 }
 
 /// Transform fields inside a struct/enum literal
 /// Input:  `start_height = 0, target_height = 100, current_height = 50`
 /// Output: `start_height: 0, target_height: 100, current_height: 50`
 fn transform_fields(fields: &str) -> String {
-    let mut result = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut brace_depth: usize = 0;
-    let mut paren_depth: usize = 0;
-    
     // Split by comma, respecting nested structures and strings
-    for c in fields.chars() {
-        if c == '"' && !current.ends_with('\\') {
-            in_string = !in_string;
-        }
-        
-        if !in_string {
-            match c {
-                '{' => brace_depth += 1,
-                '}' => brace_depth = brace_depth.saturating_sub(1),
-                '(' => paren_depth += 1,
-                ')' => paren_depth = paren_depth.saturating_sub(1),
-                ',' if brace_depth == 0 && paren_depth == 0 => {
-                    result.push(current.clone());
-                    current.clear();
-                    continue;
-                }
-                _ => {}
-            }
-        }
-        
-        current.push(c);
-    }
-    
-    if !current.trim().is_empty() {
-        result.push(current);
-    }
-    
-    // Transform each field
-    let transformed: Vec<String> = result.iter()
-        .map(|f| transform_single_field(f))
+    let transformed: Vec<String> = split_top_level(fields, ',')
+        .into_iter()
+        .filter(|f| !f.trim().is_empty())
+        .map(transform_single_field)
         .collect();
     
     transformed.join(", ")
@@ -249,7 +219,7 @@ fn find_brace_outside_string(s: &str) -> Option<usize> {
     let mut in_string = false;
     let mut escape_next = false;
     
-    for (i, c) in s.chars().enumerate() {
+    for (i, c) in s.char_indices() {
         if escape_next {
             escape_next = false;
             continue;
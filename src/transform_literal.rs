@@ -5,8 +5,10 @@
 //! - Nested struct literal handling
 //! - String literal transformation to String::from
 
-use crate::helpers::is_valid_identifier;
+use crate::helpers::{char_to_byte_index, is_valid_identifier};
 use crate::function::CurrentFunctionContext;
+use crate::lowering::depth_tracking_lowering::split_top_level;
+use crate::empty_literals::opens_multiline_value;
 
 /// Transform a literal field line: `field = value` → `field: value,`
 /// NO `let`, NO `;` - this is expression-only context!
@@ -59,7 +61,7 @@ pub fn transform_literal_field_with_ctx(line: &str, ctx: Option<&CurrentFunction
             if is_valid_field_name(field) {
                 let transformed_value = transform_nested_struct_value(value);
                 let tv = transformed_value.trim();
-                let is_multiline_start = tv.ends_with('{') || tv.ends_with('[');
+                let is_multiline_start = opens_multiline_value(tv);
                 let already_has_comma = tv.ends_with(',');
                 let suffix = if !is_multiline_start && !already_has_comma { "," } else { "" };
                 return format!("{}{}: {}{}", leading_ws, field, transformed_value, suffix);
@@ -67,7 +69,7 @@ pub fn transform_literal_field_with_ctx(line: &str, ctx: Option<&CurrentFunction
         }
         let transformed = transform_nested_struct_value(trimmed);
         let t = transformed.trim();
-        let is_multiline_start = t.ends_with('{') || t.ends_with('[');
+        let is_multiline_start = opens_multiline_value(t);
         let already_has_comma = t.ends_with(',') || t.ends_with("},");
         let suffix = if !is_multiline_start && !already_has_comma { "," } else { "" };
         return format!("{}{}{}", leading_ws, transformed, suffix);
@@ -100,10 +102,9 @@ pub fn transform_literal_field_with_ctx(line: &str, ctx: Option<&CurrentFunction
             
             // CRITICAL FIX: Don't add trailing comma when value ends with `[`
             // This means it's a multi-line array start: `public_key = [`
-            // The comma would produce invalid `public_key: [,`
-            let tv = transformed_value.trim_end();
-            let is_multiline_start = tv.ends_with('[') || tv.ends_with("vec![") || tv.ends_with("Vec::from([");
-            let suffix = if is_multiline_start { "" } else { "," };
+            // The comma would produce invalid `public_key: [,` (same for
+            // a call spanning lines: `b = make(`)
+            let suffix = if opens_multiline_value(&transformed_value) { "" } else { "," };
             return format!("{}{}: {}{}", leading_ws, field, transformed_value, suffix);
         }
     }
@@ -153,35 +154,11 @@ pub fn transform_nested_struct_value(value: &str) -> String {
 /// Transform struct fields recursively, handling nested structs
 fn transform_struct_fields_recursive(fields: &str) -> String {
     let mut result = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut brace_depth: usize = 0;
-    let mut prev_char = ' ';
-    
-    for c in fields.chars() {
-        if c == '"' && prev_char != '\\' {
-            in_string = !in_string;
-        }
-        if !in_string {
-            if c == '{' { brace_depth += 1; }
-            if c == '}' { brace_depth = brace_depth.saturating_sub(1); }
+    for field in split_top_level(fields, ',') {
+        let transformed = transform_single_struct_field_recursive(field);
+        if !transformed.is_empty() {
+            result.push(transformed);
         }
-        
-        if c == ',' && !in_string && brace_depth == 0 {
-            let transformed = transform_single_struct_field_recursive(&current);
-            if !transformed.is_empty() {
-                result.push(transformed);
-            }
-            current.clear();
-        } else {
-            current.push(c);
-        }
-        prev_char = c;
-    }
-    
-    let transformed = transform_single_struct_field_recursive(&current);
-    if !transformed.is_empty() {
-        result.push(transformed);
     }
     
     if result.is_empty() {
@@ -243,7 +220,7 @@ pub fn find_field_eq_top_level(s: &str) -> Option<usize> {
                 let next = if i + 1 < chars.len() { chars[i+1] } else { ' ' };
                 
                 if prev != '!' && prev != '<' && prev != '>' && prev != '=' && next != '=' && next != '>' {
-                    return Some(i);
+                    return Some(char_to_byte_index(s, i));
                 }
             }
             _ => {}
@@ -261,7 +238,7 @@ pub fn find_field_eq(s: &str) -> Option<usize> {
             let next = if i + 1 < chars.len() { chars[i+1] } else { ' ' };
             
             if prev != '!' && prev != '<' && prev != '>' && prev != '=' && next != '=' && next != '>' {
-                return Some(i);
+                return Some(char_to_byte_index(s, i));
             }
         }
     }
@@ -325,7 +302,7 @@ pub fn find_field_colon_position(s: &str) -> Option<usize> {
             
             if prev != ':' && next != ':' {
                 // Valid field colon found!
-                return Some(i);
+                return Some(char_to_byte_index(s, i));
             }
         }
     }
@@ -342,7 +319,7 @@ pub fn find_field_colon_position(s: &str) -> Option<usize> {
                 let prev = if i > 0 { chars[i-1] } else { ' ' };
                 let next = if i + 1 < chars.len() { chars[i+1] } else { ' ' };
                 if prev != ':' && next != ':' {
-                    return Some(i);
+                    return Some(char_to_byte_index(s, i));
                 }
             }
         }
@@ -17,13 +17,20 @@
 //!     Item { name: "bar" },
 //! ];
 //! ```
+//!
+//! A `[...]` literal becomes `vec![...]` when the binding needs a `Vec`:
+//! annotated as one, or grown or shrunk (`.push(`, `.pop()`, ...) in the
+//! function (see [`VariableTracker::needs_vec`]). Otherwise it stays a
+//! fixed array. Map literals reach this module already lowered to
+//! `HashMap::from([...])` (see [`crate::map_literals`]).
 
 use crate::modes::ArrayModeStack;
-use crate::detection::detect_array_literal_start;
+use crate::detection::{detect_array_literal_start, map_from_array};
 use crate::transform_array::transform_array_element;
 use crate::scope::ScopeAnalyzer;
 use crate::variable::VariableTracker;
 use crate::function::CurrentFunctionContext;
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// Result of processing array literal start
 pub enum ArrayLiteralResult {
@@ -45,6 +52,9 @@ pub fn process_array_literal_start(
     current_fn_ctx: &CurrentFunctionContext,
     array_mode: &mut ArrayModeStack,
 ) -> ArrayLiteralResult {
+    if !enabled(LoweringRule::Array) {
+        return ArrayLiteralResult::NotArrayLiteral;
+    }
     let (var_name, var_type, after_bracket) = match detect_array_literal_start(trimmed) {
         Some(tuple) => tuple,
         None => return ArrayLiteralResult::NotArrayLiteral,
@@ -90,7 +100,13 @@ pub fn process_array_literal_start(
     let array_open = if rhs.starts_with("vec![") {
         "vec!["
     } else if rhs.starts_with("Vec::from([") {
+        array_mode.mark_in_call(1);
         "Vec::from(["
+    } else if let Some(entries) = map_from_array(rhs) {
+        array_mode.mark_in_call(1);
+        &rhs[..rhs.len() - entries.len()]
+    } else if tracker.needs_vec(&var_name, var_type.as_deref()) {
+        "vec!["
     } else {
         "["
     };
@@ -111,6 +127,23 @@ pub fn process_array_literal_start(
     ArrayLiteralResult::Started(output_lines.join("\n"))
 }
 
+/// `[1, 2, 3]` → `vec![1, 2, 3]`, for a single-line value whose binding
+/// needs a `Vec`; `None` for anything but one complete `[...]`
+pub fn vec_literal(value: &str) -> Option<String> {
+    let value = value.trim();
+    if !value.starts_with('[') || !value.ends_with(']') {
+        return None;
+    }
+    let mut depth = 0usize;
+    for (pos, c) in crate::lowering::depth_tracking_lowering::delimiter_positions(value, &['[', ']']) {
+        depth = if c == '[' { depth + 1 } else { depth.saturating_sub(1) };
+        if depth == 0 && pos != value.len() - 1 {
+            return None;
+        }
+    }
+    Some(format!("vec!{}", value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,4 +172,38 @@ mod tests {
         // Should detect array literal
         // (actual behavior depends on detect_array_literal_start implementation)
     }
+    
+    #[test]
+    fn test_vec_literal() {
+        assert_eq!(vec_literal("[1, 2, 3]").as_deref(), Some("vec![1, 2, 3]"));
+        assert_eq!(vec_literal("[0; 8]").as_deref(), Some("vec![0; 8]"));
+        assert_eq!(vec_literal("[[1], [2]]").as_deref(), Some("vec![[1], [2]]"));
+        assert_eq!(vec_literal("[1][0..1]"), None);
+        assert_eq!(vec_literal("\"[a]\""), None);
+        assert_eq!(vec_literal("vec![1]"), None);
+    }
+    
+    #[test]
+    fn test_resized_array_becomes_vec() {
+        let mut array_mode = ArrayModeStack::new();
+        let mut tracker = VariableTracker::new();
+        let resized = ["zs".to_string()].into_iter().collect();
+        tracker.set_function_local_mutations(&Default::default(), &Default::default(), &resized);
+        
+        let result = process_array_literal_start(
+            "zs = [",
+            "    ",
+            0,
+            0,
+            1,
+            &ScopeAnalyzer::new(),
+            &tracker,
+            &CurrentFunctionContext::new(),
+            &mut array_mode,
+        );
+        match result {
+            ArrayLiteralResult::Started(out) => assert!(out.ends_with("zs = vec!["), "{}", out),
+            ArrayLiteralResult::NotArrayLiteral => panic!("`zs = [` not detected"),
+        }
+    }
 }
\ No newline at end of file
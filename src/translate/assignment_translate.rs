@@ -16,7 +16,10 @@
 //! let config = Config { ... };
 //! ```
 
-use crate::variable::{expand_value, parse_rusts_assignment_ext};
+use crate::variable::parse_rusts_assignment_ext;
+use crate::translate::conditional_translate::{expand_conditional_value, lower_conditional};
+use crate::variable::expand_value;
+use crate::translate::array_literal_translate::vec_literal;
 use crate::scope::ScopeAnalyzer;
 use crate::variable::VariableTracker;
 use crate::function::{
@@ -24,8 +27,8 @@ use crate::function::{
     transform_string_concat, transform_call_args,
 };
 use crate::control_flow::transform_enum_struct_init;
-use crate::clone_helpers::transform_array_access_clone;
 use crate::helpers::ends_with_continuation_operator;
+use crate::lowering_rules::{enabled, LoweringRule};
 
 /// Process a RustS+ assignment line
 pub fn process_assignment(
@@ -50,19 +53,30 @@ pub fn process_assignment(
     let borrowed_mut = tracker.is_mut_borrowed(var_name);
     let mutated_via_method = tracker.is_mutated_via_method(var_name);
     let scope_needs_mut = scope_analyzer.needs_mut(var_name, line_num);
-    let needs_mut = is_explicit_mut || borrowed_mut || mutated_via_method || scope_needs_mut;
+    let needs_mut = enabled(LoweringRule::L04) && (is_explicit_mut || borrowed_mut || mutated_via_method || scope_needs_mut);
     
     // Expand and transform value
-    let mut expanded_value = expand_value(value, var_type);
-    expanded_value = transform_array_access_clone(&expanded_value);
+    // A reassigned conditional takes the declared type for its branches
+    let target_type = var_type.or_else(|| tracker.declared_type(var_name, line_num));
+    let mut expanded_value = lower_conditional(value, target_type)
+        .unwrap_or_else(|| expand_value(value, var_type));
+    expanded_value = current_fn_ctx.clone_array_access(&expanded_value);
     
     if current_fn_ctx.is_inside() {
         expanded_value = transform_string_concat(&expanded_value, current_fn_ctx);
     }
     expanded_value = transform_call_args(&expanded_value, fn_registry);
     expanded_value = transform_enum_struct_init(&expanded_value);
+    // `xs = [1, 2]` for a binding that is pushed to later is a Vec
+    if tracker.needs_vec(var_name, var_type) {
+        if let Some(vec) = vec_literal(&expanded_value) {
+            expanded_value = vec;
+        }
+    }
     
     let is_param = current_fn_ctx.params.contains_key(var_name);
+    // A `for` binding is declared by the loop header, like a parameter
+    let is_loop_binding = scope_analyzer.assigns_loop_binding(line_num);
     let is_shadowing = tracker.is_shadowing(var_name, line_num);
     let should_have_let = is_decl || (!is_mutation && !is_param) || is_shadowing;
     
@@ -81,8 +95,14 @@ pub fn process_assignment(
     
     if is_outer {
         format!("{}{} = {}{}", leading_ws, var_name, expanded_value, semi)
+    } else if is_explicit_mut && !enabled(LoweringRule::L04) {
+        // L-04 off: `mut x = 10` stays as written
+        format!("{}mut {}{} = {}{}", leading_ws, var_name, type_annotation, expanded_value, semi)
     } else if is_explicit_mut {
         format!("{}let mut {}{} = {}{}", leading_ws, var_name, type_annotation, expanded_value, semi)
+    } else if is_loop_binding || !enabled(LoweringRule::L03) {
+        // L-03 off: the assignment stays as written
+        format!("{}{} = {}{}", leading_ws, var_name, expanded_value, semi)
     } else if should_have_let {
         let let_keyword = if needs_mut { "let mut" } else { "let" };
         format!("{}{} {}{} = {}{}", leading_ws, let_keyword, var_name, type_annotation, expanded_value, semi)
@@ -98,6 +118,10 @@ pub fn process_assignment(
 /// 
 /// `var_part` could be "sender &Address" which needs to become ("sender", ": &Address")
 pub fn parse_var_type_annotation(var_part: &str) -> (&str, String) {
+    if let Some((vname, vtype)) = crate::ident::split_type_annotation(var_part) {
+        return (vname, format!(": {}", vtype));
+    }
+    
     if var_part.contains(' ') {
         let space_pos = var_part.find(' ').unwrap();
         let vname = var_part[..space_pos].trim();
@@ -132,11 +156,6 @@ pub fn parse_var_type_annotation(var_part: &str) -> (&str, String) {
         if vname_valid && vtype_valid {
             return (vname, format!(": {}", vtype));
         }
-    } else if var_part.contains(':') {
-        let parts: Vec<&str> = var_part.splitn(2, ':').collect();
-        if parts.len() == 2 {
-            return (parts[0].trim(), format!(": {}", parts[1].trim()));
-        }
     }
     
     (var_part, String::new())
@@ -171,8 +190,8 @@ pub fn handle_bare_mut_in_match(
         (var_part, String::new())
     };
     
-    let mut expanded_value = expand_value(val_part, None);
-    expanded_value = transform_array_access_clone(&expanded_value);
+    let mut expanded_value = expand_conditional_value(val_part, None);
+    expanded_value = current_fn_ctx.clone_array_access(&expanded_value);
     if current_fn_ctx.is_inside() {
         expanded_value = transform_string_concat(&expanded_value, current_fn_ctx);
     }
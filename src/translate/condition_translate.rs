@@ -0,0 +1,283 @@
+//! Condition Translation
+//!
+//! Normalizes the boolean conditions of `if`, `while` and match guards
+//! before the line is lowered.
+//!
+//! RustS+ condition:
+//! ```text
+//! if 0 < x < 10 {
+//! n if (n >= 1 <= limit) { "in range" }
+//! ```
+//!
+//! Rust output:
+//! ```text
+//! if 0 < x && x < 10 {
+//! n if n >= 1 && 1 <= limit { "in range" }
+//! ```
+//!
+//! Rules:
+//! - Chained comparisons `a < b <= c` expand to `a < b && b <= c`. The middle
+//!   operand is repeated, so it is evaluated twice.
+//! - Redundant parentheses around a whole condition are dropped
+//!   (`if (x > 0) {` → `if x > 0 {`), avoiding rustc's `unused_parens` lint.
+//! - `if let` / `while let` conditions are left alone.
+
+use crate::lowering::depth_tracking_lowering::delimiter_positions;
+
+/// A comparison operator found at the top level of an operand
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ComparisonOp {
+    pos: usize,
+    len: usize,
+}
+
+/// Find comparison operators at nesting depth 0 (outside strings, parens,
+/// brackets, braces and turbofish generics). Returns None if the operand
+/// contains a plain `=` (assignment / `let` binding) and must not be touched.
+fn top_level_comparisons(expr: &str) -> Option<Vec<ComparisonOp>> {
+    let bytes = expr.as_bytes();
+    let delims = delimiter_positions(expr, &['(', ')', '[', ']', '{', '}', '<', '>', '=', '!']);
+    let mut ops = Vec::new();
+    let mut depth = 0i32;
+    let mut angle_depth = 0i32;
+    let mut skip_until = 0usize;
+
+    for &(pos, c) in &delims {
+        if pos < skip_until {
+            continue;
+        }
+        let next = bytes.get(pos + 1).copied();
+        let prev = if pos > 0 { bytes.get(pos - 1).copied() } else { None };
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ if depth > 0 => {}
+            '<' if prev == Some(b':') => angle_depth += 1,
+            '>' if angle_depth > 0 => angle_depth -= 1,
+            _ if angle_depth > 0 => {}
+            '=' | '!' | '<' | '>' => {
+                // Shifts and arrows are not comparisons
+                if (c == '<' && next == Some(b'<')) || (c == '>' && next == Some(b'>'))
+                    || (c == '>' && matches!(prev, Some(b'-') | Some(b'=') | Some(b'<') | Some(b'>')))
+                    || (c == '<' && prev == Some(b'<'))
+                {
+                    skip_until = pos + 1;
+                    continue;
+                }
+                if c == '=' && next == Some(b'>') {
+                    return None;
+                }
+                if next == Some(b'=') {
+                    ops.push(ComparisonOp { pos, len: 2 });
+                    skip_until = pos + 2;
+                } else if c == '<' || c == '>' {
+                    ops.push(ComparisonOp { pos, len: 1 });
+                } else if c == '=' {
+                    // Bare `=` - assignment, not a condition
+                    return None;
+                }
+                // Bare `!` is negation
+            }
+            _ => {}
+        }
+    }
+    Some(ops)
+}
+
+/// Expand a chained comparison: `a < b < c` → `a < b && b < c`
+pub fn expand_chained_comparison(expr: &str) -> String {
+    let ops = match top_level_comparisons(expr) {
+        Some(ops) if ops.len() >= 2 => ops,
+        _ => return expr.to_string(),
+    };
+
+    let mut operands = Vec::with_capacity(ops.len() + 1);
+    let mut start = 0;
+    for op in &ops {
+        operands.push(expr[start..op.pos].trim());
+        start = op.pos + op.len;
+    }
+    operands.push(expr[start..].trim());
+    if operands.iter().any(|o| o.is_empty()) {
+        return expr.to_string();
+    }
+
+    ops.iter()
+        .enumerate()
+        .map(|(i, op)| format!("{} {} {}", operands[i], &expr[op.pos..op.pos + op.len], operands[i + 1]))
+        .collect::<Vec<_>>()
+        .join(" && ")
+}
+
+/// If `expr` is entirely wrapped in one pair of parentheses, return the inside
+fn strip_outer_parens(expr: &str) -> Option<&str> {
+    if !expr.starts_with('(') || !expr.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0;
+    for (pos, c) in delimiter_positions(expr, &['(', ')']) {
+        depth += if c == '(' { 1 } else { -1 };
+        if depth == 0 && pos != expr.len() - 1 {
+            return None;
+        }
+    }
+    let inner = &expr[1..expr.len() - 1];
+    // `(a, b)` is a tuple, not a parenthesized condition
+    if delimiter_positions(inner, &[',', '(', ')']).iter()
+        .scan(0, |d, &(_, c)| {
+            *d += match c { '(' => 1, ')' => -1, _ => 0 };
+            Some((*d, c))
+        })
+        .any(|(d, c)| d == 0 && c == ',')
+    {
+        return None;
+    }
+    Some(inner)
+}
+
+/// Split on top-level `&&` / `||`, keeping the operators
+fn split_logical(expr: &str) -> Vec<&str> {
+    let bytes = expr.as_bytes();
+    let mut parts = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    let mut skip_until = 0;
+    for (pos, c) in delimiter_positions(expr, &['(', ')', '[', ']', '{', '}', '&', '|']) {
+        if pos < skip_until {
+            continue;
+        }
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '&' | '|' if depth == 0 && bytes.get(pos + 1) == Some(&(c as u8)) => {
+                parts.push(&expr[start..pos]);
+                parts.push(&expr[pos..pos + 2]);
+                start = pos + 2;
+                skip_until = pos + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+/// Normalize a boolean condition (the text between `if`/`while` and `{`)
+pub fn normalize_condition(cond: &str) -> String {
+    let cond = cond.trim();
+    if cond.starts_with("let ") {
+        return cond.to_string();
+    }
+    if let Some(inner) = strip_outer_parens(cond) {
+        return normalize_condition(inner);
+    }
+
+    let parts = split_logical(cond);
+    if parts.len() == 1 {
+        return expand_chained_comparison(cond);
+    }
+    parts
+        .iter()
+        .map(|part| match part.trim() {
+            op @ ("&&" | "||") => op.to_string(),
+            operand => match operand.strip_prefix('!').and_then(strip_outer_parens) {
+                Some(inner) => format!("!({})", normalize_condition(inner)),
+                None => match strip_outer_parens(operand) {
+                    Some(inner) => format!("({})", normalize_condition(inner)),
+                    None => expand_chained_comparison(operand),
+                },
+            },
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Is there an identifier character right before `pos`?
+fn ident_char_before(line: &str, pos: usize) -> bool {
+    line[..pos].chars().next_back().is_some_and(crate::ident::is_ident_continue)
+}
+
+/// Normalize every `if` / `while` / match-guard condition on a line.
+/// Conditions that continue on the next line are left untouched.
+pub fn normalize_line_conditions(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut copied = 0;
+
+    for (pos, _) in delimiter_positions(line, &['i', 'w']) {
+        if pos < copied || ident_char_before(line, pos) {
+            continue;
+        }
+        let keyword = if line[pos..].starts_with("if ") {
+            "if "
+        } else if line[pos..].starts_with("while ") {
+            "while "
+        } else {
+            continue;
+        };
+        let cond_start = pos + keyword.len();
+
+        // Condition ends at the first `{` outside any parens/brackets
+        let mut depth = 0i32;
+        let mut cond_end = None;
+        for (p, c) in delimiter_positions(&line[cond_start..], &['(', ')', '[', ']', '{']) {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                _ if depth == 0 => {
+                    cond_end = Some(cond_start + p);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let Some(cond_end) = cond_end else {
+            continue;
+        };
+
+        out.push_str(&line[copied..cond_start]);
+        out.push_str(&normalize_condition(&line[cond_start..cond_end]));
+        out.push(' ');
+        copied = cond_end;
+    }
+
+    if copied == 0 {
+        return line.to_string();
+    }
+    out.push_str(&line[copied..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_chained_comparison() {
+        assert_eq!(expand_chained_comparison("0 < x < 10"), "0 < x && x < 10");
+        assert_eq!(expand_chained_comparison("a <= f(b, c) == d"), "a <= f(b, c) && f(b, c) == d");
+        assert_eq!(expand_chained_comparison("x < 10"), "x < 10");
+        assert_eq!(expand_chained_comparison("a << 2 < b"), "a << 2 < b");
+        assert_eq!(expand_chained_comparison("v.iter().collect::<Vec<u8>>().len() > 3"),
+            "v.iter().collect::<Vec<u8>>().len() > 3");
+    }
+
+    #[test]
+    fn test_normalize_condition() {
+        assert_eq!(normalize_condition("(x > 0)"), "x > 0");
+        assert_eq!(normalize_condition("0 < x < 10 && ok"), "0 < x && x < 10 && ok");
+        assert_eq!(normalize_condition("!(1 <= n <= 9) || done"), "!(1 <= n && n <= 9) || done");
+        assert_eq!(normalize_condition("let Some(x) = opt"), "let Some(x) = opt");
+        assert_eq!(normalize_condition("(a, b) == (c, d)"), "(a, b) == (c, d)");
+    }
+
+    #[test]
+    fn test_normalize_line_conditions() {
+        assert_eq!(normalize_line_conditions("if 0 < x < 10 {"), "if 0 < x && x < 10 {");
+        assert_eq!(normalize_line_conditions("} else if (a < b < c) {"), "} else if a < b && b < c {");
+        assert_eq!(normalize_line_conditions("while lo <= mid < hi {"), "while lo <= mid && mid < hi {");
+        assert_eq!(normalize_line_conditions("n if 0 < n < 10 { \"small\" }"), "n if 0 < n && n < 10 { \"small\" }");
+        assert_eq!(normalize_line_conditions("println(\"if 1 < 2 < 3 {\")"), "println(\"if 1 < 2 < 3 {\")");
+        assert_eq!(normalize_line_conditions("verify = a < b"), "verify = a < b");
+        assert_eq!(normalize_line_conditions("if a < b &&"), "if a < b &&");
+    }
+}
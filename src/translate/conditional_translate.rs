@@ -0,0 +1,233 @@
+//! Conditional Expression Translation
+//!
+//! Lowers the conditional assignment sugar to a Rust `if` expression.
+//!
+//! RustS+ syntax:
+//! ```text
+//! sign = n < 0 ? -1 : 1
+//! label = "big" if n > 100 else "small"
+//! grade = n > 90 ? "A" : n > 50 ? "B" : "C"
+//! ```
+//!
+//! Rust output:
+//! ```text
+//! let sign = if n < 0 { -1 } else { 1 };
+//! let label = if n > 100 { "big" } else { "small" };
+//! let grade = if n > 90 { "A" } else if n > 50 { "B" } else { "C" };
+//! ```
+//!
+//! Rules:
+//! - Only operators at the top level count: not inside strings, `()`, `[]`
+//!   or `{}`. The `?` must have spaces on both sides, so the `?` operator
+//!   (`parse()?`) is never mistaken for a conditional.
+//! - The `? :` form nests to the right (`a ? b : c ? d : e`), and so does a
+//!   conditional in the `else` branch of the `if else` form.
+//! - String literal branches become `String::from(..)` when the target is
+//!   declared `String`, or when the other branch already builds a `String`.
+//! - Conditions are normalized like `if` conditions (chained comparisons).
+
+use crate::translate::condition_translate::normalize_condition;
+use crate::variable::{expand_value, VariableTracker};
+
+/// A conditional expression split into its parts
+#[derive(Debug, Clone, PartialEq)]
+struct Conditional<'a> {
+    cond: &'a str,
+    then_branch: &'a str,
+    else_branch: &'a str,
+}
+
+/// Expand an assignment value, lowering conditional sugar if present
+pub fn expand_conditional_value(value: &str, explicit_type: Option<&str>) -> String {
+    match lower_conditional(value, explicit_type) {
+        Some(lowered) => lowered,
+        None => expand_value(value, explicit_type),
+    }
+}
+
+/// `cond ? a : b` / `a if cond else b` → `if cond { a } else { b }`
+pub fn lower_conditional(value: &str, explicit_type: Option<&str>) -> Option<String> {
+    let cond = parse_conditional(value.trim())?;
+    let then_expr = lower_branch(cond.then_branch, cond.else_branch, explicit_type);
+    let else_expr = match lower_conditional(cond.else_branch, explicit_type) {
+        Some(nested) => nested,
+        None => format!("{{ {} }}", lower_branch(cond.else_branch, cond.then_branch, explicit_type)),
+    };
+    Some(format!("if {} {{ {} }} else {}", normalize_condition(cond.cond), then_expr, else_expr))
+}
+
+/// Lower one branch; `other` is the opposite branch, used to pick the
+/// string representation
+fn lower_branch(branch: &str, other: &str, explicit_type: Option<&str>) -> String {
+    let branch = branch.trim();
+    if let Some(nested) = lower_conditional(branch, explicit_type) {
+        return nested;
+    }
+    if VariableTracker::detect_string_literal(branch) && explicit_type.is_none() && builds_string(other) {
+        return expand_value(branch, Some("String"));
+    }
+    expand_value(branch, explicit_type)
+}
+
+/// Does a branch (or a nested conditional) produce an owned `String`?
+fn builds_string(expr: &str) -> bool {
+    if let Some(cond) = parse_conditional(expr.trim()) {
+        return builds_string(cond.then_branch) || builds_string(cond.else_branch);
+    }
+    let expr = expr.trim();
+    expr.starts_with("String::")
+        || expr.starts_with("format!(")
+        || expr.ends_with(".to_string()")
+        || expr.ends_with(".to_owned()")
+}
+
+fn parse_conditional(value: &str) -> Option<Conditional<'_>> {
+    // Plain `if` expressions, closures and matches are left alone
+    if value.starts_with("if ") || value.starts_with('|') || value.starts_with("match ") {
+        return None;
+    }
+    let tokens = top_level_tokens(value);
+    parse_question_form(value, &tokens).or_else(|| parse_if_else_form(value, &tokens))
+}
+
+/// `cond ? a : b`: the first ` ? ` and the ` : ` that pairs with it
+fn parse_question_form<'a>(value: &'a str, tokens: &[(usize, Token)]) -> Option<Conditional<'a>> {
+    let question = tokens.iter().position(|&(_, t)| t == Token::Question)?;
+    let mut pending = 0usize;
+    for &(pos, token) in &tokens[question + 1..] {
+        match token {
+            Token::Question => pending += 1,
+            Token::Colon if pending > 0 => pending -= 1,
+            Token::Colon => {
+                let q_pos = tokens[question].0;
+                return non_empty(Conditional {
+                    cond: &value[..q_pos],
+                    then_branch: &value[q_pos + 1..pos],
+                    else_branch: &value[pos + 1..],
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `a if cond else b`: the first ` if ` and the first ` else ` after it
+fn parse_if_else_form<'a>(value: &'a str, tokens: &[(usize, Token)]) -> Option<Conditional<'a>> {
+    let if_idx = tokens.iter().position(|&(_, t)| t == Token::If)?;
+    let if_pos = tokens[if_idx].0;
+    let else_pos = tokens[if_idx + 1..].iter().find(|&&(_, t)| t == Token::Else)?.0;
+    non_empty(Conditional {
+        then_branch: &value[..if_pos],
+        cond: &value[if_pos + 2..else_pos],
+        else_branch: &value[else_pos + 4..],
+    })
+}
+
+fn non_empty(cond: Conditional<'_>) -> Option<Conditional<'_>> {
+    let parts = [cond.cond, cond.then_branch, cond.else_branch];
+    if parts.iter().any(|p| p.trim().is_empty()) {
+        return None;
+    }
+    Some(cond)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Question,
+    Colon,
+    If,
+    Else,
+}
+
+/// Positions of ` ? `, ` : `, ` if ` and ` else ` at the top level
+fn top_level_tokens(s: &str) -> Vec<(usize, Token)> {
+    let bytes = s.as_bytes();
+    let spaced = |pos: usize, len: usize| {
+        pos > 0 && bytes[pos - 1] == b' ' && bytes.get(pos + len) == Some(&b' ')
+    };
+    let mut tokens = Vec::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            pos += 1;
+            continue;
+        }
+        match c {
+            b'"' => in_string = true,
+            // Char literal: `'x'` or `'\n'` (lifetimes have no closing quote)
+            b'\'' if bytes.get(pos + 1) == Some(&b'\\') => {
+                pos += s[pos + 2..].find('\'').map(|end| end + 2).unwrap_or(0);
+            }
+            b'\'' if bytes.get(pos + 2) == Some(&b'\'') => pos += 2,
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' => depth -= 1,
+            _ if depth != 0 => {}
+            b'?' if spaced(pos, 1) => tokens.push((pos, Token::Question)),
+            b':' if spaced(pos, 1) => tokens.push((pos, Token::Colon)),
+            b'i' if s[pos..].starts_with("if") && spaced(pos, 2) => tokens.push((pos, Token::If)),
+            b'e' if s[pos..].starts_with("else") && spaced(pos, 4) => tokens.push((pos, Token::Else)),
+            _ => {}
+        }
+        pos += 1;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_question_form() {
+        assert_eq!(lower_conditional("n < 0 ? -1 : 1", None).unwrap(), "if n < 0 { -1 } else { 1 }");
+        assert_eq!(
+            lower_conditional("n > 90 ? \"A\" : n > 50 ? \"B\" : \"C\"", None).unwrap(),
+            "if n > 90 { \"A\" } else if n > 50 { \"B\" } else { \"C\" }"
+        );
+        assert_eq!(lower_conditional("a ? b ? 1 : 2 : 3", None).unwrap(), "if a { if b { 1 } else { 2 } } else { 3 }");
+    }
+
+    #[test]
+    fn test_if_else_form() {
+        assert_eq!(lower_conditional("\"big\" if n > 100 else \"small\"", None).unwrap(),
+            "if n > 100 { \"big\" } else { \"small\" }");
+        assert_eq!(lower_conditional("x if 0 < x < 10 else 0", None).unwrap(),
+            "if 0 < x && x < 10 { x } else { 0 }");
+    }
+
+    #[test]
+    fn test_string_branches() {
+        assert_eq!(lower_conditional("ok ? \"yes\" : \"no\"", Some("String")).unwrap(),
+            "if ok { String::from(\"yes\") } else { String::from(\"no\") }");
+        assert_eq!(lower_conditional("ok ? name.to_string() : \"anon\"", None).unwrap(),
+            "if ok { name.to_string() } else { String::from(\"anon\") }");
+    }
+
+    #[test]
+    fn test_not_a_conditional() {
+        for value in [
+            "s.parse::<i32>()?",
+            "read(path)? + 1",
+            "if ok { 1 } else { 2 }",
+            "\"what ? is : this\"",
+            "f(a ? b : c)",
+            "|x| x + 1",
+            "cond ? 1",
+            "'?' == c",
+        ] {
+            assert_eq!(lower_conditional(value, None), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Dispatch Table Translation
+//!
+//! Handles `match` assignments whose arms select a function:
+//!
+//! RustS+:
+//! ```text
+//! handler = match kind {
+//!     "json" { parse_json }
+//!     _ { |s| s.len() as i32 }
+//! }
+//! result = handler(input)
+//! ```
+//!
+//! Rust output:
+//! ```text
+//! let handler: fn(&str) -> i32 = match kind {
+//!     "json" => { parse_json },
+//!     _ => { |s| s.len() as i32 },
+//! };
+//! let result = handler(input);
+//! ```
+//!
+//! The binding gets an explicit fn pointer type so fn items and closures
+//! unify, and it is registered as an alias of the selected signature so later
+//! calls through it get the same argument lowering as direct calls.
+
+use crate::function::{FunctionRegistry, FunctionSignature};
+use crate::helpers::{strip_inline_comment, transform_generic_brackets};
+use crate::lowering::depth_tracking_lowering::{count_braces_outside_strings, delimiter_positions};
+
+/// What an arm evaluates to
+enum ArmValue<'a> {
+    Function(&'a FunctionSignature),
+    Closure,
+}
+
+fn is_path_ident(s: &str) -> bool {
+    !s.is_empty()
+        && s.split("::").all(crate::ident::is_valid_identifier)
+}
+
+/// Is `s` a closure expression (`|x| ...`, `move |x| ...`, `|| ...`)?
+pub fn is_closure_expr(s: &str) -> bool {
+    let s = s.strip_prefix("move ").unwrap_or(s).trim_start();
+    s.starts_with('|')
+}
+
+/// Is `s` a bare function reference or closure (a valid dispatch arm value)?
+pub fn is_fn_value_expr(s: &str) -> bool {
+    is_path_ident(s) || is_closure_expr(s)
+}
+
+fn classify_arm_value<'a>(value: &str, registry: &'a FunctionRegistry) -> Option<ArmValue<'a>> {
+    if is_closure_expr(value) {
+        return Some(ArmValue::Closure);
+    }
+    if !is_path_ident(value) {
+        return None;
+    }
+    let name = value.rsplit("::").next().unwrap_or(value);
+    let sig = registry.get(name)?;
+    let is_method = sig.parameters.iter().any(|p| p.name == "self");
+    if sig.generics.is_some() || is_method {
+        return None;
+    }
+    Some(ArmValue::Function(sig))
+}
+
+/// Body of a single-line arm `pattern { body }`
+fn single_line_arm_body(trimmed: &str) -> Option<&str> {
+    if !trimmed.ends_with('}') {
+        return None;
+    }
+    let braces = delimiter_positions(trimmed, &['{', '}']);
+    let close = braces.last()?.0;
+    let mut depth = 0;
+    for &(pos, c) in braces.iter().rev() {
+        depth += if c == '}' { 1 } else { -1 };
+        if depth == 0 {
+            return Some(trimmed[pos + 1..close].trim());
+        }
+    }
+    None
+}
+
+/// Collect the value expression of every arm of the match starting at
+/// `match_line` (0-based). Returns None if the match is not closed.
+fn collect_arm_values(lines: &[&str], match_line: usize) -> Option<Vec<String>> {
+    let mut values = Vec::new();
+    let mut depth = 1usize;
+    let mut last_body_line: Option<String> = None;
+
+    for line in lines.iter().skip(match_line + 1) {
+        let clean = strip_inline_comment(line);
+        let trimmed = clean.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        let before = depth;
+        depth = (depth + opens).saturating_sub(closes);
+
+        if before == 1 {
+            if depth == 0 {
+                return Some(values);
+            }
+            if depth == 1 {
+                values.push(single_line_arm_body(trimmed)?.to_string());
+            }
+            last_body_line = None;
+        } else if before == 2 && depth == 1 && trimmed == "}" {
+            // Multi-line arm closed - its last line is the value
+            values.push(last_body_line.take()?);
+        } else if before == 2 && depth == 2 {
+            last_body_line = Some(trimmed.to_string());
+        } else {
+            last_body_line = None;
+        }
+    }
+    None
+}
+
+/// Render a signature as a fn pointer type: `fn(&str, i32) -> bool`
+pub fn fn_pointer_type(sig: &FunctionSignature) -> String {
+    let params: Vec<&str> = sig.parameters.iter().map(|p| p.param_type.as_str()).collect();
+    match sig.return_type.as_deref().map(str::trim) {
+        Some(rt) if !rt.is_empty() && rt != "()" => {
+            format!("fn({}) -> {}", params.join(", "), transform_generic_brackets(rt))
+        }
+        _ => format!("fn({})", params.join(", ")),
+    }
+}
+
+/// Infer the fn signature selected by a dispatch `match` assignment.
+///
+/// Every arm must evaluate to a registered function or a closure, at least
+/// one arm must be a function, and all functions must share one fn type.
+pub fn infer_dispatch_signature(
+    lines: &[&str],
+    match_line: usize,
+    registry: &FunctionRegistry,
+) -> Option<FunctionSignature> {
+    let values = collect_arm_values(lines, match_line)?;
+    let mut selected: Option<&FunctionSignature> = None;
+
+    for value in &values {
+        match classify_arm_value(value, registry)? {
+            ArmValue::Closure => {}
+            ArmValue::Function(sig) => match selected {
+                Some(prev) if fn_pointer_type(prev) != fn_pointer_type(sig) => return None,
+                _ => selected = Some(sig),
+            },
+        }
+    }
+    selected.cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::{parse_function_line, FunctionParseResult};
+
+    fn registry(fns: &[&str]) -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        for f in fns {
+            if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(f) {
+                registry.register(sig);
+            }
+        }
+        registry
+    }
+
+    #[test]
+    fn test_infer_dispatch_signature() {
+        let reg = registry(&["fn parse_json(s &str) i32 {", "fn parse_text(s &str) i32 {", "fn other(n u8) {"]);
+        let src = ["h = match kind {", "    \"json\" { parse_json }", "    \"txt\" {", "        parse_text", "    }", "    _ { |s| 0 }", "}"];
+        let sig = infer_dispatch_signature(&src, 0, &reg).expect("dispatch not detected");
+        assert_eq!(fn_pointer_type(&sig), "fn(&str) -> i32");
+
+        // Mismatched fn types or non-fn arm values are not dispatch tables
+        let mixed = ["h = match kind {", "    1 { parse_json }", "    _ { other }", "}"];
+        assert!(infer_dispatch_signature(&mixed, 0, &reg).is_none());
+        let values = ["h = match kind {", "    1 { 10 }", "    _ { parse_json }", "}"];
+        assert!(infer_dispatch_signature(&values, 0, &reg).is_none());
+    }
+
+    #[test]
+    fn test_fn_value_expr() {
+        assert!(is_fn_value_expr("parse_json"));
+        assert!(is_fn_value_expr("codec::decode"));
+        assert!(is_fn_value_expr("move |x| x + 1"));
+        assert!(!is_fn_value_expr("parse(x)"));
+        assert!(!is_fn_value_expr("\"text\""));
+    }
+}
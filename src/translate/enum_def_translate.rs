@@ -18,7 +18,10 @@
 //! }
 //! ```
 
-use crate::enum_def::{EnumParseContext, is_enum_definition, transform_enum_variant};
+use crate::enum_def::{
+    EnumParseContext, is_enum_definition, transform_enum_variant, transform_inline_enum_body,
+};
+use crate::helpers::transform_generic_brackets;
 
 /// Result of processing an enum definition line
 pub enum EnumDefResult {
@@ -46,19 +49,30 @@ pub fn process_enum_def_line(
 ) -> EnumDefResult {
     // Check for enum definition start
     if is_enum_definition(trimmed) && !enum_ctx.in_enum_def {
+        // Single-line enum: `enum Kind { A(Vec[u8]), B }`
+        // CRITICAL FIX: Must NOT enter enum mode - the closing `}` is on this line,
+        // so entering would swallow every following line as a variant.
+        if opens > 0 && opens == closes && trimmed.ends_with('}') {
+            if let (Some(open), Some(close)) = (trimmed.find('{'), trimmed.rfind('}')) {
+                let header = transform_generic_brackets(trimmed[..open].trim_end());
+                let body = transform_inline_enum_body(&trimmed[open + 1..close]);
+                return EnumDefResult::ClosedEnum(format!("{}{} {{ {} }}", leading_ws, header, body));
+            }
+        }
+        
         enum_ctx.enter_enum(brace_depth);
         
         // CRITICAL FIX: Do NOT auto-inject Clone!
         // Some enum variants may contain non-Clone types.
         // Let user explicitly add #[derive(Clone)] when needed.
         
-        return EnumDefResult::Started(format!("{}{}", leading_ws, trimmed));
+        return EnumDefResult::Started(format!("{}{}", leading_ws, transform_generic_brackets(trimmed)));
     }
     
     // Process inside enum definition
     if enum_ctx.in_enum_def {
         // Check for closing struct variant
-        if trimmed == "}" && enum_ctx.in_struct_variant {
+        if (trimmed == "}" || trimmed == "},") && enum_ctx.in_struct_variant {
             enum_ctx.exit_struct_variant();
             return EnumDefResult::ClosedStructVariant(format!("{}}},", leading_ws));
         }
@@ -76,6 +90,15 @@ pub fn process_enum_def_line(
         
         // Transform variant
         let transformed = transform_enum_variant(clean_line, enum_ctx.in_struct_variant);
+        // CRITICAL FIX: Transform generic brackets in variant payload types
+        // e.g., Batch(Vec[Tx]) -> Batch(Vec<Tx>), items Vec[Tx] -> items: Vec<Tx>
+        // Attributes pass through untouched (may contain `[` in strings)
+        let transformed = if transformed.trim_start().starts_with("#[") {
+            transformed
+        } else {
+            let ws: String = transformed.chars().take_while(|c| c.is_whitespace()).collect();
+            format!("{}{}", ws, transform_generic_brackets(&transformed))
+        };
         return EnumDefResult::Variant(transformed);
     }
     
@@ -142,4 +165,42 @@ mod tests {
         assert!(matches!(result, EnumDefResult::ClosedEnum(_)));
         assert!(!enum_ctx.in_enum_def);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_enum_def_bracket_generic_payloads() {
+        let mut enum_ctx = EnumParseContext::new();
+        enum_ctx.enter_enum(0);
+        
+        let result = process_enum_def_line(
+            "Batch(Vec[Tx])", "    Batch(Vec[Tx])", "    ", 1, 0, 0, &mut enum_ctx,
+        );
+        match result {
+            EnumDefResult::Variant(s) => assert_eq!(s, "    Batch(Vec<Tx>),"),
+            _ => panic!("Expected Variant"),
+        }
+        
+        let result = process_enum_def_line(
+            "Named { items Vec[Tx], map HashMap[String, u64] }",
+            "    Named { items Vec[Tx], map HashMap[String, u64] }",
+            "    ", 1, 1, 1, &mut enum_ctx,
+        );
+        match result {
+            EnumDefResult::Variant(s) => {
+                assert_eq!(s, "    Named { items: Vec<Tx>, map: HashMap<String, u64> },")
+            }
+            _ => panic!("Expected Variant"),
+        }
+    }
+    
+    #[test]
+    fn test_single_line_enum_does_not_enter_mode() {
+        let mut enum_ctx = EnumParseContext::new();
+        let line = "enum Inline { A(Vec[u8]), B }";
+        let result = process_enum_def_line(line, line, "", 0, 1, 1, &mut enum_ctx);
+        match result {
+            EnumDefResult::ClosedEnum(s) => assert_eq!(s, "enum Inline { A(Vec<u8>), B }"),
+            _ => panic!("Expected ClosedEnum"),
+        }
+        assert!(!enum_ctx.in_enum_def);
+    }
+}
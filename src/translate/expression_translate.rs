@@ -8,13 +8,12 @@
 //! - Return expressions
 //! - Bare expressions
 
-use crate::variable::expand_value;
+use crate::translate::conditional_translate::expand_conditional_value;
 use crate::function::{
     CurrentFunctionContext, FunctionRegistry,
     transform_string_concat, transform_call_args, should_be_tail_return,
 };
 use crate::control_flow::transform_enum_struct_init;
-use crate::clone_helpers::transform_array_access_clone;
 use crate::helpers::{ends_with_continuation_operator, needs_semicolon};
 use crate::transform_literal::is_string_literal;
 use crate::translate::assignment_translate::parse_var_type_annotation;
@@ -43,8 +42,8 @@ pub fn process_non_assignment(
             
             let (var_name, type_annotation) = parse_var_type_annotation(var_part);
             
-            let mut expanded_value = expand_value(val_part, None);
-            expanded_value = transform_array_access_clone(&expanded_value);
+            let mut expanded_value = expand_conditional_value(val_part, None);
+            expanded_value = current_fn_ctx.clone_array_access(&expanded_value);
             if current_fn_ctx.is_inside() {
                 expanded_value = transform_string_concat(&expanded_value, current_fn_ctx);
             }
@@ -138,8 +137,8 @@ pub fn process_tuple_destructuring(
     }
     
     // Transform value
-    let mut expanded_value = expand_value(value_part, None);
-    expanded_value = transform_array_access_clone(&expanded_value);
+    let mut expanded_value = expand_conditional_value(value_part, None);
+    expanded_value = current_fn_ctx.clone_array_access(&expanded_value);
     if current_fn_ctx.is_inside() {
         expanded_value = transform_string_concat(&expanded_value, current_fn_ctx);
     }
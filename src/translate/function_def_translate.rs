@@ -17,17 +17,26 @@ use crate::function::{
     CurrentFunctionContext,
 };
 use crate::helpers::needs_semicolon;
+use crate::lowering::depth_tracking_lowering::find_matching_close;
+use crate::lowering_rules::{enabled, LoweringRule};
 use crate::lowering::lookahead_lowering::check_next_line_is_where;
+use crate::translate::trait_impl_translate::adapt_trait_method_signature;
 
 /// Result of processing a function definition
 pub enum FunctionDefResult {
     /// Function was processed
     Handled(String),
+    /// Signature failed to parse - the caller reports the error
+    Malformed(String),
     /// Not a function definition
     NotFunctionDef,
 }
 
 /// Process a function definition line
+///
+/// `impl_trait`: trait being implemented by the enclosing `impl Trait for Type`
+/// block, if any. Methods of std traits get their required signature.
+#[allow(clippy::too_many_arguments)]
 pub fn process_function_def(
     trimmed: &str,
     clean_line: &str,
@@ -36,6 +45,7 @@ pub fn process_function_def(
     line_num: usize,
     current_fn_ctx: &mut CurrentFunctionContext,
     function_start_brace: usize,
+    impl_trait: Option<&str>,
 ) -> FunctionDefResult {
     if !trimmed.starts_with("fn ") && !trimmed.starts_with("pub fn ") {
         return FunctionDefResult::NotFunctionDef;
@@ -57,29 +67,53 @@ pub fn process_function_def(
     };
     
     match parse_function_line(trimmed) {
-        FunctionParseResult::RustSPlusSignature(sig) => {
-            let output = if is_trait_method_declaration {
+        FunctionParseResult::RustSPlusSignature(mut sig) => {
+            if let Some(trait_name) = impl_trait {
+                adapt_trait_method_signature(&mut sig, trait_name);
+            }
+            // A single-line function (`= expr`) has its body on the line
+            let output = if is_trait_method_declaration && !sig.is_single_line {
                 // Trait method declaration - add semicolon
                 format!("{}{};", leading_ws, signature_to_rust_with_where(&sig, true))
             } else {
                 // Regular function or function with where clause
                 format!("{}{}", leading_ws, signature_to_rust_with_where(&sig, next_line_is_where))
             };
-            FunctionDefResult::Handled(output)
+            FunctionDefResult::Handled(apply_signature_rules(output, trimmed, leading_ws))
         }
         FunctionParseResult::RustPassthrough => {
             let output = process_rust_passthrough_function(clean_line, trimmed, current_fn_ctx, function_start_brace);
             FunctionDefResult::Handled(output)
         }
-        FunctionParseResult::Error(e) => {
-            FunctionDefResult::Handled(format!("{}// COMPILE ERROR: {}\n{}", leading_ws, e, clean_line))
-        }
+        FunctionParseResult::Error(e) => FunctionDefResult::Malformed(e),
         FunctionParseResult::NotAFunction => {
             FunctionDefResult::Handled(clean_line.to_string())
         }
     }
 }
 
+/// L-01 and L-05 for a lowered signature: with L-01 off the signature
+/// stays as written, with L-05 off its `effects(..)` clause is kept after
+/// the parameter list
+pub fn apply_signature_rules(output: String, source: &str, leading_ws: &str) -> String {
+    if !enabled(LoweringRule::L01) {
+        return format!("{}{}", leading_ws, source);
+    }
+    if enabled(LoweringRule::L05) || output.contains("effects(") {
+        return output;
+    }
+    let clause = source.find("effects(").and_then(|start| {
+        find_matching_close(source, start + "effects".len()).map(|end| &source[start..=end])
+    });
+    let params_end = output.find("fn ")
+        .and_then(|fn_pos| output[fn_pos..].find('(').map(|open| fn_pos + open))
+        .and_then(|open| find_matching_close(&output, open));
+    match (clause, params_end) {
+        (Some(clause), Some(end)) => format!("{} {}{}", &output[..=end], clause, &output[end + 1..]),
+        _ => output,
+    }
+}
+
 /// Process a Rust-native function that passes through
 /// 
 /// This handles functions that are already in Rust syntax but may have
@@ -160,6 +194,7 @@ mod tests {
             0,
             &mut ctx,
             0,
+            None,
         );
         
         // Should be handled as trait method (with semicolon)
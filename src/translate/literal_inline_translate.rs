@@ -5,37 +5,17 @@
 //! RustS+ field syntax: `field = value`
 //! Rust field syntax: `field: value`
 
+use crate::lowering::depth_tracking_lowering::split_top_level;
 use crate::transform_literal::find_field_eq;
 
 /// Transform inline fields: `x = 1, y = 2` -> `x: 1, y: 2`
 pub fn transform_fields_inline(fields: &str) -> String {
-    let mut result = Vec::new();
-    let mut current = String::new();
-    let mut in_string = false;
-    let mut brace_depth: usize = 0;
-    
-    for c in fields.chars() {
-        if c == '"' && !current.ends_with('\\') {
-            in_string = !in_string;
-        }
-        if !in_string {
-            if c == '{' { brace_depth += 1; }
-            if c == '}' { brace_depth = brace_depth.saturating_sub(1); }
-        }
-        
-        if c == ',' && !in_string && brace_depth == 0 {
-            result.push(transform_single_inline_field(&current));
-            current.clear();
-        } else {
-            current.push(c);
-        }
-    }
-    
-    if !current.trim().is_empty() {
-        result.push(transform_single_inline_field(&current));
-    }
-    
-    result.join(", ")
+    split_top_level(fields, ',')
+        .into_iter()
+        .filter(|f| !f.trim().is_empty())
+        .map(transform_single_inline_field)
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Transform a single field: `field = value` -> `field: value`
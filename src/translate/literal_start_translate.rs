@@ -9,6 +9,7 @@
 //! - Bare struct literal: `Config { ... }`
 //! - Bare enum literal: `Status::Active { ... }`
 //! - Literal inside function call: `Some(Config { ... })`
+//! - Constant: `const ORIGIN Point = Point { ... }`
 
 use crate::modes::{LiteralModeStack, LiteralKind};
 use crate::detection::{
@@ -18,12 +19,18 @@ use crate::detection::{
 };
 use crate::inline_literal_transform::{
     transform_single_line_struct_literal, transform_single_line_enum_literal,
-    transform_bare_struct_literal,
+    transform_bare_struct_literal, lower_assigned_literal,
 };
-use crate::helpers::{is_field_access, is_tuple_pattern};
+use crate::parser::{parse_line, LineShape};
+use crate::helpers::{is_field_access, is_tuple_pattern, is_valid_identifier};
 use crate::scope::ScopeAnalyzer;
-use crate::variable::VariableTracker;
+use crate::variable::{VariableTracker, parse_rusts_assignment_ext};
 use crate::struct_def::StructRegistry;
+use crate::constants::parse_const_decl;
+use crate::lowering_rules::{enabled, LoweringRule};
+use crate::lowering::depth_tracking_lowering::{
+    count_parens_outside_strings, find_outside_strings, rfind_outside_strings,
+};
 
 /// Result of processing a literal start
 pub enum LiteralStartResult {
@@ -45,10 +52,16 @@ pub fn process_struct_literal_start(
     struct_registry: &StructRegistry,
     literal_mode: &mut LiteralModeStack,
 ) -> LiteralStartResult {
+    if !enabled(LoweringRule::Literal) {
+        return LiteralStartResult::NotLiteralStart;
+    }
     let (var_name, struct_name) = match detect_struct_literal_start(trimmed, struct_registry) {
         Some(pair) => pair,
         None => return LiteralStartResult::NotLiteralStart,
     };
+    if let Some(result) = process_const_literal_start(trimmed, leading_ws, opens, prev_depth, LiteralKind::Struct, literal_mode) {
+        return result;
+    }
     
     // CRITICAL FIX: Check if var_name is a field access (e.g., self.field)
     // Field assignments should NOT get `let` prefix!
@@ -70,6 +83,10 @@ pub fn process_struct_literal_start(
         "let "
     };
     
+    if let Some(output) = lower_complete_literal(trimmed, &var_name, let_keyword) {
+        return LiteralStartResult::Handled(format!("{}{}", leading_ws, output));
+    }
+    
     // Single-line struct literal
     if trimmed.ends_with('}') {
         let output = if is_field {
@@ -93,6 +110,36 @@ pub fn process_struct_literal_start(
     LiteralStartResult::Handled(format!("{}{}{} = {} {{", leading_ws, let_keyword, var_name, struct_name))
 }
 
+/// A literal assignment the statement parser reads as a whole line, lowered
+/// from the parsed statement: `a = P { x = 1 }.x` ends on `.x`, not `}`,
+/// and does not start a multi-line literal
+fn lower_complete_literal(trimmed: &str, var_name: &str, let_keyword: &str) -> Option<String> {
+    let LineShape::Complete(stmt) = parse_line(trimmed) else {
+        return None;
+    };
+    let value = lower_assigned_literal(&stmt.node, trimmed)?;
+    Some(format!("{}{} = {};", let_keyword, var_name, value))
+}
+
+/// `const ORIGIN Point = Point { x = 0, y = 0 }`: the literal is lowered
+/// as in a field assignment, after `const ORIGIN: Point` instead of `let`
+fn process_const_literal_start(
+    trimmed: &str,
+    leading_ws: &str,
+    opens: usize,
+    prev_depth: usize,
+    kind: LiteralKind,
+    literal_mode: &mut LiteralModeStack,
+) -> Option<LiteralStartResult> {
+    let decl = parse_const_decl(trimmed)?;
+    if decl.value.ends_with('}') {
+        let literal = transform_bare_struct_literal(decl.value);
+        return Some(LiteralStartResult::Handled(format!("{}{} = {};", leading_ws, decl.rust_head(), literal)));
+    }
+    literal_mode.enter(kind, prev_depth + opens, true);
+    Some(LiteralStartResult::Handled(format!("{}{} = {}", leading_ws, decl.rust_head(), decl.value)))
+}
+
 /// Process enum literal start (assignment pattern)
 pub fn process_enum_literal_start(
     trimmed: &str,
@@ -104,10 +151,16 @@ pub fn process_enum_literal_start(
     tracker: &VariableTracker,
     literal_mode: &mut LiteralModeStack,
 ) -> LiteralStartResult {
+    if !enabled(LoweringRule::Literal) {
+        return LiteralStartResult::NotLiteralStart;
+    }
     let (var_name, enum_path) = match detect_enum_literal_start(trimmed) {
         Some(pair) => pair,
         None => return LiteralStartResult::NotLiteralStart,
     };
+    if let Some(result) = process_const_literal_start(trimmed, leading_ws, opens, prev_depth, LiteralKind::EnumVariant, literal_mode) {
+        return result;
+    }
     
     // CRITICAL FIX: Check if var_name is a field access
     let is_field = is_field_access(&var_name);
@@ -127,6 +180,10 @@ pub fn process_enum_literal_start(
         "let "
     };
     
+    if let Some(output) = lower_complete_literal(trimmed, &var_name, let_keyword) {
+        return LiteralStartResult::Handled(format!("{}{}", leading_ws, output));
+    }
+    
     // Single-line enum literal
     if trimmed.ends_with('}') {
         let output = if is_field {
@@ -148,35 +205,71 @@ pub fn process_enum_literal_start(
     LiteralStartResult::Handled(format!("{}{}{} = {} {{", leading_ws, let_keyword, var_name, enum_path))
 }
 
-/// Process literal inside function call
+/// Process literal inside function call: `register(User {` or, as an
+/// assignment, `user = Some(User {`
 pub fn process_literal_in_call(
     trimmed: &str,
     leading_ws: &str,
+    line_num: usize,
     opens: usize,
     closes: usize,
     prev_depth: usize,
+    scope_analyzer: &ScopeAnalyzer,
+    tracker: &VariableTracker,
     struct_registry: &StructRegistry,
     literal_mode: &mut LiteralModeStack,
 ) -> LiteralStartResult {
+    if !enabled(LoweringRule::Literal) {
+        return LiteralStartResult::NotLiteralStart;
+    }
     if opens <= closes || !trimmed.contains('(') {
         return LiteralStartResult::NotLiteralStart;
     }
     
-    // Check for struct literal inside function call
-    if let Some(_struct_name) = detect_struct_literal_in_call(trimmed, struct_registry) {
-        literal_mode.enter(LiteralKind::Struct, prev_depth + opens, false);
-        let transformed = transform_call_with_struct_literal(trimmed);
-        return LiteralStartResult::Handled(format!("{}{}", leading_ws, transformed));
-    }
+    let kind = if detect_struct_literal_in_call(trimmed, struct_registry).is_some() {
+        LiteralKind::Struct
+    } else if detect_enum_literal_in_call(trimmed).is_some() {
+        LiteralKind::EnumVariant
+    } else {
+        return LiteralStartResult::NotLiteralStart;
+    };
     
-    // Check for enum literal inside function call
-    if let Some(_enum_path) = detect_enum_literal_in_call(trimmed) {
-        literal_mode.enter(LiteralKind::EnumVariant, prev_depth + opens, false);
+    // Parens still open at the literal's brace are closed by its last line: `})`
+    let before_brace = match rfind_outside_strings(trimmed, '{') {
+        Some(pos) => &trimmed[..pos],
+        None => trimmed,
+    };
+    let (paren_opens, paren_closes) = count_parens_outside_strings(before_brace);
+    let call_parens = paren_opens.saturating_sub(paren_closes);
+    
+    let assignment = parse_rusts_assignment_ext(trimmed)
+        .filter(|(var_name, _, value, _, _)| {
+            value.contains('(') && (is_valid_identifier(var_name) || is_field_access(var_name))
+        });
+    let Some((var_name, var_type, value, is_outer, is_explicit_mut)) = assignment else {
+        literal_mode.enter_in_call(kind, prev_depth + opens, call_parens);
         let transformed = transform_call_with_struct_literal(trimmed);
         return LiteralStartResult::Handled(format!("{}{}", leading_ws, transformed));
-    }
+    };
     
-    LiteralStartResult::NotLiteralStart
+    // Same `let` rules as `x = Struct {`
+    let needs_mut = is_explicit_mut
+        || scope_analyzer.needs_mut(&var_name, line_num)
+        || tracker.is_mut_borrowed(&var_name)
+        || tracker.is_mutated_via_method(&var_name);
+    let let_keyword = if is_field_access(&var_name) || is_outer {
+        ""
+    } else if needs_mut {
+        "let mut "
+    } else {
+        "let "
+    };
+    let annotation = var_type.map(|t| format!(": {}", t)).unwrap_or_default();
+    
+    literal_mode.enter_in_call(kind, prev_depth + opens, call_parens);
+    literal_mode.mark_assignment();
+    let transformed = transform_call_with_struct_literal(&value);
+    LiteralStartResult::Handled(format!("{}{}{}{} = {}", leading_ws, let_keyword, var_name, annotation, transformed))
 }
 
 /// Process bare struct literal (no assignment, just `StructName { ... }`)
@@ -189,6 +282,9 @@ pub fn process_bare_struct_literal(
     struct_registry: &StructRegistry,
     literal_mode: &mut LiteralModeStack,
 ) -> LiteralStartResult {
+    if !enabled(LoweringRule::Literal) {
+        return LiteralStartResult::NotLiteralStart;
+    }
     let struct_name = match detect_bare_struct_literal(trimmed, struct_registry) {
         Some(name) => name,
         None => return LiteralStartResult::NotLiteralStart,
@@ -224,6 +320,9 @@ pub fn process_bare_enum_literal(
     prev_depth: usize,
     literal_mode: &mut LiteralModeStack,
 ) -> LiteralStartResult {
+    if !enabled(LoweringRule::Literal) {
+        return LiteralStartResult::NotLiteralStart;
+    }
     let enum_path = match detect_bare_enum_literal(trimmed) {
         Some(path) => path,
         None => return LiteralStartResult::NotLiteralStart,
@@ -251,7 +350,7 @@ pub fn process_bare_enum_literal(
 fn transform_call_with_struct_literal(line: &str) -> String {
     let trimmed = line.trim();
     
-    let brace_pos = match trimmed.find('{') {
+    let brace_pos = match find_outside_strings(trimmed, '{') {
         Some(pos) => pos,
         None => return trimmed.to_string(),
     };
@@ -264,7 +363,7 @@ fn transform_call_with_struct_literal(line: &str) -> String {
     
     let before_brace = &trimmed[..brace_pos + 1];
     
-    if let Some(close_pos) = after_brace.rfind('}') {
+    if let Some(close_pos) = rfind_outside_strings(after_brace, '}') {
         let fields_part = &after_brace[..close_pos];
         let after_close = &after_brace[close_pos..];
         
@@ -273,5 +372,8 @@ fn transform_call_with_struct_literal(line: &str) -> String {
         return format!("{} {} {}", before_brace, transformed_fields, after_close);
     }
     
-    trimmed.to_string()
+    // First fields on the opening line: `f(Item { label = "}",`
+    let transformed_fields = crate::translate::literal_inline_translate::transform_fields_inline(after_brace);
+    let comma = if after_brace.trim_end().ends_with(',') && !transformed_fields.trim_end().ends_with(',') { "," } else { "" };
+    format!("{} {}{}", before_brace, transformed_fields, comma)
 }
\ No newline at end of file
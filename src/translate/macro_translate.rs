@@ -7,11 +7,14 @@
 //! - `unreachable()` → `unreachable!()`
 //! - `vec(1, 2, 3)` → `vec!(1, 2, 3)`
 
+use crate::lowering_rules::{enabled, LoweringRule};
+use crate::macro_shadowing::shadowed_macros_per_line;
+
 /// List of common macros that users might accidentally call as functions
 /// 
 /// CRITICAL: Only include macros that are NEVER used as methods or attributes
 /// DO NOT include: write, writeln (RwLock methods), cfg (attribute)
-const MACROS_TO_TRANSFORM: &[&str] = &[
+pub(crate) const MACROS_TO_TRANSFORM: &[&str] = &[
     "anyhow",
     "unreachable", 
     "unimplemented",
@@ -38,15 +41,25 @@ const MACROS_TO_TRANSFORM: &[&str] = &[
     // NOTE: Removed 'write', 'writeln', 'cfg' - these conflict with methods/attributes
 ];
 
-/// Transform function-style macro calls to correct Rust macro syntax
+/// Transform function-style macro calls to correct Rust macro syntax.
+/// Calls of a binding that shadows a macro name (`let format = ..`) are
+/// left alone within its scope, see [`crate::macro_shadowing`].
 pub fn transform_macros_to_correct_syntax(code: &str) -> String {
-    let mut result = code.to_string();
-    
-    for macro_name in MACROS_TO_TRANSFORM {
-        result = transform_single_macro(&result, macro_name);
+    if !enabled(LoweringRule::L08) {
+        return code.to_string();
     }
+    let lines: Vec<&str> = code.split('\n').collect();
+    let shadowed = shadowed_macros_per_line(&lines);
     
-    result
+    lines.iter().zip(shadowed).map(|(line, shadowed)| {
+        let mut result = line.to_string();
+        for macro_name in MACROS_TO_TRANSFORM {
+            if !shadowed.iter().any(|name| name == macro_name) {
+                result = transform_single_macro(&result, macro_name);
+            }
+        }
+        result
+    }).collect::<Vec<_>>().join("\n")
 }
 
 /// Transform a single macro from function-style to macro-style
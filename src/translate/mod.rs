@@ -13,6 +13,7 @@ pub mod literal_inline_translate;
 
 // Function and declaration translations
 pub mod function_def_translate;
+pub mod trait_impl_translate;
 pub mod const_static_translate;
 
 // Passthrough and native handling
@@ -28,6 +29,15 @@ pub mod expression_translate;
 // Macro translations
 pub mod macro_translate;
 
+// Condition normalization (if / while / match guards)
+pub mod condition_translate;
+
+// Conditional assignment sugar (`c ? a : b`, `a if c else b`)
+pub mod conditional_translate;
+
+// Function dispatch tables (`f = match k { .. { func } }`)
+pub mod dispatch_translate;
+
 // Re-exports for convenience
 pub use struct_def_translate::{process_struct_def_line, StructDefResult};
 pub use enum_def_translate::{process_enum_def_line, EnumDefResult};
@@ -41,9 +51,13 @@ pub use literal_start_translate::{
 };
 pub use literal_inline_translate::{transform_fields_inline, transform_single_inline_field};
 pub use function_def_translate::{process_function_def, process_rust_passthrough_function, FunctionDefResult};
+pub use trait_impl_translate::{TraitImplContext, adapt_trait_method_signature, parse_trait_impl_header};
 pub use const_static_translate::transform_const_or_static;
 pub use native_passthrough_translate::{is_rust_native_line, process_native_line};
 pub use array_literal_translate::{process_array_literal_start, ArrayLiteralResult};
 pub use assignment_translate::{process_assignment, parse_var_type_annotation, handle_bare_mut_in_match};
 pub use expression_translate::{process_non_assignment, process_tuple_destructuring};
-pub use macro_translate::transform_macros_to_correct_syntax;
\ No newline at end of file
+pub use macro_translate::transform_macros_to_correct_syntax;
+pub use condition_translate::{normalize_condition, normalize_line_conditions};
+pub use conditional_translate::{expand_conditional_value, lower_conditional};
+pub use dispatch_translate::{infer_dispatch_signature, fn_pointer_type, is_fn_value_expr};
\ No newline at end of file
@@ -44,14 +44,20 @@ pub fn process_struct_def_line(
 ) -> StructDefResult {
     // Check for struct definition start
     if is_struct_definition(trimmed) && !*in_struct_def {
-        *in_struct_def = true;
+        // `struct Config {}` and `struct Unit;` close on the header line,
+        // so the lines after them are not fields
+        let opens = trimmed.matches('{').count();
+        let closes = trimmed.matches('}').count();
+        let self_contained = (opens > 0 && opens == closes) || trimmed.ends_with(';');
+        *in_struct_def = !self_contained;
         *struct_def_depth = brace_depth;
         
         // CRITICAL FIX: Do NOT auto-inject Clone!
         // AtomicU64 and other types don't implement Clone.
         // Let user explicitly add #[derive(Clone)] when needed.
         
-        return StructDefResult::Started(format!("{}{}", leading_ws, trimmed));
+        // Bounds on type parameters: `struct Named<T: Into[String]> {`
+        return StructDefResult::Started(format!("{}{}", leading_ws, transform_generic_brackets(trimmed)));
     }
     
     // Process inside struct definition
@@ -114,4 +120,22 @@ mod tests {
         assert!(matches!(result, StructDefResult::Closed(_)));
         assert!(!in_struct_def);
     }
+
+    #[test]
+    fn test_single_line_empty_struct_def() {
+        let mut in_struct_def = false;
+        let mut struct_def_depth = 0;
+
+        let result = process_struct_def_line(
+            "struct Config {}",
+            "struct Config {}",
+            "",
+            0,
+            &mut in_struct_def,
+            &mut struct_def_depth,
+        );
+
+        assert!(matches!(result, StructDefResult::Started(_)));
+        assert!(!in_struct_def);
+    }
 }
\ No newline at end of file
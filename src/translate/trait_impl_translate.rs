@@ -0,0 +1,244 @@
+//! Trait Impl Translation
+//!
+//! Adapts RustS+ method signatures written inside `impl Trait for Type`
+//! blocks to the exact signatures std traits require.
+//!
+//! RustS+ trait impl:
+//! ```text
+//! impl Display for User {
+//!     fn fmt(self, f Formatter) Result {
+//!         write(f, "{}", self.name)
+//!     }
+//! }
+//! ```
+//!
+//! Rust output:
+//! ```text
+//! impl Display for User {
+//!     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+//!         write!(f, "{}", self.name)
+//!     }
+//! }
+//! ```
+//!
+//! Only well-known std traits are adapted. Methods of user-defined traits keep
+//! the signature exactly as written.
+
+use crate::function::FunctionSignature;
+
+/// Traits from `std::fmt` whose single method is `fmt(&self, &mut Formatter) -> fmt::Result`
+const FMT_TRAITS: &[&str] = &[
+    "Display", "Debug", "LowerHex", "UpperHex", "Octal", "Binary",
+    "LowerExp", "UpperExp", "Pointer",
+];
+
+/// Required receiver for methods of other well-known std traits
+const STD_RECEIVERS: &[(&str, &str, &str)] = &[
+    ("Clone", "clone", "&"),
+    ("PartialEq", "eq", "&"),
+    ("PartialEq", "ne", "&"),
+    ("PartialOrd", "partial_cmp", "&"),
+    ("Ord", "cmp", "&"),
+    ("Hash", "hash", "&"),
+    ("Iterator", "next", "&mut"),
+    ("Drop", "drop", "&mut"),
+    ("Deref", "deref", "&"),
+    ("DerefMut", "deref_mut", "&mut"),
+];
+
+/// Tracks whether the transpiler is inside an `impl Trait for Type` block
+#[derive(Debug, Clone, Default)]
+pub struct TraitImplContext {
+    /// (trait name without path/generics, brace depth before the header)
+    current: Option<(String, usize)>,
+}
+
+impl TraitImplContext {
+    pub fn new() -> Self {
+        TraitImplContext { current: None }
+    }
+
+    /// Enter a trait impl if `trimmed` is its header. `depth` is the brace
+    /// depth BEFORE the header line.
+    pub fn enter_if_header(&mut self, trimmed: &str, depth: usize) {
+        if self.current.is_some() {
+            return;
+        }
+        if let Some((trait_name, _)) = parse_trait_impl_header(trimmed) {
+            if trimmed.ends_with('{') {
+                self.current = Some((trait_name, depth));
+            }
+        }
+    }
+
+    /// Leave the impl once braces close back to its starting depth
+    pub fn update(&mut self, depth: usize) {
+        if let Some((_, start)) = self.current {
+            if depth <= start {
+                self.current = None;
+            }
+        }
+    }
+
+    /// Name of the trait being implemented, if any
+    pub fn current_trait(&self) -> Option<&str> {
+        self.current.as_ref().map(|(name, _)| name.as_str())
+    }
+}
+
+/// Split generics off the end of a path segment: `Display` / `From[T]` / `From<T>`
+fn strip_generics(s: &str) -> &str {
+    let end = s.find(['<', '[']).unwrap_or(s.len());
+    s[..end].trim()
+}
+
+/// Parse `impl[T] Trait for Type {` (or `impl<T> ...`) into (trait, type).
+/// The trait name is reduced to its last path segment without generics.
+pub fn parse_trait_impl_header(trimmed: &str) -> Option<(String, String)> {
+    let rest = trimmed.strip_prefix("impl")?;
+
+    // Skip impl-level generics: `impl[T: Clone]` / `impl<T>`
+    let rest = match rest.chars().next()? {
+        c @ ('[' | '<') => {
+            let close = if c == '[' { ']' } else { '>' };
+            let mut depth = 0i32;
+            let mut end = None;
+            for (i, ch) in rest.char_indices() {
+                if ch == c {
+                    depth += 1;
+                } else if ch == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(i + 1);
+                        break;
+                    }
+                }
+            }
+            &rest[end?..]
+        }
+        c if c.is_whitespace() => rest,
+        _ => return None,
+    };
+
+    let rest = rest.trim().trim_end_matches('{').trim();
+    let for_pos = rest.find(" for ")?;
+    let trait_path = rest[..for_pos].trim();
+    let target = rest[for_pos + 5..].trim();
+    if trait_path.is_empty() || target.is_empty() {
+        return None;
+    }
+
+    let trait_name = strip_generics(trait_path.rsplit("::").next().unwrap_or(trait_path));
+    Some((trait_name.to_string(), target.to_string()))
+}
+
+fn is_formatter_type(ty: &str) -> bool {
+    let base = strip_generics(ty.trim());
+    matches!(base, "Formatter" | "fmt::Formatter" | "std::fmt::Formatter" | "core::fmt::Formatter")
+}
+
+fn is_bare_result(ty: &str) -> bool {
+    matches!(ty.trim(), "Result" | "fmt::Result")
+}
+
+/// Rewrite the `self` receiver to the required borrow, but only when the
+/// user wrote a bare `self` (explicit `&self` / `&mut self` is respected).
+fn set_receiver(sig: &mut FunctionSignature, receiver: &str) {
+    if let Some(param) = sig.parameters.iter_mut().find(|p| p.name == "self") {
+        if param.param_type.trim().is_empty() && !param.is_mut_param {
+            param.param_type = receiver.to_string();
+            param.is_borrow = true;
+            param.is_mut_borrow = receiver == "&mut";
+        }
+    }
+}
+
+/// Adapt a parsed method signature to the std trait being implemented
+pub fn adapt_trait_method_signature(sig: &mut FunctionSignature, trait_name: &str) {
+    if FMT_TRAITS.contains(&trait_name) && sig.name == "fmt" {
+        set_receiver(sig, "&");
+        for param in sig.parameters.iter_mut().filter(|p| p.name != "self") {
+            if is_formatter_type(&param.param_type) && !param.param_type.trim().starts_with('&') {
+                param.param_type = "&mut std::fmt::Formatter<'_>".to_string();
+                param.is_borrow = true;
+                param.is_mut_borrow = true;
+            }
+        }
+        if sig.return_type.as_deref().is_some_and(is_bare_result) {
+            sig.return_type = Some("std::fmt::Result".to_string());
+        }
+        return;
+    }
+
+    if let Some((_, _, receiver)) = STD_RECEIVERS
+        .iter()
+        .find(|(t, m, _)| *t == trait_name && *m == sig.name)
+    {
+        set_receiver(sig, receiver);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::{parse_function_line, signature_to_rust, FunctionParseResult};
+
+    fn adapted(line: &str, trait_name: &str) -> String {
+        match parse_function_line(line) {
+            FunctionParseResult::RustSPlusSignature(mut sig) => {
+                adapt_trait_method_signature(&mut sig, trait_name);
+                signature_to_rust(&sig)
+            }
+            other => panic!("expected RustS+ signature, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_trait_impl_header() {
+        assert_eq!(
+            parse_trait_impl_header("impl Display for User {"),
+            Some(("Display".to_string(), "User".to_string()))
+        );
+        assert_eq!(
+            parse_trait_impl_header("impl std::fmt::Debug for Point {"),
+            Some(("Debug".to_string(), "Point".to_string()))
+        );
+        assert_eq!(
+            parse_trait_impl_header("impl[T] From[T] for Wrapper[T] {"),
+            Some(("From".to_string(), "Wrapper[T]".to_string()))
+        );
+        assert_eq!(parse_trait_impl_header("impl User {"), None);
+        assert_eq!(parse_trait_impl_header("implement x for y"), None);
+    }
+
+    #[test]
+    fn test_display_fmt_signature() {
+        let out = adapted("fn fmt(self, f Formatter) Result {", "Display");
+        assert!(out.starts_with("fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result"), "{}", out);
+    }
+
+    #[test]
+    fn test_explicit_signature_kept() {
+        let out = adapted("fn fmt(&self, f &mut fmt::Formatter) fmt::Result {", "Debug");
+        assert!(out.starts_with("fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result"), "{}", out);
+    }
+
+    #[test]
+    fn test_user_trait_untouched() {
+        let out = adapted("fn fmt(self, f Formatter) Result {", "Render");
+        assert!(out.starts_with("fn fmt(self, f: Formatter) -> Result"), "{}", out);
+        let out = adapted("fn next(self) Option[u32] {", "Iterator");
+        assert!(out.starts_with("fn next(&mut self) -> Option<u32>"), "{}", out);
+    }
+
+    #[test]
+    fn test_context_tracks_depth() {
+        let mut ctx = TraitImplContext::new();
+        ctx.enter_if_header("impl Display for User {", 0);
+        assert_eq!(ctx.current_trait(), Some("Display"));
+        ctx.update(2);
+        assert_eq!(ctx.current_trait(), Some("Display"));
+        ctx.update(0);
+        assert_eq!(ctx.current_trait(), None);
+    }
+}
@@ -3,23 +3,57 @@
 //! Orchestrates the line-by-line transpilation of RustS+ to Rust.
 //! This module coordinates all the lowering and translation modules.
 
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
 use crate::variable::{VariableTracker, parse_rusts_assignment_ext};
 use crate::scope::ScopeAnalyzer;
 use crate::function::{
-    parse_function_line, CurrentFunctionContext, FunctionParseResult,
+    parse_function_line, CurrentFunctionContext, FunctionParseResult, FunctionRegistry,
+    signature_error_location, prefix_fn_attributes, strip_fn_modifiers, is_tail_value_expr, FunctionSignature,
 };
-use crate::enum_def::EnumParseContext;
+use crate::enum_def::{EnumParseContext, parse_enum_header};
+use crate::match_arms::is_bool_match;
+use crate::contracts::{entry_lines, exit_lines, wrap_single_line, ContractMode};
+use crate::memo::{lower_memo_fn, MEMO_ATTRIBUTE};
+use crate::struct_def::parse_struct_header;
+use crate::clone_helpers::{inject_clone_derive, scan_copy_element_arrays};
 use crate::modes::{LiteralModeStack, ArrayModeStack, UseImportMode};
 use crate::control_flow::MatchModeStack;
 use crate::hex_normalizer::normalize_hex_literals;
-use crate::helpers::{strip_inline_comment, transform_generic_brackets};
-use crate::first_pass::run_first_pass;
-use crate::postprocess_output::apply_postprocessing;
+use crate::method_sugar::lower_method_sugar;
+use crate::map_literals::{lower_map_literals, lower_map_literals_importing};
+use crate::closures::{lower_fn_lambdas, opens_closure_block};
+use crate::for_loops::plan_for_loop;
+use crate::concurrency::lower_concurrency;
+use crate::fallible::{emit_error_enums, lower_fallible, ErrorEnum};
+use crate::generic_types::{generic_type_names, lower_generic_types};
+use crate::constants::ConstRegistry;
+use crate::statement_split::split_statements;
+use crate::edition::{add_dyn, explicit_format_args, trait_object_names, unavailable_async, Edition};
+use crate::helpers::{comment_out_line, is_field_access, strip_inline_comment, transform_generic_brackets};
+use crate::first_pass::FirstPassResult;
+use crate::streaming::for_each_chunk;
+use crate::input_limits::{check_input_limits, InputLimits};
+use crate::program_index::ProgramIndex;
+use crate::lowering_hook::{LineCtx, LoweringHooks};
+use crate::lowering_rules::{enabled, LoweringRule, RuleSet};
+use crate::inline_fns::inline_trivial_functions;
+use crate::string_append::append_in_place;
+use crate::effect_badges::{attach_badge, effect_badges};
+use crate::postprocess_output::{finish_postprocessing, postprocess_lines, align_to_source_lines, HOIST_MARKER};
+use crate::source_map::{align_origins, LineTable};
 use crate::rust_sanity;
+use crate::dialect::mask_rust_functions;
+use crate::error_msg::{RsplError, SourceLocation, structure_errors};
+use crate::verbatim::{
+    mask_verbatim_blocks, restore_verbatim_blocks, restore_verbatim_blocks_aligned, VerbatimBlock, VERBATIM_MARKER,
+};
 
 // Import lowering modules
 use crate::lowering::depth_tracking_lowering::{
-    count_braces_outside_strings, count_brackets_outside_strings, update_multiline_depth,
+    count_braces_outside_strings, count_brackets_outside_strings, update_multiline_depth, find_outside_strings,
 };
 use crate::lowering::lookahead_lowering::{
     check_before_closing_brace, check_next_line_is_where,
@@ -30,6 +64,7 @@ use crate::lowering::multiline_fn_lowering::{is_multiline_fn_start, process_mult
 use crate::lowering::multiline_assign_lowering::{
     is_multiline_assign_start, is_multiline_assign_complete, process_complete_multiline_assign,
 };
+use crate::lowering::continuation_lowering::{continues_expression, is_condition_continuation_start};
 use crate::lowering::use_import_lowering::{process_use_import_line, UseImportResult};
 use crate::lowering::array_mode_lowering::{process_array_mode_line, ArrayModeResult};
 use crate::lowering::literal_mode_lowering::{process_literal_mode_line, LiteralModeResult};
@@ -44,12 +79,15 @@ use crate::translate::literal_start_translate::{
     LiteralStartResult,
 };
 use crate::translate::function_def_translate::{process_function_def, FunctionDefResult};
+use crate::translate::trait_impl_translate::TraitImplContext;
 use crate::translate::const_static_translate::transform_const_or_static;
 use crate::translate::native_passthrough_translate::{is_rust_native_line, process_native_line};
 use crate::translate::array_literal_translate::{process_array_literal_start, ArrayLiteralResult};
 use crate::translate::expression_translate::{process_non_assignment, process_tuple_destructuring};
 use crate::translate::assignment_translate::process_assignment;
 use crate::translate::macro_translate::transform_macros_to_correct_syntax;
+use crate::translate::condition_translate::normalize_line_conditions;
+use crate::translate::dispatch_translate::{infer_dispatch_signature, fn_pointer_type, is_fn_value_expr};
 
 // Import for match/if handling
 use crate::control_flow::{
@@ -58,10 +96,232 @@ use crate::control_flow::{
 };
 use crate::translate::assignment_translate::parse_var_type_annotation;
 
+/// Options that change how lowering lays out its output
+#[derive(Debug, Clone, Default)]
+pub struct LoweringOptions {
+    /// Keep a 1:1 line correspondence between .rss and .rs: everything a
+    /// source line lowers to is emitted on that same output line, so rustc
+    /// line numbers match the source without a source map
+    pub preserve_lines: bool,
+    /// Derive `Clone` on every struct and enum (the old L-12 behavior).
+    /// By default only types that receive a generated `.clone()` get it.
+    pub derive_clone_all: bool,
+    /// Project-specific lowering rules (see [`crate::lowering_hook`])
+    pub hooks: LoweringHooks,
+    /// SPDX license expression written as a header comment on the output,
+    /// e.g. `MIT OR Apache-2.0`
+    pub license: Option<String>,
+    /// Attribution text written below the license header, one comment line
+    /// per text line
+    pub attribution: Option<String>,
+    /// `0` lowers calls as written; `1` and above inline calls to trivial
+    /// pure functions (see [`crate::inline_fns`]); `2` and above also build
+    /// strings in place (see [`crate::string_append`])
+    pub opt_level: u8,
+    /// Document each function's effect contract (`/// rustsp: pure`,
+    /// `/// rustsp: effects(io)`) in the output (see [`crate::effect_badges`])
+    pub effect_badges: bool,
+    /// Rust edition of the output (see [`crate::edition`]); lowered as
+    /// 2021 and left out of the header when not given
+    pub edition: Option<Edition>,
+    /// Nesting, line length and file size the source may not exceed (see
+    /// [`crate::input_limits`])
+    pub limits: InputLimits,
+    /// How `requires` / `ensures` clauses are lowered (see
+    /// [`crate::contracts`])
+    pub contracts: ContractMode,
+    /// Lowering rules to run; a disabled one leaves its construct as
+    /// written (see [`crate::lowering_rules`])
+    pub rules: RuleSet,
+    /// Give each `!T` function a `thiserror` error enum instead of a boxed
+    /// error (see [`crate::fallible`]); the output then needs the
+    /// `thiserror` crate
+    pub error_enums: bool,
+}
+
 /// Main entry point for RustS+ to Rust transpilation
 pub fn parse_rusts(source: &str) -> String {
+    parse_rusts_with_options(source, &LoweringOptions::default())
+}
+
+/// Transpile RustS+ to Rust with explicit lowering options
+///
+/// Lowering errors (see [`lower_rusts`]) are emitted as `compile_error!`.
+pub fn parse_rusts_with_options(source: &str, options: &LoweringOptions) -> String {
+    match lower_rusts(source, options) {
+        Ok(rust) => rust,
+        Err(errors) => lowering_errors_to_rust(&errors),
+    }
+}
+
+/// Transpile RustS+ to Rust, or return the diagnostics for every construct
+/// that could not be lowered. No partial output is produced on error.
+pub fn lower_rusts(source: &str, options: &LoweringOptions) -> Result<String, Vec<RsplError>> {
+    lower_rusts_mapped(source, options).map(|(rust, _)| rust)
+}
+
+/// [`lower_rusts`], together with the `.rss` line each generated line came
+/// from, so rustc diagnostics can point at the source
+pub fn lower_rusts_mapped(source: &str, options: &LoweringOptions) -> Result<(String, LineTable), Vec<RsplError>> {
+    let mut table = LineTable::default();
+    let rust = catch_lowering_panic(source, options, || lower_source(source, options, None, Some(&mut table)))?;
+    Ok(match output_header(options) {
+        Some(header) if options.preserve_lines => (prefix_first_line(&header, &rust), table),
+        Some(header) => {
+            table.offset(header.lines().count());
+            (format!("{}{}", header, rust), table)
+        }
+        None => (rust, table),
+    })
+}
+
+/// License and attribution comment lines for the top of the output
+pub(crate) fn output_header(options: &LoweringOptions) -> Option<String> {
+    if options.license.is_none() && options.attribution.is_none() && options.edition.is_none() {
+        return None;
+    }
+    let mut header = String::new();
+    if let Some(license) = &options.license {
+        header.push_str(&format!("// SPDX-License-Identifier: {}\n", license.trim()));
+    }
+    if let Some(edition) = options.edition {
+        header.push_str(&format!("// Rust edition: {}\n", edition.as_str()));
+    }
+    if let Some(attribution) = &options.attribution {
+        for line in attribution.lines() {
+            match line.trim_end() {
+                "" => header.push_str("//\n"),
+                line => header.push_str(&format!("// {}\n", line)),
+            }
+        }
+    }
+    Some(header)
+}
+
+/// Put the header on output line 1 as a block comment, so the 1:1 line
+/// correspondence of `preserve_lines` is kept
+fn prefix_first_line(header: &str, rust: &str) -> String {
+    let text: Vec<&str> = header.lines()
+        .map(|line| line.trim_start_matches('/').trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+    format!("/* {} */ {}", text.join("; ").replace("*/", "* /"), rust)
+}
+
+/// Run `lower`, turning a panic into RSPL030 for each item that panics
+/// when lowered on its own (or for the whole file if none does alone)
+fn catch_lowering_panic<T>(
+    source: &str,
+    options: &LoweringOptions,
+    lower: impl FnOnce() -> Result<T, Vec<RsplError>>,
+) -> Result<T, Vec<RsplError>> {
+    let panic = match panic::catch_unwind(AssertUnwindSafe(lower)) {
+        Ok(result) => return result,
+        Err(payload) => panic_message(payload),
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let mut errors = Vec::new();
+    let _ = for_each_chunk(&mut source.as_bytes(), 1, |item, first_line| {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| lower_source(item, options, None, None))) {
+            let why = format!("the lowering panicked: {}", panic_message(payload));
+            errors.push(item_not_lowered_error(&lines, first_line, &why));
+        }
+        Ok(())
+    });
+    if errors.is_empty() {
+        errors.push(item_not_lowered_error(&lines, 0, &format!("the lowering panicked: {}", panic)));
+    }
+    Err(errors)
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map(|s| s.to_string()).unwrap_or_else(|| "unknown cause".to_string()),
+    }
+}
+
+/// Lower one piece of a larger program against the first pass over the
+/// whole program (see [`crate::streaming`])
+pub(crate) fn lower_rusts_in_program(
+    source: &str,
+    options: &LoweringOptions,
+    program: &mut FirstPassResult,
+) -> Result<String, Vec<RsplError>> {
+    lower_source(source, options, Some(program), None)
+}
+
+/// The source after the passes that run before the first pass
+pub(crate) struct Preprocessed {
+    /// The rewritten source, `rust { .. }` blocks and Rust functions masked
+    pub source: String,
+    pub verbatim_blocks: Vec<VerbatimBlock>,
+    /// Signatures of the functions written in Rust
+    pub rust_fn_signatures: Vec<FunctionSignature>,
+    /// Attributes of the stripped fn modifiers, by line
+    pub fn_attributes: HashMap<usize, Vec<String>>,
+    /// Error enums of the `!T` functions (see [`crate::fallible`])
+    pub error_enums: Vec<ErrorEnum>,
+    /// Import the lowered map literals need, hoisted by the caller
+    pub map_import: Option<&'static str>,
+}
+
+/// Run the source-to-source passes every consumer of the first pass needs:
+/// lowering, the streaming registry scan and the registry check
+pub(crate) fn preprocess(source: &str, options: &LoweringOptions) -> Preprocessed {
+    // Mask `rust { ... }` blocks - their bodies bypass lowering entirely
+    let (masked_source, mut verbatim_blocks) = mask_verbatim_blocks(source);
+    // Functions already written in Rust pass through the same way
+    let (masked_source, rust_fn_signatures) = mask_rust_functions(&masked_source, &mut verbatim_blocks);
+    
     // CRITICAL: Normalize custom hex literals FIRST
-    let normalized_source = normalize_hex_literals(source);
+    let normalized_source = normalize_hex_literals(&masked_source);
+    // `xs push 5` → `xs.push(5)` before anything scans for mutation
+    let normalized_source = lower_method_sugar(&normalized_source);
+    // `{ "a": 1 }` → `HashMap::from([("a", 1)])`; the `use` it may need is
+    // emitted as a hoisted helper, so only for output that gets reordered
+    let (normalized_source, map_import) = if options.preserve_lines {
+        (lower_map_literals(&normalized_source), None)
+    } else {
+        lower_map_literals_importing(&normalized_source)
+    };
+    // `fn(x i32) i32 { .. }` → `|x i32| -> i32 { .. }`
+    let normalized_source = lower_fn_lambdas(&normalized_source);
+    // `h = spawn { .. }`, `(tx, rx) = channel()`, `join h` → std::thread / mpsc
+    let normalized_source = lower_concurrency(&normalized_source);
+    // `inline fn f()` → `fn f()`; the attributes go back on the emitted signature
+    let (normalized_source, fn_attributes) = strip_fn_modifiers(&normalized_source);
+    // `fn f() !T` → `fn f() Result[T, E]`, E boxed or the function's error enum
+    let (source, error_enums) = lower_fallible(&normalized_source, options.error_enums);
+    Preprocessed { source, verbatim_blocks, rust_fn_signatures, fn_attributes, error_enums, map_import }
+}
+
+fn lower_source(
+    source: &str,
+    options: &LoweringOptions,
+    mut program: Option<&mut FirstPassResult>,
+    line_table: Option<&mut LineTable>,
+) -> Result<String, Vec<RsplError>> {
+    // Refuse pathological input before any mode stack can grow with it
+    check_input_limits(source, &options.limits)?;
+    let _rules = options.rules.install();
+    
+    let Preprocessed {
+        source: normalized_source, verbatim_blocks, rust_fn_signatures, fn_attributes, error_enums, map_import,
+    } = preprocess(source, options);
+    // A streamed piece leaves the map import to the whole output
+    let map_import = map_import.filter(|_| program.is_none());
+    let badges = if options.effect_badges { effect_badges(&strip_fn_modifiers(source).0) } else { Default::default() };
+    // `Wrapper[T]` → `Wrapper<T>` for the generic types of the whole program
+    let mut generic_type_names = generic_type_names(&normalized_source);
+    if let Some(program) = program.as_deref() {
+        generic_type_names.extend(program.generic_types.iter().cloned());
+    }
+    let normalized_source = lower_generic_types(&normalized_source, &generic_type_names);
+    // `a = 1; b = 2` → one statement per line; `statement_origins` maps
+    // each line back to its source line
+    let source_line_count = normalized_source.lines().count();
+    let (normalized_source, statement_origins) = split_statements(&normalized_source);
     
     let lines: Vec<&str> = normalized_source.lines().collect();
     
@@ -69,17 +329,15 @@ pub fn parse_rusts(source: &str) -> String {
     // SOURCE BRACE BALANCE PRE-CHECK
     // =========================================================================
     // Validate that source braces are balanced BEFORE lowering.
-    // If unbalanced, report a clear SOURCE error and RETURN EARLY with
-    // a valid compile_error! output. This prevents:
+    // If unbalanced, report a SOURCE error (RSPL031) and RETURN EARLY.
+    // This prevents:
     //   1. Garbage lowering output from unbalanced source
     //   2. Misleading "COMPILER BUG" from rust_sanity
-    //   3. The CLI/build system wrapping the error in another error box
     //
     // Uses a STACK to track opening brace positions, so we can pinpoint
     // exactly WHICH brace is unclosed (not just "somewhere near EOF").
     {
-        let mut brace_stack: Vec<(usize, String)> = Vec::new(); // (line_num, context)
-        let mut depth: i64 = 0;
+        let mut brace_stack: Vec<(usize, String)> = Vec::new(); // (line index, context)
         let mut negative_at: Option<usize> = None;
         
         for (i, line) in lines.iter().enumerate() {
@@ -89,135 +347,91 @@ pub fn parse_rusts(source: &str) -> String {
             let (opens, closes) = count_braces_outside_strings(trimmed);
             
             for _ in 0..opens {
-                depth += 1;
                 // Store context: use the line content for block starters,
                 // or a breadcrumb for nested braces
                 let ctx = trimmed.chars().take(80).collect::<String>();
-                brace_stack.push((i + 1, ctx));
+                brace_stack.push((i, ctx));
             }
             
             for _ in 0..closes {
-                depth -= 1;
-                if depth < 0 {
-                    negative_at = Some(i + 1);
+                if brace_stack.pop().is_none() {
+                    negative_at = Some(i);
                     break;
                 }
-                brace_stack.pop();
             }
             
             if negative_at.is_some() { break; }
         }
         
-        if let Some(line) = negative_at {
-            // Too many closing braces
-            eprintln!();
-            eprintln!("╔══════════════════════════════════════════════════════════════╗");
-            eprintln!("║  error[RSPL_SOURCE]: unbalanced braces in source code       ║");
-            eprintln!("╚══════════════════════════════════════════════════════════════╝");
-            eprintln!("  --> line {}", line);
-            eprintln!("      Extra closing '}}' — no matching opening brace found.");
-            eprintln!("  note: This is a SOURCE error, not a compiler bug.");
-            eprintln!("        Check your brace matching around line {}.", line);
-            eprintln!();
-            
-            // EARLY RETURN: valid Rust that won't trigger rust_sanity
-            return format!(
-                "// RustS+ source error: extra closing brace at line {}\ncompile_error!(\"RustS+ source error: unbalanced braces. Extra '}}' at line {}\");\n",
-                line, line
-            );
-        }
+        let is_block_start = |ctx: &str| {
+            let t = ctx.trim();
+            t.starts_with("impl ")
+                || t.starts_with("pub fn ")
+                || t.starts_with("fn ")
+                || t.starts_with("mod ")
+                || t.starts_with("pub struct ")
+                || t.starts_with("struct ")
+                || t.starts_with("pub enum ")
+                || t.starts_with("enum ")
+        };
         
-        if depth > 0 {
+        let error = if let Some(i) = negative_at {
+            // Too many closing braces
+            Some(structure_errors::extra_closing_brace().at(source_location(&lines, i, "}")))
+        } else {
+            brace_stack.first().map(|first| {
             // Unclosed braces — the stack still has unmatched entries.
-            // The BOTTOM entries are the real culprits (earliest unclosed).
-            // Report the first unclosed brace that's at a block-start level.
-            let unclosed_count = brace_stack.len();
-            
-            // Find the most useful unclosed brace to report.
-            // Priority: the FIRST unclosed impl/fn/mod/struct (the root cause),
-            // not the last fn deep inside it.
-            let mut report_line: usize = 0;
-            let mut report_ctx = String::new();
-            
-            for (line_num, ctx) in &brace_stack {
-                let t = ctx.trim();
-                let is_block_start = t.starts_with("impl ")
-                    || t.starts_with("pub fn ")
-                    || t.starts_with("fn ")
-                    || t.starts_with("mod ")
-                    || t.starts_with("pub struct ")
-                    || t.starts_with("struct ")
-                    || t.starts_with("pub enum ")
-                    || t.starts_with("enum ");
+            // Report the FIRST unclosed impl/fn/mod/struct (the root cause:
+            // every later block is nested inside it due to the missing
+            // close), or the first unclosed brace if there is none
+                let (report_idx, report_ctx) = brace_stack.iter()
+                    .find(|(_, ctx)| is_block_start(ctx))
+                    .unwrap_or(first);
                 
-                if is_block_start {
-                    // Take the FIRST block-start that's still unclosed
-                    // This is the root cause — all subsequent blocks are 
-                    // nested inside it due to the missing close
-                    report_line = *line_num;
-                    report_ctx = ctx.clone();
-                    break;
-                }
-            }
-            
-            // Fallback: if no block-start found, use the first unclosed brace
-            if report_line == 0 {
-                if let Some((line_num, ctx)) = brace_stack.first() {
-                    report_line = *line_num;
-                    report_ctx = ctx.clone();
-                }
-            }
-            
-            eprintln!();
-            eprintln!("╔══════════════════════════════════════════════════════════════╗");
-            eprintln!("║  error[RSPL_SOURCE]: unbalanced braces in source code       ║");
-            eprintln!("╚══════════════════════════════════════════════════════════════╝");
-            eprintln!("  note: {} unclosed '{{' brace(s) at end of file.", depth);
-            eprintln!("  --> likely unclosed at line {}", report_line);
-            if !report_ctx.is_empty() {
-                eprintln!("      {}", report_ctx.trim());
-            }
-            eprintln!();
-            eprintln!("  note: This is a SOURCE error, not a compiler bug.");
-            eprintln!("        A function or impl block is missing its closing '}}'.");
-            
-            // Extra help: show ALL unclosed block-starts
-            let unclosed_blocks: Vec<_> = brace_stack.iter()
-                .filter(|(_, ctx)| {
-                    let t = ctx.trim();
-                    t.starts_with("impl ") || t.starts_with("pub fn ") || t.starts_with("fn ")
-                    || t.starts_with("mod ") || t.starts_with("struct ") || t.starts_with("enum ")
-                    || t.starts_with("pub struct ") || t.starts_with("pub enum ")
-                })
-                .collect();
-            
-            if unclosed_blocks.len() > 1 {
-                eprintln!("  help: {} block(s) still open at end of file:", unclosed_blocks.len());
-                for (ln, ctx) in &unclosed_blocks {
-                    eprintln!("         line {}: {}", ln, ctx.trim().chars().take(60).collect::<String>());
-                }
-            }
-            eprintln!();
-            
-            // EARLY RETURN: valid Rust that won't trigger rust_sanity
-            return format!(
-                "// RustS+ source error: {} unclosed brace(s), first at line {}\ncompile_error!(\"RustS+ source error: unbalanced braces. Missing '}}' — check line {}\");\n",
-                depth, report_line, report_line
-            );
+                let unclosed_blocks: Vec<(usize, String)> = brace_stack.iter()
+                    .filter(|(_, ctx)| is_block_start(ctx))
+                    .map(|(i, ctx)| (statement_origins[*i] + 1, ctx.trim().chars().take(60).collect()))
+                    .collect();
+                
+                structure_errors::unclosed_braces(brace_stack.len(), &unclosed_blocks)
+                    .at(source_location(&lines, *report_idx, report_ctx.trim()))
+            })
+        };
+        
+        // EARLY RETURN: no Rust is generated from unbalanced source
+        if let Some(mut error) = error {
+            error.location.line = statement_origins[error.location.line - 1] + 1;
+            return Err(vec![error]);
         }
     }
     
     let mut tracker = VariableTracker::new();
     
-    // Run scope analysis
-    let mut scope_analyzer = ScopeAnalyzer::new();
-    scope_analyzer.analyze(&normalized_source);
+    // One scan for scope analysis, the first pass (type registration, clone
+    // requirements) and the per-function mutation scan
+    let mut index = ProgramIndex::build(&lines, &mut tracker);
+    let scope_analyzer = std::mem::take(&mut index.scope);
     
-    // Run first pass to register types and track clone requirements
-    let first_pass_result = run_first_pass(&lines, &mut tracker);
-    let fn_registry = first_pass_result.fn_registry;
-    let struct_registry = first_pass_result.struct_registry;
-    let _enum_registry = first_pass_result.enum_registry;
+    // Constructs that cannot be lowered - reported together once the pass ends
+    // (normally Stage 1 reports these first)
+    let functions = crate::diagnostic_groups::enclosing_functions(&lines.join("\n"));
+    let mut lowering_errors: Vec<RsplError> = scope_analyzer.outer_errors.iter()
+        .map(|(line_idx, var_name)| {
+            scope_analyzer.outer_error(*line_idx, var_name, &functions)
+                .at(source_location(&lines, *line_idx, &format!("outer {}", var_name)))
+        })
+        .collect();
+    
+    let mut first_pass_result = std::mem::take(&mut index.first_pass);
+    // A piece of a larger program uses the whole-program registries instead
+    // (they already include this piece); handed back once the pass ends
+    if let Some(program) = program.as_deref_mut() {
+        std::mem::swap(&mut first_pass_result, program);
+    }
+    let FirstPassResult { mut fn_registry, struct_registry, enum_registry, types_need_clone, clone_reasons, copy_types, generic_types, const_registry } = first_pass_result;
+    for sig in rust_fn_signatures {
+        fn_registry.register(sig);
+    }
     
     // CRITICAL FIX (Bug #2): Do NOT scan all lines globally for mutating methods!
     // Global scanning causes cross-function contamination:
@@ -232,12 +446,15 @@ pub fn parse_rusts(source: &str) -> String {
     let mut bracket_depth: usize = 0;
     let mut in_function_body = false;
     let mut function_start_brace = 0;
+    // `requires` assertions wait for the `{` of the body
+    let mut contract_entry_pending = false;
     let mut current_fn_ctx = CurrentFunctionContext::new();
     
     // Struct/enum definition contexts
     let mut in_struct_def = false;
     let mut struct_def_depth = 0;
     let mut enum_ctx = EnumParseContext::new();
+    let mut trait_impl_ctx = TraitImplContext::new();
     
     // Mode stacks
     let mut literal_mode = LiteralModeStack::new();
@@ -247,23 +464,105 @@ pub fn parse_rusts(source: &str) -> String {
     
     // If expression assignment tracking
     let mut if_expr_assignment_depth: Option<usize> = None;
+    // Depth before each `f = |x| {` whose closure body is still open
+    let mut closure_assignment_depths: Vec<usize> = Vec::new();
     
     // Multi-line accumulation
     let mut multiline_fn_acc: Option<String> = None;
     let mut multiline_fn_leading_ws: String = String::new();
+    let mut multiline_fn_pieces: Vec<(usize, usize)> = Vec::new();
     let mut multiline_assign_acc: Option<String> = None;
     let mut multiline_assign_leading_ws: String = String::new();
+    // `if` / `while` condition continued after a trailing operator: (joined, leading ws)
+    let mut condition_acc: Option<(String, String)> = None;
+    
+    // Body of a function whose signature failed to parse: commented out
+    // until brace depth returns to this value
+    let mut malformed_fn_end: Option<usize> = None;
     
     // Expression continuation tracking
     let mut prev_line_was_continuation = false;
     let mut multiline_expr_depth: i32 = 0;
     
+    // Source line (0-based) that produced each entry of `output_lines`
+    let mut line_origins: Vec<usize> = Vec::new();
+    
+    // Top-level item being lowered; its output is kept only if it ends
+    // with every mode closed
+    let mut unit = LoweringUnit { output_start: 0, first_line: 0 };
+    
     for (line_num, line) in lines.iter().enumerate() {
+        // Everything pushed since the previous iteration came from the previous line
+        line_origins.resize(output_lines.len(), line_num.saturating_sub(1));
+        // Back at the top level: the previous item either lowered with every
+        // mode closed and its output stands, or it is rolled back
+        if brace_depth == 0 && bracket_depth == 0 && multiline_expr_depth == 0 && multiline_fn_acc.is_none()
+            && multiline_assign_acc.is_none() && condition_acc.is_none() && malformed_fn_end.is_none()
+        {
+            if let Some(mode) = open_mode(&literal_mode, &array_mode, &match_mode, &use_import_mode) {
+                output_lines.truncate(unit.output_start);
+                line_origins.truncate(unit.output_start);
+                lowering_errors.push(item_not_lowered_error(&lines, unit.first_line, &format!("{} opened in this item was never closed", mode)));
+                literal_mode = LiteralModeStack::new();
+                array_mode = ArrayModeStack::new();
+                match_mode = MatchModeStack::new();
+                use_import_mode = UseImportMode::new();
+            }
+            unit = LoweringUnit { output_start: output_lines.len(), first_line: line_num };
+        }
         let line = line.trim_start_matches('\u{FEFF}');
         
+        if contract_entry_pending && brace_depth >= function_start_brace {
+            contract_entry_pending = false;
+            let name = current_fn_ctx.name.clone().unwrap_or_default();
+            output_lines.extend(entry_lines(&name, &current_fn_ctx.contract, options.contracts, ""));
+        }
+        
+        // Verbatim block marker: keep as-is, restored after post-processing
+        if line.trim_start().starts_with(VERBATIM_MARKER) {
+            output_lines.push(line.to_string());
+            continue;
+        }
+        
         let clean_line = strip_inline_comment(line);
+        let mut leading_ws: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        
+        // Join `if a > 0 &&` / `    b < 10 {` into one condition line so the
+        // `{` is seen together with its `if`
+        let clean_line = match condition_acc.take() {
+            Some((mut acc, ws)) => {
+                acc.push(' ');
+                acc.push_str(clean_line.trim());
+                if continues_expression(&acc) {
+                    condition_acc = Some((acc, ws));
+                    continue;
+                }
+                leading_ws = ws;
+                acc
+            }
+            None if is_condition_continuation_start(clean_line.trim()) => {
+                condition_acc = Some((clean_line.trim().to_string(), leading_ws));
+                continue;
+            }
+            None => clean_line,
+        };
+        
+        // Chained comparisons etc. in if / while / guard conditions
+        let clean_line = normalize_line_conditions(&clean_line);
         let trimmed = clean_line.trim();
-        let leading_ws: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+        
+        // CRITICAL FIX: Skip the body of a function with a malformed signature.
+        // Lowering it as top-level code leaks `effects(...)` and statements
+        // into item position; keep the lines (as comments) so line numbers hold.
+        if let Some(end_depth) = malformed_fn_end {
+            let (opens, closes) = count_braces_outside_strings(trimmed);
+            brace_depth = (brace_depth + opens).saturating_sub(closes);
+            if brace_depth <= end_depth {
+                malformed_fn_end = None;
+            }
+            output_lines.push(comment_out_line(&leading_ws, trimmed));
+            continue;
+        }
         
         // Update multiline expression depth
         let multiline_depth_before = multiline_expr_depth;
@@ -278,6 +577,7 @@ pub fn parse_rusts(source: &str) -> String {
         // Handle multi-line function signature accumulation
         if let Some(ref mut acc) = multiline_fn_acc {
             acc.push(' ');
+            multiline_fn_pieces.push((line_num, acc.len()));
             acc.push_str(trimmed);
             
             match process_multiline_fn_signature(
@@ -285,13 +585,24 @@ pub fn parse_rusts(source: &str) -> String {
                 &mut current_fn_ctx, brace_depth,
             ) {
                 MultilineFnResult::Continue => continue,
+                MultilineFnResult::Malformed { error, has_body } => {
+                    lowering_errors.push(malformed_signature_error(&lines, &multiline_fn_pieces, acc, &error));
+                    multiline_fn_acc = None;
+                    if has_body {
+                        malformed_fn_end = Some(brace_depth);
+                        brace_depth += 1;
+                    }
+                    continue;
+                }
                 MultilineFnResult::Complete { output, has_body } => {
                     multiline_fn_acc = None;
-                    output_lines.push(output);
+                    let attributes = fn_attributes.get(&statement_origins[multiline_fn_pieces[0].0]);
+                    output_lines.push(apply_fn_modifiers(&output, attributes.map_or(&[], Vec::as_slice)));
                     if has_body {
                         in_function_body = true;
                         function_start_brace = brace_depth + 1;
                         brace_depth += 1;
+                        contract_entry_pending = !current_fn_ctx.contract.is_empty();
                     }
                     continue;
                 }
@@ -302,6 +613,7 @@ pub fn parse_rusts(source: &str) -> String {
         if is_multiline_fn_start(trimmed) {
             multiline_fn_acc = Some(trimmed.to_string());
             multiline_fn_leading_ws = leading_ws.clone();
+            multiline_fn_pieces = vec![(line_num, 0)];
             continue;
         }
         
@@ -335,12 +647,26 @@ pub fn parse_rusts(source: &str) -> String {
             continue;
         }
         
+        // Malformed single-line signature: report it and skip the body
+        if trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ") {
+            if let FunctionParseResult::Error(e) = parse_function_line(trimmed) {
+                lowering_errors.push(malformed_signature_error(&lines, &[(line_num, 0)], trimmed, &e));
+                let (opens, closes) = count_braces_outside_strings(trimmed);
+                if opens > closes {
+                    malformed_fn_end = Some(brace_depth);
+                    brace_depth += opens - closes;
+                }
+                continue;
+            }
+        }
+        
         // Track function context
         if trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ") {
             in_function_body = true;
             function_start_brace = brace_depth + 1;
             if let FunctionParseResult::RustSPlusSignature(ref sig) = parse_function_line(trimmed) {
                 current_fn_ctx.enter(sig, function_start_brace);
+                contract_entry_pending = !sig.is_single_line && !sig.contract.is_empty();
             }
             
             // CRITICAL FIX (Bug #2): Per-function mutation scanning
@@ -348,20 +674,14 @@ pub fn parse_rusts(source: &str) -> String {
             // This prevents cross-function contamination where e.g.
             // `encrypted.field ^= 0xFF` in one test marks ALL `encrypted`
             // variables as mut across every function.
-            tracker.clear_function_local_mutations();
-            let mut fn_brace_depth: usize = 0;
-            let mut fn_started = false;
-            for future_line in lines.iter().skip(line_num) {
-                let ft = future_line.trim();
-                for c in ft.chars() {
-                    if c == '{' { fn_brace_depth += 1; fn_started = true; }
-                    if c == '}' { fn_brace_depth = fn_brace_depth.saturating_sub(1); }
-                }
-                if fn_started {
-                    tracker.scan_for_mutating_methods(ft);
-                    tracker.scan_for_mut_borrows(ft);
-                    if fn_brace_depth == 0 { break; }
+            // The body was scanned up front by `ProgramIndex::build`.
+            match index.function(line_num) {
+                Some(body) => {
+                    tracker.set_function_local_mutations(&body.mutated_via_method, &body.mut_borrowed, &body.resized);
+                    current_fn_ctx.copy_element_arrays =
+                        scan_copy_element_arrays(&index.body_lines(body), &current_fn_ctx.params, &copy_types);
                 }
+                None => tracker.clear_function_local_mutations(),
             }
         }
         
@@ -375,10 +695,17 @@ pub fn parse_rusts(source: &str) -> String {
         let (bracket_opens, bracket_closes) = count_brackets_outside_strings(trimmed);
         bracket_depth += bracket_opens;
         bracket_depth = bracket_depth.saturating_sub(bracket_closes);
+        match_mode.track_arm_body_line(trimmed);
+        
+        // Track `impl Trait for Type` blocks (std trait method signatures)
+        trait_impl_ctx.update(brace_depth);
+        trait_impl_ctx.enter_if_header(trimmed, prev_depth);
         
         // Exit function context
         if in_function_body && brace_depth < function_start_brace && trimmed == "}" {
             in_function_body = false;
+            let name = current_fn_ctx.name.clone().unwrap_or_default();
+            output_lines.extend(exit_lines(&name, &current_fn_ctx.contract, options.contracts, ""));
             current_fn_ctx.exit();
         }
         
@@ -409,13 +736,16 @@ pub fn parse_rusts(source: &str) -> String {
         
         // Literal mode
         match process_literal_mode_line(
-            trimmed, &clean_line, &leading_ws, brace_depth, opens, closes, prev_depth,
-            &mut literal_mode, &array_mode, Some(&current_fn_ctx),
+            trimmed, &clean_line, &leading_ws, brace_depth, prev_depth,
+            &mut literal_mode, &array_mode, Some(&current_fn_ctx), is_before_closing_brace,
         ) {
             LiteralModeResult::Handled(s) => { output_lines.push(s); continue; }
             LiteralModeResult::NotHandled => {}
         }
         
+        // Last line of an arm of an assigned match: the arm's value
+        let mut is_arm_value = false;
+        
         // Match mode
         match process_match_mode_line(
             line, trimmed, &clean_line, &leading_ws, &lines, line_num,
@@ -424,16 +754,26 @@ pub fn parse_rusts(source: &str) -> String {
         ) {
             MatchModeResult::Handled(s) => { output_lines.push(s); continue; }
             MatchModeResult::ProcessAsArmBody => {
-                // Process as match arm body (handled below in assignment/expression)
+                // Function reference / closure selected by a dispatch match:
+                // it is the arm's value, so no semicolon
+                if match_mode.current_is_assignment() && match_mode.at_arm_body_top()
+                    && is_before_closing_brace && is_fn_value_expr(trimmed)
+                {
+                    output_lines.push(format!("{}{}", leading_ws, trimmed));
+                    continue;
+                }
+                is_arm_value = match_mode.current_is_assignment() && match_mode.at_arm_body_top()
+                    && is_before_closing_brace && is_tail_value_expr(trimmed);
+                // Otherwise process as match arm body (handled below in assignment/expression)
             }
             MatchModeResult::NotHandled => {}
         }
         
         // Match expression start
-        if is_match_start(trimmed) {
+        if is_match_start(trimmed) && enabled(LoweringRule::Match) {
             let output = process_match_start(
                 trimmed, &leading_ws, &lines, line_num,
-                &scope_analyzer, &tracker, &current_fn_ctx, &mut match_mode, prev_depth,
+                &scope_analyzer, &tracker, &current_fn_ctx, &mut fn_registry, &const_registry, &mut match_mode, prev_depth,
             );
             output_lines.push(output);
             continue;
@@ -462,12 +802,27 @@ pub fn parse_rusts(source: &str) -> String {
             }
         }
         
+        // Closure assignment end: the `}` closing the body ends the `let`
+        if trimmed == "}" && closure_assignment_depths.last() == Some(&brace_depth) {
+            closure_assignment_depths.pop();
+            output_lines.push(format!("{}}};", leading_ws));
+            continue;
+        }
+        
         // Struct definition
         match process_struct_def_line(
             trimmed, &clean_line, &leading_ws, brace_depth,
             &mut in_struct_def, &mut struct_def_depth,
         ) {
-            StructDefResult::Started(s) | StructDefResult::Closed(s) | StructDefResult::Field(s) => {
+            StructDefResult::Started(s) => {
+                if let Some(name) = parse_struct_header(trimmed) {
+                    let needs_clone = options.derive_clone_all || types_need_clone.contains(&name);
+                    inject_clone_derive(&mut output_lines, &leading_ws, needs_clone);
+                }
+                output_lines.push(s);
+                continue;
+            }
+            StructDefResult::Closed(s) | StructDefResult::Field(s) => {
                 output_lines.push(s);
                 continue;
             }
@@ -480,6 +835,11 @@ pub fn parse_rusts(source: &str) -> String {
         ) {
             EnumDefResult::Started(s) | EnumDefResult::ClosedStructVariant(s) 
             | EnumDefResult::ClosedEnum(s) | EnumDefResult::Variant(s) => {
+                // Header line (multi-line start or single-line enum)
+                if let Some(name) = parse_enum_header(trimmed) {
+                    let needs_clone = options.derive_clone_all || types_need_clone.contains(&name);
+                    inject_clone_derive(&mut output_lines, &leading_ws, needs_clone);
+                }
                 output_lines.push(s);
                 continue;
             }
@@ -506,8 +866,8 @@ pub fn parse_rusts(source: &str) -> String {
         
         // Literal in function call
         match process_literal_in_call(
-            trimmed, &leading_ws, opens, closes, prev_depth,
-            &struct_registry, &mut literal_mode,
+            trimmed, &leading_ws, line_num, opens, closes, prev_depth,
+            &scope_analyzer, &tracker, &struct_registry, &mut literal_mode,
         ) {
             LiteralStartResult::Handled(s) => { output_lines.push(s); continue; }
             LiteralStartResult::NotLiteralStart => {}
@@ -533,23 +893,67 @@ pub fn parse_rusts(source: &str) -> String {
         // Function definition
         match process_function_def(
             trimmed, &clean_line, &leading_ws, &lines, line_num,
-            &mut current_fn_ctx, function_start_brace,
+            &mut current_fn_ctx, function_start_brace, trait_impl_ctx.current_trait(),
         ) {
-            FunctionDefResult::Handled(s) => { output_lines.push(s); continue; }
+            FunctionDefResult::Handled(s) => {
+                let s = match parse_function_line(trimmed) {
+                    FunctionParseResult::RustSPlusSignature(sig) if sig.is_single_line && !sig.contract.is_empty() => {
+                        wrap_single_line(&s, &sig.name, &sig.contract, options.contracts)
+                    }
+                    _ => s,
+                };
+                let origin = statement_origins[line_num];
+                let s = apply_fn_modifiers(&s, fn_attributes.get(&origin).map_or(&[], Vec::as_slice));
+                match badges.get(&origin) {
+                    Some(badge) => output_lines.extend(attach_badge(&s, &leading_ws, badge, options.preserve_lines)),
+                    None => output_lines.push(s),
+                }
+                continue;
+            }
+            FunctionDefResult::Malformed(e) => {
+                lowering_errors.push(malformed_signature_error(&lines, &[(line_num, 0)], trimmed, &e));
+                continue;
+            }
             FunctionDefResult::NotFunctionDef => {}
         }
         
+        // Embedder lowering hooks
+        if !options.hooks.is_empty() {
+            let ctx = LineCtx {
+                line: line_num + 1,
+                source: line,
+                code: trimmed,
+                indent: &leading_ws,
+                depth: brace_depth,
+                function: current_fn_ctx.name.as_deref(),
+                in_match_arm: match_mode.in_arm_body(),
+            };
+            if let Some(rewritten) = options.hooks.rewrite(&ctx) {
+                output_lines.extend(rewritten);
+                continue;
+            }
+        }
+        
         // Const/static declaration
         if let Some(transformed) = transform_const_or_static(trimmed) {
             output_lines.push(format!("{}{}", leading_ws, transformed));
             continue;
         }
         
-        // Effect statement skip
-        if trimmed.starts_with("effect ") {
+        // Effect statement skip (L-07)
+        if trimmed.starts_with("effect ") && enabled(LoweringRule::L07) {
             continue;
         }
         
+        // `for` over a collection: move, borrow, iterate or clone it (L-11)
+        let for_header = (trimmed.starts_with("for ") && enabled(LoweringRule::L11)).then(|| {
+            let is_reference = |name: &str| current_fn_ctx.params.get(name).is_some_and(|ty| ty.starts_with('&'))
+                || tracker.get_variable(name).is_some_and(|var| var.is_borrow);
+            let has_copy_elements = |iterable: &str| current_fn_ctx.copy_element_arrays.contains(iterable);
+            plan_for_loop(&lines, line_num, is_reference, has_copy_elements).map(|plan| plan.lower_header(trimmed))
+        }).flatten();
+        let trimmed = for_header.as_deref().unwrap_or(trimmed);
+        
         // Rust native passthrough
         if is_rust_native_line(trimmed) {
             let output = process_native_line(
@@ -579,6 +983,9 @@ pub fn parse_rusts(source: &str) -> String {
         
         // RustS+ assignment
         if let Some((var_name, var_type, value, is_outer, is_explicit_mut)) = parse_rusts_assignment_ext(&clean_line) {
+            if opens_closure_block(&value, opens, closes) {
+                closure_assignment_depths.push(prev_depth);
+            }
             let transformed_type = var_type.map(|t| transform_generic_brackets(&t));
             let result = process_assignment(
                 &var_name, transformed_type.as_deref(), &value, is_outer, is_explicit_mut,
@@ -588,33 +995,210 @@ pub fn parse_rusts(source: &str) -> String {
             );
             output_lines.push(result);
         } else {
+            // Last line of an assigned closure's block: the closure's value
+            let is_closure_value = is_before_closing_brace
+                && closure_assignment_depths.last().is_some_and(|&depth| brace_depth == depth + 1)
+                && is_tail_value_expr(trimmed);
             // Non-assignment
             let result = process_non_assignment(
                 trimmed, &leading_ws, line_num, &current_fn_ctx, &fn_registry,
                 is_before_closing_brace, inside_multiline_expr, next_line_is_method_chain,
                 next_line_closes_expr, &mut prev_line_was_continuation,
             );
-            output_lines.push(result);
+            match result.strip_suffix(';') {
+                Some(value) if is_arm_value || is_closure_value => output_lines.push(value.to_string()),
+                _ => output_lines.push(result),
+            }
         }
     }
     
+    line_origins.resize(output_lines.len(), lines.len().saturating_sub(1));
+    if let Some(mode) = open_mode(&literal_mode, &array_mode, &match_mode, &use_import_mode) {
+        output_lines.truncate(unit.output_start);
+        line_origins.truncate(unit.output_start);
+        lowering_errors.push(item_not_lowered_error(&lines, unit.first_line, &format!("{} opened in this item was still open at the end of the file", mode)));
+    }
+    
+    if let Some(program) = program {
+        *program = FirstPassResult { fn_registry, struct_registry, enum_registry, types_need_clone, clone_reasons, copy_types, generic_types, const_registry };
+    }
+    
+    let edition = options.edition.unwrap_or_default();
+    for (line_num, column, construct) in unavailable_async(&normalized_source, edition) {
+        let source_line = lines.get(line_num - 1).copied().unwrap_or("");
+        let len = if construct == "async block" { "async".len() } else { construct.len() };
+        lowering_errors.push(
+            structure_errors::unavailable_in_edition(construct, edition.as_str(), "2018")
+                .at(SourceLocation::new("", line_num, column + 1).with_source(source_line, column, len))
+        );
+    }
+    
+    // Abort code generation: output around an unlowerable construct is not
+    // meaningful and would only trip the sanity check somewhere else
+    if !lowering_errors.is_empty() {
+        for error in &mut lowering_errors {
+            if let Some(origin) = error.location.line.checked_sub(1).and_then(|i| statement_origins.get(i)) {
+                error.location.line = origin + 1;
+            }
+        }
+        lowering_errors.sort_by_key(|e| e.location.line);
+        return Err(lowering_errors);
+    }
+    
+    if let Some(import) = map_import {
+        output_lines.push(format!("{} {}", import, HOIST_MARKER));
+        line_origins.push(0);
+    }
+    
     // Apply post-processing
-    let mut result = apply_postprocessing(output_lines);
+    let line_origins: Vec<usize> = line_origins.iter().map(|&i| statement_origins.get(i).copied().unwrap_or(i)).collect();
+    let mut lowered_lines: Vec<(String, usize)> = Vec::new();
+    let mut result = if options.preserve_lines {
+        align_to_source_lines(&postprocess_lines(output_lines), &line_origins, source_line_count)
+    } else {
+        let processed = postprocess_lines(output_lines);
+        if line_table.is_some() {
+            lowered_lines = processed.iter().zip(&line_origins)
+                .flat_map(|(entry, &origin)| entry.split('\n').map(move |line| (line.to_string(), origin + 1)))
+                .collect();
+        }
+        finish_postprocessing(processed)
+    };
     result = transform_macros_to_correct_syntax(&result);
+    if !edition.formats_captured_args() {
+        result = result.split('\n').map(explicit_format_args).collect::<Vec<_>>().join("\n");
+    }
+    if edition.requires_dyn() {
+        let traits = trait_object_names(&normalized_source);
+        result = result.split('\n').map(|line| add_dyn(line, &traits)).collect::<Vec<_>>().join("\n");
+    }
+    if options.opt_level >= 1 {
+        result = inline_trivial_functions(&result, &normalized_source);
+    }
+    if options.opt_level >= 2 {
+        result = append_in_place(&result);
+    }
+    result = emit_error_enums(&result, &error_enums, options.preserve_lines);
     
     // Rust sanity check (non-test only)
     #[cfg(not(test))]
     {
-        let sanity = rust_sanity::check_rust_output(&result);
+        let sanity = rust_sanity::check_rust_output_with_rules(&result, &options.rules);
         if !sanity.is_valid {
-            eprintln!("{}", rust_sanity::format_internal_error(&sanity));
+            eprintln!("{}", rust_sanity::format_internal_error_with_rules(&sanity, &options.rules));
         }
     }
     
-    result
+    // Emit verbatim Rust blocks byte-for-byte
+    let result = if options.preserve_lines {
+        restore_verbatim_blocks_aligned(&result, &verbatim_blocks)
+    } else {
+        restore_verbatim_blocks(&result, &verbatim_blocks)
+    };
+    if let Some(table) = line_table {
+        *table = if options.preserve_lines {
+            LineTable::identity(result.lines().count())
+        } else {
+            // `rust { }` bodies come back byte-for-byte from their source
+            // lines, in place of the marker
+            let lowered_lines: Vec<(String, usize)> = lowered_lines.into_iter().flat_map(|(line, origin)| {
+                let block = line.trim().strip_prefix(VERBATIM_MARKER)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .and_then(|n| verbatim_blocks.get(n.parse::<usize>().ok()?));
+                let body: Vec<(String, usize)> = match block {
+                    Some(block) => {
+                        let first = if block.starts_on_header { block.line } else { block.line + 1 };
+                        block.body.lines().enumerate().map(|(i, line)| (line.to_string(), first + i)).collect()
+                    }
+                    None => Vec::new(),
+                };
+                std::iter::once((line, origin)).chain(body)
+            }).collect();
+            align_origins(&lowered_lines, &result)
+        };
+    }
+    Ok(result)
+}
+
+/// Stand-in output for a file that failed to lower: one `compile_error!`
+/// per diagnostic, so rustc never sees partial code
+fn lowering_errors_to_rust(errors: &[RsplError]) -> String {
+    errors.iter()
+        .map(|e| format!(
+            "compile_error!({:?});\n",
+            format!("RustS+ lowering error at line {}: {}", e.location.line, e.title)
+        ))
+        .collect()
+}
+
+/// Put modifier attributes on a lowered signature; `memo` expands it into
+/// the caching wrapper (see [`crate::memo`])
+fn apply_fn_modifiers(signature: &str, attributes: &[String]) -> String {
+    let (memo, attributes): (Vec<String>, Vec<String>) = attributes.iter().cloned().partition(|a| a == MEMO_ATTRIBUTE);
+    let signature = match memo.is_empty() {
+        true => signature.to_string(),
+        false => lower_memo_fn(signature).unwrap_or_else(|| signature.to_string()),
+    };
+    prefix_fn_attributes(&signature, &attributes)
+}
+
+/// Output of one top-level item, from `output_lines[output_start]` on
+struct LoweringUnit {
+    output_start: usize,
+    /// Source line (0-based) the item starts at
+    first_line: usize,
+}
+
+/// The mode left open, if any
+fn open_mode(
+    literal_mode: &LiteralModeStack,
+    array_mode: &ArrayModeStack,
+    match_mode: &MatchModeStack,
+    use_import_mode: &UseImportMode,
+) -> Option<&'static str> {
+    if literal_mode.is_active() {
+        Some("a struct literal")
+    } else if array_mode.is_active() {
+        Some("an array literal")
+    } else if match_mode.is_active() {
+        Some("a `match`")
+    } else if use_import_mode.is_active() {
+        Some("a `use` list")
+    } else {
+        None
+    }
+}
+
+/// RSPL030 at the first non-blank line of the item starting at `first_line`
+fn item_not_lowered_error(lines: &[&str], first_line: usize, why: &str) -> RsplError {
+    let line_idx = (first_line..lines.len())
+        .find(|&i| !lines[i].trim().is_empty())
+        .unwrap_or(first_line);
+    let item = lines.get(line_idx).map(|l| l.trim().trim_end_matches(" {")).unwrap_or("");
+    structure_errors::item_not_lowered(item, why).at(source_location(lines, line_idx, item))
+}
+
+/// RSPL020 for a signature that parsed in neither RustS+ nor Rust form
+fn malformed_signature_error(lines: &[&str], pieces: &[(usize, usize)], joined: &str, error: &str) -> RsplError {
+    structure_errors::malformed_signature(error)
+        .at(signature_error_location(lines, pieces, joined, error))
+        .note("no Rust is generated for a file with a malformed signature")
+}
+
+/// Location of `needle` on source line `line_idx` (0-based), or the whole
+/// line if it does not appear
+fn source_location(lines: &[&str], line_idx: usize, needle: &str) -> SourceLocation {
+    let source_line = lines.get(line_idx).copied().unwrap_or("");
+    let indent = source_line.len() - source_line.trim_start().len();
+    let (start, len) = match source_line.find(needle) {
+        Some(pos) => (pos, needle.len()),
+        None => (indent, source_line.trim().len()),
+    };
+    SourceLocation::new("", line_idx + 1, start + 1).with_source(source_line, start, len)
 }
 
 // Helper function for match start processing
+#[allow(clippy::too_many_arguments)]
 fn process_match_start(
     trimmed: &str,
     leading_ws: &str,
@@ -623,6 +1207,8 @@ fn process_match_start(
     scope_analyzer: &ScopeAnalyzer,
     tracker: &VariableTracker,
     current_fn_ctx: &CurrentFunctionContext,
+    fn_registry: &mut FunctionRegistry,
+    const_registry: &ConstRegistry,
     match_mode: &mut MatchModeStack,
     prev_depth: usize,
 ) -> String {
@@ -630,26 +1216,54 @@ fn process_match_start(
     let mut match_string_ctx = MatchStringContext::from_match_line(trimmed);
     
     // Look ahead for string patterns
+    // CRITICAL FIX: Only lines at arm depth are patterns. Arm bodies (string
+    // return values, nested `match mode { "strict" ... }`) must not put
+    // `.as_str()` on an integer scrutinee like `match score { 0..=59 { ... } }`
+    let mut arm_depth: i64 = 0;
     for future_line in lines.iter().skip(line_num + 1) {
         let ft = strip_inline_comment(future_line);
         let ft_trim = ft.trim();
-        if ft_trim == "}" { break; }
-        if pattern_is_string_literal(ft_trim) {
-            match_string_ctx.has_string_patterns = true;
-            break;
+        if arm_depth == 0 {
+            if ft_trim.starts_with('}') { break; }
+            let pattern = ft_trim.split("=>").next().unwrap_or_default();
+            let pattern = &pattern[..find_outside_strings(pattern, '{').unwrap_or(pattern.len())];
+            // `ADMIN | GUEST {` compares against `&str` constants
+            let has_str_constant = pattern.split(" if ").next().unwrap_or_default()
+                .split('|')
+                .any(|alternative| const_registry.is_str(alternative.trim()));
+            if pattern_is_string_literal(ft_trim) || has_str_constant {
+                match_string_ctx.has_string_patterns = true;
+                break;
+            }
+            // A match on a bool never needs `.as_str()`
+            if is_bool_match(&[pattern]) { break; }
         }
+        let (opens, closes) = count_braces_outside_strings(ft_trim);
+        arm_depth += opens as i64 - closes as i64;
+        if arm_depth < 0 { break; }
     }
     
     let needs_as_str = match_string_ctx.needs_as_str();
     
     let output = if let Some((var_name_raw, match_expr)) = parse_control_flow_assignment(trimmed) {
-        let (actual_var_name, type_annotation) = parse_var_type_annotation(&var_name_raw);
+        let (actual_var_name, mut type_annotation) = parse_var_type_annotation(&var_name_raw);
+        // `res.status = match code {` mutates a field: no `let`, and the
+        // container is already marked mutated by the first pass
+        let is_field = is_field_access(actual_var_name);
         let is_param = current_fn_ctx.params.contains_key(actual_var_name);
         let is_decl = scope_analyzer.is_decl(line_num);
         let is_mutation = scope_analyzer.is_mut(line_num);
         let is_shadowing = tracker.is_shadowing(actual_var_name, line_num);
         let needs_mut = scope_analyzer.needs_mut(actual_var_name, line_num);
-        let needs_let = is_decl || (!is_mutation && !is_param) || is_shadowing;
+        let needs_let = !is_field && (is_decl || (!is_mutation && !is_param) || is_shadowing);
+        
+        // Dispatch table: every arm selects a function (or closure) of one fn type
+        if let Some(sig) = infer_dispatch_signature(lines, line_num, fn_registry) {
+            if type_annotation.is_empty() && needs_let {
+                type_annotation = format!(": {}", fn_pointer_type(&sig));
+            }
+            fn_registry.register_alias(actual_var_name, &sig);
+        }
         
         let transformed_match_expr = if needs_as_str {
             transform_match_for_string_patterns(&match_expr, true)
@@ -690,12 +1304,14 @@ fn process_if_assignment(
     let (var_name_raw, if_expr) = parse_control_flow_assignment(trimmed)?;
     
     let (actual_var_name, type_annotation) = parse_var_type_annotation(&var_name_raw);
+    // Field target (`res.code = if ...`): assign, never declare
+    let is_field = is_field_access(actual_var_name);
     let is_param = current_fn_ctx.params.contains_key(actual_var_name);
     let is_decl = scope_analyzer.is_decl(line_num);
     let is_mutation = scope_analyzer.is_mut(line_num);
     let is_shadowing = tracker.is_shadowing(actual_var_name, line_num);
     let needs_mut = scope_analyzer.needs_mut(actual_var_name, line_num);
-    let needs_let = is_decl || (!is_mutation && !is_param) || is_shadowing;
+    let needs_let = !is_field && (is_decl || (!is_mutation && !is_param) || is_shadowing);
     
     let output = if needs_let {
         let keyword = if needs_mut { "let mut" } else { "let" };
@@ -56,9 +56,11 @@ use std::collections::{HashMap, HashSet, BTreeSet};
 use crate::ast::{Type, EffectDecl, Ident, FnDef, Literal};
 use crate::hir::{
     BindingId, BindingInfo, HirExpr, HirStmt, HirBlock, HirCallTarget,
-    HirMatchArm, Spanned,
+    HirMatchArm, Path, Spanned,
 };
-use crate::eir::{Effect, EffectSet};
+use crate::eir::{Effect, EffectAlgebra, EffectSet};
+use crate::effect_db::with_effect_db;
+use crate::effect_detector::EffectCategory;
 
 //=============================================================================
 // EFFECT SIGNATURE
@@ -147,7 +149,8 @@ impl EffectSignature {
                     }
                     sig.is_pure = false;
                 }
-                EffectDecl::Write(param) => {
+                // A field write is a write to the parameter binding
+                EffectDecl::Write(param) | EffectDecl::WriteField { param, .. } => {
                     if let Some(&id) = param_bindings.get(&param.name) {
                         sig.effects.insert(Effect::Write(id));
                         let current = sig.param_effects.get(&param.name)
@@ -272,6 +275,9 @@ pub struct TypeEnv {
     
     /// Method effect signatures: method_name -> EffectSet
     method_effects: HashMap<String, EffectSet>,
+    
+    /// Methods that call the function passed to them
+    higher_order_methods: HashSet<String>,
 }
 
 impl TypeEnv {
@@ -284,89 +290,31 @@ impl TypeEnv {
     
     /// Register standard library effect signatures
     ///
-    /// This registers known effects for standard library functions.
-    /// These are the "intrinsics" that form the basis of effect inference.
+    /// These are the "intrinsics" that form the basis of effect inference,
+    /// taken from the stdlib effect database (see [`crate::effect_db`]).
+    /// `concurrent` has no counterpart in the IR and is left out.
     fn register_stdlib(&mut self) {
-        // ===== I/O Functions =====
-        let io_funcs = [
-            // Console I/O
-            "println", "print", "eprintln", "eprint",
-            "writeln", "write",
-            // File I/O (function-style)
-            "read", "read_exact", "read_to_string", "read_to_end",
-            "write_all", "flush",
-            // Stdin/Stdout
-            "stdin", "stdout", "stderr",
-        ];
-        for name in &io_funcs {
-            self.stdlib_effects.insert(
-                name.to_string(),
-                EffectSet::singleton(Effect::Io)
-            );
-        }
-        
-        // ===== Allocating Functions =====
-        let alloc_funcs = [
-            // Constructors
-            "Vec::new", "Vec::with_capacity",
-            "String::new", "String::from", "String::with_capacity",
-            "Box::new", "Rc::new", "Arc::new",
-            "HashMap::new", "HashMap::with_capacity",
-            "HashSet::new", "HashSet::with_capacity",
-            "BTreeMap::new", "BTreeSet::new",
-            "VecDeque::new", "LinkedList::new", "BinaryHeap::new",
-            // Macros (treated as functions)
-            "vec", "format",
-            // Conversion methods that allocate
-            "to_string", "to_owned", "to_vec",
-            "into_boxed_slice", "into_boxed_str",
-        ];
-        for name in &alloc_funcs {
-            self.stdlib_effects.insert(
-                name.to_string(),
-                EffectSet::singleton(Effect::Alloc)
-            );
-        }
-        
-        // ===== Panicking Functions =====
-        let panic_funcs = [
-            "panic", "unwrap", "expect",
-            "assert", "assert_eq", "assert_ne",
-            "unreachable", "unimplemented", "todo",
-        ];
-        for name in &panic_funcs {
-            self.stdlib_effects.insert(
-                name.to_string(),
-                EffectSet::singleton(Effect::Panic)
-            );
-        }
-        
-        // ===== Method Effects =====
-        // I/O methods
-        for method in &["read", "read_line", "read_to_string", "write", "write_all", "flush"] {
-            self.method_effects.insert(
-                method.to_string(),
-                EffectSet::singleton(Effect::Io)
-            );
-        }
-        
-        // Allocating methods
-        // NOTE: Removed clone() and collect() - they may not allocate
-        // depending on the type (Copy types don't allocate on clone)
-        for method in &["to_string", "to_owned", "to_vec", "push", "insert"] {
-            self.method_effects.insert(
-                method.to_string(),
-                EffectSet::singleton(Effect::Alloc)
-            );
-        }
-        
-        // Panicking methods
-        for method in &["unwrap", "expect"] {
-            self.method_effects.insert(
-                method.to_string(),
-                EffectSet::singleton(Effect::Panic)
-            );
-        }
+        let to_set = |categories: &BTreeSet<EffectCategory>| {
+            let mut effects = EffectSet::new();
+            for category in categories {
+                match category {
+                    EffectCategory::Io => effects.insert(Effect::Io),
+                    EffectCategory::Alloc => effects.insert(Effect::Alloc),
+                    EffectCategory::Panic => effects.insert(Effect::Panic),
+                    EffectCategory::Concurrent => {}
+                }
+            }
+            effects
+        };
+        with_effect_db(|db| {
+            for (name, categories) in &db.functions {
+                self.stdlib_effects.insert(name.clone(), to_set(categories));
+            }
+            for (method, categories) in &db.methods {
+                self.method_effects.insert(method.clone(), to_set(categories));
+            }
+            self.higher_order_methods.extend(db.higher_order.iter().cloned());
+        });
     }
     
     /// Register a user-defined function from parsed FnDef
@@ -431,6 +379,11 @@ impl TypeEnv {
         self.method_effects.get(method)
     }
     
+    /// Whether `method` calls the function passed to it (`map`, `for_each`)
+    pub fn calls_argument(&self, method: &str) -> bool {
+        self.higher_order_methods.contains(method)
+    }
+    
     /// Get full function type
     pub fn get_function_type(&self, name: &str) -> Option<&FunctionType> {
         self.functions.get(name)
@@ -546,6 +499,11 @@ impl<'a> TypeDrivenInference<'a> {
                 }
             }
             
+            // ===== FUNCTION VALUE =====
+            // Naming a function performs nothing; calling it does (see
+            // higher-order methods in `infer_call`)
+            HirExpr::FnRef(_) => EffectSet::empty(),
+            
             // ===== BINARY OP RULE =====
             // Γ ⊢ e₁ : τ | E₁,  Γ ⊢ e₂ : τ | E₂
             // ────────────────────────────────────
@@ -703,21 +661,7 @@ impl<'a> TypeDrivenInference<'a> {
         
         // Get function effects FROM TYPE SIGNATURE
         match target {
-            HirCallTarget::Function(path) => {
-                let func_name = path.to_string();
-                
-                // Look up effect signature in type environment
-                if let Some(func_effects) = self.type_env.get_function_effects(&func_name) {
-                    effects.extend(func_effects);
-                }
-                
-                // Also check for short name (e.g., "println" vs "std::io::println")
-                if let Some(last_segment) = path.segments.last() {
-                    if let Some(func_effects) = self.type_env.get_function_effects(&last_segment.name) {
-                        effects.extend(func_effects);
-                    }
-                }
-            }
+            HirCallTarget::Function(path) => effects.extend(&self.function_effects(path)),
             HirCallTarget::Method { receiver, method } => {
                 // Add receiver effects
                 effects.extend(&self.infer_expr(receiver));
@@ -726,12 +670,39 @@ impl<'a> TypeDrivenInference<'a> {
                 if let Some(method_effects) = self.type_env.get_method_effects(&method.name) {
                     effects.extend(method_effects);
                 }
+                
+                // `xs.map(parse)` calls `parse`
+                if self.type_env.calls_argument(&method.name) {
+                    for arg in args {
+                        if let HirExpr::FnRef(path) = &arg.node {
+                            effects.extend(&self.function_effects(path));
+                        }
+                    }
+                }
             }
         }
         
         effects
     }
     
+    /// Effects of calling the function at `path`, by full or short name
+    fn function_effects(&self, path: &Path) -> EffectSet {
+        let mut effects = EffectSet::new();
+        
+        // Look up effect signature in type environment
+        if let Some(func_effects) = self.type_env.get_function_effects(&path.to_string()) {
+            effects.extend(func_effects);
+        }
+        
+        // Also check for short name (e.g., "println" vs "std::io::println")
+        if let Some(last_segment) = path.segments.last() {
+            if let Some(func_effects) = self.type_env.get_function_effects(&last_segment.name) {
+                effects.extend(func_effects);
+            }
+        }
+        effects
+    }
+    
     /// Infer effects for a block
     pub fn infer_block(&self, block: &Spanned<HirBlock>) -> EffectSet {
         let mut effects = EffectSet::new();
@@ -1045,7 +1016,29 @@ mod tests {
         let effects = inference.infer_expr(&alloc_call);
         assert!(effects.has_alloc());
     }
-    
+
+    #[test]
+    fn test_type_driven_inference_higher_order() {
+        let mut env = TypeEnv::new();
+        env.register_function_sig("log_item", &[EffectDecl::Io], &HashMap::new(), 1);
+        env.register_function_sig("double", &[], &HashMap::new(), 2);
+        let inference = TypeDrivenInference::new(&env);
+
+        // xs.<method>(<function>)
+        let call = |method: &str, function: &str| make_spanned(HirExpr::Call {
+            target: HirCallTarget::Method {
+                receiver: Box::new(make_spanned(HirExpr::Literal(Literal::Int(0)))),
+                method: Ident::new(method),
+            },
+            args: vec![make_spanned(HirExpr::FnRef(HirPath::simple(function)))],
+        });
+
+        assert!(inference.infer_expr(&call("for_each", "log_item")).has_io());
+        assert!(inference.infer_expr(&call("map", "double")).is_empty());
+        // Not a higher-order method: the function is only passed along
+        assert!(!inference.infer_expr(&call("push", "log_item")).has_io());
+    }
+
     #[test]
     fn test_type_driven_inference_binary() {
         let mut env = TypeEnv::new();
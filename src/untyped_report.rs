@@ -0,0 +1,404 @@
+//! Gradual Typing Report for RustS+
+//!
+//! RustS+ lets users write `x = expr` without a type annotation. The lowering
+//! turns this into `let x = expr;` and relies on rustc to infer the type.
+//! For literals, known constructors and calls to functions with a declared
+//! return type this is safe, but for everything else the lowering is GUESSING.
+//!
+//! This module lists every such auto-let binding so users can incrementally
+//! annotate performance- or correctness-critical code:
+//!
+//! ```text
+//! total = compute(items)      // `compute` is not a RustS+ function → untyped
+//! total u64 = compute(items)  // annotated → no longer reported
+//! ```
+
+use std::collections::HashSet;
+
+use crate::helpers::{strip_inline_comment, is_field_access, is_tuple_pattern};
+use crate::variable::{VariableTracker, parse_rusts_assignment_ext};
+use crate::function::{parse_function_line, FunctionParseResult, FunctionRegistry};
+use crate::struct_def::StructRegistry;
+use crate::first_pass::run_first_pass;
+use crate::detection::{
+    detect_struct_literal_start, detect_bare_struct_literal, detect_bare_enum_literal,
+    detect_struct_literal_in_call, detect_enum_literal_in_call, detect_enum_literal_start,
+};
+use crate::lowering::depth_tracking_lowering::{
+    count_braces_outside_strings, count_brackets_outside_strings,
+};
+
+//=============================================================================
+// REPORT TYPES
+//=============================================================================
+
+/// Why the lowering could not determine the type of a binding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UntypedReason {
+    /// Call to a function that is not defined in this RustS+ source
+    UnknownFunction(String),
+    /// Call to an associated function whose return type is unknown
+    UnknownAssociatedFn(String),
+    /// Method call - return type depends on the receiver
+    MethodCall(String),
+    /// Field access, indexing or a plain variable copy
+    Projection,
+    /// Arithmetic / logical expression over untyped operands
+    Expression,
+}
+
+impl UntypedReason {
+    pub fn describe(&self) -> String {
+        match self {
+            UntypedReason::UnknownFunction(f) => format!("return type of `{}` is unknown", f),
+            UntypedReason::UnknownAssociatedFn(f) => format!("return type of `{}` is unknown", f),
+            UntypedReason::MethodCall(m) => format!("return type of method `.{}()` depends on the receiver", m),
+            UntypedReason::Projection => "type comes from a field, index or another binding".to_string(),
+            UntypedReason::Expression => "type of the expression is not known to the lowering".to_string(),
+        }
+    }
+}
+
+/// An auto-let binding whose type had to be guessed
+#[derive(Debug, Clone)]
+pub struct UntypedBinding {
+    pub name: String,
+    /// 1-based source line
+    pub line: usize,
+    pub value: String,
+    pub reason: UntypedReason,
+    /// Likely type, when a well-known method or pattern suggests one
+    pub likely_type: Option<String>,
+}
+
+impl UntypedBinding {
+    /// Annotated form the user can paste back into the source
+    pub fn suggestion(&self) -> String {
+        let ty = self.likely_type.as_deref().unwrap_or("<Type>");
+        format!("{} {} = {}", self.name, ty, self.value)
+    }
+}
+
+//=============================================================================
+// ANALYSIS
+//=============================================================================
+
+/// Kind of brace block we are currently inside
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BlockKind {
+    Code,
+    Literal,
+}
+
+/// Find all auto-let bindings whose type the lowering could not determine
+pub fn find_untyped_bindings(source: &str) -> Vec<UntypedBinding> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut tracker = VariableTracker::new();
+    let first_pass = run_first_pass(&lines, &mut tracker);
+
+    let mut result = Vec::new();
+    let mut blocks: Vec<BlockKind> = Vec::new();
+    let mut bracket_depth: usize = 0;
+    let mut fn_depth: Option<usize> = None;
+    let mut declared: HashSet<String> = HashSet::new();
+
+    for (idx, line) in lines.iter().enumerate() {
+        let clean_line = strip_inline_comment(line);
+        let trimmed = clean_line.trim();
+
+        // Function entry: reset declared names, parameters count as typed
+        if fn_depth.is_none() && (trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ")) {
+            declared.clear();
+            if let FunctionParseResult::RustSPlusSignature(sig) = parse_function_line(trimmed) {
+                for p in &sig.parameters {
+                    declared.insert(p.name.clone());
+                }
+            }
+            fn_depth = Some(blocks.len());
+        }
+
+        let in_code = fn_depth.is_some()
+            && bracket_depth == 0
+            && blocks.last() == Some(&BlockKind::Code);
+
+        if in_code {
+            if let Some(binding) = classify_line(trimmed, idx + 1, &first_pass.fn_registry, &mut declared) {
+                result.push(binding);
+            }
+        }
+
+        // Update block stack
+        let (opens, closes) = count_braces_outside_strings(trimmed);
+        let enters_literal = in_code && is_literal_start(trimmed, &first_pass.struct_registry);
+        let kind = if enters_literal || blocks.last() == Some(&BlockKind::Literal) {
+            BlockKind::Literal
+        } else {
+            BlockKind::Code
+        };
+        for _ in 0..closes.min(blocks.len()) {
+            blocks.pop();
+        }
+        for _ in 0..opens {
+            blocks.push(kind);
+        }
+
+        let (b_opens, b_closes) = count_brackets_outside_strings(trimmed);
+        bracket_depth = (bracket_depth + b_opens).saturating_sub(b_closes);
+
+        if let Some(d) = fn_depth {
+            if blocks.len() <= d && closes > 0 {
+                fn_depth = None;
+            }
+        }
+    }
+
+    result
+}
+
+fn is_literal_start(trimmed: &str, registry: &StructRegistry) -> bool {
+    detect_struct_literal_start(trimmed, registry).is_some()
+        || detect_enum_literal_start(trimmed).is_some()
+        || detect_bare_struct_literal(trimmed, registry).is_some()
+        || detect_bare_enum_literal(trimmed).is_some()
+        || detect_struct_literal_in_call(trimmed, registry).is_some()
+        || detect_enum_literal_in_call(trimmed).is_some()
+}
+
+/// Classify a single line. Returns Some only for a first auto-let binding
+/// whose type cannot be determined.
+fn classify_line(
+    trimmed: &str,
+    line: usize,
+    registry: &FunctionRegistry,
+    declared: &mut HashSet<String>,
+) -> Option<UntypedBinding> {
+    let (name, explicit_type, value, _is_outer, _is_mut) = parse_rusts_assignment_ext(trimmed)?;
+    let name = name.trim().to_string();
+
+    if name == "_" || is_field_access(&name) || is_tuple_pattern(&name) || name.contains('[') {
+        return None;
+    }
+
+    // Reassignment (or `outer`) - the type was fixed at the declaration
+    if !declared.insert(name.clone()) {
+        return None;
+    }
+
+    if explicit_type.is_some() {
+        return None;
+    }
+
+    let value = value.trim().to_string();
+    let reason = untyped_reason(&value, registry)?;
+    let likely_type = likely_type(&value);
+
+    Some(UntypedBinding { name, line, value, reason, likely_type })
+}
+
+/// Decide whether the lowering knows the type of `value`.
+/// Returns None when the type is known (literal, constructor, typed call).
+fn untyped_reason(value: &str, registry: &FunctionRegistry) -> Option<UntypedReason> {
+    let v = value.trim();
+
+    // Literals and obvious constructors
+    if VariableTracker::infer_type(v).is_some() || is_suffixed_number(v) {
+        return None;
+    }
+    if v.starts_with("format!(") || v.starts_with("vec!") || v.starts_with('[') || v.starts_with('(') {
+        return None;
+    }
+    if v.ends_with('{') {
+        // Struct / enum literal start - the type is named explicitly
+        return None;
+    }
+
+    // Top-level binary operators → expression
+    if has_top_level_operator(v) {
+        return Some(UntypedReason::Expression);
+    }
+
+    // Trailing method call: `receiver.method(...)`
+    if let Some(method) = trailing_method(v) {
+        return Some(UntypedReason::MethodCall(method));
+    }
+
+    // Function call: `name(...)` or `Path::name(...)`
+    if let Some(paren) = v.find('(') {
+        if v.ends_with(')') {
+            let callee = v[..paren].trim();
+            if callee.contains("::") {
+                // `Type::new(...)` / `Type::Variant(...)` name their type
+                let last = callee.rsplit("::").next().unwrap_or("");
+                let owner = callee.rsplit("::").nth(1).unwrap_or("");
+                let owner_is_type = owner.chars().next().is_some_and(|c| c.is_uppercase());
+                let is_ctor = last.chars().next().is_some_and(|c| c.is_uppercase())
+                    || matches!(last, "new" | "default" | "from" | "with_capacity");
+                if owner_is_type && is_ctor {
+                    return None;
+                }
+                return Some(UntypedReason::UnknownAssociatedFn(callee.to_string()));
+            }
+            if matches!(callee, "Some" | "Ok" | "Err" | "Box::new") {
+                return None;
+            }
+            if registry.get(callee).is_some() {
+                return None;
+            }
+            return Some(UntypedReason::UnknownFunction(callee.to_string()));
+        }
+    }
+
+    // Unit-like enum variants / constants
+    if v.contains("::") || v.chars().next().is_some_and(|c| c.is_uppercase()) {
+        return None;
+    }
+
+    Some(UntypedReason::Projection)
+}
+
+fn is_suffixed_number(v: &str) -> bool {
+    const SUFFIXES: [&str; 14] = [
+        "u8", "u16", "u32", "u64", "u128", "usize",
+        "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+    ];
+    v.chars().next().is_some_and(|c| c.is_ascii_digit())
+        && SUFFIXES.iter().any(|s| v.ends_with(s))
+}
+
+/// Does the expression contain an operator outside of parens/strings?
+fn has_top_level_operator(v: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut prev = ' ';
+    for c in v.chars() {
+        if c == '"' && prev != '\\' {
+            in_string = !in_string;
+        }
+        if !in_string {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                '+' | '*' | '/' | '%' | '<' | '>' if depth == 0 => return true,
+                '-' if depth == 0 && prev == ' ' => return true,
+                '&' | '|' if depth == 0 && prev == c => return true,
+                _ => {}
+            }
+        }
+        prev = c;
+    }
+    false
+}
+
+/// Name of the last method call in a `receiver.method(...)` chain
+fn trailing_method(v: &str) -> Option<String> {
+    if !v.ends_with(')') {
+        return None;
+    }
+    let mut depth = 0i32;
+    let bytes = v.as_bytes();
+    let mut open = None;
+    for i in (0..bytes.len()).rev() {
+        match bytes[i] {
+            b')' => depth += 1,
+            b'(' => {
+                depth -= 1;
+                if depth == 0 {
+                    open = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let head = &v[..open?];
+    let dot = head.rfind('.')?;
+    let method = &head[dot + 1..];
+    if method.is_empty() || !method.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(method.to_string())
+}
+
+/// Suggest a type for well-known methods
+fn likely_type(value: &str) -> Option<String> {
+    let method = trailing_method(value)?;
+    let ty = match method.as_str() {
+        "len" | "count" | "capacity" => "usize",
+        "to_string" | "to_owned" | "to_uppercase" | "to_lowercase" | "join" | "repeat" => "String",
+        "is_empty" | "contains" | "starts_with" | "ends_with" | "is_some" | "is_none"
+        | "is_ok" | "is_err" | "any" | "all" => "bool",
+        _ => return None,
+    };
+    Some(ty.to_string())
+}
+
+//=============================================================================
+// FORMATTING
+//=============================================================================
+
+/// Format the report for terminal output
+pub fn format_untyped_report(bindings: &[UntypedBinding], file_name: &str) -> String {
+    use crate::anti_fail_logic::ansi;
+
+    let mut out = String::new();
+    out.push_str(&format!("{}Untyped bindings in {}{}\n\n", ansi::BOLD_CYAN, file_name, ansi::RESET));
+
+    if bindings.is_empty() {
+        out.push_str(&format!("  {}✓{} every auto-let binding has a known type\n", ansi::BOLD_GREEN, ansi::RESET));
+        return out;
+    }
+
+    for b in bindings {
+        out.push_str(&format!("  {}{}:{}{} `{}`\n", ansi::BOLD_YELLOW, file_name, b.line, ansi::RESET, b.name));
+        out.push_str(&format!("    {}reason{}: {}\n", ansi::CYAN, ansi::RESET, b.reason.describe()));
+        out.push_str(&format!("    {}suggest{}: {}\n", ansi::GREEN, ansi::RESET, b.suggestion()));
+    }
+    out.push_str(&format!("\n  {} untyped binding(s)\n", bindings.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(src: &str) -> Vec<String> {
+        find_untyped_bindings(src).into_iter().map(|b| b.name).collect()
+    }
+
+    #[test]
+    fn test_literals_are_typed() {
+        let src = "fn main() {\n    a = 1\n    b = \"x\"\n    c = 2.5\n    d = true\n    e = 3u64\n}";
+        assert!(names(src).is_empty());
+    }
+
+    #[test]
+    fn test_known_function_is_typed() {
+        let src = "fn add(a i32, b i32) i32 {\n    a + b\n}\n\nfn main() {\n    x = add(1, 2)\n    y = mystery(1)\n}";
+        let found = find_untyped_bindings(src);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "y");
+        assert_eq!(found[0].line, 7);
+        assert_eq!(found[0].reason, UntypedReason::UnknownFunction("mystery".to_string()));
+    }
+
+    #[test]
+    fn test_annotation_and_reassignment_not_reported() {
+        let src = "fn main() {\n    n u64 = mystery()\n    mut m = 0\n    m = mystery()\n}";
+        assert!(names(src).is_empty());
+    }
+
+    #[test]
+    fn test_method_call_suggestion() {
+        let src = "fn f(v Vec[i32]) {\n    n = v.len()\n    w = v.first()\n}";
+        let found = find_untyped_bindings(src);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].suggestion(), "n usize = v.len()");
+        assert_eq!(found[1].suggestion(), "w <Type> = v.first()");
+    }
+
+    #[test]
+    fn test_struct_literal_fields_skipped() {
+        let src = "struct P {\n    x i32\n}\n\nfn main() {\n    p = P {\n        x = other\n    }\n}";
+        assert!(names(src).is_empty());
+    }
+}
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use crate::helpers::char_to_byte_index;
+use crate::lowering::depth_tracking_lowering::split_top_level_types;
 
 #[derive(Debug, Clone)]
 pub struct Variable {
@@ -35,6 +37,8 @@ pub struct VariableTracker {
     mut_borrowed_vars: std::collections::HashSet<String>,
     // Track variables that have mutating methods called on them (.push(), .insert(), etc.)
     mutated_via_method: std::collections::HashSet<String>,
+    // Track variables whose length changes (.push(), .pop(), etc.): a fixed array cannot hold them
+    resized_vars: std::collections::HashSet<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -56,6 +60,7 @@ impl VariableTracker {
             scope_mutability: HashMap::new(),
             mut_borrowed_vars: std::collections::HashSet::new(),
             mutated_via_method: std::collections::HashSet::new(),
+            resized_vars: std::collections::HashSet::new(),
         }
     }
 
@@ -222,35 +227,7 @@ impl VariableTracker {
     
     /// Scan a line for &mut <identifier> patterns and mark those variables as needing mutability
     pub fn scan_for_mut_borrows(&mut self, line: &str) {
-        let trimmed = line.trim();
-        
-        // Skip empty lines and comments
-        if trimmed.is_empty() || trimmed.starts_with("//") {
-            return;
-        }
-        
-        // Find all occurrences of &mut followed by an identifier
-        let mut remaining = trimmed;
-        while let Some(pos) = remaining.find("&mut ") {
-            let after_mut = &remaining[pos + 5..];
-            
-            // Extract the identifier after &mut
-            let ident: String = after_mut
-                .chars()
-                .take_while(|c| c.is_alphanumeric() || *c == '_')
-                .collect();
-            
-            if !ident.is_empty() && is_valid_identifier(&ident) {
-                self.mut_borrowed_vars.insert(ident);
-            }
-            
-            // Move past this occurrence
-            if pos + 5 < remaining.len() {
-                remaining = &remaining[pos + 5..];
-            } else {
-                break;
-            }
-        }
+        self.mut_borrowed_vars.extend(mut_borrow_targets(line));
     }
     
     /// Mark a variable as being borrowed mutably
@@ -261,56 +238,7 @@ impl VariableTracker {
     /// Scan a line for mutating method calls like .push(), .insert(), etc.
     /// These require the variable to be declared as `mut`
     pub fn scan_for_mutating_methods(&mut self, line: &str) {
-        let trimmed = line.trim();
-        
-        // Skip empty lines and comments
-        if trimmed.is_empty() || trimmed.starts_with("//") {
-            return;
-        }
-        
-        // List of mutating methods that require &mut self
-        const MUTATING_METHODS: &[&str] = &[
-            // Vec methods
-            ".push(", ".pop()", ".insert(", ".remove(", ".clear()", 
-            ".append(", ".truncate(", ".resize(", ".extend(",
-            ".sort(", ".sort_by(", ".sort_by_key(", ".reverse()",
-            ".drain(", ".retain(", ".dedup(", ".swap(",
-            ".split_off(", ".swap_remove(",
-            // HashMap/HashSet methods
-            ".entry(", ".or_insert(", ".and_modify(",
-            // String methods
-            ".push_str(",
-            // Common mutation patterns
-            ".get_mut(",
-        ];
-        
-        // Compound assignment operators that indicate mutation
-        const COMPOUND_ASSIGNS: &[&str] = &[
-            " += ", " -= ", " *= ", " /= ", " %= ",
-            " &= ", " |= ", " ^= ", " <<= ", " >>= ",
-        ];
-        
-        // Check for mutating methods: var.method(...)
-        for method in MUTATING_METHODS {
-            if let Some(pos) = trimmed.find(method) {
-                // Extract variable name before the method call
-                let before_method = &trimmed[..pos];
-                if let Some(var_name) = extract_var_name_before_dot(before_method) {
-                    self.mutated_via_method.insert(var_name);
-                }
-            }
-        }
-        
-        // Check for compound assignments: var += value
-        for op in COMPOUND_ASSIGNS {
-            if let Some(pos) = trimmed.find(op) {
-                let before_op = trimmed[..pos].trim();
-                // Handle simple variable or field access
-                if let Some(var_name) = extract_root_var(before_op) {
-                    self.mutated_via_method.insert(var_name);
-                }
-            }
-        }
+        self.mutated_via_method.extend(mutating_method_targets(line));
     }
     
     /// Check if a variable is mutated via method calls
@@ -318,6 +246,16 @@ impl VariableTracker {
         self.mutated_via_method.contains(var_name)
     }
     
+    /// Does `var_name`, declared with `var_type`, need a `Vec` rather than a
+    /// fixed array for a `[...]` literal? It does when annotated as a `Vec`
+    /// or, without an annotation, when the function grows or shrinks it.
+    pub fn needs_vec(&self, var_name: &str, var_type: Option<&str>) -> bool {
+        match var_type {
+            Some(t) => t.starts_with("Vec<") || t.starts_with("Vec["),
+            None => self.resized_vars.contains(var_name),
+        }
+    }
+    
     /// Clear function-local mutation tracking sets.
     /// 
     /// CRITICAL FIX (Bug #2): `mutated_via_method` and `mut_borrowed_vars` were
@@ -330,6 +268,20 @@ impl VariableTracker {
     pub fn clear_function_local_mutations(&mut self) {
         self.mutated_via_method.clear();
         self.mut_borrowed_vars.clear();
+        self.resized_vars.clear();
+    }
+
+    /// Replace the function-local mutation sets with one function's, as
+    /// collected up front by [`crate::program_index::ProgramIndex`]
+    pub fn set_function_local_mutations(
+        &mut self,
+        mutated_via_method: &std::collections::HashSet<String>,
+        mut_borrowed_vars: &std::collections::HashSet<String>,
+        resized_vars: &std::collections::HashSet<String>,
+    ) {
+        self.mutated_via_method.clone_from(mutated_via_method);
+        self.mut_borrowed_vars.clone_from(mut_borrowed_vars);
+        self.resized_vars.clone_from(resized_vars);
     }
 
     pub fn is_first_assignment(&self, var_name: &str, line_num: usize) -> bool {
@@ -382,6 +334,14 @@ impl VariableTracker {
         }
     }
 
+    /// Type annotation of the first assignment to `var_name` before
+    /// `line_num` (`title String = ...`), if it had one
+    pub fn declared_type(&self, var_name: &str, line_num: usize) -> Option<&str> {
+        self.assignments.iter()
+            .find(|a| a.var_name == var_name && a.line_num < line_num)
+            .and_then(|a| a.var_type.as_deref())
+    }
+
     pub fn get_variable(&self, name: &str) -> Option<&Variable> {
         self.variables.get(name)
     }
@@ -468,6 +428,11 @@ pub fn parse_rusts_assignment_ext(line: &str) -> Option<(String, Option<String>,
     // Must check for space-separated `var Type` BEFORE rejecting lines with `[`
     // because the type might contain `[` like `Vec[T]`
     
+    // Colon style `user: Option[User]`: the type may contain `[` as well
+    if let Some((var_name, var_type)) = crate::ident::split_type_annotation(left) {
+        return Some((var_name.to_string(), Some(var_type.to_string()), right.to_string(), is_outer, is_explicit_mut));
+    }
+    
     // Check if left contains space (potential RustS+ style: `var Type`)
     if left.contains(' ') {
         // RustS+ style: var Type (no colon)
@@ -519,20 +484,6 @@ pub fn parse_rusts_assignment_ext(line: &str) -> Option<(String, Option<String>,
         return None;
     }
     
-    if left.contains(':') {
-        let type_parts: Vec<&str> = left.splitn(2, ':').collect();
-        if type_parts.len() == 2 {
-            let var_name = type_parts[0].trim();
-            let var_type = type_parts[1].trim();
-            
-            if !is_valid_identifier(var_name) {
-                return None;
-            }
-            
-            return Some((var_name.to_string(), Some(var_type.to_string()), right.to_string(), is_outer, is_explicit_mut));
-        }
-    }
-    
     if !is_valid_identifier(left) {
         return None;
     }
@@ -624,7 +575,9 @@ fn find_standalone_assignment_eq(s: &str) -> Option<usize> {
             }
             
             // This is a standalone assignment `=`
-            return Some(i);
+            // CRITICAL FIX: Return a BYTE offset - callers slice the &str with it,
+            // and a char index panics on non-ASCII identifiers (`größe = 1`)
+            return Some(char_to_byte_index(s, i));
         }
         
         prev_char = c;
@@ -694,16 +647,7 @@ fn extract_root_var(expr: &str) -> Option<String> {
 }
 
 pub fn is_valid_identifier(s: &str) -> bool {
-    if s.is_empty() {
-        return false;
-    }
-    
-    let first = s.chars().next().unwrap();
-    if !first.is_alphabetic() && first != '_' {
-        return false;
-    }
-    
-    s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    crate::ident::is_valid_identifier(s)
 }
 
 pub fn expand_value(value: &str, explicit_type: Option<&str>) -> String {
@@ -758,9 +702,8 @@ fn transform_closure_params(closure: &str) -> String {
     // Find the closing `|` of the parameter list
     let mut depth = 0;
     let mut close_pipe_pos = None;
-    let chars: Vec<char> = trimmed.chars().collect();
     
-    for (i, &c) in chars.iter().enumerate().skip(1) {
+    for (i, c) in trimmed.char_indices().skip(1) {
         match c {
             '<' | '(' | '[' => depth += 1,
             '>' | ')' | ']' => depth -= 1,
@@ -782,7 +725,7 @@ fn transform_closure_params(closure: &str) -> String {
     let after_params = &trimmed[close_pos..]; // Includes the closing `|`
     
     // Split parameters by comma
-    let params: Vec<&str> = split_closure_params(params_str);
+    let params: Vec<&str> = split_top_level_types(params_str, ',');
     
     // Transform each parameter
     let transformed_params: Vec<String> = params.iter()
@@ -792,31 +735,6 @@ fn transform_closure_params(closure: &str) -> String {
     format!("|{}|{}", transformed_params.join(", "), &after_params[1..])
 }
 
-/// Split closure parameters by comma, respecting nested angle brackets and parens
-fn split_closure_params(params: &str) -> Vec<&str> {
-    let mut result = Vec::new();
-    let mut start = 0;
-    let mut depth = 0;
-    
-    for (i, c) in params.char_indices() {
-        match c {
-            '<' | '(' | '[' => depth += 1,
-            '>' | ')' | ']' => depth -= 1,
-            ',' if depth == 0 => {
-                result.push(&params[start..i]);
-                start = i + 1;
-            }
-            _ => {}
-        }
-    }
-    
-    if start < params.len() {
-        result.push(&params[start..]);
-    }
-    
-    result
-}
-
 /// Transform a single closure parameter: `param TYPE` -> `param: TYPE`
 fn transform_single_closure_param(param: &str) -> String {
     let trimmed = param.trim();
@@ -914,6 +832,149 @@ fn expand_string_concatenation(expr: &str) -> String {
     result_parts.join(" + ")
 }
 
+/// Variables borrowed as `&mut <identifier>` on a line
+pub fn mut_borrow_targets(line: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    let trimmed = line.trim();
+    
+    // Skip empty lines and comments
+    if trimmed.is_empty() || trimmed.starts_with("//") || !trimmed.contains("&mut ") {
+        return targets;
+    }
+    
+    // Find all occurrences of &mut followed by an identifier
+    let mut remaining = trimmed;
+    while let Some(pos) = remaining.find("&mut ") {
+        let after_mut = &remaining[pos + 5..];
+        
+        // Extract the identifier after &mut
+        let ident: String = after_mut
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        
+        if !ident.is_empty() && is_valid_identifier(&ident) {
+            targets.push(ident);
+        }
+        
+        // Move past this occurrence
+        if pos + 5 < remaining.len() {
+            remaining = &remaining[pos + 5..];
+        } else {
+            break;
+        }
+    }
+    targets
+}
+
+/// Variables a line mutates through a mutating method call (`.push(`,
+/// `.insert(`, ...) or a compound assignment (`+=`)
+pub fn mutating_method_targets(line: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    let trimmed = line.trim();
+    
+    // Skip empty lines and comments, and lines with neither a method call
+    // nor an assignment operator
+    if trimmed.is_empty() || trimmed.starts_with("//") || !trimmed.contains(['.', '=']) {
+        return targets;
+    }
+    
+    // List of mutating methods that require &mut self
+    const MUTATING_METHODS: &[&str] = &[
+        // Vec methods
+        ".push(", ".pop()", ".insert(", ".remove(", ".clear()", 
+        ".append(", ".truncate(", ".resize(", ".extend(",
+        ".sort(", ".sort_by(", ".sort_by_key(", ".reverse()",
+        ".drain(", ".retain(", ".dedup(", ".swap(",
+        ".split_off(", ".swap_remove(",
+        // HashMap/HashSet methods
+        ".entry(", ".or_insert(", ".and_modify(",
+        // String methods
+        ".push_str(",
+        // Common mutation patterns
+        ".get_mut(",
+    ];
+    
+    // Compound assignment operators that indicate mutation
+    const COMPOUND_ASSIGNS: &[&str] = &[
+        " += ", " -= ", " *= ", " /= ", " %= ",
+        " &= ", " |= ", " ^= ", " <<= ", " >>= ",
+    ];
+    
+    // Check for mutating methods: var.method(...)
+    for method in MUTATING_METHODS {
+        if let Some(pos) = trimmed.find(method) {
+            // Extract variable name before the method call
+            let before_method = &trimmed[..pos];
+            if let Some(var_name) = extract_var_name_before_dot(before_method) {
+                targets.push(var_name);
+            }
+        }
+    }
+    
+    // Check for compound assignments: var += value
+    for op in COMPOUND_ASSIGNS {
+        if let Some(pos) = trimmed.find(op) {
+            let before_op = trimmed[..pos].trim();
+            // Handle simple variable or field access
+            if let Some(var_name) = extract_root_var(before_op) {
+                targets.push(var_name);
+            }
+        }
+    }
+    
+    // Field assignment mutates its container: `res.status = value`,
+    // including `res.status = match code {` and `= if`
+    if let Some(var_name) = field_assignment_root(trimmed) {
+        targets.push(var_name);
+    }
+    targets
+}
+
+/// Variables a line grows or shrinks with a method only a `Vec` has
+/// (`xs.push(`, `xs.pop()`): the receiver itself, not a field or element
+pub fn resizing_method_targets(line: &str) -> Vec<String> {
+    const RESIZING_METHODS: &[&str] = &[
+        ".push(", ".pop()", ".insert(", ".remove(", ".clear()",
+        ".append(", ".truncate(", ".resize(", ".extend(",
+        ".drain(", ".retain(", ".dedup(", ".split_off(", ".swap_remove(",
+    ];
+    let code = crate::helpers::strip_inline_comment(line);
+    let mut targets = Vec::new();
+    for method in RESIZING_METHODS {
+        for (pos, _) in code.match_indices(method) {
+            let before = &code[..pos];
+            let start = before
+                .char_indices()
+                .rfind(|&(_, c)| !crate::ident::is_ident_continue(c))
+                .map_or(0, |(p, c)| p + c.len_utf8());
+            let name = &before[start..];
+            let is_field = before[..start].ends_with('.');
+            if is_valid_identifier(name) && !is_field && !targets.iter().any(|t| t == name) {
+                targets.push(name.to_string());
+            }
+        }
+    }
+    targets
+}
+
+/// Container of an assigned field: `res` for `res.status = value`
+fn field_assignment_root(trimmed: &str) -> Option<String> {
+    let trimmed = trimmed.strip_prefix("outer ").unwrap_or(trimmed);
+    let chars: Vec<char> = trimmed.chars().collect();
+    let eq = (0..chars.len()).find(|&i| {
+        chars[i] == '='
+            && !matches!(chars.get(i + 1), Some('=') | Some('>'))
+            && !(i > 0 && "=!<>+-*/%&|^".contains(chars[i - 1]))
+    })?;
+    let target: String = chars[..eq].iter().collect();
+    let target = target.trim();
+    if !crate::helpers::is_field_access(target) || target.contains(['(', '"', ' ']) {
+        return None;
+    }
+    extract_root_var(target)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -972,6 +1033,31 @@ mod tests {
             "result should be marked as mutated via .push()");
     }
     
+    #[test]
+    fn test_resizing_method_targets() {
+        assert_eq!(resizing_method_targets("xs.push(4)"), vec!["xs"]);
+        assert_eq!(resizing_method_targets("last = stack.pop()   // ys.push(1)"), vec!["stack"]);
+        assert!(resizing_method_targets("self.items.push(x)").is_empty());
+        assert!(resizing_method_targets("grid[0].push(x)").is_empty());
+        
+        let mut tracker = VariableTracker::new();
+        let resized = ["xs".to_string()].into_iter().collect();
+        tracker.set_function_local_mutations(&Default::default(), &Default::default(), &resized);
+        assert!(tracker.needs_vec("xs", None));
+        assert!(!tracker.needs_vec("ys", None));
+        assert!(tracker.needs_vec("ys", Some("Vec<i32>")));
+        assert!(!tracker.needs_vec("xs", Some("[i32; 3]")));
+    }
+    
+    #[test]
+    fn test_field_assignment_mutates_container() {
+        assert_eq!(mutating_method_targets("res.status = match code {"), vec!["res"]);
+        assert_eq!(mutating_method_targets("outer self.count = if done {"), vec!["self"]);
+        assert!(mutating_method_targets("ok = res.status == 200").is_empty());
+        assert!(mutating_method_targets("if res.code >= 400 {").is_empty());
+        assert!(mutating_method_targets("println(\"a.b = {}\", x)").is_empty());
+    }
+    
     #[test]
     fn test_scan_for_mutating_methods_insert() {
         let mut tracker = VariableTracker::new();
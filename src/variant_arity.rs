@@ -0,0 +1,157 @@
+//! Enum Variant Construction Arity (RSPL027)
+//!
+//! A variant built with the wrong number of values only fails in rustc, as
+//! E0061 on the generated code. Stage 1 checks every `Enum::Variant(..)`
+//! against the declaration the first pass recorded:
+//!
+//! ```text
+//! enum Event {
+//!     Query(u32)
+//!     Move { x i32, y i32 }
+//!     Ping
+//! }
+//!
+//! e = Event::Query(3, 4)      // RSPL027: takes 1 value, 2 given
+//! m = Event::Move(1, 2)       // RSPL027: struct variant, needs named fields
+//! p = Event::Ping()           // RSPL027: unit variant, takes no values
+//! ```
+//!
+//! Only enums declared in the same source are checked, and only calls whose
+//! parentheses close on the same line. Patterns with `..` are skipped.
+
+use crate::enum_def::{EnumRegistry, VariantDecl, VariantKind};
+use crate::first_pass::run_first_pass;
+use crate::helpers::strip_inline_comment;
+use crate::lowering::depth_tracking_lowering::{delimiter_positions, split_top_level_types};
+use crate::variable::VariableTracker;
+
+/// A variant construction that does not match its declaration
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArityMismatch {
+    /// 1-based line of the construction
+    pub line: usize,
+    /// `Enum::Variant(..)` as written
+    pub call: String,
+    /// `Enum::Variant`
+    pub path: String,
+    /// Number of values given
+    pub given: usize,
+    pub decl: VariantDecl,
+}
+
+/// Find every construction of a known variant with the wrong number of values
+pub fn find_arity_mismatches(source: &str) -> Vec<ArityMismatch> {
+    let lines: Vec<&str> = source.lines().collect();
+    let registry = run_first_pass(&lines, &mut VariableTracker::new()).enum_registry;
+
+    let mut mismatches = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let clean = strip_inline_comment(line);
+        for (path, start, open, close) in variant_calls(&clean, &registry) {
+            let (enum_name, variant) = path.split_once("::").unwrap_or_default();
+            let Some(decl) = registry.variant(enum_name, variant) else {
+                continue;
+            };
+            let args: Vec<&str> = split_top_level_types(&clean[open + 1..close], ',')
+                .into_iter()
+                .map(str::trim)
+                .filter(|a| !a.is_empty())
+                .collect();
+            if args.iter().any(|a| a.starts_with("..")) {
+                continue;
+            }
+            let wrong = match decl.kind {
+                VariantKind::Tuple => args.len() != decl.arity,
+                VariantKind::Unit | VariantKind::Struct => true,
+            };
+            if wrong {
+                mismatches.push(ArityMismatch {
+                    line: idx + 1,
+                    call: clean[start..=close].to_string(),
+                    path,
+                    given: args.len(),
+                    decl: decl.clone(),
+                });
+            }
+        }
+    }
+    mismatches
+}
+
+/// `(Enum::Variant, start, open paren, close paren)` for every call of a
+/// registered enum's variant that closes on this line
+fn variant_calls(line: &str, registry: &EnumRegistry) -> Vec<(String, usize, usize, usize)> {
+    let parens = delimiter_positions(line, &['(', ')']);
+    let mut calls = Vec::new();
+    for (i, &(open, c)) in parens.iter().enumerate() {
+        if c != '(' {
+            continue;
+        }
+        let before = &line[..open];
+        let start = before
+            .char_indices()
+            .rfind(|&(_, c)| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .map_or(0, |(p, c)| p + c.len_utf8());
+        let segments: Vec<&str> = before[start..].split("::").collect();
+        if segments.len() < 2 {
+            continue;
+        }
+        let (enum_name, variant) = (segments[segments.len() - 2], segments[segments.len() - 1]);
+        if !registry.is_enum(enum_name) || variant.is_empty() {
+            continue;
+        }
+        let mut depth = 0;
+        let close = parens[i..].iter().find_map(|&(pos, c)| {
+            depth += if c == '(' { 1 } else { -1 };
+            (depth == 0).then_some(pos)
+        });
+        if let Some(close) = close {
+            let start = open - enum_name.len() - variant.len() - 2;
+            calls.push((format!("{}::{}", enum_name, variant), start, open, close));
+        }
+    }
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = "enum Event {\n    Query(u32)\n    Pair(i32, String)\n    Move { x i32, y i32 }\n    Ping\n}\n";
+
+    #[test]
+    fn test_wrong_tuple_arity() {
+        let src = format!("{}\nfn main() {{\n    e = Event::Query(3, 4)\n    p = Event::Pair(1, f(2, 3))\n}}", EVENT);
+        let found = find_arity_mismatches(&src);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line, 9);
+        assert_eq!(found[0].call, "Event::Query(3, 4)");
+        assert_eq!(found[0].given, 2);
+        assert_eq!(found[0].decl.arity, 1);
+        assert_eq!(found[0].decl.line, 2);
+    }
+
+    #[test]
+    fn test_unit_and_struct_variants_called() {
+        let src = format!("{}\nfn main() {{\n    a = Event::Ping()\n    b = Event::Move(1, 2)\n    c = Event::Move {{ x = 1, y = 2 }}\n}}", EVENT);
+        let found: Vec<_> = find_arity_mismatches(&src).into_iter().map(|m| (m.path, m.decl.kind)).collect();
+        assert_eq!(found, vec![
+            ("Event::Ping".to_string(), VariantKind::Unit),
+            ("Event::Move".to_string(), VariantKind::Struct),
+        ]);
+    }
+
+    #[test]
+    fn test_skipped_constructions() {
+        let src = "enum Kind { A(u8), B }\n\nfn main() {\n    s = \"Kind::A(1, 2)\"\n    match k {\n        Kind::A(..) { 0 }\n        Other::A(1, 2) { 1 }\n    }\n    k = Kind::A(\n        1, 2)\n}";
+        assert!(find_arity_mismatches(src).is_empty());
+    }
+
+    #[test]
+    fn test_inline_enum_declaration() {
+        let src = "enum Kind { A(u8), B }\n\nfn main() {\n    k = Kind::A()\n}";
+        let found = find_arity_mismatches(src);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].given, found[0].decl.arity, found[0].decl.line), (0, 1, 1));
+    }
+}
@@ -0,0 +1,308 @@
+//! Verbatim Rust Blocks for RustS+
+//!
+//! Escape hatch for constructs the transpiler does not support:
+//!
+//! ```text
+//! fn now_ms() effects(io) u128 {
+//!     rust effects(io) {
+//!         let t = std::time::SystemTime::now();
+//!         t.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
+//!     }
+//! }
+//! ```
+//!
+//! The body of a `rust { ... }` block is emitted byte-for-byte: no lowering,
+//! no semicolon insertion, no effect detection. The optional `effects(...)`
+//! annotation is the only thing the effect checker sees - those effects count
+//! as performed by the enclosing function.
+//!
+//! ## How it works
+//!
+//! Before lowering (and before Stage 1) each block is MASKED: the header
+//! becomes a marker comment and the body lines become empty lines, so line
+//! numbers are preserved. After lowering the marker is replaced by the
+//! original body.
+
+use crate::lowering::depth_tracking_lowering::count_braces_outside_strings;
+
+/// Prefix of the marker comment that replaces a block header
+pub const VERBATIM_MARKER: &str = "// @rustsp-verbatim ";
+
+/// A `rust { ... }` block found in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerbatimBlock {
+    /// 1-based source line of the `rust` header
+    pub line: usize,
+    /// Effects declared on the block header (`rust effects(io) {`)
+    pub effects: Vec<String>,
+    /// Block contents exactly as written (without the enclosing braces)
+    pub body: String,
+    /// Number of source lines after the header covered by the block
+    pub span: usize,
+    /// Whether the body begins on the header line (`rust { body...`)
+    pub starts_on_header: bool,
+}
+
+/// Parse a block header: `rust {` or `rust effects(io, alloc) {`.
+/// Returns the declared effects and whatever follows the opening brace.
+fn parse_verbatim_header(trimmed: &str) -> Option<(Vec<String>, &str)> {
+    let rest = trimmed.strip_prefix("rust")?;
+    if !rest.starts_with([' ', '{']) {
+        return None;
+    }
+    let rest = rest.trim_start();
+
+    let (effects, rest) = if let Some(after) = rest.strip_prefix("effects(") {
+        let close = after.find(')')?;
+        let effects = after[..close]
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        (effects, after[close + 1..].trim_start())
+    } else {
+        (Vec::new(), rest)
+    };
+
+    let body_start = rest.strip_prefix('{')?;
+    Some((effects, body_start))
+}
+
+/// Build the marker comment for block `index`
+pub(crate) fn marker_line(leading_ws: &str, index: usize, effects: &[String]) -> String {
+    if effects.is_empty() {
+        format!("{}{}{}", leading_ws, VERBATIM_MARKER, index)
+    } else {
+        format!("{}{}{} effects({})", leading_ws, VERBATIM_MARKER, index, effects.join(", "))
+    }
+}
+
+/// Effects declared by a marker line, or None if `trimmed` is not a marker
+pub fn marker_effects(trimmed: &str) -> Option<Vec<String>> {
+    let rest = trimmed.strip_prefix(VERBATIM_MARKER)?;
+    match rest.find("effects(") {
+        Some(pos) => {
+            let inner = rest[pos + 8..].trim_end().trim_end_matches(')');
+            Some(inner.split(',').map(|e| e.trim().to_string()).filter(|e| !e.is_empty()).collect())
+        }
+        None => Some(Vec::new()),
+    }
+}
+
+/// Replace every `rust { ... }` block with a marker comment + empty lines.
+///
+/// Line count is preserved so diagnostics keep pointing at the original source.
+pub fn mask_verbatim_blocks(source: &str) -> (String, Vec<VerbatimBlock>) {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    let mut blocks: Vec<VerbatimBlock> = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        let Some((effects, after_brace)) = parse_verbatim_header(trimmed) else {
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        };
+        let leading_ws: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+
+        // Single-line block: `rust { body }`
+        let (opens, closes) = count_braces_outside_strings(after_brace);
+        if closes > opens {
+            if let Some(end) = after_brace.rfind('}') {
+                let body = after_brace[..end].trim().to_string();
+                out.push(marker_line(&leading_ws, blocks.len(), &effects));
+                blocks.push(VerbatimBlock { line: i + 1, effects, body, span: 0, starts_on_header: true });
+                i += 1;
+                continue;
+            }
+        }
+
+        // Multi-line block: collect raw lines until the matching `}`
+        let mut depth: usize = 1 + opens - closes;
+        let mut body_lines: Vec<&str> = Vec::new();
+        if !after_brace.trim().is_empty() {
+            body_lines.push(after_brace);
+        }
+        let mut j = i + 1;
+        let mut closed = false;
+        while j < lines.len() {
+            let (o, c) = count_braces_outside_strings(lines[j]);
+            if depth + o <= c {
+                // Closing line - keep anything before the final `}`
+                let raw = lines[j];
+                if let Some(end) = raw.rfind('}') {
+                    if !raw[..end].trim().is_empty() {
+                        body_lines.push(&raw[..end]);
+                    }
+                }
+                closed = true;
+                break;
+            }
+            depth = depth + o - c;
+            body_lines.push(lines[j]);
+            j += 1;
+        }
+
+        if !closed {
+            // Unterminated block - leave it alone so the brace pre-check reports it
+            out.push(line.to_string());
+            i += 1;
+            continue;
+        }
+
+        out.push(marker_line(&leading_ws, blocks.len(), &effects));
+        for _ in i + 1..=j {
+            out.push(String::new());
+        }
+        blocks.push(VerbatimBlock {
+            line: i + 1,
+            effects,
+            body: body_lines.join("\n"),
+            span: j - i,
+            starts_on_header: !after_brace.trim().is_empty(),
+        });
+        i = j + 1;
+    }
+
+    (out.join("\n"), blocks)
+}
+
+/// Replace marker comments in lowered output with the original block bodies
+pub fn restore_verbatim_blocks(output: &str, blocks: &[VerbatimBlock]) -> String {
+    if blocks.is_empty() {
+        return output.to_string();
+    }
+    let mut result: Vec<String> = Vec::new();
+    // Blank lines left by masking the block body, still to be dropped
+    let mut masked_blanks = 0;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if masked_blanks > 0 && trimmed.is_empty() {
+            masked_blanks -= 1;
+            continue;
+        }
+        masked_blanks = 0;
+        let index = trimmed
+            .strip_prefix(VERBATIM_MARKER)
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|n| n.parse::<usize>().ok());
+        match index.and_then(|n| blocks.get(n)) {
+            Some(block) => {
+                if !block.body.is_empty() {
+                    result.push(block.body.clone());
+                }
+                masked_blanks = block.span;
+            }
+            None => result.push(line.to_string()),
+        }
+    }
+    let mut joined = result.join("\n");
+    if output.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+/// Line-preserving variant of [`restore_verbatim_blocks`]: body lines are put
+/// back on the (empty) output lines that correspond to their source lines.
+pub fn restore_verbatim_blocks_aligned(output: &str, blocks: &[VerbatimBlock]) -> String {
+    if blocks.is_empty() {
+        return output.to_string();
+    }
+    let mut lines: Vec<String> = output.lines().map(String::from).collect();
+    for idx in 0..lines.len() {
+        let index = lines[idx]
+            .trim()
+            .strip_prefix(VERBATIM_MARKER)
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|n| n.parse::<usize>().ok());
+        let Some(block) = index.and_then(|n| blocks.get(n)) else {
+            continue;
+        };
+        lines[idx] = String::new();
+        let first = if block.starts_on_header { idx } else { idx + 1 };
+        for (offset, body_line) in block.body.lines().enumerate() {
+            let target = (first + offset).min(idx + block.span);
+            if target < lines.len() && lines[target].is_empty() {
+                lines[target] = body_line.to_string();
+            } else if target < lines.len() {
+                lines[target].push(' ');
+                lines[target].push_str(body_line.trim());
+            }
+        }
+    }
+    let mut joined = lines.join("\n");
+    if output.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verbatim_header() {
+        assert_eq!(parse_verbatim_header("rust {"), Some((vec![], "")));
+        assert_eq!(
+            parse_verbatim_header("rust effects(io, alloc) {"),
+            Some((vec!["io".to_string(), "alloc".to_string()], ""))
+        );
+        assert_eq!(parse_verbatim_header("rust{ x }"), Some((vec![], " x }")));
+        assert_eq!(parse_verbatim_header("rusty {"), None);
+        assert_eq!(parse_verbatim_header("rust = 1"), None);
+    }
+
+    #[test]
+    fn test_mask_preserves_lines() {
+        let src = "fn f() {\n    rust effects(io) {\n        let v: Vec<&'static str> = vec![\"}\"];\n        if true { println!(\"{:?}\", v); }\n    }\n    x = 1\n}";
+        let (masked, blocks) = mask_verbatim_blocks(src);
+        assert_eq!(masked.lines().count(), src.lines().count());
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].line, 2);
+        assert_eq!(blocks[0].effects, vec!["io".to_string()]);
+        assert!(blocks[0].body.starts_with("        let v: Vec<&'static str>"));
+        assert!(blocks[0].body.ends_with("println!(\"{:?}\", v); }"));
+        assert!(masked.contains("    // @rustsp-verbatim 0 effects(io)"));
+        assert!(masked.contains("    x = 1"));
+        assert!(!masked.contains("println!"));
+    }
+
+    #[test]
+    fn test_single_line_block() {
+        let (masked, blocks) = mask_verbatim_blocks("rust { static N: u8 = 1; }");
+        assert_eq!(masked, "// @rustsp-verbatim 0");
+        assert_eq!(blocks[0].body, "static N: u8 = 1;");
+    }
+
+    #[test]
+    fn test_restore_round_trip() {
+        let src = "rust {\n    impl<'a> Foo<'a> { }\n}";
+        let (masked, blocks) = mask_verbatim_blocks(src);
+        assert_eq!(restore_verbatim_blocks(&masked, &blocks).trim_end(), "    impl<'a> Foo<'a> { }");
+    }
+
+    #[test]
+    fn test_restore_aligned_keeps_line_numbers() {
+        let src = "fn f() u8 {\n    rust {\n        let a = 1u8;\n        a + 1\n    }\n}";
+        let (masked, blocks) = mask_verbatim_blocks(src);
+        let restored = restore_verbatim_blocks_aligned(&masked, &blocks);
+        let lines: Vec<&str> = restored.lines().collect();
+        assert_eq!(lines.len(), src.lines().count());
+        assert_eq!(lines[2], "        let a = 1u8;");
+        assert_eq!(lines[3], "        a + 1");
+        assert_eq!(lines[4], "");
+    }
+
+    #[test]
+    fn test_marker_effects() {
+        assert_eq!(marker_effects("// @rustsp-verbatim 3 effects(io, alloc)"),
+            Some(vec!["io".to_string(), "alloc".to_string()]));
+        assert_eq!(marker_effects("// @rustsp-verbatim 0"), Some(vec![]));
+        assert_eq!(marker_effects("// ordinary comment"), None);
+    }
+}
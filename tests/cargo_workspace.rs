@@ -0,0 +1,85 @@
+//! `--emit-cargo-workspace` writes one crate per module file, and Cargo
+//! builds the result into the same program a single `.rs` would be.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn scratch_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustsp-workspace-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (path, source) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_module_files_become_crates() {
+    let dir = scratch_dir("modules", &[
+        ("main.rss", "mod util\n\nfn main() effects(io) {\n    util::log(util::shapes::area(2, 3))\n}\n"),
+        ("util.rss", "pub mod shapes\n\npub fn log(x i32) effects(io) {\n    println!(\"area {}\", x)\n}\n"),
+        ("util/shapes.rss", "pub fn area(w i32, h i32) i32 {\n    w * h\n}\n"),
+    ]);
+    let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .current_dir(&dir)
+        .args(["main.rss", "--emit-cargo-workspace", "gen", "--quiet"])
+        .status()
+        .expect("rustsp runs");
+    assert_eq!(status.code(), Some(0));
+
+    let gen = dir.join("gen");
+    assert!(fs::read_to_string(gen.join("Cargo.toml")).unwrap().contains("\"main_util_shapes\","));
+    assert_eq!(fs::read_to_string(gen.join("main/src/main.rs")).unwrap().lines().next(), Some("use main_util as util;"));
+    assert!(fs::read_to_string(gen.join("main_util/src/lib.rs")).unwrap().starts_with("pub use main_util_shapes as shapes;\n"));
+
+    let run = Command::new(env!("CARGO"))
+        .current_dir(&gen)
+        .args(["run", "--offline", "--quiet"])
+        .output()
+        .expect("cargo runs");
+    assert!(run.status.success(), "{}", String::from_utf8_lossy(&run.stderr));
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "area 6\n");
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_error_enums_need_thiserror() {
+    let dir = scratch_dir("thiserror", &[
+        ("main.rss", "mod util\n\nfn main() effects(io) {\n    println!(\"{:?}\", util::count(\"3\"))\n}\n"),
+        ("util.rss", "pub fn count(s &str) !usize {\n    n = s.parse[usize]()?\n    Ok(n)\n}\n"),
+    ]);
+    let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .current_dir(&dir)
+        .args(["main.rss", "--emit-cargo-workspace", "gen", "--thiserror", "--quiet"])
+        .status()
+        .expect("rustsp runs");
+    assert_eq!(status.code(), Some(0));
+
+    let gen = dir.join("gen");
+    let util = fs::read_to_string(gen.join("main_util/src/lib.rs")).unwrap();
+    assert!(util.starts_with("pub fn count(s: &str) -> Result<usize, CountError> {\n"), "{}", util);
+    assert!(util.contains("#[derive(Debug, thiserror::Error)]\npub enum CountError {\n"), "{}", util);
+    assert!(fs::read_to_string(gen.join("main_util/Cargo.toml")).unwrap().ends_with("[dependencies]\nthiserror = \"2\"\n"));
+    assert!(!fs::read_to_string(gen.join("main/Cargo.toml")).unwrap().contains("thiserror"));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_workspace_rejects_other_outputs() {
+    let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .args(["main.rss", "--emit-cargo-workspace", "gen", "--emit-rs"])
+        .status()
+        .expect("rustsp runs");
+    assert_eq!(status.code(), Some(1));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .args(["main.rss", "--thiserror", "--emit-rs"])
+        .status()
+        .expect("rustsp runs");
+    assert_eq!(status.code(), Some(1));
+}
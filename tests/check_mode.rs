@@ -0,0 +1,53 @@
+//! `--check` runs every stage before rustc and nothing after: no file is
+//! written next to the input and rustc is never looked up, so it works
+//! with an empty `PATH`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SOURCE: &str = r#"fn log(x i32) effects(io) {
+    println!("{}", x)
+}
+
+fn main() effects(io) {
+    log(1)
+}
+"#;
+
+fn scratch_dir(name: &str, source: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustsp-check-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.rss"), source).unwrap();
+    dir
+}
+
+/// Exit code of `rustsp main.rss --check` in `dir`, and the files there after
+fn check(dir: &Path) -> (Option<i32>, Vec<String>) {
+    let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .current_dir(dir)
+        .env("PATH", "")
+        .args(["main.rss", "--check", "--quiet"])
+        .status()
+        .expect("rustsp runs");
+    let mut files: Vec<String> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    (status.code(), files)
+}
+
+#[test]
+fn test_check_mode_runs_no_rustc_and_writes_nothing() {
+    let ok = scratch_dir("ok", SOURCE);
+    assert_eq!(check(&ok), (Some(0), vec!["main.rss".to_string()]));
+
+    // Exit code 3: `run` does not declare the `io` of `log`
+    let undeclared = scratch_dir("effect", &SOURCE.replace("fn main() effects(io) {", "fn run() {"));
+    assert_eq!(check(&undeclared), (Some(3), vec!["main.rss".to_string()]));
+
+    for dir in [&ok, &undeclared] {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,42 @@
+//! `--error-format=json` prints each diagnostic as one JSON object per
+//! line on stderr; the run summary is the only other line.
+
+use std::fs;
+use std::process::Command;
+
+const SOURCE: &str = r#"fn log(x i32) {
+    println!("{}", x)
+}
+
+fn main() effects(io) {
+    log(1)
+}
+"#;
+
+#[test]
+fn test_json_error_format() {
+    let dir = std::env::temp_dir().join(format!("rustsp-error-format-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.rss"), SOURCE).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+        .current_dir(&dir)
+        .env("PATH", "")
+        .args(["main.rss", "--check", "--error-format=json"])
+        .output()
+        .expect("rustsp runs");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().filter(|line| !line.is_empty()).collect();
+
+    assert_eq!(output.status.code(), Some(3), "{}", stderr);
+    assert_eq!(lines.last(), Some(&"rustsp: effect functions=2 violations=1"), "{}", stderr);
+    let records = &lines[..lines.len() - 1];
+    assert_eq!(records.len(), 1, "{}", stderr);
+    assert!(records[0].starts_with("{\"level\":\"error\",\"code\":\"RSPL300\",\"category\":\"effect\","), "{}", records[0]);
+    assert!(records[0].contains("\"file\":\"main.rss\",\"line\":1,\"column\":4,"), "{}", records[0]);
+    assert!(records[0].contains("\"suggestion\":\"add `effects(io)` to the function signature"), "{}", records[0]);
+    assert!(records[0].ends_with("\"labels\":[{\"file\":\"main.rss\",\"line\":2,\"column\":5,\"message\":\"`io` performed here\"}]}"), "{}", records[0]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
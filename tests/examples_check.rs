@@ -0,0 +1,105 @@
+//! Every `examples/*.rss` through the whole pipeline: lowered, then checked
+//! by rustc (`--emit=metadata`, no code generation). Needs rustc on PATH, so
+//! it only runs with `cargo test --features examples-check`;
+//! `RUSTSP_EXAMPLES_DIR` points it at another directory.
+//!
+//! A failure names the first rustc error of each example with the
+//! generated line and the `.rss` line it came from:
+//!
+//! ```text
+//! examples/wallet.rss: error[E0425]: cannot find value `total` in this scope
+//!   generated wallet.rs:14   let x = total + 1;
+//!   source    wallet.rss:11  x = total + 1
+//! ```
+
+#![cfg(feature = "examples-check")]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustsp::output_target::rustc_command;
+use rustsp::source_map::parse_rustc_errors;
+use rustsp::Compiler;
+
+fn examples_dir() -> PathBuf {
+    std::env::var_os("RUSTSP_EXAMPLES_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("examples"))
+}
+
+/// rustc's stderr for `rust_code`, or `None` when it type-checks
+fn rustc_check(rust_code: &str, dir: &Path, stem: &str) -> Option<String> {
+    let source = dir.join(format!("{}.rs", stem));
+    fs::write(&source, rust_code).expect("write generated Rust");
+    let flags = ["--emit=metadata".to_string(), "--crate-type=bin".to_string()];
+    let output = rustc_command(&source, &dir.join(format!("lib{}.rmeta", stem)), &flags)
+        .current_dir(dir)
+        .output()
+        .expect("run rustc");
+    (!output.status.success()).then(|| String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+/// The first rustc error, located in the generated code and in the source.
+/// The source line comes from checking the `preserve_lines` lowering, whose
+/// line N is source line N.
+fn describe_failure(name: &str, source: &str, rust_code: &str, stderr: &str, dir: &Path) -> String {
+    let Some(error) = parse_rustc_errors(stderr).into_iter().next() else {
+        return format!("{}: rustc failed\n{}", name, stderr);
+    };
+    let code = error.error_code.map(|c| format!("[{}]", c)).unwrap_or_default();
+    let mut report = format!("{}: error{}: {}\n", name, code, error.message);
+    let stem = Path::new(name).file_stem().unwrap().to_string_lossy().into_owned();
+
+    let generated = rust_code.lines().nth(error.line.saturating_sub(1)).unwrap_or("").trim();
+    report.push_str(&format!("  generated {}.rs:{:<4} {}\n", stem, error.line, generated));
+
+    let aligned = Compiler::builder().preserve_lines(true).build().lower(source).ok();
+    let source_line = aligned
+        .and_then(|aligned| rustc_check(&aligned, dir, &format!("{}_aligned", stem)))
+        .and_then(|stderr| parse_rustc_errors(&stderr).into_iter().next())
+        .map(|e| e.line);
+    match source_line {
+        Some(line) => {
+            let text = source.lines().nth(line.saturating_sub(1)).unwrap_or("").trim();
+            report.push_str(&format!("  source    {}.rss:{:<3} {}\n", stem, line, text));
+        }
+        None => report.push_str("  source    (line not found: the line-preserving lowering checks cleanly)\n"),
+    }
+    report
+}
+
+#[test]
+fn every_example_passes_rustc() {
+    let dir = examples_dir();
+    let mut examples: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", dir.display(), e))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rss"))
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty(), "no .rss files in {}", dir.display());
+
+    let work = std::env::temp_dir().join(format!("rustsp-examples-{}", std::process::id()));
+    fs::create_dir_all(&work).unwrap();
+
+    let mut failures = Vec::new();
+    for path in &examples {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let source = fs::read_to_string(path).unwrap();
+        let rust_code = match Compiler::builder().build().lower(&source) {
+            Ok(rust_code) => rust_code,
+            Err(errors) => {
+                let first = &errors[0];
+                failures.push(format!("{}: lowering failed at line {}: {}\n", name, first.location.line, first.title));
+                continue;
+            }
+        };
+        if let Some(stderr) = rustc_check(&rust_code, &work, &stem) {
+            failures.push(describe_failure(&name, &source, &rust_code, &stderr, &work));
+        }
+    }
+
+    let _ = fs::remove_dir_all(&work);
+    assert!(failures.is_empty(), "{} of {} examples failed:\n\n{}", failures.len(), examples.len(), failures.join("\n"));
+}
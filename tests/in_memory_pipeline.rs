@@ -0,0 +1,66 @@
+//! The full pipeline (lowering, rustc, running the binary) through
+//! `CompileInMemory`: no file in the working directory is read or written,
+//! so several compilations can run at once.
+
+use std::thread;
+
+use rustsp::{CompileInMemory, Compiler, OutputError};
+
+fn program(n: i64) -> String {
+    format!(
+        "fn square(x i64) i64 {{\n    x * x\n}}\n\nfn main() effects(io) {{\n    y = square({})\n    println(\"{{}}\", y)\n}}\n",
+        n
+    )
+}
+
+#[test]
+fn parallel_compilations_do_not_interfere() {
+    let handles: Vec<_> = (1..=4i64).map(|n| {
+        thread::spawn(move || {
+            let compiler = Compiler::builder().build();
+            let built = compiler.emit(&program(n), &mut CompileInMemory::new()).expect("compile");
+            let output = built.run().expect("run");
+            let dir = built.dir().to_path_buf();
+            drop(built);
+            assert!(!dir.exists(), "temporary directory left behind: {}", dir.display());
+            (n, String::from_utf8(output.stdout).unwrap())
+        })
+    }).collect();
+
+    for handle in handles {
+        let (n, stdout) = handle.join().unwrap();
+        assert_eq!(stdout.trim(), (n * n).to_string());
+    }
+    assert!(std::fs::read_dir(".").unwrap().all(|e| !e.unwrap().file_name().to_string_lossy().starts_with("main")),
+        "output written to the working directory");
+}
+
+#[test]
+fn rustc_errors_are_returned() {
+    let source = "fn main() effects(io) {\n    x i32 = \"text\"\n    println(\"{}\", x)\n}\n";
+    match Compiler::builder().build().emit(source, &mut CompileInMemory::new()) {
+        Err(OutputError::Rustc { stderr }) => assert!(stderr.contains("mismatched types"), "{}", stderr),
+        Err(other) => panic!("expected a rustc error, got {}", other),
+        Ok(_) => panic!("ill-typed program compiled"),
+    }
+}
+
+#[test]
+fn collection_literals_run() {
+    let source = "use std::collections::HashMap\n\nfn main() effects(io) {\n    xs = [1, 2]\n    xs.push(3)\n    ages = {\n        \"ann\": 31,\n        \"bob\": 42,\n    }\n    nested = { 1: { 'y': [4, 5] } }\n    println(\"{:?} {}\", xs, ages.len())\n    println(\"{:?}\", nested[&1][&'y'])\n}\n";
+    let built = Compiler::builder().build().emit(source, &mut CompileInMemory::new()).expect("compile");
+    let stdout = String::from_utf8(built.run().expect("run").stdout).unwrap();
+    assert_eq!(stdout, "[1, 2, 3] 2\n[4, 5]\n");
+}
+
+#[test]
+fn map_import_is_hoisted() {
+    let source = "fn lookup(key &str) i32 {\n    ages = { \"ann\": 31 }\n    ages[key]\n}\n\nfn main() effects(io) {\n    counts = {\n        'a': 1,\n    }\n    println(\"{} {}\", lookup(\"ann\"), counts[&'a'])\n}\n";
+    let compiler = Compiler::builder().build();
+    let rust = compiler.lower(source).expect("lower");
+    assert!(rust.starts_with("use std::collections::HashMap;\n"), "Import not hoisted: {}", rust);
+    assert_eq!(rust.matches("use std::collections::HashMap;").count(), 1, "{}", rust);
+    let built = compiler.emit(source, &mut CompileInMemory::new()).expect("compile");
+    let stdout = String::from_utf8(built.run().expect("run").stdout).unwrap();
+    assert_eq!(stdout, "31 1\n");
+}
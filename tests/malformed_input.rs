@@ -0,0 +1,115 @@
+//! Malformed input must end in diagnostics, never in a panic or a stall:
+//! pathological sources hit the input limits (`RSPL029`), and random
+//! token soup goes through Stage 1 and lowering without panicking.
+
+use std::panic;
+use std::time::{Duration, Instant};
+
+use rustsp::anti_fail_logic::{analyze_functions, check_logic};
+use rustsp::error_msg::{ErrorCode, RsplError};
+use rustsp::input_limits::InputLimits;
+use rustsp::{lower_rusts, Compiler, LoweringOptions};
+
+/// Fragments random sources are built from: keywords, unbalanced
+/// delimiters, string and char starts, and multi-byte characters
+const PIECES: &[&str] = &[
+    "fn ", "f", "(", ")", "{", "}", "[", "]", "\"", "'", "x", " = ", "mut ", "match ", " => ",
+    "\n", "    ", "struct ", "S", "enum ", "E", "::", ",", "i32", " ", "if ", "else ", "for ",
+    "in ", "0..", "1", "//", "/*", "*/", "rust ", "effects(", "io", "test ", "impl ", "<", ">",
+    "->", "é", "日本", "🦀", "let ", ";", "#[", "outer ", "spawn ", "join ", "return ", "!",
+    "&", ".", "println(", "vec![", "Some(", "|", "\\", "r#\"", "?", "when ", "where ", "pub ",
+    "x i32", "'a'", "\"é\"", "0x1F", "\r\n", "fn main() {\n", "}\n",
+];
+
+/// Sources that panicked before the fixes that came with the limits
+const REGRESSIONS: &[&str] = &[
+    "enum E { A(i32) }\nfn main() {\n    x = 🦀E::A(1)\n}\n",
+    "fn main() {\n    🦀.push(1)\n    a🦀.b.push(1)\n}\n",
+    "fn )effects(",
+    "fn f() effects(é)\n",
+    "fn f(s é) effects(io) {\n}\n",
+    "x = \"é\" {\n",
+    "enum E } {\n",
+    "(é, b) = (1,\n    2)\n",
+    "fn main() {\n    x = a::[🦀]()\n    f = |é a| 1\n}\n",
+];
+
+/// Run every stage that reads .rss source; `false` if one panicked (the
+/// lowering reports its panics as RSPL030)
+fn survives(source: &str) -> bool {
+    let source = source.to_string();
+    panic::catch_unwind(move || {
+        let _ = check_logic(&source, "fuzz.rss");
+        let _ = analyze_functions(&source, "fuzz.rss");
+        let default = lower_rusts(&source, &LoweringOptions::default());
+        let tuned = lower_rusts(&source, &LoweringOptions {
+            preserve_lines: true,
+            opt_level: 2,
+            effect_badges: true,
+            ..LoweringOptions::default()
+        });
+        [default, tuned].iter().all(|result| !lowering_panicked(result))
+    })
+    .unwrap_or(false)
+}
+
+fn lowering_panicked(result: &Result<String, Vec<RsplError>>) -> bool {
+    result.as_ref().err().into_iter().flatten()
+        .any(|e| e.explanation.as_deref().is_some_and(|note| note.contains("the lowering panicked")))
+}
+
+fn only_limit_error(result: Result<impl Sized, Vec<RsplError>>) {
+    let errors = result.err().expect("input within limits");
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].code, ErrorCode::RSPL029);
+}
+
+#[test]
+fn random_sources_never_panic() {
+    panic::set_hook(Box::new(|_| {}));
+    let mut failures = Vec::new();
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    for case in 0..1500 {
+        let mut source = String::new();
+        for _ in 0..1 + case % 120 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            source.push_str(PIECES[(state % PIECES.len() as u64) as usize]);
+        }
+        if !survives(&source) {
+            failures.push(source);
+        }
+    }
+    failures.extend(REGRESSIONS.iter().filter(|s| !survives(s)).map(|s| s.to_string()));
+    let _ = panic::take_hook();
+    assert!(failures.is_empty(), "{} sources panicked, e.g. {:?}", failures.len(), failures[0]);
+}
+
+#[test]
+fn pathological_sources_hit_a_limit() {
+    let sources = [
+        format!("fn main() {{\n{}", "{\n".repeat(10_000)),
+        format!("fn main() {{\n    x = {}1\n}}\n", "[".repeat(5_000)),
+        format!("fn main() {{\n    x = {}\n}}\n", "vec![".repeat(3_000)),
+        format!("fn main() {{\n    x = \"{}\"\n}}\n", "a".repeat(2_000_000)),
+        "x = 1\n".repeat(3_000_000),
+    ];
+    for source in &sources {
+        let start = Instant::now();
+        only_limit_error(check_logic(source, "deep.rss"));
+        only_limit_error(lower_rusts(source, &LoweringOptions::default()));
+        assert!(start.elapsed() < Duration::from_secs(10), "took {:?}", start.elapsed());
+    }
+}
+
+#[test]
+fn limits_can_be_raised_and_lowered() {
+    let source = format!("fn main() {{\n    x = {}1{}\n}}\n", "(".repeat(300), ")".repeat(300));
+    only_limit_error(Compiler::builder().build().lower(&source));
+    let raised = InputLimits { max_nesting_depth: 400, ..InputLimits::default() };
+    assert!(Compiler::builder().limits(raised).build().lower(&source).is_ok());
+
+    let strict = InputLimits { max_line_length: 20, ..InputLimits::default() };
+    only_limit_error(Compiler::builder().limits(strict).build().lower("fn main() {\n    s = \"twenty-one bytes\"\n}\n"));
+}
@@ -0,0 +1,39 @@
+//! Lowering at an `--opt-level` is compared with the checked-in Rust next to
+//! each `.rss` in `tests/snapshots`. After an intended change to the output,
+//! regenerate the snapshots with `UPDATE_SNAPSHOTS=1 cargo test`.
+
+use std::fs;
+use std::path::Path;
+
+use rustsp::Compiler;
+
+fn check_snapshot(name: &str, opt_level: u8) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let source = fs::read_to_string(dir.join(format!("{}.rss", name))).unwrap();
+    let lowered = Compiler::builder().opt_level(opt_level).build().lower(&source).expect("snapshot lowers");
+    let expected_path = dir.join(format!("{}.rs", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&expected_path, &lowered).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&expected_path).unwrap();
+    assert_eq!(lowered, expected, "{}.rss lowered differently from {}.rs", name, name);
+}
+
+#[test]
+fn test_inline_helpers_snapshot() {
+    check_snapshot("inline_helpers", 1);
+}
+
+#[test]
+fn test_string_append_snapshot() {
+    check_snapshot("string_append", 2);
+}
+
+#[test]
+fn test_opt_level_0_keeps_calls() {
+    let source = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/inline_helpers.rss")).unwrap();
+    let lowered = Compiler::builder().build().lower(&source).unwrap();
+    assert!(lowered.contains("let area = square(side);"), "{}", lowered);
+    assert!(!lowered.contains("allow(dead_code)"));
+}
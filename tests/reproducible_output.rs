@@ -0,0 +1,85 @@
+//! Generated files must be byte-for-byte identical between machines: the
+//! same input is lowered twice, from different working directories, through
+//! a relative and an absolute path, under different environments.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SOURCE: &str = r#"struct Account {
+    owner String
+    balance i64
+}
+
+fn deposit(acc Account, amount i64) Account {
+    Account {
+        owner = acc.owner
+        balance = acc.balance + amount
+    }
+}
+
+fn main() effects(io) {
+    acc = Account { owner = String::from("ana"), balance = 10 }
+    acc = deposit(acc, 5)
+    println("{} {}", acc.owner, acc.balance)
+}
+"#;
+
+struct Run {
+    rs: Vec<u8>,
+    ir: Vec<u8>,
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustsp-repro-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/wallet.rss"), SOURCE).unwrap();
+    dir
+}
+
+/// Lower `src/wallet.rss` in `dir` to .rs and to IR
+fn lower(dir: &Path, input: &Path, env: &[(&str, &str)]) -> Run {
+    let run = |mode: &str, out: &str, extra: &[&str]| {
+        let status = Command::new(env!("CARGO_BIN_EXE_rustsp"))
+            .current_dir(dir)
+            .envs(env.iter().copied())
+            .arg(input)
+            .args([mode, "-o", out, "--quiet", "--license", "MIT OR Apache-2.0"])
+            .args(["--attribution", "Generated by rustsp"])
+            .args(extra)
+            .status()
+            .expect("rustsp runs");
+        assert!(status.success(), "rustsp {} failed in {}", mode, dir.display());
+        fs::read(dir.join(out)).unwrap()
+    };
+    Run {
+        rs: run("--emit-rs", "wallet.rs", &[]),
+        ir: run("--emit-ir", "wallet.ir.json", &["--reproducible"]),
+    }
+}
+
+#[test]
+fn test_output_is_reproducible_across_cwd_and_env() {
+    let first_dir = scratch_dir("a");
+    let second_dir = scratch_dir("b");
+
+    let first = lower(&first_dir, Path::new("src/wallet.rss"), &[
+        ("HOME", "/home/alice"), ("TZ", "UTC"), ("LANG", "C"), ("SOURCE_DATE_EPOCH", "0"),
+    ]);
+    let second = lower(&second_dir, &second_dir.join("src/wallet.rss"), &[
+        ("HOME", "/home/bob"), ("TZ", "Asia/Jakarta"), ("LANG", "id_ID.UTF-8"), ("SOURCE_DATE_EPOCH", "1700000000"),
+    ]);
+
+    assert_eq!(first.rs, second.rs, "Lowered Rust differs between runs");
+    assert_eq!(first.ir, second.ir, "IR differs between runs");
+
+    let rs = String::from_utf8(first.rs).unwrap();
+    assert!(rs.starts_with("// SPDX-License-Identifier: MIT OR Apache-2.0\n// Generated by rustsp\n"), "Missing header: {}", rs);
+    let ir = String::from_utf8(first.ir).unwrap();
+    assert!(ir.contains("\"source\": \"wallet.rss\""), "IR source not normalized: {}", ir);
+    for dir in [&first_dir, &second_dir] {
+        assert!(!ir.contains(&*dir.to_string_lossy()) && !rs.contains(&*dir.to_string_lossy()));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
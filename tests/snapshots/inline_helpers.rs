@@ -0,0 +1,28 @@
+#[allow(dead_code)] fn square(x: i64) -> i64 {
+x * x
+}
+
+#[allow(dead_code)] fn average(a: f64, b: f64) -> f64 {
+(a + b) / 2.0
+}
+
+#[allow(dead_code)] fn in_range(v: i64, lo: i64, hi: i64) -> bool {
+v >= lo && v <= hi
+}
+
+fn halve(n: i64) -> i64 {
+n / 2
+}
+
+fn describe(n: i64) -> String {
+format!("{} squared is {}", n, ({ let x: i64 = n; x * x }))
+}
+
+fn main() {
+let side = 7;
+let area = { let x: i64 = side; x * x };
+let mean = { let (a, b): (f64, f64) = (1.5, area as f64); (a + b) / 2.0 };
+let ok = { let (v, lo, hi): (i64, i64, i64) = (({ let x: i64 = side + 1; x * x }), 0, 100); v >= lo && v <= hi };
+println!("{} {} {} {}", area, mean, ok, halve(area));
+println!("{}", describe(3));
+}
\ No newline at end of file
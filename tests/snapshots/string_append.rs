@@ -0,0 +1,17 @@
+fn join(parts: Vec<String>, sep: &str) -> String {
+let mut out = String::new();
+for p in parts {
+out.push_str(&p); out.push_str(&sep);
+}
+out
+}
+
+fn tag(name: &str) -> String {
+let mut label = name.to_string();
+label.push_str("!");
+label
+}
+
+fn main() {
+println!("{} {}", join(vec![String::from("a")], ","), tag("x"));
+}
\ No newline at end of file